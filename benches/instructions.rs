@@ -0,0 +1,38 @@
+use bbcore::instruction::codec::{self, Instruction, InstructionWriter};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rand::Rng;
+
+fn random_stream(instructions: usize) -> Vec<u8> {
+    let mut rng = rand::rng();
+    let mut writer = InstructionWriter::with_estimated_instructions(instructions);
+    for _ in 0..instructions {
+        // most instructions keep the pen where it is, as in a typical drawing
+        let pen_up = match rng.random_range(0..20) {
+            0 => Some(true),
+            1 => Some(false),
+            _ => None,
+        };
+        writer.write(&Instruction::movement(rng.random_range(-200..200), rng.random_range(-200..200), pen_up));
+    }
+    writer.into_bytes()
+}
+
+fn instructions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions");
+    group.sample_size(20);
+
+    // large drawings, such as dense stippling, are several megabytes of instructions
+    for count in [200_000, 1_000_000] {
+        let ins_bytes = random_stream(count);
+        group.throughput(Throughput::Bytes(ins_bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("validate", count), &ins_bytes, |b, ins_bytes| b.iter(|| codec::validate(ins_bytes)));
+        group.bench_with_input(BenchmarkId::new("chunk_bounds", count), &ins_bytes, |b, ins_bytes| b.iter(|| codec::chunk_bounds(ins_bytes, 4096)));
+        group.bench_with_input(BenchmarkId::new("decode_steps", count), &ins_bytes, |b, ins_bytes| b.iter(|| codec::decode_steps(ins_bytes)));
+        group.bench_with_input(BenchmarkId::new("content_hash", count), &ins_bytes, |b, ins_bytes| b.iter(|| codec::content_hash(ins_bytes)));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, instructions);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+use bbcore::preview::canvas::PreviewCanvas;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use image::Rgba;
+use rand::Rng;
+
+fn random_walk(count: usize) -> Vec<(f64, f64)> {
+    let mut rng = rand::rng();
+    let mut position: (f64, f64) = (150., 200.);
+    (0..count).map(|_| {
+        position = ((position.0 + rng.random_range(-1. ..1.)).clamp(0., 297.), (position.1 + rng.random_range(-1. ..1.)).clamp(0., 420.));
+        position
+    }).collect()
+}
+
+fn preview(c: &mut Criterion) {
+    let mut group = c.benchmark_group("preview");
+    group.sample_size(10);
+
+    // a dense A3 drawing at 4px/mm, where each instruction is a short segment
+    for count in [100_000, 1_000_000] {
+        let points = random_walk(count);
+        group.bench_with_input(BenchmarkId::new("single", count), &points, |b, points| b.iter(|| {
+            let mut canvas = PreviewCanvas::new(297, 420, Some(4)).unwrap();
+            for w in points.windows(2) {
+                canvas.coloured_line(w[0].0, w[0].1, w[1].0, w[1].1, Rgba([0, 0, 0, 255]));
+            }
+            canvas
+        }));
+        group.bench_with_input(BenchmarkId::new("batched", count), &points, |b, points| b.iter(|| {
+            let mut canvas = PreviewCanvas::new(297, 420, Some(4)).unwrap();
+            for w in points.windows(2) {
+                canvas.queue_line(w[0].0, w[0].1, w[1].0, w[1].1, Rgba([0, 0, 0, 255]));
+            }
+            canvas.flush_lines();
+            canvas
+        }));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, preview);
+criterion_main!(benches);
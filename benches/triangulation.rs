@@ -0,0 +1,77 @@
+use bbcore::drawing::util::delaunay;
+use bbcore::drawing::util::geometry::Point;
+use bbcore::drawing::util::stipple_structures::Triangle;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::Rng;
+use std::collections::HashMap;
+
+fn random_points(count: usize) -> Vec<Point> {
+    let mut rng = rand::rng();
+    (0..count).map(|_| Point::new(rng.random::<f32>() * 1000., rng.random::<f32>() * 1000.)).collect()
+}
+
+///
+/// The Bowyer-Watson triangulation stippling used before the sweep-hull one, kept as a baseline.
+/// Every point tests every triangle for whether it is inside its circumcircle, so it is O(n²).
+///
+fn bowyer_watson(points: &[Point]) -> Vec<[usize; 3]> {
+    let mut all_points = points.to_vec();
+
+    // a super triangle enclosing every point
+    let max_x = points.iter().map(|p| p.x).max().unwrap().into_inner() * 2.;
+    let max_y = points.iter().map(|p| p.y).max().unwrap().into_inner() * 2.;
+    let super_index = all_points.len();
+    all_points.extend([Point::new(0., 0.), Point::new(max_x, 0.), Point::new(0., max_y)]);
+
+    let anticlockwise = |a: usize, b: usize, c: usize, points: &[Point]| {
+        let (pa, pb, pc) = (points[a], points[b], points[c]);
+        if ((pb.x - pa.x) * (pc.y - pa.y) - (pb.y - pa.y) * (pc.x - pa.x)).into_inner() > 0. { [a, b, c] } else { [a, c, b] }
+    };
+    let mut triangles = vec![anticlockwise(super_index, super_index + 1, super_index + 2, &all_points)];
+
+    for point_idx in 0..points.len() {
+        let bad_triangles: Vec<usize> = triangles.iter().enumerate()
+            .filter(|(_, t)| Triangle::point_in_circle(&all_points[t[0]], &all_points[t[1]], &all_points[t[2]], &all_points[point_idx]))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        // the edges used by only one bad triangle form the hole's boundary
+        let mut edge_count: HashMap<(usize, usize), usize> = HashMap::new();
+        for &idx in bad_triangles.iter() {
+            for (a, b) in Triangle::get_edge_indexes(&triangles[idx]) {
+                *edge_count.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        for &idx in bad_triangles.iter().rev() {
+            triangles.swap_remove(idx);
+        }
+        for (&(a, b), _) in edge_count.iter().filter(|(_, count)| **count == 1) {
+            triangles.push(anticlockwise(a, b, point_idx, &all_points));
+        }
+    }
+
+    triangles.retain(|t| t.iter().all(|&idx| idx < super_index));
+    triangles
+}
+
+fn triangulation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("delaunay");
+    group.sample_size(20);
+
+    // stippling typically uses 5k to 50k points
+    for count in [1_000, 5_000, 20_000, 50_000] {
+        let points = random_points(count);
+        group.bench_with_input(BenchmarkId::new("sweep hull", count), &points, |b, points| b.iter(|| delaunay::triangulate(points)));
+
+        // bowyer-watson takes seconds per iteration past a few thousand points
+        if count <= 5_000 {
+            group.bench_with_input(BenchmarkId::new("bowyer-watson", count), &points, |b, points| b.iter(|| bowyer_watson(points)));
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, triangulation);
+criterion_main!(benches);
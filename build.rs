@@ -0,0 +1,24 @@
+fn main() {
+    // the C header is only generated when the bindings are built, into the build's output
+    // directory so building never changes the source tree. The copy in `include/` is
+    // regenerated by hand, as described in the README
+    #[cfg(feature = "ffi")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+
+        // only the bindings are parsed, so no other public types of the crate end up in the header
+        let generated = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+            .map_err(|err| err.to_string())
+            .and_then(|config| cbindgen::Builder::new().with_config(config).with_src(format!("{}/src/ffi.rs", crate_dir)).generate().map_err(|err| err.to_string()));
+
+        match generated {
+            Ok(bindings) => {
+                bindings.write_to_file(format!("{}/bbcore.h", out_dir));
+            },
+            Err(err) => println!("cargo:warning=Could not generate bbcore.h: {}", err),
+        }
+    }
+}
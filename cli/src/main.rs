@@ -0,0 +1,483 @@
+//!
+//! `blot`, a command-line companion to the desktop app, for scripting drawings
+//!
+//! Drawings are generated once into `.blot` files, which can then be previewed and plotted
+//! without regenerating them.
+//!
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bbcore::client::{self, conformance};
+use bbcore::client::schedule::{PausePoint, PauseSchedule};
+use bbcore::drawing::registry;
+use bbcore::drawing::util::placement::Rect;
+use bbcore::hardware::PhysicalDimensions;
+use bbcore::instruction::{container, ParkingAction, ReplotSelection};
+use bbcore::pipeline::{run_queue, DrawingJob, JobEvent};
+use bbcore::preview;
+use clap::{Parser, Subcommand};
+use tokio::sync::Notify;
+
+#[derive(Parser)]
+#[command(name = "blot", version, about = "Generate, preview and plot drawings on a blot-bot")]
+struct Cli {
+    /// The machine configuration
+    #[arg(short, long, global = true, default_value = "machine.toml")]
+    config: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a drawing into a .blot file
+    Gen {
+        /// The backend ID of the drawing method, listed by `blot methods`
+        method: String,
+        /// A JSON file of the drawing method's parameters, its default parameters if not given
+        #[arg(short, long)]
+        params: Option<String>,
+        /// The .blot file to write, <method>.blot if not given
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Also save a preview image of the drawing
+        #[arg(long)]
+        preview: Option<String>,
+        /// Also export the drawing as an SVG, with labelled strokes grouped by their labels
+        #[arg(long)]
+        svg: Option<String>,
+        /// The width of the pen's line in millimetres, to warn of strokes shorter than it
+        #[arg(long, default_value_t = 0.)]
+        line_width: f64,
+        /// Remove strokes shorter than the line width, and pen down moves which don't move
+        #[arg(long)]
+        remove_degenerate: bool,
+        /// Snap the drawing to a fine grid, so a seed generates the same file on every platform
+        #[arg(long)]
+        reproducible: bool,
+        /// Simplify the drawing's strokes, straying at most this many millimetres from them
+        #[arg(long)]
+        simplify: Option<f64>,
+    },
+    /// Render the preview of a .blot file to an image
+    Preview {
+        /// The .blot file
+        input: String,
+        /// The image to write
+        output: String,
+        /// The number of pixels per millimetre of paper
+        #[arg(short, long, default_value_t = 1)]
+        scale: u32,
+        /// Number the strokes, and draw a direction arrow every this many millimetres along them
+        #[arg(long)]
+        debug_arrows: Option<f64>,
+    },
+    /// Show the settings a .blot file was generated with
+    Info {
+        /// The .blot file
+        input: String,
+    },
+    /// Draw .blot files on a machine, one after another
+    Plot {
+        /// The .blot files, in the order they are drawn
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        /// The IP address of the machine
+        #[arg(short, long)]
+        addr: String,
+        /// The port of the machine
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+        /// Advance the paper roll by this many millimetres after each drawing
+        #[arg(long)]
+        paper_advance: Option<f64>,
+        /// Where to park the pen after each drawing: home, bottom-corner or raise-pen
+        #[arg(long, value_parser = parse_parking)]
+        park: Option<ParkingAction>,
+        /// Pause each drawing before these instructions, such as "120,800", until enter is pressed
+        #[arg(long, value_delimiter = ',')]
+        pause_at: Vec<usize>,
+        /// Pause each drawing once it has been drawing for this many minutes, until enter is pressed
+        #[arg(long)]
+        pause_after: Option<f64>,
+    },
+    /// Write part of a .blot file to a new .blot file, to re-ink an area without plotting it all again
+    Replot {
+        /// The .blot file
+        input: String,
+        /// The .blot file to write
+        output: String,
+        /// Only the area x,y,width,height of the page, in millimetres from its top left
+        #[arg(long, value_parser = parse_region)]
+        region: Option<Rect>,
+        /// Only the instructions first..last, such as a chunk drawn while the pen ran dry
+        #[arg(long, value_parser = parse_range)]
+        instructions: Option<std::ops::Range<usize>>,
+        /// Where the pen is, as x,y in millimetres from the top left of the page
+        #[arg(long, value_parser = parse_point, default_value = "0,0")]
+        from: (f64, f64),
+    },
+    /// Find machines on the local network
+    Discover {
+        /// The port machines listen on
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+        /// How long to wait for each host, in milliseconds
+        #[arg(short, long, default_value_t = 300)]
+        timeout: u64,
+    },
+    /// Run a machine's firmware through the protocol conformance suite
+    Conformance {
+        /// The IP address of the machine
+        #[arg(short, long)]
+        addr: String,
+        /// The port of the machine
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+    },
+    /// List the drawing methods
+    Methods,
+    /// Print the default parameters of a drawing method, to start a parameters file from
+    Defaults {
+        /// The backend ID of the drawing method, listed by `blot methods`
+        method: String,
+    },
+    /// Render a preview of every drawing method into a directory, with an index.json
+    Gallery {
+        /// The existing directory to write the previews to
+        directory: String,
+        /// A JSON file of each method's parameters, as an object keyed by backend ID. Methods
+        /// missing from it use their default parameters
+        #[arg(short, long)]
+        params: Option<String>,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+///
+/// Runs a command.
+///
+/// # Parameters:
+/// - `cli`: The parsed command line
+///
+/// # Returns:
+/// - Void if the command succeeded
+/// - An error explaining why the command failed
+///
+fn run(cli: Cli) -> Result<(), String> {
+    match cli.command {
+        Command::Gen { method, params, output, preview, svg, line_width, remove_degenerate, reproducible, simplify } => {
+            let physical_dimensions = load_config(&cli.config)?;
+            let parameters_json = match &params {
+                Some(params) => std::fs::read_to_string(params).map_err(|err| format!("Error reading {}. {}", params, err))?,
+                None => registry::default_parameters(&method)?,
+            };
+            let output = output.unwrap_or(format!("{}.blot", method));
+
+            let mut metadata = container::Metadata::new(&method, &parameters_json, &physical_dimensions).map_err(|err| err.to_string())?;
+            let mut job = DrawingJob::new(physical_dimensions).with_method_id(&method, &parameters_json).with_line_width(line_width);
+            if remove_degenerate {
+                job = job.with_degenerate_removal();
+            }
+            if reproducible {
+                job = job.with_reproducibility();
+            }
+            if let Some(tolerance_mm) = simplify {
+                job = job.with_simplification(tolerance_mm);
+            }
+            if let Some(path) = &preview {
+                job = job.with_preview(path);
+            }
+            if let Some(path) = &svg {
+                job = job.with_svg(path);
+            }
+
+            let instruction_set = job.generate(print_event).map_err(|err| err.to_string())?;
+            metadata.content_hash = Some(instruction_set.content_hash());
+            container::save_with_metadata(&instruction_set, Some(&metadata), &output).map_err(|err| err.to_string())?;
+            println!("Saved {}", output);
+        },
+        Command::Preview { input, output, scale, debug_arrows } => {
+            let physical_dimensions = load_config(&cli.config)?;
+            let instruction_set = container::load(&input).map_err(|err| err.to_string())?;
+
+            let options = preview::PreviewOptions { scale, debug_arrow_spacing: debug_arrows, ..Default::default() };
+            preview::render_preview(instruction_set.get_init(), &physical_dimensions, &instruction_set, &options)
+                .and_then(|preview_canvas| preview_canvas.save(&output))
+                .map_err(|err| err.to_string())?;
+            println!("Saved {}", output);
+        },
+        Command::Info { input } => {
+            match container::load_metadata(&input).map_err(|err| err.to_string())? {
+                Some(metadata) => {
+                    println!("Method     {}", metadata.method_id);
+                    println!("Parameters {}", metadata.parameters);
+                    if let Some(seed) = metadata.seed {
+                        println!("Seed       {}", seed);
+                    }
+                    println!("Generated  {} seconds after the Unix epoch, by version {}", metadata.generated_at, metadata.crate_version);
+                    println!("Machine    {}", serde_json::to_string(&metadata.physical_dimensions).map_err(|err| err.to_string())?);
+                    if let Some(content_hash) = metadata.content_hash {
+                        println!("Hash       {:016x}", content_hash);
+                    }
+                },
+                None => println!("{} has no metadata, it was saved without its settings", input),
+            }
+        },
+        Command::Plot { inputs, addr, port, paper_advance, park, pause_at, pause_after } => {
+            let physical_dimensions = load_config(&cli.config)?;
+            let mut schedule = pause_at.into_iter().fold(PauseSchedule::new(), |schedule, instruction_idx| schedule.with_pause(PausePoint::Instruction(instruction_idx)));
+            if let Some(minutes) = pause_after {
+                schedule = schedule.with_pause(PausePoint::AfterMinutes(minutes));
+            }
+            let resume = Arc::new(Notify::new());
+
+            let mut jobs = Vec::with_capacity(inputs.len());
+            for input in &inputs {
+                let instruction_set = container::load(input).map_err(|err| err.to_string())?;
+                let (start_x, start_y) = instruction_set.get_init();
+                let mut job = DrawingJob::new(physical_dimensions.clone()).with_instructions(instruction_set.get_binary().clone(), start_x, start_y).with_machine(&addr, port);
+                if let Some(mm) = paper_advance {
+                    job = job.with_paper_advance(mm);
+                }
+                if let Some(action) = park {
+                    job = job.with_parking(action);
+                }
+                jobs.push(job.with_pause_schedule(schedule.clone(), resume.clone()));
+            }
+
+            let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+            runtime.block_on(run_queue(jobs, |index, event| {
+                if inputs.len() > 1 && event == JobEvent::Validated {
+                    println!("{}:", inputs[index]);
+                }
+                let paused = matches!(event, JobEvent::WaitingForUser { .. });
+                print_event(event);
+
+                // the machine stays paused until enter is pressed
+                if paused {
+                    let resume = resume.clone();
+                    std::thread::spawn(move || {
+                        let _ = std::io::stdin().read_line(&mut String::new());
+                        resume.notify_one();
+                    });
+                }
+            })).map_err(|err| err.to_string())?;
+        },
+        Command::Replot { input, output, region, instructions, from } => {
+            let physical_dimensions = load_config(&cli.config)?;
+            let instruction_set = container::load(&input).map_err(|err| err.to_string())?;
+
+            let mut selection = ReplotSelection::new();
+            if let Some(region) = region {
+                selection = selection.with_region(region);
+            }
+            if let Some(instructions) = instructions {
+                selection = selection.with_instructions(instructions);
+            }
+
+            let replot = instruction_set.replot(&physical_dimensions, &selection, from).map_err(|err| err.to_string())?;
+            container::save(&replot, &output).map_err(|err| err.to_string())?;
+            println!("Saved {}", output);
+        },
+        Command::Discover { port, timeout } => {
+            let machines = client::discover(port, Duration::from_millis(timeout)).map_err(|err| err.to_string())?;
+            if machines.is_empty() {
+                println!("No machines found on port {}", port);
+            }
+            for machine in machines {
+                println!("{}:{}", machine, port);
+            }
+        },
+        Command::Conformance { addr, port } => {
+            let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+            let report = runtime.block_on(conformance::run(&addr, port, &conformance::Scenario::ALL));
+            for result in &report.results {
+                println!("{} {:<28}{}", if result.passed { "PASS" } else { "FAIL" }, result.scenario.name(), result.detail);
+            }
+            if !report.passed() {
+                return Err(format!("{} of {} scenarios failed", report.results.iter().filter(|result| !result.passed).count(), report.results.len()));
+            }
+        },
+        Command::Methods => {
+            for (id, name) in registry::methods() {
+                println!("{:<16}{}", id, name);
+            }
+        },
+        Command::Defaults { method } => {
+            let parameters: serde_json::Value = serde_json::from_str(&registry::default_parameters(&method)?).map_err(|err| err.to_string())?;
+            println!("{}", serde_json::to_string_pretty(&parameters).map_err(|err| err.to_string())?);
+        },
+        Command::Gallery { directory, params } => {
+            let physical_dimensions = load_config(&cli.config)?;
+            let mut parameters = serde_json::Map::new();
+            if let Some(params) = &params {
+                let parameters_json = std::fs::read_to_string(params).map_err(|err| format!("Error reading {}. {}", params, err))?;
+                parameters = serde_json::from_str(&parameters_json).map_err(|err| format!("Error reading {}. {}", params, err))?;
+            }
+
+            let index = preview::gallery::generate_gallery(&physical_dimensions, &directory, |method_id| {
+                parameters.get(method_id).map(|value| value.to_string()).or_else(|| registry::default_parameters(method_id).ok())
+            }).map_err(|err| err.to_string())?;
+            for entry in &index.methods {
+                match &entry.error {
+                    Some(err) => println!("{:<16}skipped, {}", entry.method_id, err),
+                    None => println!("{:<16}saved", entry.method_id),
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+///
+/// Loads the machine configuration.
+///
+/// # Parameters:
+/// - `path`: The path of the TOML machine configuration
+///
+/// # Returns:
+/// - The physical dimensions
+/// - An error if the configuration could not be read, parsed or validated
+///
+fn load_config(path: &str) -> Result<PhysicalDimensions, String> {
+    PhysicalDimensions::load_from_toml(path).map_err(|err| err.to_string())
+}
+
+///
+/// Parses a parking action from the command line.
+///
+/// # Parameters:
+/// - `action`: The action, such as "bottom-corner"
+///
+/// # Returns:
+/// - The parking action
+/// - An error listing the actions, if it isn't one
+///
+fn parse_parking(action: &str) -> Result<ParkingAction, String> {
+    serde_json::from_value(serde_json::Value::String(action.replace('-', "_")))
+        .map_err(|_| format!("{} isn't a parking action, expected home, bottom-corner or raise-pen", action))
+}
+
+///
+/// Parses comma separated millimetres from the command line.
+///
+/// # Parameters:
+/// - `values`: The millimetres, such as "20,30.5"
+/// - `count`: How many there should be
+///
+/// # Returns:
+/// - The millimetres
+/// - An error if there are not `count` numbers
+///
+fn parse_millimetres(values: &str, count: usize) -> Result<Vec<f64>, String> {
+    let millimetres = values.split(',').map(|value| value.trim().parse::<f64>()).collect::<Result<Vec<f64>, _>>().map_err(|err| format!("{} isn't a list of numbers, {}", values, err))?;
+    if millimetres.len() != count {
+        return Err(format!("{} has {} numbers, expected {}", values, millimetres.len(), count));
+    }
+
+    Ok(millimetres)
+}
+
+///
+/// Parses a point from the command line.
+///
+/// # Parameters:
+/// - `point`: The point, such as "20,30"
+///
+/// # Returns:
+/// - The x and y of the point
+/// - An error if it isn't two numbers
+///
+fn parse_point(point: &str) -> Result<(f64, f64), String> {
+    parse_millimetres(point, 2).map(|xy| (xy[0], xy[1]))
+}
+
+///
+/// Parses an area of the page from the command line.
+///
+/// # Parameters:
+/// - `region`: The area, such as "20,30,100,50" for its x, y, width and height
+///
+/// # Returns:
+/// - The area
+/// - An error if it isn't four numbers
+///
+fn parse_region(region: &str) -> Result<Rect, String> {
+    parse_millimetres(region, 4).map(|xywh| Rect { x: xywh[0], y: xywh[1], width: xywh[2], height: xywh[3] })
+}
+
+///
+/// Parses a range of instruction indices from the command line.
+///
+/// # Parameters:
+/// - `range`: The range, such as "100..250"
+///
+/// # Returns:
+/// - The range
+/// - An error if it isn't two indices separated by ".."
+///
+fn parse_range(range: &str) -> Result<std::ops::Range<usize>, String> {
+    let invalid = || format!("{} isn't a range of instructions, expected first..last", range);
+    let (first, last) = range.split_once("..").ok_or_else(invalid)?;
+
+    Ok(first.trim().parse().map_err(|_| invalid())?..last.trim().parse().map_err(|_| invalid())?)
+}
+
+///
+/// Prints the progress of a drawing job.
+///
+/// # Parameters:
+/// - `event`: The stage the job has reached
+///
+fn print_event(event: JobEvent) {
+    match event {
+        JobEvent::Generating { samples } => println!("Generating, {} positions so far", samples),
+        JobEvent::Generated { bytes, start, content_hash } => println!("Generated {} bytes, starting at ({:.1}, {:.1}), hash {:016x}", bytes, start.0, start.1, content_hash),
+        JobEvent::Degenerate { report, removed } => println!(
+            "{} {} dots, {} strokes shorter than the pen's line and {} pen down moves which don't move",
+            if removed { "Removed" } else { "Warning: the drawing has" }, report.dots, report.short_strokes, report.zero_length_segments
+        ),
+        JobEvent::SvgSaved { path } => println!("Saved SVG {}", path),
+        JobEvent::Duplicate { job } => println!("Warning: this drawing is the same as drawing {} in the queue", job + 1),
+        JobEvent::MarginViolations { report } => {
+            println!("Warning: {} strokes cross the margins, {} of them leave the page", report.violations.len(), report.off_page().count());
+            for violation in &report.violations {
+                println!("  instructions {}-{}: {:.1}mm of {:.1}mm outside the margins", violation.first_instruction, violation.last_instruction, violation.length_outside_margins, violation.length);
+            }
+        },
+        JobEvent::Validated => println!("The drawing stays on the page"),
+        JobEvent::PreviewSaved { path } => println!("Saved preview {}", path),
+        JobEvent::MovedToStart => println!("Moved the pen to the start"),
+        JobEvent::Connected { protocol_version, instruction_buffer_size } => println!("Connected, protocol v{} with a {} byte buffer", protocol_version, instruction_buffer_size),
+        JobEvent::LintWarnings { warnings } => {
+            println!("Warning: the drawing has {} suspicious instructions", warnings.len());
+            for warning in &warnings {
+                println!("  {}", warning);
+            }
+        },
+        JobEvent::WaitingForUser { reason } => println!("Paused at {}, press enter to resume", reason),
+        JobEvent::Streaming { chunk, num_chunks, secs_remaining, .. } => println!("Drawing {}/{}, about {}s remaining", chunk + 1, num_chunks, secs_remaining),
+        JobEvent::Transmitted { report } => println!("Sent {} chunks ({} bytes) at {:.0} bytes/s, {:.0}ms mean acknowledgement, {:.1}s stalled", report.chunks, report.bytes, report.throughput_bytes_per_sec, report.mean_ack_latency_ms, report.stall_secs),
+        JobEvent::Finished => println!("Finished"),
+        JobEvent::Stopped => println!("The machine stopped the drawing"),
+        JobEvent::ConnectionLost { chunk, reason } => println!("Lost the connection to the machine after {} chunks. {}", chunk, reason),
+        JobEvent::Reconnecting { chunk, attempt } => println!("Reconnecting to carry on from chunk {}, attempt {}", chunk + 1, attempt),
+        JobEvent::PaperAdvanced { mm } => println!("Advanced the paper {:.1}mm", mm),
+    }
+}
@@ -0,0 +1,433 @@
+//!
+//! Encoding, decoding and validation of the binary instruction format
+//!
+//! The parser uses no floating point and no I/O, so it reads instructions exactly as the machine
+//! firmware does.
+//!
+
+use alloc::vec::Vec;
+
+use super::error::{CodecError, NextInstructionError};
+use super::opcode::{self, Action, DWELL, END_OF_INSTRUCTION, MAX_INSTRUCTION_LENGTH, MIN_INSTRUCTION_LENGTH, PAYLOAD_LENGTH, PEN_DOWN, PEN_UP};
+
+/// The offset basis of the 64 bit FNV-1a hash, used by `content_hash`.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// The prime of the 64 bit FNV-1a hash, used by `content_hash`.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// The byte ending a dwell's record in `content_hash`, where a movement's record ends with its pen.
+const DWELL_HASH_TAG: u8 = 0x02;
+
+///
+/// A single decoded instruction.
+///
+/// # Fields:
+/// - `left_steps`: The number of steps the left motor moves
+/// - `right_steps`: The number of steps the right motor moves
+/// - `pen_up`: If the pen changes state before the movement, true if it is raised, else false
+/// - `dwell_ms`: How long the machine waits, in milliseconds. An instruction which dwells
+///   neither moves nor changes the pen, so its other fields are ignored when it is encoded
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub left_steps: i16,
+    pub right_steps: i16,
+    pub pen_up: Option<bool>,
+    pub dwell_ms: u16,
+}
+
+impl Instruction {
+    ///
+    /// # Parameters:
+    /// - `left_steps` and `right_steps`: The steps the motors move
+    /// - `pen_up`: If the pen changes state before the movement, true if it is raised, else false
+    ///
+    /// # Returns:
+    /// - An instruction moving the motors
+    ///
+    pub fn movement(left_steps: i16, right_steps: i16, pen_up: Option<bool>) -> Instruction {
+        Instruction { left_steps, right_steps, pen_up, dwell_ms: 0 }
+    }
+
+    ///
+    /// # Parameters:
+    /// - `dwell_ms`: How long the machine waits, in milliseconds
+    ///
+    /// # Returns:
+    /// - An instruction waiting without moving
+    ///
+    pub fn dwell(dwell_ms: u16) -> Instruction {
+        Instruction { left_steps: 0, right_steps: 0, pen_up: None, dwell_ms }
+    }
+
+    ///
+    /// Appends the binary form of the instruction to a buffer.
+    ///
+    /// # Parameters:
+    /// - `buffer`: The buffer to write the instruction to
+    ///
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let (bytes, length) = self.to_bytes();
+        buffer.extend_from_slice(&bytes[..length]);
+    }
+
+    ///
+    /// # Returns:
+    /// - The binary form of the instruction, padded to the longest instruction, and its length
+    ///
+    pub fn to_bytes(&self) -> ([u8; MAX_INSTRUCTION_LENGTH], usize) {
+        let [l0, l1] = self.left_steps.to_be_bytes();
+        let [r0, r1] = self.right_steps.to_be_bytes();
+
+        if self.dwell_ms > 0 {
+            let [d0, d1] = self.dwell_ms.to_be_bytes();
+            return ([d0, d1, 0, 0, DWELL, END_OF_INSTRUCTION], MAX_INSTRUCTION_LENGTH);
+        }
+        match self.pen_up {
+            Some(true) => ([l0, l1, r0, r1, PEN_UP, END_OF_INSTRUCTION], MAX_INSTRUCTION_LENGTH),
+            Some(false) => ([l0, l1, r0, r1, PEN_DOWN, END_OF_INSTRUCTION], MAX_INSTRUCTION_LENGTH),
+            None => ([l0, l1, r0, r1, END_OF_INSTRUCTION, 0], MIN_INSTRUCTION_LENGTH),
+        }
+    }
+
+    ///
+    /// Decodes the instruction starting at an index.
+    ///
+    /// # Parameters:
+    /// - `ins_bytes`: A slice of raw binary instructions
+    /// - `cidx`: The index of the first byte of the instruction
+    ///
+    /// # Returns:
+    /// - The instruction, and the index of the first byte of the next instruction
+    /// - An error explaining why there is no instruction at the index. At the end of every stream, an EndOfStream error occurs
+    ///
+    pub fn decode(ins_bytes: &[u8], cidx: usize) -> Result<(Instruction, usize), NextInstructionError> {
+        let (sb, eb) = get_next_instruction_bounds(ins_bytes, cidx)?;
+
+        let pen_up = match opcode::lookup(ins_bytes[sb + PAYLOAD_LENGTH]).map(|opcode| opcode.action) {
+            Some(Action::RaisePen) => Some(true),
+            Some(Action::LowerPen) => Some(false),
+            Some(Action::Dwell) => return Ok((Instruction::dwell(u16::from_be_bytes([ins_bytes[sb], ins_bytes[sb + 1]])), eb + 1)),
+            None => None,
+        };
+
+        let left_steps = i16::from_be_bytes([ins_bytes[sb], ins_bytes[sb + 1]]);
+        let right_steps = i16::from_be_bytes([ins_bytes[sb + 2], ins_bytes[sb + 3]]);
+        Ok((Instruction::movement(left_steps, right_steps, pen_up), eb + 1))
+    }
+}
+
+///
+/// Accumulates encoded instructions, reserving space up front so drawings of millions of
+/// instructions aren't repeatedly reallocated and copied as they grow.
+///
+/// # Fields:
+/// - `bytes`: The instruction bytes written so far
+///
+#[derive(Clone, Debug, Default)]
+pub struct InstructionWriter {
+    bytes: Vec<u8>,
+}
+
+impl InstructionWriter {
+    ///
+    /// # Parameters:
+    /// - `estimated_instructions`: Roughly how many instructions will be written. Writing more is
+    ///   fine, the buffer grows as usual
+    ///
+    /// # Returns:
+    /// - An empty writer, with space for the estimated instructions
+    ///
+    pub fn with_estimated_instructions(estimated_instructions: usize) -> InstructionWriter {
+        // most instructions don't change the pen, so are one byte shorter than the longest
+        InstructionWriter { bytes: Vec::with_capacity(estimated_instructions.saturating_mul(MAX_INSTRUCTION_LENGTH - 1)) }
+    }
+
+    ///
+    /// Appends an instruction.
+    ///
+    /// # Parameters:
+    /// - `instruction`: The instruction to append
+    ///
+    pub fn write(&mut self, instruction: &Instruction) {
+        instruction.encode(&mut self.bytes);
+    }
+
+    ///
+    /// # Returns:
+    /// - The number of bytes written so far
+    ///
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    ///
+    /// Discards the bytes written after a length, such as to undo the last instructions.
+    ///
+    /// # Parameters:
+    /// - `len`: The number of bytes to keep, which must be the end of an instruction
+    ///
+    pub fn truncate(&mut self, len: usize) {
+        self.bytes.truncate(len);
+    }
+
+    ///
+    /// # Returns:
+    /// - Whether no instructions have been written
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    ///
+    /// # Returns:
+    /// - The instruction bytes written, without any spare capacity
+    ///
+    pub fn into_bytes(mut self) -> Vec<u8> {
+        // an overestimate would otherwise hold on to the unused memory for as long as the drawing
+        if self.bytes.capacity() - self.bytes.len() > self.bytes.len() / 4 {
+            self.bytes.shrink_to_fit();
+        }
+        self.bytes
+    }
+}
+
+///
+/// Performs validty checks as to if the bytes are valid instructions.
+///
+/// # Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+///
+/// # Returns:
+/// - Void, if the bytes are valid instructions
+/// - An error to explain why the bytes were invalid
+///
+pub fn validate(ins_bytes: &[u8]) -> Result<(), CodecError> {
+    if ins_bytes.is_empty() {
+        return Err(CodecError::EmptyInstructionSet);
+    }
+
+    let mut c_idx = 0;
+    while c_idx < ins_bytes.len() {
+        match instruction_length(ins_bytes, c_idx) {
+            Some(length) => c_idx += length,
+            None => return Err(incomplete_at(ins_bytes, c_idx)),
+        }
+    }
+    Ok(())
+}
+
+///
+/// Gets the indices of the next instruction, inclusive. e.g. (13, 17) could be 29-0fa-40-1a-0c
+///
+/// Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+/// - `cidx`: The current starting index, the first byte of the left motor movement (always one
+///   byte after the last 0x0C eoi instruction)
+///
+/// # Returns:
+/// - a tuple of usizes, the bounds of the next instruction
+/// - An error explaining why the function failed. At the end of every stream, an EndOfStream error occurs
+///
+pub fn get_next_instruction_bounds(ins_bytes: &[u8], cidx: usize) -> Result<(usize, usize), NextInstructionError> {
+    if cidx >= ins_bytes.len() {
+        return Err(NextInstructionError::EndOfStream);
+    }
+
+    match instruction_length(ins_bytes, cidx) {
+        Some(length) => Ok((cidx, cidx + length - 1)),
+        None => Err(NextInstructionError::InvalidInstruction(cidx)),
+    }
+}
+
+///
+/// # Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+/// - `cidx`: The index of the first byte of the instruction
+///
+/// # Returns:
+/// - The length of the instruction in bytes
+/// - None if the bytes don't start with a whole instruction. A stream ending part way through an
+///   instruction is invalid, not finished
+///
+#[inline]
+fn instruction_length(ins_bytes: &[u8], cidx: usize) -> Option<usize> {
+    // skip over the payload, the next byte is either the eoi or an opcode, which is followed by
+    // the eoi. most instructions don't change the pen, so that case is checked first and
+    // predicted well. a lookup table of lengths measured slower, as each instruction's start
+    // then waits on a load rather than a predicted branch
+    match *ins_bytes.get(cidx + PAYLOAD_LENGTH)? {
+        END_OF_INSTRUCTION => Some(MIN_INSTRUCTION_LENGTH),
+        byte => {
+            let opcode = opcode::lookup(byte)?;
+            let terminated = ins_bytes.get(cidx + PAYLOAD_LENGTH + 1) == Some(&END_OF_INSTRUCTION);
+            // the payload after what the opcode reads must be zero
+            let unused_payload = &ins_bytes[cidx + opcode.payload_length..cidx + PAYLOAD_LENGTH];
+            (terminated && unused_payload.iter().all(|&byte| byte == 0)).then_some(MAX_INSTRUCTION_LENGTH)
+        }
+    }
+}
+
+///
+/// Calls a function with the movement of every instruction in turn, without collecting them.
+/// This is the hot loop of previewing and hashing very large drawings, so it walks the stream
+/// once, rather than finding each instruction's bounds and then decoding it.
+///
+/// # Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+/// - `step`: Called with the steps of the left and right motors, whether the pen is up, and how
+///   long the machine dwells, in milliseconds. Only a dwell has a non-zero duration
+///
+/// # Returns:
+/// - Void, once every instruction is decoded
+/// - An error explaining why the instructions could not be decoded
+///
+fn for_each_step<F: FnMut(i16, i16, bool, u16)>(ins_bytes: &[u8], mut step: F) -> Result<(), CodecError> {
+    let mut pen_up = true; // the pen starts raised, as it moves to the start position
+    let mut c_idx = 0;
+
+    while c_idx < ins_bytes.len() {
+        let Some(length) = instruction_length(ins_bytes, c_idx) else {
+            return Err(incomplete_at(ins_bytes, c_idx));
+        };
+        // the instruction is whole, so its payload and the byte after it exist
+        let [l0, l1, r0, r1, byte] = ins_bytes[c_idx..c_idx + MIN_INSTRUCTION_LENGTH] else { unreachable!() };
+        let steps = (i16::from_be_bytes([l0, l1]), i16::from_be_bytes([r0, r1]));
+        let (left_steps, right_steps, dwell_ms) = match opcode::lookup(byte).map(|opcode| opcode.action) {
+            Some(Action::RaisePen) => { pen_up = true; (steps.0, steps.1, 0) },
+            Some(Action::LowerPen) => { pen_up = false; (steps.0, steps.1, 0) },
+            // a dwell is still an instruction, so instruction indices line up, but it doesn't move
+            Some(Action::Dwell) => (0, 0, u16::from_be_bytes([l0, l1])),
+            None => (steps.0, steps.1, 0),
+        };
+        step(left_steps, right_steps, pen_up, dwell_ms);
+        c_idx += length;
+    }
+    Ok(())
+}
+
+///
+/// Splits the instructions into the index bounds of buffers to send to the drawing machine.
+/// The bounds, say (0, 14), means send all bytes from 0 to 14 inclusive. Instructions are never
+/// split between buffers.
+///
+/// # Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+/// - `max_chunk_size`: The maximum preferred chunk size of buffers
+///
+/// # Returns:
+/// - A vector of tuples, denoting the index boundaries of buffers
+/// - An error explaining why the index boundaries could not be computed
+///
+pub fn chunk_bounds(ins_bytes: &[u8], max_chunk_size: usize) -> Result<Vec<(usize, usize)>, CodecError> {
+    if max_chunk_size < 8 {
+        return Err(CodecError::BufferTooSmall(max_chunk_size));
+    }
+    if ins_bytes.is_empty() {
+        return Err(CodecError::EmptyInstructionSet);
+    }
+
+    let mut chunk_bounds: Vec<(usize, usize)> = Vec::new();
+    let mut c_idx: usize = 0; // current instruction, should always point to the first idx
+    // of an instruction, not an 0x0c or elsewise
+
+    loop {
+
+        let start_idx = c_idx; // index of first byte of first ins of instruction buffer
+        let mut last_valid_idx = c_idx; // idx of last valid 0x0c byte
+        loop {
+            match get_next_instruction_bounds(ins_bytes, c_idx) {
+                Ok((_sb, eb)) => {
+                    if eb >= start_idx + max_chunk_size { // if last byte of this ins is
+                        // out of bounds, push it and move on
+                        chunk_bounds.push((start_idx, last_valid_idx));
+                        break;
+                    }
+
+                    last_valid_idx = eb; // set last valid 0x0c as eb of this ins
+                    c_idx = eb + 1; // point c_idx to first byte of next ins
+                },
+                Err(NextInstructionError::EndOfStream) => {
+                    chunk_bounds.push((start_idx, last_valid_idx));
+                    return Ok(chunk_bounds);
+                },
+                Err(_) => {
+                    return Err(incomplete_at(ins_bytes, c_idx));
+                }
+            }
+        }
+
+        // we've just pushed an instruction buffer bound, so set the c_idx to the first
+        // byte of the next instruction
+        c_idx = last_valid_idx + 1;
+    }
+}
+
+///
+/// Decodes every instruction into the numerical step values the motors will perform.
+///
+/// # Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+///
+/// # Returns:
+/// - A vector of tuple (i16, i16, bool) values the belts will move by, and whether the pen is up
+/// - An error explaining why the instructions could not be decoded
+///
+pub fn decode_steps(ins_bytes: &[u8]) -> Result<Vec<(i16, i16, bool)>, CodecError> {
+    // every instruction is at least the shortest length, so this never reallocates
+    let mut numerical_instructions: Vec<(i16, i16, bool)> = Vec::with_capacity(ins_bytes.len() / MIN_INSTRUCTION_LENGTH);
+    for_each_step(ins_bytes, |left_steps, right_steps, pen_up, _| numerical_instructions.push((left_steps, right_steps, pen_up)))?;
+    Ok(numerical_instructions)
+}
+
+///
+/// Hashes the movements of the motors and pen, rather than the bytes encoding them, so the same
+/// drawing always has the same hash, however its pen changes were encoded. The hash is 64 bit
+/// FNV-1a, which is stable between versions and platforms, so it can be stored and compared
+/// later, such as by the machine. A dwell is hashed by its duration, tagged so it can't be
+/// mistaken for a movement.
+///
+/// # Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+///
+/// # Returns:
+/// - The hash of the drawing
+/// - An error explaining why the instructions could not be decoded
+///
+pub fn content_hash(ins_bytes: &[u8]) -> Result<u64, CodecError> {
+    let mut hash = FNV_OFFSET_BASIS;
+    for_each_step(ins_bytes, |left_steps, right_steps, pen_up, dwell_ms| {
+        let [l0, l1] = left_steps.to_be_bytes();
+        let [r0, r1] = right_steps.to_be_bytes();
+        let [d0, d1] = dwell_ms.to_be_bytes();
+        // the last byte of a movement is the pen, 0 or 1, so a dwell is tagged by any other value
+        let record = match dwell_ms {
+            0 => [l0, l1, r0, r1, pen_up as u8],
+            _ => [d0, d1, 0, 0, DWELL_HASH_TAG],
+        };
+        for byte in record {
+            hash = (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    })?;
+    Ok(hash)
+}
+
+///
+/// Describes an invalid instruction, by the byte where its terminator should have been.
+///
+/// # Parameters:
+/// - `ins_bytes`: A slice of raw binary instructions
+/// - `cidx`: The index of the first byte of the invalid instruction
+///
+/// # Returns:
+/// - An `IncompleteInstructions` error, with the byte found in place of the terminator, or 0 if
+///   the stream ended first
+///
+fn incomplete_at(ins_bytes: &[u8], cidx: usize) -> CodecError {
+    let terminator = match ins_bytes.get(cidx.saturating_add(PAYLOAD_LENGTH)) {
+        Some(&byte) if opcode::lookup(byte).is_some() => ins_bytes.get(cidx.saturating_add(PAYLOAD_LENGTH + 1)),
+        byte => byte,
+    };
+    CodecError::IncompleteInstructions(terminator.copied().unwrap_or(0))
+}
@@ -0,0 +1,39 @@
+use thiserror::Error;
+
+///
+/// All errors emitted from encoding, decoding and validating instructions.
+///
+/// - `IncompleteInstructions`: When an instruction doesn't end in the 0x0C termination byte
+///     Parameters:
+///     - `u8`: The byte found in place of the terminator, or 0 if the stream ended first
+/// - `EmptyInstructionSet`: When the vector contains no bytes
+/// - `BufferTooSmall`: When the requested instruction buffer size for the instruction stream is too small
+///     Parameters:
+///     - `usize`: The requested buffer size
+///
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecError {
+    #[error("An instruction did not end with the instruction termination 0x0C, instead {:#04x}", .0)]
+    IncompleteInstructions(u8),
+
+    #[error("The provided instruction set is empty")]
+    EmptyInstructionSet,
+
+    #[error("The configured instruction buffer size is too small {}", .0)]
+    BufferTooSmall(usize),
+}
+
+///
+/// All errors emitted from the get_next_instruction function.
+///
+/// - `InstructionStreamEnded`: When the instruction stream has ended, this is called normally once
+/// - `InvalidInstruction`: When an error occured finding the next instruction
+///
+#[derive(Error, Debug)]
+pub enum NextInstructionError {
+    #[error("The instruction stream ended")]
+    EndOfStream,
+
+    #[error("The instruction was invalid at index {}", .0)]
+    InvalidInstruction(usize)
+}
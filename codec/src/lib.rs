@@ -0,0 +1,16 @@
+//!
+//! The binary instruction format of the Blot Bot, shared by `bbcore` and the machine firmware
+//!
+//! It is `no_std`, needing only an allocator, so the firmware parses instructions with the same
+//! code which wrote them.
+//!
+
+#![no_std]
+// errors list the parameters of each variant beneath it, like the rest of bbcore
+#![allow(clippy::doc_overindented_list_items)]
+
+extern crate alloc;
+
+pub mod codec;
+pub mod error;
+pub mod opcode;
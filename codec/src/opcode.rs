@@ -0,0 +1,81 @@
+//!
+//! The opcode bytes of the binary instruction format, and the layout of the instructions using them
+//!
+//! Every instruction is a payload, normally the steps of the left and right motors, then either
+//! the terminator, or an opcode and the terminator. Validation, decoding, chunking and the client
+//! all read the opcodes from the table here, so adding an opcode is adding it to `OPCODES` and
+//! handling its `Action` where instructions are decoded.
+//!
+
+/// The opcode which lifts the pen off the paper, after the motor steps of an instruction.
+pub const PEN_UP: u8 = 0x0A;
+
+/// The opcode which lowers the pen onto the paper, after the motor steps of an instruction.
+pub const PEN_DOWN: u8 = 0x0B;
+
+/// The opcode which terminates every instruction.
+pub const END_OF_INSTRUCTION: u8 = 0x0C;
+
+/// The opcode which waits, such as for the pen to settle after it is raised or lowered. The
+/// first two bytes of the instruction are the wait in milliseconds, and the motor bytes after
+/// them are zero.
+pub const DWELL: u8 = 0x0D;
+
+/// The length of every instruction's payload, the bytes before its opcode or terminator.
+pub const PAYLOAD_LENGTH: usize = 4;
+
+/// The length of an instruction without an opcode, in bytes.
+pub const MIN_INSTRUCTION_LENGTH: usize = PAYLOAD_LENGTH + 1;
+
+/// The length of the longest instruction, with a pen change or dwell, in bytes.
+pub const MAX_INSTRUCTION_LENGTH: usize = PAYLOAD_LENGTH + 2;
+
+///
+/// What the machine does for an opcode.
+///
+/// - `RaisePen`: Lifts the pen before moving the motors by the payload
+/// - `LowerPen`: Lowers the pen before moving the motors by the payload
+/// - `Dwell`: Waits for the milliseconds in the payload, without moving
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    RaisePen,
+    LowerPen,
+    Dwell,
+}
+
+///
+/// An opcode which can follow an instruction's payload.
+///
+/// # Fields:
+/// - `byte`: The byte of the opcode
+/// - `action`: What the machine does for the opcode
+/// - `payload_length`: How many bytes at the start of the payload the opcode reads. The rest of
+///   the payload must be zero, so the machine can reject an instruction it would misread
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Opcode {
+    pub byte: u8,
+    pub action: Action,
+    pub payload_length: usize,
+}
+
+/// Every opcode which can follow an instruction's payload, before the terminator.
+pub const OPCODES: [Opcode; 3] = [
+    Opcode { byte: PEN_UP, action: Action::RaisePen, payload_length: PAYLOAD_LENGTH },
+    Opcode { byte: PEN_DOWN, action: Action::LowerPen, payload_length: PAYLOAD_LENGTH },
+    Opcode { byte: DWELL, action: Action::Dwell, payload_length: 2 },
+];
+
+///
+/// # Parameters:
+/// - `byte`: The byte after an instruction's payload
+///
+/// # Returns:
+/// - The opcode of the byte
+/// - None if the byte isn't an opcode, such as the terminator
+///
+#[inline]
+pub fn lookup(byte: u8) -> Option<&'static Opcode> {
+    OPCODES.iter().find(|opcode| opcode.byte == byte)
+}
@@ -0,0 +1,16 @@
+#![no_main]
+
+use bbcore::instruction::container;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((instruction_set, metadata)) = container::decode_with_metadata(data) {
+        let encoded = container::encode_with_metadata(&instruction_set, metadata.as_ref());
+
+        // JSON can encode the same metadata many ways, so only files without it are byte-identical
+        match metadata {
+            None => assert_eq!(encoded, data),
+            Some(_) => assert_eq!(container::decode(&encoded).unwrap().get_binary(), instruction_set.get_binary()),
+        }
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use bbcore::client::calculate_draw_time;
+use bbcore::hardware::motion::AccelerationProfile;
+use libfuzzer_sys::fuzz_target;
+
+// the draw time is estimated from slices of the stream while drawing
+fuzz_target!(|data: &[u8]| {
+    let _ = calculate_draw_time(data, &AccelerationProfile::from_max_motor_speed(2000.));
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use bbcore::client::header::GreetingHeader;
+use libfuzzer_sys::fuzz_target;
+
+// the greeting header is the first thing read from the machine, before anything is validated
+fuzz_target!(|data: &[u8]| {
+    if let Ok(header) = GreetingHeader::parse(data) {
+        assert_eq!(GreetingHeader::parse(&header.encode().unwrap()).unwrap(), header);
+    }
+});
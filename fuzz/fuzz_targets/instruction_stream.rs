@@ -0,0 +1,25 @@
+#![no_main]
+
+use bbcore::instruction::{codec, InstructionSet};
+use libfuzzer_sys::fuzz_target;
+
+// every parser entry point must return an error for a malformed stream, never panic
+fuzz_target!(|data: &[u8]| {
+    let valid = codec::validate(data).is_ok();
+    let _ = codec::decode_steps(data);
+    let _ = codec::chunk_bounds(data, 64);
+
+    let mut c_idx = 0;
+    while let Ok((_instruction, next_idx)) = codec::Instruction::decode(data, c_idx) {
+        c_idx = next_idx;
+    }
+
+    // a stream which validates must be fully usable
+    if let Ok(instruction_set) = InstructionSet::new(data.to_vec(), 0., 0.) {
+        assert!(valid);
+        instruction_set.parse_to_numerical_steps().unwrap();
+        for chunk_idx in 0..instruction_set.get_buffer_bounds(64).unwrap().len() {
+            instruction_set.get_chunk_instruction_range(chunk_idx, 64).unwrap();
+        }
+    }
+});
@@ -0,0 +1,323 @@
+/*
+ * C bindings for bbcore. Define BBCORE_IMAGE and BBCORE_ASYNC before including this header
+ * when the library was built with its `image` and `async` features, which are on by default.
+ */
+
+#ifndef BBCORE_H
+#define BBCORE_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ *
+ * A generated drawing, owned by the caller until it is passed to `bb_drawing_free`.
+ *
+ * # Fields:
+ * - `instruction_set`: The validated drawing instructions
+ *
+ */
+typedef struct BbDrawing BbDrawing;
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * Where to pause a drawing for the user, owned by the caller until it is passed to
+ * `bb_pauses_free`.
+ *
+ * # Fields:
+ * - `schedule`: The pause points
+ * - `resume`: Notified by `bb_pauses_resume` to resume the drawing after a pause
+ *
+ */
+typedef struct BbPauses BbPauses;
+#endif
+
+#if defined(BBCORE_IMAGE)
+/**
+ *
+ * An RGBA image, owned by the caller until it is passed to `bb_image_free`.
+ *
+ * # Fields:
+ * - `pixels`: The pixels, row by row, 4 bytes per pixel
+ * - `length`: The number of bytes in `pixels`
+ * - `width`: The width of the image, in pixels
+ * - `height`: The height of the image, in pixels
+ *
+ */
+typedef struct BbImage {
+  uint8_t *pixels;
+  size_t length;
+  uint32_t width;
+  uint32_t height;
+} BbImage;
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * A callback receiving the progress of a drawing, as a JSON object with an "event" key. The
+ * string is only valid until the callback returns.
+ *
+ */
+typedef void (*BbEventCallback)(const char *event_json, void *user_data);
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ *
+ * # Returns:
+ * - The error message of the last failed call on this thread, or null if there was none. The
+ *   string is valid until the next failed call on this thread
+ *
+ */
+const char *bb_last_error(void);
+
+/**
+ *
+ * Generates a drawing with a built-in drawing method.
+ *
+ * # Parameters:
+ * - `method_id`: The backend ID of the drawing method
+ * - `parameters_json`: The parameters of the drawing method, as a JSON object
+ * - `dimensions_toml`: The machine configuration, as TOML
+ *
+ * # Returns:
+ * - The drawing, or null if it could not be generated
+ *
+ * # Safety
+ * Every string must be a valid, null-terminated string.
+ *
+ */
+struct BbDrawing *bb_generate(const char *method_id,
+                              const char *parameters_json,
+                              const char *dimensions_toml);
+
+/**
+ *
+ * Frees a drawing.
+ *
+ * # Parameters:
+ * - `drawing`: The drawing to free, which may be null
+ *
+ * # Safety
+ * The drawing must have come from `bb_generate`, and must not be used afterwards.
+ *
+ */
+void bb_drawing_free(struct BbDrawing *drawing);
+
+/**
+ *
+ * # Parameters:
+ * - `drawing`: The drawing
+ * - `length`: Set to the number of instruction bytes
+ *
+ * # Returns:
+ * - The instruction bytes of the drawing, valid until the drawing is freed
+ *
+ * # Safety
+ * The drawing and length must be valid pointers.
+ *
+ */
+const uint8_t *bb_drawing_bytes(const struct BbDrawing *drawing, size_t *length);
+
+/**
+ *
+ * # Parameters:
+ * - `drawing`: The drawing
+ * - `x`: Set to the initial x position of the pen, relative to the top left of the page
+ * - `y`: Set to the initial y position of the pen, relative to the top left of the page
+ *
+ * # Safety
+ * Every pointer must be valid.
+ *
+ */
+void bb_drawing_start(const struct BbDrawing *drawing, double *x, double *y);
+
+#if defined(BBCORE_IMAGE)
+/**
+ *
+ * Renders the preview of a drawing into an RGBA buffer.
+ *
+ * # Parameters:
+ * - `drawing`: The drawing
+ * - `dimensions_toml`: The machine configuration, as TOML
+ * - `scale`: The number of pixels per millimetre of paper
+ * - `image`: Set to the rendered image, which must be freed with `bb_image_free`
+ *
+ * # Returns:
+ * - 0 if the preview was rendered, else -1
+ *
+ * # Safety
+ * Every pointer must be valid, and the string null-terminated.
+ *
+ */
+int32_t bb_render_preview(const struct BbDrawing *drawing,
+                          const char *dimensions_toml,
+                          uint32_t scale,
+                          struct BbImage *image);
+#endif
+
+#if defined(BBCORE_IMAGE)
+/**
+ *
+ * Frees the pixels of an image, and clears it.
+ *
+ * # Parameters:
+ * - `image`: The image to free
+ *
+ * # Safety
+ * The image must have been set by `bb_render_preview`.
+ *
+ */
+void bb_image_free(struct BbImage *image);
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * Draws a drawing on the machine, blocking until it has finished. The pen is moved to the start
+ * of the drawing first.
+ *
+ * # Parameters:
+ * - `drawing`: The drawing
+ * - `dimensions_toml`: The machine configuration, as TOML
+ * - `addr`: The IP address of the machine
+ * - `port`: The port address of the machine
+ * - `callback`: An optional callback receiving the progress of the drawing
+ * - `user_data`: Passed to every call of the callback
+ *
+ * # Returns:
+ * - 0 if the drawing finished, else -1
+ *
+ * # Safety
+ * Every pointer must be valid, and every string null-terminated. The callback is called on
+ * this thread.
+ *
+ */
+int32_t bb_draw(const struct BbDrawing *drawing,
+                const char *dimensions_toml,
+                const char *addr,
+                uint16_t port,
+                BbEventCallback callback,
+                void *user_data);
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * Draws a drawing on the machine like `bb_draw`, pausing it for the user. At each pause point,
+ * the callback receives a "waiting_for_user" event, and the drawing carries on once
+ * `bb_pauses_resume` is called from another thread.
+ *
+ * # Parameters:
+ * - `drawing`: The drawing
+ * - `dimensions_toml`: The machine configuration, as TOML
+ * - `addr`: The IP address of the machine
+ * - `port`: The port address of the machine
+ * - `pauses`: Where to pause the drawing, or null to draw it without pausing
+ * - `callback`: An optional callback receiving the progress of the drawing
+ * - `user_data`: Passed to every call of the callback
+ *
+ * # Returns:
+ * - 0 if the drawing finished, else -1
+ *
+ * # Safety
+ * Every pointer must be valid, and every string null-terminated. The pauses must not be freed
+ * until this returns. The callback is called on this thread.
+ *
+ */
+int32_t bb_draw_with_pauses(const struct BbDrawing *drawing,
+                            const char *dimensions_toml,
+                            const char *addr,
+                            uint16_t port,
+                            const struct BbPauses *pauses,
+                            BbEventCallback callback,
+                            void *user_data);
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * # Returns:
+ * - New pauses with no pause points, to pass to `bb_draw_with_pauses`
+ *
+ */
+struct BbPauses *bb_pauses_new(void);
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * Pauses the drawing before an instruction.
+ *
+ * # Parameters:
+ * - `pauses`: The pauses
+ * - `instruction_idx`: The index of the instruction
+ *
+ * # Safety
+ * The pauses must have come from `bb_pauses_new`, and not be in use by `bb_draw_with_pauses`.
+ *
+ */
+void bb_pauses_add_instruction(struct BbPauses *pauses, size_t instruction_idx);
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * Pauses the drawing once it has been drawing for a time.
+ *
+ * # Parameters:
+ * - `pauses`: The pauses
+ * - `minutes`: The time since the drawing started, in minutes
+ *
+ * # Safety
+ * The pauses must have come from `bb_pauses_new`, and not be in use by `bb_draw_with_pauses`.
+ *
+ */
+void bb_pauses_add_minutes(struct BbPauses *pauses, double minutes);
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * Resumes a drawing paused by `bb_draw_with_pauses`. It may be called from any thread.
+ *
+ * # Parameters:
+ * - `pauses`: The pauses the drawing was given
+ *
+ * # Safety
+ * The pauses must have come from `bb_pauses_new`.
+ *
+ */
+void bb_pauses_resume(const struct BbPauses *pauses);
+#endif
+
+#if defined(BBCORE_ASYNC)
+/**
+ *
+ * Frees pauses.
+ *
+ * # Parameters:
+ * - `pauses`: The pauses to free, which may be null
+ *
+ * # Safety
+ * The pauses must have come from `bb_pauses_new`, and must not be used afterwards.
+ *
+ */
+void bb_pauses_free(struct BbPauses *pauses);
+#endif
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BBCORE_H */
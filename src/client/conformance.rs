@@ -0,0 +1,533 @@
+//!
+//! A conformance suite for drawing machine firmware
+//!
+//! The suite connects to a machine, or anything speaking its protocol such as a simulator, and
+//! runs it through scripted protocol scenarios, reporting which it handled correctly. Firmware
+//! changes can be validated against this crate by running the suite against them.
+//!
+//! Every drawing sent by the suite moves the pen back and forth by a few steps with the pen
+//! raised, so the pen ends where it started and nothing is drawn.
+//!
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Instant};
+
+use crate::instruction::codec::{self, Instruction};
+use crate::instruction::opcode;
+use super::error::ClientError;
+use super::framing::read_message_async;
+use super::state::{ClientState, MachineConfiguration};
+
+///
+/// How long the machine has to respond before a scenario fails.
+///
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+///
+/// How long a paused machine is watched for, to check it doesn't ask for more instructions.
+///
+const PAUSE_WINDOW: Duration = Duration::from_millis(500);
+
+///
+/// How long after a command is sent before the next, so the machine reads them separately.
+///
+const COMMAND_GAP: Duration = Duration::from_millis(20);
+
+///
+/// How often the machine is greeted while waiting for it to accept drawings again.
+///
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(200);
+
+///
+/// The smallest chunk of instructions the suite sends in the tiny buffer scenario, in bytes.
+/// This is the smallest chunk `InstructionSet::get_buffer_bounds` allows.
+///
+const TINY_CHUNK_SIZE: usize = 8;
+
+///
+/// How far each instruction of the suite's drawings moves the motors, in steps.
+///
+const WIGGLE_STEPS: i16 = 20;
+
+///
+/// A protocol scenario the machine is run through.
+///
+/// - `Greeting`: The machine answers a greeting with a valid header, and lets the client end
+///   the drawing straight away
+/// - `TinyBuffer`: A drawing streamed in the smallest chunks, one or two instructions each, is
+///   drawn to the end
+/// - `PauseResume`: A machine paused part way through a chunk doesn't ask for more instructions
+///   until it is resumed, then finishes the drawing
+/// - `Stop`: A stopped machine ends the drawing, and accepts the next one
+/// - `Reconnection`: A machine whose client disconnects part way through a drawing accepts the
+///   next one, rather than staying in use
+/// - `OversizedChunk`: A chunk larger than the machine's instruction buffer is rejected, and the
+///   machine accepts the next drawing
+///
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    Greeting,
+    TinyBuffer,
+    PauseResume,
+    Stop,
+    Reconnection,
+    OversizedChunk,
+}
+
+impl Scenario {
+    ///
+    /// Every scenario, in the order `run` runs them.
+    ///
+    pub const ALL: [Scenario; 6] = [Scenario::Greeting, Scenario::TinyBuffer, Scenario::PauseResume, Scenario::Stop, Scenario::Reconnection, Scenario::OversizedChunk];
+
+    ///
+    /// # Returns:
+    /// - The display name of the scenario
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scenario::Greeting => "Greeting",
+            Scenario::TinyBuffer => "Tiny buffer",
+            Scenario::PauseResume => "Pause and resume mid-chunk",
+            Scenario::Stop => "Stop",
+            Scenario::Reconnection => "Reconnection",
+            Scenario::OversizedChunk => "Oversized chunk",
+        }
+    }
+}
+
+///
+/// The outcome of one scenario.
+///
+/// # Fields:
+/// - `scenario`: The scenario
+/// - `passed`: Whether the machine behaved correctly
+/// - `detail`: What the machine did wrong, or a summary of what it did right
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ScenarioResult {
+    pub scenario: Scenario,
+    pub passed: bool,
+    pub detail: String,
+}
+
+///
+/// The outcome of a conformance run.
+///
+/// # Fields:
+/// - `results`: The outcome of each scenario, in the order they were run
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConformanceReport {
+    pub results: Vec<ScenarioResult>,
+}
+
+impl ConformanceReport {
+    ///
+    /// # Returns:
+    /// - Whether the machine passed every scenario
+    ///
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+}
+
+///
+/// Runs a machine through protocol scenarios, one after another. The machine must be idle, and
+/// the pen is left where it started.
+///
+/// # Parameters:
+/// - `addr`: The IP address of the machine
+/// - `port`: The port address of the machine
+/// - `scenarios`: The scenarios to run, such as `Scenario::ALL`
+///
+/// # Returns:
+/// - The report of which scenarios passed
+///
+pub async fn run(addr: &str, port: u16, scenarios: &[Scenario]) -> ConformanceReport {
+    let mut results = Vec::with_capacity(scenarios.len());
+
+    for &scenario in scenarios {
+        let outcome = match scenario {
+            Scenario::Greeting => greeting(addr, port).await,
+            Scenario::TinyBuffer => tiny_buffer(addr, port).await,
+            Scenario::PauseResume => pause_resume(addr, port).await,
+            Scenario::Stop => stop(addr, port).await,
+            Scenario::Reconnection => reconnection(addr, port).await,
+            Scenario::OversizedChunk => oversized_chunk(addr, port).await,
+        };
+
+        let (passed, detail) = match outcome {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        results.push(ScenarioResult { scenario, passed, detail });
+    }
+
+    ConformanceReport { results }
+}
+
+///
+/// What the machine sent next.
+///
+/// - `Byte`: The machine sent a message
+///     Parameters:
+///     - `0`: The first byte of the message
+/// - `Closed`: The machine closed the connection
+/// - `Silent`: The machine sent nothing before the wait ran out
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Received {
+    Byte(u8),
+    Closed,
+    Silent,
+}
+
+///
+/// # Parameters:
+/// - `socket`: The connection to the machine
+/// - `wait`: How long to wait for the machine
+///
+/// # Returns:
+/// - What the machine sent next
+///
+async fn receive(socket: &mut TcpStream, wait: Duration) -> Received {
+    match timeout(wait, read_message_async(socket)).await {
+        Ok(Ok(message)) => Received::Byte(message.code()),
+        Ok(Err(_)) => Received::Closed,
+        Err(_) => Received::Silent,
+    }
+}
+
+///
+/// Waits for the machine to ask for instructions.
+///
+/// # Parameters:
+/// - `socket`: The connection to the machine
+/// - `stage`: What the machine was asked for, to explain a failure
+///
+/// # Returns:
+/// - Void once the machine asks for instructions
+/// - An explanation of what the machine did instead
+///
+async fn expect_request(socket: &mut TcpStream, stage: &str) -> Result<(), String> {
+    match receive(socket, RESPONSE_TIMEOUT).await {
+        Received::Byte(0x03) => Ok(()),
+        Received::Byte(byte) => Err(format!("Expected a request for instructions {}, received {:#04x}", stage, byte)),
+        Received::Closed => Err(format!("The machine closed the connection {}", stage)),
+        Received::Silent => Err(format!("The machine didn't ask for instructions {}", stage)),
+    }
+}
+
+///
+/// Greets the machine, waiting for it to be free if a previous scenario's drawing is still
+/// ending.
+///
+/// # Returns:
+/// - The connection, after the machine's first request for instructions, and its configuration
+/// - An explanation of why the machine could not be greeted
+///
+async fn connect(addr: &str, port: u16) -> Result<(TcpStream, MachineConfiguration), String> {
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+    loop {
+        match ClientState::new(addr, port).await {
+            Ok((mut socket, machine_config)) => {
+                expect_request(&mut socket, "after the greeting").await?;
+                return Ok((socket, machine_config));
+            },
+            Err(ClientError::MachineInUse) if Instant::now() < deadline => sleep(RECONNECT_INTERVAL).await,
+            Err(ClientError::MachineInUse) => return Err(format!("The machine was still in use {:?} after the last drawing ended", RESPONSE_TIMEOUT)),
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+///
+/// # Parameters:
+/// - `num_instructions`: The number of instructions, rounded up to an even number
+///
+/// # Returns:
+/// - Instructions moving the pen back and forth with the pen raised, ending where they start
+///
+fn wiggle(num_instructions: usize) -> Vec<u8> {
+    let mut bytes = vec![];
+    for i in 0..num_instructions.div_ceil(2) * 2 {
+        let steps = if i % 2 == 0 { WIGGLE_STEPS } else { -WIGGLE_STEPS };
+        Instruction::movement(steps, -steps, if i == 0 { Some(true) } else { None }).encode(&mut bytes);
+    }
+    bytes
+}
+
+///
+/// Sends a chunk of instructions.
+///
+async fn send_chunk(socket: &mut TcpStream, chunk: &[u8]) -> Result<(), String> {
+    let mut buf = Vec::with_capacity(1 + chunk.len());
+    buf.push(0x01);
+    buf.extend_from_slice(chunk);
+    socket.write_all(&buf).await.map_err(|err| format!("The chunk could not be sent. {}", err))
+}
+
+///
+/// Tells the machine the drawing has finished, after it asks for more instructions.
+///
+async fn finish(socket: &mut TcpStream) -> Result<(), String> {
+    socket.write_all(&[0x02]).await.map_err(|err| format!("The end of the drawing could not be sent. {}", err))?;
+    let _ = socket.shutdown().await;
+    Ok(())
+}
+
+///
+/// Runs `Scenario::Greeting`.
+///
+/// # Returns:
+/// - A summary of what the machine did
+/// - What the machine did wrong
+///
+async fn greeting(addr: &str, port: u16) -> Result<String, String> {
+    let (mut socket, machine_config) = connect(addr, port).await?;
+    if machine_config.max_motor_speed == 0 {
+        return Err("The machine reported a top motor speed of 0 steps per second".to_owned());
+    }
+    finish(&mut socket).await?;
+
+    let mut summary = format!("Protocol v{}, a {} byte buffer, up to {} steps per second", machine_config.protocol_version, machine_config.instruction_buffer_size, machine_config.max_motor_speed);
+    if !machine_config.unknown_fields.is_empty() {
+        let tags: Vec<String> = machine_config.unknown_fields.iter().map(|field| format!("{:#04x}", field.tag)).collect();
+        summary += &format!(", with unknown header fields {}", tags.join(", "));
+    }
+    Ok(summary)
+}
+
+///
+/// Runs `Scenario::TinyBuffer`.
+///
+/// # Returns:
+/// - A summary of what the machine did
+/// - What the machine did wrong
+///
+async fn tiny_buffer(addr: &str, port: u16) -> Result<String, String> {
+    let (mut socket, _) = connect(addr, port).await?;
+    let bytes = wiggle(16);
+    let bounds = codec::chunk_bounds(&bytes, TINY_CHUNK_SIZE).map_err(|err| err.to_string())?;
+
+    for (chunk, &(lb, ub)) in bounds.iter().enumerate() {
+        if chunk > 0 {
+            expect_request(&mut socket, &format!("after chunk {} of {}", chunk, bounds.len())).await?;
+        }
+        send_chunk(&mut socket, &bytes[lb..=ub]).await?;
+    }
+    expect_request(&mut socket, "after the last chunk").await?;
+    finish(&mut socket).await?;
+
+    Ok(format!("Drew {} chunks of at most {} bytes", bounds.len(), TINY_CHUNK_SIZE))
+}
+
+///
+/// Runs `Scenario::PauseResume`.
+///
+/// # Returns:
+/// - A summary of what the machine did
+/// - What the machine did wrong
+///
+async fn pause_resume(addr: &str, port: u16) -> Result<String, String> {
+    let (mut socket, machine_config) = connect(addr, port).await?;
+    let bytes = wiggle(machine_config.instruction_buffer_size as usize / opcode::MAX_INSTRUCTION_LENGTH);
+    let bounds = codec::chunk_bounds(&bytes, machine_config.instruction_buffer_size as usize).map_err(|err| err.to_string())?;
+
+    send_chunk(&mut socket, &bytes[bounds[0].0..=bounds[0].1]).await?;
+    sleep(COMMAND_GAP).await;
+    socket.write_all(&[0x04, 0x01]).await.map_err(|err| format!("The pause could not be sent. {}", err))?;
+
+    match receive(&mut socket, PAUSE_WINDOW).await {
+        Received::Silent => {},
+        Received::Byte(0x03) => return Err("The machine asked for more instructions while paused".to_owned()),
+        Received::Byte(byte) => return Err(format!("The machine sent {:#04x} while paused", byte)),
+        Received::Closed => return Err("The machine closed the connection when paused".to_owned()),
+    }
+
+    socket.write_all(&[0x04, 0x00]).await.map_err(|err| format!("The resume could not be sent. {}", err))?;
+    for &(lb, ub) in &bounds[1..] {
+        expect_request(&mut socket, "after resuming").await?;
+        send_chunk(&mut socket, &bytes[lb..=ub]).await?;
+    }
+    expect_request(&mut socket, "after resuming").await?;
+    finish(&mut socket).await?;
+
+    Ok(format!("Stayed paused for {:?}, then finished", PAUSE_WINDOW))
+}
+
+///
+/// Runs `Scenario::Stop`.
+///
+/// # Returns:
+/// - A summary of what the machine did
+/// - What the machine did wrong
+///
+async fn stop(addr: &str, port: u16) -> Result<String, String> {
+    let (mut socket, machine_config) = connect(addr, port).await?;
+    let bytes = wiggle(machine_config.instruction_buffer_size as usize / opcode::MAX_INSTRUCTION_LENGTH);
+    let bounds = codec::chunk_bounds(&bytes, machine_config.instruction_buffer_size as usize).map_err(|err| err.to_string())?;
+
+    send_chunk(&mut socket, &bytes[bounds[0].0..=bounds[0].1]).await?;
+    sleep(COMMAND_GAP).await;
+    socket.write_all(&[0x05]).await.map_err(|err| format!("The stop could not be sent. {}", err))?;
+
+    loop {
+        match receive(&mut socket, RESPONSE_TIMEOUT).await {
+            Received::Closed | Received::Byte(0x05) => break,
+            Received::Byte(0x03) => return Err("The machine asked for more instructions after it was stopped".to_owned()),
+            Received::Byte(_) => continue,
+            Received::Silent => return Err("The machine didn't end the drawing when stopped".to_owned()),
+        }
+    }
+    drop(socket);
+
+    let (mut socket, _) = connect(addr, port).await.map_err(|reason| format!("After stopping, the next drawing was refused. {}", reason))?;
+    finish(&mut socket).await?;
+    Ok("Ended the drawing, and accepted the next".to_owned())
+}
+
+///
+/// Runs `Scenario::Reconnection`.
+///
+/// # Returns:
+/// - A summary of what the machine did
+/// - What the machine did wrong
+///
+async fn reconnection(addr: &str, port: u16) -> Result<String, String> {
+    let (mut socket, machine_config) = connect(addr, port).await?;
+    let bytes = wiggle(machine_config.instruction_buffer_size as usize / opcode::MAX_INSTRUCTION_LENGTH);
+    let bounds = codec::chunk_bounds(&bytes, machine_config.instruction_buffer_size as usize).map_err(|err| err.to_string())?;
+
+    // the client vanishes part way through the drawing
+    send_chunk(&mut socket, &bytes[bounds[0].0..=bounds[0].1]).await?;
+    drop(socket);
+
+    let (mut socket, _) = connect(addr, port).await.map_err(|reason| format!("After the client disconnected, the next drawing was refused. {}", reason))?;
+    finish(&mut socket).await?;
+    Ok("Accepted the next drawing after the client disconnected".to_owned())
+}
+
+///
+/// Runs `Scenario::OversizedChunk`.
+///
+/// # Returns:
+/// - A summary of what the machine did
+/// - What the machine did wrong
+///
+async fn oversized_chunk(addr: &str, port: u16) -> Result<String, String> {
+    let (mut socket, machine_config) = connect(addr, port).await?;
+    let buffer_size = machine_config.instruction_buffer_size as usize;
+    let bytes = wiggle(buffer_size * 2 / opcode::MAX_INSTRUCTION_LENGTH);
+
+    send_chunk(&mut socket, &bytes).await?;
+    match receive(&mut socket, RESPONSE_TIMEOUT).await {
+        Received::Closed | Received::Silent => {},
+        Received::Byte(0x03) => return Err(format!("The machine accepted a {} byte chunk into its {} byte buffer", bytes.len(), buffer_size)),
+        Received::Byte(_) => {},
+    }
+    drop(socket);
+
+    let (mut socket, _) = connect(addr, port).await.map_err(|reason| format!("After an oversized chunk, the next drawing was refused. {}", reason))?;
+    finish(&mut socket).await?;
+    Ok(format!("Rejected a {} byte chunk, and accepted the next drawing", bytes.len()))
+}
+
+
+///
+/// Tests relating to the conformance suite, run against a simulated machine.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::header::GreetingHeader;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    ///
+    /// The instruction buffer size of the simulated machine.
+    ///
+    const SIMULATED_BUFFER_SIZE: u32 = 1024;
+
+    ///
+    /// Simulates a machine, drawing each chunk in 100ms. A faulty machine stays in use after its
+    /// client disconnects, until it is restarted.
+    ///
+    /// # Returns:
+    /// - The port the machine listens on
+    ///
+    async fn simulate_machine(faulty: bool) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let mut stuck = false;
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { return };
+                let mut incoming_buf = [0u8; 4096];
+                if !matches!(socket.read(&mut incoming_buf).await, Ok(2..)) || incoming_buf[..2] != [0x00, 0x01] {
+                    continue;
+                }
+                if stuck {
+                    let _ = socket.write_all(&[0x00]).await;
+                    continue;
+                }
+
+                let header = GreetingHeader { protocol_version: 1, instruction_buffer_size: SIMULATED_BUFFER_SIZE, max_motor_speed: 4000, min_pulse_width: 0, unknown_fields: vec![] };
+                let _ = socket.write_all(&header.encode().unwrap()).await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let _ = socket.write_all(&[0x03]).await;
+
+                let (mut paused, mut drawing_until) = (false, None);
+                let finished = loop {
+                    let wake = drawing_until.filter(|_| !paused).unwrap_or(Instant::now() + Duration::from_secs(60));
+                    tokio::select! {
+                        read = socket.read(&mut incoming_buf) => match read {
+                            Ok(0) | Err(_) => break false,
+                            Ok(length) => match incoming_buf[0] {
+                                0x01 if length - 1 > SIMULATED_BUFFER_SIZE as usize => break true,
+                                0x01 => drawing_until = Some(Instant::now() + Duration::from_millis(100)),
+                                0x02 | 0x05 => break true,
+                                0x04 => paused = incoming_buf[1] == 0x01,
+                                _ => {},
+                            },
+                        },
+                        _ = tokio::time::sleep_until(wake) => {
+                            drawing_until = None;
+                            let _ = socket.write_all(&[0x03]).await;
+                        },
+                    }
+                };
+
+                stuck = faulty && !finished;
+            }
+        });
+
+        port
+    }
+
+    #[tokio::test]
+    async fn a_conforming_machine_passes() {
+        let port = simulate_machine(false).await;
+        let report = run("127.0.0.1", port, &Scenario::ALL).await;
+
+        assert!(report.passed(), "{:#?}", report);
+        assert_eq!(report.results.len(), Scenario::ALL.len());
+    }
+
+    #[tokio::test]
+    async fn a_machine_stuck_in_use_fails_reconnection() {
+        let port = simulate_machine(true).await;
+        let report = run("127.0.0.1", port, &[Scenario::Greeting, Scenario::Reconnection]).await;
+
+        assert!(report.results[0].passed);
+        assert!(!report.results[1].passed, "{:#?}", report);
+        assert!(!report.passed());
+    }
+}
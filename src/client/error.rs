@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::hardware::error::HardwareError;
+
 ///
 /// All errors emitted from the client module.
 /// The error messages can be displayed to users on the frontend. Format nicely please.
@@ -14,6 +16,28 @@ use thiserror::Error;
 /// - `InsBufferSmall`: An error encountered when the instruction buffer on the firmware is too small
 ///     Parameters:
 ///     - `size`: The size of the instruction buffer
+/// - `NoNetwork`: When machines are searched for, but this computer is not on a local network
+///     Parameters:
+///     - `reason`: Why no local address could be found
+/// - `InvalidPaperAdvance`: When the paper is advanced by a distance the machine can not be sent
+///     Parameters:
+///     - `mm`: The requested distance, in millimetres
+/// - `InvalidPausePoint`: When a drawing is scheduled to pause somewhere it doesn't reach
+///     Parameters:
+///     - `reason`: Why the pause point isn't in the drawing
+/// - `TruncatedHeader`: When the machine's response to a greeting ends before its header does
+///     Parameters:
+///     - `expected`: The number of bytes expected, so far
+///     - `received`: The number of bytes received
+/// - `InvalidHeader`: When the machine's response to a greeting is malformed
+///     Parameters:
+///     - `reason`: What was wrong with the header
+/// - `ConnectionLost`: When the connection to the machine failed while reading or writing
+///     Parameters:
+///     - `reason`: The underlying error
+/// - `Hardware`: When the pen can't be moved to a position on the machine
+///     Parameters:
+///     - `HardwareError`: The reason the position can not be reached
 ///     
 #[derive(Error, Debug)]
 pub enum ClientError {
@@ -31,4 +55,25 @@ pub enum ClientError {
 
     #[error("The target machine's instruction buffer size was too small: {} bytes", .size)]
     InsBufferSmall { size: u32 },
+
+    #[error("Couldn't search for machines, this computer isn't connected to a local network. {}", .reason)]
+    NoNetwork { reason: String },
+
+    #[error("The paper can't be advanced by {}mm, it must be between 0.1mm and 6553.5mm.", .mm)]
+    InvalidPaperAdvance { mm: f64 },
+
+    #[error("The drawing can't be paused there, {}.", .reason)]
+    InvalidPausePoint { reason: String },
+
+    #[error("The machine's greeting was cut short, after {} of at least {} bytes.", .received, .expected)]
+    TruncatedHeader { expected: usize, received: usize },
+
+    #[error("The machine's greeting was invalid, {}.", .reason)]
+    InvalidHeader { reason: String },
+
+    #[error("The connection to the machine was lost. {}", .reason)]
+    ConnectionLost { reason: String },
+
+    #[error(transparent)]
+    Hardware(#[from] HardwareError),
 }
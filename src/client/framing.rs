@@ -0,0 +1,167 @@
+use std::io::{Read, Write};
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::error::ClientError;
+use super::header::GreetingHeader;
+
+///
+/// A message from the machine. TCP may split a message across reads, or join several into one
+/// read, so messages are read by their length rather than one per read. Every message but the
+/// greeting header is a single byte, and the header gives its own length.
+///
+/// - `InUse`: 0x00, the machine is already drawing, in answer to a greeting
+/// - `Accepted`: 0x01, the machine is free to draw, in answer to a greeting
+///     Parameters:
+///     - `0`: The machine's greeting header
+/// - `Request`: 0x03, the machine is asking for the next chunk of instructions
+/// - `Stopped`: 0x05, the machine stopped the drawing
+/// - `Unknown`: Any other byte, which is skipped
+///     Parameters:
+///     - `0`: The byte
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    InUse,
+    Accepted(GreetingHeader),
+    Request,
+    Stopped,
+    Unknown(u8),
+}
+
+impl Message {
+    ///
+    /// # Returns:
+    /// - The first byte of the message, identifying it
+    ///
+    pub fn code(&self) -> u8 {
+        match self {
+            Message::InUse => 0x00,
+            Message::Accepted(_) => 0x01,
+            Message::Request => 0x03,
+            Message::Stopped => 0x05,
+            Message::Unknown(byte) => *byte,
+        }
+    }
+}
+
+///
+/// Reads the next message from a blocking connection, reading no further than its end.
+///
+/// # Parameters:
+/// - `reader`: The connection to the machine
+///
+/// # Returns:
+/// - The message
+/// - `ClientError::ConnectionLost` if the connection closed before the message ended
+/// - An error if the message is malformed
+///
+pub fn read_message<R: Read>(reader: &mut R) -> Result<Message, ClientError> {
+    let mut frame = vec![0u8; 1];
+    reader.read_exact(&mut frame).map_err(lost)?;
+    while let Some(missing) = missing_bytes(&frame) {
+        let start = frame.len();
+        frame.resize(start + missing, 0);
+        reader.read_exact(&mut frame[start..]).map_err(lost)?;
+    }
+    decode(&frame)
+}
+
+///
+/// Reads the next message from a connection, like `read_message`, without blocking the runtime.
+///
+/// # Parameters:
+/// - `reader`: The connection to the machine
+///
+/// # Returns:
+/// - The message
+/// - `ClientError::ConnectionLost` if the connection closed before the message ended
+/// - An error if the message is malformed
+///
+#[cfg(feature = "async")]
+pub async fn read_message_async<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message, ClientError> {
+    let mut frame = vec![0u8; 1];
+    reader.read_exact(&mut frame).await.map_err(lost)?;
+    while let Some(missing) = missing_bytes(&frame) {
+        let start = frame.len();
+        frame.resize(start + missing, 0);
+        reader.read_exact(&mut frame[start..]).await.map_err(lost)?;
+    }
+    decode(&frame)
+}
+
+///
+/// Sends a message to the machine over a blocking connection.
+///
+/// # Parameters:
+/// - `writer`: The connection to the machine
+/// - `bytes`: The whole message
+///
+/// # Returns:
+/// - Void once the message is written
+/// - `ClientError::ConnectionLost` if the connection failed while writing
+///
+pub fn send<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), ClientError> {
+    writer.write_all(bytes).map_err(lost)
+}
+
+///
+/// Sends a message to the machine, like `send`, without blocking the runtime.
+///
+/// # Parameters:
+/// - `writer`: The connection to the machine
+/// - `bytes`: The whole message
+///
+/// # Returns:
+/// - Void once the message is written
+/// - `ClientError::ConnectionLost` if the connection failed while writing
+///
+#[cfg(feature = "async")]
+pub async fn send_async<W: AsyncWrite + Unpin>(writer: &mut W, bytes: &[u8]) -> Result<(), ClientError> {
+    writer.write_all(bytes).await.map_err(lost)
+}
+
+///
+/// # Parameters:
+/// - `frame`: The bytes of the message read so far, at least the first
+///
+/// # Returns:
+/// - How many more bytes to read before the message, or its length, is known. None once the
+///   message is complete
+///
+fn missing_bytes(frame: &[u8]) -> Option<usize> {
+    let needed = match frame[0] {
+        // 0x01, then the u16 length of the fields
+        0x01 if frame.len() < 3 => 3,
+        0x01 => 3 + u16::from_be_bytes([frame[1], frame[2]]) as usize,
+        _ => 1,
+    };
+    needed.checked_sub(frame.len()).filter(|missing| *missing > 0)
+}
+
+///
+/// # Parameters:
+/// - `frame`: The bytes of a complete message
+///
+/// # Returns:
+/// - The message
+/// - An error if the greeting header is malformed
+///
+fn decode(frame: &[u8]) -> Result<Message, ClientError> {
+    Ok(match frame[0] {
+        0x00 => Message::InUse,
+        0x01 => Message::Accepted(GreetingHeader::parse(frame)?),
+        0x03 => Message::Request,
+        0x05 => Message::Stopped,
+        byte => Message::Unknown(byte),
+    })
+}
+
+///
+/// # Returns:
+/// - The error for a connection which failed partway through a message
+///
+fn lost(err: std::io::Error) -> ClientError {
+    ClientError::ConnectionLost { reason: err.to_string() }
+}
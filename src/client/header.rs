@@ -0,0 +1,163 @@
+use super::error::ClientError;
+
+///
+/// The first byte of the machine's response to a greeting, when it is free to draw.
+///
+const GREETING_ACCEPTED: u8 = 0x01;
+
+///
+/// The tag of the protocol version field, a u16.
+///
+const PROTOCOL_VERSION_TAG: u8 = 0x01;
+
+///
+/// The tag of the instruction buffer size field, a u32 in bytes.
+///
+const INSTRUCTION_BUFFER_SIZE_TAG: u8 = 0x02;
+
+///
+/// The tag of the max motor speed field, a u32 in steps per second.
+///
+const MAX_MOTOR_SPEED_TAG: u8 = 0x03;
+
+///
+/// The tag of the min pulse width field, a u32 in nanoseconds.
+///
+const MIN_PULSE_WIDTH_TAG: u8 = 0x04;
+
+///
+/// The length of the 0x01 response byte and the u16 length of the fields before them.
+///
+const PREAMBLE_LENGTH: usize = 3;
+
+///
+/// A field of the greeting header this client doesn't know, such as one added by newer firmware.
+///
+/// # Fields:
+/// - `tag`: The tag identifying the field
+/// - `value`: The raw value of the field, at most 255 bytes
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderField {
+    pub tag: u8,
+    pub value: Vec<u8>,
+}
+
+///
+/// The machine's response to a greeting, when it is free to draw. The header is 0x01, then the
+/// length of the fields as a big-endian u16, then the fields. Each field is a tag byte, a length
+/// byte and the value, so fields can be added without breaking older clients.
+///
+/// # Fields:
+/// - `protocol_version`: The protocol version of the drawing machine
+/// - `instruction_buffer_size`: The size of the machine's instruction buffer, in bytes
+/// - `max_motor_speed`: The maximum steps per second
+/// - `min_pulse_width`: The minimum pulse width of a motor step, in nanoseconds
+/// - `unknown_fields`: The fields this client doesn't know, in the order they were sent
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GreetingHeader {
+    pub protocol_version: u16,
+    pub instruction_buffer_size: u32,
+    pub max_motor_speed: u32,
+    pub min_pulse_width: u32,
+    pub unknown_fields: Vec<HeaderField>,
+}
+
+impl GreetingHeader {
+    ///
+    /// Parses the machine's response to a greeting.
+    ///
+    /// # Parameters:
+    /// - `bytes`: The response, starting with 0x01. Bytes after the header are ignored
+    ///
+    /// # Returns:
+    /// - The header
+    /// - `ClientError::TruncatedHeader` if the response ends before the header does
+    /// - `ClientError::InvalidHeader` if a field is malformed, or a required field is missing
+    ///
+    pub fn parse(bytes: &[u8]) -> Result<GreetingHeader, ClientError> {
+        let truncated = |expected: usize| ClientError::TruncatedHeader { expected, received: bytes.len() };
+        let invalid = |reason: String| ClientError::InvalidHeader { reason };
+
+        if bytes.len() < PREAMBLE_LENGTH {
+            return Err(truncated(PREAMBLE_LENGTH));
+        }
+        if bytes[0] != GREETING_ACCEPTED {
+            return Err(invalid(format!("the header started with {:#04x}, not {:#04x}", bytes[0], GREETING_ACCEPTED)));
+        }
+        let length = PREAMBLE_LENGTH + u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+        if bytes.len() < length {
+            return Err(truncated(length));
+        }
+
+        let (mut protocol_version, mut instruction_buffer_size, mut max_motor_speed, mut min_pulse_width) = (None, None, None, None);
+        let mut unknown_fields = vec![];
+        let mut c_idx = PREAMBLE_LENGTH;
+        while c_idx < length {
+            if c_idx + 2 > length {
+                return Err(invalid(format!("the field at byte {} has no length", c_idx)));
+            }
+            let (tag, value_idx) = (bytes[c_idx], c_idx + 2);
+            let next_idx = value_idx + bytes[c_idx + 1] as usize;
+            if next_idx > length {
+                return Err(invalid(format!("the field at byte {} runs past the end of the header", c_idx)));
+            }
+            let value = &bytes[value_idx..next_idx];
+
+            let wrong_length = |expected: usize| invalid(format!("field {:#04x} is {} bytes, expected {}", tag, value.len(), expected));
+            match tag {
+                PROTOCOL_VERSION_TAG => protocol_version = Some(u16::from_be_bytes(value.try_into().map_err(|_| wrong_length(2))?)),
+                INSTRUCTION_BUFFER_SIZE_TAG => instruction_buffer_size = Some(u32::from_be_bytes(value.try_into().map_err(|_| wrong_length(4))?)),
+                MAX_MOTOR_SPEED_TAG => max_motor_speed = Some(u32::from_be_bytes(value.try_into().map_err(|_| wrong_length(4))?)),
+                MIN_PULSE_WIDTH_TAG => min_pulse_width = Some(u32::from_be_bytes(value.try_into().map_err(|_| wrong_length(4))?)),
+                _ => unknown_fields.push(HeaderField { tag, value: value.to_vec() }),
+            }
+            c_idx = next_idx;
+        }
+
+        let missing = |name: &str| invalid(format!("the header has no {}", name));
+        Ok(GreetingHeader {
+            protocol_version: protocol_version.ok_or_else(|| missing("protocol version"))?,
+            instruction_buffer_size: instruction_buffer_size.ok_or_else(|| missing("instruction buffer size"))?,
+            max_motor_speed: max_motor_speed.ok_or_else(|| missing("max motor speed"))?,
+            min_pulse_width: min_pulse_width.ok_or_else(|| missing("min pulse width"))?,
+            unknown_fields,
+        })
+    }
+
+    ///
+    /// Encodes the header as a machine sends it, such as to simulate a machine.
+    ///
+    /// # Returns:
+    /// - The header, starting with 0x01
+    /// - `ClientError::InvalidHeader` if an unknown field is over 255 bytes, or the fields are
+    ///   over 65535 bytes altogether
+    ///
+    pub fn encode(&self) -> Result<Vec<u8>, ClientError> {
+        let mut fields = vec![];
+        let mut push = |tag: u8, value: &[u8]| -> Result<(), ClientError> {
+            let length = u8::try_from(value.len())
+                .map_err(|_| ClientError::InvalidHeader { reason: format!("field {:#04x} is {} bytes, over 255", tag, value.len()) })?;
+            fields.push(tag);
+            fields.push(length);
+            fields.extend_from_slice(value);
+            Ok(())
+        };
+        push(PROTOCOL_VERSION_TAG, &self.protocol_version.to_be_bytes())?;
+        push(INSTRUCTION_BUFFER_SIZE_TAG, &self.instruction_buffer_size.to_be_bytes())?;
+        push(MAX_MOTOR_SPEED_TAG, &self.max_motor_speed.to_be_bytes())?;
+        push(MIN_PULSE_WIDTH_TAG, &self.min_pulse_width.to_be_bytes())?;
+        for field in &self.unknown_fields {
+            push(field.tag, &field.value)?;
+        }
+        let length = u16::try_from(fields.len())
+            .map_err(|_| ClientError::InvalidHeader { reason: format!("the fields are {} bytes, over 65535", fields.len()) })?;
+
+        let mut bytes = Vec::with_capacity(PREAMBLE_LENGTH + fields.len());
+        bytes.push(GREETING_ACCEPTED);
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(&fields);
+        Ok(bytes)
+    }
+}
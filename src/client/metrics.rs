@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+///
+/// Measures the transmission of a drawing to a machine, chunk by chunk, so a stuttering drawing
+/// can be traced to the network or the firmware.
+///
+/// # Fields:
+/// - `started`: When the first chunk was sent
+/// - `outstanding`: When the last chunk finished sending, if the machine hasn't asked for the next
+/// - `outstanding_draw_time`: The estimated time to draw the outstanding chunk
+/// - `chunks`: The number of chunks sent
+/// - `bytes`: The number of bytes sent
+/// - `send_time`: The total time spent writing chunks to the connection
+/// - `max_send_time`: The longest time spent writing a chunk
+/// - `acknowledgements`: The number of chunks the machine asked for more after
+/// - `ack_latency`: The total time between chunks being sent and the machine asking for more
+/// - `max_ack_latency`: The longest time between a chunk being sent and the machine asking for more
+/// - `stall_time`: The total time the machine asked for more later than its chunks should have
+///   taken to draw
+///
+#[derive(Clone, Debug, Default)]
+pub struct TransmissionMetrics {
+    started: Option<Instant>,
+    outstanding: Option<Instant>,
+    outstanding_draw_time: Duration,
+    chunks: usize,
+    bytes: usize,
+    send_time: Duration,
+    max_send_time: Duration,
+    acknowledgements: usize,
+    ack_latency: Duration,
+    max_ack_latency: Duration,
+    stall_time: Duration,
+}
+
+impl TransmissionMetrics {
+    ///
+    /// # Returns:
+    /// - Metrics with nothing sent yet
+    ///
+    pub fn new() -> TransmissionMetrics {
+        TransmissionMetrics::default()
+    }
+
+    ///
+    /// Records a chunk written to the connection.
+    ///
+    /// # Parameters:
+    /// - `bytes`: The length of the chunk, in bytes
+    /// - `sending_since`: When writing the chunk started
+    /// - `draw_time`: The estimated time for the machine to draw the chunk
+    ///
+    /// # Returns:
+    /// - How long the chunk took to write
+    ///
+    pub fn chunk_sent(&mut self, bytes: usize, sending_since: Instant, draw_time: Duration) -> Duration {
+        let now = Instant::now();
+        let send_time = now - sending_since;
+
+        self.started.get_or_insert(sending_since);
+        self.outstanding = Some(now);
+        self.outstanding_draw_time = draw_time;
+        self.chunks += 1;
+        self.bytes += bytes;
+        self.send_time += send_time;
+        self.max_send_time = self.max_send_time.max(send_time);
+        send_time
+    }
+
+    ///
+    /// Records the machine asking for more instructions.
+    ///
+    /// # Returns:
+    /// - The time since the last chunk was sent, or None if no chunk was waiting to be acknowledged
+    ///
+    pub fn chunk_acknowledged(&mut self) -> Option<Duration> {
+        let latency = self.outstanding.take()?.elapsed();
+
+        self.acknowledgements += 1;
+        self.ack_latency += latency;
+        self.max_ack_latency = self.max_ack_latency.max(latency);
+        self.stall_time += latency.saturating_sub(self.outstanding_draw_time);
+        Some(latency)
+    }
+
+    ///
+    /// # Returns:
+    /// - A summary of the transmission so far
+    ///
+    pub fn report(&self) -> TransmissionReport {
+        let elapsed = self.started.map_or(Duration::ZERO, |started| started.elapsed());
+        let mean = |total: Duration, count: usize| if count == 0 { 0. } else { total.as_secs_f64() * 1000. / count as f64 };
+
+        TransmissionReport {
+            chunks: self.chunks,
+            bytes: self.bytes,
+            elapsed_secs: elapsed.as_secs_f64(),
+            mean_send_ms: mean(self.send_time, self.chunks),
+            max_send_ms: self.max_send_time.as_secs_f64() * 1000.,
+            mean_ack_latency_ms: mean(self.ack_latency, self.acknowledgements),
+            max_ack_latency_ms: self.max_ack_latency.as_secs_f64() * 1000.,
+            stall_secs: self.stall_time.as_secs_f64(),
+            throughput_bytes_per_sec: if self.send_time.is_zero() { 0. } else { self.bytes as f64 / self.send_time.as_secs_f64() },
+        }
+    }
+}
+
+///
+/// A summary of a drawing's transmission. A slow send time or low throughput points to the
+/// network, while chunks taking far longer to acknowledge than to draw, with fast sends, point
+/// to the firmware.
+///
+/// # Fields:
+/// - `chunks`: The number of chunks sent
+/// - `bytes`: The number of bytes sent
+/// - `elapsed_secs`: The time since the first chunk was sent, in seconds
+/// - `mean_send_ms`: The mean time to write a chunk to the connection, in milliseconds
+/// - `max_send_ms`: The longest time to write a chunk to the connection, in milliseconds
+/// - `mean_ack_latency_ms`: The mean time from sending a chunk to the machine asking for the
+///   next, in milliseconds. This includes drawing the chunk
+/// - `max_ack_latency_ms`: The longest time from sending a chunk to the machine asking for the
+///   next, in milliseconds
+/// - `stall_secs`: The total time the machine asked for chunks later than they should have taken
+///   to draw, in seconds. This includes any time the drawing was paused
+/// - `throughput_bytes_per_sec`: The bytes sent per second spent sending
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TransmissionReport {
+    pub chunks: usize,
+    pub bytes: usize,
+    pub elapsed_secs: f64,
+    pub mean_send_ms: f64,
+    pub max_send_ms: f64,
+    pub mean_ack_latency_ms: f64,
+    pub max_ack_latency_ms: f64,
+    pub stall_secs: f64,
+    pub throughput_bytes_per_sec: f64,
+}
@@ -3,17 +3,29 @@
 //!
 
 use std::time::Duration;
-use std::{io::Read, net::TcpStream};
-use std::io::prelude::*;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use rayon::prelude::*;
 use error::ClientError;
 use byteorder::{ByteOrder, BigEndian};
+use framing::{read_message, send, Message};
 
 use crate::instruction::error::NextInstructionError;
+use crate::instruction::opcode::{self, Action, PAYLOAD_LENGTH};
 use crate::instruction::get_next_instruction_bounds;
 use crate::{drawing::DrawSurface, hardware::PhysicalDimensions, instruction::InstructionSet};
+use crate::hardware::motion::AccelerationProfile;
 
+#[cfg(feature = "async")]
 pub mod state;
+#[cfg(feature = "async")]
+pub mod conformance;
 pub mod error;
+pub mod framing;
+pub mod header;
+pub mod metrics;
+pub mod schedule;
 
 
 ///
@@ -32,10 +44,12 @@ pub mod error;
 ///
 /// # Returns:
 /// - Void if the function completed successfully
-/// - An error, explaining why the pen could not be moved to the start position
+/// - An error, explaining why the pen could not be moved to the start position, such as
+///   `ClientError::ConnectionLost` if the connection failed, or `ClientError::Hardware` if the
+///   pen can't travel there
 ///
 pub fn move_to_start(addr: &str, port: u16, physical_dimensions: &PhysicalDimensions, x: f64, y: f64) -> Result<(), ClientError> {
-    let raw_ins = DrawSurface::pen_to_start_ins(physical_dimensions, x, y);
+    let raw_ins = DrawSurface::pen_to_start_ins(physical_dimensions, x, y)?;
     let ins_set = match InstructionSet::new(raw_ins, 0., 0.) {
         Ok(val) => val,
         Err(str) => return Err(ClientError::InvalidBytes { reason: format!("Instructions to move pen to starting position were invalid. {}", str).to_owned() }),
@@ -48,77 +62,157 @@ pub fn move_to_start(addr: &str, port: u16, physical_dimensions: &PhysicalDimens
             return Err(ClientError::MachineNotFound { addr: addr.to_owned(), port });
         }
     let mut safe_socket = socket.unwrap();
-    
+    move_pen(&mut safe_socket, &ins_set)
+}
+
+///
+/// Greets the machine and draws one instruction set, in as many chunks as its instruction
+/// buffer needs.
+///
+/// # Parameters:
+/// - `safe_socket`: The connection to the machine
+/// - `ins_set`: The instructions moving the pen
+///
+/// # Returns:
+/// - Void once the machine has drawn the instructions
+/// - An error, explaining why the pen could not be moved
+///
+fn move_pen<S: Read + Write>(safe_socket: &mut S, ins_set: &InstructionSet) -> Result<(), ClientError> {
     // send the greeting bytes
-    let _ = safe_socket.write_all(&[0x00, 0x01]);
-    let mut sent_move_bytes = false;
+    send(safe_socket, &[0x00, 0x01])?;
+    // the chunks are known once the machine says how big its buffer is
+    let mut chunks = vec![].into_iter();
 
-    // then we loop, doing a blocking await for bytes
+    // then we loop, doing a blocking await for each message
     loop {
-        
-        let mut incoming_buf: [u8; 255] = [0; 255];
-        let _ = safe_socket.read(&mut incoming_buf);
+        let message = read_message(safe_socket)?;
         
         // its asking for what to do next
-        if *incoming_buf.get(0).unwrap() == 0x03 {
-            if !sent_move_bytes {
+        if message == Message::Request {
+            if let Some((lb, ub)) = chunks.next() {
                 
-                let mut buf = Vec::with_capacity(1 + ins_set.get_binary().len());
+                let mut buf = Vec::with_capacity(1 + ub - lb + 1);
                 buf.push(0x01);
-                buf.extend_from_slice(&ins_set.get_binary());
-                let _ = safe_socket.write_all(&buf);
-
-                sent_move_bytes = true;
+                buf.extend_from_slice(&ins_set.get_binary()[lb..=ub]);
+                send(safe_socket, &buf)?;
 
             } else {
                 
-                let _ = safe_socket.write_all(&[0x02]);
+                send(safe_socket, &[0x02])?;
                 return Ok(());
             }
         }
 
         // its saying the machine is in use
-        if *incoming_buf.get(0).unwrap() == 0x00 {
+        if message == Message::InUse {
             return Err(ClientError::MachineInUse);
         }
 
         // its sent a response to the greeting bytes
         // this (should) run first in the loop
-        if *incoming_buf.get(0).unwrap() == 0x01 {
-            let (_, ins_buf_size, _, _) = read_header(&incoming_buf);
-            if (ins_buf_size as usize) < ins_set.get_binary().len() {
-                return Err(ClientError::InsBufferSmall { size: ins_buf_size });
-            }
+        if let Message::Accepted(header) = message {
+            let Ok(bounds) = ins_set.get_buffer_bounds(header.instruction_buffer_size as usize) else {
+                return Err(ClientError::InsBufferSmall { size: header.instruction_buffer_size });
+            };
+            chunks = bounds.clone().into_iter();
+        }
+    }
+}
+
+
+///
+/// Advances the paper of a machine with a paper roll, such as between drawings plotted one
+/// after another. Like `move_to_start`, this makes its own connection to the machine, so it must
+/// be called while the machine is not drawing.
+///
+/// # Parameters:
+/// - `addr`: The IP address of the machine
+/// - `port`: The port address of the machine
+/// - `mm`: How far to advance the paper, in millimetres
+///
+/// # Returns:
+/// - Void once the machine has advanced the paper
+/// - An error, explaining why the paper could not be advanced
+///
+pub fn advance_paper(addr: &str, port: u16, mm: f64) -> Result<(), ClientError> {
+    let packet = paper_advance_packet(mm)?;
+
+    let Ok(mut socket) = TcpStream::connect(format!("{}:{}", addr, port)) else {
+        return Err(ClientError::MachineNotFound { addr: addr.to_owned(), port });
+    };
+
+    // send the greeting bytes
+    send(&mut socket, &[0x00, 0x01])?;
+    let mut sent_advance = false;
+
+    loop {
+        match read_message(&mut socket)? {
+            // the machine is asking what to do next, which it does again once the paper has moved
+            Message::Request => {
+                if !sent_advance {
+                    send(&mut socket, &packet)?;
+                    sent_advance = true;
+                } else {
+                    send(&mut socket, &[0x02])?;
+                    return Ok(());
+                }
+            },
+            Message::InUse => return Err(ClientError::MachineInUse),
+            _ => {},
         }
     }
 }
 
+///
+/// # Parameters:
+/// - `mm`: How far to advance the paper, in millimetres
+///
+/// # Returns:
+/// - The packet asking the machine to advance its paper roll: 0x06, then the distance in
+///   tenths of a millimetre as a big-endian u16
+/// - `ClientError::InvalidPaperAdvance` if the distance can not be sent
+///
+fn paper_advance_packet(mm: f64) -> Result<[u8; 3], ClientError> {
+    let tenths = (mm * 10.).round();
+    if !(tenths >= 1. && tenths <= u16::MAX as f64) {
+        return Err(ClientError::InvalidPaperAdvance { mm });
+    }
+
+    let [high, low] = (tenths as u16).to_be_bytes();
+    Ok([0x06, high, low])
+}
+
 
 /// 
 /// Calculates the length, in seconds, a drawing will take.
 /// By taking the raw bytes as a parameter, you can take slices to recalculate the speed
-/// as the drawing progresses.
+/// as the drawing progresses. Each instruction starts and ends at rest, so short instructions
+/// never reach the top speed, and dwells add their wait.
 ///
 /// # Parameters:
 /// - `ins_bytes`: A valid instruction set as a slice of bytes
-/// - `max_motor_speed`: The motor steps per second
-/// - `min_pulse_width`: The minimum pulse width of a motor
+/// - `acceleration_profile`: The motion limits of the motors, measured in motor steps
 ///
 /// # Returns:
 /// - A `Duration` of the time taken to draw the drawing
 ///
-pub fn calculate_draw_time(ins_bytes: &[u8], max_motor_speed: u32, _min_pulse_width: u32) -> Duration {
+pub fn calculate_draw_time(ins_bytes: &[u8], acceleration_profile: &AccelerationProfile) -> Duration {
     let mut total_secs: f64 = 0.;
     let mut c_idx: usize = 0; // current instruction, should always point to the first idx
     
     loop {
         match get_next_instruction_bounds(&ins_bytes, c_idx) {
+            Ok((sb, eb)) if opcode::lookup(ins_bytes[sb + PAYLOAD_LENGTH]).is_some_and(|opcode| opcode.action == Action::Dwell) => {
+                total_secs += BigEndian::read_u16(&ins_bytes[sb..=sb+1]) as f64 / 1000.;
+                c_idx = eb + 1;
+            },
             Ok((sb, eb)) => {
 
-                let left_steps = BigEndian::read_i16(&ins_bytes[sb..=sb+1]).abs();
-                let right_steps = BigEndian::read_i16(&ins_bytes[sb+2..=sb+3]).abs();
+                // i16::MIN has no positive i16, so the steps are made unsigned
+                let left_steps = BigEndian::read_i16(&ins_bytes[sb..=sb+1]).unsigned_abs();
+                let right_steps = BigEndian::read_i16(&ins_bytes[sb+2..=sb+3]).unsigned_abs();
                 let most_steps = left_steps.max(right_steps);
-                total_secs += most_steps as f64 / max_motor_speed as f64;
+                total_secs += acceleration_profile.move_time(most_steps as f64);
 
                 c_idx = eb + 1;
             },
@@ -140,67 +234,43 @@ pub fn calculate_draw_time(ins_bytes: &[u8], max_motor_speed: u32, _min_pulse_wi
 
 
 
-
-/// 
-/// Converts 2 bytes to a u16
 ///
-/// # Parameters:
-/// - `array`: The byte buffer
-/// - `index`: The first-byte's index
-/// 
-/// # Returns:
-/// - The value of the bytes, as a u16
-///
-fn bytes_to_u16(array: &[u8], index: usize) -> u16 {
-    if index + 1 > array.len() {
-        println!("Error converting byteslice to u16 - bytes out of array index");
-        return 0;
-    }
-
-    (array[index] as u16) << 8 | array[index + 1] as u16
-}
-
-/// 
-/// Converts 4 bytes to a u32
+/// Finds drawing machines on the local network, by trying to connect to the port on every other
+/// host in this computer's /24 subnet. No greeting is sent, so a machine which is found is not
+/// started or interrupted.
 ///
 /// # Parameters:
-/// - `array`: The byte buffer
-/// - `index`: The first-byte's index
-/// 
+/// - `port`: The port the machines listen on
+/// - `timeout`: How long to wait for each host to accept the connection
+///
 /// # Returns:
-/// - The value of the bytes, as a u32
+/// - The addresses of the hosts accepting connections on the port
+/// - `ClientError::NoNetwork` if this computer has no IPv4 address on a local network
 ///
-fn bytes_to_u32(array: &[u8], index: usize) -> u32 {
-    if index + 3 > array.len() {
-        println!("Error converting byteslice to u32 - bytes out of array index");
-        return 0;
-    }
+pub fn discover(port: u16, timeout: Duration) -> Result<Vec<Ipv4Addr>, ClientError> {
+    // connecting a udp socket sends nothing, but picks the interface which routes off this host
+    let local_addr = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| socket.connect("192.0.2.1:9").map(|_| socket))
+        .and_then(|socket| socket.local_addr())
+        .map_err(|err| ClientError::NoNetwork { reason: err.to_string() })?;
+
+    let local_ip = match local_addr.ip() {
+        IpAddr::V4(ip) if !ip.is_loopback() && !ip.is_unspecified() => ip,
+        ip => return Err(ClientError::NoNetwork { reason: format!("{} is not a local IPv4 address", ip) }),
+    };
 
-     (array[index] as u32) << 24 | (array[index + 1] as u32) << 16 | (array[index + 2] as u32) << 8 | array[index + 3] as u32
-}
+    let [a, b, c, own_host] = local_ip.octets();
+    let mut found: Vec<Ipv4Addr> = (1..255u8)
+        .into_par_iter()
+        .filter(|host| *host != own_host)
+        .map(|host| Ipv4Addr::new(a, b, c, host))
+        .filter(|ip| TcpStream::connect_timeout(&SocketAddr::from((*ip, port)), timeout).is_ok())
+        .collect();
 
-/// 
-/// Extracts and returns bytes from the greeting response
-///
-/// # Parameters:
-/// - `header`: The incoming buffer
-///
-/// # Returns:
-/// - (protocol_version, instruction_buffer_size, max_motor_speed, min_pulse_width) as reported by
-/// the machine
-///
-fn read_header(header: &[u8; 255]) -> (u16, u32, u32, u32) {
-    // ignore first byte, its the header
-    (
-        bytes_to_u16(header, 1),
-        // start from ins here, 4 bytes, ignoring it for now
-        bytes_to_u32(header, 7),
-        bytes_to_u32(header, 11),
-        bytes_to_u32(header, 15),
-    )
+    found.sort();
+    Ok(found)
 }
 
-
 ///
 /// Tests relating to client helper functions.
 ///
@@ -210,30 +280,225 @@ mod tests {
 
     #[test]
     fn parse_header() {
-        let bytes: [u8; 19] = [0x00  ,  0x00, 0x01  ,  0x00, 0x00, 0x00, 0x00  ,  0x00, 0x00, 0xFF, 0xFF  ,  0x00, 0x00, 0x10, 0x00  ,  0x00, 0x00, 0x00, 0xEA];
+        // the min pulse width comes before an unknown field 0x09, and the buffer size after it
+        let bytes: [u8; 28] = [0x01  ,  0x00, 0x19  ,  0x01, 0x02, 0x00, 0x01  ,  0x03, 0x04, 0x00, 0x00, 0x10, 0x00  ,  0x04, 0x04, 0x00, 0x00, 0x00, 0xEA  ,  0x09, 0x01, 0x7F  ,  0x02, 0x04, 0x00, 0x00, 0xFF, 0xFF];
         let mut full_packet = [0u8; 255];
 
-        full_packet[0..19].copy_from_slice(&bytes);
+        full_packet[0..28].copy_from_slice(&bytes);
 
-        let (pv, ibs, mms, mpw) = read_header(&full_packet);
-        
-        assert_eq!(pv, 1);
-        assert_eq!(ibs, 65535);
-        assert_eq!(mms, 4096);
-        assert_eq!(mpw, 234);
+        let header = header::GreetingHeader::parse(&full_packet).unwrap();
+
+        assert_eq!(header.protocol_version, 1);
+        assert_eq!(header.instruction_buffer_size, 65535);
+        assert_eq!(header.max_motor_speed, 4096);
+        assert_eq!(header.min_pulse_width, 234);
+        assert_eq!(header.unknown_fields, vec![header::HeaderField { tag: 0x09, value: vec![0x7F] }]);
+        assert_eq!(header::GreetingHeader::parse(&header.encode().unwrap()).unwrap(), header);
+    }
+
+    #[test]
+    fn messages_are_framed_across_reads() {
+        ///
+        /// A connection returning at most `max_read` bytes from each read.
+        ///
+        struct Fragmented { bytes: Vec<u8>, max_read: usize }
+        impl std::io::Read for Fragmented {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let length = self.max_read.min(buf.len()).min(self.bytes.len());
+                buf[..length].copy_from_slice(&self.bytes[..length]);
+                self.bytes.drain(..length);
+                Ok(length)
+            }
+        }
+
+        let header = header::GreetingHeader { protocol_version: 2, instruction_buffer_size: 4096, max_motor_speed: 4000, min_pulse_width: 100, unknown_fields: vec![] };
+        let mut bytes = header.encode().unwrap();
+        bytes.extend_from_slice(&[0x03, 0x03, 0x07, 0x05]);
+        let expected = vec![Message::Accepted(header), Message::Request, Message::Request, Message::Unknown(0x07), Message::Stopped];
+
+        // one byte per read, a few bytes per read, and every message in one read
+        for max_read in [1, 3, bytes.len()] {
+            let mut connection = Fragmented { bytes: bytes.clone(), max_read };
+            let messages: Vec<Message> = (0..expected.len()).map(|_| read_message(&mut connection).unwrap()).collect();
+            assert_eq!(messages, expected);
+            assert!(read_message(&mut connection).is_err());
+        }
+
+        // the connection closes partway through the header
+        assert!(read_message(&mut Fragmented { bytes: bytes[..10].to_vec(), max_read: 4 }).is_err());
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        let header = header::GreetingHeader { protocol_version: 2, instruction_buffer_size: 4096, max_motor_speed: 4000, min_pulse_width: 100, unknown_fields: vec![] }.encode().unwrap();
+
+        for length in 0..header.len() {
+            assert!(matches!(header::GreetingHeader::parse(&header[..length]), Err(ClientError::TruncatedHeader { received, .. }) if received == length));
+        }
+
+        // a buffer size of 2 bytes, rather than 4
+        let mut short_field = header.clone();
+        short_field[8] = 0x02;
+        assert!(matches!(header::GreetingHeader::parse(&short_field), Err(ClientError::InvalidHeader { .. })));
+
+        // the header ends before the min pulse width
+        let mut missing_field = header.clone();
+        missing_field[2] -= 6;
+        assert!(matches!(header::GreetingHeader::parse(&missing_field), Err(ClientError::InvalidHeader { .. })));
+
+        // fields too long for their length byte, or the header's length u16
+        let mut too_long = header::GreetingHeader::parse(&header).unwrap();
+        too_long.unknown_fields.push(header::HeaderField { tag: 0x09, value: vec![0; 256] });
+        assert!(matches!(too_long.encode(), Err(ClientError::InvalidHeader { .. })));
+        too_long.unknown_fields = vec![header::HeaderField { tag: 0x09, value: vec![0; 255] }; 300];
+        assert!(matches!(too_long.encode(), Err(ClientError::InvalidHeader { .. })));
     }
 
     #[test]
-    fn test_parse_bytes_u16() {
-        let bytes: [u8; 6] = [0x00, 0x01, 0x00, 0x00, 0x0F, 0xFF];
+    fn draw_time_of_the_longest_moves() {
+        let profile = AccelerationProfile::from_max_motor_speed(2000.);
+
+        // the right motor moves i16::MIN steps, the furthest an instruction can move
+        let time = calculate_draw_time(&[0x00, 0xFF, 0x80, 0x00, 0x0C], &profile);
+        assert!((time.as_secs_f64() - profile.move_time(32768.)).abs() < 1e-6, "{:?}", time);
+        assert_eq!(calculate_draw_time(&[0x80, 0x00, 0x7F, 0xFF, 0x0C], &profile), time);
+    }
 
-        assert_eq!(bytes_to_u16(&bytes, 1), 256);
+    #[test]
+    fn paper_advance_packets() {
+        assert_eq!(paper_advance_packet(120.).unwrap(), [0x06, 0x04, 0xB0]);
+        assert_eq!(paper_advance_packet(0.26).unwrap(), [0x06, 0x00, 0x03]);
+        assert!(paper_advance_packet(0.).is_err());
+        assert!(paper_advance_packet(-5.).is_err());
+        assert!(paper_advance_packet(f64::NAN).is_err());
+        assert!(paper_advance_packet(10_000.).is_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn pause_points_split_the_chunks() {
+        use crate::drawing::stroke::{self, Stroke};
+        use schedule::{PausePoint, PauseSchedule};
+
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let line = |y: f64, layer: usize| Stroke { points: (0..=20).map(|x| (20. + x as f64 * 5., y)).collect(), pen_up: false, layer, label: None };
+        let ((ins_bytes, init_x, init_y), layer_starts) = stroke::compile_with_layer_starts(&[line(50., 0), line(60., 0), line(70., 1)], &physical_dimensions).unwrap();
+        let ins_set = InstructionSet::new(ins_bytes, init_x, init_y).unwrap();
+
+        // each line is 20 instructions, joined by about 100mm of pen up travel in 11 moves
+        assert_eq!(layer_starts, vec![0, 51]);
+
+        let schedule = PauseSchedule::new()
+            .with_layer_starts(layer_starts.clone())
+            .with_pause(PausePoint::Layer(1))
+            .with_pause(PausePoint::Instruction(3))
+            .with_pause(PausePoint::AfterMinutes(30.));
+        let chunks = schedule.chunks(&ins_set, 1024).unwrap();
+
+        // the chunks still cover the whole drawing, in order
+        assert_eq!(chunks.first().unwrap().bounds.0, 0);
+        assert_eq!(chunks.last().unwrap().bounds.1, ins_set.get_binary().len() - 1);
+        assert!(chunks.windows(2).all(|w| w[0].bounds.1 + 1 == w[1].bounds.0));
+
+        let paused: Vec<(usize, &str)> = chunks.iter().filter_map(|chunk| Some((chunk.bounds.0, chunk.pause.as_deref()?))).collect();
+        let start_of = |instruction_idx: usize| (0..instruction_idx).fold(0, |c_idx, _| get_next_instruction_bounds(ins_set.get_binary(), c_idx).unwrap().1 + 1);
+        assert_eq!(paused, vec![(start_of(3), "instruction 3"), (start_of(51), "layer 1")]);
+        assert_eq!(schedule.timers(), vec![(Duration::from_secs(1800), "30 minutes elapsed".to_owned())]);
+
+        assert!(matches!(PauseSchedule::new().with_pause(PausePoint::Layer(2)).with_layer_starts(layer_starts).chunks(&ins_set, 1024), Err(ClientError::InvalidPausePoint { .. })));
+        assert!(matches!(PauseSchedule::new().with_pause(PausePoint::Instruction(1000)).chunks(&ins_set, 1024), Err(ClientError::InvalidPausePoint { .. })));
     }
 
     #[test]
-    fn test_parse_bytes_u32() {
-        let bytes: [u8; 6] = [0x00, 0x01, 0x00, 0x00, 0x0F, 0xFF];
+    fn transmission_metrics_pair_chunks_with_acknowledgements() {
+        let mut metrics = metrics::TransmissionMetrics::new();
+        assert_eq!(metrics.chunk_acknowledged(), None);
+
+        metrics.chunk_sent(4096, std::time::Instant::now(), std::time::Duration::ZERO);
+        metrics.chunk_sent(1024, std::time::Instant::now(), std::time::Duration::ZERO);
+        assert!(metrics.chunk_acknowledged().is_some());
+        // only the most recent chunk is waiting for the machine
+        assert_eq!(metrics.chunk_acknowledged(), None);
+
+        let report = metrics.report();
+        assert_eq!((report.chunks, report.bytes), (2, 5120));
+        assert!(report.max_ack_latency_ms >= report.mean_ack_latency_ms);
+        assert!(report.stall_secs * 1000. <= report.max_ack_latency_ms);
+    }
 
-        assert_eq!(bytes_to_u32(&bytes, 1), 16777231);
+    #[test]
+    fn failed_writes_lose_the_connection() {
+        use crate::drawing::stroke::{self, Stroke};
+
+        // replays the machine's messages, and fails partway through writing the chunk
+        struct FailingTransport { replies: std::io::Cursor<Vec<u8>>, written: usize, max_written: usize }
+        impl std::io::Read for FailingTransport {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.replies.read(buf)
+            }
+        }
+        impl std::io::Write for FailingTransport {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.written == self.max_written {
+                    return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "the machine went away"));
+                }
+                let length = buf.len().min(self.max_written - self.written);
+                self.written += length;
+                Ok(length)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let (ins_bytes, init_x, init_y) = stroke::compile(&[Stroke { points: vec![(20., 20.), (80., 40.)], pen_up: false, layer: 0, label: None }], &physical_dimensions).unwrap();
+        let ins_set = InstructionSet::new(ins_bytes, init_x, init_y).unwrap();
+        let mut replies = header::GreetingHeader { protocol_version: 2, instruction_buffer_size: 4096, max_motor_speed: 4000, min_pulse_width: 100, unknown_fields: vec![] }.encode().unwrap();
+        replies.push(0x03);
+
+        // the greeting is written, but the chunk is cut off after its first bytes
+        let mut transport = FailingTransport { replies: std::io::Cursor::new(replies), written: 0, max_written: 6 };
+        assert!(matches!(move_pen(&mut transport, &ins_set), Err(ClientError::ConnectionLost { .. })));
+        assert_eq!(transport.written, 6);
+    }
+
+    #[test]
+    fn moves_longer_than_the_buffer_are_sent_in_chunks() {
+        // replays the machine's messages, and records what is written
+        struct Transport { replies: std::io::Cursor<Vec<u8>>, written: Vec<u8> }
+        impl std::io::Read for Transport {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.replies.read(buf)
+            }
+        }
+        impl std::io::Write for Transport {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.written.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let physical_dimensions = PhysicalDimensions::new(2500., 250., 250., 2000., 1500.);
+        let ins_set = InstructionSet::new(DrawSurface::pen_to_start_ins(&physical_dimensions, 1800., 1400.).unwrap(), 0., 0.).unwrap();
+        let bounds = ins_set.get_buffer_bounds(1024).unwrap().clone();
+        assert!(bounds.len() > 1);
+
+        // the machine asks for every chunk, then once more when it has drawn them
+        let mut replies = header::GreetingHeader { protocol_version: 2, instruction_buffer_size: 1024, max_motor_speed: 4000, min_pulse_width: 100, unknown_fields: vec![] }.encode().unwrap();
+        replies.extend(std::iter::repeat_n(0x03, bounds.len() + 1));
+        let mut transport = Transport { replies: std::io::Cursor::new(replies), written: vec![] };
+        move_pen(&mut transport, &ins_set).unwrap();
+
+        let mut expected = vec![0x00, 0x01];
+        for (lb, ub) in bounds {
+            expected.push(0x01);
+            expected.extend_from_slice(&ins_set.get_binary()[lb..=ub]);
+        }
+        expected.push(0x02);
+        assert_eq!(transport.written, expected);
     }
 }
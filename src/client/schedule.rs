@@ -0,0 +1,183 @@
+#[cfg(feature = "async")]
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use crate::instruction::InstructionSet;
+#[cfg(feature = "async")]
+use crate::instruction::get_next_instruction_bounds;
+
+#[cfg(feature = "async")]
+use super::error::ClientError;
+
+///
+/// A point in a drawing where the machine pauses until the user resumes it, such as to swap the
+/// pen between layers.
+///
+/// - `Layer`: Before a layer starts, counted in drawing order from 0
+///     Parameters:
+///     - `0`: The index of the layer, into the layer starts given to the schedule
+/// - `Instruction`: Before an instruction is drawn
+///     Parameters:
+///     - `0`: The index of the instruction
+/// - `AfterMinutes`: At the first chunk after the drawing has been running for a time, such as to
+///   stop an overnight drawing at dawn
+///     Parameters:
+///     - `0`: The time since the first chunk was sent, in minutes
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum PausePoint {
+    Layer(usize),
+    Instruction(usize),
+    AfterMinutes(f64),
+}
+
+///
+/// The pause points of a drawing, honoured by `ClientState::listen`.
+///
+/// # Fields:
+/// - `points`: The pause points, in any order
+/// - `layer_starts`: The index of the first instruction of each layer, in drawing order, as
+///   returned by `stroke::compile_with_layer_starts`
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PauseSchedule {
+    points: Vec<PausePoint>,
+    layer_starts: Vec<usize>,
+}
+
+///
+/// A chunk of instructions to send to the machine, and why the machine pauses before drawing it.
+///
+/// # Fields:
+/// - `bounds`: The (first, last) byte of the chunk, inclusive
+/// - `pause`: The reason to pause before the chunk, if the machine should pause
+///
+#[cfg(feature = "async")]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ScheduledChunk {
+    pub bounds: (usize, usize),
+    pub pause: Option<String>,
+}
+
+impl PauseSchedule {
+    ///
+    /// # Returns:
+    /// - A schedule with no pause points
+    ///
+    pub fn new() -> PauseSchedule {
+        PauseSchedule::default()
+    }
+
+    ///
+    /// # Parameters:
+    /// - `point`: A point to pause the drawing at
+    ///
+    /// # Returns:
+    /// - The schedule, pausing at the point as well
+    ///
+    pub fn with_pause(mut self, point: PausePoint) -> PauseSchedule {
+        self.points.push(point);
+        self
+    }
+
+    ///
+    /// # Parameters:
+    /// - `layer_starts`: The index of the first instruction of each layer, in drawing order
+    ///
+    /// # Returns:
+    /// - The schedule, able to resolve `PausePoint::Layer`
+    ///
+    pub fn with_layer_starts(mut self, layer_starts: Vec<usize>) -> PauseSchedule {
+        self.layer_starts = layer_starts;
+        self
+    }
+
+    ///
+    /// Splits a drawing into the chunks sent to the machine, so each pause at an instruction or
+    /// layer falls between two chunks.
+    ///
+    /// # Parameters:
+    /// - `instruction_set`: The drawing
+    /// - `max_chunk_size`: The maximum size of a chunk, in bytes
+    ///
+    /// # Returns:
+    /// - The chunks, in order
+    /// - An error if a pause point isn't in the drawing
+    ///
+    #[cfg(feature = "async")]
+    pub(crate) fn chunks(&self, instruction_set: &InstructionSet, max_chunk_size: usize) -> Result<Vec<ScheduledChunk>, ClientError> {
+        let invalid = |reason: String| ClientError::InvalidPausePoint { reason };
+        let binary = instruction_set.get_binary();
+
+        // the first byte of every instruction
+        let mut instruction_starts = vec![];
+        let mut c_idx = 0;
+        while let Ok((sb, eb)) = get_next_instruction_bounds(binary, c_idx) {
+            instruction_starts.push(sb);
+            c_idx = eb + 1;
+        }
+
+        let mut pauses: Vec<(usize, String)> = vec![];
+        for point in &self.points {
+            let (instruction_idx, reason) = match point {
+                PausePoint::Layer(layer) => match self.layer_starts.get(*layer) {
+                    Some(&instruction_idx) => (instruction_idx, format!("layer {}", layer)),
+                    None => return Err(invalid(format!("the drawing has {} layers, so layer {} can't be paused at", self.layer_starts.len(), layer))),
+                },
+                PausePoint::Instruction(instruction_idx) => (*instruction_idx, format!("instruction {}", instruction_idx)),
+                PausePoint::AfterMinutes(_) => continue,
+            };
+            match instruction_starts.get(instruction_idx) {
+                Some(&byte_idx) => pauses.push((byte_idx, reason)),
+                None => return Err(invalid(format!("the drawing has {} instructions, so instruction {} can't be paused at", instruction_starts.len(), instruction_idx))),
+            }
+        }
+        pauses.sort_by_key(|(byte_idx, _)| *byte_idx);
+
+        let mut chunks = vec![];
+        let mut pauses = pauses.into_iter().peekable();
+        let bounds = instruction_set.get_buffer_bounds(max_chunk_size).map_err(|err| ClientError::InvalidBytes { reason: err.to_string() })?;
+        for &(lb, ub) in bounds {
+            let mut start = lb;
+            let mut reasons = vec![];
+            while let Some((byte_idx, _)) = pauses.peek() && *byte_idx <= ub {
+                let (byte_idx, reason) = pauses.next().unwrap();
+                if byte_idx > start {
+                    chunks.push(ScheduledChunk { bounds: (start, byte_idx - 1), pause: join(&mut reasons) });
+                    start = byte_idx;
+                }
+                reasons.push(reason);
+            }
+            chunks.push(ScheduledChunk { bounds: (start, ub), pause: join(&mut reasons) });
+        }
+
+        Ok(chunks)
+    }
+
+    ///
+    /// # Returns:
+    /// - The (time since the first chunk was sent, reason) of each timed pause, soonest first.
+    ///   Pauses after an infinite time never happen, so are left out
+    ///
+    #[cfg(feature = "async")]
+    pub(crate) fn timers(&self) -> Vec<(Duration, String)> {
+        let mut timers: Vec<(Duration, String)> = self.points.iter().filter_map(|point| match point {
+            PausePoint::AfterMinutes(minutes) if minutes.is_finite() => Some((Duration::from_secs_f64(minutes.max(0.) * 60.), format!("{} minutes elapsed", minutes))),
+            _ => None,
+        }).collect();
+        timers.sort_by_key(|(after, _)| *after);
+        timers
+    }
+}
+
+///
+/// # Returns:
+/// - The reasons joined into one, emptying them, or None if there are no reasons
+///
+#[cfg(feature = "async")]
+fn join(reasons: &mut Vec<String>) -> Option<String> {
+    match reasons.is_empty() {
+        true => None,
+        false => Some(std::mem::take(reasons).join(", ")),
+    }
+}
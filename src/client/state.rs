@@ -1,14 +1,29 @@
-use tokio::io::{AsyncWriteExt, AsyncReadExt};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::sync::Mutex;
 use tokio::net::TcpStream;
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::time::sleep;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::instruction::InstructionSet;
 use crate::client::calculate_draw_time;
+use crate::hardware::motion::AccelerationProfile;
 
 use super::error::ClientError;
-use super::read_header;
+use super::metrics::{TransmissionMetrics, TransmissionReport};
+use super::schedule::PauseSchedule;
+use super::framing::{read_message_async, send_async, Message};
+use super::header::{GreetingHeader, HeaderField};
+
+///
+/// How long `ClientState::reconnect` waits for the machine to accept drawings again.
+///
+pub const RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+///
+/// How often `ClientState::reconnect` greets the machine while waiting for it.
+///
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(200);
 
 ///
 /// Empty struct for method implementation.
@@ -41,17 +56,19 @@ impl ClientState {
         let mut safe_socket = socket.unwrap();
 
         // send greeting byte and read response
-        let _ = safe_socket.write_all(&[0x00, 0x01]).await;
-        let mut inc_buffer = [0; 255];
-        if let Err(_) = safe_socket.read(&mut inc_buffer).await {
-            return Err(ClientError::GreetingTimedOut);
+        send_async(&mut safe_socket, &[0x00, 0x01]).await?;
+        // only the response is read, so a request for instructions sent straight after it is
+        // left for the implementation to read
+        let message = match read_message_async(&mut safe_socket).await {
+            Ok(message) => message,
+            Err(err @ (ClientError::TruncatedHeader { .. } | ClientError::InvalidHeader { .. })) => return Err(err),
+            Err(_) => return Err(ClientError::GreetingTimedOut),
         };
 
-        if *inc_buffer.get(0).unwrap() == 0x01 {
+        if let Message::Accepted(header) = message {
             // machine is okay to get started with drawing. so initialise machine config, and
             // return the client state instance so the implementation (frontend, cli) can takeover
-            let (protocol_version, instruction_buffer_size, max_motor_speed, min_pulse_width) = read_header(&inc_buffer);
-            let machine_configuration = MachineConfiguration { protocol_version, instruction_buffer_size, max_motor_speed, min_pulse_width };
+            let machine_configuration = MachineConfiguration::from(header);
 
             if machine_configuration.instruction_buffer_size < 1024 {
                 return Err(ClientError::InsBufferSmall { size: machine_configuration.instruction_buffer_size });
@@ -59,17 +76,41 @@ impl ClientState {
 
             return Ok((safe_socket, machine_configuration));
 
-        } else if *inc_buffer.get(0).unwrap() == 0x00 {
+        } else if message == Message::InUse {
             // machine is NOT okay to get started. protocol should parse this here
             return Err(ClientError::MachineInUse);
 
         } else { // TODO firmware returns protocol, return invalid protocol
-            return Err(ClientError::InvalidBytes { reason: "Sent a greeting but the response header was not 0x01".to_owned() })
+            return Err(ClientError::InvalidBytes { reason: format!("Sent a greeting but the response header was {:#04x}, not 0x01", message.code()) })
 
         }
     }
 
 
+    ///
+    /// Connects to the machine again after the connection was lost, waiting for it to notice
+    /// the old connection is gone and accept drawings again.
+    ///
+    /// # Parameters:
+    /// - `addr`: The IP address of the machine
+    /// - `port`: The port address of the machine
+    ///
+    /// # Returns:
+    /// - An owned TcpStream, and the machines configuration, like `ClientState::new`
+    /// - A `ClientError` if the machine could not be reached or was still in use after
+    ///   `RECONNECT_TIMEOUT`
+    ///
+    pub async fn reconnect(addr: &str, port: u16) -> Result<(TcpStream, MachineConfiguration), ClientError> {
+        let deadline = Instant::now() + RECONNECT_TIMEOUT;
+        loop {
+            match ClientState::new(addr, port).await {
+                Err(ClientError::MachineInUse | ClientError::MachineNotFound { .. }) if Instant::now() < deadline => sleep(RECONNECT_INTERVAL).await,
+                result => return result,
+            }
+        }
+    }
+
+
     /// 
     /// TODO: If protocol enum implementations are added, can be used here
     ///
@@ -80,8 +121,14 @@ impl ClientState {
     /// - `should_pause`: true to pause, false to resume
     /// - `emit`: A callback function to emit updates from the function
     ///
-    pub async fn pause<F>(writer: &mut OwnedWriteHalf, should_pause: bool, mut emit: F)
+    /// # Returns:
+    /// - Void once the pause packet is sent
+    /// - `ClientError::ConnectionLost` if the connection failed, after emitting a
+    ///   `connection_lost` event
+    ///
+    pub async fn pause<W, F>(writer: &mut W, should_pause: bool, mut emit: F) -> Result<(), ClientError>
     where
+        W: AsyncWrite + Unpin,
         F: FnMut(String) + Send + 'static {
         let flag_byte: u8 = match should_pause {
             true => 0x01,
@@ -89,9 +136,10 @@ impl ClientState {
         };
 
         // 0x01 = pause, 0x00 = resume
-        let _ = writer.write_all(&[0x04, flag_byte]).await;
+        send_or_report(writer, &[0x04, flag_byte], &mut emit).await?;
 
         emit(r#"{"event":"pause", "is_paused":""#.to_owned() + (if flag_byte == 0x01 { "1" } else { "0" }) + r#""}"#);
+        Ok(())
     } 
 
     /// 
@@ -103,13 +151,20 @@ impl ClientState {
     /// - `writer`: A mutex-locked TcpStream write half
     /// - `emit`: A callback function to emit updates from the function
     ///
-    pub async fn stop<F>(writer: &mut OwnedWriteHalf, mut emit: F)
+    /// # Returns:
+    /// - Void once the stop packet is sent
+    /// - `ClientError::ConnectionLost` if the connection failed, after emitting a
+    ///   `connection_lost` event
+    ///
+    pub async fn stop<W, F>(writer: &mut W, mut emit: F) -> Result<(), ClientError>
     where
+        W: AsyncWrite + Unpin,
         F: FnMut(String) + Send + 'static {
         // shutdown byte
-        let _ = writer.write_all(&[0x05]).await; 
+        send_or_report(writer, &[0x05], &mut emit).await?;
         let _ = writer.shutdown().await;
         emit(r#"{"event":"shutdown"}"#.to_owned());
+        Ok(())
     }
 
 
@@ -117,37 +172,106 @@ impl ClientState {
     /// TODO: If protocol enum implementations are added, can be used here
     ///
     /// Continuously listens for bytes from a TcpStream's read half. It handles the incoming bytes
-    /// appropriately, sometimes writing to the stream.
+    /// appropriately, sometimes writing to the stream. The transmission of each chunk is measured,
+    /// and reported with its progress.
+    /// At each pause point, the machine is sent the pause packet before the next chunk, and a
+    /// `waiting_for_user` event is emitted. The drawing carries on once the user resumes it with
+    /// `ClientState::pause`.
     ///
     /// # Parameters:
     /// - `reader`: A mutex-locked read half of a TcpStream
     /// - `write_ref`: A reference to the guarded TcpStream write half
     /// - `buf_idx`: A usize identifying the ins_set bound to send to the machine
     /// - `ins_set`: The drawing instruction set
+    /// - `schedule`: Where to pause the drawing for the user. The chunks are split at each pause
+    ///   point, so `buf_idx` counts the split chunks
     /// - `emit`: A callback function to emit updates from the function
     ///
-    pub async fn listen<F>(reader: &mut OwnedReadHalf, write_ref: &Arc<Mutex<Option<OwnedWriteHalf>>>, buf_idx: &Arc<Mutex<usize>>, ins_set: &InstructionSet, machine_config: &MachineConfiguration, mut emit: F)
+    /// # Returns:
+    /// - A summary of the transmission, once the drawing finishes or is stopped
+    /// - `ClientError::InvalidPausePoint` if a pause point isn't in the drawing
+    /// - `ClientError::ConnectionLost` if the connection failed before the drawing finished, after
+    ///   emitting a `connection_lost` event. If a write failed, the machine was waiting for the
+    ///   next chunk, so the drawing can be resumed from `buf_idx` after `ClientState::reconnect`
+    ///
+    pub async fn listen<R, W, F>(reader: &mut R, write_ref: &Arc<Mutex<Option<W>>>, buf_idx: &Arc<Mutex<usize>>, ins_set: &InstructionSet, machine_config: &MachineConfiguration, schedule: &PauseSchedule, mut emit: F) -> Result<TransmissionReport, ClientError>
     where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
         F: FnMut(String) + Send + 'static,
     {
-        // continuous blocking loop
-        loop {
-            let mut incoming_buf: [u8; 255] = [0; 255];
-            let _ = reader.read(&mut incoming_buf).await; // will block
+        ClientState::stream(reader, write_ref, buf_idx, ins_set, machine_config, schedule, &mut |event| match event {
+            ListenEvent::Drawing { bounds: (lb, ub), chunk, num_chunks, secs_remaining, send_time, ack_latency } => {
+                // this is a little progress update
+                // event:drawing, new_ins: bytes:bytes (num/of num) time:newseconds
+                emit(
+                    format!(
+                        r#"{{"event":"drawing", "ins_pos":"{}", "secs_remaining":"{}", "send_ms":"{:.1}", "ack_latency_ms":"{:.1}"}}"#, format!("{} 🡲 {} ({}/{})", lb, ub, chunk, num_chunks), secs_remaining, send_time.as_secs_f64() * 1000., ack_latency.as_secs_f64() * 1000.
+                    )
+                );
+            },
+            ListenEvent::WaitingForUser { reason } => emit(format!(r#"{{"event":"waiting_for_user", "reason":{}}}"#, serde_json::to_string(&reason).unwrap_or_default())),
+            ListenEvent::ConnectionLost { reason, .. } => report_lost(&ClientError::ConnectionLost { reason }, &mut emit),
+            ListenEvent::Finished { report } => {
+                emit(format!(r#"{{"event":"transmission_report", "report":{}}}"#, serde_json::to_string(&report).unwrap_or_default()));
+                emit(r#"{"event":"drawing_finished"}"#.to_owned());
+            },
+            ListenEvent::Stopped { .. } => {},
+        }).await
+    }
 
-            if *incoming_buf.get(0).unwrap() == 0x02 {}
+    ///
+    /// Streams a drawing to the machine like `ClientState::listen`, emitting its progress as
+    /// `ListenEvent`s, so the pipeline can report it as its own events.
+    ///
+    /// # Parameters:
+    /// - `reader`: A mutex-locked read half of a TcpStream
+    /// - `write_ref`: A reference to the guarded TcpStream write half
+    /// - `buf_idx`: A usize identifying the ins_set bound to send to the machine
+    /// - `ins_set`: The drawing instruction set
+    /// - `schedule`: Where to pause the drawing for the user
+    /// - `emit`: A callback receiving the progress of the drawing
+    ///
+    /// # Returns:
+    /// - A summary of the transmission, once the drawing finishes or is stopped
+    /// - `ClientError::InvalidPausePoint` if a pause point isn't in the drawing
+    /// - `ClientError::ConnectionLost` if the connection failed before the drawing finished,
+    ///   after emitting `ListenEvent::ConnectionLost`
+    ///
+    pub(crate) async fn stream<R, W, F>(reader: &mut R, write_ref: &Arc<Mutex<Option<W>>>, buf_idx: &Arc<Mutex<usize>>, ins_set: &InstructionSet, machine_config: &MachineConfiguration, schedule: &PauseSchedule, emit: &mut F) -> Result<TransmissionReport, ClientError>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+        F: FnMut(ListenEvent),
+    {
+        let mut metrics = TransmissionMetrics::new();
+        let chunks = schedule.chunks(ins_set, machine_config.instruction_buffer_size as usize)?;
+        let mut timers = schedule.timers().into_iter().peekable();
+        let mut started: Option<Instant> = None;
 
-            if *incoming_buf.get(0).unwrap() == 0x03 {
+        // continuous blocking loop
+        loop {
+            let message = match read_message_async(reader).await { // will block
+                Ok(message) => message,
+                Err(err) => {
+                    let chunk = *buf_idx.lock().await;
+                    return Err(lost(err, chunk, false, emit));
+                },
+            };
+
+            if message == Message::Request {
+                let ack_latency = metrics.chunk_acknowledged().unwrap_or_default();
                 let mut next_buf_lock = buf_idx.lock().await;
                 *next_buf_lock += 1;
 
-                let bounds = ins_set.get_buffer_bounds(machine_config.instruction_buffer_size as usize).unwrap();
-
-                if *next_buf_lock - 1 == bounds.len() {
+                if *next_buf_lock - 1 == chunks.len() {
 
                     let mut write_lock = write_ref.lock().await;
                     let writer = write_lock.as_mut().unwrap();
-                    let _ = writer.write_all(&[0x02]).await;
+                    if let Err(err) = send_async(writer, &[0x02]).await {
+                        *next_buf_lock -= 1;
+                        return Err(lost(err, chunks.len(), true, emit));
+                    }
                     
                     // reader gets shutdown when write does im pretty sure
                     let _ = writer.shutdown().await;
@@ -155,43 +279,155 @@ impl ClientState {
                     drop(write_lock);
                     drop(next_buf_lock);
 
-                    emit(r#"{"event":"drawing_finished"}"#.to_owned());
+                    let report = metrics.report();
+                    emit(ListenEvent::Finished { report: report.clone() });
 
                     // println!("Drawing has finished. Stopped listen loop.");
-                    return;
+                    return Ok(report);
                 }
                 
 
-                let (lb, ub) = bounds.get(*next_buf_lock - 1).unwrap();
+                let chunk = chunks.get(*next_buf_lock - 1).unwrap();
+                let (lb, ub) = chunk.bounds;
+                let sent = *next_buf_lock - 1;
+
+                // every timer which has run out pauses the drawing once, before this chunk
+                let elapsed = started.get_or_insert_with(Instant::now).elapsed();
+                let mut reasons: Vec<String> = chunk.pause.iter().cloned().collect();
+                while let Some((after, _)) = timers.peek() && *after <= elapsed {
+                    reasons.push(timers.next().unwrap().1);
+                }
 
                 let mut write_lock = write_ref.lock().await;
                 let writer = write_lock.as_mut().unwrap();
+                if !reasons.is_empty() {
+                    // the machine's buffer is empty, so it pauses before drawing the next chunk
+                    if let Err(err) = send_async(writer, &[0x04, 0x01]).await {
+                        *next_buf_lock -= 1;
+                        return Err(lost(err, sent, true, emit));
+                    }
+                    emit(ListenEvent::WaitingForUser { reason: reasons.join(", ") });
+                }
+
                 let mut buf = Vec::with_capacity(1 + ub - lb + 1);
                 buf.push(0x01);
-                buf.extend_from_slice(&ins_set.get_binary()[*lb..=*ub]);
-                let _ = writer.write_all(&buf).await;
-                
-                // this is a little progress update
-                // event:drawing, new_ins: bytes:bytes (num/of num) time:newseconds
-                let remaining_draw_time = calculate_draw_time(&ins_set.get_binary()[*lb..], machine_config.max_motor_speed, machine_config.min_pulse_width).as_secs();
-                emit(
-                    format!(
-                        r#"{{"event":"drawing", "ins_pos":"{}", "secs_remaining":"{}"}}"#, format!("{} 🡲 {} ({}/{})", lb, ub, *next_buf_lock, ins_set.get_buffer_bounds(4096).unwrap().len()), remaining_draw_time
-                    )
-                );
+                buf.extend_from_slice(&ins_set.get_binary()[lb..=ub]);
+                let sending_since = Instant::now();
+                if let Err(err) = send_async(writer, &buf).await {
+                    *next_buf_lock -= 1;
+                    return Err(lost(err, sent, true, emit));
+                }
+                let chunk_draw_time = calculate_draw_time(&ins_set.get_binary()[lb..=ub], &machine_config.acceleration_profile());
+                let send_time = metrics.chunk_sent(buf.len() - 1, sending_since, chunk_draw_time);
+
+                let secs_remaining = calculate_draw_time(&ins_set.get_binary()[lb..], &machine_config.acceleration_profile()).as_secs();
+                emit(ListenEvent::Drawing { bounds: (lb, ub), chunk: *next_buf_lock, num_chunks: chunks.len(), secs_remaining, send_time, ack_latency });
 
                 drop(write_lock);
                 drop(next_buf_lock);
                 continue;
             }
 
-            if *incoming_buf.get(0).unwrap() == 0x05 {
-                return;
+            if message == Message::Stopped {
+                let report = metrics.report();
+                emit(ListenEvent::Stopped { report: report.clone() });
+                return Ok(report);
             }
         }
     }
 }
 
+///
+/// The progress of a drawing streamed by `ClientState::stream`.
+///
+/// - `Drawing`: A chunk of instructions was sent to the machine
+///     Parameters:
+///     - `bounds`: The (first, last) byte of the chunk, inclusive
+///     - `chunk`: The number of chunks sent so far
+///     - `num_chunks`: The total number of chunks
+///     - `secs_remaining`: The estimated number of seconds left in the drawing
+///     - `send_time`: How long the chunk took to write
+///     - `ack_latency`: The time between the previous chunk being sent and the machine asking
+///       for this one
+/// - `WaitingForUser`: The machine was paused at a pause point, until the user resumes it
+///     Parameters:
+///     - `reason`: The pause points reached
+/// - `ConnectionLost`: The connection to the machine failed during the drawing
+///     Parameters:
+///     - `chunk`: The number of chunks sent before the connection failed
+///     - `reason`: Why the connection failed
+///     - `resumable`: Whether a write failed while the machine waited for the next chunk. Its
+///       buffer is then empty, so the drawing can carry on from `chunk` on a new connection
+/// - `Finished`: Every instruction was sent, and the machine was told the drawing has finished
+///     Parameters:
+///     - `report`: A summary of the transmission
+/// - `Stopped`: The machine stopped the drawing before it finished
+///     Parameters:
+///     - `report`: A summary of the transmission
+///
+pub(crate) enum ListenEvent {
+    Drawing { bounds: (usize, usize), chunk: usize, num_chunks: usize, secs_remaining: u64, send_time: Duration, ack_latency: Duration },
+    WaitingForUser { reason: String },
+    ConnectionLost { chunk: usize, reason: String, resumable: bool },
+    Finished { report: TransmissionReport },
+    Stopped { report: TransmissionReport },
+}
+
+///
+/// Emits `ListenEvent::ConnectionLost`, if the error is a lost connection.
+///
+/// # Parameters:
+/// - `err`: The error
+/// - `chunk`: The number of chunks sent before the error
+/// - `resumable`: Whether the drawing can carry on from `chunk` on a new connection
+/// - `emit`: A callback receiving the progress of the drawing
+///
+/// # Returns:
+/// - The error
+///
+fn lost<F: FnMut(ListenEvent)>(err: ClientError, chunk: usize, resumable: bool, emit: &mut F) -> ClientError {
+    if let ClientError::ConnectionLost { reason } = &err {
+        emit(ListenEvent::ConnectionLost { chunk, reason: reason.clone(), resumable });
+    }
+    err
+}
+
+///
+/// Sends a message to the machine, emitting a `connection_lost` event if the connection failed.
+///
+/// # Parameters:
+/// - `writer`: The connection to the machine
+/// - `bytes`: The whole message
+/// - `emit`: A callback function to emit updates from the function
+///
+/// # Returns:
+/// - Void once the message is written
+/// - `ClientError::ConnectionLost` if the connection failed while writing
+///
+async fn send_or_report<W, F>(writer: &mut W, bytes: &[u8], emit: &mut F) -> Result<(), ClientError>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(String) {
+    let result = send_async(writer, bytes).await;
+    if let Err(err) = &result {
+        report_lost(err, emit);
+    }
+    result
+}
+
+///
+/// Emits a `connection_lost` event, if the error is a lost connection.
+///
+/// # Parameters:
+/// - `err`: The error
+/// - `emit`: A callback function to emit updates from the function
+///
+fn report_lost<F: FnMut(String)>(err: &ClientError, emit: &mut F) {
+    if let ClientError::ConnectionLost { reason } = err {
+        emit(format!(r#"{{"event":"connection_lost", "reason":{}}}"#, serde_json::to_string(reason).unwrap_or_default()));
+    }
+}
+
 
 /// 
 /// Wrapper of basic machine configuration information.
@@ -202,11 +438,36 @@ impl ClientState {
 /// - `instruction_buffer_size`: The size of the machines instruction buffer
 /// - `max_motor_speed`: The maximum steps per second
 /// - `min_pulse_width`: The minimum pulse width of a motor step, in nanoseconds
+/// - `unknown_fields`: The fields of the greeting header this client doesn't know, such as ones
+///   added by newer firmware
 ///
 pub struct MachineConfiguration {
     pub protocol_version: u16,
     pub instruction_buffer_size: u32,
     pub max_motor_speed: u32,
     pub min_pulse_width: u32,
+    pub unknown_fields: Vec<HeaderField>,
+}
+
+impl From<GreetingHeader> for MachineConfiguration {
+    fn from(header: GreetingHeader) -> MachineConfiguration {
+        MachineConfiguration {
+            protocol_version: header.protocol_version,
+            instruction_buffer_size: header.instruction_buffer_size,
+            max_motor_speed: header.max_motor_speed,
+            min_pulse_width: header.min_pulse_width,
+            unknown_fields: header.unknown_fields,
+        }
+    }
+}
+
+impl MachineConfiguration {
+    ///
+    /// # Returns:
+    /// - The estimated motion limits of the machine's motors, measured in motor steps
+    ///
+    pub fn acceleration_profile(&self) -> AccelerationProfile {
+        AccelerationProfile::from_max_motor_speed(self.max_motor_speed as f64)
+    }
 }
 
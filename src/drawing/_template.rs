@@ -3,6 +3,7 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 ///
 /// An empty struct to implement the "%%%" draw method on.
@@ -30,7 +31,15 @@ impl DrawMethod for %%%Method {
     }
 
     ///
-    /// Generates instructions to perform the %%%TOLOWERCASE drawing method.
+    /// # Returns:
+    /// - The parameters a new %%%TOLOWERCASE drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        %%%Parameters {}
+    }
+
+    ///
+    /// Generates the strokes to perform the %%%TOLOWERCASE drawing method.
     /// This drawing method .........................................
     ///
     /// # Parameters:
@@ -38,10 +47,10 @@ impl DrawMethod for %%%Method {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &%%%Parameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &%%%Parameters) -> Result<Vec<Stroke>, String> {
         
         let mut surface = DrawSurface::new(physical_dimensions);
         
@@ -49,7 +58,7 @@ impl DrawMethod for %%%Method {
         surface.raise_pen(false);
         surface.sample_xy(20., 20.).unwrap();
         
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
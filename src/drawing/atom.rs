@@ -6,6 +6,7 @@ use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 ///
 /// An empty struct to implement the "Atom" draw method on.
@@ -33,7 +34,15 @@ impl DrawMethod for AtomMethod {
     }
 
     ///
-    /// Generates instructions to perform the atom drawing method.
+    /// # Returns:
+    /// - The parameters a new atom drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        AtomParameters { seed: 1, num_shells: 4, min_shell_width: 10., max_shell_width: 20., nucleus_size: 15., nucleus_scramble: 2., nucleus_circles: 6 }
+    }
+
+    ///
+    /// Generates the strokes to perform the atom drawing method.
     /// This drawing method draws a small "nucleus" surrounded by random, orbiting shells.
     ///
     /// # Parameters:
@@ -41,10 +50,10 @@ impl DrawMethod for AtomMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &AtomParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &AtomParameters) -> Result<Vec<Stroke>, String> {
 
         let cx = physical_dimensions.page_width() / 2.;
         let cy = physical_dimensions.page_height() / 2.;
@@ -55,7 +64,8 @@ impl DrawMethod for AtomMethod {
         surface.raise_pen(true);
 
 
-        for _ in 0..parameters.num_shells {
+        for shell in 0..parameters.num_shells {
+            surface.set_label(Some(format!("shell {}", shell + 1)));
             let x_scale;
             let y_scale;
             if rng.random_bool(0.5) {
@@ -86,6 +96,7 @@ impl DrawMethod for AtomMethod {
 
 
         // now draw the nucleus
+        surface.set_label(Some("nucleus".to_owned()));
         for _ in 0..parameters.nucleus_circles {
             // generate the points to draw
             let x_scale;
@@ -120,7 +131,7 @@ impl DrawMethod for AtomMethod {
         }
 
         
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -0,0 +1,104 @@
+//!
+//! Generating drawings on tokio's blocking thread pool, so async frontends don't stall their
+//! runtime while a drawing method reads files and crunches numbers for seconds to minutes
+//!
+//! Progress is reported as the number of positions sampled on the `DrawSurface` so far. Drawing
+//! methods don't know how many positions they will sample, so this is a sign of life rather than
+//! a percentage.
+//!
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::DrawMethod;
+use crate::hardware::PhysicalDimensions;
+
+///
+/// How often the progress of a generation is checked.
+///
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+thread_local! {
+    /// The sample counter of the generation running on this thread, if any.
+    static SAMPLES: RefCell<Option<Arc<AtomicUsize>>> = const { RefCell::new(None) };
+}
+
+///
+/// Counts a position sampled by a `DrawSurface`, towards the progress of the generation running
+/// on this thread.
+///
+pub(crate) fn count_sample() {
+    SAMPLES.with(|samples| {
+        if let Some(samples) = samples.borrow().as_ref() {
+            samples.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+}
+
+///
+/// Runs a generation on tokio's blocking thread pool, reporting its progress until it finishes.
+///
+/// # Parameters:
+/// - `generate`: The generation to run
+/// - `on_progress`: A callback receiving the number of positions sampled so far, called only when
+///   it has changed
+///
+/// # Returns:
+/// - The result of the generation
+/// - An error if the generation panicked or was cancelled
+///
+pub async fn spawn_generation<T, G, P>(generate: G, mut on_progress: P) -> Result<T, String>
+where
+    T: Send + 'static,
+    G: FnOnce() -> T + Send + 'static,
+    P: FnMut(usize) {
+    let samples = Arc::new(AtomicUsize::new(0));
+    let counter = samples.clone();
+
+    let mut handle = tokio::task::spawn_blocking(move || {
+        SAMPLES.with(|samples| *samples.borrow_mut() = Some(counter));
+        let result = generate();
+        // blocking threads are reused, so the next generation must not count towards this one
+        SAMPLES.with(|samples| *samples.borrow_mut() = None);
+        result
+    });
+
+    let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+    let mut reported = 0;
+    loop {
+        tokio::select! {
+            result = &mut handle => return result.map_err(|err| format!("The drawing could not be generated. {}", err)),
+            _ = interval.tick() => {
+                let sampled = samples.load(Ordering::Relaxed);
+                if sampled != reported {
+                    reported = sampled;
+                    on_progress(sampled);
+                }
+            },
+        }
+    }
+}
+
+///
+/// The async equivalent of `DrawMethod::gen_instructions`, generating on tokio's blocking
+/// thread pool.
+///
+/// # Parameters:
+/// - `method`: The drawing method
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `parameters`: The parameters of the drawing method
+/// - `on_progress`: A callback receiving the number of positions sampled so far
+///
+/// # Returns:
+/// - The drawing instruction bytes and pen start position
+/// - An error explaining why the drawing could not be generated
+///
+pub async fn gen_instructions_async<M, P>(method: M, physical_dimensions: PhysicalDimensions, parameters: M::DrawParameters, on_progress: P) -> Result<(Vec<u8>, f64, f64), String>
+where
+    M: DrawMethod + Send + 'static,
+    M::DrawParameters: Send + 'static,
+    P: FnMut(usize) {
+    spawn_generation(move || method.gen_instructions(&physical_dimensions, &parameters), on_progress).await?
+}
@@ -3,7 +3,9 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 use crate::drawing::util::*;
+use crate::drawing::util::placement::{Fit, Margins, Placement};
 
 ///
 /// An empty struct to implement the "Bubbles" draw method on.
@@ -30,7 +32,19 @@ impl DrawMethod for BubblesMethod {
     }
 
     ///
-    /// Generates instructions to perform the bubbles drawing method.
+    /// # Returns:
+    /// - The parameters a new bubbles drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        BubblesParameters {
+            image_path: String::new(), placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            brightness_threshold: 200, num_stipples: 2000, num_iterations: 20, relaxation_tendency: 50,
+            tone_curve: Default::default(), seeding: Default::default(), seed: None, tour: Default::default(), separation: None,
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the bubbles drawing method.
     /// This drawing method uses a weighted voronoi stippling technique in order to create an even
     /// distribution of points on a plane. Finally, it creates circles in conjunction with these
     /// points to simulate bubbles.
@@ -40,10 +54,10 @@ impl DrawMethod for BubblesMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error explaining why the drawing instructions could not be generated
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &BubblesParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &BubblesParameters) -> Result<Vec<Stroke>, String> {
         
         if parameters.image_path.is_empty() {
             return Err("Select an input image".to_owned());
@@ -51,47 +65,81 @@ impl DrawMethod for BubblesMethod {
 
         let relaxation_coefficient = parameters.relaxation_tendency as f32 / 100.;
         
-        let stippled_points: Vec<stipple_structures::Point> = match stipple::stipple_points(parameters.image_path.as_str(), parameters.num_stipples, parameters.num_iterations, relaxation_coefficient, parameters.brightness_threshold) {
-            Ok(val) => val,
-            Err(err_str) => return Err(err_str),
+        // a separated image is stippled once per layer, each drawn with its own pen
+        let layers: Vec<stipple::StippleLayer> = match parameters.separation {
+            None => {
+                let points = stipple::stipple_points(parameters.image_path.as_str(), &parameters.tone_curve, parameters.num_stipples, parameters.num_iterations, relaxation_coefficient, parameters.brightness_threshold, parameters.seeding, parameters.seed)?;
+                vec![stipple::StippleLayer { name: "stipples".to_owned(), colour: [0, 0, 0, 255], points }]
+            },
+            Some(separation) => stipple::stipple_separated(parameters.image_path.as_str(), &parameters.tone_curve, separation, parameters.num_stipples, parameters.num_iterations, relaxation_coefficient, parameters.brightness_threshold, parameters.seeding, parameters.seed)?,
         };
-        let tour = stipple::nearest_neighbour_tour(&stippled_points);
+        if layers.is_empty() {
+            return Err("The input image has too little ink in any layer to stipple".to_owned());
+        }
 
-        let max_x = stippled_points.iter().max_by_key(|p| p.x).unwrap().x.into_inner();
-        let max_y = stippled_points.iter().max_by_key(|p| p.y).unwrap().y.into_inner();
+        // every layer is scaled the same, so the layers line up on the page
+        let all_points = || layers.iter().flat_map(|layer| layer.points.iter());
+        let max_x = all_points().map(|p| p.x).max().unwrap().into_inner();
+        let max_y = all_points().map(|p| p.y).max().unwrap().into_inner();
 
-        let biggest_divisor = 1. / (parameters.width / max_x).min(parameters.height / max_y);
+        let area = parameters.placement.area(physical_dimensions)?;
+        let placed = parameters.placement.place(physical_dimensions, max_x as f64, max_y as f64)?;
+        let (scale_x, scale_y) = ((placed.width / max_x as f64) as f32, (placed.height / max_y as f64) as f32);
+        let (left, top) = (placed.x as f32, placed.y as f32);
 
         let mut surface = DrawSurface::new(physical_dimensions);
         surface.raise_pen(false);
 
-        for t in tour.windows(2) {
-            let scaled_x = stippled_points[t[0]].x.into_inner() / biggest_divisor;
-            let scaled_y = stippled_points[t[0]].y.into_inner() / biggest_divisor;
-            
-            let center: (f32, f32) = ((scaled_x + stippled_points[t[1]].x.into_inner() / biggest_divisor) / 2., (scaled_y + stippled_points[t[1]].y.into_inner() / biggest_divisor) / 2.);
-            let dist_to_next = ((stippled_points[t[1]].x / biggest_divisor - scaled_x).powi(2) + (stippled_points[t[1]].y / biggest_divisor - scaled_y).powi(2)).sqrt();
-
-            let start_angle = (scaled_y - center.1).atan2(scaled_x - center.0);
-            let end_angle = start_angle + 1.5 * 2. * std::f32::consts::PI;
-
-            let radius = dist_to_next / 2.;
-            let iterations: usize = ((radius * 6.) as usize).max(6);
-
-            for i in 0..=iterations {
-                let theta = start_angle + (end_angle - start_angle) * (i as f32 / iterations as f32);
-                let offset_x = f32::cos(theta) * radius;
-                let offset_y = f32::sin(theta) * radius;
-                // let lerped = lerp_xy(scaled_x + offset_x, stippled_points[t[1]].x / 5., scaled_y + offset_y, stippled_points[t[1]].y / 5., (i as f32 / iterations as f32));
-
-                // surface.sample_xy((scaled_x + offset_x + lerped.0).into_inner() as f64, (scaled_y + offset_y + lerped.1).into_inner() as f64);
-                if let Err(err_str) = surface.sample_xy((center.0 + offset_x + parameters.horizontal_offset) as f64, (center.1 + offset_y + parameters.vertical_offset) as f64) {
-                    return Err(err_str);
-                };
+        for (layer_idx, layer) in layers.iter().enumerate() {
+            if parameters.separation.is_some() {
+                surface.set_layer(layer_idx);
+                surface.set_label(Some(layer.name.clone()));
+            }
+            if layer_idx > 0 {
+                surface.raise_pen(true);
+            }
+
+            let stippled_points = &layer.points;
+            let mut tour = stipple::nearest_neighbour_tour(stippled_points, &parameters.tour);
+            tour::improve_tour(stippled_points, &mut tour, tour::DEFAULT_TIME_BUDGET, tour::DEFAULT_MOVE_BUDGET);
+
+            for t in tour.windows(2) {
+                let scaled_x = stippled_points[t[0]].x.into_inner() * scale_x + left;
+                let scaled_y = stippled_points[t[0]].y.into_inner() * scale_y + top;
+                let next_x = stippled_points[t[1]].x.into_inner() * scale_x + left;
+                let next_y = stippled_points[t[1]].y.into_inner() * scale_y + top;
+
+                let center: (f32, f32) = ((scaled_x + next_x) / 2., (scaled_y + next_y) / 2.);
+                let dist_to_next = (next_x - scaled_x).hypot(next_y - scaled_y);
+
+                // a covering image is cropped at the margins, so bubbles outside them are travelled over
+                if parameters.placement.fit == Fit::Cover && !area.contains(center.0 as f64, center.1 as f64) {
+                    surface.raise_pen(true);
+                    continue;
+                }
+
+                let start_angle = (scaled_y - center.1).atan2(scaled_x - center.0);
+                let end_angle = start_angle + 1.5 * 2. * std::f32::consts::PI;
+
+                let radius = dist_to_next / 2.;
+                let iterations: usize = ((radius * 6.) as usize).max(6);
+
+                for i in 0..=iterations {
+                    let theta = start_angle + (end_angle - start_angle) * (i as f32 / iterations as f32);
+                    let offset_x = f32::cos(theta) * radius;
+                    let offset_y = f32::sin(theta) * radius;
+                    // let lerped = lerp_xy(scaled_x + offset_x, stippled_points[t[1]].x / 5., scaled_y + offset_y, stippled_points[t[1]].y / 5., (i as f32 / iterations as f32));
+
+                    // surface.sample_xy((scaled_x + offset_x + lerped.0).into_inner() as f64, (scaled_y + offset_y + lerped.1).into_inner() as f64);
+                    if let Err(err_str) = surface.sample_xy((center.0 + offset_x) as f64, (center.1 + offset_y) as f64) {
+                        return Err(err_str);
+                    };
+                    surface.raise_pen(false);
+                }
             }
         }
 
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -100,28 +148,44 @@ impl DrawMethod for BubblesMethod {
 ///
 /// # Fields:
 /// - `image_path`: The path of the image to stipple
-/// - `width`: The maximum width of the drawing
-/// - `height`: The maximum height of the drawing
-/// - `horizontal_offset`: The horizontal offset of the drawing
-/// - `vertical_offset`: The vertical offset of the drawing
+/// - `placement`: Where the stippled image is placed on the page
 /// - `num_stipples`: The desired number of stipple points
 /// - `num_iterations`: The desired number of iterations of Lloyd's relaxation
 /// - `relaxation_tendency`: A float to represent a scalar multiplier for the relaxation tendency
+/// - `tone_curve`: The curve the brightness of the image is remapped through
+/// - `seeding`: How the stipple points are placed before they are relaxed
+/// - `seed`: A seed for the stipple points, or None for different points every time
+/// - `tour`: Where the path through the stipple points starts, and how it avoids long jumps
+/// - `separation`: How the image is separated into layers, each stippled and drawn with its own
+///   pen and labelled with the layer's name, or None to stipple it with one pen
 ///
 #[derive(Serialize, Deserialize)]
 pub struct BubblesParameters {
     image_path: String,
 
-    width: f32,
-    height: f32,
-    horizontal_offset: f32,
-    vertical_offset: f32,
+    #[serde(default)]
+    placement: Placement,
 
     brightness_threshold: u8,
 
     num_stipples: usize,
     num_iterations: usize,
     relaxation_tendency: u8,
+
+    #[serde(default)]
+    tone_curve: tone::ToneCurve,
+
+    #[serde(default)]
+    seeding: stipple::Seeding,
+
+    #[serde(default)]
+    seed: Option<u64>,
+
+    #[serde(default)]
+    tour: stipple::TourOptions,
+
+    #[serde(default)]
+    separation: Option<stipple::Separation>,
 }
 
 impl DrawParameters for BubblesParameters {}
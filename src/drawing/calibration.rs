@@ -0,0 +1,124 @@
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use crate::hardware::calibration::reference_grid;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+///
+/// An empty struct to implement the "Calibration" draw method on.
+///
+pub struct CalibrationMethod;
+
+impl DrawMethod for CalibrationMethod {
+    type DrawParameters = CalibrationParameters;
+    
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "calibration"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Calibration"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new calibration drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        CalibrationParameters { columns: 3, rows: 4, margin: 15., cross_size: 6. }
+    }
+
+    ///
+    /// Generates the strokes to perform the calibration drawing method.
+    /// This drawing method plots a grid of crosses across the page. The user measures where each
+    /// cross was actually drawn, to fit a `CalibrationMap` for the machine.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &CalibrationParameters) -> Result<Vec<Stroke>, String> {
+        let (origin, spacing) = parameters.grid_layout(physical_dimensions)?;
+        let half_cross = parameters.cross_size / 2.;
+
+        let mut surface = DrawSurface::new(physical_dimensions);
+
+        for (x, y) in reference_grid(origin, spacing, parameters.columns, parameters.rows) {
+            surface.sample_xy(x - half_cross, y)?;
+            surface.raise_pen(false);
+            surface.sample_xy(x + half_cross, y)?;
+            surface.raise_pen(true);
+            surface.sample_xy(x, y - half_cross)?;
+            surface.raise_pen(false);
+            surface.sample_xy(x, y + half_cross)?;
+            surface.raise_pen(true);
+        }
+        
+        Ok(surface.into_strokes())
+    }
+}
+
+
+///
+/// The position of the first cross, and the horizontal and vertical spacing between crosses.
+///
+type GridLayout = ((f64, f64), (f64, f64));
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `columns`: The number of crosses in each row, at least 2
+/// - `rows`: The number of crosses in each column, at least 2
+/// - `margin`: The distance between the edge of the page and the outermost crosses, in millimetres
+/// - `cross_size`: The width and height of each cross, in millimetres
+///
+#[derive(Serialize, Deserialize)]
+pub struct CalibrationParameters {
+    pub columns: usize,
+    pub rows: usize,
+    pub margin: f64,
+    pub cross_size: f64,
+}
+
+impl CalibrationParameters {
+    ///
+    /// Calculates the layout of the grid, to pass to `CalibrationMap::fit_mesh` alongside the
+    /// measured positions of the crosses.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    ///
+    /// # Returns:
+    /// - The position of the first cross, and the horizontal and vertical spacing between crosses
+    /// - An error, explaning why the grid does not fit the page
+    ///
+    pub fn grid_layout(&self, physical_dimensions: &PhysicalDimensions) -> Result<GridLayout, String> {
+        if self.columns < 2 || self.rows < 2 {
+            return Err("The calibration grid needs at least 2 rows and columns.".to_owned());
+        }
+
+        let spacing_x = (physical_dimensions.page_width() - 2. * self.margin) / (self.columns - 1) as f64;
+        let spacing_y = (physical_dimensions.page_height() - 2. * self.margin) / (self.rows - 1) as f64;
+        if spacing_x <= 0. || spacing_y <= 0. {
+            return Err("The calibration grid margin is too large for the page.".to_owned());
+        }
+
+        Ok(((self.margin, self.margin), (spacing_x, spacing_y)))
+    }
+}
+
+impl DrawParameters for CalibrationParameters {}
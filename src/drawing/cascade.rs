@@ -2,9 +2,11 @@
 use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use rand::seq::SliceRandom;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 ///
 /// An empty struct to implement the "Cascade" draw method on.
@@ -31,7 +33,15 @@ impl DrawMethod for CascadeMethod {
     }
 
     ///
-    /// Generates instructions to perform the cascade drawing method.
+    /// # Returns:
+    /// - The parameters a new cascade drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        CascadeParameters { horizontal_margin: 20., vertical_margin: 20., boxes_vertical: 30, boxes_horizontal: 10, seed: None }
+    }
+
+    ///
+    /// Generates the strokes to perform the cascade drawing method.
     /// This drawing method creates a wall of triangles falling down the page, with many single
     /// triangles, as well as a many longer triangles too.
     ///
@@ -40,10 +50,10 @@ impl DrawMethod for CascadeMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error explainig why the drawing instructions could not be generated
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &CascadeParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &CascadeParameters) -> Result<Vec<Stroke>, String> {
         
         // calculate constants
         let vertical_mm_per_box = (physical_dimensions.page_height() - 2. * parameters.vertical_margin) / parameters.boxes_vertical as f64;
@@ -57,6 +67,8 @@ impl DrawMethod for CascadeMethod {
         let mut surface = DrawSurface::new(physical_dimensions);
         surface.raise_pen(false);
 
+        let mut rng = parameters.seed.map_or_else(|| StdRng::from_rng(&mut rand::rng()), StdRng::seed_from_u64);
+
         for i in 0..parameters.boxes_horizontal {
             triangle_pattern.push(Vec::new());
 
@@ -65,7 +77,7 @@ impl DrawMethod for CascadeMethod {
 
             // singles are not currently implemented.
             for _ in 0..total_singles {
-                if rand::rng().random::<f32>() < 0.8 {
+                if rng.random::<f32>() < 0.8 {
                     triangle_pattern[i].push(1);
                 } else {
                     triangle_pattern[i].push(1);
@@ -75,7 +87,7 @@ impl DrawMethod for CascadeMethod {
             // only do long triangles on every 3rd row
             if i % 3 == 0 {
                 for _ in 0..others {
-                    let rand_num = (rand::rng().random::<f64>() * 20.).round() as usize + 10;
+                    let rand_num = (rng.random::<f64>() * 20.).round() as usize + 10;
 
                     if rand_num >= others {
                         triangle_pattern[i].push(others);
@@ -92,7 +104,7 @@ impl DrawMethod for CascadeMethod {
             }
             
             // shuffle them to make them appear random
-            triangle_pattern[i].shuffle(&mut rand::rng());
+            triangle_pattern[i].shuffle(&mut rng);
         }
 
         // move to start position
@@ -132,7 +144,7 @@ impl DrawMethod for CascadeMethod {
         }
 
 
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -217,6 +229,7 @@ impl CascadeMethod {
 /// - `vertical_margin`: The vertical margin of the drawing, in millimetres
 /// - `boxes_horizontal`: The number of triangle columns wanted
 /// - `boxes_vertical`: The number of triangle rows wanted
+/// - `seed`: A seed for the triangle pattern, or None for a different pattern every time
 ///
 #[derive(Serialize, Deserialize)]
 pub struct CascadeParameters {
@@ -225,6 +238,9 @@ pub struct CascadeParameters {
 
     pub boxes_vertical: usize,
     pub boxes_horizontal: usize,
+
+    #[serde(default)]
+    pub seed: Option<u64>,
 }
 
 impl DrawParameters for CascadeParameters {}
@@ -6,6 +6,7 @@ use pyo3::types::PyAnyMethods;
 use pyo3::{PyRef, Python};
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 ///
 /// An empty struct to implement the "Custom" draw method on.
@@ -33,7 +34,15 @@ impl DrawMethod for CustomMethod {
     }
 
     ///
-    /// Generates instructions to perform the custom drawing method.
+    /// # Returns:
+    /// - The parameters a new custom drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        CustomParameters { plugin_path: String::new(), plugin_parameters_json: "{}".to_owned() }
+    }
+
+    ///
+    /// Generates the strokes to perform the custom drawing method.
     /// This drawing method uses a custom Python plugin to generate a drawing.
     ///
     /// # Parameters:
@@ -41,10 +50,10 @@ impl DrawMethod for CustomMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &CustomParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &CustomParameters) -> Result<Vec<Stroke>, String> {
 
         let rust_instructions: Vec<GenericInstruction> = match Python::with_gil(|py| {
             let module = match plugin::load_plugin_module(py, &parameters.plugin_path) {
@@ -112,7 +121,7 @@ impl DrawMethod for CustomMethod {
             }
         }
         
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -3,9 +3,10 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 
-use super::util::heightmap::gen_terrain;
+use super::util::heightmap::{from_file, gen_terrain};
 
 ///
 /// An empty struct to implement the "Dunes" draw method on.
@@ -32,7 +33,19 @@ impl DrawMethod for DunesMethod {
     }
 
     ///
-    /// Generates instructions to perform the dunes drawing method.
+    /// # Returns:
+    /// - The parameters a new dunes drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        DunesParameters {
+            seed: 1, layers: 60, sample_per_mm: 1, width: 170, height: 170, vertical_offset: 0,
+            base_size: 120., base_amplitude: 1., mid_size: 40., mid_amplitude: 0.4, high_size: 10., high_amplitude: 0.1,
+            heightmap_path: String::new(),
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the dunes drawing method.
     /// This drawing creates a set of lines, whose height is affected by 3 layers of perlin noise.
     /// The lines are layered to create a semi-2D effect, looking similar to sane dunes.
     ///
@@ -41,10 +54,10 @@ impl DrawMethod for DunesMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error explaining why the drawing instructions could not be generated
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &DunesParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &DunesParameters) -> Result<Vec<Stroke>, String> {
         
         let vertical_offset = (physical_dimensions.page_height() - parameters.height as f64) / 2. + parameters.vertical_offset as f64;
         let horizontal_offset = (physical_dimensions.page_width() - parameters.width as f64) / 2.;
@@ -52,7 +65,12 @@ impl DrawMethod for DunesMethod {
         let samples_width = parameters.sample_per_mm * parameters.width;
         let layer_height = parameters.height as f64 / parameters.layers as f64;
 
-        let heightmap_values = gen_terrain(parameters.seed, samples_width, parameters.layers, layer_height, parameters.base_size, parameters.base_amplitude, parameters.mid_size, parameters.mid_amplitude, parameters.high_size, parameters.high_amplitude);
+        // a heightmap file replaces the procedural terrain, if one is given
+        let heightmap_values = if parameters.heightmap_path.is_empty() {
+            gen_terrain(parameters.seed, samples_width, parameters.layers, layer_height, parameters.base_size, parameters.base_amplitude, parameters.mid_size, parameters.mid_amplitude, parameters.high_size, parameters.high_amplitude)
+        } else {
+            from_file(&parameters.heightmap_path, samples_width, parameters.layers)?
+        };
 
         let mut y_samples: Vec<Vec<f64>> = Vec::new();
 
@@ -75,7 +93,7 @@ impl DrawMethod for DunesMethod {
             }
         }
 
-        let mut surface = DrawSurface::new(physical_dimensions);
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, y_samples.iter().map(Vec::len).sum());
         surface.raise_pen(false);
 
         for layer_idx in 0..parameters.layers {
@@ -96,7 +114,7 @@ impl DrawMethod for DunesMethod {
         }
 
 
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -117,6 +135,7 @@ impl DrawMethod for DunesMethod {
 /// - `mid_amplitude`: The amplitude of the mid perlin noise
 /// - `high_size`: The size of the high perlin noise
 /// - `high_amplitude`: The amplitude of the high perlin noise
+/// - `heightmap_path`: An optional greyscale image or .asc elevation grid, used instead of perlin noise
 ///
 #[derive(Serialize, Deserialize)]
 pub struct DunesParameters {
@@ -134,6 +153,9 @@ pub struct DunesParameters {
     mid_amplitude: f64,
     high_size: f64,
     high_amplitude: f64,
+
+    #[serde(default)]
+    heightmap_path: String,
 }
 
 impl DrawParameters for DunesParameters {}
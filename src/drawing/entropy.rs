@@ -3,7 +3,10 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
-use noise::{NoiseFn, Perlin};
+use crate::drawing::stroke::Stroke;
+use crate::drawing::util::noise::{Fbm, NoiseBasis, Octave};
+use crate::drawing::util::geometry;
+use crate::drawing::util::resample;
 
 
 ///
@@ -30,6 +33,19 @@ impl DrawMethod for EntropyMethod {
         "Entropy"
     }
 
+    ///
+    /// # Returns:
+    /// - The parameters a new entropy drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        EntropyParameters {
+            start_radius: 10., cycle_distance: 3., cycle_density: 60, total_steps: 3000, swirl_factor: 1., swirl_decay: 0.5,
+            horizontal_offset: 0., vertical_offset: 0., seed: 1,
+            base_size: 80., base_strength: 4., mid_size: 20., mid_strength: 1., high_size: 5., high_strength: 0.2,
+            spacing_mm: None,
+        }
+    }
+
     ///
     /// This drawing method creates an initial spiral, which is then manipulated by 3 layers of
     /// perlin noise to create abstract patterns.
@@ -39,13 +55,10 @@ impl DrawMethod for EntropyMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &EntropyParameters) -> Result<(Vec<u8>, f64, f64), String> {
-
-        let mut surface = DrawSurface::new(physical_dimensions);
-        surface.raise_pen(false);
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &EntropyParameters) -> Result<Vec<Stroke>, String> {
 
         let center_x = physical_dimensions.page_width() / 2.;
         let center_y = physical_dimensions.page_height() / 2.;
@@ -56,45 +69,46 @@ impl DrawMethod for EntropyMethod {
         let cycle_distance = parameters.cycle_distance as f64 / 100.;
         let swirl_factor = parameters.swirl_factor / 100.;
 
-        let perlin = Perlin::new(parameters.seed);
-
-        for i in 0..parameters.total_steps {
-            let theta = i as f64 * angle_step_rad;
-            let radius = parameters.start_radius + cycle_distance * theta;
-
-            let default_x = center_x + radius * theta.cos();
-            let default_y = center_y + radius * theta.sin();
-            
-            let (dx, dy) = get_perlin_d(default_x, default_y, &perlin, parameters);
+        // every layer shares one seed, and y displacements come from an offset slice of the noise
+        let displacement_x = Fbm::from_octaves(NoiseBasis::Perlin, vec![
+            Octave { seed: parameters.seed, frequency: 1. / parameters.base_size, amplitude: parameters.base_strength },
+            Octave { seed: parameters.seed, frequency: 1. / parameters.mid_size, amplitude: parameters.mid_strength },
+            Octave { seed: parameters.seed, frequency: 1. / parameters.high_size, amplitude: parameters.high_strength },
+        ]);
+        let displacement_y = Fbm::from_octaves(NoiseBasis::Perlin, displacement_x.octaves().to_vec()).with_offset(1000.);
+
+        // segments are kept to the length of one angle step on the outermost turn, so inner turns aren't over-inked
+        let end_theta = parameters.total_steps.saturating_sub(1) as f64 * angle_step_rad;
+        let max_segment = (parameters.start_radius + cycle_distance * end_theta) * angle_step_rad;
+        let spiral = geometry::polar_path((center_x, center_y), |theta| parameters.start_radius + cycle_distance * theta, (0., end_theta), max_segment);
+
+        let mut points = Vec::with_capacity(spiral.len());
+        for (default_x, default_y) in spiral {
+            let (dx, dy) = (displacement_x.sample_2d(default_x, default_y), displacement_y.sample_2d(default_x, default_y));
             
             if swirl_center.is_none() { // first iteration
                 swirl_center = Some((default_x + dx, default_y + dy));
-                surface.sample_xy(default_x + dx + parameters.horizontal_offset, default_y + dy + parameters.vertical_offset).unwrap();
+                points.push((default_x + dx + parameters.horizontal_offset, default_y + dy + parameters.vertical_offset));
                 continue;
             } 
 
             let (sx, sy) = swirl_transform(default_x + dx, default_y + dy, swirl_center.unwrap().0, swirl_center.unwrap().1, swirl_factor, parameters.swirl_decay);
-            surface.sample_xy(sx + parameters.horizontal_offset, sy + parameters.vertical_offset).unwrap();
+            points.push((sx + parameters.horizontal_offset, sy + parameters.vertical_offset));
         }
-        
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
-    }
-}
-
-fn get_perlin_d(x: f64, y: f64, perlin: &Perlin, parameters: &EntropyParameters) -> (f64, f64) {
-    let mut dx = 0.;
-    let mut dy = 0.;
 
-    dx += perlin.get([x / parameters.base_size, y / parameters.base_size]) * parameters.base_strength;
-    dy += perlin.get([x / parameters.base_size + 1000., y / parameters.base_size + 1000.]) * parameters.base_strength;
-
-    dx += perlin.get([x / parameters.mid_size, y / parameters.mid_size]) * parameters.mid_strength;
-    dy += perlin.get([x / parameters.mid_size + 1000., y / parameters.mid_size + 1000.]) * parameters.mid_strength;
-
-    dx += perlin.get([x / parameters.high_size, y / parameters.high_size]) * parameters.high_strength;
-    dy += perlin.get([x / parameters.high_size + 1000., y / parameters.high_size + 1000.]) * parameters.high_strength;
+        // the noise and swirl stretch and squash the spiral, so its points are no longer evenly spaced
+        if let Some(spacing_mm) = parameters.spacing_mm {
+            points = resample::by_arc_length(&points, spacing_mm);
+        }
 
-    (dx, dy)
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, points.len());
+        surface.raise_pen(false);
+        for (x, y) in points {
+            surface.sample_xy(x, y).unwrap();
+        }
+        
+        Ok(surface.into_strokes())
+    }
 }
 
 fn swirl_transform(x: f64, y: f64, cx: f64, cy: f64, factor: f64, decay: f64) -> (f64, f64) {
@@ -138,6 +152,9 @@ pub struct EntropyParameters {
     pub mid_strength: f64,
     pub high_size: f64,
     pub high_strength: f64,
+
+    #[serde(default)]
+    pub spacing_mm: Option<f64>,
 }
 
 impl DrawParameters for EntropyParameters {}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+use super::util::placement::{Margins, Placement};
+use super::util::turtle::{self, LSystem};
+
+///
+/// An empty struct to implement the "Fractal" draw method on.
+///
+pub struct FractalMethod;
+
+impl DrawMethod for FractalMethod {
+    type DrawParameters = FractalParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "fractal"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Fractal"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new fractal drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        FractalParameters { curve: Curve::Dragon, depth: 10, placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() } }
+    }
+
+    ///
+    /// Generates the strokes to perform the fractal drawing method.
+    /// This drawing method draws a classic fractal curve, built by rewriting a string of turtle
+    /// commands a number of times, scaled to fit the page.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &FractalParameters) -> Result<Vec<Stroke>, String> {
+
+        let (system, turn_degrees, draw_symbols) = parameters.curve.l_system();
+        let commands = system.expand(parameters.depth)?;
+        let polylines = turtle::interpret(&commands, turn_degrees, draw_symbols)?;
+        let Some((min_x, min_y, max_x, max_y)) = turtle::bounds(&polylines) else {
+            return Err("The curve has nothing to draw".to_owned());
+        };
+
+        let placed = parameters.placement.place(physical_dimensions, max_x - min_x, max_y - min_y)?;
+        let (scale_x, scale_y) = (placed.width / (max_x - min_x), placed.height / (max_y - min_y));
+
+        let num_samples = polylines.iter().map(|polyline| polyline.len()).sum();
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, num_samples);
+        for polyline in polylines {
+            surface.raise_pen(true);
+            for (x, y) in polyline {
+                surface.sample_xy(placed.x + (x - min_x) * scale_x, placed.y + (y - min_y) * scale_y)?;
+                surface.raise_pen(false);
+            }
+        }
+
+        Ok(surface.into_strokes())
+    }
+}
+
+
+///
+/// The fractal curve to draw.
+///
+/// - `Koch`: The Koch snowflake, a triangle whose sides grow smaller triangles
+/// - `Dragon`: The Heighway dragon, a path folded in half over and over
+/// - `SierpinskiArrowhead`: A single path which traces the Sierpinski triangle
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    Koch,
+    Dragon,
+    SierpinskiArrowhead,
+}
+
+impl Curve {
+    ///
+    /// # Returns:
+    /// - The (L-system, turn angle in degrees, symbols drawn with the pen down) of the curve
+    ///
+    fn l_system(&self) -> (LSystem, f64, &'static str) {
+        let (axiom, rules, turn_degrees, draw_symbols): (&str, &[(char, &str)], f64, &str) = match self {
+            Curve::Koch => ("F--F--F", &[('F', "F+F--F+F")], 60., "F"),
+            Curve::Dragon => ("FX", &[('X', "X+YF+"), ('Y', "-FX-Y")], 90., "F"),
+            Curve::SierpinskiArrowhead => ("A", &[('A', "B-A-B"), ('B', "A+B+A")], 60., "AB"),
+        };
+
+        let rules: BTreeMap<char, String> = rules.iter().map(|(symbol, rule)| (*symbol, rule.to_string())).collect();
+        (LSystem { axiom: axiom.to_owned(), rules }, turn_degrees, draw_symbols)
+    }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `curve`: The fractal curve to draw
+/// - `depth`: The number of times the curve is rewritten, where each adds a finer level of detail
+/// - `placement`: How the curve is fitted onto the page
+///
+#[derive(Serialize, Deserialize)]
+pub struct FractalParameters {
+    curve: Curve,
+    depth: usize,
+
+    #[serde(default)]
+    placement: Placement,
+}
+
+impl DrawParameters for FractalParameters {}
@@ -0,0 +1,213 @@
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+use super::util::files;
+use super::util::placement::{Margins, Placement};
+
+///
+/// An empty struct to implement the "Handwriting" draw method on.
+///
+pub struct HandwritingMethod;
+
+impl DrawMethod for HandwritingMethod {
+    type DrawParameters = HandwritingParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "handwriting"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Handwriting"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new handwriting drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        HandwritingParameters { recording_path: String::new(), placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() }, sample_spacing: 1. }
+    }
+
+    ///
+    /// Generates the strokes to perform the handwriting drawing method.
+    /// This drawing method replays strokes recorded on a tablet or touchscreen, such as
+    /// handwriting or a signature, scaled to the page and lifting the pen between strokes.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &HandwritingParameters) -> Result<Vec<Stroke>, String> {
+
+        if parameters.recording_path.is_empty() {
+            return Err("Select a recording of strokes".to_owned());
+        }
+        if !(parameters.sample_spacing.is_finite() && parameters.sample_spacing > 0.) {
+            return Err("The sample spacing must be above 0".to_owned());
+        }
+
+        let bytes = files::read(&parameters.recording_path).map_err(|err| format!("Error loading recording. {}", err))?;
+        let recording: Recording = serde_json::from_slice(&bytes).map_err(|err| format!("Error decoding recording. {}", err))?;
+        let strokes = recording.in_order();
+
+        let points = strokes.iter().flatten();
+        let (min_x, min_y, max_x, max_y) = points.fold((f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY), |(min_x, min_y, max_x, max_y), point| {
+            (min_x.min(point.x), min_y.min(point.y), max_x.max(point.x), max_y.max(point.y))
+        });
+        if !(min_x.is_finite() && min_y.is_finite() && max_x.is_finite() && max_y.is_finite()) {
+            return Err("The recording has no points to draw".to_owned());
+        }
+
+        // a single stroke along one axis has no size across the other, so is given a little
+        let (width, height) = ((max_x - min_x).max(f64::EPSILON), (max_y - min_y).max(f64::EPSILON));
+        let placed = parameters.placement.place(physical_dimensions, width, height)?;
+        let (scale_x, scale_y) = (placed.width / width, placed.height / height);
+
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, strokes.len() * 2);
+        for stroke in strokes {
+            let scaled: Vec<(f64, f64)> = stroke.iter().map(|point| (placed.x + (point.x - min_x) * scale_x, placed.y + (point.y - min_y) * scale_y)).collect();
+            let resampled = resample(&scaled, parameters.sample_spacing);
+
+            // a tap is a stroke of one point, which is drawn as a dot
+            surface.raise_pen(true);
+            surface.sample_xy(resampled[0].0, resampled[0].1)?;
+            surface.raise_pen(false);
+            for &(x, y) in &resampled[1..] {
+                surface.sample_xy(x, y)?;
+            }
+            if resampled.len() == 1 {
+                surface.sample_xy(resampled[0].0, resampled[0].1)?;
+            }
+        }
+
+        Ok(surface.into_strokes())
+    }
+}
+
+///
+/// Resamples a polyline to evenly spaced points along its length. Tablets report points at a
+/// fixed rate, so slow parts of a stroke are crowded with points and fast parts are sparse.
+///
+/// # Parameters:
+/// - `points`: The points of the polyline, of which there is at least one
+/// - `spacing`: The distance between the resampled points, in millimetres
+///
+/// # Returns:
+/// - The resampled points, which always keep the first and last points
+///
+fn resample(points: &[(f64, f64)], spacing: f64) -> Vec<(f64, f64)> {
+    let mut resampled = vec![points[0]];
+    let mut travelled = 0.;
+
+    for segment in points.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        let length = (end.0 - start.0).hypot(end.1 - start.1);
+
+        // the distance along this segment of the next resampled point
+        let mut along = spacing - travelled;
+        while along <= length {
+            let t = along / length;
+            resampled.push((start.0 + (end.0 - start.0) * t, start.1 + (end.1 - start.1) * t));
+            along += spacing;
+        }
+        travelled = length - (along - spacing);
+    }
+
+    if points.len() > 1 && resampled.last() != points.last() {
+        resampled.push(points[points.len() - 1]);
+    }
+    resampled
+}
+
+///
+/// A recording of strokes, as exported from a tablet or touchscreen app.
+///
+/// ```json
+/// { "strokes": [[{ "x": 10, "y": 20, "t": 0 }, { "x": 12, "y": 24, "t": 16 }], ...] }
+/// ```
+///
+/// # Fields:
+/// - `strokes`: Each stroke the pen drew without lifting, as its points
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub strokes: Vec<Vec<RecordedPoint>>,
+}
+
+///
+/// A point of a recorded stroke.
+///
+/// # Fields:
+/// - `x` and `y`: The position of the point, in any units, with y pointing down
+/// - `t`: The time the point was recorded, in any units, if the recording has timings
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RecordedPoint {
+    pub x: f64,
+    pub y: f64,
+
+    #[serde(default)]
+    pub t: Option<f64>,
+}
+
+impl Recording {
+    ///
+    /// Orders the strokes as they were recorded. Apps don't always export strokes or points in
+    /// the order they were drawn, so where points have timings, they are sorted by them.
+    ///
+    /// # Returns:
+    /// - The non-empty strokes, in the order they were drawn
+    ///
+    pub fn in_order(&self) -> Vec<Vec<RecordedPoint>> {
+        let mut strokes: Vec<Vec<RecordedPoint>> = self.strokes.iter().filter(|stroke| !stroke.is_empty()).cloned().collect();
+        let timed = |point: &RecordedPoint| point.t.is_some_and(f64::is_finite);
+        let time = |point: &RecordedPoint| point.t.unwrap_or_default();
+
+        // recordings without timings, or with only some, are left in the order they are listed
+        for stroke in strokes.iter_mut() {
+            if stroke.iter().all(timed) {
+                stroke.sort_by(|a, b| time(a).total_cmp(&time(b)));
+            }
+        }
+        if strokes.iter().all(|stroke| timed(&stroke[0])) {
+            strokes.sort_by(|a, b| time(&a[0]).total_cmp(&time(&b[0])));
+        }
+        strokes
+    }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `recording_path`: The path of the recording, a JSON file of timed points per stroke
+/// - `placement`: How the recording is fitted onto the page
+/// - `sample_spacing`: The distance between the points each stroke is resampled to, in millimetres
+///
+#[derive(Serialize, Deserialize)]
+pub struct HandwritingParameters {
+    recording_path: String,
+
+    #[serde(default)]
+    placement: Placement,
+
+    sample_spacing: f64,
+}
+
+impl DrawParameters for HandwritingParameters {}
@@ -3,9 +3,10 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 
-use super::util::heightmap::gen_terrain;
+use super::util::heightmap::{from_file, gen_terrain};
 
 ///
 /// An empty struct to implement the "Islands" draw method on.
@@ -32,7 +33,19 @@ impl DrawMethod for IslandsMethod {
     }
 
     ///
-    /// Generates instructions to perform the islands drawing method.
+    /// # Returns:
+    /// - The parameters a new islands drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        IslandsParameters {
+            seed: 1, layers: 60, sample_per_mm: 1, width: 170, height: 170, vertical_offset: 0, ocean_height: 60,
+            base_size: 120., base_amplitude: 1., mid_size: 40., mid_amplitude: 0.4, high_size: 10., high_amplitude: 0.1,
+            heightmap_path: String::new(),
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the islands drawing method.
     /// This drawing creates a set of lines, whose height is affected by 3 layers of perlin noise.
     /// The lines are layered to create a semi-2D effect, looking similar to sane dunes.
     /// Finally, the height of the heights are given a minimum of "ocean_height" to make the dunes
@@ -43,10 +56,10 @@ impl DrawMethod for IslandsMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error explaining why the drawing instructions could not be generated
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &IslandsParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &IslandsParameters) -> Result<Vec<Stroke>, String> {
         
         let vertical_offset = (physical_dimensions.page_height() - parameters.height as f64) / 2. + parameters.vertical_offset as f64;
         let horizontal_offset = (physical_dimensions.page_width() - parameters.width as f64) / 2.;
@@ -54,7 +67,12 @@ impl DrawMethod for IslandsMethod {
         let samples_width = parameters.sample_per_mm * parameters.width;
         let layer_height = parameters.height as f64 / parameters.layers as f64;
 
-        let heightmap_values = gen_terrain(parameters.seed, samples_width, parameters.layers, layer_height, parameters.base_size, parameters.base_amplitude, parameters.mid_size, parameters.mid_amplitude, parameters.high_size, parameters.high_amplitude);
+        // a heightmap file replaces the procedural terrain, if one is given
+        let heightmap_values = if parameters.heightmap_path.is_empty() {
+            gen_terrain(parameters.seed, samples_width, parameters.layers, layer_height, parameters.base_size, parameters.base_amplitude, parameters.mid_size, parameters.mid_amplitude, parameters.high_size, parameters.high_amplitude)
+        } else {
+            from_file(&parameters.heightmap_path, samples_width, parameters.layers)?
+        };
 
         let mut y_samples: Vec<Vec<f64>> = Vec::new();
 
@@ -77,7 +95,7 @@ impl DrawMethod for IslandsMethod {
             }
         }
 
-        let mut surface = DrawSurface::new(physical_dimensions);
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, y_samples.iter().map(Vec::len).sum());
         surface.raise_pen(false);
 
         for layer_idx in 0..parameters.layers {
@@ -97,7 +115,7 @@ impl DrawMethod for IslandsMethod {
             }
         }
 
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -119,6 +137,7 @@ impl DrawMethod for IslandsMethod {
 /// - `mid_amplitude`: The amplitude of the mid perlin noise
 /// - `high_size`: The size of the high perlin noise
 /// - `high_amplitude`: The amplitude of the high perlin noise
+/// - `heightmap_path`: An optional greyscale image or .asc elevation grid, used instead of perlin noise
 ///
 #[derive(Serialize, Deserialize)]
 pub struct IslandsParameters {
@@ -137,6 +156,9 @@ pub struct IslandsParameters {
     mid_amplitude: f64,
     high_size: f64,
     high_amplitude: f64,
+
+    #[serde(default)]
+    heightmap_path: String,
 }
 
 impl DrawParameters for IslandsParameters {}
@@ -3,6 +3,7 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 ///
 /// An empty struct to implement the "Lines" draw method on.
@@ -29,7 +30,15 @@ impl DrawMethod for LinesMethod {
     }
 
     ///
-    /// Generates instructions to perform the lines drawing method.
+    /// # Returns:
+    /// - The parameters a new lines drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        LinesParameters { num_lines: 12, horizontal_margin: 20 }
+    }
+
+    ///
+    /// Generates the strokes to perform the lines drawing method.
     /// This drawing method creates a set of lines which move down the page. It is used for
     /// testing, only.
     ///
@@ -38,12 +47,13 @@ impl DrawMethod for LinesMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &LinesParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &LinesParameters) -> Result<Vec<Stroke>, String> {
         
-        let mut surface = DrawSurface::new(physical_dimensions);
+        // 101 samples along each line, and 10 down to the next
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, parameters.num_lines as usize * 111);
         surface.raise_pen(false);
         
         for i in 0..parameters.num_lines {
@@ -61,7 +71,7 @@ impl DrawMethod for LinesMethod {
                 }
             }
 
-            let (current_x, current_y) = surface.get_xy();
+            let (current_x, current_y) = surface.get_xy().map_err(|err| err.to_string())?;
             for y in 0..10 {
                 if let Err(err_str) = surface.sample_xy(current_x, current_y + y as f64) {
                     return Err(err_str);
@@ -69,7 +79,7 @@ impl DrawMethod for LinesMethod {
             }
         }
 
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -0,0 +1,361 @@
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::drawing::stroke::{self, Stroke};
+
+use super::util::files;
+use super::util::geometry;
+use super::util::placement::{Margins, Placement, Rect};
+
+///
+/// The values of the OSM `highway` tag which are drawn thin, as footpaths rather than roads.
+///
+const PATH_HIGHWAYS: [&str; 7] = ["footway", "path", "cycleway", "steps", "pedestrian", "track", "bridleway"];
+
+///
+/// An empty struct to implement the "Map" draw method on.
+///
+pub struct MapMethod;
+
+impl DrawMethod for MapMethod {
+    type DrawParameters = MapParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "map"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Map"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new map drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        MapParameters {
+            geojson_path: String::new(), bounding_box: None, placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            road_passes: 2, pen_width: 0.5, water_spacing: 2., hide_other: false,
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the map drawing method.
+    /// This drawing method draws a street map from a GeoJSON file, such as an OpenStreetMap
+    /// extract. Roads are drawn thick with several passes, footpaths thin, and water is hatched.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &MapParameters) -> Result<Vec<Stroke>, String> {
+
+        if parameters.geojson_path.is_empty() {
+            return Err("Select a GeoJSON file".to_owned());
+        }
+        if parameters.road_passes == 0 || !(parameters.pen_width.is_finite() && parameters.pen_width > 0.) {
+            return Err("Roads need at least one pass, and the pen width must be above 0".to_owned());
+        }
+        if !(parameters.water_spacing.is_finite() && parameters.water_spacing >= 0.) {
+            return Err("The water hatching spacing can not be negative".to_owned());
+        }
+
+        let bytes = files::read(&parameters.geojson_path).map_err(|err| format!("Error loading map. {}", err))?;
+        let geojson: Value = serde_json::from_slice(&bytes).map_err(|err| format!("Error decoding map. {}", err))?;
+        let mut features = vec![];
+        read_features(&geojson, &mut features)?;
+
+        let [west, south, east, north] = match parameters.bounding_box {
+            Some(bounding_box) => bounding_box,
+            None => data_bounds(&features).ok_or("The map has no lines or areas to draw")?,
+        };
+        if !(west < east && south < north) {
+            return Err("The bounding box must be given as [west, south, east, north], with some width and height".to_owned());
+        }
+
+        // an equirectangular projection, with longitude narrowed to its length at the middle latitude,
+        // which is close enough to true shape at the scale of a city
+        let x_scale = ((south + north) / 2.).to_radians().cos();
+        let placed = parameters.placement.place(physical_dimensions, (east - west) * x_scale, north - south)?;
+        let visible = placed.intersect(&parameters.placement.area(physical_dimensions)?);
+        let project = |positions: &[(f64, f64)]| -> Vec<(f64, f64)> {
+            positions.iter().map(|&(lon, lat)| {
+                (placed.x + (lon - west) / (east - west) * placed.width, placed.y + (north - lat) / (north - south) * placed.height)
+            }).collect()
+        };
+
+        let mut polylines = vec![];
+        for feature in features {
+            let lines: Vec<Vec<(f64, f64)>> = feature.lines.iter().chain(feature.polygons.iter().flatten()).map(|line| project(line)).collect();
+
+            match feature.class {
+                WayClass::Road => {
+                    // the passes are spread either side of the centre line, a pen width apart
+                    for pass in 0..parameters.road_passes {
+                        let offset = (pass as f64 - (parameters.road_passes - 1) as f64 / 2.) * parameters.pen_width;
+                        polylines.extend(lines.iter().map(|line| geometry::offset_polyline(line, offset)));
+                    }
+                },
+                WayClass::Path => polylines.extend(lines),
+                WayClass::Other => if !parameters.hide_other {
+                    polylines.extend(lines);
+                },
+                WayClass::Water => {
+                    polylines.extend(lines);
+                    if parameters.water_spacing > 0. {
+                        for polygon in &feature.polygons {
+                            let rings: Vec<Vec<(f64, f64)>> = polygon.iter().map(|ring| project(ring)).collect();
+                            polylines.extend(hatch(&rings, &visible, parameters.water_spacing));
+                        }
+                    }
+                },
+            }
+        }
+
+        let strokes = polylines.iter()
+            .flat_map(|polyline| visible.clip_polyline(polyline))
+            .map(|points| Stroke { points, pen_up: false, layer: 0, label: None })
+            .collect();
+        Ok(stroke::order_strokes(strokes, (visible.x, visible.y)))
+    }
+}
+
+///
+/// How a feature of the map is drawn, from its OSM tags.
+///
+/// - `Road`: A road, drawn thick
+/// - `Path`: A footpath, cycleway or track, drawn thin
+/// - `Water`: A lake, river or reservoir, whose areas are hatched
+/// - `Other`: Anything else, such as buildings and boundaries, drawn thin
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WayClass {
+    Road,
+    Path,
+    Water,
+    Other,
+}
+
+impl WayClass {
+    ///
+    /// # Parameters:
+    /// - `properties`: The properties of a GeoJSON feature, which hold the OSM tags
+    ///
+    /// # Returns:
+    /// - How the feature is drawn
+    ///
+    fn of(properties: &Value) -> WayClass {
+        let tag = |key: &str| properties.get(key).and_then(Value::as_str);
+
+        if tag("natural") == Some("water") || tag("waterway").is_some() || tag("water").is_some() || matches!(tag("landuse"), Some("reservoir" | "basin")) {
+            WayClass::Water
+        } else if let Some(highway) = tag("highway") {
+            if PATH_HIGHWAYS.contains(&highway) { WayClass::Path } else { WayClass::Road }
+        } else {
+            WayClass::Other
+        }
+    }
+}
+
+///
+/// The lines and areas of a GeoJSON feature, in (longitude, latitude).
+///
+/// # Fields:
+/// - `class`: How the feature is drawn
+/// - `lines`: The line strings of the feature
+/// - `polygons`: The polygons of the feature, each an outer ring followed by any holes
+///
+struct Feature {
+    class: WayClass,
+    lines: Vec<Vec<(f64, f64)>>,
+    polygons: Vec<Vec<Vec<(f64, f64)>>>,
+}
+
+///
+/// Reads every feature from a GeoJSON object, which may be a feature collection, a single
+/// feature or a bare geometry. Points are skipped, since they have nothing to draw.
+///
+/// # Parameters:
+/// - `geojson`: The GeoJSON object
+/// - `features`: The features read so far, which are added to
+///
+/// # Returns:
+/// - Void if the object was read
+/// - An error explaining which part of the object is not valid GeoJSON
+///
+fn read_features(geojson: &Value, features: &mut Vec<Feature>) -> Result<(), String> {
+    match geojson.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => {
+            let collection = geojson.get("features").and_then(Value::as_array).ok_or("A feature collection has no features")?;
+            for feature in collection {
+                read_features(feature, features)?;
+            }
+        },
+        Some("Feature") => {
+            let mut feature = Feature { class: WayClass::of(&geojson["properties"]), lines: vec![], polygons: vec![] };
+            if !geojson["geometry"].is_null() {
+                read_geometry(&geojson["geometry"], &mut feature)?;
+            }
+            features.push(feature);
+        },
+        _ => {
+            let mut feature = Feature { class: WayClass::Other, lines: vec![], polygons: vec![] };
+            read_geometry(geojson, &mut feature)?;
+            features.push(feature);
+        },
+    }
+    Ok(())
+}
+
+///
+/// Reads the lines and areas of a GeoJSON geometry into a feature.
+///
+/// # Parameters:
+/// - `geometry`: The GeoJSON geometry
+/// - `feature`: The feature the geometry belongs to
+///
+/// # Returns:
+/// - Void if the geometry was read
+/// - An error explaining why the geometry is not valid GeoJSON
+///
+fn read_geometry(geometry: &Value, feature: &mut Feature) -> Result<(), String> {
+    let coordinates = &geometry["coordinates"];
+    let rings = |value: &Value| array(value)?.iter().map(positions).collect::<Result<Vec<_>, String>>();
+
+    match geometry.get("type").and_then(Value::as_str) {
+        Some("LineString") => feature.lines.push(positions(coordinates)?),
+        Some("MultiLineString") => feature.lines.extend(rings(coordinates)?),
+        Some("Polygon") => feature.polygons.push(rings(coordinates)?),
+        Some("MultiPolygon") => {
+            for polygon in array(coordinates)? {
+                feature.polygons.push(rings(polygon)?);
+            }
+        },
+        Some("GeometryCollection") => {
+            for geometry in array(&geometry["geometries"])? {
+                read_geometry(geometry, feature)?;
+            }
+        },
+        Some("Point" | "MultiPoint") => {},
+        other => return Err(format!("Unknown GeoJSON geometry type {:?}", other.unwrap_or("(none)"))),
+    }
+    Ok(())
+}
+
+///
+/// # Returns:
+/// - The elements of a GeoJSON array
+/// - An error if the value isn't an array
+///
+fn array(value: &Value) -> Result<&Vec<Value>, String> {
+    value.as_array().ok_or_else(|| format!("Expected an array of coordinates, found {}", value))
+}
+
+///
+/// # Returns:
+/// - The (longitude, latitude) of every position in a GeoJSON coordinate array
+/// - An error if any position isn't an array of at least two numbers
+///
+fn positions(value: &Value) -> Result<Vec<(f64, f64)>, String> {
+    array(value)?.iter().map(|position| match position.as_array().map(|position| (position.first().and_then(Value::as_f64), position.get(1).and_then(Value::as_f64))) {
+        Some((Some(lon), Some(lat))) => Ok((lon, lat)),
+        _ => Err(format!("Expected a [longitude, latitude] position, found {}", position)),
+    }).collect()
+}
+
+///
+/// # Returns:
+/// - The [west, south, east, north] bounds of every line and area of the features, or None if there are none
+///
+fn data_bounds(features: &[Feature]) -> Option<[f64; 4]> {
+    features.iter()
+        .flat_map(|feature| feature.lines.iter().chain(feature.polygons.iter().flatten()))
+        .flatten()
+        .fold(None, |bounds, &(lon, lat)| match bounds {
+            None => Some([lon, lat, lon, lat]),
+            Some([west, south, east, north]) => Some([west.min(lon), south.min(lat), east.max(lon), north.max(lat)]),
+        })
+}
+
+///
+/// Hatches a polygon with horizontal lines, where the lines alternate direction so the pen
+/// travels less between them.
+///
+/// # Parameters:
+/// - `rings`: The outer ring of the polygon followed by any holes, in millimetres on the page
+/// - `visible`: The visible part of the page, which the hatching doesn't go beyond
+/// - `spacing`: The distance between the lines, in millimetres
+///
+/// # Returns:
+/// - The hatching lines
+///
+fn hatch(rings: &[Vec<(f64, f64)>], visible: &Rect, spacing: f64) -> Vec<Vec<(f64, f64)>> {
+    let (top, bottom) = rings.iter().flatten().fold((f64::INFINITY, f64::NEG_INFINITY), |(top, bottom), point| (top.min(point.1), bottom.max(point.1)));
+    let (top, bottom) = (top.max(visible.y), bottom.min(visible.y + visible.height));
+
+    let mut lines = vec![];
+    let mut y = top + spacing / 2.;
+    while y < bottom {
+        // the crossings of every ring pair up into the spans inside the polygon, holes included
+        let mut crossings: Vec<f64> = rings.iter()
+            .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)).take(ring.len()))
+            .filter(|(a, b)| (a.1 <= y) != (b.1 <= y))
+            .map(|(a, b)| a.0 + (y - a.1) / (b.1 - a.1) * (b.0 - a.0))
+            .collect();
+        crossings.sort_by(f64::total_cmp);
+
+        for span in crossings.chunks_exact(2) {
+            let line = if lines.len() % 2 == 0 { vec![(span[0], y), (span[1], y)] } else { vec![(span[1], y), (span[0], y)] };
+            lines.push(line);
+        }
+        y += spacing;
+    }
+    lines
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `geojson_path`: The path of the GeoJSON file, such as an OpenStreetMap extract
+/// - `bounding_box`: The [west, south, east, north] longitudes and latitudes of the part of the
+///   map to draw, or None to draw all of it
+/// - `placement`: How the map is fitted onto the page
+/// - `road_passes`: The number of lines each road is drawn with, side by side
+/// - `pen_width`: The distance between the lines of a road, in millimetres
+/// - `water_spacing`: The distance between the lines hatching water, in millimetres, or 0 to
+///   only draw its outline
+/// - `hide_other`: Whether to leave out features which aren't roads, paths or water, such as buildings
+///
+#[derive(Serialize, Deserialize)]
+pub struct MapParameters {
+    geojson_path: String,
+
+    #[serde(default)]
+    bounding_box: Option<[f64; 4]>,
+    #[serde(default)]
+    placement: Placement,
+
+    road_passes: usize,
+    pen_width: f64,
+    water_spacing: f64,
+
+    #[serde(default)]
+    hide_other: bool,
+}
+
+impl DrawParameters for MapParameters {}
@@ -0,0 +1,238 @@
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::registry;
+use crate::drawing::shades::ShadesMethod;
+use crate::drawing::stroke::Stroke;
+
+#[cfg(feature = "image")]
+use super::util::files;
+use super::util::geometry;
+use super::util::placement::{Margins, Placement};
+#[cfg(feature = "image")]
+use super::util::placement::Rect;
+
+///
+/// An empty struct to implement the "Masked" draw method on.
+///
+pub struct MaskedMethod;
+
+impl DrawMethod for MaskedMethod {
+    type DrawParameters = MaskedParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "masked"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Masked"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new masked drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        MaskedParameters {
+            method_id: ShadesMethod.get_id().to_owned(),
+            method_parameters_json: serde_json::to_string(&ShadesMethod.default_parameters()).unwrap_or_default(),
+            mask: Mask::Polygon { rings: vec![vec![(0., 0.), (100., 0.), (100., 100.), (0., 100.)]] },
+            placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            invert: false,
+            offset: 0.,
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the masked drawing method.
+    /// This drawing method generates the drawing of another method, then only keeps the parts of
+    /// it inside a mask, such as a flow field filling a silhouette.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &MaskedParameters) -> Result<Vec<Stroke>, String> {
+
+        let region = Region::new(parameters, physical_dimensions)?;
+        let strokes = registry::gen_strokes(&parameters.method_id, physical_dimensions, &parameters.method_parameters_json)?;
+
+        // pen up strokes are dropped, as the pen is moved between the clipped strokes when compiling
+        let mut clipped = vec![];
+        for stroke in strokes.into_iter().filter(|stroke| !stroke.pen_up) {
+            for points in region.clip(&stroke.points, !parameters.invert) {
+                clipped.push(Stroke { points, pen_up: false, layer: stroke.layer, label: stroke.label.clone() });
+            }
+        }
+
+        Ok(clipped)
+    }
+}
+
+///
+/// The shape the drawing is masked to.
+///
+/// - `Polygon`: A polygon, such as a traced silhouette
+///     Parameters:
+///     - `rings`: The outline of the polygon followed by any holes, in any units with y pointing down
+/// - `Image`: The dark parts of an image
+///     Parameters:
+///     - `image_path`: The path of the image
+///     - `threshold`: The brightness, from 0 to 255, below which a pixel is part of the mask
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Mask {
+    Polygon { rings: Vec<Vec<(f64, f64)>> },
+    #[cfg(feature = "image")]
+    Image { image_path: String, threshold: u8 },
+}
+
+///
+/// A mask placed on the page.
+///
+enum Region {
+    Polygon(Vec<Vec<(f64, f64)>>),
+    #[cfg(feature = "image")]
+    Image { image: image::GrayImage, threshold: u8, placed: Rect },
+}
+
+impl Region {
+    ///
+    /// # Parameters:
+    /// - `parameters`: The parameters of the drawing, for the mask and its placement
+    /// - `physical_dimensions`: The physical dimensions, for placing the mask
+    ///
+    /// # Returns:
+    /// - The mask, placed on the page
+    /// - An error explaining why the mask could not be opened or placed
+    ///
+    fn new(parameters: &MaskedParameters, physical_dimensions: &PhysicalDimensions) -> Result<Region, String> {
+        match &parameters.mask {
+            Mask::Polygon { rings } => {
+                if rings.first().is_none_or(|outline| outline.len() < 3) {
+                    return Err("The mask polygon needs at least 3 points".to_owned());
+                }
+
+                let (min_x, min_y, max_x, max_y) = rings.iter().flatten().fold((f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY), |(min_x, min_y, max_x, max_y), point| {
+                    (min_x.min(point.0), min_y.min(point.1), max_x.max(point.0), max_y.max(point.1))
+                });
+                let placed = parameters.placement.place(physical_dimensions, max_x - min_x, max_y - min_y)?;
+                let (scale_x, scale_y) = (placed.width / (max_x - min_x), placed.height / (max_y - min_y));
+                let (page_width, page_height) = (*physical_dimensions.page_width(), *physical_dimensions.page_height());
+
+                // growing the outline shrinks the holes, and a grown mask is kept on the page
+                let rings = rings.iter().enumerate().map(|(index, ring)| {
+                    let ring: Vec<(f64, f64)> = ring.iter().map(|point| (placed.x + (point.0 - min_x) * scale_x, placed.y + (point.1 - min_y) * scale_y)).collect();
+                    let offset = if index == 0 { parameters.offset } else { -parameters.offset };
+                    geometry::clip_to_rectangle(&geometry::offset_polygon(&ring, offset), 0., 0., page_width, page_height)
+                }).collect::<Vec<_>>();
+
+                if rings[0].len() < 3 {
+                    return Err("The mask polygon is off the page".to_owned());
+                }
+                Ok(Region::Polygon(rings.into_iter().filter(|ring| ring.len() >= 3).collect()))
+            },
+            #[cfg(feature = "image")]
+            Mask::Image { image_path, threshold } => {
+                let image = match files::read(image_path).map(|bytes| image::load_from_memory(&bytes)) {
+                    Ok(Ok(img)) => img.into_luma8(),
+                    Ok(Err(err)) => return Err(format!("Error decoding image. {}", err)),
+                    Err(err) => return Err(format!("Error loading image. {}", err)),
+                };
+                let placed = parameters.placement.place(physical_dimensions, image.width() as f64, image.height() as f64)?;
+                Ok(Region::Image { image, threshold: *threshold, placed })
+            },
+        }
+    }
+
+    ///
+    /// Clips a polyline to the mask.
+    ///
+    /// # Parameters:
+    /// - `points`: The points of the polyline, in millimetres on the page
+    /// - `keep_inside`: Whether to keep the parts inside the mask, or the parts outside it
+    ///
+    /// # Returns:
+    /// - The kept parts of the polyline, in order
+    ///
+    fn clip(&self, points: &[(f64, f64)], keep_inside: bool) -> Vec<Vec<(f64, f64)>> {
+        match self {
+            Region::Polygon(rings) => geometry::clip_polyline_to_polygon(points, rings, keep_inside),
+            #[cfg(feature = "image")]
+            Region::Image { image, threshold, placed } => {
+                let contains = |(x, y): (f64, f64)| {
+                    if !placed.contains(x, y) {
+                        return false;
+                    }
+                    let pixel_x = (((x - placed.x) / placed.width * image.width() as f64) as u32).min(image.width() - 1);
+                    let pixel_y = (((y - placed.y) / placed.height * image.height() as f64) as u32).min(image.height() - 1);
+                    image.get_pixel(pixel_x, pixel_y).0[0] < *threshold
+                };
+
+                // segments are walked in steps of half a pixel, and each edge found is narrowed down by bisection
+                let step = (placed.width / image.width() as f64).min(placed.height / image.height() as f64) / 2.;
+                let crossings = |start: (f64, f64), end: (f64, f64)| {
+                    let at = |t: f64| (start.0 + (end.0 - start.0) * t, start.1 + (end.1 - start.1) * t);
+                    let steps = ((end.0 - start.0).hypot(end.1 - start.1) / step).ceil().max(1.) as usize;
+
+                    let mut ts = vec![];
+                    for i in 0..steps {
+                        let (mut low, mut high) = (i as f64 / steps as f64, (i + 1) as f64 / steps as f64);
+                        let inside = contains(at(low));
+                        if inside == contains(at(high)) {
+                            continue;
+                        }
+                        for _ in 0..20 {
+                            let middle = (low + high) / 2.;
+                            if contains(at(middle)) == inside { low = middle; } else { high = middle; }
+                        }
+                        ts.push(high);
+                    }
+                    ts
+                };
+                geometry::split_polyline(points, crossings, |point| contains(point) == keep_inside)
+            },
+        }
+    }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `method_id`: The backend ID of the drawing method to mask
+/// - `method_parameters_json`: The parameters of the drawing method to mask, as a JSON object
+/// - `mask`: The shape the drawing is masked to
+/// - `placement`: How the mask is fitted onto the page
+/// - `invert`: Whether to keep the parts of the drawing outside the mask, rather than inside it
+/// - `offset`: How far to grow a polygon mask, or shrink it if negative, in millimetres on the page.
+///   A mask shrunk by more than half its width turns inside out
+///
+#[derive(Serialize, Deserialize)]
+pub struct MaskedParameters {
+    method_id: String,
+    method_parameters_json: String,
+
+    mask: Mask,
+    #[serde(default)]
+    placement: Placement,
+    #[serde(default)]
+    invert: bool,
+    #[serde(default)]
+    offset: f64,
+}
+
+impl DrawParameters for MaskedParameters {}
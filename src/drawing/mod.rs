@@ -2,43 +2,93 @@
 //! Drawing method trait, helpers and implementations
 //!
 
-use byteorder::{BigEndian, ByteOrder};
 use crate::hardware::PhysicalDimensions;
+use crate::hardware::error::{HardwareError, KinematicsError};
 use serde::{Serialize, Deserialize};
 use crate::preview::belts::Belts;
-use crate::hardware::math::*;
+use crate::instruction::InstructionSet;
+use crate::instruction::codec::{Instruction, InstructionWriter};
+use crate::instruction::error::InstructionError;
+use stroke::Stroke;
+use util::placement::Rect;
 
 pub mod util;
 
 pub mod lines;
 pub mod cascade;
+#[cfg(feature = "image")]
 pub mod scribble;
+#[cfg(feature = "image")]
 pub mod bubbles;
 pub mod islands;
 pub mod dunes;
+#[cfg(feature = "image")]
 pub mod waves;
 pub mod entropy;
 pub mod shades;
+#[cfg(feature = "audio")]
 pub mod vinyl;
+#[cfg(feature = "audio")]
+pub mod record;
 pub mod atom;
+pub mod calibration;
+pub mod superformula;
+pub mod fractal;
+pub mod pattern;
+pub mod stars;
+pub mod map;
+pub mod handwriting;
+pub mod masked;
+pub mod registration;
+pub mod text;
 
+#[cfg(feature = "plugin")]
 pub mod custom;
 
+pub mod registry;
+pub mod stroke;
+#[cfg(feature = "async")]
+pub mod background;
+
 ///
 /// The trait for all drawing methods to implement.
 ///
 /// # Functions:
 /// - `get_id`: Should return the unique ID of a drawing method
 /// - `get_formatted_name`: Should return the formatted name of a drawing method
-/// - `gen_instructions`: Should return the drawing instruction bytes as a vector and pen start position, or an error. Takes the page parameters.
+/// - `default_parameters`: Should return parameters which draw a typical drawing, for frontends to start from
+/// - `gen_strokes`: Should return the strokes of the drawing in the order they are drawn, or an error. Takes the page parameters.
+/// - `gen_instructions`: Returns the drawing instruction bytes as a vector and pen start position, or an error. Compiles the strokes by default.
 ///
 pub trait DrawMethod {
     type DrawParameters;
 
     fn get_id(&self) -> &'static str;
     fn get_formatted_name(&self) -> &'static str;
+    fn default_parameters(&self) -> Self::DrawParameters;
+
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, params: &Self::DrawParameters) -> Result<Vec<Stroke>, String>;
 
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, params: &Self::DrawParameters) -> Result<(Vec<u8>, f64, f64), String>;
+    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, params: &Self::DrawParameters) -> Result<(Vec<u8>, f64, f64), String> {
+        stroke::compile(&self.gen_strokes(physical_dimensions, params)?, physical_dimensions)
+    }
+}
+
+///
+/// What a `DrawSurface` produces as it is drawn on.
+///
+/// - `Strokes`: The strokes drawn so far, as drawing methods produce
+///     Parameters:
+///     - `strokes`: The strokes drawn so far
+///     - `estimated_samples`: Roughly how many times `sample_xy` will be called, reserved for the first stroke
+/// - `Instructions`: The instructions drawn so far, when compiling strokes
+///     Parameters:
+///     - `writer`: The instructions drawn so far
+///     - `num_instructions`: The number of instructions written, including dwells
+///
+enum SurfaceOutput {
+    Strokes { strokes: Vec<Stroke>, estimated_samples: usize },
+    Instructions { writer: InstructionWriter, num_instructions: usize },
 }
 
 /// 
@@ -47,29 +97,191 @@ pub trait DrawMethod {
 ///
 pub trait DrawParameters: Serialize + for<'d> Deserialize<'d> {}
 
+///
+/// The longest segment of the move to the start of a drawing, in millimetres.
+///
+const START_SEGMENT_MM: f64 = 5.;
+
+///
+/// The state of a `DrawSurface` at a point in the drawing, to roll back to.
+///
+/// # Fields:
+/// - `output_len`: The number of strokes and points of the last stroke, or instruction bytes and instructions
+/// - `belt_lengths`: The left and right belt lengths
+/// - `first_sample`: The initial position of the pen, if it had been sampled
+/// - `pen_up`: The pen state
+/// - `swap_pen_state`: Whether a pen change was pending
+/// - `layer`: The layer strokes were drawn on
+/// - `label`: The label strokes were drawn with
+/// - `last_sample`: The last position sampled
+/// - `extent`: The bounds of everything drawn with the pen down
+/// - `num_samples`: The number of samples taken
+/// - `off_page_len`: The number of samples recorded off the page
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    output_len: (usize, usize),
+    belt_lengths: (f64, f64),
+    first_sample: (Option<f64>, Option<f64>),
+    pen_up: bool,
+    swap_pen_state: bool,
+    layer: usize,
+    label: Option<String>,
+    last_sample: (f64, f64),
+    extent: Option<(f64, f64, f64, f64)>,
+    num_samples: usize,
+    off_page_len: usize,
+}
+
+///
+/// A sample a `DrawSurface` checking the page recorded outside of it.
+///
+/// # Fields:
+/// - `x` and `y`: The sample, relative to the top left of the page
+/// - `sample_num`: How many samples were taken before it
+/// - `context`: What the drawing method said it was drawing, if anything
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct OffPageSample {
+    pub x: f64,
+    pub y: f64,
+    pub sample_num: usize,
+    pub context: Option<String>,
+}
+
+///
+/// The samples off the page of one part of a drawing, as the drawing method labelled it.
+///
+/// # Fields:
+/// - `context`: What the drawing method said it was drawing, if anything
+/// - `num_samples`: The number of samples off the page
+/// - `first`: The first of them
+/// - `bounds`: The (min_x, min_y, max_x, max_y) of the finite samples, or None if every sample was NaN or infinite
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct OffPageSummary {
+    pub context: Option<String>,
+    pub num_samples: usize,
+    pub first: OffPageSample,
+    pub bounds: Option<(f64, f64, f64, f64)>,
+}
+
+///
+/// Every sample a `DrawSurface` checking the page recorded outside of it, grouped by context in the
+/// order they were first drawn.
+///
+/// # Fields:
+/// - `page_size`: The (width, height) of the page the samples were checked against, in millimetres
+/// - `parts`: The samples off the page of each context
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PageReport {
+    pub page_size: (f64, f64),
+    pub parts: Vec<OffPageSummary>,
+}
+
+impl PageReport {
+    ///
+    /// Groups the samples recorded off the page by context.
+    ///
+    /// # Parameters:
+    /// - `page_size`: The (width, height) of the page
+    /// - `samples`: The samples off the page, in the order they were drawn
+    ///
+    /// # Returns:
+    /// - The report
+    ///
+    fn new(page_size: (f64, f64), samples: Vec<OffPageSample>) -> PageReport {
+        let mut parts: Vec<OffPageSummary> = vec![];
+        for sample in samples {
+            let (x, y) = (sample.x, sample.y);
+            let part = match parts.iter().position(|part| part.context == sample.context) {
+                Some(idx) => &mut parts[idx],
+                None => {
+                    parts.push(OffPageSummary { context: sample.context.clone(), num_samples: 0, first: sample, bounds: None });
+                    parts.last_mut().unwrap()
+                },
+            };
+
+            part.num_samples += 1;
+            if x.is_finite() && y.is_finite() {
+                let (min_x, min_y, max_x, max_y) = part.bounds.unwrap_or((x, y, x, y));
+                part.bounds = Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)));
+            }
+        }
+        PageReport { page_size, parts }
+    }
+
+    ///
+    /// # Returns:
+    /// - Whether every sample was on the page
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    ///
+    /// # Returns:
+    /// - The number of samples off the page
+    ///
+    pub fn num_samples(&self) -> usize {
+        self.parts.iter().map(|part| part.num_samples).sum()
+    }
+}
+
+impl std::fmt::Display for PageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} samples are off the {:.1}mm by {:.1}mm page", self.num_samples(), self.page_size.0, self.page_size.1)?;
+        for part in &self.parts {
+            write!(f, "\n- {}: {} samples, first ({:.1}, {:.1}) at sample {}", part.context.as_deref().unwrap_or("(no context)"), part.num_samples, part.first.x, part.first.y, part.first.sample_num)?;
+            if let Some((min_x, min_y, max_x, max_y)) = part.bounds {
+                write!(f, ", spanning ({:.1}, {:.1}) to ({:.1}, {:.1})", min_x, min_y, max_x, max_y)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// 
 /// An abstract surface to draw on. Methods such as goto(x, y) and sample can be
-/// called to construct an image.
+/// called to construct an image. The belts are tracked as the pen moves, so unreachable points
+/// are rejected as they are sampled.
 ///
 /// # Fields:
 /// - `first_sample_x`: The initial x position of the pen, in millimetres from the top-left motor
 /// - `first_sample_y`: The initial y position of the pen, in millimetres from the top-left motor
-/// - `current_ins`: The vector containing the current instructions
+/// - `output`: The strokes or instructions drawn so far
+/// - `layer`: The layer new strokes are drawn on
+/// - `label`: The label new strokes are drawn with
 /// - `physical_dimensions`: The physical parameters of the machine
 /// - `belts`: An object representing the belts
 /// - `pen_up`: The current pen state, true if the pen is raised off the paper
 /// - `swap_pen_state`: Whether to change the pen state in the next instruction
+/// - `last_sample`: The last position sampled, relative to the top left of the page
+/// - `extent`: The (min_x, min_y, max_x, max_y) of everything drawn with the pen down
+/// - `num_samples`: The number of samples taken
+/// - `off_page`: The samples outside the page, or None if the surface isn't checking the page
+/// - `context`: What the drawing method says it is drawing, recorded with samples off the page
 ///
 pub struct DrawSurface<'pd> {
     first_sample_x: Option<f64>,
     first_sample_y: Option<f64>,
 
-    current_ins: Vec<u8>,
+    output: SurfaceOutput,
+    layer: usize,
+    label: Option<String>,
     physical_dimensions: &'pd PhysicalDimensions,
     belts: Belts,
 
     pen_up: bool,
     swap_pen_state: bool,
+
+    last_sample: (f64, f64),
+    extent: Option<(f64, f64, f64, f64)>,
+
+    num_samples: usize,
+    off_page: Option<Vec<OffPageSample>>,
+    context: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -83,11 +295,44 @@ impl<'pd> DrawSurface<'pd> {
     /// # Returns:
     /// - A blank `DrawSurface` object
     ///
-    fn new(physical_dimensions: &PhysicalDimensions) -> DrawSurface {
-        let belts = Belts::new_by_cartesian(0., 0., 0.);
+    fn new(physical_dimensions: &PhysicalDimensions) -> DrawSurface<'_> {
+        DrawSurface::with_estimated_samples(physical_dimensions, 0)
+    }
+
+    /// 
+    /// Creates a new drawing surface, reserving space for a drawing whose size is known up front.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimensions object representing the current hardware
+    /// - `estimated_samples`: Roughly how many times `sample_xy` will be called
+    ///
+    /// # Returns:
+    /// - A blank `DrawSurface` object
+    ///
+    fn with_estimated_samples(physical_dimensions: &PhysicalDimensions, estimated_samples: usize) -> DrawSurface<'_> {
+        DrawSurface::with_output(physical_dimensions, SurfaceOutput::Strokes { strokes: vec![], estimated_samples })
+    }
+
+    /// 
+    /// Creates a new drawing surface which writes instructions rather than strokes, for
+    /// compiling strokes.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimensions object representing the current hardware
+    /// - `num_samples`: Roughly how many times `sample_xy` will be called
+    ///
+    /// # Returns:
+    /// - A blank `DrawSurface` object
+    ///
+    fn compiler(physical_dimensions: &PhysicalDimensions, num_samples: usize) -> DrawSurface<'_> {
+        DrawSurface::with_output(physical_dimensions, SurfaceOutput::Instructions { writer: InstructionWriter::with_estimated_instructions(num_samples), num_instructions: 0 })
+    }
+
+    fn with_output(physical_dimensions: &PhysicalDimensions, output: SurfaceOutput) -> DrawSurface<'_> {
+        let belts = Belts::new_by_cartesian(0., 0., physical_dimensions).expect("The machine origin has belt lengths");
 
         // pen is assumed as starting up (for example, as it has to move to the start position)
-        DrawSurface { current_ins: Vec::new(), physical_dimensions, belts, first_sample_x: None, first_sample_y: None, pen_up: true, swap_pen_state: false }
+        DrawSurface { output, layer: 0, label: None, physical_dimensions, belts, first_sample_x: None, first_sample_y: None, pen_up: true, swap_pen_state: false, last_sample: (0., 0.), extent: None, num_samples: 0, off_page: None, context: None }
     }
 
     /// 
@@ -95,6 +340,7 @@ impl<'pd> DrawSurface<'pd> {
     /// current pen position.
     /// If there is no initial position, we set the passed x, y as the initial position and update
     /// the belts to reflect this. No instructions are added in this case.
+    /// Points inside a keep-out zone, or where the belts are too shallow, are rejected.
     ///
     /// # Parameters:
     /// - `x`: The new pen x position, relative to the top left of the paper in millimetres
@@ -105,71 +351,139 @@ impl<'pd> DrawSurface<'pd> {
     /// - An error as an owned string, explaining the problem
     ///
     pub fn sample_xy(&mut self, x: f64, y: f64) -> Result<(), String> {
+        if let Err(err) = self.physical_dimensions.check_bounds(x, y) {
+            return Err(err.to_string());
+        }
+
+        #[cfg(feature = "async")]
+        background::count_sample();
+
+        if let Some(off_page) = &mut self.off_page {
+            let (width, height) = (*self.physical_dimensions.page_width(), *self.physical_dimensions.page_height());
+            if !((0. ..=width).contains(&x) && (0. ..=height).contains(&y)) {
+                off_page.push(OffPageSample { x, y, sample_num: self.num_samples, context: self.context.clone() });
+            }
+        }
+        self.num_samples += 1;
+
         if self.first_sample_x.is_none() || self.first_sample_y.is_none() {
             // here we basically initialise the object
             // the first sample marks the first point of the belts
             // it does not create any instructions
 
-            self.first_sample_x = Some(x);
-            self.first_sample_y = Some(y);
-
             let belts = Belts::new_by_cartesian(
                 self.physical_dimensions.page_horizontal_offset() + x,
                 self.physical_dimensions.page_vertical_offset() + y,
-                *self.physical_dimensions.motor_interspace()
-            );
+                self.physical_dimensions
+            ).map_err(|err| err.to_string())?;
+
+            self.first_sample_x = Some(x);
+            self.first_sample_y = Some(y);
             self.belts = belts;
+            self.last_sample = (x, y);
 
             return Ok(());
         }
 
-        let (new_left, new_right) = cartesian_to_belt(*self.physical_dimensions.page_horizontal_offset() + x, *self.physical_dimensions.page_vertical_offset() + y, *self.physical_dimensions.motor_interspace());
+        let (new_left, new_right) = self.belts.lengths_at(*self.physical_dimensions.page_horizontal_offset() + x, *self.physical_dimensions.page_vertical_offset() + y).map_err(|err| err.to_string())?;
 
-        // delta length of belts in mm
+        // delta length of belts in mm, from where the belts really are rather than where the last
+        // sample aimed, so the rounding error of each movement is carried into the next. a long
+        // path of tiny segments then stays within half a step of the intended geometry, rather
+        // than drifting by the rounding of every segment
         let delta_left_length = new_left - self.belts.get_lengths().0;
         let delta_right_length = new_right - self.belts.get_lengths().1;
 
-        let delta_left_steps = delta_left_length * steps_per_mm();
-        let delta_right_steps = -(delta_right_length * steps_per_mm());
+        let steps_per_mm = self.physical_dimensions.steps_per_mm();
+        let delta_left_steps = delta_left_length * steps_per_mm;
+        let delta_right_steps = -(delta_right_length * steps_per_mm);
 
         if delta_left_steps >= i16::MAX as f64 || delta_left_steps <= i16::MIN as f64 || delta_right_steps >= i16::MAX as f64 || delta_right_steps <= i16::MIN as f64 {
-            return Err(format!("Steps are outside range! Currently have {} instructions generated, with step sizes l:{} and r:{}", self.current_ins.len(), delta_left_steps, delta_right_steps).to_owned());
+            return Err(format!("Steps are outside range! Currently at ({:.1}, {:.1}), with step sizes l:{} and r:{}", x, y, delta_left_steps, delta_right_steps).to_owned());
             // TODO: Error impl
         }
         
         let ls: i16 = (delta_left_steps.round() as i16).try_into().unwrap();
         let rs: i16 = (delta_right_steps.round() as i16).try_into().unwrap();
-        self.belts.move_by_steps(ls, -rs); // adjust state of belts, we have to invert the already inverted r
+        self.belts.move_by_steps_towards(ls, -rs, (new_left, new_right)); // adjust state of belts, we have to invert the already inverted r
 
     
-        // prepare bytes for socket
-        let mut left_step_bytes: [u8; 2] = [0_u8; 2];
-        let mut right_step_bytes: [u8; 2] = [0_u8; 2];
-        BigEndian::write_i16(&mut left_step_bytes, ls);
-        BigEndian::write_i16(&mut right_step_bytes, rs);
-
-        // push instruction bytes to buffer
-        self.current_ins.push(left_step_bytes[0]);    
-        self.current_ins.push(left_step_bytes[1]);    
-        self.current_ins.push(right_step_bytes[0]);    
-        self.current_ins.push(right_step_bytes[1]);
-
-        if self.swap_pen_state {
-            if self.pen_up {
-                self.current_ins.push(0x0B);
-                self.pen_up = false;
-            } else {
-                self.current_ins.push(0x0A);
-                self.pen_up = true;
-            }
+        // a pending pen change is performed before this movement
+        let pen_up = match self.swap_pen_state {
+            true => Some(!self.pen_up),
+            false => None,
+        };
+        if let Some(raised) = pen_up {
+            self.pen_up = raised;
             self.swap_pen_state = false;
         }
 
-        self.current_ins.push(0x0C);
+        match &mut self.output {
+            SurfaceOutput::Instructions { writer, num_instructions } => {
+                // the pen settles before moving, so the change and the wait come first on their own
+                let settle_ms = pen_up.map_or(0, |raised| self.physical_dimensions.pen_settle().after(raised));
+                let pen_up = match settle_ms > 0 {
+                    true => {
+                        writer.write(&Instruction::movement(0, 0, pen_up));
+                        writer.write(&Instruction::dwell(settle_ms));
+                        *num_instructions += 2;
+                        None
+                    },
+                    false => pen_up,
+                };
+                writer.write(&Instruction::movement(ls, rs, pen_up));
+                *num_instructions += 1;
+            },
+            SurfaceOutput::Strokes { strokes, estimated_samples } => {
+                let (pen_up, layer) = (self.pen_up, self.layer);
+                match strokes.last_mut() {
+                    Some(stroke) if stroke.pen_up == pen_up && stroke.layer == layer && stroke.label == self.label => stroke.points.push((x, y)),
+                    _ => {
+                        // each stroke starts where the last ended, so strokes stand alone
+                        let start = self.last_sample;
+                        let capacity = if strokes.is_empty() { (*estimated_samples).max(2) } else { 2 };
+                        if let [first] = strokes.as_mut_slice() {
+                            first.points.shrink_to_fit();
+                        }
+
+                        let mut points = Vec::with_capacity(capacity);
+                        points.extend_from_slice(&[start, (x, y)]);
+                        strokes.push(Stroke { points, pen_up, layer, label: self.label.clone() });
+                    },
+                }
+            },
+        }
+
+        if !self.pen_up {
+            let ((x1, y1), (min_x, min_y, max_x, max_y)) = (self.last_sample, self.extent.unwrap_or((x, y, x, y)));
+            self.extent = Some((min_x.min(x1).min(x), min_y.min(y1).min(y), max_x.max(x1).max(x), max_y.max(y1).max(y)));
+        }
+        self.last_sample = (x, y);
 
         Ok(())
     }
 
+    ///
+    /// Sets the layer of the strokes drawn from now on, such as which pen draws them.
+    ///
+    /// # Parameters:
+    /// - `layer`: The layer
+    ///
+    pub fn set_layer(&mut self, layer: usize) {
+        self.layer = layer;
+    }
+
+    ///
+    /// Labels the strokes drawn from now on, such as "outline" or "row 14". The label is carried
+    /// into exports and previews, so parts of the drawing can be picked out and plotted again.
+    ///
+    /// # Parameters:
+    /// - `label`: The label, or None to clear it
+    ///
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
+    }
+
     ///
     /// Raises of lowers the pen on the next instruction call.
     ///
@@ -185,50 +499,145 @@ impl<'pd> DrawSurface<'pd> {
         self.swap_pen_state = true;
     }
 
-    /// 
-    /// Pops the last draw call off the instruction list, and reverts the belts to their old
-    /// position accordingly.
+    ///
+    /// Turns checking the page on or off. While it's on, every sample outside the page is recorded
+    /// for `into_strokes_and_report`, rather than only being found when the preview reaches it.
+    /// Samples outside the page aren't rejected, as a method may deliberately travel off it.
+    ///
+    /// # Parameters:
+    /// - `enabled`: true to record the samples outside the page from now on
+    ///
+    pub fn check_page(&mut self, enabled: bool) {
+        match (enabled, self.off_page.is_some()) {
+            (true, false) => self.off_page = Some(vec![]),
+            (false, true) => self.off_page = None,
+            _ => {},
+        }
+    }
+
+    ///
+    /// Labels the samples taken from now on, such as with the layer or shape being drawn, so a
+    /// sample off the page can be traced back to what drew it.
+    ///
+    /// # Parameters:
+    /// - `context`: The label, or None to clear it
+    ///
+    pub fn set_context(&mut self, context: Option<String>) {
+        self.context = context;
+    }
+
+    ///
+    /// Records the state of the surface, so everything drawn after it can be discarded with
+    /// `rollback`, such as a shape which turned out to overlap another.
     ///
     /// # Returns:
-    /// - An error as an owned string, if the function failed
+    /// - The checkpoint
     ///
-    /* nneds adjustment to support pen up / down, but not in use rn
-    fn pop_sample(&mut self) -> Result<(), String> {
-        if self.current_ins.len() < 5 {
-            return Err("Could not pop instructions, as there were no instructions in the vector.".to_owned());
+    pub fn checkpoint(&self) -> Checkpoint {
+        let output_len = match &self.output {
+            SurfaceOutput::Strokes { strokes, .. } => (strokes.len(), strokes.last().map_or(0, |stroke| stroke.points.len())),
+            SurfaceOutput::Instructions { writer, num_instructions } => (writer.len(), *num_instructions),
+        };
+
+        Checkpoint {
+            output_len,
+            belt_lengths: self.belts.get_lengths(),
+            first_sample: (self.first_sample_x, self.first_sample_y),
+            pen_up: self.pen_up,
+            swap_pen_state: self.swap_pen_state,
+            layer: self.layer,
+            label: self.label.clone(),
+            last_sample: self.last_sample,
+            extent: self.extent,
+            num_samples: self.num_samples,
+            off_page_len: self.off_page.as_ref().map_or(0, |off_page| off_page.len()),
         }
+    }
 
-        let _ = self.current_ins.pop().unwrap(); // 0x0C terminator byte
+    ///
+    /// Discards everything drawn since a checkpoint, returning the pen, belts and pen state to
+    /// where they were. Checkpoints taken after this one can no longer be rolled back to.
+    ///
+    /// # Parameters:
+    /// - `checkpoint`: A checkpoint taken from this surface
+    ///
+    pub fn rollback(&mut self, checkpoint: &Checkpoint) {
+        match &mut self.output {
+            SurfaceOutput::Strokes { strokes, .. } => {
+                let (num_strokes, num_points) = checkpoint.output_len;
+                strokes.truncate(num_strokes);
+                if let Some(stroke) = strokes.last_mut() {
+                    stroke.points.truncate(num_points);
+                }
+            },
+            SurfaceOutput::Instructions { writer, num_instructions } => {
+                writer.truncate(checkpoint.output_len.0);
+                *num_instructions = checkpoint.output_len.1;
+            },
+        }
 
-        // right step bytes popped off first
-        let right_step_bytes: [u8; 2] = [self.current_ins.pop().unwrap(), self.current_ins.pop().unwrap()];
-        let left_step_bytes: [u8; 2] = [self.current_ins.pop().unwrap(), self.current_ins.pop().unwrap()];
+        self.belts.set_lengths(checkpoint.belt_lengths.0, checkpoint.belt_lengths.1);
+        (self.first_sample_x, self.first_sample_y) = checkpoint.first_sample;
+        self.pen_up = checkpoint.pen_up;
+        self.swap_pen_state = checkpoint.swap_pen_state;
+        self.layer = checkpoint.layer;
+        self.label = checkpoint.label.clone();
+        self.last_sample = checkpoint.last_sample;
+        self.extent = checkpoint.extent;
+        self.num_samples = checkpoint.num_samples;
+        if let Some(off_page) = &mut self.off_page {
+            off_page.truncate(checkpoint.off_page_len);
+        }
+    }
 
-        // here we use little endian to decode, as the bytes are popped off in reverse
-        let left_steps: i16 = LittleEndian::read_i16(&left_step_bytes);
-        let right_steps: i16 = LittleEndian::read_i16(&right_step_bytes);
+    ///
+    /// # Returns:
+    /// - The bounds of everything drawn so far with the pen down, relative to the top left of the
+    ///   page, so a drawing can be centred or scaled to fit in a second pass
+    /// - None if nothing has been drawn with the pen down
+    ///
+    pub fn extent(&self) -> Option<Rect> {
+        self.extent.map(|(min_x, min_y, max_x, max_y)| Rect { x: min_x, y: min_y, width: max_x - min_x, height: max_y - min_y })
+    }
 
-        // move the belts in reverse, to revert the instruction. right belt is double-reversed hence not
-        self.belts.move_by_steps(-left_steps, right_steps);
+    ///
+    /// # Returns:
+    /// - The number of instructions written so far, including the dwells after pen changes. A
+    ///   surface drawing strokes writes none
+    ///
+    fn num_instructions(&self) -> usize {
+        match &self.output {
+            SurfaceOutput::Instructions { num_instructions, .. } => *num_instructions,
+            SurfaceOutput::Strokes { .. } => 0,
+        }
+    }
 
-        Ok(())
+    ///
+    /// # Returns:
+    /// - The left and right belt lengths of the last sample minus the lengths the motors really
+    ///   stepped to, in millimetres. It is carried into the next sample, so never grows past half
+    ///   a step
+    ///
+    pub fn quantisation_error(&self) -> (f64, f64) {
+        self.belts.quantisation_error()
     }
-    */
 
     ///
     /// # Returns:
     /// - The curent (x, y) position of the pen, relative to the top corner of the paper
+    /// - `KinematicsError` with the belt lengths, if the belts can not meet at a point
     /// 
-    pub fn get_xy(&self) -> (f64, f64) {
-        let (total_x, total_y) = self.belts.get_as_cartesian();
-        (total_x - self.physical_dimensions.page_horizontal_offset(), total_y - self.physical_dimensions.page_vertical_offset())
+    pub fn get_xy(&self) -> Result<(f64, f64), KinematicsError> {
+        let (total_x, total_y) = self.belts.get_as_cartesian()?;
+        Ok((total_x - self.physical_dimensions.page_horizontal_offset(), total_y - self.physical_dimensions.page_vertical_offset()))
     }
 
     /// 
-    /// TODO: Lerp between 0, 0 -> init_x, init_y appropriately to fit ins into i16 bytes
-    ///
     /// Creates the drawing instructions required to move the pen from 0, 0 on the page to the
-    /// given point, used to position the pen initially to start the drawing.
+    /// given point, used to position the pen initially to start the drawing. The move is split
+    /// into short segments, so the pen travels in a straight line rather than swinging along an
+    /// arc, and no segment overflows the motor steps of an instruction. A long move is more
+    /// instructions than one instruction buffer holds, so it's sent in several chunks.
     ///
     /// # Parameters:
     /// - `physical_dimensions`: A physical dimensions object representing the current hardware
@@ -237,13 +646,440 @@ impl<'pd> DrawSurface<'pd> {
     ///
     /// # Returns:
     /// - A vector of instruction bytes
+    /// - `HardwareError` if the pen can't travel to the start, such as through a keep-out zone
     ///
-    pub fn pen_to_start_ins(physical_dimensions: &PhysicalDimensions, init_x: f64, init_y: f64) -> Vec<u8> {
-        let mut ds = DrawSurface::new(physical_dimensions);
+    pub fn pen_to_start_ins(physical_dimensions: &PhysicalDimensions, init_x: f64, init_y: f64) -> Result<Vec<u8>, HardwareError> {
+        let distance = init_x.hypot(init_y);
+        // a belt never changes length by more than the pen moves, so this bounds the steps
+        let max_segment_mm = START_SEGMENT_MM.min((i16::MAX - 1) as f64 / physical_dimensions.steps_per_mm());
+        let segments = ((distance / max_segment_mm).ceil() as usize).max(1);
 
-        ds.sample_xy(0., 0.).unwrap(); // init at to top/left of page
-        ds.sample_xy(init_x, init_y).unwrap(); // move to start pos
+        let mut ds = DrawSurface::compiler(physical_dimensions, segments + 1);
+        // init at the top/left of the page, then move towards the start pos
+        for i in 0..=segments {
+            let t = i as f64 / segments as f64;
+            let (x, y) = (init_x * t, init_y * t);
+            physical_dimensions.check_bounds(x, y)?;
+            ds.sample_xy(x, y).map_err(|reason| HardwareError::Unreachable { x, y, reason })?;
+        }
         
-        ds.current_ins
+        ds.into_instructions().map(|(ins_bytes, _, _)| ins_bytes).map_err(|reason| HardwareError::Unreachable { x: init_x, y: init_y, reason })
+    }
+
+    ///
+    /// Finishes the drawing, in the form `DrawMethod::gen_strokes` returns.
+    ///
+    /// # Returns:
+    /// - The strokes drawn, in order. A surface compiling strokes has none
+    ///
+    pub fn into_strokes(self) -> Vec<Stroke> {
+        match self.output {
+            SurfaceOutput::Strokes { strokes, .. } => strokes,
+            SurfaceOutput::Instructions { .. } => vec![],
+        }
+    }
+
+    ///
+    /// Finishes the drawing, in the form `DrawMethod::gen_strokes` returns, with what checking
+    /// the page found.
+    ///
+    /// # Returns:
+    /// - The strokes drawn, in order. A surface compiling strokes has none
+    /// - The samples off the page, or None if the surface wasn't checking the page when it finished
+    ///
+    pub fn into_strokes_and_report(mut self) -> (Vec<Stroke>, Option<PageReport>) {
+        let page_size = (*self.physical_dimensions.page_width(), *self.physical_dimensions.page_height());
+        let report = self.off_page.take().map(|off_page| PageReport::new(page_size, off_page));
+        (self.into_strokes(), report)
+    }
+
+    ///
+    /// Finishes the drawing, in the form `DrawMethod::gen_instructions` returns.
+    ///
+    /// # Returns:
+    /// - The drawing instruction bytes, and the initial x, y position of the pen relative to the
+    ///   top left of the page
+    /// - An error explaining which point of the drawing can't be reached, if recorded strokes
+    ///   couldn't be compiled
+    ///
+    pub fn into_instructions(self) -> Result<(Vec<u8>, f64, f64), String> {
+        let start = (self.first_sample_x.unwrap_or(0.), self.first_sample_y.unwrap_or(0.));
+        let ins_bytes = match self.output {
+            SurfaceOutput::Instructions { writer, .. } => writer.into_bytes(),
+            SurfaceOutput::Strokes { strokes, .. } => stroke::compile(&strokes, self.physical_dimensions)?.0,
+        };
+        Ok((ins_bytes, start.0, start.1))
+    }
+
+    ///
+    /// Finishes the drawing, validating its instructions.
+    ///
+    /// # Returns:
+    /// - The instruction set of the drawing
+    /// - An error explaining why the instructions are invalid, such as nothing being drawn, or
+    ///   why the strokes couldn't be compiled
+    ///
+    pub fn into_instruction_set(self) -> Result<InstructionSet, InstructionError> {
+        let (ins_bytes, init_x, init_y) = self.into_instructions().map_err(|reason| InstructionError::Uncompilable { reason })?;
+        InstructionSet::new(ins_bytes, init_x, init_y)
+    }
+}
+
+
+///
+/// Tests relating to the DrawSurface struct and compiling strokes.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn physical_dimensions() -> PhysicalDimensions {
+        PhysicalDimensions::new(500., 150., 150., 200., 250.)
+    }
+
+    #[test]
+    fn strokes_compile_to_the_same_instructions() {
+        let physical_dimensions = physical_dimensions();
+        let draw = |surface: &mut DrawSurface| {
+            surface.sample_xy(20., 20.).unwrap();
+            surface.raise_pen(false);
+            surface.sample_xy(60., 20.).unwrap();
+            surface.sample_xy(60., 60.).unwrap();
+            surface.raise_pen(true);
+            surface.sample_xy(100., 100.).unwrap();
+            surface.raise_pen(false);
+            surface.sample_xy(120., 100.).unwrap();
+        };
+
+        let mut compiler = DrawSurface::compiler(&physical_dimensions, 0);
+        draw(&mut compiler);
+        let mut recorder = DrawSurface::new(&physical_dimensions);
+        draw(&mut recorder);
+        let strokes = recorder.into_strokes();
+
+        assert_eq!(strokes.iter().map(|stroke| stroke.pen_up).collect::<Vec<_>>(), [false, true, false]);
+        assert_eq!(strokes[0].points, [(20., 20.), (60., 20.), (60., 60.)]);
+        assert_eq!(stroke::compile(&strokes, &physical_dimensions).unwrap(), compiler.into_instructions().unwrap());
+    }
+
+    #[test]
+    fn rollback_discards_everything_since_the_checkpoint() {
+        let physical_dimensions = physical_dimensions();
+        let mut expected = DrawSurface::new(&physical_dimensions);
+        let mut surface = DrawSurface::new(&physical_dimensions);
+        for surface in [&mut expected, &mut surface] {
+            surface.sample_xy(20., 20.).unwrap();
+            surface.raise_pen(false);
+            surface.sample_xy(60., 20.).unwrap();
+        }
+
+        let checkpoint = surface.checkpoint();
+        surface.sample_xy(60., 60.).unwrap();
+        surface.raise_pen(true);
+        surface.sample_xy(100., 100.).unwrap();
+        surface.rollback(&checkpoint);
+
+        for surface in [&mut expected, &mut surface] {
+            surface.sample_xy(80., 20.).unwrap();
+        }
+        assert_eq!(surface.get_xy().unwrap(), expected.get_xy().unwrap());
+        assert_eq!(surface.extent(), expected.extent());
+        assert_eq!(surface.into_strokes(), expected.into_strokes());
+    }
+
+    #[test]
+    fn samples_off_the_page_are_reported() {
+        let physical_dimensions = physical_dimensions();
+        let mut surface = DrawSurface::new(&physical_dimensions);
+        surface.sample_xy(-5., 10.).unwrap();
+        surface.check_page(true);
+        surface.sample_xy(10., 10.).unwrap();
+        surface.set_context(Some("circle 1".to_owned()));
+        surface.sample_xy(-5., 10.).unwrap();
+        surface.sample_xy(210., 300.).unwrap();
+        surface.set_context(Some("circle 2".to_owned()));
+        assert!(surface.sample_xy(f64::NAN, 10.).is_err());
+
+        // off page samples drawn since a checkpoint are discarded with it
+        let checkpoint = surface.checkpoint();
+        surface.sample_xy(-1., -1.).unwrap();
+        surface.rollback(&checkpoint);
+
+        let (_, report) = surface.into_strokes_and_report();
+        let report = report.unwrap();
+        assert_eq!(report.num_samples(), 3);
+        assert_eq!(report.parts.iter().map(|part| (part.context.as_deref(), part.num_samples)).collect::<Vec<_>>(), [(Some("circle 1"), 2), (Some("circle 2"), 1)]);
+        assert_eq!(report.parts[0].first.sample_num, 2);
+        assert_eq!(report.parts[0].bounds, Some((-5., 10., 210., 300.)));
+        assert_eq!(report.parts[1].bounds, None);
+
+        let mut unchecked = DrawSurface::new(&physical_dimensions);
+        unchecked.sample_xy(-5., 10.).unwrap();
+        assert_eq!(unchecked.into_strokes_and_report().1, None);
+    }
+
+    #[test]
+    fn extent_covers_pen_down_lines_only() {
+        let physical_dimensions = physical_dimensions();
+        let mut surface = DrawSurface::new(&physical_dimensions);
+        surface.sample_xy(10., 10.).unwrap();
+        assert_eq!(surface.extent(), None);
+
+        surface.sample_xy(20., 30.).unwrap();
+        surface.raise_pen(false);
+        surface.sample_xy(50., 40.).unwrap();
+        surface.sample_xy(40., 25.).unwrap();
+        surface.raise_pen(true);
+        surface.sample_xy(100., 100.).unwrap();
+
+        assert_eq!(surface.extent(), Some(Rect { x: 20., y: 25., width: 30., height: 15. }));
+    }
+
+    #[test]
+    fn moves_to_the_start_in_a_straight_line() {
+        let physical_dimensions = physical_dimensions();
+        let ins_bytes = DrawSurface::pen_to_start_ins(&physical_dimensions, 180., 240.).unwrap();
+        let instruction_set = InstructionSet::new(ins_bytes, 0., 0.).unwrap();
+
+        let mut end = (0., 0.);
+        crate::preview::simulate(instruction_set.get_init(), &physical_dimensions, &instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), _is_pen_up: bool| {
+            // the distance from the line between (0, 0) and (180, 240)
+            let off_line = (to.0 * 240. - to.1 * 180.).abs() / 300.;
+            assert!(off_line < 0.5, "({}, {}) is {}mm off the line", to.0, to.1, off_line);
+            assert!((to.0 - from.0).hypot(to.1 - from.1) <= START_SEGMENT_MM + 0.1);
+            end = to;
+        }).unwrap();
+
+        assert!((end.0 - 180.).abs() < 0.1 && (end.1 - 240.).abs() < 0.1);
+    }
+
+    #[test]
+    fn moves_to_a_far_start_in_short_segments() {
+        // over 2 metres from the pen, so the move is longer than one instruction buffer holds
+        let physical_dimensions = PhysicalDimensions::new(2500., 250., 250., 2000., 1500.);
+        let ins_bytes = DrawSurface::pen_to_start_ins(&physical_dimensions, 1800., 1400.).unwrap();
+        let instruction_set = InstructionSet::new(ins_bytes, 0., 0.).unwrap();
+        assert!(instruction_set.get_binary().len() > 1024);
+
+        let mut end = (0., 0.);
+        crate::preview::simulate(instruction_set.get_init(), &physical_dimensions, &instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), _is_pen_up: bool| {
+            let length = (to.0 - from.0).hypot(to.1 - from.1);
+            assert!(length <= START_SEGMENT_MM + 0.1, "the segment to ({}, {}) is {}mm", to.0, to.1, length);
+            end = to;
+        }).unwrap();
+
+        assert!((end.0 - 1800.).abs() < 0.1 && (end.1 - 1400.).abs() < 0.1);
+    }
+
+    #[test]
+    fn moving_to_the_start_through_a_keep_out_zone_is_an_error() {
+        use crate::client::error::ClientError;
+        use crate::hardware::KeepOutZone;
+
+        let physical_dimensions = physical_dimensions().with_keep_out_zones(vec![KeepOutZone { x: 0., y: 0., width: 20., height: 20. }]);
+        assert!(matches!(DrawSurface::pen_to_start_ins(&physical_dimensions, 180., 240.), Err(HardwareError::InKeepOutZone { x: 0., y: 0. })));
+
+        // the move is checked before connecting, so no machine is needed to see the error
+        let result = crate::client::move_to_start("127.0.0.1", 1, &physical_dimensions, 180., 240.);
+        assert!(matches!(result, Err(ClientError::Hardware(HardwareError::InKeepOutZone { .. }))), "{:?}", result);
+    }
+
+    #[test]
+    fn strokes_which_cannot_be_compiled_are_an_error() {
+        use crate::hardware::KeepOutZone;
+
+        // recording checks each point, so a stroke into the zone is added as if it had been
+        // recorded before the zone was
+        let physical_dimensions = physical_dimensions().with_keep_out_zones(vec![KeepOutZone { x: 80., y: 0., width: 40., height: 250. }]);
+        let mut surface = DrawSurface::new(&physical_dimensions);
+        surface.sample_xy(20., 20.).unwrap();
+        surface.raise_pen(false);
+        surface.sample_xy(40., 20.).unwrap();
+        if let SurfaceOutput::Strokes { strokes, .. } = &mut surface.output {
+            strokes.push(Stroke { points: vec![(40., 20.), (100., 20.)], pen_up: false, layer: 0, label: None });
+        }
+
+        assert!(matches!(surface.into_instruction_set(), Err(InstructionError::Uncompilable { .. })));
+    }
+
+    #[test]
+    fn labels_are_carried_into_compiled_spans_and_svg() {
+        let physical_dimensions = physical_dimensions();
+        let mut surface = DrawSurface::new(&physical_dimensions);
+        surface.set_label(Some("outline".to_owned()));
+        surface.sample_xy(20., 20.).unwrap();
+        surface.raise_pen(false);
+        surface.sample_xy(60., 20.).unwrap();
+        surface.set_label(Some("row <1>".to_owned()));
+        surface.sample_xy(60., 60.).unwrap();
+        surface.set_label(Some("outline".to_owned()));
+        surface.sample_xy(20., 60.).unwrap();
+
+        // a new label starts a new stroke, even with the pen down
+        let strokes = surface.into_strokes();
+        assert_eq!(strokes.iter().map(|stroke| stroke.label.as_deref()).collect::<Vec<_>>(), [Some("outline"), Some("row <1>"), Some("outline")]);
+
+        let ((ins_bytes, _, _), labels) = stroke::compile_with_labels(&strokes, &physical_dimensions).unwrap();
+        assert_eq!(labels.iter().map(|span| span.label.as_str()).collect::<Vec<_>>(), ["outline", "row <1>", "outline"]);
+        assert_eq!(labels[0].first_instruction, 0);
+        assert!(labels.windows(2).all(|w| w[1].first_instruction == w[0].last_instruction + 1));
+        assert_eq!(labels[2].last_instruction + 1, ins_bytes.len() / 5);
+
+        let svg = stroke::to_svg(&strokes, &physical_dimensions);
+        assert_eq!(svg.matches("<g id=\"outline\"").count(), 1);
+        assert!(svg.contains("<g id=\"row--1-\" data-label=\"row &lt;1&gt;\">"));
+        assert_eq!(svg.matches("<polyline").count(), 3);
+    }
+
+    #[test]
+    fn quantised_drawings_compile_identically_despite_float_noise() {
+        let physical_dimensions = physical_dimensions();
+        let circle = |noise: f64| vec![Stroke {
+            points: (0..=360).map(|i| {
+                let theta = (i as f64).to_radians();
+                (100. + 40. * theta.cos() + noise * (i % 3) as f64, 120. + 40. * theta.sin() - noise)
+            }).collect(),
+            pen_up: false, layer: 0, label: None,
+        }];
+
+        // noise in the last bits, as a different platform's maths library would give
+        let quantised = stroke::quantise(circle(0.), stroke::REPRODUCIBLE_GRID_MM);
+        let noisy = stroke::quantise(circle(1e-12), stroke::REPRODUCIBLE_GRID_MM);
+        assert!(quantised[0].points.iter().all(|&(x, y)| (x * 1024.).fract() == 0. && (y * 1024.).fract() == 0.));
+        assert_eq!(stroke::compile(&quantised, &physical_dimensions).unwrap(), stroke::compile(&noisy, &physical_dimensions).unwrap());
+    }
+
+    #[test]
+    fn disjoint_strokes_are_joined_with_the_pen_up() {
+        let physical_dimensions = physical_dimensions();
+        let strokes = [
+            Stroke { points: vec![(20., 20.), (40., 20.)], pen_up: false, layer: 0, label: None },
+            Stroke { points: vec![(150., 200.), (170., 200.)], pen_up: false, layer: 0, label: None },
+        ];
+
+        let (ins_bytes, start_x, start_y) = stroke::compile(&strokes, &physical_dimensions).unwrap();
+        let instruction_set = InstructionSet::new(ins_bytes, start_x, start_y).unwrap();
+
+        let mut pen_down_mm = 0.;
+        crate::preview::simulate(instruction_set.get_init(), &physical_dimensions, &instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+            if !is_pen_up {
+                pen_down_mm += (to.0 - from.0).hypot(to.1 - from.1);
+            }
+        }).unwrap();
+
+        // only the two 20mm strokes are drawn, not the move between them
+        assert!((pen_down_mm - 40.).abs() < 0.5, "drew {}mm", pen_down_mm);
+    }
+
+    #[test]
+    fn strokes_are_ordered_by_the_nearest_end() {
+        let stroke = |points: Vec<(f64, f64)>, layer: usize| Stroke { points, pen_up: false, layer, label: None };
+        let strokes = vec![
+            stroke(vec![(100., 0.), (90., 0.)], 0),
+            stroke(vec![(0., 50.), (0., 60.)], 1),
+            Stroke { points: vec![(0., 0.), (100., 100.)], pen_up: true, layer: 0, label: None },
+            stroke(vec![(50., 0.), (60., 0.)], 0),
+            stroke(vec![(10., 0.), (20., 0.)], 0),
+        ];
+
+        // layer 0 is drawn first, with the stroke from 100 to 90 reversed, then layer 1
+        let ordered = stroke::order_strokes(strokes, (0., 0.));
+        assert_eq!(ordered.iter().map(|stroke| stroke.points[0]).collect::<Vec<_>>(), [(10., 0.), (50., 0.), (90., 0.), (0., 50.)]);
+        assert!(ordered.iter().all(|stroke| !stroke.pen_up));
+    }
+
+    #[test]
+    fn degenerate_strokes_are_found_and_removed() {
+        let stroke = |points: Vec<(f64, f64)>| Stroke { points, pen_up: false, layer: 0, label: None };
+        let strokes = vec![
+            stroke(vec![(10., 10.), (10., 10.)]),
+            stroke(vec![(20., 10.), (20.2, 10.)]),
+            stroke(vec![(30., 10.), (40., 10.), (40., 10.), (40., 20.)]),
+            Stroke { points: vec![(40., 20.), (40., 20.)], pen_up: true, layer: 0, label: None },
+        ];
+
+        assert_eq!(stroke::find_degenerate(&strokes, 0.5), stroke::DegenerateReport { dots: 1, short_strokes: 1, zero_length_segments: 1 });
+        // without a line width, only the dot and the segment which doesn't move are degenerate
+        assert_eq!(stroke::find_degenerate(&strokes, 0.).short_strokes, 0);
+
+        let removed = stroke::remove_degenerate(strokes.clone(), 0.5);
+        assert_eq!(removed, [stroke(vec![(30., 10.), (40., 10.), (40., 20.)]), strokes[3].clone()]);
+        assert!(stroke::find_degenerate(&removed, 0.5).is_empty());
+        let physical_dimensions = physical_dimensions();
+        let ins_length = |strokes: &[Stroke]| stroke::compile(strokes, &physical_dimensions).unwrap().0.len();
+        assert!(ins_length(&removed) < ins_length(&strokes));
+    }
+
+    #[test]
+    fn strokes_are_flipped_to_avoid_motor_reversals() {
+        let physical_dimensions = physical_dimensions();
+        let strokes = vec![
+            Stroke { points: vec![(20., 50.), (120., 50.)], pen_up: false, layer: 0, label: None },
+            Stroke { points: vec![(110., 50.), (190., 50.)], pen_up: false, layer: 0, label: None },
+        ];
+
+        // going back to the near end reverses both motors twice, but going on to the far end
+        // and back only reverses them once
+        let oriented = stroke::orient_strokes(strokes.clone(), (20., 50.), &physical_dimensions, 0.);
+        assert_eq!(oriented, strokes);
+        let oriented = stroke::orient_strokes(strokes.clone(), (20., 50.), &physical_dimensions, 100.);
+        assert_eq!(oriented[0], strokes[0]);
+        assert_eq!(oriented[1].points, [(190., 50.), (110., 50.)]);
+    }
+
+    #[test]
+    fn pen_changes_wait_for_the_pen_to_settle() {
+        use crate::client::calculate_draw_time;
+        use crate::hardware::motion::AccelerationProfile;
+        use crate::instruction::codec;
+        use crate::instruction::opcode::MAX_INSTRUCTION_LENGTH;
+
+        let strokes = vec![
+            Stroke { points: vec![(20., 50.), (60., 50.)], pen_up: false, layer: 0, label: None },
+            Stroke { points: vec![(60., 80.), (20., 80.)], pen_up: false, layer: 1, label: None },
+        ];
+        let settling = physical_dimensions().with_pen_settle(crate::hardware::PenSettle { raise_ms: 150, lower_ms: 400 });
+        let ((ins_bytes, _, _), layer_starts) = stroke::compile_with_layer_starts(&strokes, &physical_dimensions()).unwrap();
+        let ((settled_bytes, _, _), settled_layer_starts) = stroke::compile_with_layer_starts(&strokes, &settling).unwrap();
+
+        // each pen change is followed by a dwell, before the pen moves
+        let mut dwells = vec![];
+        let mut c_idx = 0;
+        while let Ok((instruction, next_idx)) = Instruction::decode(&settled_bytes, c_idx) {
+            if instruction.dwell_ms > 0 {
+                let (previous, _) = Instruction::decode(&settled_bytes, c_idx - MAX_INSTRUCTION_LENGTH).unwrap();
+                assert_eq!((previous.left_steps, previous.right_steps), (0, 0));
+                dwells.push((previous.pen_up, instruction.dwell_ms));
+            }
+            c_idx = next_idx;
+        }
+        assert_eq!(dwells, [(Some(false), 400), (Some(true), 150), (Some(false), 400)]);
+
+        // the pen moves exactly as it did, and the layers still start at their pen up moves
+        let moves = |ins_bytes: &[u8]| codec::decode_steps(ins_bytes).unwrap().into_iter().filter(|&(ls, rs, _)| ls != 0 || rs != 0).collect::<Vec<_>>();
+        assert_eq!(moves(&settled_bytes), moves(&ins_bytes));
+        assert_eq!(settled_layer_starts, [0, layer_starts[1] + 2]);
+
+        let profile = AccelerationProfile::from_max_motor_speed(2000.);
+        let extra = calculate_draw_time(&settled_bytes, &profile) - calculate_draw_time(&ins_bytes, &profile);
+        assert!(extra >= std::time::Duration::from_millis(950), "{:?}", extra);
+    }
+
+    #[test]
+    fn rounding_errors_are_carried_between_samples() {
+        let physical_dimensions = physical_dimensions();
+        let half_step = 0.5 / physical_dimensions.steps_per_mm();
+        let mut surface = DrawSurface::compiler(&physical_dimensions, 0);
+
+        // every segment is shorter than half a step, so rounding each alone would never move
+        surface.sample_xy(20., 50.).unwrap();
+        for i in 1..=4000 {
+            surface.sample_xy(20. + i as f64 * 0.005, 50.).unwrap();
+            let (left_error, right_error) = surface.quantisation_error();
+            assert!(left_error.abs() <= half_step + 1e-9 && right_error.abs() <= half_step + 1e-9);
+        }
+
+        let (x, y) = surface.get_xy().unwrap();
+        assert!((x - 40.).abs() < 4. * half_step && (y - 50.).abs() < 4. * half_step, "ended at ({}, {})", x, y);
     }
 }
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::f64::consts::FRAC_PI_3;
+
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+use super::util::placement::{Margins, Placement};
+
+///
+/// The most cells a pattern may cover the page with, which bounds the drawing time of tiny cells.
+///
+const MAX_CELLS: f64 = 100_000.;
+
+///
+/// The grid points are snapped to in the pattern's own space, in millimetres, so vertices shared
+/// by neighbouring cells are exactly equal.
+///
+const SNAP_MM: f64 = 1e-6;
+
+type Segment = ((f64, f64), (f64, f64));
+
+///
+/// An empty struct to implement the "Pattern" draw method on.
+///
+pub struct PatternMethod;
+
+impl DrawMethod for PatternMethod {
+    type DrawParameters = PatternParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "pattern"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Pattern"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new pattern drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        PatternParameters {
+            pattern: Pattern::IsometricCubes, placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            cell_size: 15., line_spacing: 2., jitter: 0., rotation: 0., seed: 1,
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the pattern drawing method.
+    /// This drawing method covers the area inside the margins with a repeating tile pattern, for
+    /// backgrounds and wrapping paper. Tiles can be shaded with parallel lines, rotated, and
+    /// jittered to look hand drawn.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &PatternParameters) -> Result<Vec<Stroke>, String> {
+
+        if !(parameters.cell_size.is_finite() && parameters.cell_size > 0.) {
+            return Err("The cell size must be above 0".to_owned());
+        }
+        if !(parameters.line_spacing.is_finite() && (parameters.line_spacing == 0. || parameters.line_spacing >= parameters.cell_size / 100.)) {
+            return Err("The line spacing must be 0 for outlines only, or at least a hundredth of the cell size".to_owned());
+        }
+        if !(parameters.jitter.is_finite() && parameters.jitter >= 0.) {
+            return Err("The jitter can not be negative".to_owned());
+        }
+
+        // the pattern is generated around the centre of the area, far enough out to cover it at any rotation
+        let area = parameters.placement.area(physical_dimensions)?;
+        let reach = area.width.hypot(area.height) / 2. + parameters.cell_size * 2.;
+        if (reach * 2. / parameters.cell_size).powi(2) > MAX_CELLS {
+            return Err(format!("The cells are too small to cover the page, try a cell size above {:.1}mm", reach * 2. / MAX_CELLS.sqrt()));
+        }
+
+        let segments = match parameters.pattern {
+            Pattern::BasketWeave => basket_weave(reach, parameters.cell_size, parameters.line_spacing),
+            Pattern::Herringbone => herringbone(reach, parameters.cell_size, parameters.line_spacing),
+            Pattern::IsometricCubes => isometric_cubes(reach, parameters.cell_size, parameters.line_spacing),
+            Pattern::HexGrid => hex_grid(reach, parameters.cell_size, parameters.line_spacing),
+        };
+
+        let (centre_x, centre_y) = (area.x + area.width / 2., area.y + area.height / 2.);
+        let (sin, cos) = parameters.rotation.to_radians().sin_cos();
+        let to_page = |point: (f64, f64)| {
+            let (jitter_x, jitter_y) = jitter_offset(point, parameters.seed, parameters.jitter);
+            (centre_x + point.0 * cos - point.1 * sin + jitter_x, centre_y + point.0 * sin + point.1 * cos + jitter_y)
+        };
+
+        // edges shared by neighbouring cells are only drawn once
+        let mut drawn = HashSet::new();
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, segments.len() * 2);
+        let mut pen_at = None;
+        for (start, end) in segments {
+            let (start, end) = (snap(start), snap(end));
+            let key = if start < end { (start, end) } else { (end, start) };
+            if start == end || !drawn.insert([key.0.0.to_bits(), key.0.1.to_bits(), key.1.0.to_bits(), key.1.1.to_bits()]) {
+                continue;
+            }
+
+            let Some((mut start, mut end)) = area.clip_segment(to_page(start), to_page(end)) else { continue };
+
+            // segments continuing from the last are drawn without lifting the pen
+            if pen_at == Some(end) {
+                (start, end) = (end, start);
+            }
+            if pen_at != Some(start) {
+                surface.raise_pen(true);
+                surface.sample_xy(start.0, start.1)?;
+                surface.raise_pen(false);
+            }
+            surface.sample_xy(end.0, end.1)?;
+            pen_at = Some(end);
+        }
+
+        Ok(surface.into_strokes())
+    }
+}
+
+///
+/// # Returns:
+/// - The point snapped to the grid of `SNAP_MM`
+///
+fn snap(point: (f64, f64)) -> (f64, f64) {
+    // adding 0 turns -0 into 0, so both have the same bits
+    ((point.0 / SNAP_MM).round() * SNAP_MM + 0., (point.1 / SNAP_MM).round() * SNAP_MM + 0.)
+}
+
+///
+/// Offsets a point of the pattern by a random amount, which is the same every time the point is
+/// offset, so cells sharing a vertex stay joined.
+///
+/// # Parameters:
+/// - `point`: The point, in the pattern's own space
+/// - `seed`: The seed of the drawing
+/// - `jitter`: The furthest the point may move on each axis, in millimetres
+///
+/// # Returns:
+/// - The (x, y) offset of the point, in millimetres
+///
+fn jitter_offset(point: (f64, f64), seed: u64, jitter: f64) -> (f64, f64) {
+    if jitter == 0. {
+        return (0., 0.);
+    }
+
+    // splitmix64, mixing the seed and the bits of the point
+    let mix = |mut z: u64| {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    };
+    let hash = mix(mix(seed ^ point.0.to_bits()).wrapping_add(0x9e3779b97f4a7c15) ^ point.1.to_bits());
+    let unit = |bits: u64| (bits >> 11) as f64 / (1u64 << 53) as f64 * 2. - 1.;
+    (unit(hash) * jitter, unit(mix(hash)) * jitter)
+}
+
+///
+/// Shades a parallelogram with lines parallel to one of its sides.
+///
+/// # Parameters:
+/// - `corner`: A corner of the parallelogram
+/// - `along`: The side from the corner the lines are parallel to
+/// - `across`: The other side from the corner, which the lines are spaced along
+/// - `line_spacing`: The distance between the lines, in millimetres, or 0 for none
+///
+/// # Returns:
+/// - The shading lines, not including the sides of the parallelogram
+///
+fn hatch(corner: (f64, f64), along: (f64, f64), across: (f64, f64), line_spacing: f64) -> Vec<Segment> {
+    if line_spacing == 0. {
+        return vec![];
+    }
+
+    // the distance between the two sides parallel to `along`
+    let height = (along.0 * across.1 - along.1 * across.0).abs() / along.0.hypot(along.1);
+    (1..).map(|line| (line, line as f64 * line_spacing / height))
+        .take_while(|(_, t)| *t < 1.)
+        .map(|(line, t)| {
+            let start = (corner.0 + across.0 * t, corner.1 + across.1 * t);
+            let end = (start.0 + along.0, start.1 + along.1);
+            // alternate lines run backwards, so the pen travels less between them
+            if line % 2 == 0 { (end, start) } else { (start, end) }
+        })
+        .collect()
+}
+
+///
+/// # Returns:
+/// - The segments around a closed polygon
+///
+fn outline(polygon: &[(f64, f64)]) -> Vec<Segment> {
+    (0..polygon.len()).map(|i| (polygon[i], polygon[(i + 1) % polygon.len()])).collect()
+}
+
+///
+/// A grid of square cells, shaded in alternating directions like woven strands.
+///
+fn basket_weave(reach: f64, cell_size: f64, line_spacing: f64) -> Vec<Segment> {
+    let cells = (reach / cell_size).ceil() as i64;
+    let mut segments = vec![];
+
+    for row in -cells..cells {
+        for column in -cells..cells {
+            let corner = (column as f64 * cell_size, row as f64 * cell_size);
+            let square = [corner, (corner.0 + cell_size, corner.1), (corner.0 + cell_size, corner.1 + cell_size), (corner.0, corner.1 + cell_size)];
+            segments.extend(outline(&square));
+
+            let (along, across) = if (row + column).rem_euclid(2) == 0 { ((cell_size, 0.), (0., cell_size)) } else { ((0., cell_size), (cell_size, 0.)) };
+            segments.extend(hatch(corner, along, across, line_spacing));
+        }
+    }
+    segments
+}
+
+///
+/// Bricks twice as long as they are wide, laid alternately across and down in diagonal stairs.
+///
+fn herringbone(reach: f64, cell_size: f64, line_spacing: f64) -> Vec<Segment> {
+    // in units of the brick width, each stair is a brick across at (k, k) and a brick down at
+    // (k + 2, k - 1), and the stairs repeat every 4 to the right
+    let width = cell_size / 2.;
+    let steps = (reach / width).ceil() as i64 + 4;
+    let mut segments = vec![];
+
+    for step in -steps..steps {
+        for stair in (-steps - step - 4).div_euclid(4)..=(steps - step + 4).div_euclid(4) {
+            let x = (step + stair * 4) as f64 * width;
+            let y = step as f64 * width;
+
+            for (corner, along, across) in [((x, y), (cell_size, 0.), (0., width)), ((x + cell_size, y - width), (0., cell_size), (width, 0.))] {
+                // bricks overlap their neighbours' sides only in part, so each brick draws just its
+                // left and top sides, which between them cover every joint once
+                let (right, bottom) = (along.0 + across.0, along.1 + across.1);
+                segments.push(((corner.0, corner.1 + bottom), corner));
+                segments.push((corner, (corner.0 + right, corner.1)));
+                segments.extend(hatch(corner, along, across, line_spacing));
+            }
+        }
+    }
+    segments
+}
+
+///
+/// Hexagons split into three rhombuses, which read as a stack of cubes seen from above. The left
+/// face of each cube is shaded.
+///
+fn isometric_cubes(reach: f64, cell_size: f64, line_spacing: f64) -> Vec<Segment> {
+    // pointy topped hexagons, with a side as long as the edge of a cube
+    let column_spacing = cell_size * 3f64.sqrt();
+    let row_spacing = cell_size * 1.5;
+    let (columns, rows) = ((reach / column_spacing).ceil() as i64 + 1, (reach / row_spacing).ceil() as i64 + 1);
+    let mut segments = vec![];
+
+    for row in -rows..=rows {
+        for column in -columns..=columns {
+            let offset = if row.rem_euclid(2) == 1 { column_spacing / 2. } else { 0. };
+            let centre = (column as f64 * column_spacing + offset, row as f64 * row_spacing);
+            let vertex = |index: i32| {
+                let angle = FRAC_PI_3 * index as f64 - FRAC_PI_3 / 2.;
+                (centre.0 + cell_size * angle.cos(), centre.1 + cell_size * angle.sin())
+            };
+            let hexagon: Vec<(f64, f64)> = (0..6).map(vertex).collect();
+
+            // the spokes to the bottom, upper left and upper right vertices are the near edges of the cube
+            segments.extend(outline(&hexagon));
+            segments.extend([2, 4, 0].map(|index| (centre, hexagon[index])));
+
+            let (bottom, upper_left) = (hexagon[2], hexagon[4]);
+            segments.extend(hatch(centre, (bottom.0 - centre.0, bottom.1 - centre.1), (upper_left.0 - centre.0, upper_left.1 - centre.1), line_spacing));
+        }
+    }
+    segments
+}
+
+///
+/// A honeycomb of flat topped hexagons, each filled with smaller hexagons inset by the line spacing.
+///
+fn hex_grid(reach: f64, cell_size: f64, line_spacing: f64) -> Vec<Segment> {
+    // the cell size is the width of each hexagon, corner to corner
+    let radius = cell_size / 2.;
+    let apothem = radius * 3f64.sqrt() / 2.;
+    let (columns, rows) = ((reach / (radius * 1.5)).ceil() as i64 + 1, (reach / (apothem * 2.)).ceil() as i64 + 1);
+    let mut segments = vec![];
+
+    for column in -columns..=columns {
+        for row in -rows..=rows {
+            let offset = if column.rem_euclid(2) == 1 { apothem } else { 0. };
+            let centre = (column as f64 * radius * 1.5, row as f64 * apothem * 2. + offset);
+
+            let mut inset = 0.;
+            while inset < apothem {
+                let scaled = radius * (apothem - inset) / apothem;
+                let hexagon: Vec<(f64, f64)> = (0..6).map(|index| {
+                    let angle = FRAC_PI_3 * index as f64;
+                    (centre.0 + scaled * angle.cos(), centre.1 + scaled * angle.sin())
+                }).collect();
+                segments.extend(outline(&hexagon));
+
+                if line_spacing == 0. {
+                    break;
+                }
+                inset += line_spacing;
+            }
+        }
+    }
+    segments
+}
+
+
+///
+/// The repeating tile pattern to draw.
+///
+/// - `BasketWeave`: Square cells shaded in alternating directions, like woven strands
+/// - `Herringbone`: Rectangular bricks laid alternately across and down in a zig-zag
+/// - `IsometricCubes`: Hexagons split into rhombuses, which read as stacked cubes
+/// - `HexGrid`: A honeycomb of hexagons
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    BasketWeave,
+    Herringbone,
+    IsometricCubes,
+    HexGrid,
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `pattern`: The repeating tile pattern to draw
+/// - `placement`: The margins of the drawing, which fills the area inside them
+/// - `cell_size`: The size of each tile, in millimetres. This is the side of a weave square, the
+///   length of a brick, the edge of a cube, or the width of a hexagon
+/// - `line_spacing`: The distance between the lines shading each tile, in millimetres, or 0 to
+///   only draw the outlines
+/// - `jitter`: The furthest each corner of a tile is randomly moved, in millimetres, for a hand
+///   drawn look
+/// - `rotation`: The angle the pattern is turned clockwise by, in degrees
+/// - `seed`: The seed of the jitter
+///
+#[derive(Serialize, Deserialize)]
+pub struct PatternParameters {
+    pattern: Pattern,
+
+    #[serde(default)]
+    placement: Placement,
+
+    cell_size: f64,
+    line_spacing: f64,
+    jitter: f64,
+    rotation: f64,
+    seed: u64,
+}
+
+impl DrawParameters for PatternParameters {}
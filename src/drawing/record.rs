@@ -0,0 +1,161 @@
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+use super::util::audio;
+use super::util::geometry;
+use super::util::resample;
+use super::util::placement::{Margins, Placement};
+
+///
+/// An empty struct to implement the "Record" draw method on.
+///
+pub struct RecordMethod;
+
+impl DrawMethod for RecordMethod {
+    type DrawParameters = RecordParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "record"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Record"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new record drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        RecordParameters {
+            audio_path: String::new(), placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            inner_radius: 20., outer_radius: 80., rotations: 40., amplitude_scale: 1., samples_per_rotation: 400, spacing_mm: None,
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the record drawing method.
+    /// This drawing method draws an audio file as the groove of a vinyl record, a spiral from the
+    /// outer edge inwards which wiggles further from its path the louder the audio is.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &RecordParameters) -> Result<Vec<Stroke>, String> {
+
+        if parameters.audio_path.is_empty() {
+            return Err("Select an audio file".to_owned());
+        }
+        if !(parameters.inner_radius >= 0. && parameters.outer_radius > parameters.inner_radius) {
+            return Err("The outer radius must be larger than the inner radius".to_owned());
+        }
+        if !(parameters.rotations.is_finite() && parameters.rotations > 0. && parameters.samples_per_rotation >= 3) {
+            return Err("The record needs a positive number of rotations, with at least 3 samples per rotation".to_owned());
+        }
+        if !(parameters.amplitude_scale.is_finite() && parameters.amplitude_scale >= 0.) {
+            return Err("The amplitude scale can not be negative".to_owned());
+        }
+
+        // the record is round, so is centred in the area inside the margins
+        let area = parameters.placement.area(physical_dimensions)?;
+        let (centre_x, centre_y) = (area.x + area.width / 2., area.y + area.height / 2.);
+        let max_radius = parameters.outer_radius + parameters.amplitude_scale;
+        if max_radius > area.width.min(area.height) / 2. {
+            return Err(format!("The record is {:.1}mm across, but the area inside the margins is {:.1}mm by {:.1}mm", max_radius * 2., area.width, area.height));
+        }
+
+        // the samples are spread evenly along the spiral, as far apart as `samples_per_rotation` puts them on the outer turn
+        let end_theta = parameters.rotations * std::f64::consts::TAU;
+        let max_segment = std::f64::consts::TAU * parameters.outer_radius / parameters.samples_per_rotation as f64;
+        let spiral_radius = |theta: f64| parameters.outer_radius - (parameters.outer_radius - parameters.inner_radius) * theta / end_theta;
+        let spiral = geometry::polar_path((0., 0.), spiral_radius, (0., end_theta), max_segment);
+
+        let num_samples = spiral.len();
+        let sample_heights = match audio::get_sampled_waveform(&parameters.audio_path, num_samples) {
+            Ok(val) => val,
+            Err(err) => {
+                return Err(format!("Couldn't open audio file: {}", err));
+            }
+        };
+        let max = sample_heights.iter().copied().max().unwrap_or(0).max(1); // silent audio would divide by zero
+        let scalar = parameters.amplitude_scale / max as f64;
+
+        let mut points = Vec::with_capacity(num_samples + 1);
+        points.push((centre_x, centre_y - parameters.outer_radius));
+
+        for (sample_num, (&(x, y), height)) in spiral.iter().zip(sample_heights.iter()).enumerate() {
+            // the groove swings either side of the spiral on alternate samples, like a cut groove
+            let side = if sample_num % 2 == 0 { 1. } else { -1. };
+            let radius = x.hypot(y);
+            let swing = 1. + side * *height as f64 * scalar / radius.max(f64::EPSILON);
+
+            // the spiral is turned to start at the top of the record, and run clockwise
+            points.push((centre_x + y * swing, centre_y - x * swing));
+        }
+
+        // the groove's swing spaces its points unevenly, widest where the audio is loud
+        if let Some(spacing_mm) = parameters.spacing_mm {
+            points = resample::by_arc_length(&points, spacing_mm);
+        }
+
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, points.len());
+        surface.sample_xy(points[0].0, points[0].1)?;
+        surface.raise_pen(false);
+        for &(x, y) in &points[1..] {
+            surface.sample_xy(x, y)?;
+        }
+
+        Ok(surface.into_strokes())
+    }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `audio_path`: The path of the audio file
+/// - `placement`: The margins of the drawing, the record being centred in the area inside them
+/// - `inner_radius`: The radius the spiral ends at, in millimetres
+/// - `outer_radius`: The radius the spiral starts at, in millimetres
+/// - `rotations`: The number of turns of the spiral between the outer and inner radius
+/// - `amplitude_scale`: How far the groove swings from the spiral at the loudest sample, in millimetres
+/// - `samples_per_rotation`: The number of samples taken on the audio waveform on the outer turn of the spiral. The
+///   samples are spread evenly along the spiral, so inner turns take fewer
+/// - `spacing_mm`: If set, the groove is resampled to points this far apart, in millimetres. Spacings
+///   wider than the groove's swing smooth the waveform away
+///
+#[derive(Serialize, Deserialize)]
+pub struct RecordParameters {
+    audio_path: String,
+
+    #[serde(default)]
+    placement: Placement,
+
+    inner_radius: f64,
+    outer_radius: f64,
+    rotations: f64,
+    amplitude_scale: f64,
+    samples_per_rotation: usize,
+
+    #[serde(default)]
+    spacing_mm: Option<f64>,
+}
+
+impl DrawParameters for RecordParameters {}
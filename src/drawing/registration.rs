@@ -0,0 +1,181 @@
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::stroke::{self, Stroke};
+
+use super::util::placement::{Margins, Placement};
+
+///
+/// The closest the ticks along the edges may be, in millimetres.
+///
+const MIN_TICK_SPACING_MM: f64 = 1.;
+
+///
+/// An empty struct to implement the "Registration" draw method on.
+///
+pub struct RegistrationMethod;
+
+impl DrawMethod for RegistrationMethod {
+    type DrawParameters = RegistrationParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "registration"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Registration"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new registration drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        RegistrationParameters { placement: Placement { margins: Margins::symmetric(15., 15.), ..Default::default() }, mark_size: 10., centre_cross: false, tick_spacing: 0., crosses: vec![] }
+    }
+
+    ///
+    /// Generates the strokes to perform the registration drawing method.
+    /// This drawing method draws alignment marks: crop marks at the corners of the margins, and
+    /// optionally a centre cross, ticks along the margins and crosses at chosen positions. Paper
+    /// can be lined up against the marks to re-register it for another pen, to assemble tiles,
+    /// or to line up pre-printed paper.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &RegistrationParameters) -> Result<Vec<Stroke>, String> {
+        parameters.marks(physical_dimensions)
+    }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls. These can also be drawn
+/// before any other drawing, with `DrawingJob::with_registration_marks`.
+///
+/// # Fields:
+/// - `placement`: The margins, whose corners the crop marks are drawn at
+/// - `mark_size`: The length of each arm of the crop marks and the width of the crosses, in millimetres
+/// - `centre_cross`: Whether to draw a cross in the centre of the margins
+/// - `tick_spacing`: The distance between ticks along the margins, in millimetres, or 0 for no ticks
+/// - `crosses`: The positions of any more crosses, in millimetres from the top left of the page
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegistrationParameters {
+    #[serde(default)]
+    pub placement: Placement,
+
+    pub mark_size: f64,
+
+    #[serde(default)]
+    pub centre_cross: bool,
+    #[serde(default)]
+    pub tick_spacing: f64,
+    #[serde(default)]
+    pub crosses: Vec<(f64, f64)>,
+}
+
+impl RegistrationParameters {
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    ///
+    /// # Returns:
+    /// - The strokes of the marks: the crop marks clockwise from the top left, then the ticks,
+    ///   the centre cross and the other crosses
+    /// - An error, explaning why the marks could not be drawn
+    ///
+    pub fn marks(&self, physical_dimensions: &PhysicalDimensions) -> Result<Vec<Stroke>, String> {
+        if !(self.mark_size.is_finite() && self.mark_size > 0.) {
+            return Err("The mark size must be above 0".to_owned());
+        }
+        if !(self.tick_spacing == 0. || self.tick_spacing.is_finite() && self.tick_spacing >= MIN_TICK_SPACING_MM) {
+            return Err(format!("The tick spacing must be 0, or at least {}mm", MIN_TICK_SPACING_MM));
+        }
+
+        let area = self.placement.area(physical_dimensions)?;
+        let (left, top, right, bottom) = (area.x, area.y, area.x + area.width, area.y + area.height);
+        let arm = self.mark_size.min(area.width / 2.).min(area.height / 2.);
+        let line = |points: Vec<(f64, f64)>, label: &str| Stroke { points, pen_up: false, layer: 0, label: Some(label.to_owned()) };
+        let cross = |(x, y): (f64, f64), label: &str| [line(vec![(x - self.mark_size / 2., y), (x + self.mark_size / 2., y)], label), line(vec![(x, y - self.mark_size / 2.), (x, y + self.mark_size / 2.)], label)];
+
+        // each crop mark is an L in the corner, pointing along the two margins
+        let mut strokes = vec![
+            line(vec![(left, top + arm), (left, top), (left + arm, top)], "crop marks"),
+            line(vec![(right - arm, top), (right, top), (right, top + arm)], "crop marks"),
+            line(vec![(right, bottom - arm), (right, bottom), (right - arm, bottom)], "crop marks"),
+            line(vec![(left + arm, bottom), (left, bottom), (left, bottom - arm)], "crop marks"),
+        ];
+
+        // ticks point inwards from the margins, stopping short of the crop marks
+        if self.tick_spacing > 0. {
+            let tick = self.mark_size / 2.;
+            let mut offset = self.tick_spacing;
+            while offset < area.width - arm {
+                if offset > arm {
+                    strokes.push(line(vec![(left + offset, top), (left + offset, top + tick)], "ticks"));
+                    strokes.push(line(vec![(left + offset, bottom), (left + offset, bottom - tick)], "ticks"));
+                }
+                offset += self.tick_spacing;
+            }
+            let mut offset = self.tick_spacing;
+            while offset < area.height - arm {
+                if offset > arm {
+                    strokes.push(line(vec![(left, top + offset), (left + tick, top + offset)], "ticks"));
+                    strokes.push(line(vec![(right, top + offset), (right - tick, top + offset)], "ticks"));
+                }
+                offset += self.tick_spacing;
+            }
+        }
+
+        if self.centre_cross {
+            strokes.extend(cross((left + area.width / 2., top + area.height / 2.), "centre cross"));
+        }
+        for &position in &self.crosses {
+            strokes.extend(cross(position, "crosses"));
+        }
+
+        Ok(strokes)
+    }
+
+    ///
+    /// Draws the marks before a drawing, so any drawing can be registered against them.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `drawing`: The (instruction set, start_x, start_y) of the drawing
+    ///
+    /// # Returns:
+    /// - The (instruction set, start_x, start_y) of the marks followed by the drawing
+    /// - An error, explaning why the marks could not be drawn
+    ///
+    pub fn prepend_to(&self, physical_dimensions: &PhysicalDimensions, drawing: (Vec<u8>, f64, f64)) -> Result<(Vec<u8>, f64, f64), String> {
+        let (ins_bytes, start_x, start_y) = drawing;
+        let mut strokes = self.marks(physical_dimensions)?;
+
+        // the pen is lifted and moved to where the drawing starts, which is where its instructions expect it to be
+        if !ins_bytes.is_empty() {
+            strokes.push(Stroke { points: vec![(start_x, start_y), (start_x, start_y)], pen_up: true, layer: 0, label: None });
+        }
+        let (mut marks_bytes, marks_x, marks_y) = stroke::compile(&strokes, physical_dimensions)?;
+
+        marks_bytes.extend(ins_bytes);
+        Ok((marks_bytes, marks_x, marks_y))
+    }
+}
+
+impl DrawParameters for RegistrationParameters {}
@@ -0,0 +1,278 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::drawing::DrawMethod;
+use crate::drawing::stroke::{self, Stroke};
+use crate::drawing::{atom, calibration, cascade, dunes, entropy, fractal, handwriting, islands, lines, map, masked, pattern, registration, shades, stars, superformula, text};
+#[cfg(feature = "image")]
+use crate::drawing::{bubbles, scribble, waves};
+#[cfg(feature = "audio")]
+use crate::drawing::{record, vinyl};
+#[cfg(feature = "plugin")]
+use crate::drawing::custom;
+use crate::hardware::PhysicalDimensions;
+
+///
+/// # Returns:
+/// - The (backend ID, frontend display name) of every drawing method enabled in this build
+///
+pub fn methods() -> Vec<(&'static str, &'static str)> {
+    #[allow(unused_mut)]
+    let mut methods = vec![
+        (lines::LinesMethod.get_id(), lines::LinesMethod.get_formatted_name()),
+        (cascade::CascadeMethod.get_id(), cascade::CascadeMethod.get_formatted_name()),
+        (islands::IslandsMethod.get_id(), islands::IslandsMethod.get_formatted_name()),
+        (dunes::DunesMethod.get_id(), dunes::DunesMethod.get_formatted_name()),
+        (entropy::EntropyMethod.get_id(), entropy::EntropyMethod.get_formatted_name()),
+        (shades::ShadesMethod.get_id(), shades::ShadesMethod.get_formatted_name()),
+        (atom::AtomMethod.get_id(), atom::AtomMethod.get_formatted_name()),
+        (calibration::CalibrationMethod.get_id(), calibration::CalibrationMethod.get_formatted_name()),
+        (superformula::SuperformulaMethod.get_id(), superformula::SuperformulaMethod.get_formatted_name()),
+        (fractal::FractalMethod.get_id(), fractal::FractalMethod.get_formatted_name()),
+        (pattern::PatternMethod.get_id(), pattern::PatternMethod.get_formatted_name()),
+        (stars::StarsMethod.get_id(), stars::StarsMethod.get_formatted_name()),
+        (map::MapMethod.get_id(), map::MapMethod.get_formatted_name()),
+        (handwriting::HandwritingMethod.get_id(), handwriting::HandwritingMethod.get_formatted_name()),
+        (masked::MaskedMethod.get_id(), masked::MaskedMethod.get_formatted_name()),
+        (registration::RegistrationMethod.get_id(), registration::RegistrationMethod.get_formatted_name()),
+        (text::TextMethod.get_id(), text::TextMethod.get_formatted_name()),
+    ];
+
+    #[cfg(feature = "image")]
+    methods.extend([
+        (scribble::ScribbleMethod.get_id(), scribble::ScribbleMethod.get_formatted_name()),
+        (bubbles::BubblesMethod.get_id(), bubbles::BubblesMethod.get_formatted_name()),
+        (waves::WavesMethod.get_id(), waves::WavesMethod.get_formatted_name()),
+    ]);
+    #[cfg(feature = "audio")]
+    methods.extend([
+        (vinyl::VinylMethod.get_id(), vinyl::VinylMethod.get_formatted_name()),
+        (record::RecordMethod.get_id(), record::RecordMethod.get_formatted_name()),
+    ]);
+    #[cfg(feature = "plugin")]
+    methods.push((custom::CustomMethod.get_id(), custom::CustomMethod.get_formatted_name()));
+
+    methods
+}
+
+///
+/// Generates the instructions of a drawing method chosen by its backend ID, with its parameters
+/// given as JSON. This lets frontends select a method at runtime without matching on every ID.
+/// Methods disabled in this build are reported as missing.
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+/// - `physical_dimensions`: A physical dimension object, including paper width / height
+/// - `parameters_json`: The drawing method's parameters, as a JSON object
+///
+/// # Returns:
+/// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+/// - An error explaining why the drawing instructions could not be generated
+///
+pub fn gen_instructions(method_id: &str, physical_dimensions: &PhysicalDimensions, parameters_json: &str) -> Result<(Vec<u8>, f64, f64), String> {
+    stroke::compile(&gen_strokes(method_id, physical_dimensions, parameters_json)?, physical_dimensions)
+}
+
+///
+/// Generates the strokes of a drawing method chosen by its backend ID, with its parameters given
+/// as JSON, so methods can be combined or post-processed before they are compiled.
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+/// - `physical_dimensions`: A physical dimension object, including paper width / height
+/// - `parameters_json`: The drawing method's parameters, as a JSON object
+///
+/// # Returns:
+/// - The strokes of the drawing, in the order they are drawn
+/// - An error explaining why the strokes could not be generated
+///
+pub fn gen_strokes(method_id: &str, physical_dimensions: &PhysicalDimensions, parameters_json: &str) -> Result<Vec<Stroke>, String> {
+    match method_id {
+        "lines" => gen_with(lines::LinesMethod, physical_dimensions, parameters_json),
+        "cascade" => gen_with(cascade::CascadeMethod, physical_dimensions, parameters_json),
+        #[cfg(feature = "image")]
+        "scribble" => gen_with(scribble::ScribbleMethod, physical_dimensions, parameters_json),
+        #[cfg(feature = "image")]
+        "bubbles" => gen_with(bubbles::BubblesMethod, physical_dimensions, parameters_json),
+        "islands" => gen_with(islands::IslandsMethod, physical_dimensions, parameters_json),
+        "dunes" => gen_with(dunes::DunesMethod, physical_dimensions, parameters_json),
+        #[cfg(feature = "image")]
+        "waves" => gen_with(waves::WavesMethod, physical_dimensions, parameters_json),
+        "entropy" => gen_with(entropy::EntropyMethod, physical_dimensions, parameters_json),
+        "shades" => gen_with(shades::ShadesMethod, physical_dimensions, parameters_json),
+        #[cfg(feature = "audio")]
+        "vinyl" => gen_with(vinyl::VinylMethod, physical_dimensions, parameters_json),
+        #[cfg(feature = "audio")]
+        "record" => gen_with(record::RecordMethod, physical_dimensions, parameters_json),
+        "atom" => gen_with(atom::AtomMethod, physical_dimensions, parameters_json),
+        "calibration" => gen_with(calibration::CalibrationMethod, physical_dimensions, parameters_json),
+        "superformula" => gen_with(superformula::SuperformulaMethod, physical_dimensions, parameters_json),
+        "fractal" => gen_with(fractal::FractalMethod, physical_dimensions, parameters_json),
+        "pattern" => gen_with(pattern::PatternMethod, physical_dimensions, parameters_json),
+        "stars" => gen_with(stars::StarsMethod, physical_dimensions, parameters_json),
+        "map" => gen_with(map::MapMethod, physical_dimensions, parameters_json),
+        "handwriting" => gen_with(handwriting::HandwritingMethod, physical_dimensions, parameters_json),
+        "masked" => gen_with(masked::MaskedMethod, physical_dimensions, parameters_json),
+        "registration" => gen_with(registration::RegistrationMethod, physical_dimensions, parameters_json),
+        "text" => gen_with(text::TextMethod, physical_dimensions, parameters_json),
+        #[cfg(feature = "plugin")]
+        "custom" => gen_with(custom::CustomMethod, physical_dimensions, parameters_json),
+        _ => Err(format!("There is no drawing method with the ID \"{}\"", method_id)),
+    }
+}
+
+///
+/// Gets the default parameters of a drawing method chosen by its backend ID, as JSON, so
+/// frontends can fill in a form with sensible starting values rather than leaving it empty.
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+///
+/// # Returns:
+/// - The drawing method's default parameters, as a JSON object
+/// - An error if there is no such method, or its parameters could not be serialised
+///
+pub fn default_parameters(method_id: &str) -> Result<String, String> {
+    match method_id {
+        "lines" => default_with(lines::LinesMethod),
+        "cascade" => default_with(cascade::CascadeMethod),
+        #[cfg(feature = "image")]
+        "scribble" => default_with(scribble::ScribbleMethod),
+        #[cfg(feature = "image")]
+        "bubbles" => default_with(bubbles::BubblesMethod),
+        "islands" => default_with(islands::IslandsMethod),
+        "dunes" => default_with(dunes::DunesMethod),
+        #[cfg(feature = "image")]
+        "waves" => default_with(waves::WavesMethod),
+        "entropy" => default_with(entropy::EntropyMethod),
+        "shades" => default_with(shades::ShadesMethod),
+        #[cfg(feature = "audio")]
+        "vinyl" => default_with(vinyl::VinylMethod),
+        #[cfg(feature = "audio")]
+        "record" => default_with(record::RecordMethod),
+        "atom" => default_with(atom::AtomMethod),
+        "calibration" => default_with(calibration::CalibrationMethod),
+        "superformula" => default_with(superformula::SuperformulaMethod),
+        "fractal" => default_with(fractal::FractalMethod),
+        "pattern" => default_with(pattern::PatternMethod),
+        "stars" => default_with(stars::StarsMethod),
+        "map" => default_with(map::MapMethod),
+        "handwriting" => default_with(handwriting::HandwritingMethod),
+        "masked" => default_with(masked::MaskedMethod),
+        "registration" => default_with(registration::RegistrationMethod),
+        "text" => default_with(text::TextMethod),
+        #[cfg(feature = "plugin")]
+        "custom" => default_with(custom::CustomMethod),
+        _ => Err(format!("There is no drawing method with the ID \"{}\"", method_id)),
+    }
+}
+
+///
+/// Parses the parameters of a drawing method, then generates its strokes.
+///
+/// # Parameters:
+/// - `method`: The drawing method
+/// - `physical_dimensions`: A physical dimension object, including paper width / height
+/// - `parameters_json`: The drawing method's parameters, as a JSON object
+///
+/// # Returns:
+/// - The strokes of the drawing, in the order they are drawn
+/// - An error explaining why the parameters were invalid, or the strokes could not be generated
+///
+fn gen_with<M: DrawMethod>(method: M, physical_dimensions: &PhysicalDimensions, parameters_json: &str) -> Result<Vec<Stroke>, String>
+where
+    M::DrawParameters: DeserializeOwned {
+    let parameters: M::DrawParameters = match serde_json::from_str(parameters_json) {
+        Ok(val) => val,
+        Err(err) => return Err(format!("Invalid parameters for {}: {}", method.get_formatted_name(), err)),
+    };
+
+    method.gen_strokes(physical_dimensions, &parameters)
+}
+
+///
+/// Serialises the default parameters of a drawing method.
+///
+/// # Parameters:
+/// - `method`: The drawing method
+///
+/// # Returns:
+/// - The drawing method's default parameters, as a JSON object
+/// - An error explaining why the parameters could not be serialised
+///
+fn default_with<M: DrawMethod>(method: M) -> Result<String, String>
+where
+    M::DrawParameters: Serialize {
+    serde_json::to_string(&method.default_parameters()).map_err(|err| format!("Invalid default parameters for {}: {}", method.get_formatted_name(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_method_has_default_parameters() {
+        let physical_dimensions = PhysicalDimensions::new(600., 195., 150., 210., 297.);
+        for (method_id, name) in methods() {
+            let parameters_json = default_parameters(method_id).unwrap();
+            assert!(serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&parameters_json).is_ok());
+
+            // methods drawing from a file have no default file, but their parameters must still parse
+            if let Err(err) = gen_strokes(method_id, &physical_dimensions, &parameters_json) {
+                assert!(!err.starts_with(&format!("Invalid parameters for {}", name)), "{}", err);
+            }
+        }
+
+        for method_id in ["lines", "cascade", "islands", "dunes", "entropy", "shades", "atom", "calibration", "superformula", "fractal", "pattern", "stars", "masked", "registration"] {
+            assert!(gen_strokes(method_id, &physical_dimensions, &default_parameters(method_id).unwrap()).is_ok(), "{}", method_id);
+        }
+        assert!(default_parameters("missing").is_err());
+    }
+
+    #[test]
+    fn masks_can_be_offset() {
+        let physical_dimensions = PhysicalDimensions::new(600., 195., 150., 210., 297.);
+        let bounds = |offset: f64| {
+            let mut parameters: serde_json::Value = serde_json::from_str(&default_parameters("masked").unwrap()).unwrap();
+            parameters["offset"] = serde_json::json!(offset);
+            let strokes = gen_strokes("masked", &physical_dimensions, &parameters.to_string()).unwrap();
+            strokes.iter().flat_map(|stroke| stroke.points.iter()).fold((f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY), |(min_x, min_y, max_x, max_y), point| {
+                (min_x.min(point.0), min_y.min(point.1), max_x.max(point.0), max_y.max(point.1))
+            })
+        };
+
+        // the shaded lines cross the whole mask, so shrinking it moves their ends 10mm further in
+        let (plain, shrunk) = (bounds(0.), bounds(-10.));
+        assert!((shrunk.0 - plain.0 - 10.).abs() < 0.1 && (plain.2 - shrunk.2 - 10.).abs() < 0.1, "{:?} {:?}", plain, shrunk);
+        assert!(shrunk.1 >= plain.1 && shrunk.3 <= plain.3);
+
+        // a mask grown past the page is clipped to it
+        let grown = bounds(500.);
+        assert!(grown.0 >= 0. && grown.1 >= 0. && grown.2 <= 210. && grown.3 <= 297., "{:?}", grown);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn separated_bubbles_are_drawn_on_a_layer_per_pen() {
+        let image = image::RgbImage::from_fn(60, 30, |x, _| if x < 30 { image::Rgb([0, 255, 255]) } else { image::Rgb([0, 0, 0]) });
+        let mut bytes = vec![];
+        image.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        crate::drawing::util::files::insert("separated-bubbles.png", bytes);
+
+        let physical_dimensions = PhysicalDimensions::new(600., 195., 150., 210., 297.);
+        let mut parameters: serde_json::Value = serde_json::from_str(&default_parameters("bubbles").unwrap()).unwrap();
+        parameters["image_path"] = serde_json::json!("separated-bubbles.png");
+        parameters["num_stipples"] = serde_json::json!(100);
+        parameters["num_iterations"] = serde_json::json!(2);
+        parameters["seed"] = serde_json::json!(1);
+        parameters["separation"] = serde_json::json!("Cmyk");
+        let strokes = gen_strokes("bubbles", &physical_dimensions, &parameters.to_string()).unwrap();
+        crate::drawing::util::files::remove("separated-bubbles.png");
+
+        // the cyan layer is drawn, then the black one, each labelled with its pen
+        let drawn: Vec<(usize, Option<String>)> = strokes.iter().filter(|stroke| !stroke.pen_up).map(|stroke| (stroke.layer, stroke.label.clone())).collect();
+        let mut layers = drawn.clone();
+        layers.dedup();
+        assert_eq!(layers, vec![(0, Some("cyan".to_owned())), (1, Some("black".to_owned()))]);
+    }
+}
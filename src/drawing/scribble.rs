@@ -3,6 +3,7 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 use crate::drawing::util::*;
 
 ///
@@ -30,7 +31,19 @@ impl DrawMethod for ScribbleMethod {
     }
 
     ///
-    /// Generates instructions to perform the scribbles drawing method.
+    /// # Returns:
+    /// - The parameters a new scribble drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        ScribbleParameters {
+            image_path: String::new(), width: 150., height: 150., horizontal_offset: 30., vertical_offset: 30.,
+            brightness_threshold: 200, num_stipples: 2000, num_iterations: 20, relaxation_tendency: 50, scribble_size: 50,
+            tone_curve: Default::default(), seeding: Default::default(), seed: None, tour: Default::default(),
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the scribbles drawing method.
     /// This drawing method uses a weighted voronoi stippling technique in order to create an even
     /// distribution of points on a plane. Finally, it creates circles in conjunction with these
     /// points to simulate scribbles.
@@ -40,10 +53,10 @@ impl DrawMethod for ScribbleMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error explaining why the drawing instructions could not be generated
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &ScribbleParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &ScribbleParameters) -> Result<Vec<Stroke>, String> {
         
         if parameters.image_path.is_empty() {
             return Err("Select an input image".to_owned());
@@ -51,11 +64,12 @@ impl DrawMethod for ScribbleMethod {
 
         let relaxation_coefficient = parameters.relaxation_tendency as f32 / 100.;
         
-        let stippled_points: Vec<stipple_structures::Point> = match stipple::stipple_points(parameters.image_path.as_str(), parameters.num_stipples, parameters.num_iterations, relaxation_coefficient, parameters.brightness_threshold) {
+        let stippled_points: Vec<stipple_structures::Point> = match stipple::stipple_points(parameters.image_path.as_str(), &parameters.tone_curve, parameters.num_stipples, parameters.num_iterations, relaxation_coefficient, parameters.brightness_threshold, parameters.seeding, parameters.seed) {
             Ok(val) => val,
             Err(err_str) => return Err(err_str),
         };
-        let tour = stipple::nearest_neighbour_tour(&stippled_points);
+        let mut tour = stipple::nearest_neighbour_tour(&stippled_points, &parameters.tour);
+        tour::improve_tour(&stippled_points, &mut tour, tour::DEFAULT_TIME_BUDGET, tour::DEFAULT_MOVE_BUDGET);
 
         let max_x = stippled_points.iter().max_by_key(|p| p.x).unwrap().x.into_inner();
         let max_y = stippled_points.iter().max_by_key(|p| p.y).unwrap().y.into_inner();
@@ -70,7 +84,7 @@ impl DrawMethod for ScribbleMethod {
             let scaled_x = stippled_points[t[0]].x.into_inner() / biggest_divisor;
             let scaled_y = stippled_points[t[0]].y.into_inner() / biggest_divisor;
             
-            let dist_to_next = ((stippled_points[t[1]].x / biggest_divisor - stippled_points[t[0]].x / biggest_divisor).powi(2) + (stippled_points[t[1]].y / biggest_divisor - stippled_points[t[0]].y / biggest_divisor).powi(2)).sqrt();
+            let dist_to_next = stippled_points[t[0]].distance(&stippled_points[t[1]]) / biggest_divisor;
 
             let radius = dist_to_next / radius_divisor;
             let iterations: usize = ((radius * 4.) as usize).max(6);
@@ -87,7 +101,7 @@ impl DrawMethod for ScribbleMethod {
             }
         }
 
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -100,6 +114,10 @@ impl DrawMethod for ScribbleMethod {
 /// - `relaxation_tendency`: A float to represent a scalar multiplier for the relaxation tendency
 /// - `scribble_size`: A scalar size to affect the circles
 /// - `vertical_offset`: A y-offset of the entire drawing
+/// - `tone_curve`: The curve the brightness of the image is remapped through
+/// - `seeding`: How the stipple points are placed before they are relaxed
+/// - `seed`: A seed for the stipple points, or None for different points every time
+/// - `tour`: Where the path through the stipple points starts, and how it avoids long jumps
 ///
 #[derive(Serialize, Deserialize)]
 pub struct ScribbleParameters {
@@ -116,6 +134,18 @@ pub struct ScribbleParameters {
     num_iterations: usize,
     relaxation_tendency: u8,
     scribble_size: usize,
+
+    #[serde(default)]
+    tone_curve: tone::ToneCurve,
+
+    #[serde(default)]
+    seeding: stipple::Seeding,
+
+    #[serde(default)]
+    seed: Option<u64>,
+
+    #[serde(default)]
+    tour: stipple::TourOptions,
 }
 
 impl DrawParameters for ScribbleParameters {}
@@ -2,6 +2,8 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+use crate::drawing::util::placement::{Margins, Placement};
 
 ///
 /// An empty struct to implement the "Shades" draw method on.
@@ -27,6 +29,14 @@ impl DrawMethod for ShadesMethod {
         "Shades"
     }
 
+    ///
+    /// # Returns:
+    /// - The parameters a new shades drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        ShadesParameters { placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() }, num_lines: 40, power: 2 }
+    }
+
     ///
     /// This drawing methods creates lines that converge into each other, within a box.
     /// It is the first / test drawing method for the pen lifting off the page.
@@ -36,31 +46,32 @@ impl DrawMethod for ShadesMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &ShadesParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &ShadesParameters) -> Result<Vec<Stroke>, String> {
         
-        let offset_left = (physical_dimensions.page_width() - parameters.width) / 2.;
-        let offset_top = (physical_dimensions.page_height() - parameters.height) / 2.;
+        // the shades have no shape of their own, so fill the area inside the margins
+        let area = parameters.placement.area(physical_dimensions)?;
+        let (offset_left, offset_top) = (area.x, area.y);
 
         let mut heights = Vec::with_capacity(parameters.num_lines);
         for i in 0..parameters.num_lines {
-            heights.push((parameters.height) * ( (i as f64 / parameters.num_lines as f64).powf(parameters.power as f64 / 10.) ));
+            heights.push((area.height) * ( (i as f64 / parameters.num_lines as f64).powf(parameters.power as f64 / 10.) ));
         }
     
-        let mut surface = DrawSurface::new(physical_dimensions);
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, 1 + parameters.num_lines * 2);
 
         surface.sample_xy(offset_left, offset_top).unwrap();
         
         for i in 0..parameters.num_lines {
             surface.sample_xy(offset_left, offset_top + heights[i]).unwrap();
             surface.raise_pen(false);
-            surface.sample_xy(offset_left + parameters.width, offset_top + heights[i]).unwrap();
+            surface.sample_xy(offset_left + area.width, offset_top + heights[i]).unwrap();
             surface.raise_pen(true);
         }
         
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -69,15 +80,14 @@ impl DrawMethod for ShadesMethod {
 /// A set of parameters to instruct the generation of the draw calls.
 ///
 /// # Fields:
-/// - `width`: The horizontal margin of the drawing, in millimetres
-/// - `height`: The horizontal margin of the drawing, in millimetres
+/// - `placement`: The margins of the drawing, which fills the area inside them
 /// - `num_lines`: The number of horizontal lines to draw
 /// - `power`: The tendency for the lines to converge
 ///
 #[derive(Serialize, Deserialize)]
 pub struct ShadesParameters {
-    pub width: f64,
-    pub height: f64,
+    #[serde(default)]
+    pub placement: Placement,
 
     pub num_lines: usize,
     pub power: usize,
@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+
+use crate::drawing::util::delaunay;
+use crate::drawing::util::geometry::Point;
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+#[cfg(feature = "image")]
+use super::util::files;
+use super::util::placement::{Margins, Placement, Rect};
+
+///
+/// The most random positions tried per star, before giving up on filling a mostly white mask.
+///
+const ATTEMPTS_PER_STAR: usize = 1000;
+
+///
+/// The gap left between a constellation line and the star it joins, in millimetres, on top of
+/// the size of the star.
+///
+const LINE_GAP_MM: f64 = 1.;
+
+///
+/// An empty struct to implement the "Stars" draw method on.
+///
+pub struct StarsMethod;
+
+impl DrawMethod for StarsMethod {
+    type DrawParameters = StarsParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "stars"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Stars"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new stars drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        StarsParameters {
+            seed: 1, num_stars: 300, placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            star_size: 6., bright_fraction: 0.1, constellation_stars: 12, mask_path: String::new(),
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the stars drawing method.
+    /// This drawing method scatters a field of stars, where faint stars are dots and bright stars
+    /// are crosses sized by their brightness. The brightest stars can be joined into
+    /// constellations, and an image can mask where the stars are dense.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &StarsParameters) -> Result<Vec<Stroke>, String> {
+
+        if !(0. ..=1.).contains(&parameters.bright_fraction) {
+            return Err("The fraction of bright stars must be between 0 and 1".to_owned());
+        }
+        if !(parameters.star_size.is_finite() && parameters.star_size > 0.) {
+            return Err("The star size must be above 0".to_owned());
+        }
+
+        let area = parameters.placement.area(physical_dimensions)?;
+        let density = Density::new(parameters, physical_dimensions, area)?;
+
+        // faint stars are far more common than bright ones
+        let mut rng = StdRng::seed_from_u64(parameters.seed);
+        let mut stars = vec![];
+        for _ in 0..parameters.num_stars * ATTEMPTS_PER_STAR {
+            if stars.len() == parameters.num_stars {
+                break;
+            }
+
+            let (x, y) = (area.x + rng.random::<f64>() * area.width, area.y + rng.random::<f64>() * area.height);
+            if rng.random::<f64>() < density.at(x, y) {
+                stars.push(((x, y), rng.random::<f64>().powi(3)));
+            }
+        }
+        stars.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let num_bright = (stars.len() as f64 * parameters.bright_fraction).round() as usize;
+        let arm = |index: usize| if index < num_bright { parameters.star_size * (0.5 + stars[index].1 / 2.) / 2. } else { 0. };
+
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, stars.len() * 4 + parameters.constellation_stars * 6);
+        for (index, &((x, y), _)) in stars.iter().enumerate() {
+            // dots are a touch of the pen, crosses are two lines through the star
+            let arm = arm(index);
+            let lines = if arm == 0. {
+                vec![((x, y), (x, y))]
+            } else {
+                vec![((x - arm, y), (x + arm, y)), ((x, y - arm), (x, y + arm))]
+            };
+
+            for (start, end) in lines {
+                let (start, end) = (clamp_to(&area, start), clamp_to(&area, end));
+                surface.raise_pen(true);
+                surface.sample_xy(start.0, start.1)?;
+                surface.raise_pen(false);
+                surface.sample_xy(end.0, end.1)?;
+            }
+        }
+
+        // constellation lines stop short of the stars they join, like a star chart
+        let brightest = &stars[..parameters.constellation_stars.min(stars.len())];
+        for (a, b) in relative_neighbourhood_graph(&brightest.iter().map(|(position, _)| *position).collect::<Vec<_>>()) {
+            let ((start, end), (start_gap, end_gap)) = ((brightest[a].0, brightest[b].0), (arm(a) + LINE_GAP_MM, arm(b) + LINE_GAP_MM));
+            let length = (end.0 - start.0).hypot(end.1 - start.1);
+            if length <= start_gap + end_gap {
+                continue;
+            }
+
+            let (dx, dy) = ((end.0 - start.0) / length, (end.1 - start.1) / length);
+            surface.raise_pen(true);
+            surface.sample_xy(start.0 + dx * start_gap, start.1 + dy * start_gap)?;
+            surface.raise_pen(false);
+            surface.sample_xy(end.0 - dx * end_gap, end.1 - dy * end_gap)?;
+        }
+
+        Ok(surface.into_strokes())
+    }
+}
+
+///
+/// # Returns:
+/// - The point moved inside the rectangle, so crosses near the margins don't cross them
+///
+fn clamp_to(area: &Rect, point: (f64, f64)) -> (f64, f64) {
+    (point.0.clamp(area.x, area.x + area.width), point.1.clamp(area.y, area.y + area.height))
+}
+
+///
+/// Finds the relative neighbourhood graph of a set of points, where two points are joined if no
+/// other point is closer to both of them than they are to each other. It joins near neighbours
+/// without the crossing lines of a triangulation, which reads like a constellation.
+///
+/// # Parameters:
+/// - `points`: The points to join
+///
+/// # Returns:
+/// - The edges of the graph, as pairs of indices into `points`
+///
+fn relative_neighbourhood_graph(points: &[(f64, f64)]) -> Vec<(usize, usize)> {
+    let distance = |a: usize, b: usize| (points[a].0 - points[b].0).hypot(points[a].1 - points[b].1);
+
+    // the graph is a subgraph of the delaunay triangulation, so only its edges need checking.
+    // fewer than 3 points, or collinear points, have no triangulation so every pair is checked
+    let triangles = delaunay::triangulate(&points.iter().map(|&(x, y)| Point::new(x as f32, y as f32)).collect::<Vec<_>>());
+    let candidates: HashSet<(usize, usize)> = if triangles.is_empty() {
+        (0..points.len()).flat_map(|a| (a + 1..points.len()).map(move |b| (a, b))).collect()
+    } else {
+        triangles.iter().flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])]).map(|(a, b)| (a.min(b), a.max(b))).collect()
+    };
+
+    let mut edges: Vec<(usize, usize)> = candidates.into_iter()
+        .filter(|&(a, b)| {
+            let length = distance(a, b);
+            (0..points.len()).all(|c| c == a || c == b || distance(a, c).max(distance(b, c)) >= length)
+        })
+        .collect();
+    edges.sort();
+    edges
+}
+
+///
+/// How likely a star is to be kept at each position, from an optional image mask.
+///
+enum Density {
+    Uniform,
+    #[cfg(feature = "image")]
+    Mask { image: image::GrayImage, placed: Rect },
+}
+
+impl Density {
+    ///
+    /// # Parameters:
+    /// - `parameters`: The parameters of the drawing, for the mask
+    /// - `physical_dimensions`: The physical dimensions, for placing the mask
+    /// - `area`: The area inside the margins
+    ///
+    /// # Returns:
+    /// - The density of the stars
+    /// - An error explaining why the mask could not be opened
+    ///
+    #[cfg_attr(not(feature = "image"), allow(unused_variables))]
+    fn new(parameters: &StarsParameters, physical_dimensions: &PhysicalDimensions, area: Rect) -> Result<Density, String> {
+        if parameters.mask_path.is_empty() {
+            return Ok(Density::Uniform);
+        }
+
+        #[cfg(feature = "image")]
+        {
+            let image = match files::read(&parameters.mask_path).map(|bytes| image::load_from_memory(&bytes)) {
+                Ok(Ok(img)) => img.into_luma8(),
+                Ok(Err(err)) => return Err(format!("Error decoding image. {}", err)),
+                Err(err) => return Err(format!("Error loading image. {}", err)),
+            };
+            let placed = parameters.placement.place(physical_dimensions, image.width() as f64, image.height() as f64)?.intersect(&area);
+            Ok(Density::Mask { image, placed })
+        }
+
+        #[cfg(not(feature = "image"))]
+        Err("Image masks are not supported in this build".to_owned())
+    }
+
+    ///
+    /// # Returns:
+    /// - The chance of keeping a star at the position, from 0 to 1
+    ///
+    #[cfg_attr(not(feature = "image"), allow(unused_variables))]
+    fn at(&self, x: f64, y: f64) -> f64 {
+        match self {
+            Density::Uniform => 1.,
+            #[cfg(feature = "image")]
+            Density::Mask { image, placed } => {
+                if !placed.contains(x, y) || placed.width == 0. || placed.height == 0. {
+                    return 0.;
+                }
+
+                // dark parts of the mask are dense with stars
+                let pixel_x = (((x - placed.x) / placed.width * image.width() as f64) as u32).min(image.width() - 1);
+                let pixel_y = (((y - placed.y) / placed.height * image.height() as f64) as u32).min(image.height() - 1);
+                1. - image.get_pixel(pixel_x, pixel_y).0[0] as f64 / 255.
+            },
+        }
+    }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `seed`: The seed of the star positions and brightnesses
+/// - `num_stars`: The number of stars to scatter
+/// - `placement`: The margins of the star field, and how the mask is fitted into them
+/// - `star_size`: The width of the brightest cross, in millimetres
+/// - `bright_fraction`: The share of the stars drawn as crosses rather than dots, from 0 to 1
+/// - `constellation_stars`: The number of the brightest stars joined into constellations, or 0 for none
+/// - `mask_path`: The path of an image whose dark parts are dense with stars, or empty for an even field
+///
+#[derive(Serialize, Deserialize)]
+pub struct StarsParameters {
+    seed: u64,
+    num_stars: usize,
+
+    #[serde(default)]
+    placement: Placement,
+
+    star_size: f64,
+    bright_fraction: f64,
+
+    #[serde(default)]
+    constellation_stars: usize,
+    #[serde(default)]
+    mask_path: String,
+}
+
+impl DrawParameters for StarsParameters {}
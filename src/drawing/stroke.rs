@@ -0,0 +1,549 @@
+//!
+//! Strokes, the output of every drawing method, and the compiler from strokes to instructions
+//!
+//! A drawing is a list of polylines, each travelled with the pen either up or down. Reordering,
+//! simplifying, clipping, splitting by pen and exporting all work on strokes, and the drawing is
+//! only turned into motor steps at the very end, by `compile`.
+//!
+
+use serde::{Serialize, Deserialize};
+
+use super::DrawSurface;
+use super::util::geometry::Point;
+use super::util::simplify;
+use super::util::spatial::PointGrid;
+use crate::hardware::PhysicalDimensions;
+
+///
+/// The longest pen up move `compile` makes in one instruction, when joining strokes which don't
+/// meet, in millimetres. Longer moves could overflow the motor steps of a single instruction.
+///
+const TRAVEL_SEGMENT_MM: f64 = 10.;
+
+///
+/// The longest pen down movement treated as no movement at all, in millimetres. It is far less
+/// than a motor step, so the machine sits still with the pen down.
+///
+const ZERO_LENGTH_MM: f64 = 1e-6;
+
+///
+/// The grid `quantise` snaps the points of a drawing to, in millimetres. It is far finer than a
+/// motor step, and a power of two so every snapped point is exact in binary.
+///
+pub const REPRODUCIBLE_GRID_MM: f64 = 1. / 1024.;
+
+///
+/// Compiled instructions, as an (instruction set, start_x, start_y).
+///
+type Compiled = (Vec<u8>, f64, f64);
+
+///
+/// A polyline travelled with the pen in one state.
+///
+/// # Fields:
+/// - `points`: The positions of the pen in order, in millimetres relative to the top left of the
+///   page. The first point is where the stroke starts, so a stroke has at least two points
+/// - `pen_up`: Whether the pen is raised off the paper for the whole stroke
+/// - `layer`: The layer of the stroke, such as which pen draws it
+/// - `label`: What the drawing method says the stroke is, such as "outline" or "row 14", so parts
+///   of a drawing can be told apart once exported or previewed
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub points: Vec<(f64, f64)>,
+    pub pen_up: bool,
+    pub layer: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Stroke {
+    ///
+    /// # Returns:
+    /// - The length of the stroke, in millimetres
+    ///
+    pub fn length(&self) -> f64 {
+        self.points.windows(2).map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1)).sum()
+    }
+}
+
+///
+/// The pen down strokes and segments of a drawing which leave a blob rather than a line, such as
+/// from degenerate samples of an image.
+///
+/// # Fields:
+/// - `dots`: The pen down strokes which don't move, so the pen is lowered and raised in one place
+/// - `short_strokes`: The pen down strokes which move, but less than the width of the pen's line
+/// - `zero_length_segments`: The pen down moves within longer strokes which don't move, so the
+///   pen dwells on the paper
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DegenerateReport {
+    pub dots: usize,
+    pub short_strokes: usize,
+    pub zero_length_segments: usize,
+}
+
+impl DegenerateReport {
+    ///
+    /// # Returns:
+    /// - Whether the drawing has no degenerate strokes or segments
+    ///
+    pub fn is_empty(&self) -> bool {
+        *self == DegenerateReport::default()
+    }
+}
+
+///
+/// Finds the pen down strokes and segments of a drawing which leave a blob rather than a line.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `line_width_mm`: The width of the pen's line. Strokes shorter than it are reported
+///
+/// # Returns:
+/// - How many of each kind of degenerate stroke or segment the drawing has
+///
+pub fn find_degenerate(strokes: &[Stroke], line_width_mm: f64) -> DegenerateReport {
+    let mut report = DegenerateReport::default();
+    for stroke in strokes.iter().filter(|stroke| !stroke.pen_up && !stroke.points.is_empty()) {
+        let length = stroke.length();
+        if length <= ZERO_LENGTH_MM {
+            report.dots += 1;
+        } else if length < line_width_mm {
+            report.short_strokes += 1;
+        } else {
+            report.zero_length_segments += stroke.points.windows(2).filter(|w| is_zero_length(w[0], w[1])).count();
+        }
+    }
+    report
+}
+
+///
+/// Removes what `find_degenerate` finds: the pen down strokes shorter than the pen's line,
+/// including dots, and the pen down moves which don't move. Drawings made of dots on purpose,
+/// such as stippling, lose those dots, so this is only done when asked for.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `line_width_mm`: The width of the pen's line
+///
+/// # Returns:
+/// - The strokes, without the degenerate strokes and segments. The rest are drawn the same
+///
+pub fn remove_degenerate(strokes: Vec<Stroke>, line_width_mm: f64) -> Vec<Stroke> {
+    strokes.into_iter().filter_map(|mut stroke| {
+        if stroke.pen_up {
+            return Some(stroke);
+        }
+        let length = stroke.length();
+        if length <= ZERO_LENGTH_MM || length < line_width_mm {
+            return None;
+        }
+        stroke.points.dedup_by(|point, previous| is_zero_length(*previous, *point));
+        Some(stroke)
+    }).collect()
+}
+
+///
+/// # Returns:
+/// - Whether a pen down move between two points doesn't move the pen
+///
+fn is_zero_length(from: (f64, f64), to: (f64, f64)) -> bool {
+    (to.0 - from.0).hypot(to.1 - from.1) <= ZERO_LENGTH_MM
+}
+
+///
+/// Snaps every point of a drawing to a fine grid, so a drawing compiles to the same
+/// instructions on every platform. Drawing methods use trigonometry and noise whose last bits
+/// differ between the maths libraries of macOS, Windows and Linux, which can otherwise round a
+/// belt movement to a different motor step.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `grid_mm`: The spacing of the grid, such as `REPRODUCIBLE_GRID_MM`
+///
+/// # Returns:
+/// - The strokes, with every point snapped to the grid
+///
+pub fn quantise(mut strokes: Vec<Stroke>, grid_mm: f64) -> Vec<Stroke> {
+    let snap = |value: f64| (value / grid_mm).round() * grid_mm;
+    for stroke in &mut strokes {
+        stroke.points.iter_mut().for_each(|point| *point = (snap(point.0), snap(point.1)));
+    }
+    strokes
+}
+
+///
+/// Simplifies the pen down strokes of a drawing with `simplify::rdp`, dropping points which
+/// barely change the line, such as the dense samples of a gentle curve. Fewer, longer moves
+/// compile to fewer instructions, and the machine spends less time accelerating between them.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `tolerance_mm`: The furthest a simplified stroke may stray from the original, in millimetres
+///
+/// # Returns:
+/// - The strokes, with the pen down strokes simplified. Pen up strokes are kept as they are, so
+///   travel still avoids whatever the drawing method steered around
+///
+pub fn simplify(mut strokes: Vec<Stroke>, tolerance_mm: f64) -> Vec<Stroke> {
+    for stroke in strokes.iter_mut().filter(|stroke| !stroke.pen_up) {
+        stroke.points = simplify::rdp(&stroke.points, tolerance_mm);
+    }
+    strokes
+}
+
+///
+/// Reorders pen down strokes to shorten the pen up travel between them. From where the pen is,
+/// the stroke with the nearest end is drawn next, reversed if its last point is nearer. Layers
+/// are kept in the order they first appear, so each pen still draws all its strokes together.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing. Pen up strokes are dropped, since `compile` moves
+///   the pen between strokes
+/// - `start`: Where the pen is before the first stroke, in millimetres
+///
+/// # Returns:
+/// - The pen down strokes, reordered
+///
+pub fn order_strokes(strokes: Vec<Stroke>, start: (f64, f64)) -> Vec<Stroke> {
+    let mut layers: Vec<(usize, Vec<Stroke>)> = vec![];
+    for stroke in strokes.into_iter().filter(|stroke| !stroke.pen_up && !stroke.points.is_empty()) {
+        match layers.iter_mut().find(|(layer, _)| *layer == stroke.layer) {
+            Some((_, layer_strokes)) => layer_strokes.push(stroke),
+            None => layers.push((stroke.layer, vec![stroke])),
+        }
+    }
+
+    let mut ordered = vec![];
+    let mut position = start;
+    for (_, layer_strokes) in layers {
+        // both ends of stroke i are points 2i and 2i + 1
+        let ends: Vec<Point> = layer_strokes.iter()
+            .flat_map(|stroke| [stroke.points[0], stroke.points[stroke.points.len() - 1]])
+            .map(Point::from)
+            .collect();
+        let mut grid = PointGrid::new(&ends);
+
+        let mut taken: Vec<Option<Stroke>> = layer_strokes.into_iter().map(Some).collect();
+        while let Some(end) = grid.nearest(position.0 as f32, position.1 as f32) {
+            grid.remove(end - end % 2);
+            grid.remove(end - end % 2 + 1);
+
+            let mut stroke = taken[end / 2].take().unwrap();
+            if end % 2 == 1 {
+                stroke.points.reverse();
+            }
+            position = stroke.points[stroke.points.len() - 1];
+            ordered.push(stroke);
+        }
+    }
+
+    ordered
+}
+
+///
+/// Flips the direction of pen down strokes, keeping their order, so each motor changes
+/// direction as rarely as possible. Every reversal of a belt takes up the backlash of the belt
+/// and gears, which shows as a small offset in the drawing on hanging plotters. Flipping a
+/// stroke can lengthen the pen up travel to it, so reversals are weighed against travel.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing, in the order they are drawn
+/// - `start`: Where the pen is before the first stroke, in millimetres
+/// - `physical_dimensions`: The physical dimensions of the machine, for how its motors move
+/// - `travel_per_reversal_mm`: How much further, in millimetres, the pen may travel to avoid one
+///   reversal of a motor. 0 only shortens the travel, as `order_strokes` does for the stroke ends
+///
+/// # Returns:
+/// - The strokes, in the same order, each drawn in the direction with the least cost
+///
+pub fn orient_strokes(strokes: Vec<Stroke>, start: (f64, f64), physical_dimensions: &PhysicalDimensions, travel_per_reversal_mm: f64) -> Vec<Stroke> {
+    let kinematics = physical_dimensions.kinematics_model();
+    let offset = (*physical_dimensions.page_horizontal_offset(), *physical_dimensions.page_vertical_offset());
+    // points which aren't numbers have no belt lengths, and are skipped rather than counted
+    let axes = |(x, y): (f64, f64)| kinematics.cartesian_to_axes(offset.0 + x, offset.1 + y).ok();
+    // moves of less than half a step don't turn the motors
+    let min_move_mm = 0.5 / physical_dimensions.steps_per_mm();
+
+    // walks the pen from a point along a path, counting the reversals of each motor, and
+    // returning the last direction each motor turned in
+    let walk = |mut heading: [f64; 2], from: (f64, f64), path: &[(f64, f64)]| {
+        let mut reversals = 0;
+        let mut last = axes(from);
+        for &point in path {
+            let Some(next) = axes(point) else {
+                continue;
+            };
+            let Some(last) = last.replace(next) else {
+                continue;
+            };
+            for (direction, delta) in heading.iter_mut().zip([next.0 - last.0, next.1 - last.1]) {
+                if delta.abs() < min_move_mm {
+                    continue;
+                }
+                if *direction * delta < 0. {
+                    reversals += 1;
+                }
+                *direction = delta.signum();
+            }
+        }
+        (reversals, heading)
+    };
+
+    let mut oriented = Vec::with_capacity(strokes.len());
+    let mut position = start;
+    // the direction each motor last turned, or 0 before it has turned
+    let mut heading = [0., 0.];
+
+    for mut stroke in strokes {
+        if stroke.points.is_empty() {
+            continue;
+        }
+
+        if !stroke.pen_up && stroke.points.len() > 1 {
+            let cost = |points: &[(f64, f64)]| {
+                let travel = (points[0].0 - position.0).hypot(points[0].1 - position.1);
+                travel + walk(heading, position, points).0 as f64 * travel_per_reversal_mm
+            };
+            let reversed: Vec<(f64, f64)> = stroke.points.iter().rev().copied().collect();
+            if cost(&reversed) < cost(&stroke.points) {
+                stroke.points = reversed;
+            }
+        }
+
+        heading = walk(heading, position, &stroke.points).1;
+        position = stroke.points[stroke.points.len() - 1];
+        oriented.push(stroke);
+    }
+
+    oriented
+}
+
+///
+/// Compiles strokes into drawing instructions. Strokes are drawn in order, and where one
+/// stroke doesn't start where the last ended, the pen is raised and moved between them.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `physical_dimensions`: The physical dimensions of the machine
+///
+/// # Returns:
+/// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+/// - An error explaining which point of the drawing can't be reached
+///
+pub fn compile(strokes: &[Stroke], physical_dimensions: &PhysicalDimensions) -> Result<(Vec<u8>, f64, f64), String> {
+    compile_with_layer_starts(strokes, physical_dimensions).map(|(compiled, _)| compiled)
+}
+
+///
+/// Compiles strokes into drawing instructions like `compile`, also finding where each layer
+/// starts, so a drawing can be paused to swap pens.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `physical_dimensions`: The physical dimensions of the machine
+///
+/// # Returns:
+/// - An (instruction set, start_x, start_y), and the index of the first instruction of each
+///   layer in drawing order, including the pen up move to it. A layer drawn again after another
+///   starts again
+/// - An error explaining which point of the drawing can't be reached
+///
+pub fn compile_with_layer_starts(strokes: &[Stroke], physical_dimensions: &PhysicalDimensions) -> Result<(Compiled, Vec<usize>), String> {
+    compile_strokes(strokes, physical_dimensions).map(|(compiled, layer_starts, _)| (compiled, layer_starts))
+}
+
+///
+/// Compiles strokes into drawing instructions like `compile`, also finding the instructions of
+/// each labelled stroke, so a preview can hide parts of the drawing by label.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `physical_dimensions`: The physical dimensions of the machine
+///
+/// # Returns:
+/// - An (instruction set, start_x, start_y), and the instructions of each run of labelled
+///   strokes, in drawing order
+/// - An error explaining which point of the drawing can't be reached
+///
+pub fn compile_with_labels(strokes: &[Stroke], physical_dimensions: &PhysicalDimensions) -> Result<(Compiled, Vec<LabelSpan>), String> {
+    compile_strokes(strokes, physical_dimensions).map(|(compiled, _, labels)| (compiled, labels))
+}
+
+///
+/// The instructions of a run of strokes with the same label, once compiled.
+///
+/// # Fields:
+/// - `label`: The label of the strokes
+/// - `first_instruction`: The index of the first instruction of the strokes, after the pen up
+///   move to them
+/// - `last_instruction`: The index of the last instruction of the strokes
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LabelSpan {
+    pub label: String,
+    pub first_instruction: usize,
+    pub last_instruction: usize,
+}
+
+impl LabelSpan {
+    ///
+    /// # Returns:
+    /// - Whether an instruction is one of the strokes' instructions
+    ///
+    pub fn contains(&self, instruction_idx: usize) -> bool {
+        (self.first_instruction..=self.last_instruction).contains(&instruction_idx)
+    }
+}
+
+///
+/// Compiles strokes into drawing instructions, finding where each layer starts and the
+/// instructions of each labelled stroke.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `physical_dimensions`: The physical dimensions of the machine
+///
+/// # Returns:
+/// - An (instruction set, start_x, start_y), the first instruction of each layer, and the
+///   instructions of each run of labelled strokes
+/// - An error explaining which point of the drawing can't be reached
+///
+fn compile_strokes(strokes: &[Stroke], physical_dimensions: &PhysicalDimensions) -> Result<(Compiled, Vec<usize>, Vec<LabelSpan>), String> {
+    let Some(&start) = strokes.iter().find_map(|stroke| stroke.points.first()) else {
+        return Ok(((vec![], 0., 0.), vec![], vec![]));
+    };
+
+    let num_samples = strokes.iter().map(|stroke| stroke.points.len()).sum();
+    let mut surface = DrawSurface::compiler(physical_dimensions, num_samples);
+    surface.sample_xy(start.0, start.1)?;
+    let mut position = start;
+
+    let mut layer_starts = vec![];
+    let mut layer = None;
+    let mut labels: Vec<LabelSpan> = vec![];
+
+    for stroke in strokes {
+        let Some(&first) = stroke.points.first() else { continue };
+
+        if layer != Some(stroke.layer) {
+            layer_starts.push(surface.num_instructions());
+            layer = Some(stroke.layer);
+        }
+
+        if first != position {
+            surface.raise_pen(true);
+            let distance = (first.0 - position.0).hypot(first.1 - position.1);
+            let segments = (distance / TRAVEL_SEGMENT_MM).ceil().max(1.) as usize;
+            for i in 1..=segments {
+                let t = i as f64 / segments as f64;
+                surface.sample_xy(position.0 + (first.0 - position.0) * t, position.1 + (first.1 - position.1) * t)?;
+            }
+        }
+
+        let first_instruction = surface.num_instructions();
+        surface.raise_pen(stroke.pen_up);
+        for &(x, y) in &stroke.points[1..] {
+            surface.sample_xy(x, y)?;
+        }
+        position = *stroke.points.last().unwrap();
+
+        // a stroke following on from one with the same label extends its span
+        if let (Some(label), true) = (&stroke.label, surface.num_instructions() > first_instruction) {
+            let last_instruction = surface.num_instructions() - 1;
+            match labels.last_mut() {
+                Some(span) if span.label == *label && span.last_instruction + 1 == first_instruction => span.last_instruction = last_instruction,
+                _ => labels.push(LabelSpan { label: label.clone(), first_instruction, last_instruction }),
+            }
+        }
+    }
+
+    Ok((surface.into_instructions()?, layer_starts, labels))
+}
+
+///
+/// Exports the pen down strokes of a drawing as an SVG, one millimetre to a user unit. Strokes
+/// with a label are grouped by it, the group's id being the label made into a valid XML id, so
+/// each part of the drawing can be picked out in a vector editor.
+///
+/// # Parameters:
+/// - `strokes`: The strokes of the drawing
+/// - `physical_dimensions`: The physical dimensions of the machine, for the size of the page
+///
+/// # Returns:
+/// - The SVG document
+///
+pub fn to_svg(strokes: &[Stroke], physical_dimensions: &PhysicalDimensions) -> String {
+    let (width, height) = (*physical_dimensions.page_width(), *physical_dimensions.page_height());
+
+    // groups are ordered by where each label is first drawn, with unlabelled strokes first
+    let mut groups: Vec<(Option<&str>, Vec<&Stroke>)> = vec![(None, vec![])];
+    for stroke in strokes.iter().filter(|stroke| !stroke.pen_up && stroke.points.len() > 1) {
+        let label = stroke.label.as_deref();
+        match groups.iter_mut().find(|(group_label, _)| *group_label == label) {
+            Some((_, group)) => group.push(stroke),
+            None => groups.push((label, vec![stroke])),
+        }
+    }
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}mm\" height=\"{1}mm\" viewBox=\"0 0 {0} {1}\">\n", width, height);
+    svg.push_str("<g fill=\"none\" stroke=\"black\" stroke-width=\"0.3\" stroke-linecap=\"round\" stroke-linejoin=\"round\">\n");
+    let mut ids: Vec<String> = vec![];
+    for (label, group) in groups.into_iter().filter(|(_, group)| !group.is_empty()) {
+        let indent = match label {
+            Some(label) => {
+                let id = svg_id(label, &ids);
+                svg.push_str(&format!("  <g id=\"{}\" data-label=\"{}\">\n", id, escape_xml(label)));
+                ids.push(id);
+                "    "
+            },
+            None => "  ",
+        };
+        for stroke in group {
+            let points: Vec<String> = stroke.points.iter().map(|(x, y)| format!("{:.3},{:.3}", x, y)).collect();
+            svg.push_str(&format!("{}<polyline points=\"{}\"/>\n", indent, points.join(" ")));
+        }
+        if label.is_some() {
+            svg.push_str("  </g>\n");
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+
+    svg
+}
+
+///
+/// Makes a label into an XML id, replacing the characters an id can't hold with hyphens.
+///
+/// # Parameters:
+/// - `label`: The label of the strokes
+/// - `taken`: The ids already used in the document
+///
+/// # Returns:
+/// - An id which isn't taken
+///
+fn svg_id(label: &str, taken: &[String]) -> String {
+    let mut id: String = label.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' }).collect();
+    // ids can't start with a digit or hyphen
+    if !id.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        id.insert_str(0, "label-");
+    }
+
+    let mut unique = id.clone();
+    let mut suffix = 2;
+    while taken.contains(&unique) {
+        unique = format!("{}-{}", id, suffix);
+        suffix += 1;
+    }
+    unique
+}
+
+///
+/// # Returns:
+/// - The text, with the characters special to XML attributes escaped
+///
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
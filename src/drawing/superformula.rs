@@ -0,0 +1,210 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+use super::util::geometry;
+use super::util::placement::{Margins, Placement};
+
+///
+/// An empty struct to implement the "Superformula" draw method on.
+///
+pub struct SuperformulaMethod;
+
+impl DrawMethod for SuperformulaMethod {
+    type DrawParameters = SuperformulaParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "superformula"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Superformula"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new superformula drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        SuperformulaParameters {
+            shape: Shape::Superformula { m: 5., n1: 0.3, n2: 0.3, n3: 0.3 }, placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            scale: 80., layers: 6, rotation_per_layer: 10., samples_per_layer: 360,
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the superformula drawing method.
+    /// This drawing method draws a closed polar curve, such as a superformula outline or a rose,
+    /// repeated in smaller, rotated layers nested inside each other.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &SuperformulaParameters) -> Result<Vec<Stroke>, String> {
+
+        if parameters.layers == 0 || parameters.samples_per_layer < 3 {
+            return Err("The drawing needs at least one layer, with at least 3 samples per layer".to_owned());
+        }
+        if !(parameters.scale.is_finite() && parameters.scale > 0.) {
+            return Err("The scale must be above 0".to_owned());
+        }
+
+        // the shape is drawn around its centre, so is centred in the area inside the margins
+        let area = parameters.placement.area(physical_dimensions)?;
+        let (centre_x, centre_y) = (area.x + area.width / 2., area.y + area.height / 2.);
+        if parameters.scale > area.width.min(area.height) / 2. {
+            return Err(format!("The shape is {:.1}mm across, but the area inside the margins is {:.1}mm by {:.1}mm", parameters.scale * 2., area.width, area.height));
+        }
+
+        // the curve's length is estimated at evenly spaced angles, then its samples are spread evenly along it
+        let period = parameters.shape.period()?;
+        let estimate: Vec<(f64, f64)> = (0..=parameters.samples_per_layer).map(|sample_num| {
+            let angle = period * sample_num as f64 / parameters.samples_per_layer as f64;
+            let radius = parameters.shape.radius(angle);
+            (radius * angle.cos(), radius * angle.sin())
+        }).collect();
+        let length: f64 = estimate.windows(2).map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1)).sum();
+        let curve = geometry::polar_path((0., 0.), |angle| parameters.shape.radius(angle), (0., period), length / parameters.samples_per_layer as f64);
+
+        // the curve is normalised so its furthest point is 1 from the centre, and the outer layer is `scale` across
+        let furthest = curve.iter().fold(0., |furthest: f64, &(x, y)| furthest.max(x.hypot(y)));
+        if furthest == 0. || !furthest.is_finite() {
+            return Err("The shape has no size, check its parameters".to_owned());
+        }
+
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, parameters.layers * (curve.len() + 1));
+        for layer in 0..parameters.layers {
+            let size = parameters.scale * (parameters.layers - layer) as f64 / parameters.layers as f64 / furthest;
+            let (sin, cos) = (parameters.rotation_per_layer * layer as f64).to_radians().sin_cos();
+
+            surface.raise_pen(true);
+            for &(x, y) in &curve {
+                surface.sample_xy(centre_x + (x * cos - y * sin) * size, centre_y + (x * sin + y * cos) * size)?;
+                surface.raise_pen(false);
+            }
+        }
+
+        Ok(surface.into_strokes())
+    }
+}
+
+
+///
+/// The closed polar curve drawn on each layer.
+///
+/// - `Superformula`: Gielis' superformula, which produces stars, polygons and organic outlines
+///     Parameters:
+///     - `m`: The rotational symmetry of the shape, a whole number for a closed outline
+///     - `n1`: The overall pinch of the shape, above 0
+///     - `n2`: The shape of the curve at each point of symmetry
+///     - `n3`: The shape of the curve between the points of symmetry
+/// - `Rose`: A rose curve, r = cos(n/d θ), which produces petals
+///     Parameters:
+///     - `n`: The numerator of the petal frequency
+///     - `d`: The denominator of the petal frequency
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Superformula { m: f64, n1: f64, n2: f64, n3: f64 },
+    Rose { n: u32, d: u32 },
+}
+
+impl Shape {
+    ///
+    /// Checks the shape's parameters.
+    ///
+    /// # Returns:
+    /// - The angle the curve closes after, in radians
+    /// - An error explaining why the shape's parameters are invalid
+    ///
+    fn period(&self) -> Result<f64, String> {
+        match *self {
+            Shape::Superformula { m, n1, n2, n3 } => {
+                if !(n1.is_finite() && n1 > 0. && [m, n2, n3].iter().all(|value| value.is_finite())) {
+                    return Err("The superformula needs n1 above 0, and finite m, n2 and n3".to_owned());
+                }
+                Ok(TAU)
+            },
+            Shape::Rose { n, d } => {
+                if n == 0 || d == 0 {
+                    return Err("A rose needs n and d above 0".to_owned());
+                }
+
+                // reduced to lowest terms, the rose closes after πd if n and d are both odd, otherwise 2πd
+                let divisor = gcd(n, d);
+                let (n, d) = (n / divisor, d / divisor);
+                Ok(if n % 2 == 1 && d % 2 == 1 { PI * d as f64 } else { TAU * d as f64 })
+            },
+        }
+    }
+
+    ///
+    /// # Parameters:
+    /// - `angle`: The angle around the centre, in radians
+    ///
+    /// # Returns:
+    /// - The unscaled radius of the curve at the angle
+    ///
+    fn radius(&self, angle: f64) -> f64 {
+        match *self {
+            Shape::Superformula { m, n1, n2, n3 } => {
+                let cos = (m * angle / 4.).cos().abs().powf(n2);
+                let sin = (m * angle / 4.).sin().abs().powf(n3);
+                (cos + sin).powf(-1. / n1)
+            },
+            Shape::Rose { n, d } => (n as f64 / d as f64 * angle).cos(),
+        }
+    }
+}
+
+///
+/// # Returns:
+/// - The greatest common divisor of two numbers
+///
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `shape`: The closed polar curve drawn on each layer
+/// - `placement`: The margins of the drawing, the shape being centred in the area inside them
+/// - `scale`: The distance from the centre to the furthest point of the outer layer, in millimetres
+/// - `layers`: The number of nested repeats of the shape, each smaller than the last
+/// - `rotation_per_layer`: How far each layer is rotated from the one outside it, in degrees
+/// - `samples_per_layer`: The number of positions sampled along each layer, spread evenly along its length
+///
+#[derive(Serialize, Deserialize)]
+pub struct SuperformulaParameters {
+    shape: Shape,
+
+    #[serde(default)]
+    placement: Placement,
+
+    scale: f64,
+    layers: usize,
+    rotation_per_layer: f64,
+    samples_per_layer: usize,
+}
+
+impl DrawParameters for SuperformulaParameters {}
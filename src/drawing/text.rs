@@ -0,0 +1,136 @@
+use crate::drawing::{DrawMethod, DrawParameters};
+use crate::hardware::PhysicalDimensions;
+use serde::{Serialize, Deserialize};
+use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
+
+use super::util::fonts::Font;
+use super::util::placement::{Align, Margins, Placement};
+
+///
+/// An empty struct to implement the "Text" draw method on.
+///
+pub struct TextMethod;
+
+impl DrawMethod for TextMethod {
+    type DrawParameters = TextParameters;
+
+    ///
+    /// # Returns:
+    /// - The backend ID of the drawing method
+    ///
+    fn get_id(&self) -> &'static str {
+        "text"
+    }
+
+    ///
+    /// # Returns:
+    /// - The frontend display name of the drawing method
+    ///
+    fn get_formatted_name(&self) -> &'static str {
+        "Text"
+    }
+
+    ///
+    /// # Returns:
+    /// - The parameters a new text drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        TextParameters {
+            font_path: String::new(), text: "Hello".to_owned(), line_spacing: 1.5,
+            placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the text drawing method.
+    /// This drawing method writes text in a single-stroke Hershey or SVG font, so each letter is
+    /// drawn as lines rather than outlined. The text is scaled to the page, with each line
+    /// aligned like the placement.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: A physical dimension object, including paper width / height
+    /// - `parameters`: The user-configured parameters to adjust the drawing style
+    ///
+    /// # Returns:
+    /// - The strokes of the drawing, in the order they are drawn
+    /// - An error, explaning why the drawing instructions could not be created
+    ///
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &TextParameters) -> Result<Vec<Stroke>, String> {
+
+        if parameters.font_path.is_empty() {
+            return Err("Select a font".to_owned());
+        }
+        if !(parameters.line_spacing.is_finite() && parameters.line_spacing > 0.) {
+            return Err("The line spacing must be above 0".to_owned());
+        }
+
+        let font = Font::load(&parameters.font_path)?;
+
+        // lines are laid out at a size of 1, then the whole block is scaled onto the page
+        let lines: Vec<&str> = parameters.text.lines().collect();
+        let block_width = lines.iter().map(|line| font.measure(line, 1.)).fold(0., f64::max);
+        let align = match parameters.placement.h_align {
+            Align::Start => 0.,
+            Align::Centre => 0.5,
+            Align::End => 1.,
+        };
+        let laid_out: Vec<Vec<Vec<(f64, f64)>>> = lines.iter().enumerate().map(|(idx, line)| {
+            let indent = (block_width - font.measure(line, 1.)) * align;
+            font.layout(line, 1., (indent, idx as f64 * parameters.line_spacing))
+        }).collect();
+
+        let points = laid_out.iter().flatten().flatten();
+        let (min_x, min_y, max_x, max_y) = points.fold((f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY), |(min_x, min_y, max_x, max_y), point| {
+            (min_x.min(point.0), min_y.min(point.1), max_x.max(point.0), max_y.max(point.1))
+        });
+        if !(min_x.is_finite() && min_y.is_finite() && max_x.is_finite() && max_y.is_finite()) {
+            return Err("The text has no characters in the font to draw".to_owned());
+        }
+
+        // a line of text along one axis, such as a dash, has no size across the other
+        let (width, height) = ((max_x - min_x).max(f64::EPSILON), (max_y - min_y).max(f64::EPSILON));
+        let placed = parameters.placement.place(physical_dimensions, width, height)?;
+        let (scale_x, scale_y) = (placed.width / width, placed.height / height);
+
+        let mut surface = DrawSurface::new(physical_dimensions);
+        for (idx, polylines) in laid_out.iter().enumerate() {
+            surface.set_label(Some(format!("line {}", idx + 1)));
+
+            for polyline in polylines {
+                surface.raise_pen(true);
+                for (i, &(x, y)) in polyline.iter().enumerate() {
+                    surface.sample_xy(placed.x + (x - min_x) * scale_x, placed.y + (y - min_y) * scale_y)?;
+                    if i == 0 {
+                        surface.raise_pen(false);
+                    }
+                }
+            }
+        }
+
+        Ok(surface.into_strokes())
+    }
+}
+
+
+///
+/// A set of parameters to instruct the generation of the draw calls.
+///
+/// # Fields:
+/// - `font_path`: The path of the font, a Hershey .jhf or SVG font file
+/// - `text`: The text to write, where each line of the text is a line on the page
+/// - `line_spacing`: The distance between the baselines of lines, in ems
+/// - `placement`: How the text is fitted onto the page. Its horizontal alignment also aligns
+///   each line within the text
+///
+#[derive(Serialize, Deserialize)]
+pub struct TextParameters {
+    font_path: String,
+    text: String,
+    line_spacing: f64,
+
+    #[serde(default)]
+    placement: Placement,
+}
+
+impl DrawParameters for TextParameters {}
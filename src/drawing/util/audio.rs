@@ -1,40 +1,258 @@
-use std::{fs::File, path::Path};
+use std::io::Cursor;
 
-use symphonia::{core::{audio::{AudioBufferRef, Signal}, codecs::CODEC_TYPE_NULL, io::MediaSourceStream}, default::{get_codecs, get_probe}};
+use crate::drawing::util::files;
+
+use serde::{Serialize, Deserialize};
+use symphonia::{core::{audio::{AudioBufferRef, Signal}, codecs::CODEC_TYPE_NULL, conv::FromSample, io::MediaSourceStream}, default::{get_codecs, get_probe}};
+
+///
+/// How each window of audio is measured, when sampling a waveform.
+///
+/// - `Rms`: The root mean square of the window, which follows perceived loudness
+/// - `Peak`: The largest absolute sample in the window, which follows transients
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WaveformMode {
+    #[default]
+    Rms,
+    Peak,
+}
 
 /// 
-/// Creates a waveform representation using u8s, where 0 is quiet and 255 is loud.
+/// Creates a waveform representation using u8s, where 0 is quiet and 255 is loud. Each value is
+/// the RMS loudness of an equal window of the audio.
 ///
 /// # Parameters:
 /// - `file`: The file path
 /// - `sample_count`: The number of samples to return
 ///
 /// # Returns:
-/// - a vector of u8s representing the waveform
+/// - a vector of exactly `sample_count` u8s representing the waveform
 /// - a string explaining why the function failed
 ///
 pub fn get_sampled_waveform(file: &str, sample_count: usize) -> Result<Vec<u8>, String> {
-    
-    let path = Path::new(&file);
-    match path.try_exists() {
-        Ok(exists) => {
-            if !exists {
-                return Err("file did not exist".to_string());
+    get_waveform(file, sample_count, WaveformMode::Rms, false)
+}
+
+/// 
+/// Creates a waveform representation using u8s, where 0 is quiet and 255 is loud.
+///
+/// # Parameters:
+/// - `file`: The file path
+/// - `sample_count`: The number of samples to return
+/// - `mode`: How each window of audio is measured
+/// - `decibels`: Whether to scale the loudness logarithmically, which brings out quiet passages
+///
+/// # Returns:
+/// - a vector of exactly `sample_count` u8s representing the waveform
+/// - a string explaining why the function failed
+///
+pub fn get_waveform(file: &str, sample_count: usize, mode: WaveformMode, decibels: bool) -> Result<Vec<u8>, String> {
+    let (all_samples, _) = decode_mono(file)?;
+    Ok(sample_waveform(&all_samples, sample_count, mode, decibels))
+}
+
+/// 
+/// Measures the loudness of equal windows of a mono signal.
+///
+/// # Parameters:
+/// - `samples`: The signal, with samples between -1 and 1
+/// - `sample_count`: The number of windows to split the signal into
+/// - `mode`: How each window is measured
+/// - `decibels`: Whether to scale the loudness logarithmically, mapping -60dB to 0dB onto 0 to 255
+///
+/// # Returns:
+/// - a vector of exactly `sample_count` u8s. A signal shorter than `sample_count` has windows
+///   narrower than a sample, which each repeat the sample they fall on, or are 0 for an empty signal
+///
+pub fn sample_waveform(samples: &[f32], sample_count: usize, mode: WaveformMode, decibels: bool) -> Vec<u8> {
+    const DECIBEL_FLOOR: f32 = -60.;
+
+    (0..sample_count).map(|i| {
+        // window boundaries are rounded separately, so every sample falls in exactly one window
+        let start = i * samples.len() / sample_count;
+        let end = ((i + 1) * samples.len() / sample_count).max(start + 1);
+        let Some(window) = samples.get(start..end) else {
+            return 0;
+        };
+
+        let level = match mode {
+            WaveformMode::Rms => (window.iter().map(|s| s * s).sum::<f32>() / window.len() as f32).sqrt(),
+            WaveformMode::Peak => window.iter().fold(0_f32, |peak, s| peak.max(s.abs())),
+        };
+
+        let scaled = if decibels {
+            let db = 20. * level.max(f32::MIN_POSITIVE).log10();
+            (db - DECIBEL_FLOOR) / -DECIBEL_FLOOR
+        } else {
+            level
+        };
+
+        (scaled.clamp(0., 1.) * 255.).round() as u8
+    }).collect()
+}
+
+/// 
+/// Mixes several channels into one, by averaging each sample across the channels.
+///
+/// # Parameters:
+/// - `channels`: The samples of each channel, which should be the same length
+///
+/// # Returns:
+/// - The mixed samples, as long as the shortest channel
+///
+pub fn mix_channels(channels: &[Vec<f32>]) -> Vec<f32> {
+    let length = channels.iter().map(|c| c.len()).min().unwrap_or(0);
+    (0..length).map(|i| channels.iter().map(|c| c[i]).sum::<f32>() / channels.len() as f32).collect()
+}
+
+/// 
+/// Creates a spectrogram, the strength of each frequency band over time, using an FFT of evenly
+/// spaced windows of the audio. Bands are spaced logarithmically from 20Hz, as pitch is heard.
+///
+/// # Parameters:
+/// - `file`: The file path
+/// - `bands`: The number of frequency bands per frame
+/// - `frames`: The number of frames, spread evenly across the audio
+///
+/// # Returns:
+/// - A 2D vector of band magnitudes between 0 and 1, in the form Frame<Band<f32>>
+/// - a string explaining why the function failed
+///
+pub fn get_spectrogram(file: &str, bands: usize, frames: usize) -> Result<Vec<Vec<f32>>, String> {
+    let (samples, sample_rate) = decode_mono(file)?;
+    spectrogram(&samples, sample_rate, bands, frames)
+}
+
+/// 
+/// Creates a spectrogram of a mono signal, as `get_spectrogram` does for a file.
+///
+/// # Parameters:
+/// - `samples`: The signal, with samples between -1 and 1
+/// - `sample_rate`: The sample rate of the signal in hertz
+/// - `bands`: The number of frequency bands per frame
+/// - `frames`: The number of frames, spread evenly across the signal
+///
+/// # Returns:
+/// - A 2D vector of band magnitudes between 0 and 1, in the form Frame<Band<f32>>
+/// - a string explaining why the function failed
+///
+pub fn spectrogram(samples: &[f32], sample_rate: u32, bands: usize, frames: usize) -> Result<Vec<Vec<f32>>, String> {
+    const FFT_SIZE: usize = 2048;
+    const MIN_FREQUENCY: f32 = 20.;
+
+    if samples.is_empty() {
+        return Err("audio had no samples".to_string());
+    }
+    if bands == 0 || frames == 0 {
+        return Ok(vec![vec![0.; bands]; frames]);
+    }
+
+    // the lower and upper fft bin of each band
+    let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+    let max_frequency = (sample_rate as f32 / 2.).min(20000.);
+    let band_edges: Vec<usize> = (0..=bands).map(|i| {
+        let frequency = MIN_FREQUENCY * (max_frequency / MIN_FREQUENCY).powf(i as f32 / bands as f32);
+        ((frequency / bin_hz) as usize).min(FFT_SIZE / 2)
+    }).collect();
+
+    let hann: Vec<f32> = (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2. * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos()).collect();
+
+    let mut spectrogram: Vec<Vec<f32>> = Vec::with_capacity(frames);
+    for frame in 0..frames {
+        // windows are centred on evenly spaced points, and zero padded past either end
+        let centre = ((frame as f64 + 0.5) / frames as f64 * samples.len() as f64) as isize;
+        let start = centre - FFT_SIZE as isize / 2;
+
+        let mut real: Vec<f32> = (0..FFT_SIZE).map(|i| {
+            let index = start + i as isize;
+            if index < 0 || index as usize >= samples.len() { 0. } else { samples[index as usize] * hann[i] }
+        }).collect();
+        let mut imaginary = vec![0.; FFT_SIZE];
+        fft(&mut real, &mut imaginary);
+
+        let magnitudes: Vec<f32> = (0..band_edges.len() - 1).map(|band| {
+            let low = band_edges[band];
+            let high = band_edges[band + 1].max(low + 1).min(FFT_SIZE / 2);
+            if low >= high {
+                return 0.;
             }
-        },
-        Err(err) => {
-            return Err(err.to_string());
+            (low..high).map(|bin| (real[bin] * real[bin] + imaginary[bin] * imaginary[bin]).sqrt()).sum::<f32>() / (high - low) as f32
+        }).collect();
+
+        spectrogram.push(magnitudes);
+    }
+
+    // normalise so the loudest band of any frame is 1
+    let max_magnitude = spectrogram.iter().flatten().copied().fold(0., f32::max);
+    if max_magnitude > 0. {
+        spectrogram.iter_mut().flatten().for_each(|m| *m /= max_magnitude);
+    }
+
+    Ok(spectrogram)
+}
+
+/// 
+/// Performs an in-place, iterative radix-2 fast fourier transform.
+///
+/// # Parameters:
+/// - `real`: The real part of the signal, with a power of two length
+/// - `imaginary`: The imaginary part of the signal, the same length as `real`
+///
+fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+    let n = real.len();
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            real.swap(i, j);
+            imaginary.swap(i, j);
         }
     }
 
-    let audio_file = match File::open(path) {
-        Ok(val) => val,
-        Err(err) => {
-            return Err(err.to_string());
+    // butterflies, doubling the transform length each pass
+    let mut length = 2;
+    while length <= n {
+        let angle = -2. * std::f32::consts::PI / length as f32;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (w_real, w_imaginary) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a, b) = (start + k, start + k + length / 2);
+
+                let t_real = real[b] * w_real - imaginary[b] * w_imaginary;
+                let t_imaginary = real[b] * w_imaginary + imaginary[b] * w_real;
+
+                real[b] = real[a] - t_real;
+                imaginary[b] = imaginary[a] - t_imaginary;
+                real[a] += t_real;
+                imaginary[a] += t_imaginary;
+            }
         }
-    };
+        length <<= 1;
+    }
+}
+
+/// 
+/// Decodes an audio file, mixing every channel down to one.
+///
+/// # Parameters:
+/// - `file`: The file path, or the name of a file in memory
+///
+/// # Returns:
+/// - The mixed samples, and the sample rate of the audio in hertz
+/// - a string explaining why the function failed
+///
+fn decode_mono(file: &str) -> Result<(Vec<f32>, u32), String> {
+    let audio_bytes = files::read(file)?;
 
-    let mss = MediaSourceStream::new(Box::new(audio_file), Default::default());
+    let mss = MediaSourceStream::new(Box::new(Cursor::new(audio_bytes)), Default::default());
 
     let mut probed_audio = match get_probe().format(&Default::default(), mss, &Default::default(), &Default::default()) {
         Ok(val) => val,
@@ -50,6 +268,8 @@ pub fn get_sampled_waveform(file: &str, sample_count: usize) -> Result<Vec<u8>,
         }
     };
 
+    let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
+
     let mut decoder = match get_codecs().make(&track.codec_params, &Default::default()) {
         Ok(val) => val,
         Err(err) => {
@@ -71,36 +291,18 @@ pub fn get_sampled_waveform(file: &str, sample_count: usize) -> Result<Vec<u8>,
             return Err("audio had no channels".to_string());
         }
 
-        let mut samples = samples_as_f32(&decoded_packet, 0); // first take channel 0
-        for c in 1..channels {
-            let next_channel_samples = samples_as_f32(&decoded_packet, c);
-            for i in 0..next_channel_samples.len() {
-                samples[i] = (samples[i] + next_channel_samples[i]) * (c as f32 / (c as f32 + 1.));
-            }
-        }
+        let channel_samples: Vec<Vec<f32>> = (0..channels).map(|c| samples_as_f32(&decoded_packet, c)).collect();
+        let mut samples = mix_channels(&channel_samples);
 
-        // here we normalise -1 <-> 1 to 0 <-> 1 with abs
         all_samples.append(&mut samples);
     }
 
-    let sample_group_size = (all_samples.len() as f64 / sample_count as f64).ceil() as usize;
-    let mut means: Vec<f32> = Vec::new();
-
-    for i in 0..sample_count {
-        let start = i * sample_group_size;
-        let end = ((i + 1) * sample_group_size).min(all_samples.len());
-        if start < end {
-            let section: &[f32] = &all_samples[start..end];
-            let mean = section.iter().copied().sum::<f32>() / section.len() as f32;
-            means.push(mean);
-        }
-    }
-
-    Ok(means.iter().map(|s| (s * 255.).floor() as u8).collect())
+    Ok((all_samples, sample_rate))
 }
 
+
 /// 
-/// Decodes each audio channel from any type to an f32
+/// Decodes each audio channel from any type to an f32, normalised between -1 and 1.
 ///
 /// # Parameters:
 /// - `buf`: A reference to an audio buffer
@@ -111,12 +313,91 @@ pub fn get_sampled_waveform(file: &str, sample_count: usize) -> Result<Vec<u8>,
 ///
 fn samples_as_f32(buf: &AudioBufferRef, chan: usize) -> Vec<f32> {
     match buf {
-        AudioBufferRef::U8(buf) => buf.chan(chan).iter().map(|s|  *s as f32).collect(),
-        AudioBufferRef::S16(buf) => buf.chan(chan).iter().map(|s| *s as f32).collect(),
-        AudioBufferRef::U16(buf) => buf.chan(chan).iter().map(|s| *s as f32).collect(),
-        AudioBufferRef::S32(buf) => buf.chan(chan).iter().map(|s| *s as f32).collect(),
-        AudioBufferRef::F64(buf) => buf.chan(chan).iter().map(|s| *s as f32).collect(),
+        AudioBufferRef::U8(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::U16(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::U24(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::U32(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::S8(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::S16(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::S24(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::S32(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
+        AudioBufferRef::F64(buf) => buf.chan(chan).iter().map(|s| f32::from_sample(*s)).collect(),
         AudioBufferRef::F32(buf) => buf.chan(chan).to_vec(),
-        _ => Vec::new(), // or handle error
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(length: usize, amplitude: f32) -> Vec<f32> {
+        (0..length).map(|i| (i as f32 * 0.1).sin() * amplitude).collect()
+    }
+
+    #[test]
+    fn symmetric_audio_is_not_flat() {
+        let waveform = sample_waveform(&sine(10000, 1.), 10, WaveformMode::Rms, false);
+
+        // a full scale sine has an RMS of 1/sqrt(2)
+        assert_eq!(waveform.len(), 10);
+        assert!(waveform.iter().all(|&v| (175..=185).contains(&v)), "{:?}", waveform);
+
+        let peaks = sample_waveform(&sine(10000, 0.5), 10, WaveformMode::Peak, false);
+        assert!(peaks.iter().all(|&v| (125..=129).contains(&v)), "{:?}", peaks);
+    }
+
+    #[test]
+    fn waveform_follows_loudness() {
+        let mut samples = sine(5000, 0.1);
+        samples.extend(sine(5000, 0.8));
+        let waveform = sample_waveform(&samples, 2, WaveformMode::Rms, false);
+        assert!(waveform[1] > waveform[0] * 7);
+
+        // -20dB is two thirds of the way up a 60dB range
+        let decibels = sample_waveform(&[0.1; 100], 1, WaveformMode::Peak, true);
+        assert_eq!(decibels, vec![170]);
+        assert_eq!(sample_waveform(&[0.; 100], 1, WaveformMode::Rms, true), vec![0]);
+    }
+
+    #[test]
+    fn short_audio_repeats_samples() {
+        // windows narrower than a sample repeat the sample they fall on, rather than being silent
+        assert_eq!(sample_waveform(&[0.2, -0.6, 1.], 5, WaveformMode::Peak, false), vec![51, 51, 153, 153, 255]);
+        assert_eq!(sample_waveform(&[0.5], 4, WaveformMode::Rms, false), vec![128; 4]);
+        assert_eq!(sample_waveform(&[], 3, WaveformMode::Rms, false), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn pure_tones_land_in_their_band() {
+        let sample_rate = 8000;
+        let tone = |frequency: f32| -> Vec<f32> {
+            (0..sample_rate).map(|i| (2. * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin() * 0.5).collect()
+        };
+
+        // bands are spaced logarithmically from 20Hz to the 4kHz nyquist frequency
+        let band_of = |frequency: f32| ((frequency / 20.).ln() / (4000_f32 / 20.).ln() * 16.) as usize;
+        for frequency in [100., 440., 1000., 3000.] {
+            let frames = spectrogram(&tone(frequency), sample_rate, 16, 4).unwrap();
+            assert_eq!(frames.len(), 4);
+
+            for bands in frames.iter() {
+                let loudest = (0..16).max_by(|&a, &b| bands[a].total_cmp(&bands[b])).unwrap();
+                assert_eq!(loudest, band_of(frequency), "{}Hz: {:?}", frequency, bands);
+                assert!((bands[loudest] - 1.).abs() < 0.05, "{:?}", bands);
+
+                // and bands far from the tone are quiet
+                assert!(bands.iter().enumerate().all(|(band, &m)| band.abs_diff(loudest) <= 1 || m < 0.05), "{}Hz: {:?}", frequency, bands);
+            }
+        }
+
+        assert!(spectrogram(&[], sample_rate, 16, 4).is_err());
+        assert_eq!(spectrogram(&tone(440.), sample_rate, 0, 2).unwrap(), vec![Vec::<f32>::new(); 2]);
+    }
+
+    #[test]
+    fn channels_are_averaged() {
+        let mixed = mix_channels(&[vec![1., 0.5, -1.], vec![0., 0.5, 1.], vec![0.5, 0.5, 0.]]);
+        assert_eq!(mixed, vec![0.5, 0.5, 0.]);
     }
 }
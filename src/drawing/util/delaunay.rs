@@ -0,0 +1,515 @@
+use crate::drawing::util::geometry::Point;
+
+///
+/// Marks a missing half-edge or hull entry.
+///
+const EMPTY: usize = usize::MAX;
+
+///
+/// Computes the delaunay triangulation of a set of points, with a sweep-hull algorithm in the
+/// style of Delaunator. Points are added in order of distance from a seed triangle, each joined
+/// to the visible part of the convex hull and then legalised by edge flips, which runs in
+/// roughly O(n log n) rather than the O(n²) of Bowyer-Watson.
+/// Where points are duplicated, only one of them is triangulated, and the others are left out of
+/// every triangle. Callers which need every point in a triangle should deduplicate them first.
+///
+/// # Parameters:
+/// - `points`: The points to triangulate
+///
+/// # Returns:
+/// - A vector of arrays, where each array holds the indices of a triangle's 3 vertices in
+///   anticlockwise order, or an empty vector if every point is collinear
+///
+pub fn triangulate(points: &[Point]) -> Vec<[usize; 3]> {
+    let coords: Vec<(f64, f64)> = points.iter().map(|&p| p.into()).collect();
+    match Triangulation::new(&coords) {
+        Some(triangulation) => triangulation.triangles.chunks_exact(3).map(|t| [t[0], t[2], t[1]]).collect(),
+        None => vec![],
+    }
+}
+
+///
+/// The working state of the sweep-hull triangulation.
+///
+/// # Fields:
+/// - `coords`: The points being triangulated
+/// - `triangles`: The vertex of each half-edge, where each 3 half-edges form a triangle
+/// - `halfedges`: The opposite half-edge of each half-edge, in the adjacent triangle
+/// - `hull_prev` and `hull_next`: A doubly linked list of the convex hull, by point index
+/// - `hull_tri`: The half-edge on the hull leaving each hull point
+/// - `hull_hash`: Hull points bucketed by angle around the centre, to find the hull quickly
+/// - `hull_start`: A point on the hull
+/// - `centre`: The circumcentre of the seed triangle
+/// - `edge_stack`: Half-edges waiting to be legalised
+///
+struct Triangulation<'c> {
+    coords: &'c [(f64, f64)],
+    triangles: Vec<usize>,
+    halfedges: Vec<usize>,
+
+    hull_prev: Vec<usize>,
+    hull_next: Vec<usize>,
+    hull_tri: Vec<usize>,
+    hull_hash: Vec<usize>,
+    hull_start: usize,
+    centre: (f64, f64),
+
+    edge_stack: Vec<usize>,
+}
+
+impl<'c> Triangulation<'c> {
+    ///
+    /// Triangulates a set of points.
+    ///
+    /// # Parameters:
+    /// - `coords`: The points to triangulate
+    ///
+    /// # Returns:
+    /// - The completed triangulation
+    /// - `None` if there are fewer than 3 points, or every point is collinear
+    ///
+    fn new(coords: &'c [(f64, f64)]) -> Option<Triangulation<'c>> {
+        let n = coords.len();
+        if n < 3 {
+            return None;
+        }
+
+        let (min_x, max_x) = coords.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+        let (min_y, max_y) = coords.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+        let bounds_centre = ((min_x + max_x) / 2., (min_y + max_y) / 2.);
+
+        // the seed triangle is the point closest to the centre, its nearest neighbour, and the
+        // point making the smallest circumcircle with them
+        let i0 = (0..n).min_by(|&a, &b| dist_sq(coords[a], bounds_centre).total_cmp(&dist_sq(coords[b], bounds_centre)))?;
+        let i1 = (0..n).filter(|&i| dist_sq(coords[i], coords[i0]) > 0.).min_by(|&a, &b| dist_sq(coords[a], coords[i0]).total_cmp(&dist_sq(coords[b], coords[i0])))?;
+        let (mut i1, mut i2) = (i1, EMPTY);
+        let mut min_radius = f64::INFINITY;
+        for i in 0..n {
+            if i == i0 || i == i1 {
+                continue;
+            }
+            let radius = circumradius(coords[i0], coords[i1], coords[i]);
+            if radius < min_radius {
+                i2 = i;
+                min_radius = radius;
+            }
+        }
+        if !min_radius.is_finite() {
+            return None;
+        }
+
+        if orient(coords[i0], coords[i1], coords[i2]) {
+            std::mem::swap(&mut i1, &mut i2);
+        }
+
+        let centre = circumcentre(coords[i0], coords[i1], coords[i2]);
+        let mut ids: Vec<usize> = (0..n).collect();
+        let distances: Vec<f64> = coords.iter().map(|&p| dist_sq(p, centre)).collect();
+        ids.sort_unstable_by(|&a, &b| distances[a].total_cmp(&distances[b]));
+
+        let hash_size = (n as f64).sqrt().ceil() as usize;
+        let mut triangulation = Triangulation {
+            coords,
+            triangles: Vec::with_capacity(6 * n),
+            halfedges: Vec::with_capacity(6 * n),
+            hull_prev: vec![0; n],
+            hull_next: vec![0; n],
+            hull_tri: vec![0; n],
+            hull_hash: vec![EMPTY; hash_size],
+            hull_start: i0,
+            centre,
+            edge_stack: Vec::with_capacity(512),
+        };
+
+        triangulation.hull_next[i0] = i1;
+        triangulation.hull_prev[i2] = i1;
+        triangulation.hull_next[i1] = i2;
+        triangulation.hull_prev[i0] = i2;
+        triangulation.hull_next[i2] = i0;
+        triangulation.hull_prev[i1] = i0;
+        triangulation.hull_tri[i0] = 0;
+        triangulation.hull_tri[i1] = 1;
+        triangulation.hull_tri[i2] = 2;
+        for i in [i0, i1, i2] {
+            let key = triangulation.hash_key(coords[i]);
+            triangulation.hull_hash[key] = i;
+        }
+
+        triangulation.add_triangle(i0, i1, i2, EMPTY, EMPTY, EMPTY);
+
+        let mut previous: Option<(f64, f64)> = None;
+        for &i in ids.iter() {
+            let point = coords[i];
+
+            // skip near-duplicate points
+            if let Some(p) = previous && (point.0 - p.0).abs() <= f64::EPSILON && (point.1 - p.1).abs() <= f64::EPSILON {
+                continue;
+            }
+            previous = Some(point);
+
+            if i == i0 || i == i1 || i == i2 {
+                continue;
+            }
+
+            triangulation.add_point(i);
+        }
+
+        Some(triangulation)
+    }
+
+    ///
+    /// Adds a point outside the current hull, joining it to each hull edge it can see.
+    ///
+    /// # Parameters:
+    /// - `i`: The index of the point
+    ///
+    fn add_point(&mut self, i: usize) {
+        let point = self.coords[i];
+
+        // find a visible edge on the hull, starting near the point's angle
+        let key = self.hash_key(point);
+        let hash_size = self.hull_hash.len();
+        let mut start = 0;
+        for j in 0..hash_size {
+            start = self.hull_hash[(key + j) % hash_size];
+            if start != EMPTY && start != self.hull_next[start] {
+                break;
+            }
+        }
+        start = self.hull_prev[start];
+
+        let mut e = start;
+        loop {
+            let q = self.hull_next[e];
+            if orient(point, self.coords[e], self.coords[q]) {
+                break;
+            }
+            e = q;
+            if e == start {
+                // the point is on the hull, which only happens with near-duplicates
+                return;
+            }
+        }
+
+        // join the point to the first visible edge
+        let t = self.add_triangle(e, i, self.hull_next[e], EMPTY, EMPTY, self.hull_tri[e]);
+        self.hull_tri[i] = self.legalize(t + 2);
+        self.hull_tri[e] = t;
+
+        // walk forwards along the hull, joining each further visible edge
+        let mut n = self.hull_next[e];
+        loop {
+            let q = self.hull_next[n];
+            if !orient(point, self.coords[n], self.coords[q]) {
+                break;
+            }
+            let t = self.add_triangle(n, i, q, self.hull_tri[i], EMPTY, self.hull_tri[n]);
+            self.hull_tri[i] = self.legalize(t + 2);
+            self.hull_next[n] = n; // marks n as removed from the hull
+            n = q;
+        }
+
+        // and backwards, if the first visible edge was where the search started
+        if e == start {
+            loop {
+                let q = self.hull_prev[e];
+                if !orient(point, self.coords[q], self.coords[e]) {
+                    break;
+                }
+                let t = self.add_triangle(q, i, e, EMPTY, self.hull_tri[e], self.hull_tri[q]);
+                self.legalize(t + 2);
+                self.hull_tri[q] = t;
+                self.hull_next[e] = e;
+                e = q;
+            }
+        }
+
+        self.hull_start = e;
+        self.hull_prev[i] = e;
+        self.hull_next[e] = i;
+        self.hull_prev[n] = i;
+        self.hull_next[i] = n;
+
+        let key = self.hash_key(point);
+        self.hull_hash[key] = i;
+        let key = self.hash_key(self.coords[e]);
+        self.hull_hash[key] = e;
+    }
+
+    ///
+    /// Flips edges until the triangles around a half-edge all satisfy the delaunay condition.
+    ///
+    /// # Parameters:
+    /// - `a`: The half-edge to start from
+    ///
+    /// # Returns:
+    /// - The half-edge which ends up on the hull, in place of `a`'s original triangle's edge
+    ///
+    fn legalize(&mut self, mut a: usize) -> usize {
+        let mut ar;
+
+        loop {
+            let b = self.halfedges[a];
+            let a0 = a - a % 3;
+            ar = a0 + (a + 2) % 3;
+
+            if b == EMPTY {
+                match self.edge_stack.pop() {
+                    Some(next) => { a = next; continue; },
+                    None => break,
+                }
+            }
+
+            let b0 = b - b % 3;
+            let al = a0 + (a + 1) % 3;
+            let bl = b0 + (b + 2) % 3;
+
+            let p0 = self.triangles[ar];
+            let pr = self.triangles[a];
+            let pl = self.triangles[al];
+            let p1 = self.triangles[bl];
+
+            if in_circle(self.coords[p0], self.coords[pr], self.coords[pl], self.coords[p1]) {
+                self.triangles[a] = p1;
+                self.triangles[b] = p0;
+
+                // the flipped edge was on the hull, so the hull's reference to it moves
+                let hbl = self.halfedges[bl];
+                if hbl == EMPTY {
+                    let mut e = self.hull_start;
+                    loop {
+                        if self.hull_tri[e] == bl {
+                            self.hull_tri[e] = a;
+                            break;
+                        }
+                        e = self.hull_prev[e];
+                        if e == self.hull_start {
+                            break;
+                        }
+                    }
+                }
+
+                self.link(a, hbl);
+                self.link(b, self.halfedges[ar]);
+                self.link(ar, bl);
+
+                self.edge_stack.push(b0 + (b + 1) % 3);
+            } else {
+                match self.edge_stack.pop() {
+                    Some(next) => a = next,
+                    None => break,
+                }
+            }
+        }
+
+        ar
+    }
+
+    ///
+    /// Adds a triangle and links its half-edges to their opposites.
+    ///
+    /// # Returns:
+    /// - The index of the triangle's first half-edge
+    ///
+    fn add_triangle(&mut self, i0: usize, i1: usize, i2: usize, a: usize, b: usize, c: usize) -> usize {
+        let t = self.triangles.len();
+        self.triangles.extend([i0, i1, i2]);
+        self.halfedges.extend([EMPTY; 3]);
+
+        self.link(t, a);
+        self.link(t + 1, b);
+        self.link(t + 2, c);
+
+        t
+    }
+
+    ///
+    /// Links two half-edges as opposites, where `b` may be `EMPTY`.
+    ///
+    fn link(&mut self, a: usize, b: usize) {
+        self.halfedges[a] = b;
+        if b != EMPTY {
+            self.halfedges[b] = a;
+        }
+    }
+
+    ///
+    /// # Returns:
+    /// - The hull hash bucket for a point, by its angle around the centre
+    ///
+    fn hash_key(&self, point: (f64, f64)) -> usize {
+        let (dx, dy) = (point.0 - self.centre.0, point.1 - self.centre.1);
+
+        // a monotonic stand-in for the angle, between 0 and 1
+        let p = dx / (dx.abs() + dy.abs());
+        let angle = (if dy > 0. { 3. - p } else { 1. + p }) / 4.;
+
+        let hash_size = self.hull_hash.len();
+        ((angle * hash_size as f64).floor() as usize) % hash_size
+    }
+}
+
+///
+/// # Returns:
+/// - The squared distance between two points
+///
+fn dist_sq(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+///
+/// # Returns:
+/// - Whether p, q, r turn one way, the same way as the hull is stored
+///
+fn orient(p: (f64, f64), q: (f64, f64), r: (f64, f64)) -> bool {
+    (q.1 - p.1) * (r.0 - q.0) - (q.0 - p.0) * (r.1 - q.1) < 0.
+}
+
+///
+/// # Returns:
+/// - Whether p lies inside the circumcircle of a, b and c
+///
+fn in_circle(a: (f64, f64), b: (f64, f64), c: (f64, f64), p: (f64, f64)) -> bool {
+    let (dx, dy) = (a.0 - p.0, a.1 - p.1);
+    let (ex, ey) = (b.0 - p.0, b.1 - p.1);
+    let (fx, fy) = (c.0 - p.0, c.1 - p.1);
+
+    let ap = dx * dx + dy * dy;
+    let bp = ex * ex + ey * ey;
+    let cp = fx * fx + fy * fy;
+
+    dx * (ey * cp - bp * fy) - dy * (ex * cp - bp * fx) + ap * (ex * fy - ey * fx) < 0.
+}
+
+///
+/// # Returns:
+/// - The circumcentre of a triangle, relative to its first vertex
+///
+fn circumcentre_offset(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (ex, ey) = (c.0 - a.0, c.1 - a.1);
+
+    let bl = dx * dx + dy * dy;
+    let cl = ex * ex + ey * ey;
+    let d = 0.5 / (dx * ey - dy * ex);
+
+    ((ey * bl - dy * cl) * d, (dx * cl - ex * bl) * d)
+}
+
+///
+/// # Returns:
+/// - The squared circumradius of a triangle, which is infinite or NaN for collinear points
+///
+fn circumradius(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    let (x, y) = circumcentre_offset(a, b, c);
+    let radius = x * x + y * y;
+    if radius.is_nan() { f64::INFINITY } else { radius }
+}
+
+///
+/// # Returns:
+/// - The circumcentre of a triangle
+///
+fn circumcentre(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> (f64, f64) {
+    let (x, y) = circumcentre_offset(a, b, c);
+    (a.0 + x, a.1 + y)
+}
+
+
+///
+/// Tests relating to delaunay triangulation.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use std::collections::HashMap;
+
+    fn random_points(count: usize, seed: u64) -> Vec<Point> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count).map(|_| Point::new(rng.random::<f32>() * 1000., rng.random::<f32>() * 1000.)).collect()
+    }
+
+    fn coords(point: Point) -> (f64, f64) {
+        point.into()
+    }
+
+    /// Twice the signed area of a triangle, positive when it is anticlockwise.
+    fn signed_area(points: &[Point], t: &[usize; 3]) -> f64 {
+        let (a, b, c) = (coords(points[t[0]]), coords(points[t[1]]), coords(points[t[2]]));
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+    }
+
+    #[test]
+    fn circumcircles_are_empty() {
+        let points = random_points(500, 3);
+        let triangles = triangulate(&points);
+
+        for t in triangles.iter() {
+            assert!(signed_area(&points, t) > 0., "{:?} isn't anticlockwise", t);
+
+            let centre = circumcentre(coords(points[t[0]]), coords(points[t[1]]), coords(points[t[2]]));
+            let radius_sq = dist_sq(coords(points[t[0]]), centre);
+            for (idx, &point) in points.iter().enumerate() {
+                if !t.contains(&idx) {
+                    assert!(dist_sq(coords(point), centre) >= radius_sq * (1. - 1e-9), "point {} is inside the circumcircle of {:?}", idx, t);
+                }
+            }
+        }
+
+        // every point is used, and the triangles cover the convex hull, by euler's formula
+        // where the hull is made of the edges with only one triangle
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for t in triangles.iter() {
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        assert!(edges.values().all(|&count| count <= 2));
+        let hull = edges.values().filter(|&&count| count == 1).count();
+        assert_eq!(triangles.len(), 2 * points.len() - 2 - hull);
+    }
+
+    #[test]
+    fn grids_give_two_triangles_per_square() {
+        let (columns, rows) = (12, 9);
+        let points: Vec<Point> = (0..columns * rows).map(|i| Point::new((i % columns) as f32 * 10., (i / columns) as f32 * 10.)).collect();
+        let triangles = triangulate(&points);
+
+        assert_eq!(triangles.len(), 2 * (columns - 1) * (rows - 1));
+        let area: f64 = triangles.iter().map(|t| signed_area(&points, t) / 2.).sum();
+        assert!((area - ((columns - 1) * (rows - 1) * 100) as f64).abs() < 1e-6, "{}", area);
+        assert!(triangles.iter().all(|t| signed_area(&points, t) > 0.));
+    }
+
+    #[test]
+    fn duplicate_points_are_in_no_triangle() {
+        let mut points = random_points(100, 5);
+        points.extend_from_slice(&points[..10].to_vec());
+        let triangles = triangulate(&points);
+
+        // one of each pair of duplicates is used, and the other is left out
+        for idx in 0..10 {
+            let used = |i: usize| triangles.iter().any(|t| t.contains(&i));
+            assert!(used(idx) != used(100 + idx), "point {}", idx);
+        }
+        assert_eq!(triangles.len(), triangulate(&points[..100]).len());
+    }
+
+    #[test]
+    fn degenerate_inputs_have_no_triangles() {
+        let collinear: Vec<Point> = (0..20).map(|i| Point::new(i as f32, 2. * i as f32)).collect();
+        assert!(triangulate(&collinear).is_empty());
+
+        assert!(triangulate(&[]).is_empty());
+        assert!(triangulate(&[Point::new(0., 0.), Point::new(1., 1.)]).is_empty());
+        assert!(triangulate(&[Point::new(1., 1.); 5]).is_empty());
+
+        // collinear points on the edge of a triangle are still joined to it
+        let mut points = collinear.clone();
+        points.push(Point::new(0., 10.));
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), collinear.len() - 1);
+    }
+}
@@ -0,0 +1,195 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ordered_float::OrderedFloat;
+
+use crate::drawing::util::geometry::Point;
+
+///
+/// A graph stored as an adjacency matrix. `matrix[a][b]` is the weight of the edge from `a` to
+/// `b`, or `None` if the two vertices are not connected.
+///
+pub type AdjacencyMatrix = Vec<Vec<Option<f64>>>;
+
+///
+/// Builds a complete, undirected graph over a set of points, weighted by the distance between
+/// each pair of points.
+///
+/// # Parameters:
+/// - `points`: The points to use as vertices
+///
+/// # Returns:
+/// - The adjacency matrix of the graph
+///
+pub fn complete_graph(points: &[Point]) -> AdjacencyMatrix {
+    points.iter().enumerate().map(|(a, pa)| {
+        points.iter().enumerate().map(|(b, pb)| if a == b { None } else { Some(pa.distance(pb) as f64) }).collect()
+    }).collect()
+}
+
+///
+/// Finds the shortest path from a source vertex to every other vertex, using Dijkstra's
+/// algorithm with a binary heap. Edge weights must not be negative.
+///
+/// # Parameters:
+/// - `adjacency`: The graph, as an adjacency matrix
+/// - `source`: The vertex to measure paths from
+///
+/// # Returns:
+/// - The distance to each vertex, or `f64::INFINITY` if a vertex can't be reached
+/// - The previous vertex on the shortest path to each vertex, or `None` for the source and
+///   unreachable vertices
+///
+pub fn dijkstras(adjacency: &AdjacencyMatrix, source: usize) -> (Vec<f64>, Vec<Option<usize>>) {
+    let n = adjacency.len();
+    let mut distances = vec![f64::INFINITY; n];
+    let mut previous: Vec<Option<usize>> = vec![None; n];
+
+    if source >= n {
+        return (distances, previous);
+    }
+
+    let mut queue = BinaryHeap::new();
+    distances[source] = 0.;
+    queue.push(Reverse((OrderedFloat(0.), source)));
+
+    while let Some(Reverse((OrderedFloat(distance), vertex))) = queue.pop() {
+        // stale entries are left in the heap rather than decreasing their key
+        if distance > distances[vertex] {
+            continue;
+        }
+
+        for (neighbour, weight) in adjacency[vertex].iter().enumerate() {
+            let Some(weight) = weight else { continue };
+
+            let candidate = distance + weight;
+            if candidate < distances[neighbour] {
+                distances[neighbour] = candidate;
+                previous[neighbour] = Some(vertex);
+                queue.push(Reverse((OrderedFloat(candidate), neighbour)));
+            }
+        }
+    }
+
+    (distances, previous)
+}
+
+///
+/// Follows the previous vertices returned by `dijkstras` back from a target vertex, to build the
+/// shortest path to it.
+///
+/// # Parameters:
+/// - `previous`: The previous vertex of each vertex, from `dijkstras`
+/// - `source`: The source vertex `dijkstras` was run from
+/// - `target`: The vertex to find the path to
+///
+/// # Returns:
+/// - The vertices along the path, from the source to the target
+/// - `None` if the target can't be reached
+///
+pub fn shortest_path(previous: &[Option<usize>], source: usize, target: usize) -> Option<Vec<usize>> {
+    let mut path = vec![target];
+    let mut current = target;
+
+    while current != source {
+        current = previous.get(current).copied().flatten()?;
+        path.push(current);
+    }
+
+    path.reverse();
+    Some(path)
+}
+
+///
+/// Finds the minimum spanning tree of an undirected graph, using Prim's algorithm with a binary
+/// heap. If the graph is disconnected, a spanning forest is returned instead, with one tree per
+/// connected component.
+///
+/// # Parameters:
+/// - `adjacency`: The graph, as a symmetric adjacency matrix
+///
+/// # Returns:
+/// - The edges of the tree, as (parent, child) pairs of vertices
+///
+pub fn minimum_spanning_tree(adjacency: &AdjacencyMatrix) -> Vec<(usize, usize)> {
+    let n = adjacency.len();
+    let mut in_tree = vec![false; n];
+    let mut edges: Vec<(usize, usize)> = Vec::with_capacity(n.saturating_sub(1));
+    let mut queue = BinaryHeap::new();
+
+    for root in 0..n {
+        if in_tree[root] {
+            continue;
+        }
+
+        // each entry is (weight, vertex, the tree vertex it is joined from)
+        queue.push(Reverse((OrderedFloat(0.), root, usize::MAX)));
+
+        while let Some(Reverse((_, vertex, parent))) = queue.pop() {
+            if in_tree[vertex] {
+                continue;
+            }
+
+            in_tree[vertex] = true;
+            if parent != usize::MAX {
+                edges.push((parent, vertex));
+            }
+
+            for (neighbour, weight) in adjacency[vertex].iter().enumerate() {
+                if let Some(weight) = weight && !in_tree[neighbour] {
+                    queue.push(Reverse((OrderedFloat(*weight), neighbour, vertex)));
+                }
+            }
+        }
+    }
+
+    edges
+}
+
+///
+/// Tests relating to shortest paths and spanning trees.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(n: usize, edges: &[(usize, usize, f64)]) -> AdjacencyMatrix {
+        let mut adjacency = vec![vec![None; n]; n];
+        for &(a, b, weight) in edges {
+            adjacency[a][b] = Some(weight);
+            adjacency[b][a] = Some(weight);
+        }
+        adjacency
+    }
+
+    #[test]
+    fn shortest_paths() {
+        let adjacency = graph(5, &[(0, 1, 4.), (0, 2, 1.), (2, 1, 2.), (1, 3, 1.), (2, 3, 5.)]);
+        let (distances, previous) = dijkstras(&adjacency, 0);
+
+        assert_eq!(distances[..4], [0., 3., 1., 4.]);
+        assert!(distances[4].is_infinite());
+        assert_eq!(shortest_path(&previous, 0, 3), Some(vec![0, 2, 1, 3]));
+        assert_eq!(shortest_path(&previous, 0, 4), None);
+    }
+
+    #[test]
+    fn spanning_tree() {
+        let adjacency = graph(4, &[(0, 1, 1.), (1, 2, 2.), (0, 2, 3.), (2, 3, 1.), (0, 3, 5.)]);
+        let edges = minimum_spanning_tree(&adjacency);
+
+        let total: f64 = edges.iter().map(|&(a, b)| adjacency[a][b].unwrap()).sum();
+        assert_eq!(edges.len(), 3);
+        assert_eq!(total, 4.);
+    }
+
+    #[test]
+    fn spanning_forest() {
+        let adjacency = graph(4, &[(0, 1, 1.), (2, 3, 1.)]);
+        assert_eq!(minimum_spanning_tree(&adjacency).len(), 2);
+
+        let points = [Point::new(0., 0.), Point::new(3., 4.), Point::new(0., 1.)];
+        let edges = minimum_spanning_tree(&complete_graph(&points));
+        assert_eq!(edges, vec![(0, 2), (2, 1)]);
+    }
+}
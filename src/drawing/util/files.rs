@@ -0,0 +1,54 @@
+//!
+//! An in-memory file store, read before the filesystem. Builds without a filesystem (such as
+//! the browser) insert image and audio files here, and drawing methods refer to them by name.
+//!
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+static FILES: Lazy<Mutex<HashMap<String, Vec<u8>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+///
+/// Stores the contents of a file in memory, replacing any file with the same name.
+///
+/// # Parameters:
+/// - `name`: The name drawing methods refer to the file by, in place of a path
+/// - `bytes`: The contents of the file
+///
+pub fn insert(name: &str, bytes: Vec<u8>) {
+    FILES.lock().unwrap().insert(name.to_owned(), bytes);
+}
+
+///
+/// Removes a file from memory.
+///
+/// # Parameters:
+/// - `name`: The name of the file
+///
+/// # Returns:
+/// - Whether there was a file with the name
+///
+pub fn remove(name: &str) -> bool {
+    FILES.lock().unwrap().remove(name).is_some()
+}
+
+///
+/// Reads a file, from memory if one was inserted with the path as its name, else from the
+/// filesystem.
+///
+/// # Parameters:
+/// - `path`: The name or path of the file
+///
+/// # Returns:
+/// - The contents of the file
+/// - An error explaining why the file could not be read
+///
+pub fn read(path: &str) -> Result<Vec<u8>, String> {
+    if let Some(bytes) = FILES.lock().unwrap().get(path) {
+        return Ok(bytes.clone());
+    }
+
+    std::fs::read(path).map_err(|err| format!("Error reading {}. {}", path, err))
+}
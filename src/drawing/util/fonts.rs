@@ -0,0 +1,538 @@
+use std::collections::HashMap;
+
+use crate::drawing::util::files;
+
+///
+/// A single-stroke glyph, drawn as a set of polylines.
+///
+/// # Fields:
+/// - `strokes`: The polylines of the glyph in font units, with y pointing down the page
+/// - `advance`: How far along the pen moves after drawing the glyph, in font units
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Glyph {
+    pub strokes: Vec<Vec<(f64, f64)>>,
+    pub advance: f64,
+}
+
+///
+/// A single-stroke font, made of polyline glyphs, for drawing text with a pen.
+///
+/// # Fields:
+/// - `glyphs`: The glyph of each character
+/// - `kerning`: Adjustments to the advance between pairs of characters, in font units
+/// - `units_per_em`: The number of font units in one em, which is the font size
+/// - `ascent`: The height of capital letters above the baseline, in font units
+///
+#[derive(Clone, Debug, Default)]
+pub struct Font {
+    pub glyphs: HashMap<char, Glyph>,
+    pub kerning: HashMap<(char, char), f64>,
+    pub units_per_em: f64,
+    pub ascent: f64,
+}
+
+impl Font {
+    ///
+    /// Parses Hershey font data, in the .jhf format. Glyphs are assigned to characters in
+    /// order, starting from a space, as in the standard Hershey ASCII fonts.
+    ///
+    /// # Parameters:
+    /// - `data`: The contents of the .jhf file
+    ///
+    /// # Returns:
+    /// - The parsed font
+    /// - An error explaining why the data could not be parsed
+    ///
+    pub fn from_hershey(data: &str) -> Result<Font, String> {
+        let mut font = Font { units_per_em: 32., ascent: 21., ..Default::default() };
+
+        // long glyphs are wrapped onto continuation lines, so lines are joined until each glyph's
+        // declared number of vertices has been read
+        let mut lines = data.lines().filter(|line| !line.trim().is_empty());
+        let mut character = ' ';
+        while let Some(first_line) = lines.next() {
+            let mut record = first_line.to_owned();
+
+            let vertex_count: usize = match record.get(5..8).map(|c| c.trim().parse()) {
+                Some(Ok(val)) => val,
+                _ => return Err(format!("Hershey glyph for {:?} has an invalid vertex count", character)),
+            };
+            let length = 8 + vertex_count * 2;
+            while record.len() < length {
+                match lines.next() {
+                    Some(next) => record.push_str(next),
+                    None => return Err(format!("Hershey glyph for {:?} is missing vertices", character)),
+                }
+            }
+
+            // every coordinate is one ASCII character, so anything else is a corrupt file
+            let coordinates = match record.get(8..length) {
+                Some(val) if val.is_ascii() => val,
+                _ => return Err(format!("Hershey glyph for {:?} has vertices which aren't ASCII", character)),
+            };
+            font.glyphs.insert(character, parse_hershey_glyph(coordinates));
+            character = match char::from_u32(character as u32 + 1) {
+                Some(val) => val,
+                None => break,
+            };
+        }
+
+        Ok(font)
+    }
+
+    ///
+    /// Loads a Hershey or SVG font, telling them apart by whether the file starts with a tag.
+    ///
+    /// # Parameters:
+    /// - `path`: The path of the .jhf or .svg file, or the name of a file in memory
+    ///
+    /// # Returns:
+    /// - The parsed font
+    /// - An error explaining why the font could not be loaded
+    ///
+    pub fn load(path: &str) -> Result<Font, String> {
+        let bytes = files::read(path).map_err(|err| format!("Error loading font. {}", err))?;
+        let data = String::from_utf8(bytes).map_err(|_| "Error decoding font. It isn't a text file".to_owned())?;
+
+        if data.trim_start().starts_with('<') {
+            Font::from_svg(&data)
+        } else {
+            Font::from_hershey(&data)
+        }
+    }
+
+    ///
+    /// Parses a simple SVG font, reading the `<font-face>`, `<glyph>` and `<hkern>` elements.
+    /// Glyph paths may use lines and curves, which are flattened into polylines. Closed paths
+    /// are drawn back to their start, as single-stroke fonts have no fills.
+    ///
+    /// # Parameters:
+    /// - `data`: The contents of the .svg file
+    ///
+    /// # Returns:
+    /// - The parsed font
+    /// - An error explaining why the data could not be parsed
+    ///
+    pub fn from_svg(data: &str) -> Result<Font, String> {
+        let mut font = Font { units_per_em: 1000., ascent: 750., ..Default::default() };
+        let mut default_advance = 500.;
+
+        for element in data.split('<').skip(1) {
+            let name = element.split(|c: char| c.is_whitespace() || c == '>' || c == '/').next().unwrap_or("");
+            let attribute = |key: &str| svg_attribute(element, key);
+            let number = |key: &str| attribute(key).and_then(|v| v.parse::<f64>().ok());
+
+            match name {
+                "font" => default_advance = number("horiz-adv-x").unwrap_or(default_advance),
+                "font-face" => {
+                    font.units_per_em = number("units-per-em").unwrap_or(font.units_per_em);
+                    font.ascent = number("cap-height").or(number("ascent")).unwrap_or(font.ascent);
+                },
+                "glyph" => {
+                    let Some(unicode) = attribute("unicode") else { continue };
+                    let unicode = decode_entities(&unicode);
+                    let mut chars = unicode.chars();
+                    let (Some(character), None) = (chars.next(), chars.next()) else { continue };
+
+                    // svg fonts have y pointing up, so glyphs are flipped onto the page
+                    let strokes = match attribute("d") {
+                        Some(d) => parse_svg_path(&d)?.into_iter().map(|stroke| stroke.into_iter().map(|(x, y)| (x, -y)).collect()).collect(),
+                        None => vec![],
+                    };
+                    font.glyphs.insert(character, Glyph { strokes, advance: number("horiz-adv-x").unwrap_or(default_advance) });
+                },
+                "hkern" => {
+                    let (Some(u1), Some(u2), Some(k)) = (attribute("u1"), attribute("u2"), number("k")) else { continue };
+                    for first in decode_entities(&u1).split(',').filter_map(|s| s.chars().next()) {
+                        for second in decode_entities(&u2).split(',').filter_map(|s| s.chars().next()) {
+                            // svg kerning is subtracted from the advance
+                            font.kerning.insert((first, second), -k);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if font.glyphs.is_empty() {
+            return Err("SVG font has no glyphs".to_owned());
+        }
+        Ok(font)
+    }
+
+    ///
+    /// Lays out a line of text as polylines, applying kerning between characters. Characters
+    /// missing from the font are skipped, advancing by half an em.
+    ///
+    /// # Parameters:
+    /// - `text`: The text to lay out
+    /// - `size`: The font size, which is the height of one em in millimetres
+    /// - `origin`: The position of the start of the text's baseline, in millimetres
+    ///
+    /// # Returns:
+    /// - The polylines of the text, in millimetres
+    ///
+    pub fn layout(&self, text: &str, size: f64, origin: (f64, f64)) -> Vec<Vec<(f64, f64)>> {
+        let scale = size / self.units_per_em;
+        let mut pen_x = 0.;
+        let mut polylines = vec![];
+        let mut previous: Option<char> = None;
+
+        for character in text.chars() {
+            if let Some(before) = previous {
+                pen_x += self.kerning.get(&(before, character)).copied().unwrap_or(0.);
+            }
+            previous = Some(character);
+
+            let Some(glyph) = self.glyphs.get(&character) else {
+                pen_x += self.units_per_em / 2.;
+                continue;
+            };
+
+            for stroke in glyph.strokes.iter() {
+                polylines.push(stroke.iter().map(|(x, y)| (origin.0 + (pen_x + x) * scale, origin.1 + y * scale)).collect());
+            }
+            pen_x += glyph.advance;
+        }
+
+        polylines
+    }
+
+    ///
+    /// Measures the width of a line of text, including kerning.
+    ///
+    /// # Parameters:
+    /// - `text`: The text to measure
+    /// - `size`: The font size, which is the height of one em in millimetres
+    ///
+    /// # Returns:
+    /// - The width of the text, in millimetres
+    ///
+    pub fn measure(&self, text: &str, size: f64) -> f64 {
+        let mut width = 0.;
+        let mut previous: Option<char> = None;
+
+        for character in text.chars() {
+            if let Some(before) = previous {
+                width += self.kerning.get(&(before, character)).copied().unwrap_or(0.);
+            }
+            previous = Some(character);
+            width += self.glyphs.get(&character).map(|g| g.advance).unwrap_or(self.units_per_em / 2.);
+        }
+
+        width * size / self.units_per_em
+    }
+}
+
+///
+/// Parses the coordinates of a Hershey glyph. Each coordinate is a character, offset from 'R',
+/// and the pair " R" lifts the pen. The first pair holds the left and right extents of the glyph.
+///
+/// # Parameters:
+/// - `coordinates`: The ASCII coordinate characters of the glyph, including the extents
+///
+/// # Returns:
+/// - The glyph, shifted so its left extent is at x = 0
+///
+fn parse_hershey_glyph(coordinates: &str) -> Glyph {
+    let value = |c: u8| c as f64 - b'R' as f64;
+    let bytes = coordinates.as_bytes();
+    if bytes.len() < 2 {
+        return Glyph::default();
+    }
+
+    let (left, right) = (value(bytes[0]), value(bytes[1]));
+    let mut strokes: Vec<Vec<(f64, f64)>> = vec![];
+    let mut stroke: Vec<(f64, f64)> = vec![];
+
+    for pair in bytes[2..].chunks_exact(2) {
+        if pair == b" R" {
+            if stroke.len() > 1 {
+                strokes.push(std::mem::take(&mut stroke));
+            }
+            stroke.clear();
+            continue;
+        }
+        stroke.push((value(pair[0]) - left, value(pair[1])));
+    }
+    if stroke.len() > 1 {
+        strokes.push(stroke);
+    }
+
+    Glyph { strokes, advance: right - left }
+}
+
+///
+/// Finds the value of an attribute within an SVG element.
+///
+/// # Parameters:
+/// - `element`: The text of the element, after its opening '<'
+/// - `key`: The attribute name
+///
+/// # Returns:
+/// - The attribute's value, if it is present
+///
+fn svg_attribute(element: &str, key: &str) -> Option<String> {
+    let tag_end = element.find('>').unwrap_or(element.len());
+    let tag = &element[..tag_end];
+
+    let mut search_from = 0;
+    while let Some(found) = tag[search_from..].find(key) {
+        let start = search_from + found;
+        search_from = start + key.len();
+
+        // the key must be a whole attribute name, not part of a longer one
+        let preceded_by_space = tag[..start].ends_with(|c: char| c.is_whitespace());
+        let rest = tag[search_from..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let rest = rest[1..].trim_start();
+        let quote = rest.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let end = rest[1..].find(quote)?;
+        return Some(rest[1..1 + end].to_owned());
+    }
+
+    None
+}
+
+///
+/// Decodes the XML entities which commonly appear in SVG font unicode attributes.
+///
+fn decode_entities(text: &str) -> String {
+    let mut decoded = text.replace("&quot;", "\"").replace("&apos;", "'").replace("&lt;", "<").replace("&gt;", ">");
+
+    // numeric entities, such as &#x41; or &#65;
+    while let Some(start) = decoded.find("&#") {
+        let Some(length) = decoded[start..].find(';') else { break };
+        let entity = &decoded[start + 2..start + length];
+        let code = match entity.strip_prefix('x') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => entity.parse().ok(),
+        };
+        let replacement = code.and_then(char::from_u32).map(String::from).unwrap_or_default();
+        decoded.replace_range(start..start + length + 1, &replacement);
+    }
+
+    decoded.replace("&amp;", "&")
+}
+
+///
+/// Parses SVG path data into polylines. Lines, horizontal and vertical lines, cubic and
+/// quadratic curves and closes are supported, in absolute and relative forms. Curves are
+/// flattened into a fixed number of line segments.
+///
+/// # Parameters:
+/// - `d`: The path data
+///
+/// # Returns:
+/// - The polylines of the path, in path units
+/// - An error explaining why the path could not be parsed
+///
+pub fn parse_svg_path(d: &str) -> Result<Vec<Vec<(f64, f64)>>, String> {
+    const CURVE_SEGMENTS: usize = 8;
+
+    // split the path into command letters and numbers
+    let mut tokens: Vec<String> = vec![];
+    let mut number = String::new();
+    for c in d.chars() {
+        let starts_new_number = (c == '-' && !number.is_empty() && !number.ends_with(['e', 'E'])) || (c == '.' && number.contains('.'));
+        if c.is_ascii_alphabetic() && c != 'e' && c != 'E' || c == ',' || c.is_whitespace() || starts_new_number {
+            if !number.is_empty() {
+                tokens.push(std::mem::take(&mut number));
+            }
+            if c.is_ascii_alphabetic() {
+                tokens.push(c.to_string());
+            } else if starts_new_number {
+                number.push(c);
+            }
+        } else {
+            number.push(c);
+        }
+    }
+    if !number.is_empty() {
+        tokens.push(number);
+    }
+
+    let mut polylines: Vec<Vec<(f64, f64)>> = vec![];
+    let mut current: Vec<(f64, f64)> = vec![];
+    let (mut x, mut y) = (0., 0.);
+    let mut start = (0., 0.);
+    let mut command = 'M';
+    let mut index = 0;
+
+    let read = |index: &mut usize| -> Result<f64, String> {
+        let token = tokens.get(*index).ok_or("SVG path ended in the middle of a command")?;
+        *index += 1;
+        token.parse::<f64>().map_err(|_| format!("SVG path has an invalid number `{}`", token))
+    };
+
+    while index < tokens.len() {
+        if let Some(c) = tokens[index].chars().next().filter(|c| c.is_ascii_alphabetic()) {
+            command = c;
+            index += 1;
+        }
+
+        let relative = command.is_ascii_lowercase();
+        let (ox, oy) = if relative { (x, y) } else { (0., 0.) };
+
+        match command.to_ascii_uppercase() {
+            'M' => {
+                if current.len() > 1 {
+                    polylines.push(std::mem::take(&mut current));
+                }
+                current.clear();
+                (x, y) = (ox + read(&mut index)?, oy + read(&mut index)?);
+                start = (x, y);
+                current.push((x, y));
+
+                // further coordinate pairs after a move are lines
+                command = if relative { 'l' } else { 'L' };
+            },
+            'L' => {
+                (x, y) = (ox + read(&mut index)?, oy + read(&mut index)?);
+                current.push((x, y));
+            },
+            'H' => {
+                x = ox + read(&mut index)?;
+                current.push((x, y));
+            },
+            'V' => {
+                y = oy + read(&mut index)?;
+                current.push((x, y));
+            },
+            'C' | 'Q' => {
+                let cubic = command.eq_ignore_ascii_case(&'C');
+                let control_count = if cubic { 3 } else { 2 };
+                let mut controls = vec![(x, y)];
+                for _ in 0..control_count {
+                    controls.push((ox + read(&mut index)?, oy + read(&mut index)?));
+                }
+
+                for step in 1..=CURVE_SEGMENTS {
+                    current.push(bezier_point(&controls, step as f64 / CURVE_SEGMENTS as f64));
+                }
+                (x, y) = controls[control_count];
+            },
+            'Z' => {
+                if current.len() > 1 {
+                    current.push(start);
+                    polylines.push(std::mem::take(&mut current));
+                }
+                current.clear();
+                (x, y) = start;
+                current.push(start);
+            },
+            other => return Err(format!("SVG path command `{}` is not supported", other)),
+        }
+
+        if command.eq_ignore_ascii_case(&'Z') && index < tokens.len() && !tokens[index].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Err("SVG path has numbers after a close command".to_owned());
+        }
+    }
+
+    if current.len() > 1 {
+        polylines.push(current);
+    }
+    Ok(polylines)
+}
+
+///
+/// Evaluates a bezier curve of any degree, with de Casteljau's algorithm.
+///
+/// # Parameters:
+/// - `controls`: The control points of the curve, including its endpoints
+/// - `t`: How far along the curve to evaluate, between 0 and 1
+///
+/// # Returns:
+/// - The point on the curve
+///
+fn bezier_point(controls: &[(f64, f64)], t: f64) -> (f64, f64) {
+    let mut points = controls.to_vec();
+    while points.len() > 1 {
+        points = points.windows(2).map(|pair| (pair[0].0 + (pair[1].0 - pair[0].0) * t, pair[0].1 + (pair[1].1 - pair[0].1) * t)).collect();
+    }
+    points[0]
+}
+
+
+///
+/// Tests relating to loading fonts and laying out text.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A space and the letter A from the Hershey roman simplex font, with A wrapped over two lines.
+    /// Glyphs are assigned to characters in order, so the A is read as '!'.
+    const HERSHEY: &str = "12345  1JZ\n  501  9I[RFJ[ RRFZ[\n RMTWT\n";
+
+    #[test]
+    fn parsing_hershey_glyphs() {
+        let font = Font::from_hershey(HERSHEY).unwrap();
+
+        assert_eq!(font.glyphs[&' '], Glyph { strokes: vec![], advance: 16. });
+        let a = &font.glyphs[&'!'];
+        assert_eq!(a.advance, 18.);
+        assert_eq!(a.strokes, vec![vec![(9., -12.), (1., 9.)], vec![(9., -12.), (17., 9.)], vec![(4., 2.), (14., 2.)]]);
+    }
+
+    #[test]
+    fn malformed_hershey_is_an_error() {
+        for data in ["  501", "  501 xx", "  501 20I[RF", "  501  2J\u{e9}\u{e9}", "  501  2J\u{e9}Z"] {
+            assert!(Font::from_hershey(data).is_err(), "{:?}", data);
+        }
+        assert!(Font::from_hershey("").unwrap().glyphs.is_empty());
+    }
+
+    #[test]
+    fn parsing_svg_fonts() {
+        let data = r#"<svg><font horiz-adv-x="400"><font-face units-per-em="1000" cap-height="700"/>
+            <glyph unicode="L" d="M 100 700 L 100 0 L 400 0"/>
+            <glyph unicode="&#x56;" horiz-adv-x="600" d="M0 700l300-700 300 700"/>
+            <hkern u1="L" u2="V" k="100"/>
+        </font></svg>"#;
+        let font = Font::from_svg(data).unwrap();
+
+        assert_eq!((font.units_per_em, font.ascent), (1000., 700.));
+        assert_eq!(font.glyphs[&'L'], Glyph { strokes: vec![vec![(100., -700.), (100., 0.), (400., 0.)]], advance: 400. });
+        assert_eq!(font.glyphs[&'V'].strokes, vec![vec![(0., -700.), (300., 0.), (600., -700.)]]);
+        assert_eq!(font.kerning[&('L', 'V')], -100.);
+
+        assert!(Font::from_svg("<svg></svg>").is_err());
+        assert!(Font::from_svg(r#"<glyph unicode="x" d="M 0 0 A 1 1"/>"#).is_err());
+    }
+
+    #[test]
+    fn layout_applies_size_and_kerning() {
+        let font = Font::from_svg(r#"<font-face units-per-em="100"/><glyph unicode="a" horiz-adv-x="50" d="M0 0L50 0"/><hkern u1="a" u2="a" k="10"/>"#).unwrap();
+
+        // each a is 5mm wide at a 10mm size, and pairs are kerned 1mm closer
+        assert_eq!(font.measure("aa", 10.), 9.);
+        assert_eq!(font.layout("aa", 10., (20., 30.)), vec![vec![(20., 30.), (25., 30.)], vec![(24., 30.), (29., 30.)]]);
+
+        // missing characters advance by half an em
+        assert_eq!(font.measure("a?a", 10.), 15.);
+        assert_eq!(font.layout("?", 10., (0., 0.)), Vec::<Vec<(f64, f64)>>::new());
+    }
+
+    #[test]
+    fn loading_either_format() {
+        files::insert("fonts-test.jhf", HERSHEY.as_bytes().to_vec());
+        files::insert("fonts-test.svg", br#"<glyph unicode="a" d="M0 0L50 0"/>"#.to_vec());
+        files::insert("fonts-test.bin", vec![0xff, 0xfe]);
+
+        assert!(Font::load("fonts-test.jhf").unwrap().glyphs.contains_key(&'!'));
+        assert!(Font::load("fonts-test.svg").unwrap().glyphs.contains_key(&'a'));
+        assert!(Font::load("fonts-test.bin").is_err());
+
+        for name in ["fonts-test.jhf", "fonts-test.svg", "fonts-test.bin"] {
+            files::remove(name);
+        }
+    }
+}
@@ -1,3 +1,72 @@
+use ordered_float::OrderedFloat;
+
+
+///
+/// A representation of a 2D point, shared by the drawing utilities. Points are stored as ordered
+/// floats so they can be compared, sorted and hashed; plain `(f64, f64)` tuples, as used by
+/// `DrawSurface` and the preview canvas, convert to and from points with `From`.
+///
+/// # Fields:
+/// - `x`: An f32 for the x component
+/// - `y`: An f32 for the y component
+///
+#[derive(Eq, PartialEq, Clone, Debug, Copy)]
+pub struct Point {
+    pub x: OrderedFloat<f32>,
+    pub y: OrderedFloat<f32>,
+}
+
+impl Point {
+    ///
+    /// Creates a new point.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The components of the point
+    ///
+    /// # Returns:
+    /// - A new `Point`
+    ///
+    pub fn new(x: f32, y: f32) -> Point {
+        Point { x: OrderedFloat(x), y: OrderedFloat(y) }
+    }
+
+    ///
+    /// Calculates the euclidean distance to another point.
+    ///
+    /// # Returns:
+    /// - An f32 representing the straight line distance
+    ///
+    pub fn distance(&self, other: &Self) -> f32 {
+        self.distance_sq(other).sqrt()
+    }
+
+    ///
+    /// Calculates the squared euclidean distance to another point. This is cheaper than
+    /// `distance`, and gives the same ordering when comparing distances.
+    ///
+    /// # Returns:
+    /// - An f32 representing the squared straight line distance
+    ///
+    pub fn distance_sq(&self, other: &Self) -> f32 {
+        let dx = self.x.into_inner() - other.x.into_inner();
+        let dy = self.y.into_inner() - other.y.into_inner();
+        dx * dx + dy * dy
+    }
+}
+
+impl From<(f64, f64)> for Point {
+    fn from((x, y): (f64, f64)) -> Point {
+        Point::new(x as f32, y as f32)
+    }
+}
+
+impl From<Point> for (f64, f64) {
+    fn from(point: Point) -> (f64, f64) {
+        (point.x.into_inner() as f64, point.y.into_inner() as f64)
+    }
+}
+
+
 
 
 /// 
@@ -44,3 +113,457 @@ pub fn get_circle_samples(num_samples: usize, center: (f64, f64), radius: f64, t
 
     points
 }
+
+
+///
+/// Samples a polar curve, such as a spiral or rose, stepping the angle so every segment is as
+/// close to `max_segment` long as possible without going over. Stepping by a fixed angle instead
+/// bunches points wherever the radius is small, and spreads them wherever it's large.
+///
+/// # Parameters:
+/// - `center`: The point the radius is measured from
+/// - `r_of_theta`: The radius of the curve at an angle, in radians. Negative radii are drawn on the opposite side of the centre
+/// - `theta_range`: The (start, end) angles of the curve, in radians. The end may be below the start, to sample backwards
+/// - `max_segment`: The longest distance between two consecutive points
+///
+/// # Returns:
+/// - The points of the curve, at angles increasing (x towards y) from 0 along the x axis. The start and end
+///   angles are always sampled, and no step is more than 1/32 of a turn, so tiny curves keep their shape.
+///   A `max_segment` which isn't above 0 samples only the start and end
+///
+pub fn polar_path(center: (f64, f64), r_of_theta: impl Fn(f64) -> f64, theta_range: (f64, f64), max_segment: f64) -> Vec<(f64, f64)> {
+    const MAX_STEP: f64 = std::f64::consts::TAU / 32.;
+    const MAX_BEND: f64 = 0.02;
+
+    let point = |theta: f64| {
+        let radius = r_of_theta(theta);
+        (center.0 + radius * theta.cos(), center.1 + radius * theta.sin())
+    };
+
+    let (start, end) = theta_range;
+    let mut points = vec![point(start)];
+    if start == end {
+        return points;
+    }
+    if !(max_segment.is_finite() && max_segment > 0.) {
+        points.push(point(end));
+        return points;
+    }
+
+    // each step is guessed from how far the last one went, then shrunk until its segment fits. Steps
+    // are measured through their midpoint, and halved where the curve bends sharply, so spikes keep their tips
+    let direction = (end - start).signum();
+    let min_step = (end - start).abs() * 1e-9;
+    let mut theta = start;
+    let mut step = MAX_STEP;
+    while (end - theta) * direction > min_step {
+        step = step.clamp(min_step, MAX_STEP).min((end - theta).abs());
+
+        let previous = points[points.len() - 1];
+        let (mut next, mut length, mut chord) = polar_step(&point, previous, theta, step * direction);
+        while (length > max_segment || length - chord > max_segment * MAX_BEND) && length.is_finite() && step > min_step {
+            step = if length > max_segment { step * 0.99 * max_segment / length } else { step / 2. }.max(min_step);
+            (next, length, chord) = polar_step(&point, previous, theta, step * direction);
+        }
+
+        theta += step * direction;
+        points.push(next);
+
+        // the next step is scaled to land on `max_segment`, so steps grow back after a tight stretch of curve
+        step *= if length > 0. { (max_segment / length).min(2.) } else { 2. };
+    }
+
+    // the last point is kept exactly, rather than as the sum of every step
+    let last = points.len() - 1;
+    points[last] = point(end);
+    points
+}
+
+
+///
+/// # Returns:
+/// - The (point, length through the step's midpoint, straight line length) of a step along a polar curve
+///
+fn polar_step(point: &impl Fn(f64) -> (f64, f64), previous: (f64, f64), theta: f64, step: f64) -> ((f64, f64), f64, f64) {
+    let (middle, next) = (point(theta + step / 2.), point(theta + step));
+    let length = (middle.0 - previous.0).hypot(middle.1 - previous.1) + (next.0 - middle.0).hypot(next.1 - middle.1);
+    (next, length, (next.0 - previous.0).hypot(next.1 - previous.1))
+}
+
+
+///
+/// Clips a polygon against a convex clipping polygon, using the Sutherland-Hodgman algorithm.
+/// The subject polygon may be concave, though clipping a concave polygon can leave zero-width
+/// edges along the clip boundary.
+///
+/// # Parameters:
+/// - `subject`: The vertices of the polygon to clip
+/// - `clip`: The vertices of the convex clipping polygon, in either winding order
+///
+/// # Returns:
+/// - The vertices of the clipped polygon, or an empty vector if nothing is left
+///
+pub fn clip_polygon(subject: &[(f64, f64)], clip: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut output = subject.to_vec();
+    if clip.len() < 3 {
+        return output;
+    }
+
+    // the inside of each clip edge is on the left for anticlockwise clip polygons
+    let winding = signed_area(clip).signum();
+
+    for i in 0..clip.len() {
+        if output.is_empty() {
+            break;
+        }
+
+        let edge_start = clip[i];
+        let edge_end = clip[(i + 1) % clip.len()];
+        let inside = |p: (f64, f64)| winding * cross(edge_start, edge_end, p) >= 0.;
+
+        let input = std::mem::take(&mut output);
+        let mut previous = input[input.len() - 1];
+        for &current in input.iter() {
+            let crossing = line_intersection(previous, current, edge_start, edge_end);
+            let new_points: &[(f64, f64)] = match (inside(previous), inside(current)) {
+                (true, true) => &[current],
+                (true, false) => &[crossing],
+                (false, true) => &[crossing, current],
+                (false, false) => &[],
+            };
+
+            // vertices lying on the clip edge would otherwise be added twice
+            for &point in new_points {
+                if output.last() != Some(&point) {
+                    output.push(point);
+                }
+            }
+            previous = current;
+        }
+
+        if output.len() > 1 && output.first() == output.last() {
+            output.pop();
+        }
+    }
+
+    output
+}
+
+///
+/// Clips a polygon to a rectangle, such as the page or a keep-out zone.
+///
+/// # Parameters:
+/// - `subject`: The vertices of the polygon to clip
+/// - `x` and `y`: The top left corner of the rectangle
+/// - `width` and `height`: The size of the rectangle
+///
+/// # Returns:
+/// - The vertices of the clipped polygon, or an empty vector if nothing is left
+///
+pub fn clip_to_rectangle(subject: &[(f64, f64)], x: f64, y: f64, width: f64, height: f64) -> Vec<(f64, f64)> {
+    clip_polygon(subject, &[(x, y), (x + width, y), (x + width, y + height), (x, y + height)])
+}
+
+///
+/// Offsets a polygon by moving each edge along its normal, joining the edges with mitred
+/// corners. This is used to inset fills, and to draw thick strokes as several passes.
+/// Very sharp corners are bevelled, so their mitres don't shoot off to infinity.
+///
+/// # Parameters:
+/// - `polygon`: The vertices of the polygon, in either winding order
+/// - `distance`: How far to move each edge, positive to grow the polygon and negative to shrink it
+///
+/// # Returns:
+/// - The vertices of the offset polygon. Insetting by more than the polygon's half-width
+///   produces a self-intersecting polygon, which callers should stop at
+///
+pub fn offset_polygon(polygon: &[(f64, f64)], distance: f64) -> Vec<(f64, f64)> {
+    const MITRE_LIMIT: f64 = 4.;
+
+    let n = polygon.len();
+    if n < 3 || distance == 0. {
+        return polygon.to_vec();
+    }
+
+    // the normals point into the polygon, whichever its winding
+    let winding = signed_area(polygon).signum();
+    let normal = |a: (f64, f64), b: (f64, f64)| {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length = (dx * dx + dy * dy).sqrt().max(f64::EPSILON);
+        (-dy / length * winding, dx / length * winding)
+    };
+
+    let mut output = Vec::with_capacity(n);
+    for i in 0..n {
+        let before = polygon[(i + n - 1) % n];
+        let current = polygon[i];
+        let after = polygon[(i + 1) % n];
+
+        let n0 = normal(before, current);
+        let n1 = normal(current, after);
+
+        // the mitre direction bisects the normals, and is scaled to keep each edge at `distance`.
+        // the mitre is 1 / cos(half the angle between the normals) times longer than `distance`
+        let bisector = (n0.0 + n1.0, n0.1 + n1.1);
+        let cos_sq_half_angle = (bisector.0 * n1.0 + bisector.1 * n1.1) / 2.;
+
+        if cos_sq_half_angle * MITRE_LIMIT * MITRE_LIMIT < 1. {
+            output.push((current.0 - n0.0 * distance, current.1 - n0.1 * distance));
+            output.push((current.0 - n1.0 * distance, current.1 - n1.1 * distance));
+        } else {
+            let scale = distance / (2. * cos_sq_half_angle);
+            output.push((current.0 - bisector.0 * scale, current.1 - bisector.1 * scale));
+        }
+    }
+
+    output
+}
+
+///
+/// Offsets an open polyline sideways, joining its segments with mitred corners. Drawing a line
+/// several times at small offsets makes it thicker than the pen.
+///
+/// # Parameters:
+/// - `points`: The points of the polyline
+/// - `distance`: How far to move the line, positive to its left when travelling along it with
+///   y pointing down, and negative to its right
+///
+/// # Returns:
+/// - The points of the offset polyline, without any repeated points of the original
+///
+pub fn offset_polyline(points: &[(f64, f64)], distance: f64) -> Vec<(f64, f64)> {
+    const MITRE_LIMIT: f64 = 4.;
+
+    let mut points = points.to_vec();
+    points.dedup();
+    if points.len() < 2 || distance == 0. {
+        return points;
+    }
+
+    let normal = |a: (f64, f64), b: (f64, f64)| {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length = dx.hypot(dy);
+        (dy / length, -dx / length)
+    };
+
+    let n = points.len();
+    (0..n).map(|i| {
+        // the segments before and after the point, where the ends only have one segment
+        let (before, after) = (i.saturating_sub(1).min(n - 2), i.min(n - 2));
+        let n0 = normal(points[before], points[before + 1]);
+        let n1 = normal(points[after], points[after + 1]);
+
+        let bisector = (n0.0 + n1.0, n0.1 + n1.1);
+        let cos_half_angle = (bisector.0 * n1.0 + bisector.1 * n1.1) / bisector.0.hypot(bisector.1).max(f64::EPSILON);
+        let scale = distance / cos_half_angle.max(1. / MITRE_LIMIT) / bisector.0.hypot(bisector.1).max(f64::EPSILON);
+        (points[i].0 + bisector.0 * scale, points[i].1 + bisector.1 * scale)
+    }).collect()
+}
+
+///
+/// # Parameters:
+/// - `rings`: The rings of a polygon, such as an outline and its holes, in either winding order
+/// - `point`: The point to test
+///
+/// # Returns:
+/// - Whether the point is inside the polygon, by the even-odd rule so holes are outside
+///
+pub fn polygon_contains(rings: &[Vec<(f64, f64)>], point: (f64, f64)) -> bool {
+    let crossings = rings.iter()
+        .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)).take(ring.len()))
+        .filter(|(a, b)| (a.1 <= point.1) != (b.1 <= point.1))
+        .filter(|(a, b)| a.0 + (point.1 - a.1) / (b.1 - a.1) * (b.0 - a.0) > point.0)
+        .count();
+    crossings % 2 == 1
+}
+
+///
+/// Clips a polyline to a polygon, which may be concave or have holes. A polyline leaving and
+/// re-entering the polygon is split into several.
+///
+/// # Parameters:
+/// - `points`: The points of the polyline
+/// - `rings`: The rings of the polygon, such as an outline and its holes
+/// - `keep_inside`: Whether to keep the parts inside the polygon, or the parts outside it
+///
+/// # Returns:
+/// - The kept parts of the polyline, in order
+///
+pub fn clip_polyline_to_polygon(points: &[(f64, f64)], rings: &[Vec<(f64, f64)>], keep_inside: bool) -> Vec<Vec<(f64, f64)>> {
+    let edges: Vec<((f64, f64), (f64, f64))> = rings.iter()
+        .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)).take(ring.len()))
+        .map(|(a, b)| (*a, *b))
+        .collect();
+
+    let crossings = |start: (f64, f64), end: (f64, f64)| {
+        edges.iter().filter_map(|&(a, b)| {
+            // solves start + t * (end - start) = a + u * (b - a) for t and u
+            let denominator = (end.0 - start.0) * (b.1 - a.1) - (end.1 - start.1) * (b.0 - a.0);
+            if denominator == 0. {
+                return None;
+            }
+            let t = ((a.0 - start.0) * (b.1 - a.1) - (a.1 - start.1) * (b.0 - a.0)) / denominator;
+            let u = ((a.0 - start.0) * (end.1 - start.1) - (a.1 - start.1) * (end.0 - start.0)) / denominator;
+            ((0. ..=1.).contains(&t) && (0. ..=1.).contains(&u)).then_some(t)
+        }).collect()
+    };
+    split_polyline(points, crossings, |point| polygon_contains(rings, point) == keep_inside)
+}
+
+///
+/// Splits a polyline where it crosses the edge of a region, keeping the parts in the region.
+///
+/// # Parameters:
+/// - `points`: The points of the polyline
+/// - `crossings`: Finds where a segment crosses the edge of the region, as fractions of the way
+///   along it from 0 to 1, in any order
+/// - `keep`: Whether a point is in the region. Each piece of a segment between its crossings is
+///   kept if its middle is
+///
+/// # Returns:
+/// - The kept parts of the polyline, in order
+///
+pub fn split_polyline(points: &[(f64, f64)], crossings: impl Fn((f64, f64), (f64, f64)) -> Vec<f64>, keep: impl Fn((f64, f64)) -> bool) -> Vec<Vec<(f64, f64)>> {
+    let mut polylines: Vec<Vec<(f64, f64)>> = vec![];
+    let mut joined = false;
+
+    for segment in points.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        let at = |t: f64| (start.0 + (end.0 - start.0) * t, start.1 + (end.1 - start.1) * t);
+
+        let mut ts = crossings(start, end);
+        ts.extend([0., 1.]);
+        ts.sort_by(f64::total_cmp);
+        ts.dedup();
+
+        for piece in ts.windows(2) {
+            if !keep(at((piece[0] + piece[1]) / 2.)) {
+                joined = false;
+                continue;
+            }
+
+            let (piece_start, piece_end) = (at(piece[0]), at(piece[1]));
+            match polylines.last_mut() {
+                Some(polyline) if joined => polyline.push(piece_end),
+                _ => polylines.push(vec![piece_start, piece_end]),
+            }
+            joined = true;
+        }
+    }
+
+    polylines
+}
+
+///
+/// # Returns:
+/// - The signed area of a polygon, positive for anticlockwise winding with y pointing up
+///
+pub fn signed_area(polygon: &[(f64, f64)]) -> f64 {
+    let n = polygon.len();
+    (0..n).map(|i| {
+        let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+        a.0 * b.1 - b.0 * a.1
+    }).sum::<f64>() / 2.
+}
+
+///
+/// # Returns:
+/// - The z component of the cross product of (b - a) and (p - a)
+///
+fn cross(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+///
+/// # Returns:
+/// - The intersection of the infinite lines through p0-p1 and p2-p3, or p1 if they're parallel
+///
+fn line_intersection(p0: (f64, f64), p1: (f64, f64), p2: (f64, f64), p3: (f64, f64)) -> (f64, f64) {
+    let denominator = (p0.0 - p1.0) * (p2.1 - p3.1) - (p0.1 - p1.1) * (p2.0 - p3.0);
+    if denominator == 0. {
+        return p1;
+    }
+
+    let t = ((p0.0 - p2.0) * (p2.1 - p3.1) - (p0.1 - p2.1) * (p2.0 - p3.0)) / denominator;
+    (p0.0 + t * (p1.0 - p0.0), p0.1 + t * (p1.1 - p0.1))
+}
+
+///
+/// Tests relating to geometry utilities.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an L shape, concave at (4, 4)
+    const L_SHAPE: [(f64, f64); 6] = [(0., 0.), (10., 0.), (10., 4.), (4., 4.), (4., 10.), (0., 10.)];
+
+    fn segment_lengths(points: &[(f64, f64)]) -> Vec<f64> {
+        points.windows(2).map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1)).collect()
+    }
+
+    #[test]
+    fn polar_path_spiral_segments() {
+        // an archimedean spiral from the centre, where fixed angle steps would bunch up
+        let points = polar_path((10., 20.), |theta| theta, (0., 10. * std::f64::consts::TAU), 1.);
+        let lengths = segment_lengths(&points);
+
+        assert_eq!(points[0], (10., 20.));
+        assert!(lengths.iter().all(|&length| length <= 1. + 1e-9));
+        // the first turn is capped at 32 steps, but every later segment but the last is close to the maximum
+        assert!(lengths[32..lengths.len() - 1].iter().all(|&length| length > 0.95));
+    }
+
+    #[test]
+    fn polar_path_endpoints() {
+        let points = polar_path((0., 0.), |_| 5., (std::f64::consts::PI, 0.), 0.5);
+        let (first, last) = (points[0], points[points.len() - 1]);
+
+        assert!((first.0 + 5.).abs() < 1e-9 && first.1.abs() < 1e-9);
+        assert_eq!(last, (5., 0.));
+        // sampled backwards, through positive y
+        assert!(points[1].1 > 0.);
+        assert_eq!(polar_path((0., 0.), |_| 5., (0., 1.), 0.).len(), 2);
+    }
+
+    #[test]
+    fn clipping_convex_polygons() {
+        let square = [(0., 0.), (10., 0.), (10., 10.), (0., 10.)];
+        let overlapping = clip_polygon(&square, &[(5., 5.), (15., 5.), (15., 15.), (5., 15.)]);
+        assert!((signed_area(&overlapping).abs() - 25.).abs() < 1e-9);
+
+        // the clip polygon's winding doesn't matter, and a polygon inside it is unchanged
+        let reversed: Vec<(f64, f64)> = [(-1., -1.), (11., -1.), (11., 11.), (-1., 11.)].into_iter().rev().collect();
+        assert!((signed_area(&clip_polygon(&square, &reversed)).abs() - 100.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn clipping_concave_polygons() {
+        // only the 1x1 notch of the corner square is missing
+        let clipped = clip_to_rectangle(&L_SHAPE, 0., 0., 5., 5.);
+        assert!((signed_area(&clipped).abs() - 24.).abs() < 1e-9, "{:?}", clipped);
+        assert!(clipped.iter().all(|&(x, y)| (0. ..=5.).contains(&x) && (0. ..=5.).contains(&y)));
+    }
+
+    #[test]
+    fn clipping_polygons_outside_the_clip() {
+        assert!(clip_to_rectangle(&L_SHAPE, 20., 20., 5., 5.).is_empty());
+        assert!(clip_to_rectangle(&L_SHAPE, -10., 0., 5., 10.).is_empty());
+    }
+
+    #[test]
+    fn offsetting_polygons() {
+        let square = [(0., 0.), (10., 0.), (10., 10.), (0., 10.)];
+        let reversed: Vec<(f64, f64)> = square.into_iter().rev().collect();
+        for polygon in [&square[..], &reversed] {
+            assert!((signed_area(&offset_polygon(polygon, 1.)).abs() - 144.).abs() < 1e-9);
+            assert!((signed_area(&offset_polygon(polygon, -1.)).abs() - 64.).abs() < 1e-9);
+        }
+
+        // the concave corner moves diagonally, keeping both arms of the L the same width apart
+        let inset = offset_polygon(&L_SHAPE, -1.);
+        assert!((signed_area(&inset).abs() - 28.).abs() < 1e-9, "{:?}", inset);
+        assert!(inset.iter().any(|&(x, y)| (x - 3.).abs() < 1e-9 && (y - 3.).abs() < 1e-9));
+        assert!((signed_area(&offset_polygon(&L_SHAPE, 1.)).abs() - 108.).abs() < 1e-9);
+    }
+}
@@ -1,39 +1,29 @@
-use noise::{NoiseFn, PerlinSurflet};
+use crate::drawing::util::files;
+use crate::drawing::util::noise::{Fbm, NoiseBasis, Octave};
 
 /// 
-/// Generates a 2D vector of perlin noise, with an f64 between 0 and 1 to represent height.
+/// Generates a 2D vector of one octave of noise, with an f64 between -1 and 1 to represent height.
 ///
 /// # Parameters:
-/// - `seed`: A number to seed the perlin noise
+/// - `fbm`: The layered noise to sample
+/// - `octave`: The octave of the noise to sample
 /// - `width`: The number of horizontal samples
 /// - `height`: The number of vertical samples
 /// - `layer_height`: The y step-size per layer
-/// - `base_scale`: The scale of the perlin noise, between 1 and 255
 ///
 /// # Returns:
 /// - A 2D vector, with f64 values, in the form Row<Column<f64>>
 ///
-fn gen_heightmap(seed: u32, width: usize, height: usize, layer_height: f64, base_scale: f64) -> Vec<Vec<f64>> {
-    let perlin = PerlinSurflet::new(seed);
-
-    let base_scale_coefficient = 255. / base_scale;
-
-    let mut vec: Vec<Vec<f64>> = vec![];
-    for i in 0..height {
-        let mut v = vec![];
-        for j in 0..width {
-            v.push(perlin.get( [ (j as f64 / width as f64) * base_scale_coefficient, ((i as f64 * layer_height) / height as f64) * base_scale_coefficient ] ));
-        }
-        vec.push(v);
-    }
-
-    vec
+fn gen_heightmap(fbm: &Fbm, octave: usize, width: usize, height: usize, layer_height: f64) -> Vec<Vec<f64>> {
+    (0..height).map(|i| {
+        (0..width).map(|j| fbm.octave_2d(octave, j as f64 / width as f64, (i as f64 * layer_height) / height as f64)).collect()
+    }).collect()
 }
 
 
 /// 
 /// Generates "terrain", an alias for 3 layers of perlin noise.
-/// In short, it layers the `gen_heightmap` function a couple of times.
+/// In short, it layers the octaves of an `Fbm`, each darkening the terrain left by the last.
 ///
 /// # Parameters:
 /// - `seed`: A number to seed the perlin noise
@@ -56,7 +46,14 @@ pub fn gen_terrain(seed: u32, width: usize, height: usize, layer_height: f64, bs
         }
     }
     
-    let base_vals: Vec<Vec<f64>> = gen_heightmap(seed * 2, width, height, layer_height, bs);
+    // the sizes are given between 1 and 255, where larger sizes are lower frequencies
+    let fbm = Fbm::from_octaves(NoiseBasis::Surflet, vec![
+        Octave { seed: seed.wrapping_mul(2), frequency: 255. / bs, amplitude: ba },
+        Octave { seed: seed.wrapping_mul(4), frequency: 255. / ms, amplitude: ma },
+        Octave { seed: seed.wrapping_mul(8), frequency: 255. / hs, amplitude: ha },
+    ]);
+
+    let base_vals: Vec<Vec<f64>> = gen_heightmap(&fbm, 0, width, height, layer_height);
 
     for row in 0..base_vals.len() {
         for pix in 0..base_vals[row].len() {
@@ -64,7 +61,7 @@ pub fn gen_terrain(seed: u32, width: usize, height: usize, layer_height: f64, bs
         }
     }
 
-    let mid_vals: Vec<Vec<f64>> = gen_heightmap(seed * 4, width, height, layer_height, ms);
+    let mid_vals: Vec<Vec<f64>> = gen_heightmap(&fbm, 1, width, height, layer_height);
 
     for row in 0..mid_vals.len() {
         for pix in 0..mid_vals[row].len() {
@@ -80,7 +77,7 @@ pub fn gen_terrain(seed: u32, width: usize, height: usize, layer_height: f64, bs
         }
     }
 
-    let high_vals: Vec<Vec<f64>> = gen_heightmap(seed * 8, width, height, layer_height, hs);
+    let high_vals: Vec<Vec<f64>> = gen_heightmap(&fbm, 2, width, height, layer_height);
 
     for row in 0..high_vals.len() {
         for pix in 0..high_vals[row].len() {
@@ -98,3 +95,167 @@ pub fn gen_terrain(seed: u32, width: usize, height: usize, layer_height: f64, bs
 
     values
 }
+
+
+/// 
+/// Loads a heightmap from a file, resampled to the given size. ESRI ASCII grids (.asc) are read
+/// as elevation models, and any other file is read as a greyscale image.
+///
+/// # Parameters:
+/// - `path`: The path of the heightmap file, or the name of a file in memory
+/// - `width`: The number of horizontal samples
+/// - `height`: The number of vertical samples
+///
+/// # Returns:
+/// - A 2D vector, with u8 values, in the form Row<Column<u8>>
+/// - An error explaining why the file could not be loaded
+///
+pub fn from_file(path: &str, width: usize, height: usize) -> Result<Vec<Vec<u8>>, String> {
+    if path.to_ascii_lowercase().ends_with(".asc") {
+        return from_ascii_grid(path, width, height);
+    }
+
+    #[cfg(feature = "image")]
+    return from_image(path, width, height);
+
+    #[cfg(not(feature = "image"))]
+    return Err("Image heightmaps are not supported in this build. Use an .asc elevation model instead.".to_owned());
+}
+
+
+/// 
+/// Loads a heightmap from a greyscale image, resampled to the given size. Brighter pixels are
+/// higher ground, and the values match `gen_terrain`, so the result can be used in its place.
+///
+/// # Parameters:
+/// - `path`: The path of the image file, or the name of a file in memory
+/// - `width`: The number of horizontal samples
+/// - `height`: The number of vertical samples
+///
+/// # Returns:
+/// - A 2D vector, with u8 values, in the form Row<Column<u8>>
+/// - An error explaining why the image could not be loaded
+///
+#[cfg(feature = "image")]
+pub fn from_image(path: &str, width: usize, height: usize) -> Result<Vec<Vec<u8>>, String> {
+    let bytes = files::read(path).map_err(|err| format!("Error loading heightmap image. {}", err))?;
+    let image = match image::load_from_memory(&bytes) {
+        Ok(img) => img.into_luma8(),
+        Err(err) => return Err(format!("Error loading heightmap image. {}", err)),
+    };
+
+    let elevations: Vec<Vec<f64>> = image.rows().map(|row| row.map(|pixel| pixel.0[0] as f64).collect()).collect();
+    Ok(resample_elevations(&elevations, width, height))
+}
+
+
+/// 
+/// Loads a heightmap from an ESRI ASCII grid digital elevation model, resampled to the given
+/// size. Elevations are scaled so the lowest point is 0 and the highest is 255, and no-data
+/// cells are treated as the lowest point.
+///
+/// # Parameters:
+/// - `path`: The path of the .asc file, or the name of a file in memory
+/// - `width`: The number of horizontal samples
+/// - `height`: The number of vertical samples
+///
+/// # Returns:
+/// - A 2D vector, with u8 values, in the form Row<Column<u8>>
+/// - An error explaining why the grid could not be loaded
+///
+pub fn from_ascii_grid(path: &str, width: usize, height: usize) -> Result<Vec<Vec<u8>>, String> {
+    let contents = match files::read(path).map(String::from_utf8) {
+        Ok(Ok(val)) => val,
+        Ok(Err(err)) => return Err(format!("Error reading elevation grid. {}", err)),
+        Err(err) => return Err(format!("Error reading elevation grid. {}", err)),
+    };
+
+    let mut columns: Option<usize> = None;
+    let mut rows: Option<usize> = None;
+    let mut no_data: Option<f64> = None;
+    let mut values: Vec<f64> = vec![];
+
+    for line in contents.lines() {
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(first) = tokens.peek() else { continue };
+
+        // header lines start with a keyword, data lines start with a number
+        if first.parse::<f64>().is_err() {
+            let key = first.to_ascii_lowercase();
+            let value = tokens.nth(1).ok_or(format!("Elevation grid header `{}` has no value", key))?;
+            match key.as_str() {
+                "ncols" => columns = value.parse().ok(),
+                "nrows" => rows = value.parse().ok(),
+                "nodata_value" => no_data = value.parse().ok(),
+                _ => {},
+            }
+            continue;
+        }
+
+        for token in tokens {
+            match token.parse::<f64>() {
+                Ok(v) => values.push(v),
+                Err(_) => return Err(format!("Elevation grid has an invalid value `{}`", token)),
+            }
+        }
+    }
+
+    let (Some(columns), Some(rows)) = (columns, rows) else {
+        return Err("Elevation grid is missing its ncols or nrows header".to_owned());
+    };
+    if columns == 0 || rows == 0 || values.len() != columns * rows {
+        return Err(format!("Elevation grid should have {} values, but has {}", columns * rows, values.len()));
+    }
+
+    let is_data = |v: &f64| Some(*v) != no_data;
+    let min = values.iter().filter(|v| is_data(v)).copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().filter(|v| is_data(v)).copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1. };
+
+    let elevations: Vec<Vec<f64>> = values.chunks(columns).map(|row| {
+        row.iter().map(|v| if is_data(v) { (v - min) / range * 255. } else { 0. }).collect()
+    }).collect();
+
+    Ok(resample_elevations(&elevations, width, height))
+}
+
+
+/// 
+/// Resamples a grid of elevations between 0 and 255 with bilinear interpolation, and converts
+/// them to `gen_terrain` values, where 255 is the lowest ground.
+///
+/// # Parameters:
+/// - `elevations`: The elevations, in the form Row<Column<f64>>
+/// - `width`: The number of horizontal samples
+/// - `height`: The number of vertical samples
+///
+/// # Returns:
+/// - A 2D vector, with u8 values, in the form Row<Column<u8>>
+///
+fn resample_elevations(elevations: &[Vec<f64>], width: usize, height: usize) -> Vec<Vec<u8>> {
+    let source_height = elevations.len();
+    let source_width = elevations.first().map(|row| row.len()).unwrap_or(0);
+    if source_width == 0 {
+        return vec![vec![u8::MAX; width]; height];
+    }
+
+    let sample = |x: f64, y: f64| {
+        let x = x.clamp(0., (source_width - 1) as f64);
+        let y = y.clamp(0., (source_height - 1) as f64);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(source_width - 1), (y0 + 1).min(source_height - 1));
+        let (tx, ty) = (x - x0 as f64, y - y0 as f64);
+
+        let top = elevations[y0][x0] * (1. - tx) + elevations[y0][x1] * tx;
+        let bottom = elevations[y1][x0] * (1. - tx) + elevations[y1][x1] * tx;
+        top * (1. - ty) + bottom * ty
+    };
+
+    (0..height).map(|row| {
+        let y = if height > 1 { row as f64 / (height - 1) as f64 * (source_height - 1) as f64 } else { 0. };
+        (0..width).map(|column| {
+            let x = if width > 1 { column as f64 / (width - 1) as f64 * (source_width - 1) as f64 } else { 0. };
+            (255. - sample(x, y)).round().clamp(0., 255.) as u8
+        }).collect()
+    }).collect()
+}
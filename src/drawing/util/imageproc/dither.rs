@@ -0,0 +1,204 @@
+use image::GrayImage;
+
+use crate::drawing::util::geometry::Point;
+
+///
+/// The error diffusion kernels, as (x offset, y offset, weight) triples. The weights are divided
+/// by the kernel's divisor, and Atkinson deliberately only diffuses 6/8 of the error.
+///
+const FLOYD_STEINBERG: ([(i32, i32, f32); 4], f32) = ([(1, 0, 7.), (-1, 1, 3.), (0, 1, 5.), (1, 1, 1.)], 16.);
+const ATKINSON: ([(i32, i32, f32); 6], f32) = ([(1, 0, 1.), (2, 0, 1.), (-1, 1, 1.), (0, 1, 1.), (1, 1, 1.), (0, 2, 1.)], 8.);
+
+///
+/// Dithers an image with Floyd-Steinberg error diffusion. Dark pixels become dots.
+///
+/// # Parameters:
+/// - `image`: The greyscale input image
+///
+/// # Returns:
+/// - A 2D vector of dots, true where a dot should be drawn, in the form Row<Column<bool>>
+///
+pub fn floyd_steinberg(image: &GrayImage) -> Vec<Vec<bool>> {
+    diffuse(image, &FLOYD_STEINBERG.0, FLOYD_STEINBERG.1)
+}
+
+///
+/// Dithers an image with Atkinson error diffusion. Only part of the error is diffused, so
+/// highlights and shadows lose detail but midtones have more contrast than Floyd-Steinberg.
+///
+/// # Parameters:
+/// - `image`: The greyscale input image
+///
+/// # Returns:
+/// - A 2D vector of dots, true where a dot should be drawn, in the form Row<Column<bool>>
+///
+pub fn atkinson(image: &GrayImage) -> Vec<Vec<bool>> {
+    diffuse(image, &ATKINSON.0, ATKINSON.1)
+}
+
+///
+/// Dithers an image with an ordered Bayer matrix. This is faster than error diffusion and gives
+/// a regular cross-hatched pattern, rather than a noisy one.
+///
+/// # Parameters:
+/// - `image`: The greyscale input image
+/// - `order`: The size of the Bayer matrix as a power of two, between 1 (2x2) and 4 (16x16)
+///
+/// # Returns:
+/// - A 2D vector of dots, true where a dot should be drawn, in the form Row<Column<bool>>
+///
+pub fn bayer(image: &GrayImage, order: u32) -> Vec<Vec<bool>> {
+    let size = 1_usize << order.clamp(1, 4);
+    let matrix = bayer_matrix(size);
+    let levels = (size * size) as f32;
+
+    (0..image.height()).map(|y| {
+        (0..image.width()).map(|x| {
+            let threshold = (matrix[y as usize % size][x as usize % size] as f32 + 0.5) / levels * 255.;
+            (image.get_pixel(x, y).0[0] as f32) < threshold
+        }).collect()
+    }).collect()
+}
+
+///
+/// Converts a dot map into a list of points, one per dot, at the pixel centres.
+///
+/// # Parameters:
+/// - `dots`: A dot map, from any of the dithering functions
+///
+/// # Returns:
+/// - The positions of the dots, in pixels
+///
+pub fn dot_points(dots: &[Vec<bool>]) -> Vec<Point> {
+    let mut points = vec![];
+    for (y, row) in dots.iter().enumerate() {
+        for (x, &dot) in row.iter().enumerate() {
+            if dot {
+                points.push(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+            }
+        }
+    }
+    points
+}
+
+///
+/// Dithers an image by error diffusion, with a given kernel. Rows are scanned alternately left
+/// to right and right to left, which avoids the diagonal worm artifacts of a plain raster scan.
+///
+/// # Parameters:
+/// - `image`: The greyscale input image
+/// - `kernel`: The (x offset, y offset, weight) of each neighbour which receives error
+/// - `divisor`: The number the weights are divided by
+///
+/// # Returns:
+/// - A 2D vector of dots, true where a dot should be drawn, in the form Row<Column<bool>>
+///
+fn diffuse(image: &GrayImage, kernel: &[(i32, i32, f32)], divisor: f32) -> Vec<Vec<bool>> {
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    let mut values: Vec<Vec<f32>> = (0..height).map(|y| (0..width).map(|x| image.get_pixel(x as u32, y as u32).0[0] as f32).collect()).collect();
+    let mut dots = vec![vec![false; width as usize]; height as usize];
+
+    for y in 0..height {
+        let reverse = y % 2 == 1;
+        let direction = if reverse { -1 } else { 1 };
+
+        for step in 0..width {
+            let x = if reverse { width - 1 - step } else { step };
+
+            let old_value = values[y as usize][x as usize];
+            let is_dot = old_value < 128.;
+            dots[y as usize][x as usize] = is_dot;
+
+            let error = old_value - if is_dot { 0. } else { 255. };
+            for &(dx, dy, weight) in kernel {
+                let (nx, ny) = (x + dx * direction, y + dy);
+                if nx >= 0 && nx < width && ny < height {
+                    values[ny as usize][nx as usize] += error * weight / divisor;
+                }
+            }
+        }
+    }
+
+    dots
+}
+
+///
+/// Builds a Bayer threshold matrix recursively.
+///
+/// # Parameters:
+/// - `size`: The width and height of the matrix, a power of two
+///
+/// # Returns:
+/// - The matrix, holding each value from 0 to size² - 1 once
+///
+fn bayer_matrix(size: usize) -> Vec<Vec<u32>> {
+    if size <= 1 {
+        return vec![vec![0]];
+    }
+
+    let half = size / 2;
+    let smaller = bayer_matrix(half);
+    let mut matrix = vec![vec![0; size]; size];
+
+    for y in 0..size {
+        for x in 0..size {
+            let quadrant = match (x < half, y < half) {
+                (true, true) => 0,
+                (false, false) => 1,
+                (false, true) => 2,
+                (true, false) => 3,
+            };
+            matrix[y][x] = 4 * smaller[y % half][x % half] + quadrant;
+        }
+    }
+
+    matrix
+}
+
+
+///
+/// Tests relating to dithering.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    fn dot_fraction(dots: &[Vec<bool>]) -> f64 {
+        dots.iter().flatten().filter(|&&dot| dot).count() as f64 / dots.iter().map(|row| row.len()).sum::<usize>() as f64
+    }
+
+    #[test]
+    fn dot_density_follows_the_grey_level() {
+        for level in [0_u8, 64, 128, 192, 255] {
+            let image = GrayImage::from_pixel(32, 32, Luma([level]));
+            let expected = 1. - level as f64 / 255.;
+
+            for (name, dots) in [("floyd-steinberg", floyd_steinberg(&image)), ("bayer", bayer(&image, 3))] {
+                assert_eq!((dots.len(), dots[0].len()), (32, 32));
+                assert!((dot_fraction(&dots) - expected).abs() < 0.05, "{} at {}: {}", name, level, dot_fraction(&dots));
+            }
+        }
+
+        // atkinson drops a quarter of the error, so only black and white are exact
+        assert_eq!(dot_fraction(&atkinson(&GrayImage::from_pixel(16, 16, Luma([0])))), 1.);
+        assert_eq!(dot_fraction(&atkinson(&GrayImage::from_pixel(16, 16, Luma([255])))), 0.);
+        let half = dot_fraction(&atkinson(&GrayImage::from_pixel(32, 32, Luma([128]))));
+        assert!(half > 0.3 && half < 0.7, "{}", half);
+    }
+
+    #[test]
+    fn bayer_matrices_hold_each_threshold_once() {
+        for size in [2, 4, 8, 16] {
+            let mut values: Vec<u32> = bayer_matrix(size).into_iter().flatten().collect();
+            values.sort();
+            assert_eq!(values, (0..(size * size) as u32).collect::<Vec<u32>>());
+        }
+    }
+
+    #[test]
+    fn dots_are_at_pixel_centres() {
+        let dots = vec![vec![true, false], vec![false, true]];
+        assert_eq!(dot_points(&dots), vec![Point::new(0.5, 0.5), Point::new(1.5, 1.5)]);
+    }
+}
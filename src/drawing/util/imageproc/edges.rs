@@ -0,0 +1,214 @@
+use image::GrayImage;
+
+///
+/// The offsets of the 8 neighbours of a pixel, clockwise from the right.
+///
+const NEIGHBOURS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+
+///
+/// Computes the image gradient with the Sobel operator.
+///
+/// # Parameters:
+/// - `image`: The greyscale input image
+///
+/// # Returns:
+/// - The gradient magnitude of each pixel, in the form Row<Column<f32>>
+/// - The gradient direction of each pixel in radians, in the form Row<Column<f32>>
+///
+pub fn sobel(image: &GrayImage) -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    let pixel = |x: i32, y: i32| image.get_pixel(x.clamp(0, width - 1) as u32, y.clamp(0, height - 1) as u32).0[0] as f32;
+
+    let mut magnitudes = vec![vec![0.; width as usize]; height as usize];
+    let mut directions = vec![vec![0.; width as usize]; height as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let gx = (pixel(x + 1, y - 1) + 2. * pixel(x + 1, y) + pixel(x + 1, y + 1)) - (pixel(x - 1, y - 1) + 2. * pixel(x - 1, y) + pixel(x - 1, y + 1));
+            let gy = (pixel(x - 1, y + 1) + 2. * pixel(x, y + 1) + pixel(x + 1, y + 1)) - (pixel(x - 1, y - 1) + 2. * pixel(x, y - 1) + pixel(x + 1, y - 1));
+
+            magnitudes[y as usize][x as usize] = (gx * gx + gy * gy).sqrt();
+            directions[y as usize][x as usize] = gy.atan2(gx);
+        }
+    }
+
+    (magnitudes, directions)
+}
+
+///
+/// Detects edges with the Canny algorithm. The image is blurred, edges are thinned to one pixel
+/// along the gradient, and then hysteresis keeps weak edges only where they touch strong ones.
+///
+/// # Parameters:
+/// - `image`: The greyscale input image
+/// - `sigma`: The standard deviation of the gaussian blur, in pixels. Larger values ignore finer detail
+/// - `low_threshold`: The gradient magnitude an edge pixel must reach, to continue an edge
+/// - `high_threshold`: The gradient magnitude an edge pixel must reach, to start an edge
+///
+/// # Returns:
+/// - A 2D vector, true where a pixel is on an edge, in the form Row<Column<bool>>
+///
+pub fn canny(image: &GrayImage, sigma: f32, low_threshold: f32, high_threshold: f32) -> Vec<Vec<bool>> {
+    let blurred = if sigma > 0. { ::imageproc::filter::gaussian_blur_f32(image, sigma) } else { image.clone() };
+    let (magnitudes, directions) = sobel(&blurred);
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    let magnitude = |x: i32, y: i32| if x < 0 || y < 0 || x >= width || y >= height { 0. } else { magnitudes[y as usize][x as usize] };
+
+    // non-maximum suppression, keeping pixels which are the peak across the edge
+    let mut thinned = vec![vec![0_f32; width as usize]; height as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let value = magnitudes[y as usize][x as usize];
+            if value < low_threshold {
+                continue;
+            }
+
+            let sector = ((directions[y as usize][x as usize].to_degrees() + 202.5) / 45.) as usize % 4;
+            let (dx, dy) = NEIGHBOURS[sector];
+            // ties are broken to one side, so a symmetric step edge stays one pixel wide
+            if value > magnitude(x + dx, y + dy) && value >= magnitude(x - dx, y - dy) {
+                thinned[y as usize][x as usize] = value;
+            }
+        }
+    }
+
+    // hysteresis, flood filling from each strong pixel through the weak ones
+    let mut edges = vec![vec![false; width as usize]; height as usize];
+    let mut stack: Vec<(i32, i32)> = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            if thinned[y as usize][x as usize] < high_threshold || edges[y as usize][x as usize] {
+                continue;
+            }
+
+            edges[y as usize][x as usize] = true;
+            stack.push((x, y));
+            while let Some((cx, cy)) = stack.pop() {
+                for (dx, dy) in NEIGHBOURS {
+                    let (nx, ny) = (cx + dx, cy + dy);
+                    if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                        continue;
+                    }
+                    if !edges[ny as usize][nx as usize] && thinned[ny as usize][nx as usize] >= low_threshold {
+                        edges[ny as usize][nx as usize] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+        }
+    }
+
+    edges
+}
+
+///
+/// Chains edge pixels into ordered polylines, which a pen can follow. Each chain is walked from
+/// an end pixel where possible, so edges aren't split in the middle.
+///
+/// # Parameters:
+/// - `edges`: A 2D vector of edge pixels, such as from `canny`, in the form Row<Column<bool>>
+/// - `min_length`: The fewest pixels a polyline can have, shorter chains are dropped as noise
+///
+/// # Returns:
+/// - A list of polylines, each a list of pixel centres
+///
+pub fn link_edges(edges: &[Vec<bool>], min_length: usize) -> Vec<Vec<(f64, f64)>> {
+    let height = edges.len() as i32;
+    let width = edges.first().map(|row| row.len()).unwrap_or(0) as i32;
+    let is_edge = |x: i32, y: i32| x >= 0 && y >= 0 && x < width && y < height && edges[y as usize][x as usize];
+    let neighbour_count = |x: i32, y: i32| NEIGHBOURS.iter().filter(|(dx, dy)| is_edge(x + dx, y + dy)).count();
+
+    let mut visited = vec![vec![false; width as usize]; height as usize];
+    let mut polylines = vec![];
+
+    // end pixels are walked first, then whatever is left is closed loops
+    for ends_only in [true, false] {
+        for y in 0..height {
+            for x in 0..width {
+                if !is_edge(x, y) || visited[y as usize][x as usize] || (ends_only && neighbour_count(x, y) != 1) {
+                    continue;
+                }
+
+                let mut polyline = vec![];
+                let (mut cx, mut cy) = (x, y);
+                loop {
+                    visited[cy as usize][cx as usize] = true;
+                    polyline.push((cx as f64 + 0.5, cy as f64 + 0.5));
+
+                    let next = NEIGHBOURS.iter()
+                        .map(|(dx, dy)| (cx + dx, cy + dy))
+                        .find(|&(nx, ny)| is_edge(nx, ny) && !visited[ny as usize][nx as usize]);
+
+                    match next {
+                        Some((nx, ny)) => (cx, cy) = (nx, ny),
+                        None => break,
+                    }
+                }
+
+                if polyline.len() >= min_length.max(2) {
+                    polylines.push(polyline);
+                }
+            }
+        }
+    }
+
+    polylines
+}
+
+
+///
+/// Tests relating to edge detection.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    /// A 40x30 image, black left of x = 20 and white from it.
+    fn step_image() -> GrayImage {
+        GrayImage::from_fn(40, 30, |x, _| if x < 20 { Luma([0]) } else { Luma([255]) })
+    }
+
+    #[test]
+    fn sobel_finds_the_step() {
+        let (magnitudes, directions) = sobel(&step_image());
+
+        for y in 0..30 {
+            for x in 0..40 {
+                let expected = if x == 19 || x == 20 { 4. * 255. } else { 0. };
+                assert_eq!(magnitudes[y][x], expected, "({}, {})", x, y);
+            }
+            // the gradient points from dark to light, along x
+            assert_eq!(directions[y][19], 0.);
+        }
+    }
+
+    #[test]
+    fn canny_gives_one_line_along_the_step() {
+        let edges = canny(&step_image(), 1., 50., 150.);
+
+        for (y, row) in edges.iter().enumerate() {
+            let columns: Vec<usize> = (0..40).filter(|&x| row[x]).collect();
+            assert_eq!(columns.len(), 1, "row {}: {:?}", y, columns);
+            assert!(columns[0] == 19 || columns[0] == 20, "row {}: {:?}", y, columns);
+        }
+
+        // a flat image has no edges
+        let flat = canny(&GrayImage::from_pixel(40, 30, Luma([128])), 1., 50., 150.);
+        assert!(flat.iter().flatten().all(|&edge| !edge));
+    }
+
+    #[test]
+    fn linking_the_step_gives_one_polyline() {
+        let polylines = link_edges(&canny(&step_image(), 1., 50., 150.), 5);
+        assert_eq!(polylines.len(), 1);
+        assert_eq!(polylines[0].len(), 30);
+
+        // it is walked from one end to the other, without turning back
+        let ys: Vec<f64> = polylines[0].iter().map(|p| p.1).collect();
+        assert!(ys.windows(2).all(|pair| (pair[1] - pair[0]).abs() == 1.), "{:?}", ys);
+
+        // chains shorter than the minimum are dropped
+        assert!(link_edges(&canny(&step_image(), 1., 50., 150.), 31).is_empty());
+    }
+}
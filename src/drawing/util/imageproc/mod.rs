@@ -0,0 +1,6 @@
+//!
+//! Image processing helpers, which turn input images into structures the drawing methods can plot
+//!
+
+pub mod dither;
+pub mod edges;
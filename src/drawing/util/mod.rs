@@ -2,9 +2,25 @@
 //! Helpers and functions for any generic drawing functions
 //!
 
+#[cfg(feature = "image")]
 pub mod stipple;
 pub mod stipple_structures;
+pub mod delaunay;
+pub mod spatial;
+pub mod tour;
 
 pub mod heightmap;
+pub mod noise;
+#[cfg(feature = "audio")]
 pub mod audio;
 pub mod geometry;
+pub mod fonts;
+pub mod files;
+pub mod dijkstra;
+pub mod simplify;
+pub mod resample;
+pub mod tone;
+pub mod placement;
+pub mod turtle;
+#[cfg(feature = "image")]
+pub mod imageproc;
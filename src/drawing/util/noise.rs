@@ -0,0 +1,158 @@
+use ::noise::{NoiseFn, Perlin, PerlinSurflet};
+
+///
+/// The gradient noise each octave of an `Fbm` is built from.
+///
+/// - `Perlin`: Classic perlin noise
+/// - `Surflet`: Perlin surflet noise, which is smoother and has no grid artifacts
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseBasis {
+    Perlin,
+    Surflet,
+}
+
+///
+/// A single layer of noise within an `Fbm`.
+///
+/// # Fields:
+/// - `seed`: The seed of this octave's noise
+/// - `frequency`: The number of noise features per unit of input
+/// - `amplitude`: The weight of this octave when octaves are summed
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Octave {
+    pub seed: u32,
+    pub frequency: f64,
+    pub amplitude: f64,
+}
+
+///
+/// Fractional brownian motion, layering several octaves of gradient noise at increasing
+/// frequencies. This replaces the hand-written three layer perlin blends in the drawing methods.
+///
+/// # Fields:
+/// - `octaves`: The octaves, from the coarsest to the finest
+/// - `offset`: An offset added to every sample in noise space, to take an unrelated slice of
+///   the same noise
+/// - `noises`: The noise function of each octave
+///
+pub struct Fbm {
+    octaves: Vec<Octave>,
+    offset: f64,
+
+    noises: Vec<Box<dyn NoiseFn<f64, 2> + Send + Sync>>,
+}
+
+impl Fbm {
+    ///
+    /// Creates conventional fbm noise, where each octave is `lacunarity` times the frequency
+    /// and `gain` times the amplitude of the previous.
+    ///
+    /// # Parameters:
+    /// - `seed`: The seed of the first octave, each following octave's seed is one higher
+    /// - `octaves`: The number of octaves
+    /// - `lacunarity`: The frequency multiplier between octaves, usually 2
+    /// - `gain`: The amplitude multiplier between octaves, usually 0.5
+    ///
+    /// # Returns:
+    /// - A new `Fbm` with a base frequency and amplitude of 1, using perlin noise
+    ///
+    pub fn new(seed: u32, octaves: usize, lacunarity: f64, gain: f64) -> Fbm {
+        let octaves = (0..octaves).map(|i| Octave {
+            seed: seed.wrapping_add(i as u32),
+            frequency: lacunarity.powi(i as i32),
+            amplitude: gain.powi(i as i32),
+        }).collect();
+
+        Fbm::from_octaves(NoiseBasis::Perlin, octaves)
+    }
+
+    ///
+    /// Creates fbm noise from explicit octaves, for drawing methods which let the user size and
+    /// weight each layer of noise.
+    ///
+    /// # Parameters:
+    /// - `basis`: The noise each octave is built from
+    /// - `octaves`: The octaves, from the coarsest to the finest
+    ///
+    /// # Returns:
+    /// - A new `Fbm`
+    ///
+    pub fn from_octaves(basis: NoiseBasis, octaves: Vec<Octave>) -> Fbm {
+        let noises = octaves.iter().map(|octave| -> Box<dyn NoiseFn<f64, 2> + Send + Sync> {
+            match basis {
+                NoiseBasis::Perlin => Box::new(Perlin::new(octave.seed)),
+                NoiseBasis::Surflet => Box::new(PerlinSurflet::new(octave.seed)),
+            }
+        }).collect();
+
+        Fbm { octaves, offset: 0., noises }
+    }
+
+    ///
+    /// Sets the noise space offset, so the same octaves can give a second, unrelated value. For
+    /// example, x and y displacements can be taken from one `Fbm` and an offset copy of it.
+    ///
+    /// # Parameters:
+    /// - `offset`: The offset added to every coordinate, after scaling by frequency
+    ///
+    /// # Returns:
+    /// - The `Fbm` with the offset set
+    ///
+    pub fn with_offset(mut self, offset: f64) -> Fbm {
+        self.offset = offset;
+        self
+    }
+
+    ///
+    /// # Returns:
+    /// - The octaves of the noise
+    ///
+    pub fn octaves(&self) -> &[Octave] {
+        &self.octaves
+    }
+
+    ///
+    /// Samples a single octave, without its amplitude. This is for methods which blend octaves
+    /// with something other than a sum.
+    ///
+    /// # Parameters:
+    /// - `octave`: The index of the octave
+    /// - `x` and `y`: The position to sample
+    ///
+    /// # Returns:
+    /// - The octave's noise value, roughly between -1 and 1
+    ///
+    pub fn octave_2d(&self, octave: usize, x: f64, y: f64) -> f64 {
+        let frequency = self.octaves[octave].frequency;
+        self.noises[octave].get([x * frequency + self.offset, y * frequency + self.offset])
+    }
+
+    ///
+    /// Samples the noise in two dimensions, summing every octave weighted by its amplitude.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The position to sample
+    ///
+    /// # Returns:
+    /// - The noise value, between roughly -1 and 1 times the sum of the amplitudes
+    ///
+    pub fn sample_2d(&self, x: f64, y: f64) -> f64 {
+        (0..self.octaves.len()).map(|i| self.octave_2d(i, x, y) * self.octaves[i].amplitude).sum()
+    }
+
+    ///
+    /// Samples the noise in one dimension, along a line through the two dimensional noise.
+    ///
+    /// # Parameters:
+    /// - `x`: The position to sample
+    ///
+    /// # Returns:
+    /// - The noise value, between roughly -1 and 1 times the sum of the amplitudes
+    ///
+    pub fn sample_1d(&self, x: f64) -> f64 {
+        // y is kept off the lattice, where gradient noise is always zero
+        self.sample_2d(x, 0.5)
+    }
+}
@@ -0,0 +1,256 @@
+use serde::{Serialize, Deserialize};
+
+use crate::hardware::PhysicalDimensions;
+
+///
+/// How a drawing is scaled into the area inside the margins.
+///
+/// - `Contain`: Scaled as large as possible while staying inside the area, keeping its aspect ratio
+/// - `Cover`: Scaled as small as possible while covering the whole area, keeping its aspect ratio.
+///   The parts outside the area are cropped
+/// - `Stretch`: Scaled to fill the area exactly, ignoring its aspect ratio
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Fit {
+    #[default]
+    Contain,
+    Cover,
+    Stretch,
+}
+
+///
+/// Where a drawing sits along one axis of the area, when it doesn't fill it.
+///
+/// - `Start`: Against the left or top margin
+/// - `Centre`: Centred between the margins
+/// - `End`: Against the right or bottom margin
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Align {
+    Start,
+    #[default]
+    Centre,
+    End,
+}
+
+///
+/// The space kept clear around each edge of the page.
+///
+/// # Fields:
+/// - `top`: The top margin, in millimetres
+/// - `right`: The right margin, in millimetres
+/// - `bottom`: The bottom margin, in millimetres
+/// - `left`: The left margin, in millimetres
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct Margins {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Margins {
+    ///
+    /// # Parameters:
+    /// - `horizontal`: The left and right margins, in millimetres
+    /// - `vertical`: The top and bottom margins, in millimetres
+    ///
+    /// # Returns:
+    /// - Margins which are the same on opposite edges
+    ///
+    pub fn symmetric(horizontal: f64, vertical: f64) -> Margins {
+        Margins { top: vertical, right: horizontal, bottom: vertical, left: horizontal }
+    }
+}
+
+///
+/// A rectangle on the page.
+///
+/// # Fields:
+/// - `x`: The left edge, in millimetres from the left of the page
+/// - `y`: The top edge, in millimetres from the top of the page
+/// - `width`: The width, in millimetres
+/// - `height`: The height, in millimetres
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    ///
+    /// # Returns:
+    /// - Whether the point is inside the rectangle, including its edges
+    ///
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && y >= self.y && x <= self.x + self.width && y <= self.y + self.height
+    }
+
+    ///
+    /// # Returns:
+    /// - The part of this rectangle inside the other, which is empty if they don't overlap
+    ///
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let width = ((self.x + self.width).min(other.x + other.width) - x).max(0.);
+        let height = ((self.y + self.height).min(other.y + other.height) - y).max(0.);
+        Rect { x, y, width, height }
+    }
+
+    ///
+    /// Clips a line segment to the rectangle, with the Liang-Barsky algorithm.
+    ///
+    /// # Parameters:
+    /// - `start`: The start of the segment
+    /// - `end`: The end of the segment
+    ///
+    /// # Returns:
+    /// - The part of the segment inside the rectangle, in the same direction, or None if it is all outside
+    ///
+    pub fn clip_segment(&self, start: (f64, f64), end: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let (mut t_start, mut t_end) = (0., 1.);
+
+        // for each edge, p is how fast the segment heads outside it and q is how far inside it the start is
+        for (p, q) in [(-dx, start.0 - self.x), (dx, self.x + self.width - start.0), (-dy, start.1 - self.y), (dy, self.y + self.height - start.1)] {
+            if p == 0. {
+                if q < 0. {
+                    return None;
+                }
+                continue;
+            }
+
+            let t = q / p;
+            if p < 0. {
+                t_start = t.max(t_start);
+            } else {
+                t_end = t.min(t_end);
+            }
+        }
+
+        if t_start > t_end {
+            return None;
+        }
+        Some(((start.0 + dx * t_start, start.1 + dy * t_start), (start.0 + dx * t_end, start.1 + dy * t_end)))
+    }
+
+    ///
+    /// Clips a polyline to the rectangle. A polyline leaving and re-entering the rectangle is
+    /// split into several.
+    ///
+    /// # Parameters:
+    /// - `points`: The points of the polyline
+    ///
+    /// # Returns:
+    /// - The parts of the polyline inside the rectangle, in order
+    ///
+    pub fn clip_polyline(&self, points: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+        let mut polylines: Vec<Vec<(f64, f64)>> = vec![];
+        let mut joined = false;
+
+        for segment in points.windows(2) {
+            let Some((start, end)) = self.clip_segment(segment[0], segment[1]) else {
+                joined = false;
+                continue;
+            };
+
+            match polylines.last_mut() {
+                Some(polyline) if joined && polyline.last() == Some(&start) => polyline.push(end),
+                _ => polylines.push(vec![start, end]),
+            }
+            // the next segment only continues this polyline if this one wasn't cut short
+            joined = end == segment[1];
+        }
+
+        polylines
+    }
+}
+
+///
+/// Where a drawing is placed on the page, shared by the drawing methods so each places its
+/// drawing the same way.
+///
+/// # Fields:
+/// - `fit`: How the drawing is scaled into the area inside the margins
+/// - `h_align`: Where the drawing sits horizontally, if it is narrower than the area
+/// - `v_align`: Where the drawing sits vertically, if it is shorter than the area
+/// - `margins`: The space kept clear around each edge of the page
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct Placement {
+    pub fit: Fit,
+    pub h_align: Align,
+    pub v_align: Align,
+    pub margins: Margins,
+}
+
+impl Placement {
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions, for the size of the page
+    ///
+    /// # Returns:
+    /// - The area of the page inside the margins, which drawings without a shape of their own fill
+    /// - An error if the margins are negative or leave no space to draw in
+    ///
+    pub fn area(&self, physical_dimensions: &PhysicalDimensions) -> Result<Rect, String> {
+        let Margins { top, right, bottom, left } = self.margins;
+        if [top, right, bottom, left].iter().any(|margin| !margin.is_finite() || *margin < 0.) {
+            return Err("Margins can not be negative".to_owned());
+        }
+
+        let width = physical_dimensions.page_width() - left - right;
+        let height = physical_dimensions.page_height() - top - bottom;
+        if width <= 0. || height <= 0. {
+            return Err(format!("The margins leave no space to draw in, the area inside them is {:.1}mm by {:.1}mm", width, height));
+        }
+
+        Ok(Rect { x: left, y: top, width, height })
+    }
+
+    ///
+    /// Fits a drawing into the area inside the margins.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions, for the size of the page
+    /// - `content_width`: The width of the drawing in its own units, such as image pixels
+    /// - `content_height`: The height of the drawing in its own units
+    ///
+    /// # Returns:
+    /// - Where the drawing is placed on the page. With `Fit::Cover`, this extends outside the area
+    /// - An error if the margins leave no space to draw in, or the drawing has no size
+    ///
+    pub fn place(&self, physical_dimensions: &PhysicalDimensions, content_width: f64, content_height: f64) -> Result<Rect, String> {
+        let area = self.area(physical_dimensions)?;
+        if !(content_width > 0. && content_height > 0.) {
+            return Err("The drawing has no size to place on the page".to_owned());
+        }
+
+        let (width, height) = match self.fit {
+            Fit::Stretch => (area.width, area.height),
+            Fit::Contain | Fit::Cover => {
+                let (scale_x, scale_y) = (area.width / content_width, area.height / content_height);
+                let scale = if self.fit == Fit::Contain { scale_x.min(scale_y) } else { scale_x.max(scale_y) };
+                (content_width * scale, content_height * scale)
+            },
+        };
+
+        let align = |align: Align, start: f64, space: f64| match align {
+            Align::Start => start,
+            Align::Centre => start + space / 2.,
+            Align::End => start + space,
+        };
+        Ok(Rect {
+            x: align(self.h_align, area.x, area.width - width),
+            y: align(self.v_align, area.y, area.height - height),
+            width,
+            height,
+        })
+    }
+}
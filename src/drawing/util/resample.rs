@@ -0,0 +1,86 @@
+///
+/// Resamples a polyline so its points are evenly spaced along its length. Curves sampled at even
+/// steps of a parameter, such as the angle of a spiral, bunch their points where the curve moves
+/// slowly, which the machine draws as many tiny instructions over the same spot, over-inking it.
+///
+/// # Parameters:
+/// - `points`: The points of the polyline, in millimetres
+/// - `spacing_mm`: The distance along the polyline between the new points, in millimetres
+///
+/// # Returns:
+/// - The resampled polyline, which always keeps the first and last points. The last gap may be
+///   shorter than the spacing. A polyline with fewer than 2 points, or a spacing which isn't
+///   above 0, is returned unchanged
+///
+pub fn by_arc_length(points: &[(f64, f64)], spacing_mm: f64) -> Vec<(f64, f64)> {
+    if points.len() < 2 || !(spacing_mm.is_finite() && spacing_mm > 0.) {
+        return points.to_vec();
+    }
+
+    let length: f64 = points.windows(2).map(|pair| distance(pair[0], pair[1])).sum();
+    let mut resampled = Vec::with_capacity((length / spacing_mm).ceil() as usize + 2);
+    resampled.push(points[0]);
+
+    // the distance along the polyline of the next point, and of the start of the current segment
+    let mut next_mm = spacing_mm;
+    let mut travelled_mm = 0.;
+    for pair in points.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        let segment_mm = distance(from, to);
+
+        while next_mm < travelled_mm + segment_mm {
+            let t = (next_mm - travelled_mm) / segment_mm;
+            resampled.push((from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t));
+            next_mm += spacing_mm;
+        }
+        travelled_mm += segment_mm;
+    }
+
+    // the last point is kept exactly, rather than as the sum of every segment's length
+    let last = points[points.len() - 1];
+    match resampled.last() {
+        Some(&point) if distance(point, last) < spacing_mm * 1e-6 => *resampled.last_mut().unwrap() = last,
+        _ => resampled.push(last),
+    }
+    resampled
+}
+
+///
+/// # Returns:
+/// - The distance between two points
+///
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evenly_spaced() {
+        // an L of length 10, bunched at the start
+        let points = [(0., 0.), (0.1, 0.), (0.2, 0.), (6., 0.), (6., 4.)];
+        let resampled = by_arc_length(&points, 2.);
+
+        assert_eq!(resampled.len(), 6);
+        assert_eq!(resampled[0], (0., 0.));
+        assert_eq!(*resampled.last().unwrap(), (6., 4.));
+        for pair in resampled.windows(2) {
+            assert!((distance(pair[0], pair[1]) - 2.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn keeps_short_remainder() {
+        let resampled = by_arc_length(&[(0., 0.), (5., 0.)], 2.);
+        assert_eq!(resampled, vec![(0., 0.), (2., 0.), (4., 0.), (5., 0.)]);
+    }
+
+    #[test]
+    fn degenerate_input_unchanged() {
+        assert_eq!(by_arc_length(&[(1., 1.)], 2.), vec![(1., 1.)]);
+        assert_eq!(by_arc_length(&[(0., 0.), (1., 0.)], 0.), vec![(0., 0.), (1., 0.)]);
+    }
+}
@@ -0,0 +1,190 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use ordered_float::OrderedFloat;
+
+///
+/// Simplifies a polyline with the Ramer-Douglas-Peucker algorithm. Points are dropped while the
+/// simplified line stays within the tolerance of every original point, so the result never
+/// strays further than the tolerance from the original.
+///
+/// # Parameters:
+/// - `points`: The points of the polyline, in millimetres
+/// - `tolerance_mm`: The furthest the simplified line may be from any original point, in millimetres
+///
+/// # Returns:
+/// - The simplified polyline, which always keeps the first and last points
+///
+pub fn rdp(points: &[(f64, f64)], tolerance_mm: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    // an explicit stack of (start, end) ranges, so long polylines can't overflow the call stack
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let mut furthest = start;
+        let mut furthest_distance = 0.;
+
+        for index in start + 1..end {
+            let distance = segment_distance(points[index], points[start], points[end]);
+            if distance > furthest_distance {
+                furthest = index;
+                furthest_distance = distance;
+            }
+        }
+
+        if furthest_distance > tolerance_mm {
+            keep[furthest] = true;
+            ranges.push((start, furthest));
+            ranges.push((furthest, end));
+        }
+    }
+
+    points.iter().zip(keep).filter_map(|(point, kept)| kept.then_some(*point)).collect()
+}
+
+///
+/// Simplifies a polyline with the Visvalingam-Whyatt algorithm. The point forming the smallest
+/// triangle with its neighbours is repeatedly removed, which tends to keep the overall shape of
+/// a line better than `rdp` at heavy simplification.
+///
+/// # Parameters:
+/// - `points`: The points of the polyline, in millimetres
+/// - `tolerance_mm`: Points are removed while their triangle is smaller than a square of this
+///   side length, so the tolerance is comparable to `rdp`'s
+///
+/// # Returns:
+/// - The simplified polyline, which always keeps the first and last points
+///
+pub fn visvalingam(points: &[(f64, f64)], tolerance_mm: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let min_area = tolerance_mm * tolerance_mm;
+    let n = points.len();
+
+    // a doubly linked list over the remaining points
+    let mut previous: Vec<usize> = (0..n).map(|i| i.wrapping_sub(1)).collect();
+    let mut next: Vec<usize> = (1..=n).collect();
+    let mut areas: Vec<f64> = vec![f64::INFINITY; n];
+    let mut removed = vec![false; n];
+
+    let mut queue = BinaryHeap::new();
+    for index in 1..n - 1 {
+        areas[index] = triangle_area(points[index - 1], points[index], points[index + 1]);
+        queue.push(Reverse((OrderedFloat(areas[index]), index)));
+    }
+
+    while let Some(Reverse((OrderedFloat(area), index))) = queue.pop() {
+        // entries are stale if the point is gone, or its area changed since it was queued
+        if removed[index] || area != areas[index] {
+            continue;
+        }
+        if area >= min_area {
+            break;
+        }
+
+        removed[index] = true;
+        let (before, after) = (previous[index], next[index]);
+        next[before] = after;
+        previous[after] = before;
+
+        // the neighbours now form new triangles, which can't be smaller than the removed one
+        for neighbour in [before, after] {
+            if neighbour == 0 || neighbour == n - 1 {
+                continue;
+            }
+
+            let new_area = triangle_area(points[previous[neighbour]], points[neighbour], points[next[neighbour]]).max(area);
+            areas[neighbour] = new_area;
+            queue.push(Reverse((OrderedFloat(new_area), neighbour)));
+        }
+    }
+
+    points.iter().zip(removed).filter_map(|(point, removed)| (!removed).then_some(*point)).collect()
+}
+
+///
+/// # Returns:
+/// - The shortest distance from a point to the line segment between `start` and `end`
+///
+fn segment_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_sq = dx * dx + dy * dy;
+
+    let t = if length_sq == 0. { 0. } else { (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_sq).clamp(0., 1.) };
+    ((point.0 - start.0 - t * dx).powi(2) + (point.1 - start.1 - t * dy).powi(2)).sqrt()
+}
+
+///
+/// # Returns:
+/// - The area of the triangle formed by three points
+///
+fn triangle_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.
+}
+
+///
+/// Tests relating to polyline simplification.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wavy_line() -> Vec<(f64, f64)> {
+        (0..=200).map(|i| (i as f64 / 2., (i as f64 / 10.).sin() * 5. + (i as f64 * 1.7).sin() * 0.05)).collect()
+    }
+
+    ///
+    /// # Returns:
+    /// - The furthest any original point is from the simplified polyline
+    ///
+    fn deviation(original: &[(f64, f64)], simplified: &[(f64, f64)]) -> f64 {
+        original.iter().map(|&point| {
+            simplified.windows(2).map(|pair| segment_distance(point, pair[0], pair[1])).fold(f64::INFINITY, f64::min)
+        }).fold(0., f64::max)
+    }
+
+    #[test]
+    fn simplified_lines_stay_within_tolerance() {
+        let line = wavy_line();
+        for simplify in [rdp, visvalingam] {
+            let coarse = simplify(&line, 0.5);
+            let fine = simplify(&line, 0.1);
+            assert!(coarse.len() < fine.len() && fine.len() < line.len(), "{} {} {}", coarse.len(), fine.len(), line.len());
+
+            // every kept point is an original point, in order, and the ends are always kept
+            assert!(coarse.iter().all(|point| line.contains(point)));
+            assert_eq!((coarse[0], coarse[coarse.len() - 1]), (line[0], line[line.len() - 1]));
+        }
+
+        // rdp's tolerance bounds how far the line moves
+        assert!(deviation(&line, &rdp(&line, 0.5)) <= 0.5);
+        assert!(deviation(&line, &rdp(&line, 0.1)) <= 0.1);
+    }
+
+    #[test]
+    fn straight_lines_keep_only_their_ends() {
+        let line: Vec<(f64, f64)> = (0..10).map(|i| (i as f64, i as f64 * 2.)).collect();
+        assert_eq!(rdp(&line, 0.01), [(0., 0.), (9., 18.)]);
+        assert_eq!(visvalingam(&line, 0.01), [(0., 0.), (9., 18.)]);
+    }
+
+    #[test]
+    fn short_lines_are_unchanged() {
+        for line in [vec![], vec![(1., 1.)], vec![(1., 1.), (1., 1.)], vec![(0., 0.), (5., 5.)]] {
+            assert_eq!(rdp(&line, 1.), line);
+            assert_eq!(visvalingam(&line, 1.), line);
+        }
+
+        // a closed loop's ends are the same point, which doesn't collapse the loop
+        let square = [(0., 0.), (10., 0.), (10., 10.), (0., 10.), (0., 0.)];
+        assert_eq!(rdp(&square, 0.1), square);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::drawing::util::geometry::Point;
+
+///
+/// A uniform grid over a set of points, answering nearest-neighbour queries without scanning
+/// every point. Points can be removed once used, such as when building a tour.
+///
+/// # Fields:
+/// - `min_x` and `min_y`: The corner of the grid, at the smallest x/y of any point
+/// - `cell_size`: The width and height of each grid cell
+/// - `columns` and `rows`: The number of cells across and down the grid
+/// - `cells`: The indices of the remaining points in each cell, row by row
+/// - `remaining`: The number of points not yet removed
+///
+pub struct PointGrid<'p> {
+    points: &'p [Point],
+
+    min_x: f32,
+    min_y: f32,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+
+    cells: Vec<Vec<usize>>,
+    remaining: usize,
+}
+
+impl<'p> PointGrid<'p> {
+    ///
+    /// Creates a grid holding every given point. The cell size is picked so each cell holds
+    /// around two points.
+    ///
+    /// # Parameters:
+    /// - `points`: The points to index
+    ///
+    /// # Returns:
+    /// - A new `PointGrid` containing all the points
+    ///
+    pub fn new(points: &'p [Point]) -> PointGrid<'p> {
+        let min_x = points.iter().map(|p| p.x.into_inner()).fold(f32::INFINITY, f32::min);
+        let min_y = points.iter().map(|p| p.y.into_inner()).fold(f32::INFINITY, f32::min);
+        let max_x = points.iter().map(|p| p.x.into_inner()).fold(f32::NEG_INFINITY, f32::max);
+        let max_y = points.iter().map(|p| p.y.into_inner()).fold(f32::NEG_INFINITY, f32::max);
+
+        if points.is_empty() {
+            return PointGrid { points, min_x: 0., min_y: 0., cell_size: 1., columns: 1, rows: 1, cells: vec![vec![]], remaining: 0 };
+        }
+
+        // points along a line have no area, so the cells are also at least as large as an equal
+        // share of the longer side, or there would be a huge number of empty cells
+        let area = (max_x - min_x) * (max_y - min_y);
+        let cell_size = (area * 2. / points.len() as f32).sqrt().max((max_x - min_x).max(max_y - min_y) / points.len() as f32).max(f32::EPSILON);
+        let columns = ((max_x - min_x) / cell_size) as usize + 1;
+        let rows = ((max_y - min_y) / cell_size) as usize + 1;
+
+        let mut grid = PointGrid { points, min_x, min_y, cell_size, columns, rows, cells: vec![vec![]; columns * rows], remaining: points.len() };
+        for (index, point) in points.iter().enumerate() {
+            let cell = grid.cell_of(point.x.into_inner(), point.y.into_inner());
+            grid.cells[cell.1 * columns + cell.0].push(index);
+        }
+
+        grid
+    }
+
+    ///
+    /// Removes a point from the grid, so it is no longer returned by `nearest`.
+    ///
+    /// # Parameters:
+    /// - `index`: The index of the point to remove
+    ///
+    pub fn remove(&mut self, index: usize) {
+        let point = self.points[index];
+        let (column, row) = self.cell_of(point.x.into_inner(), point.y.into_inner());
+        let cell = &mut self.cells[row * self.columns + column];
+
+        if let Some(position) = cell.iter().position(|&i| i == index) {
+            cell.swap_remove(position);
+            self.remaining -= 1;
+        }
+    }
+
+    ///
+    /// Finds the closest remaining point to a position. Rings of cells are searched outwards
+    /// from the position, stopping once no closer point could exist further out.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The position to search from
+    ///
+    /// # Returns:
+    /// - The index of the closest remaining point, or `None` if the grid is empty
+    ///
+    pub fn nearest(&self, x: f32, y: f32) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let target = Point::new(x, y);
+        let (column, row) = self.cell_of(x, y);
+        let max_ring = self.columns.max(self.rows);
+
+        let mut nearest = None;
+        let mut nearest_distance_sq = f32::INFINITY;
+
+        for ring in 0..=max_ring {
+            // every cell in this ring is at least (ring - 1) cells away from the position
+            let ring_distance = (ring as f32 - 1.).max(0.) * self.cell_size;
+            if nearest.is_some() && ring_distance * ring_distance > nearest_distance_sq {
+                break;
+            }
+
+            for (c, r) in ring_cells(column, row, ring, self.columns, self.rows) {
+                for &index in self.cells[r * self.columns + c].iter() {
+                    let distance_sq = self.points[index].distance_sq(&target);
+
+                    if distance_sq < nearest_distance_sq {
+                        nearest_distance_sq = distance_sq;
+                        nearest = Some(index);
+                    }
+                }
+            }
+        }
+
+        nearest
+    }
+
+    ///
+    /// Finds every remaining point within a distance of a position, searching only the cells
+    /// overlapping the circle.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The centre of the search
+    /// - `radius`: The greatest distance from the centre, inclusive
+    ///
+    /// # Returns:
+    /// - The indices of the points within the distance, ascending
+    ///
+    pub fn within(&self, x: f32, y: f32, radius: f32) -> Vec<usize> {
+        let target = Point::new(x, y);
+        let (min_column, min_row) = self.cell_of(x - radius, y - radius);
+        let (max_column, max_row) = self.cell_of(x + radius, y + radius);
+
+        let mut found: Vec<usize> = (min_row..=max_row)
+            .flat_map(|r| (min_column..=max_column).map(move |c| (c, r)))
+            .flat_map(|(c, r)| self.cells[r * self.columns + c].iter().copied())
+            .filter(|&index| self.points[index].distance_sq(&target) <= radius * radius)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    ///
+    /// # Returns:
+    /// - The number of points not yet removed
+    ///
+    pub fn len(&self) -> usize {
+        self.remaining
+    }
+
+    ///
+    /// # Returns:
+    /// - Whether every point has been removed
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    ///
+    /// # Returns:
+    /// - The (column, row) of the cell containing a position, clamped to the grid
+    ///
+    fn cell_of(&self, x: f32, y: f32) -> (usize, usize) {
+        let column = ((x - self.min_x) / self.cell_size).max(0.) as usize;
+        let row = ((y - self.min_y) / self.cell_size).max(0.) as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+}
+
+///
+/// Lists the cells on the square ring a given number of cells away from a centre cell, skipping
+/// any outside the grid.
+///
+/// # Parameters:
+/// - `column` and `row`: The centre cell
+/// - `ring`: The distance of the ring from the centre, where 0 is the centre cell alone
+/// - `columns` and `rows`: The size of the grid
+///
+/// # Returns:
+/// - The (column, row) of each cell on the ring
+///
+fn ring_cells(column: usize, row: usize, ring: usize, columns: usize, rows: usize) -> Vec<(usize, usize)> {
+    let (column, row, ring) = (column as isize, row as isize, ring as isize);
+    let in_grid = |c: isize, r: isize| c >= 0 && r >= 0 && (c as usize) < columns && (r as usize) < rows;
+
+    if ring == 0 {
+        return vec![(column as usize, row as usize)];
+    }
+
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+    for offset in -ring..=ring {
+        for (c, r) in [(column + offset, row - ring), (column + offset, row + ring)] {
+            if in_grid(c, r) {
+                cells.push((c as usize, r as usize));
+            }
+        }
+    }
+    for offset in -ring + 1..ring {
+        for (c, r) in [(column - ring, row + offset), (column + ring, row + offset)] {
+            if in_grid(c, r) {
+                cells.push((c as usize, r as usize));
+            }
+        }
+    }
+
+    cells
+}
+
+///
+/// Tests relating to the spatial index, against a brute force search.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let points: Vec<Point> = (0..500).map(|_| Point::new(rng.random_range(0. ..100.), rng.random_range(0. ..50.))).collect();
+        let mut grid = PointGrid::new(&points);
+        let mut removed = vec![false; points.len()];
+
+        for query in 0..400 {
+            // positions inside and well outside the points
+            let target = Point::new(rng.random_range(-50. ..150.), rng.random_range(-50. ..100.));
+            let brute_force = (0..points.len()).filter(|&i| !removed[i]).min_by(|&a, &b| points[a].distance_sq(&target).total_cmp(&points[b].distance_sq(&target)));
+
+            let nearest = grid.nearest(target.x.into_inner(), target.y.into_inner());
+            assert_eq!(nearest.map(|i| points[i].distance_sq(&target)), brute_force.map(|i| points[i].distance_sq(&target)), "query {}", query);
+
+            // remove points as a tour would
+            grid.remove(nearest.unwrap());
+            removed[nearest.unwrap()] = true;
+        }
+        assert_eq!(grid.len(), 100);
+    }
+
+    #[test]
+    fn within_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(12);
+        // clustered and collinear points, as well as scattered ones
+        let mut points: Vec<Point> = (0..300).map(|_| Point::new(rng.random_range(0. ..100.), rng.random_range(0. ..100.))).collect();
+        points.extend((0..50).map(|i| Point::new(i as f32, 20.)));
+        points.extend((0..50).map(|_| Point::new(rng.random_range(40. ..41.), rng.random_range(40. ..41.))));
+        let grid = PointGrid::new(&points);
+
+        for _ in 0..200 {
+            let (x, y, radius) = (rng.random_range(-20. ..120.), rng.random_range(-20. ..120.), rng.random_range(0. ..30.));
+            let brute_force: Vec<usize> = (0..points.len()).filter(|&i| points[i].distance_sq(&Point::new(x, y)) <= radius * radius).collect();
+            assert_eq!(grid.within(x, y, radius), brute_force);
+        }
+    }
+
+    #[test]
+    fn empty_grid() {
+        let grid = PointGrid::new(&[]);
+        assert_eq!(grid.nearest(1., 1.), None);
+        assert!(grid.within(0., 0., 10.).is_empty() && grid.is_empty());
+    }
+}
@@ -1,10 +1,62 @@
 use crate::drawing::util::stipple_structures::*;
-use image::{ImageBuffer, ImageReader};
-use rand::Rng;
+use crate::drawing::util::spatial::PointGrid;
+use crate::drawing::util::delaunay;
+use crate::drawing::util::files;
+use crate::drawing::util::tone::ToneCurve;
+use crate::drawing::util::imageproc::dither;
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use image::imageops::{self, FilterType};
+use serde::{Serialize, Deserialize};
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
 use ordered_float::OrderedFloat;
-use std::{collections::HashMap};
+use std::collections::BTreeMap;
 
 
+///
+/// How a colour image is separated into layers, each stippled separately for its own pen.
+///
+/// - `Cmyk`: Cyan, magenta, yellow and black layers
+/// - `Grey`: A number of grey levels, where darker areas of the image are covered by more layers
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Separation {
+    Cmyk,
+    Grey { levels: usize },
+}
+
+///
+/// How the initial points are placed, before they are relaxed.
+///
+/// - `Random`: Points are placed randomly, more likely on darker pixels
+/// - `Dithered`: Points are placed on the dots of a Floyd-Steinberg dither of the image, scaled so
+///   it has about as many dots as points. The points start far closer to an even distribution, so
+///   fewer iterations of relaxation are needed
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Seeding {
+    #[default]
+    Random,
+    Dithered,
+}
+
+///
+/// A set of stippled points for one pen.
+///
+/// # Fields:
+/// - `name`: The name of the layer, such as "cyan"
+/// - `colour`: The suggested pen colour for the layer, as RGBA
+/// - `points`: The positions of the stippled points, in image pixels
+///
+#[derive(Clone, Debug)]
+pub struct StippleLayer {
+    pub name: String,
+    pub colour: [u8; 4],
+    pub points: Vec<Point>,
+}
+
 /// 
 /// Main function to stipple points. This function plots the initial points,
 /// weighted towards darker areas of the image, and then calls n iterations of Lloyd's relaxation.
@@ -12,46 +64,127 @@ use std::{collections::HashMap};
 ///
 /// # Parameters:
 /// - `file_path`: The path of the input image file
+/// - `tone_curve`: The curve the brightness of the image is remapped through first
 /// - `num_points`: The number of points to stipple
 /// - `iterations`: The number of iterations of Lloyd's relaxation to perform
 /// - `relaxation_tendency`: The coefficient for Lloyd's relaxation
 /// - `brightness_threshold`: The luma value which below pixels are seeded
+/// - `seeding`: How the initial points are placed
+/// - `seed`: A seed for the initial points, or None for different points every time
 ///
 /// # Returns
 /// - A vector containing the positions of the stippled points
 /// - An error explaining why the stipple failed
 ///
-pub fn stipple_points(file_path: &str, num_points: usize, iterations: usize, relaxation_tendency: f32, brightness_threshold: u8) -> Result<Vec<Point>, String> {
+pub fn stipple_points(file_path: &str, tone_curve: &ToneCurve, num_points: usize, iterations: usize, relaxation_tendency: f32, brightness_threshold: u8, seeding: Seeding, seed: Option<u64>) -> Result<Vec<Point>, String> {
+    let input_image = tone_curve.apply(&open_image(file_path)?)?;
+    stipple_image(&input_image, num_points, iterations, relaxation_tendency, brightness_threshold, seeding, seed)
+}
 
-    // open input image
-    let input_image = match ImageReader::open(file_path) {
-        Ok(img) => {
-            img.decode().unwrap().into_rgb8()
-        },
-        Err(err) => {
-            return Err(format!("Error loading image. {}", err.to_string()).to_owned());
+///
+/// Stipples each layer of a colour separation separately, so each layer can be drawn with its
+/// own pen. The points are shared between the layers by how much ink each layer needs, and
+/// layers needing too little ink to stipple are left out.
+///
+/// # Parameters:
+/// - `file_path`: The path of the input image file
+/// - `tone_curve`: The curve the coverage of each layer is remapped through, before stippling
+/// - `separation`: How to separate the image into layers
+/// - `num_points`: The total number of points to stipple, across every layer
+/// - `iterations`: The number of iterations of Lloyd's relaxation to perform
+/// - `relaxation_tendency`: The coefficient for Lloyd's relaxation
+/// - `brightness_threshold`: The luma value which below pixels are seeded, in each layer
+/// - `seeding`: How the initial points of each layer are placed
+/// - `seed`: A seed for the initial points, or None for different points every time
+///
+/// # Returns
+/// - The stippled layers, in the order they should be drawn
+/// - An error explaining why the stipple failed
+///
+pub fn stipple_separated(file_path: &str, tone_curve: &ToneCurve, separation: Separation, num_points: usize, iterations: usize, relaxation_tendency: f32, brightness_threshold: u8, seeding: Seeding, seed: Option<u64>) -> Result<Vec<StippleLayer>, String> {
+    let input_image = open_image(file_path)?;
+    let mut channels = separate(&input_image, separation);
+    for (_, _, image) in channels.iter_mut() {
+        *image = tone_curve.apply(image)?;
+    }
+
+    // each layer's share of points is proportional to its total ink coverage
+    let coverages: Vec<f64> = channels.iter().map(|(_, _, image)| image.pixels().map(|p| 255. - p.0[0] as f64).sum()).collect();
+    let total_coverage: f64 = coverages.iter().sum();
+    if total_coverage <= 0. {
+        return Err("The input image has nothing to stipple, as it is blank".to_owned());
+    }
+
+    let mut layers = vec![];
+    for ((name, colour, image), coverage) in channels.into_iter().zip(coverages) {
+        let layer_points = (num_points as f64 * coverage / total_coverage).round() as usize;
+        if layer_points < 3 {
+            continue;
         }
+
+        let points = stipple_image(&image, layer_points, iterations, relaxation_tendency, brightness_threshold, seeding, seed)?;
+        layers.push(StippleLayer { name, colour, points });
+    }
+
+    Ok(layers)
+}
+
+///
+/// Opens an image file as RGB.
+///
+/// # Parameters:
+/// - `file_path`: The path of the image file, or the name of a file in memory
+///
+/// # Returns:
+/// - The decoded image
+/// - An error explaining why the image could not be opened
+///
+fn open_image(file_path: &str) -> Result<RgbImage, String> {
+    let bytes = files::read(file_path).map_err(|err| format!("Error loading image. {}", err))?;
+    match image::load_from_memory(&bytes) {
+        Ok(decoded) => Ok(decoded.into_rgb8()),
+        Err(err) => Err(format!("Error decoding image. {}", err)),
+    }
+}
+
+///
+/// Stipples points onto a loaded image, placing the initial points and relaxing them.
+///
+/// # Parameters:
+/// - `input_image`: The image to stipple, where darker pixels attract more points
+/// - `num_points`: The number of points to stipple
+/// - `iterations`: The number of iterations of Lloyd's relaxation to perform
+/// - `relaxation_tendency`: The coefficient for Lloyd's relaxation
+/// - `brightness_threshold`: The luma value which below pixels are seeded
+/// - `seeding`: How the initial points are placed
+/// - `seed`: A seed for the initial points, or None for different points every time
+///
+/// # Returns
+/// - A vector containing the positions of the stippled points
+/// - An error explaining why the stipple failed
+///
+fn stipple_image(input_image: &RgbImage, num_points: usize, iterations: usize, relaxation_tendency: f32, brightness_threshold: u8, seeding: Seeding, seed: Option<u64>) -> Result<Vec<Point>, String> {
+    let mut rng = seed.map_or_else(|| StdRng::from_rng(&mut rand::rng()), StdRng::seed_from_u64);
+
+    let mut points = match seeding {
+        Seeding::Random => vec![],
+        Seeding::Dithered => dithered_points(input_image, num_points, brightness_threshold, &mut rng)?,
     };
-    
-    // create list of points, place them randomly at darker areas of image
-    let mut points: Vec<Point> = Vec::with_capacity(num_points);
-    let mut points_placed = 0;
-    let mut rng = rand::rng();
 
-    while points_placed < num_points {
+    // place any points still needed randomly at darker areas of image
+    while points.len() < num_points {
         let rand_x = rng.random::<f32>() * input_image.width() as f32;
         let rand_y = rng.random::<f32>() * input_image.height() as f32;
 
         let pixel = input_image.get_pixel(rand_x as u32, rand_y as u32).0;
         if (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3 < ((rng.random::<f32>() * brightness_threshold as f32 * rng.random::<f32>()) as u32) {
             points.push(Point { x: OrderedFloat(rand_x), y: OrderedFloat(rand_y) });
-            points_placed += 1;
         }
     }
 
     // iterate the lloyd's relaxation n times
     for _ in 0..iterations {
-        if let Err(err_str) = iterate(&mut points, &input_image, relaxation_tendency) {
+        if let Err(err_str) = iterate(&mut points, input_image, relaxation_tendency) {
             return Err(err_str);
         };
     }
@@ -59,6 +192,96 @@ pub fn stipple_points(file_path: &str, num_points: usize, iterations: usize, rel
     Ok(points)
 }
 
+///
+/// Places initial points on the dots of a dithered image. The image is scaled so its dither has
+/// about `num_points` dots, as a dither has one dot for every 255 levels of darkness.
+///
+/// # Parameters:
+/// - `input_image`: The image to stipple, where darker pixels attract more points
+/// - `num_points`: The most points to place
+/// - `brightness_threshold`: The luma value which below pixels are seeded
+/// - `rng`: The generator which chooses the points to drop, when the dither has too many dots
+///
+/// # Returns:
+/// - Up to `num_points` points, in image pixels
+/// - An error if the image has no pixels darker than the threshold
+///
+fn dithered_points(input_image: &RgbImage, num_points: usize, brightness_threshold: u8, rng: &mut StdRng) -> Result<Vec<Point>, String> {
+    let luma = GrayImage::from_fn(input_image.width(), input_image.height(), |x, y| {
+        let [r, g, b] = input_image.get_pixel(x, y).0;
+        let value = ((r as u32 + g as u32 + b as u32) / 3) as u8;
+        Luma([if value < brightness_threshold { value } else { 255 }])
+    });
+
+    let darkness: f64 = luma.pixels().map(|p| (255 - p.0[0]) as f64 / 255.).sum();
+    if darkness <= 0. {
+        return Err("The input image has nothing to stipple, as it is lighter than the brightness threshold".to_owned());
+    }
+
+    let scale = (num_points as f64 / darkness).sqrt();
+    let width = ((luma.width() as f64 * scale).round() as u32).max(1);
+    let height = ((luma.height() as f64 * scale).round() as u32).max(1);
+    let scaled = imageops::resize(&luma, width, height, FilterType::Triangle);
+
+    let (scale_x, scale_y) = (luma.width() as f32 / width as f32, luma.height() as f32 / height as f32);
+    let mut points: Vec<Point> = dither::dot_points(&dither::floyd_steinberg(&scaled)).into_iter()
+        .map(|p| Point::new(p.x.into_inner() * scale_x, p.y.into_inner() * scale_y))
+        .collect();
+
+    points.shuffle(rng);
+    points.truncate(num_points);
+    Ok(points)
+}
+
+///
+/// Separates a colour image into one greyscale image per layer, where black is full ink
+/// coverage for that layer. The images stay RGB, so they can be stippled like any other image.
+///
+/// # Parameters:
+/// - `input_image`: The colour image to separate
+/// - `separation`: How to separate the image into layers
+///
+/// # Returns:
+/// - A (name, pen colour, coverage image) for each layer
+///
+fn separate(input_image: &RgbImage, separation: Separation) -> Vec<(String, [u8; 4], RgbImage)> {
+    let coverage_image = |coverage: &dyn Fn([f64; 3]) -> f64| {
+        RgbImage::from_fn(input_image.width(), input_image.height(), |x, y| {
+            let [r, g, b] = input_image.get_pixel(x, y).0;
+            let value = 255. - coverage([r as f64 / 255., g as f64 / 255., b as f64 / 255.]).clamp(0., 1.) * 255.;
+            Rgb([value.round() as u8; 3])
+        })
+    };
+
+    match separation {
+        Separation::Cmyk => {
+            // naive separation, with full under colour removal into the black layer
+            let black = |rgb: [f64; 3]| 1. - rgb[0].max(rgb[1]).max(rgb[2]);
+            let ink = |channel: usize| move |rgb: [f64; 3]| {
+                let k = black(rgb);
+                if k >= 1. { 0. } else { (1. - rgb[channel] - k) / (1. - k) }
+            };
+
+            vec![
+                ("yellow".to_owned(), [255, 220, 0, 255], coverage_image(&ink(2))),
+                ("magenta".to_owned(), [220, 0, 130, 255], coverage_image(&ink(1))),
+                ("cyan".to_owned(), [0, 160, 230, 255], coverage_image(&ink(0))),
+                ("black".to_owned(), [0, 0, 0, 255], coverage_image(&black)),
+            ]
+        },
+        Separation::Grey { levels } => {
+            let levels = levels.max(1);
+
+            // layer i covers darkness above i / levels, so the darkest areas get every layer
+            (0..levels).map(|level| {
+                let coverage = move |rgb: [f64; 3]| (1. - (rgb[0] + rgb[1] + rgb[2]) / 3.) * levels as f64 - level as f64;
+                let shade = (200. * (1. - (level + 1) as f64 / levels as f64)).round() as u8;
+                (format!("grey {}", level + 1), [shade, shade, shade, 255], coverage_image(&coverage))
+            }).collect()
+        },
+    }
+}
+
 
 ///
 /// Performs an iteration of relaxation on a list of points, changing the points in place.
@@ -74,15 +297,15 @@ pub fn stipple_points(file_path: &str, num_points: usize, iterations: usize, rel
 /// - Void if an iteration suceeded
 /// - An error as an owned string, explaining why the function failed
 ///
-fn iterate(points: &mut Vec<Point>, input_image: &ImageBuffer<image::Rgb<u8>, Vec<u8>>, relaxation_tendency: f32) -> Result<(), String> {
+fn iterate(points: &mut Vec<Point>, input_image: &RgbImage, relaxation_tendency: f32) -> Result<(), String> {
     
     // computes the delaunay triangulation
-    let (triangles, new_points) = match bowyer_watson(points) {
+    let (triangles, new_points) = match triangulate(points) {
         Ok((tri, n_p)) => (tri, n_p),
         Err(err_str) => return Err(err_str),
     };
 
-    let edge_triangles: HashMap<(usize, usize), (usize, usize)> = match get_edge_triangles(&triangles) {
+    let edge_triangles: BTreeMap<(usize, usize), (usize, usize)> = match get_edge_triangles(&triangles) {
         Ok(val) => val,
         Err(err_str) => return Err(err_str),
     };
@@ -94,8 +317,10 @@ fn iterate(points: &mut Vec<Point>, input_image: &ImageBuffer<image::Rgb<u8>, Ve
     };
 
     // performs the weghted lloyd's stippling, tending cell sites towards the cell centroids given
-    // a scalar `relaxation_tendency`
-    for (index, (&site, neighbours)) in site_vertices.iter().enumerate() {
+    // a scalar `relaxation_tendency`. each site only reads shared data, so sites are relaxed in
+    // parallel and the results written back afterwards
+    let sites: Vec<(usize, &Vec<usize>)> = site_vertices.iter().map(|(&site, neighbours)| (site, neighbours)).collect();
+    let relaxed: Vec<(usize, Point)> = sites.par_iter().map(|&(site, neighbours)| {
         let mut sum_weighted_x = 0.;
         let mut sum_weighted_y = 0.;
         let mut total_weight = 0.;
@@ -118,53 +343,126 @@ fn iterate(points: &mut Vec<Point>, input_image: &ImageBuffer<image::Rgb<u8>, Ve
         let lerp_x = new_points[site].x + (centroid_x - *new_points[site].x) * relaxation_tendency;
         let lerp_y = new_points[site].y + (centroid_y - *new_points[site].y) * relaxation_tendency;
 
-        points[index] = Point { x: lerp_x, y: lerp_y };
+        (site, Point { x: lerp_x, y: lerp_y })
+    }).collect();
+
+    // each site is written back to its own index, so no point is moved twice or left behind
+    for (site, point) in relaxed {
+        points[site] = point;
     }
 
     Ok(())
 }
 
 
+///
+/// Where a stipple tour starts.
+///
+/// - `FirstPoint`: The first stippled point, wherever it is
+/// - `TopLeft`, `TopRight`, `BottomLeft` and `BottomRight`: The point closest to a corner of the image
+/// - `Position`: The point closest to a position, as fractions of the width and height of the image
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum TourStart {
+    #[default]
+    FirstPoint,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Position { x: f32, y: f32 },
+}
+
+///
+/// How a stipple tour is seeded, to avoid long jumps across the page.
+///
+/// # Fields:
+/// - `start`: Where the tour starts
+/// - `bands`: The number of horizontal bands the image is split into. The tour prefers to
+///   finish the band it is in before moving to another, or doesn't prefer any points if 0 or 1
+/// - `band_weight`: How many times further a point in the current band can be than the closest
+///   point in another band, and still be visited first. At least 1
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct TourOptions {
+    pub start: TourStart,
+    pub bands: usize,
+    pub band_weight: f32,
+}
+
+impl Default for TourOptions {
+    fn default() -> Self {
+        TourOptions { start: TourStart::FirstPoint, bands: 0, band_weight: 2. }
+    }
+}
+
 ///
 /// Performs the nearest neighbour pathfinding algorithm on a given set of points.
 /// I use nearest neighbour only to create a path for the pen to follow - hence a bad,
 /// heuristic pathfinding algorithm is not the end of the world.
+/// Points are looked up through a `PointGrid` per band, so large sets of points don't need a
+/// linear scan for every step of the tour.
 ///
 /// # Parameters:
 /// - `points`: A list of points to perform the pathfinding algorithm on
+/// - `options`: Where the tour starts, and how strongly it stays within bands
 ///
 /// # Returns:
 /// - A new vector, the tour, representing the indices of the points in order
 ///
-pub fn nearest_neighbour_tour(points: &Vec<Point>) -> Vec<usize> {
-    let mut visited = vec![false; points.len()];
-    
+pub fn nearest_neighbour_tour(points: &Vec<Point>, options: &TourOptions) -> Vec<usize> {
     let mut tour: Vec<usize> = Vec::with_capacity(points.len());
-    let mut current_idx = 0;
+    if points.is_empty() {
+        return tour;
+    }
+
+    let (min_x, max_x) = (points.iter().map(|p| p.x).min().unwrap().into_inner(), points.iter().map(|p| p.x).max().unwrap().into_inner());
+    let (min_y, max_y) = (points.iter().map(|p| p.y).min().unwrap().into_inner(), points.iter().map(|p| p.y).max().unwrap().into_inner());
+
+    let bands = options.bands.max(1);
+    let band_of = |point: &Point| ((((point.y.into_inner() - min_y) / (max_y - min_y).max(f32::EPSILON)) * bands as f32) as usize).min(bands - 1);
+
+    // each band's grid only holds the points in that band
+    let mut grids: Vec<PointGrid> = (0..bands).map(|band| {
+        let mut grid = PointGrid::new(points);
+        points.iter().enumerate().filter(|(_, p)| band_of(p) != band).for_each(|(idx, _)| grid.remove(idx));
+        grid
+    }).collect();
+
+    let start = match options.start {
+        TourStart::FirstPoint => None,
+        TourStart::TopLeft => Some((min_x, min_y)),
+        TourStart::TopRight => Some((max_x, min_y)),
+        TourStart::BottomLeft => Some((min_x, max_y)),
+        TourStart::BottomRight => Some((max_x, max_y)),
+        TourStart::Position { x, y } => Some((min_x + x * (max_x - min_x), min_y + y * (max_y - min_y))),
+    };
+    let mut current_idx = match start {
+        Some((x, y)) => (0..points.len()).min_by(|&a, &b| points[a].distance_sq(&Point::new(x, y)).total_cmp(&points[b].distance_sq(&Point::new(x, y)))).unwrap(),
+        None => 0,
+    };
 
     tour.push(current_idx);
-    visited[current_idx] = true;
-
-    // repeatedly find the next closest point, and add it to the tour
-    for _ in 1..points.len() {
-        let mut nearest = None;
-        let mut nearest_distance = f32::INFINITY;
-
-        for k in 0..points.len() {
-            if !visited[k] {
-                let dist = points[current_idx].calc_shortest_dist(&points[k]);
-                if dist < nearest_distance {
-                    nearest_distance = dist;
-                    nearest = Some(k);
-                }
-            }
-        }
+    grids[band_of(&points[current_idx])].remove(current_idx);
 
-        if let Some(next_idx) = nearest {
-            current_idx = next_idx;
-            tour.push(current_idx);
-            visited[current_idx] = true;
-        }
+    // repeatedly find the next closest point, staying in the current band unless the closest
+    // point elsewhere is much closer, and add it to the tour
+    let band_weight = options.band_weight.max(1.);
+    loop {
+        let current = points[current_idx];
+        let nearest = |grid: &PointGrid| grid.nearest(current.x.into_inner(), current.y.into_inner()).map(|idx| (idx, points[idx].distance(&current)));
+
+        let Some((closest_idx, closest_distance)) = grids.iter().filter_map(nearest).min_by(|a, b| a.1.total_cmp(&b.1)) else {
+            break;
+        };
+        current_idx = match nearest(&grids[band_of(&current)]) {
+            Some((idx, distance)) if distance <= closest_distance * band_weight => idx,
+            _ => closest_idx,
+        };
+
+        tour.push(current_idx);
+        grids[band_of(&points[current_idx])].remove(current_idx);
     }
 
     tour
@@ -173,76 +471,28 @@ pub fn nearest_neighbour_tour(points: &Vec<Point>) -> Vec<usize> {
 
 /// 
 /// Computes the delaunay triangulation, given a set of points.
-/// This function is an implementation of the Bowyer-Watson algorithm.
-/// Pseudocode reference: https://en.wikipedia.org/wiki/Bowyer%E2%80%93Watson_algorithm#Pseudocode
+/// This used to be a Bowyer-Watson implementation, which rebuilt its bad triangle list for every
+/// point and dominated the stippling time. It now uses the sweep-hull triangulation in
+/// `util::delaunay`, which also triangulates the whole convex hull.
+/// Duplicated points are left out of the triangulation, so have no voronoi cell and aren't moved
+/// by that iteration. The copy which is triangulated is moved away, so they are apart and both
+/// relaxed from the next iteration.
 ///
 /// # Parameters:
 /// - `points`: The list of points of which to compute the delaunay triangulation
 ///
 /// # Returns:
 /// - A new vector of arrays, where each array of 3 indices points to the 3 vertices of a triangle
-/// - A list of points with the super-triangle vertices
-///
-fn bowyer_watson(points: &Vec<Point>) -> Result<(Vec<[usize; 3]>, Vec<Point>), String> {
-
-    // single copy to vec occurs here
-    let mut all_points = points.to_vec();
-
-    let super_triangle = get_super_triangle(points);
-    let super_triangle_index = all_points.len();
-    all_points.push(super_triangle[0].clone());
-    all_points.push(super_triangle[1].clone());
-    all_points.push(super_triangle[2].clone());
-
-    // 3 consecutive integers are indices of all_points, which form a triangle
-    let mut triangle_indices: Vec<[usize; 3]> = Vec::with_capacity(all_points.len() * 2); // roughly get enough space for all triangles
-    triangle_indices.push(make_anticlockwise_triangle(super_triangle_index, super_triangle_index + 1, super_triangle_index + 2, &all_points));
-
-    // assign memory outside iterator, just clear within. typically lengths wont go above around 50
-    let mut bad_edges: Vec<(usize, usize)> = Vec::with_capacity(100);
-    let mut polygon: Vec<(usize, usize)> = Vec::with_capacity(100);
-
-    // doesn't iterate super_triangle points
-    for point_idx in 0..points.len() {
-        bad_edges.clear();
-        polygon.clear();
-
-        // nb: point in circle tests are expensive
-        let bad_triangles: Vec<usize> = triangle_indices.iter().enumerate()
-            .filter_map(|(index_set_index, triangle)| {
-                if Triangle::point_in_circle(all_points.get(triangle[0]).unwrap(), all_points.get(triangle[1]).unwrap(), all_points.get(triangle[2]).unwrap(), &all_points[point_idx]) {
-                    Some(index_set_index)
-                } else {
-                    None
-                }
-            }).collect();
-
-
-        // count edges and build polygon in one pass
-        let mut edge_count = HashMap::new();
-        for &tri_idx in bad_triangles.iter() {
-            let tri = &triangle_indices[tri_idx];
-            for &(a, b) in &[make_edge(tri[0], tri[1]), make_edge(tri[1], tri[2]), make_edge(tri[2], tri[0])] {
-                let count = edge_count.entry((a, b)).or_insert(0);
-                *count += 1;
-            }
-        }
-        polygon.extend(edge_count.iter().filter_map(|(&edge, &count)| if count == 1 { Some(edge) } else { None }));
-
-
-        for bad_triangle_index in bad_triangles.iter().rev() { // reverse iterator to preverse index ordering
-            triangle_indices.swap_remove(*bad_triangle_index);
-        }
-
-        for &(a, b) in polygon.iter() {
-            triangle_indices.push(make_anticlockwise_triangle(a, b, point_idx, &all_points));
-        }
+/// - The list of points the triangle indices refer to
+/// - An error as an owned string, if the points could not be triangulated
+///
+fn triangulate(points: &Vec<Point>) -> Result<(Vec<[usize; 3]>, Vec<Point>), String> {
+    let triangles = delaunay::triangulate(points);
+    if triangles.is_empty() {
+        return Err("Could not triangulate the stippled points, as there were fewer than 3 or they were all in a line".to_owned());
     }
 
-    // remove all triangles connected to super_triangle
-    triangle_indices.retain(|tri| !(tri.contains(&super_triangle_index) || tri.contains(&(super_triangle_index + 1)) || tri.contains(&(super_triangle_index + 2))));
-
-    Ok((triangle_indices, all_points))
+    Ok((triangles, points.to_vec()))
 }
 
 
@@ -258,10 +508,10 @@ fn bowyer_watson(points: &Vec<Point>) -> Result<(Vec<[usize; 3]>, Vec<Point>), S
 /// - `triangles`: A vector of arrays of triangle indices
 ///
 /// # Returns:
-/// - A HashMap of edges <-> triangles as described above
+/// - A BTreeMap of edges <-> triangles as described above
 /// - An error as an owned string, explaining the error
 ///
-fn get_edge_triangles(triangles: &Vec<[usize; 3]>) -> Result<HashMap<(usize, usize), (usize, usize)>, String> {
+fn get_edge_triangles(triangles: &Vec<[usize; 3]>) -> Result<BTreeMap<(usize, usize), (usize, usize)>, String> {
     // theoretically, if there are 18446744073709551615 or more points, we have a problem.
     if triangles.len() >= usize::MAX {
         return Err("There were too many triangles to safely set null to usize::MAX".to_owned());
@@ -270,7 +520,9 @@ fn get_edge_triangles(triangles: &Vec<[usize; 3]>) -> Result<HashMap<(usize, usi
     // normalised edge (usize usize) <-> (usize, usize) pointers to triangles
     // by default, the pointers to triangles are usize::MAX. each tuple will have either 2 or 1
     // indexes, if it has 1 index and one usize::MAX, it is a hull edge.
-    let mut edge_triangle: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    // the voronoi diagram is built in the order of this map, and summing floats in a different
+    // order gives different points, so it is ordered rather than hashed with a random seed
+    let mut edge_triangle: BTreeMap<(usize, usize), (usize, usize)> = BTreeMap::new();
     for (index, triangle) in triangles.iter().enumerate() {
         for (edge_idx0, edge_idx1) in Triangle::get_edge_indexes(&triangle) {
             let key = if edge_idx0 > edge_idx1 { (edge_idx0, edge_idx1) } else { (edge_idx1, edge_idx0) };
@@ -300,17 +552,17 @@ fn get_edge_triangles(triangles: &Vec<[usize; 3]>) -> Result<HashMap<(usize, usi
 /// # Parameters:
 /// - `points`: A list of points which form the vertices of the delaunay triangulation
 /// - `triangles`: A list of triangle arrays, which are 3 indices representing point indices
-/// - `edge_triangles`: A HashMap to lookup which edge makes which triangle(s)
+/// - `edge_triangles`: A BTreeMap to lookup which edge makes which triangle(s)
 /// - `max_wh`: The width/height to bound the diagram to
 ///
 /// # Returns:
 /// - A vector of the voronoi diagram's indices
 /// - A vector containing two indices of the point vector, which form the edges of the voronoi diagram
-/// - A HashMap of a site, with a key as an index of the point vector, and a list of
+/// - A BTreeMap of a site, with a key as an index of the point vector, and a list of
 ///   corresponding indices of the point vector, which form the polygon of the voronoi cell
 /// - An error as an owned string, explaining the error
 ///
-fn get_extended_voronoi(points: &Vec<Point>, triangles: &Vec<[usize; 3]>, edge_triangles: &HashMap<(usize, usize), (usize, usize)>, max_wh: (f32, f32)) -> Result<(Vec<Point>, Vec<(usize, usize)>, HashMap<usize, Vec<usize>>), String> {
+fn get_extended_voronoi(points: &Vec<Point>, triangles: &Vec<[usize; 3]>, edge_triangles: &BTreeMap<(usize, usize), (usize, usize)>, max_wh: (f32, f32)) -> Result<(Vec<Point>, Vec<(usize, usize)>, BTreeMap<usize, Vec<usize>>), String> {
     // vector, the index of the site point corresponds to the index of the triangle in `triangles`
     let mut voronoi_sites: Vec<Point> = Vec::with_capacity(triangles.len());
     voronoi_sites.extend(std::iter::repeat(Point { x: OrderedFloat(0.), y: OrderedFloat(0.) }).take(triangles.len()));
@@ -330,7 +582,7 @@ fn get_extended_voronoi(points: &Vec<Point>, triangles: &Vec<[usize; 3]>, edge_t
 
     // lazily compute hull edges
     let mut hull_point_tri: Vec<((usize, usize), usize)> = vec![];
-    let mut site_vertices: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut site_vertices: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
     
     // for every edge, and its corresponding triangles
     for ((p0, p1), (t0, t1)) in edge_triangles.iter() {
@@ -533,60 +785,82 @@ fn get_extended_voronoi(points: &Vec<Point>, triangles: &Vec<[usize; 3]>, edge_t
 }
 
 
-/// 
-/// Computes the size of the initial super triangle for the delaunay triangulation.
-/// The super triangle must enclose all given points.
 ///
-/// # Parameters:
-/// - `points`: The points of which to create the super triangle on
-///
-/// # Returns:
-/// - An array of 3 points which form the super triangle
+/// Tests relating to stippling and stipple tours.
 ///
-fn get_super_triangle(points: &[Point]) -> [Point; 3] {
-    let max_x = points.iter().max_by_key(|p| p.x).unwrap().x * 2.;
-    let max_y = points.iter().max_by_key(|p| p.y).unwrap().y * 2.;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tours_start_from_the_chosen_corner_and_finish_each_band() {
+        let points: Vec<Point> = (0..100).map(|i| Point::new((i % 10) as f32, (i / 10) as f32)).collect();
+
+        let tour = nearest_neighbour_tour(&points, &TourOptions::default());
+        assert_eq!(tour[0], 0);
+        assert_eq!(tour.len(), points.len());
+
+        let options = TourOptions { start: TourStart::BottomRight, bands: 2, band_weight: 100. };
+        let tour = nearest_neighbour_tour(&points, &options);
+        assert_eq!(tour[0], 99);
+        let mut visited = tour.clone();
+        visited.sort();
+        assert_eq!(visited, (0..100).collect::<Vec<usize>>());
+
+        // the bottom band is finished before the tour moves up to the top band
+        assert!(tour[..50].iter().all(|&idx| points[idx].y.into_inner() >= 5.), "{:?}", tour);
+    }
 
-    [ Point { x: OrderedFloat(0.), y: OrderedFloat(0.) }, Point { x: max_x, y: OrderedFloat(0.) }, Point { x: OrderedFloat(0.), y: max_y } ]
-}
+    #[test]
+    fn dithered_seeding_places_points_on_the_dark_areas() {
+        // the left half is black and the right half white
+        let image = RgbImage::from_fn(100, 50, |x, _| if x < 50 { Rgb([0; 3]) } else { Rgb([255; 3]) });
 
+        let points = stipple_image(&image, 500, 0, 0.5, 200, Seeding::Dithered, Some(1)).unwrap();
+        assert_eq!(points.len(), 500);
+        // scaling the image blurs the edge by up to a scaled pixel, about 2.2 pixels
+        assert!(points.iter().all(|p| p.x.into_inner() < 52.5), "{:?}", points.iter().find(|p| p.x.into_inner() >= 52.5));
 
-/// 
-/// Orders a list of triangle indices, ensuring geometrical vertices are anti-clockwise.
-///
-/// # Parameters:
-/// - `i0`: The first point-index of the triangle
-/// - `i1`: The second point-index of the triangle
-/// - `i2`: The third point-index of the triangle
-/// - `points`: A reference to all points in the graph
-/// 
-/// # Returns:
-/// - An array of 3 indices, referencing points in the triangle
-///
-fn make_anticlockwise_triangle(i0: usize, i1: usize, i2: usize, points: &Vec<Point>) -> [usize; 3] {
-    let pa = &points[i0];
-    let pb = &points[i1];
-    let pc = &points[i2];
+        // no two dots of a dither are in the same place
+        let distinct: std::collections::BTreeSet<(OrderedFloat<f32>, OrderedFloat<f32>)> = points.iter().map(|p| (p.x, p.y)).collect();
+        assert_eq!(distinct.len(), points.len());
 
-    let cross = (pb.x - pa.x) * (pc.y - pa.y) - (pb.y - pa.y) * (pc.x - pa.x);
-    if cross.into_inner() > 0.0 {
-        [i0, i1, i2] // anticlockwise already
-    } else {
-        [i0, i2, i1] // or swap last two points
+        let blank = RgbImage::from_pixel(10, 10, Rgb([255; 3]));
+        assert!(stipple_image(&blank, 10, 0, 0.5, 200, Seeding::Dithered, Some(1)).is_err());
     }
-}
 
-/// 
-/// Creates a normalised edge.
-/// 
-/// # Parameters:
-/// - `a`: First index
-/// - `b`: Second index
-///
-/// # Returns:
-/// - A normalised tuple of the indices
-///
-#[inline(always)]
-fn make_edge(a: usize, b: usize) -> (usize, usize) {
-    if a < b { (a, b) } else { (b, a) }
+    /// Encodes a 90x30 image, cyan on the left, magenta in the middle and black on the right.
+    fn thirds_image() -> Vec<u8> {
+        let image = RgbImage::from_fn(90, 30, |x, _| match x / 30 {
+            0 => Rgb([0, 255, 255]),
+            1 => Rgb([255, 0, 255]),
+            _ => Rgb([0, 0, 0]),
+        });
+        let mut bytes = vec![];
+        image.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn separated_layers_have_disjoint_dots() {
+        files::insert("separated-thirds.png", thirds_image());
+        let layers = stipple_separated("separated-thirds.png", &ToneCurve::default(), Separation::Cmyk, 300, 5, 0.5, 200, Seeding::Random, Some(2)).unwrap();
+
+        // there is no yellow, and each other layer only has dots on its own third
+        let names: Vec<&str> = layers.iter().map(|layer| layer.name.as_str()).collect();
+        assert_eq!(names, vec!["magenta", "cyan", "black"]);
+        for (layer, third) in layers.iter().zip([1., 0., 2.]) {
+            assert_eq!(layer.points.len(), 100);
+            assert!(layer.points.iter().all(|p| p.x.into_inner() >= third * 30. - 1. && p.x.into_inner() <= third * 30. + 31.), "{}", layer.name);
+        }
+
+        let blank = RgbImage::from_pixel(10, 10, Rgb([255; 3]));
+        let mut bytes = vec![];
+        blank.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+        files::insert("separated-blank.png", bytes);
+        assert!(stipple_separated("separated-blank.png", &ToneCurve::default(), Separation::Grey { levels: 3 }, 300, 5, 0.5, 200, Seeding::Random, Some(2)).is_err());
+
+        files::remove("separated-thirds.png");
+        files::remove("separated-blank.png");
+    }
 }
@@ -1,31 +1,8 @@
 use ordered_float::OrderedFloat;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 
-///
-/// A representation of a 2D point.
-///
-/// # Fields:
-/// - `x`: An f32 for the x component
-/// - `y`: An f32 for the y component
-///
-#[derive(Eq, PartialEq, Clone, Debug, Copy)]
-pub struct Point {
-    pub x: OrderedFloat<f32>,
-    pub y: OrderedFloat<f32>,
-}
-
-impl Point {
-    ///
-    /// Calculates the distance to the given neighbour point.
-    /// 
-    /// # Returns:
-    /// - An f32 representing the direct distance
-    ///
-    pub fn calc_shortest_dist(&self, other: &Self) -> f32 {
-        (self.x.into_inner() - *other.x).max(0.) + (self.y.into_inner() - *other.y).max(0.)
-    }
-}
+pub use crate::drawing::util::geometry::Point;
 
 ///
 /// An empty struct, with an implemented edge-related function.
@@ -162,7 +139,7 @@ impl Triangle {
     /// - `Some(usize)` if there is a neighbouring triangle, with the index of the neighbouring
     /// triangle
     ///
-    pub fn get_neighbouring_triangle(requested_triangle_index: usize, edge_indices: (usize, usize), edge_triangle: &HashMap<(usize, usize), (usize, usize)>) -> Option<usize> {
+    pub fn get_neighbouring_triangle(requested_triangle_index: usize, edge_indices: (usize, usize), edge_triangle: &BTreeMap<(usize, usize), (usize, usize)>) -> Option<usize> {
         let key = if edge_indices.0 > edge_indices.1 { (edge_indices.0, edge_indices.1) } else { (edge_indices.1, edge_indices.0) }; 
 
         if let Some((i0, i1)) = edge_triangle.get(&key) {
@@ -0,0 +1,93 @@
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "image")]
+use image::RgbImage;
+
+///
+/// How the brightness of an input image is remapped before an image-driven method draws it,
+/// since each pen and paper responds differently. Every curve maps 0 (black) to 255 (white).
+///
+/// - `Linear`: The image is drawn as it is
+/// - `Gamma`: A power curve, where a gamma above 1 darkens the midtones and below 1 lightens them
+///     Parameters:
+///     - `gamma`: The exponent of the curve, above 0
+/// - `SCurve`: Increases the contrast of the midtones, keeping black and white in place
+///     Parameters:
+///     - `contrast`: How strongly the curve bends, from 0 (linear) to 1
+/// - `Lut`: A custom curve through evenly spaced output values, interpolated between them
+///     Parameters:
+///     - `values`: The output brightness at evenly spaced inputs, from black to white. At least two
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum ToneCurve {
+    #[default]
+    Linear,
+    Gamma { gamma: f64 },
+    SCurve { contrast: f64 },
+    Lut { values: Vec<u8> },
+}
+
+impl ToneCurve {
+    ///
+    /// Builds the lookup table of the curve, checking its parameters.
+    ///
+    /// # Returns:
+    /// - The output brightness for every input brightness
+    /// - An error explaining why the curve's parameters are invalid
+    ///
+    pub fn table(&self) -> Result<[u8; 256], String> {
+        let curve: Box<dyn Fn(f64) -> f64> = match self {
+            ToneCurve::Linear => Box::new(|x| x),
+            ToneCurve::Gamma { gamma } => {
+                if !gamma.is_finite() || *gamma <= 0. {
+                    return Err(format!("The gamma of a tone curve must be above 0, not {}", gamma));
+                }
+                let gamma = *gamma;
+                Box::new(move |x| x.powf(gamma))
+            },
+            ToneCurve::SCurve { contrast } => {
+                if !(0. ..=1.).contains(contrast) {
+                    return Err(format!("The contrast of an s-curve must be between 0 and 1, not {}", contrast));
+                }
+                // blends towards smoothstep, which is flat at black and white and steep at the midtones
+                let contrast = *contrast;
+                Box::new(move |x| x + contrast * (x * x * (3. - 2. * x) - x))
+            },
+            ToneCurve::Lut { values } => {
+                if values.len() < 2 {
+                    return Err("A tone curve lookup table needs at least two values".to_owned());
+                }
+                let values = values.clone();
+                Box::new(move |x| {
+                    let position = x * (values.len() - 1) as f64;
+                    let lower = (position.floor() as usize).min(values.len() - 2);
+                    let t = position - lower as f64;
+                    (values[lower] as f64 * (1. - t) + values[lower + 1] as f64 * t) / 255.
+                })
+            },
+        };
+
+        let mut table = [0; 256];
+        for (input, output) in table.iter_mut().enumerate() {
+            *output = (curve(input as f64 / 255.).clamp(0., 1.) * 255.).round() as u8;
+        }
+        Ok(table)
+    }
+
+    ///
+    /// Remaps every channel of an image through the curve.
+    ///
+    /// # Parameters:
+    /// - `input_image`: The image to remap
+    ///
+    /// # Returns:
+    /// - The remapped image
+    /// - An error explaining why the curve's parameters are invalid
+    ///
+    #[cfg(feature = "image")]
+    pub fn apply(&self, input_image: &RgbImage) -> Result<RgbImage, String> {
+        let table = self.table()?;
+        let mut output_image = input_image.clone();
+        output_image.pixels_mut().for_each(|pixel| pixel.0 = pixel.0.map(|channel| table[channel as usize]));
+        Ok(output_image)
+    }
+}
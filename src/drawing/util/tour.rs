@@ -0,0 +1,292 @@
+use std::time::{Duration, Instant};
+
+use crate::drawing::util::geometry::Point;
+
+///
+/// How long a tour is improved for, when a drawing method has no reason to choose otherwise.
+///
+pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_millis(500);
+
+///
+/// The number of candidate moves tried while improving a tour, when a drawing method has no
+/// reason to choose otherwise. This is around half a second of work on a desktop, so on a fast
+/// computer the same points give the same tour, however busy it is.
+///
+pub const DEFAULT_MOVE_BUDGET: usize = 50_000_000;
+
+///
+/// Improves a tour with 2-opt, then Or-opt, repeating until neither improves the tour or either
+/// budget runs out. The tour is an open path, it does not return to its first point.
+///
+/// # Parameters:
+/// - `points`: The points visited by the tour
+/// - `tour`: The indices of the points, in order, which are reordered in place
+/// - `max_time`: How long to improve the tour for, after which the best tour so far is kept.
+///   In the browser there is no clock, so only `max_moves` applies
+/// - `max_moves`: The number of candidate moves to try, after which the best tour so far is kept
+///
+pub fn improve_tour(points: &[Point], tour: &mut Vec<usize>, max_time: Duration, max_moves: usize) {
+    let deadline = deadline_after(max_time);
+    let mut budget = max_moves;
+
+    loop {
+        let improved_two_opt = two_opt(points, tour, deadline, &mut budget);
+        let improved_or_opt = or_opt(points, tour, deadline, &mut budget);
+
+        if (!improved_two_opt && !improved_or_opt) || budget == 0 || is_past(deadline) {
+            return;
+        }
+    }
+}
+
+///
+/// Improves a tour by reversing sections of it, which removes any crossing lines. Passes are
+/// repeated until no reversal shortens the tour, the deadline passes, or the move budget runs out.
+///
+/// # Parameters:
+/// - `points`: The points visited by the tour
+/// - `tour`: The indices of the points, in order, which are reordered in place
+/// - `deadline`: When to stop improving the tour, or None to stop only on the move budget
+/// - `budget`: The number of candidate moves left to try, reduced by those tried
+///
+/// # Returns:
+/// - Whether the tour was shortened
+///
+pub fn two_opt(points: &[Point], tour: &mut [usize], deadline: Option<Instant>, budget: &mut usize) -> bool {
+    let n = tour.len();
+    let mut improved_any = false;
+    let mut improved = true;
+
+    while improved {
+        improved = false;
+
+        for i in 0..n.saturating_sub(2) {
+            // a pass is O(n²) even when nothing improves, so the clock is checked within it
+            if is_past(deadline) {
+                return improved_any;
+            }
+            let (a, b) = (tour[i], tour[i + 1]);
+            for j in i + 2..n {
+                if *budget == 0 {
+                    return improved_any;
+                }
+                *budget -= 1;
+
+                let c = tour[j];
+
+                // reversing b..=c replaces edges a-b and c-d with a-c and b-d
+                let mut delta = distance(points, a, c) - distance(points, a, b);
+                if j + 1 < n {
+                    let d = tour[j + 1];
+                    delta += distance(points, b, d) - distance(points, c, d);
+                }
+
+                if delta < -1e-6 {
+                    tour[i + 1..=j].reverse();
+                    improved = true;
+                    improved_any = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    improved_any
+}
+
+///
+/// Improves a tour by moving short runs of up to 3 points to a better place in the tour,
+/// possibly reversed. Passes are repeated until no move shortens the tour, the deadline passes,
+/// or the move budget runs out.
+///
+/// # Parameters:
+/// - `points`: The points visited by the tour
+/// - `tour`: The indices of the points, in order, which are reordered in place
+/// - `deadline`: When to stop improving the tour, or None to stop only on the move budget
+/// - `budget`: The number of candidate moves left to try, reduced by those tried
+///
+/// # Returns:
+/// - Whether the tour was shortened
+///
+pub fn or_opt(points: &[Point], tour: &mut Vec<usize>, deadline: Option<Instant>, budget: &mut usize) -> bool {
+    let n = tour.len();
+    let mut improved_any = false;
+    let mut improved = true;
+
+    while improved {
+        improved = false;
+
+        'search: for segment_len in 1..=3 {
+            // the segment can be anywhere in the tour, including at its end
+            if n < segment_len {
+                break;
+            }
+            for start in 0..=n - segment_len {
+                if is_past(deadline) {
+                    return improved_any;
+                }
+                let end = start + segment_len - 1;
+                let (first, last) = (tour[start], tour[end]);
+
+                // the length saved by cutting the segment out, and joining its neighbours
+                let mut removal_gain = 0.;
+                if start > 0 {
+                    removal_gain += distance(points, tour[start - 1], first);
+                }
+                if end + 1 < n {
+                    removal_gain += distance(points, last, tour[end + 1]);
+                }
+                if start > 0 && end + 1 < n {
+                    removal_gain -= distance(points, tour[start - 1], tour[end + 1]);
+                }
+
+                // try inserting the segment between every other pair of neighbours
+                for j in 0..n - 1 {
+                    if j + 1 >= start && j <= end {
+                        continue;
+                    }
+                    if *budget == 0 {
+                        return improved_any;
+                    }
+                    *budget -= 1;
+
+                    let (p, q) = (tour[j], tour[j + 1]);
+                    let forwards = distance(points, p, first) + distance(points, last, q);
+                    let backwards = distance(points, p, last) + distance(points, first, q);
+                    let insertion_cost = forwards.min(backwards) - distance(points, p, q);
+
+                    if removal_gain - insertion_cost > 1e-6 {
+                        let mut segment: Vec<usize> = tour.drain(start..=end).collect();
+                        if backwards < forwards {
+                            segment.reverse();
+                        }
+
+                        // the insertion point shifts back if it was after the removed segment
+                        let insert_at = if j > end { j + 1 - segment_len } else { j + 1 };
+                        tour.splice(insert_at..insert_at, segment);
+
+                        improved = true;
+                        improved_any = true;
+                        break 'search;
+                    }
+                }
+            }
+        }
+    }
+
+    improved_any
+}
+
+///
+/// # Parameters:
+/// - `max_time`: How long from now until the deadline
+///
+/// # Returns:
+/// - The deadline, or None if there is no clock to check it against, or it is too far away to
+///   represent
+///
+#[cfg(not(target_arch = "wasm32"))]
+fn deadline_after(max_time: Duration) -> Option<Instant> {
+    Instant::now().checked_add(max_time)
+}
+
+///
+/// `Instant::now` panics in the browser, so there is never a deadline there.
+///
+#[cfg(target_arch = "wasm32")]
+fn deadline_after(_max_time: Duration) -> Option<Instant> {
+    None
+}
+
+///
+/// # Returns:
+/// - Whether the deadline has passed
+///
+fn is_past(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+///
+/// # Returns:
+/// - The total length of an open tour
+///
+pub fn tour_length(points: &[Point], tour: &[usize]) -> f64 {
+    tour.windows(2).map(|pair| distance(points, pair[0], pair[1])).sum()
+}
+
+///
+/// # Returns:
+/// - The euclidean distance between two points, by index
+///
+fn distance(points: &[Point], a: usize, b: usize) -> f64 {
+    points[a].distance(&points[b]) as f64
+}
+
+///
+/// Tests relating to tour improvement.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn random_points(rng: &mut StdRng, num_points: usize) -> Vec<Point> {
+        (0..num_points).map(|_| Point::new(rng.random_range(0. ..100.), rng.random_range(0. ..100.))).collect()
+    }
+
+    #[test]
+    fn improved_tours_are_shorter_permutations() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for num_points in [0, 1, 2, 3, 4, 5, 40, 200] {
+            let points = random_points(&mut rng, num_points);
+            let original: Vec<usize> = (0..num_points).collect();
+
+            for max_moves in [0, 100, DEFAULT_MOVE_BUDGET] {
+                let mut tour = original.clone();
+                improve_tour(&points, &mut tour, DEFAULT_TIME_BUDGET, max_moves);
+
+                let mut visited = tour.clone();
+                visited.sort_unstable();
+                assert_eq!(visited, original, "{} points, {} moves", num_points, max_moves);
+                assert!(tour_length(&points, &tour) <= tour_length(&points, &original) + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn improvement_is_deterministic() {
+        let points = random_points(&mut StdRng::seed_from_u64(5), 300);
+        let (mut first, mut second): (Vec<usize>, Vec<usize>) = ((0..300).collect(), (0..300).collect());
+        improve_tour(&points, &mut first, Duration::MAX, 200_000);
+        improve_tour(&points, &mut second, Duration::MAX, 200_000);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn improvement_stops_at_the_deadline() {
+        let points = random_points(&mut StdRng::seed_from_u64(7), 3000);
+        let original: Vec<usize> = (0..3000).collect();
+
+        let mut tour = original.clone();
+        improve_tour(&points, &mut tour, Duration::ZERO, usize::MAX);
+        assert_eq!(tour, original);
+
+        // a pass over 3000 points is millions of moves, so the deadline falls within one
+        let mut tour = original.clone();
+        let started = Instant::now();
+        improve_tour(&points, &mut tour, Duration::from_millis(50), usize::MAX);
+        assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+        assert!(tour_length(&points, &tour) < tour_length(&points, &original));
+    }
+
+    #[test]
+    fn or_opt_moves_the_last_point() {
+        // the last point belongs between the first two, which only a segment at the end finds
+        let points = [Point::new(0., 0.), Point::new(2., 0.), Point::new(3., 0.), Point::new(1., 0.)];
+        let mut tour = vec![0, 1, 2, 3];
+        let mut budget = usize::MAX;
+        assert!(or_opt(&points, &mut tour, None, &mut budget));
+        assert_eq!(tour_length(&points, &tour), 3.);
+    }
+}
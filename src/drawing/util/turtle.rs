@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+///
+/// The most commands an L-system may expand to, which bounds the memory and drawing time of
+/// deep iterations, since the length of most systems grows exponentially.
+///
+pub const MAX_COMMANDS: usize = 1_000_000;
+
+///
+/// A Lindenmayer system, a string rewritten by replacing every symbol with its rule on each
+/// iteration. The expanded string is drawn by the turtle.
+///
+/// # Fields:
+/// - `axiom`: The string the system starts from
+/// - `rules`: The string each symbol is replaced with. Symbols without a rule are kept as they are
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LSystem {
+    pub axiom: String,
+    pub rules: BTreeMap<char, String>,
+}
+
+impl LSystem {
+    ///
+    /// Rewrites the axiom a number of times.
+    ///
+    /// # Parameters:
+    /// - `iterations`: The number of times every symbol is replaced
+    ///
+    /// # Returns:
+    /// - The expanded string of turtle commands
+    /// - An error if the expansion would be longer than `MAX_COMMANDS`
+    ///
+    pub fn expand(&self, iterations: usize) -> Result<String, String> {
+        let mut commands = self.axiom.clone();
+        for iteration in 0..iterations {
+            let length: usize = commands.chars().map(|symbol| self.rules.get(&symbol).map_or(1, |rule| rule.chars().count())).sum();
+            if length > MAX_COMMANDS {
+                return Err(format!("The curve has too much detail after {} iterations, try {} or fewer", iterations, iteration));
+            }
+
+            let mut next = String::with_capacity(length);
+            for symbol in commands.chars() {
+                match self.rules.get(&symbol) {
+                    Some(rule) => next.push_str(rule),
+                    None => next.push(symbol),
+                }
+            }
+            commands = next;
+        }
+        Ok(commands)
+    }
+}
+
+///
+/// Draws a string of turtle commands, as a turtle taking unit steps across the page. The turtle
+/// starts at the origin facing right, and understands:
+/// - Any symbol in `draw_symbols`: Step forward with the pen down
+/// - `f`: Step forward with the pen up
+/// - `+` and `-`: Turn clockwise and anticlockwise on the page, by the turn angle
+/// - `|`: Turn around
+/// - `[` and `]`: Save the position and heading, and return to the last one saved
+///
+/// Any other symbol is ignored, so rules may use symbols which only steer the rewriting.
+///
+/// # Parameters:
+/// - `commands`: The turtle commands, such as an expanded `LSystem`
+/// - `turn_degrees`: The angle turned by `+` and `-`, in degrees
+/// - `draw_symbols`: The symbols which step forward with the pen down
+///
+/// # Returns:
+/// - The polylines drawn, in unit steps, in the order they are drawn
+/// - An error if a `]` has no matching `[`
+///
+pub fn interpret(commands: &str, turn_degrees: f64, draw_symbols: &str) -> Result<Vec<Vec<(f64, f64)>>, String> {
+    let turn = turn_degrees.to_radians();
+    let mut position = (0., 0.);
+    let mut heading: f64 = 0.;
+    let mut stack = vec![];
+
+    let mut polylines = vec![];
+    let mut polyline = vec![position];
+    // ends the current polyline, when the turtle moves without drawing
+    let lift = |polyline: &mut Vec<(f64, f64)>, polylines: &mut Vec<Vec<(f64, f64)>>, position: (f64, f64)| {
+        if polyline.len() > 1 {
+            polylines.push(std::mem::take(polyline));
+        }
+        *polyline = vec![position];
+    };
+
+    for symbol in commands.chars() {
+        match symbol {
+            'f' => {
+                position = (position.0 + heading.cos(), position.1 + heading.sin());
+                lift(&mut polyline, &mut polylines, position);
+            },
+            '+' => heading += turn,
+            '-' => heading -= turn,
+            '|' => heading += std::f64::consts::PI,
+            '[' => stack.push((position, heading)),
+            ']' => {
+                (position, heading) = stack.pop().ok_or("The turtle commands have a ] without a matching [")?;
+                lift(&mut polyline, &mut polylines, position);
+            },
+            symbol if draw_symbols.contains(symbol) => {
+                position = (position.0 + heading.cos(), position.1 + heading.sin());
+                polyline.push(position);
+            },
+            _ => {},
+        }
+    }
+    lift(&mut polyline, &mut polylines, position);
+
+    Ok(polylines)
+}
+
+///
+/// # Returns:
+/// - The (min_x, min_y, max_x, max_y) of every point of the polylines, or None if there are none
+///
+pub fn bounds(polylines: &[Vec<(f64, f64)>]) -> Option<(f64, f64, f64, f64)> {
+    polylines.iter().flatten().fold(None, |bounds, &(x, y)| match bounds {
+        None => Some((x, y, x, y)),
+        Some((min_x, min_y, max_x, max_y)) => Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))),
+    })
+}
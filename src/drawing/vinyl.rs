@@ -2,8 +2,10 @@ use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 use super::util::audio;
+use super::util::placement::{Margins, Placement};
 
 ///
 /// An empty struct to implement the "Vinyl" draw method on.
@@ -30,7 +32,15 @@ impl DrawMethod for VinylMethod {
     }
 
     ///
-    /// Generates instructions to perform the vinyl drawing method.
+    /// # Returns:
+    /// - The parameters a new vinyl drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        VinylParameters { audio_path: String::new(), placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() }, num_samples: 2000 }
+    }
+
+    ///
+    /// Generates the strokes to perform the vinyl drawing method.
     /// This drawing method generates a visualisation of an audio file and draws the audio
     /// waveforms on a sheet of paper.
     /// 
@@ -39,14 +49,15 @@ impl DrawMethod for VinylMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &VinylParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &VinylParameters) -> Result<Vec<Stroke>, String> {
         
-        let offset_left = (physical_dimensions.page_width() - parameters.width) / 2.;
-        let offset_top = (physical_dimensions.page_height() - parameters.height) / 2.;
-        let sample_spacing = parameters.width / parameters.num_samples as f64;
+        // the waveform has no shape of its own, so fills the area inside the margins
+        let area = parameters.placement.area(physical_dimensions)?;
+        let (offset_left, offset_top) = (area.x, area.y);
+        let sample_spacing = area.width / parameters.num_samples as f64;
 
         if parameters.audio_path.is_empty() {
             return Err(format!("Select an audio file"));
@@ -58,20 +69,20 @@ impl DrawMethod for VinylMethod {
                 return Err(format!("Couldn't open audio file: {}", err.to_string()).to_string());
             }
         };
-        let max = sample_heights.iter().max().unwrap();
-        let scalar = parameters.height / (*max as f64);
+        let max = sample_heights.iter().copied().max().unwrap_or(0).max(1); // silent audio would divide by zero
+        let scalar = area.height / (max as f64);
 
-        let mut surface = DrawSurface::new(physical_dimensions);
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, 1 + parameters.num_samples * 2);
         surface.sample_xy(offset_left, offset_top).unwrap();
         
         for sample_num in 0..parameters.num_samples {
-            surface.sample_xy(offset_left + sample_num as f64 * sample_spacing, offset_top + (parameters.height / 2.) - ((sample_heights[sample_num] as f64)) * scalar).unwrap();
+            surface.sample_xy(offset_left + sample_num as f64 * sample_spacing, offset_top + (area.height / 2.) - ((sample_heights[sample_num] as f64)) * scalar).unwrap();
             surface.raise_pen(false);
-            surface.sample_xy(offset_left + sample_num as f64 * sample_spacing, offset_top + (parameters.height / 2.) + ((sample_heights[sample_num] as f64)) * scalar).unwrap();
+            surface.sample_xy(offset_left + sample_num as f64 * sample_spacing, offset_top + (area.height / 2.) + ((sample_heights[sample_num] as f64)) * scalar).unwrap();
             surface.raise_pen(true);
         }
         
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -81,16 +92,15 @@ impl DrawMethod for VinylMethod {
 ///
 /// # Fields:
 /// - `audio_path`: The path of the audio file
-/// - `width`: The horizontal margin of the drawing, in millimetres
-/// - `height`: The horizontal margin of the drawing, in millimetres
+/// - `placement`: The margins of the drawing, which fills the area inside them
 /// - `num_samples`: The number of samples to take on the audio waveform
 ///
 #[derive(Serialize, Deserialize)]
 pub struct VinylParameters {
     audio_path: String,
 
-    width: f64,
-    height: f64,
+    #[serde(default)]
+    placement: Placement,
 
     num_samples: usize,
 }
@@ -1,8 +1,12 @@
 use crate::drawing::{DrawMethod, DrawParameters};
 use crate::hardware::PhysicalDimensions;
-use image::{GrayImage, ImageReader, Luma};
+use crate::drawing::util::files;
+use crate::drawing::util::tone::ToneCurve;
+use crate::drawing::util::placement::{Margins, Placement};
+use image::{GrayImage, Luma};
 use serde::{Serialize, Deserialize};
 use crate::drawing::DrawSurface;
+use crate::drawing::stroke::Stroke;
 
 ///
 /// An empty struct to implement the "Waves" draw method on.
@@ -29,7 +33,18 @@ impl DrawMethod for WavesMethod {
     }
 
     ///
-    /// Generates instructions to perform the waves drawing method.
+    /// # Returns:
+    /// - The parameters a new waves drawing starts from, for frontends to offer before the user changes them
+    ///
+    fn default_parameters(&self) -> Self::DrawParameters {
+        WavesParameters {
+            image_path: String::new(), num_waves: 40, horizontal_samples: 200, placement: Placement { margins: Margins::symmetric(20., 20.), ..Default::default() },
+            wave_amplifier: 10., tone_curve: ToneCurve::default(),
+        }
+    }
+
+    ///
+    /// Generates the strokes to perform the waves drawing method.
     /// This drawing method generates layers of sine waves, which are more intense
     /// in darker areas of the input image.
     ///
@@ -38,48 +53,43 @@ impl DrawMethod for WavesMethod {
     /// - `parameters`: The user-configured parameters to adjust the drawing style
     ///
     /// # Returns:
-    /// - An (instruction set, start_x, start_y), represented as a u8 vector and floats respectively
+    /// - The strokes of the drawing, in the order they are drawn
     /// - An error, explaning why the drawing instructions could not be created
     ///
-    fn gen_instructions(&self, physical_dimensions: &PhysicalDimensions, parameters: &WavesParameters) -> Result<(Vec<u8>, f64, f64), String> {
+    fn gen_strokes(&self, physical_dimensions: &PhysicalDimensions, parameters: &WavesParameters) -> Result<Vec<Stroke>, String> {
         
         if parameters.image_path.is_empty() {
             return Err("Select an input image".to_owned());
         }
 
-        let input_image = match ImageReader::open(parameters.image_path.as_str()) {
-            Ok(img) => {
-                img.decode().unwrap().into_rgb8()
-            },
-            Err(err) => {
-                return Err(format!("Error loading image. {}", err.to_string()).to_owned());
-            }
+        let input_image = match files::read(parameters.image_path.as_str()).map(|bytes| image::load_from_memory(&bytes)) {
+            Ok(Ok(img)) => img.into_rgb8(),
+            Ok(Err(err)) => return Err(format!("Error decoding image. {}", err)),
+            Err(err) => return Err(format!("Error loading image. {}", err)),
         };
-        let mut surface = DrawSurface::new(physical_dimensions);
+        let mut surface = DrawSurface::with_estimated_samples(physical_dimensions, parameters.num_waves * parameters.horizontal_samples * 10);
         surface.raise_pen(false);
 
-        let temp_max_width = physical_dimensions.page_width() - (parameters.horizontal_margin as f64 * 2.);
-        let temp_max_height = physical_dimensions.page_height() - (parameters.vertical_margin as f64 * 2.);
-        let divisor = 1. / (temp_max_width / input_image.width() as f64).min(temp_max_height / input_image.height() as f64);
+        let placed = parameters.placement.place(physical_dimensions, input_image.width() as f64, input_image.height() as f64)?;
+        // when the image covers the area, only the part inside the margins is drawn
+        let visible = placed.intersect(&parameters.placement.area(physical_dimensions)?);
+        let (crop_x, crop_width) = ((visible.x - placed.x) / placed.width, visible.width / placed.width);
+        let (crop_y, crop_height) = ((visible.y - placed.y) / placed.height, visible.height / placed.height);
 
-        let total_width = input_image.width() as f64 / divisor;
-        let total_height = input_image.height() as f64 / divisor;
-
-        let height_per_wave = total_height / parameters.num_waves as f64;
-        let mm_per_x_sample = total_width / parameters.horizontal_samples as f64;
+        let height_per_wave = visible.height / parameters.num_waves as f64;
+        let mm_per_x_sample = visible.width / parameters.horizontal_samples as f64;
         let wave_multiplier = parameters.wave_amplifier / 10.;
 
-        let true_horizontal_margin = (*physical_dimensions.page_width() - total_width) / 2.;
-        let true_vertical_margin = (*physical_dimensions.page_height() - total_height) / 2.;
+        let tone_table = parameters.tone_curve.table()?;
 
         // we will approximate the image to the dedicated size + make it greyscale
         let mut processed_img = GrayImage::new(parameters.horizontal_samples as u32, parameters.num_waves as u32);
         for x in 0..parameters.horizontal_samples {
             for y in 0..parameters.num_waves {
 
-                let pix = input_image.get_pixel(((input_image.width() as f64 * (x as f64 / parameters.horizontal_samples as f64)).round() as u32).min(input_image.width() - 1) as u32, (((input_image.height() as f64 * (y as f64 / parameters.num_waves as f64)).round() as u32).min(input_image.height() - 1)) as u32).0;
+                let pix = input_image.get_pixel(((input_image.width() as f64 * (crop_x + crop_width * (x as f64 / parameters.horizontal_samples as f64))).round() as u32).min(input_image.width() - 1) as u32, (((input_image.height() as f64 * (crop_y + crop_height * (y as f64 / parameters.num_waves as f64))).round() as u32).min(input_image.height() - 1)) as u32).0;
                 let mean = ((pix[0] as f32 * 0.299 + pix[1] as f32 * 0.587 + pix[2] as f32 * 0.114)).round() as u8;
-                *processed_img.get_pixel_mut(x as u32, y as u32) = Luma([mean as u8]);
+                *processed_img.get_pixel_mut(x as u32, y as u32) = Luma([tone_table[mean as usize]]);
 
             }
         }
@@ -92,10 +102,10 @@ impl DrawMethod for WavesMethod {
                 let iterations = 10;
                 let step_x = mm_per_x_sample / iterations as f64;
                 let start_x = match is_reversed {
-                    false => true_horizontal_margin + sample_idx as f64 * mm_per_x_sample,
-                    true => *physical_dimensions.page_width() as f64 - true_horizontal_margin - (sample_idx as f64 * mm_per_x_sample),
+                    false => visible.x + sample_idx as f64 * mm_per_x_sample,
+                    true => visible.x + visible.width - (sample_idx as f64 * mm_per_x_sample),
                 };
-                let start_y = true_vertical_margin as f64 + row_idx as f64 * height_per_wave + 0.5 * height_per_wave;
+                let start_y = visible.y + row_idx as f64 * height_per_wave + 0.5 * height_per_wave;
 
                 let intensity = 1. - (processed_img.get_pixel(if is_reversed { parameters.horizontal_samples - sample_idx - 1 } else { sample_idx } as u32, row_idx as u32).0[0] as f64) / 255.;
 
@@ -111,7 +121,7 @@ impl DrawMethod for WavesMethod {
         }
         
         
-        Ok((surface.current_ins, surface.first_sample_x.unwrap_or(0.), surface.first_sample_y.unwrap_or(0.)))
+        Ok(surface.into_strokes())
     }
 }
 
@@ -123,9 +133,9 @@ impl DrawMethod for WavesMethod {
 /// - `image_path`: The path of the image to stipple
 /// - `num_waves`: The number of sine waves to layer
 /// - `horizontal_samples`: The number of horizontal samples (individual waves) taken
-/// - `horizontal_margin`: The horizontal margin of the drawing, in millimetres
-/// - `vertical_margin`: The vertical margin of the drawing, in millimetres
+/// - `placement`: Where the image is placed on the page
 /// - `wave_amplifier`: A coefficient for the height of the sine waves
+/// - `tone_curve`: The curve the brightness of the image is remapped through
 ///
 #[derive(Serialize, Deserialize)]
 pub struct WavesParameters {
@@ -134,10 +144,13 @@ pub struct WavesParameters {
     pub num_waves: usize,
     pub horizontal_samples: usize,
 
-    pub horizontal_margin: u32,
-    pub vertical_margin: u32,
+    #[serde(default)]
+    pub placement: Placement,
 
     pub wave_amplifier: f64,
+
+    #[serde(default)]
+    pub tone_curve: ToneCurve,
 }
 
 impl DrawParameters for WavesParameters {}
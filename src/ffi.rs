@@ -0,0 +1,442 @@
+//!
+//! C-compatible bindings, so non-Rust frontends (C++, Swift) can embed the library
+//!
+//! The header is generated into `include/bbcore.h` when building with the `ffi` feature.
+//! Functions returning an `int32_t` return 0 on success and -1 on failure, after which
+//! `bb_last_error` describes the failure. Strings are null-terminated UTF-8, and physical
+//! dimensions are passed as the contents of a TOML machine configuration.
+//!
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+#[cfg(feature = "async")]
+use std::ffi::c_void;
+#[cfg(feature = "async")]
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use tokio::sync::Notify;
+
+use crate::drawing::registry;
+use crate::hardware::PhysicalDimensions;
+use crate::instruction::InstructionSet;
+#[cfg(feature = "async")]
+use crate::client::schedule::{PausePoint, PauseSchedule};
+#[cfg(feature = "async")]
+use crate::pipeline::DrawingJob;
+
+thread_local! {
+    /// The error message of the last failed call on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+///
+/// A generated drawing, owned by the caller until it is passed to `bb_drawing_free`.
+///
+/// # Fields:
+/// - `instruction_set`: The validated drawing instructions
+///
+pub struct BbDrawing {
+    instruction_set: InstructionSet,
+}
+
+///
+/// Where to pause a drawing for the user, owned by the caller until it is passed to
+/// `bb_pauses_free`.
+///
+/// # Fields:
+/// - `schedule`: The pause points
+/// - `resume`: Notified by `bb_pauses_resume` to resume the drawing after a pause
+///
+#[cfg(feature = "async")]
+pub struct BbPauses {
+    schedule: PauseSchedule,
+    resume: Arc<Notify>,
+}
+
+///
+/// An RGBA image, owned by the caller until it is passed to `bb_image_free`.
+///
+/// # Fields:
+/// - `pixels`: The pixels, row by row, 4 bytes per pixel
+/// - `length`: The number of bytes in `pixels`
+/// - `width`: The width of the image, in pixels
+/// - `height`: The height of the image, in pixels
+///
+#[cfg(feature = "image")]
+#[repr(C)]
+pub struct BbImage {
+    pub pixels: *mut u8,
+    pub length: usize,
+    pub width: u32,
+    pub height: u32,
+}
+
+///
+/// A callback receiving the progress of a drawing, as a JSON object with an "event" key. The
+/// string is only valid until the callback returns.
+///
+#[cfg(feature = "async")]
+pub type BbEventCallback = Option<unsafe extern "C" fn(event_json: *const c_char, user_data: *mut c_void)>;
+
+///
+/// # Returns:
+/// - The error message of the last failed call on this thread, or null if there was none. The
+///   string is valid until the next failed call on this thread
+///
+#[unsafe(no_mangle)]
+pub extern "C" fn bb_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ref().map_or(std::ptr::null(), |err| err.as_ptr()))
+}
+
+///
+/// Generates a drawing with a built-in drawing method.
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+/// - `parameters_json`: The parameters of the drawing method, as a JSON object
+/// - `dimensions_toml`: The machine configuration, as TOML
+///
+/// # Returns:
+/// - The drawing, or null if it could not be generated
+///
+/// # Safety
+/// Every string must be a valid, null-terminated string.
+///
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_generate(method_id: *const c_char, parameters_json: *const c_char, dimensions_toml: *const c_char) -> *mut BbDrawing {
+    let result = (|| {
+        let method_id = unsafe { read_str(method_id, "method_id") }?;
+        let parameters_json = unsafe { read_str(parameters_json, "parameters_json") }?;
+        let physical_dimensions = unsafe { read_dimensions(dimensions_toml) }?;
+
+        let (ins_bytes, start_x, start_y) = registry::gen_instructions(method_id, &physical_dimensions, parameters_json)?;
+        InstructionSet::new(ins_bytes, start_x, start_y).map_err(|err| err.to_string())
+    })();
+
+    match result {
+        Ok(instruction_set) => Box::into_raw(Box::new(BbDrawing { instruction_set })),
+        Err(err) => {
+            set_last_error(err);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+///
+/// Frees a drawing.
+///
+/// # Parameters:
+/// - `drawing`: The drawing to free, which may be null
+///
+/// # Safety
+/// The drawing must have come from `bb_generate`, and must not be used afterwards.
+///
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_drawing_free(drawing: *mut BbDrawing) {
+    if !drawing.is_null() {
+        drop(unsafe { Box::from_raw(drawing) });
+    }
+}
+
+///
+/// # Parameters:
+/// - `drawing`: The drawing
+/// - `length`: Set to the number of instruction bytes
+///
+/// # Returns:
+/// - The instruction bytes of the drawing, valid until the drawing is freed
+///
+/// # Safety
+/// The drawing and length must be valid pointers.
+///
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_drawing_bytes(drawing: *const BbDrawing, length: *mut usize) -> *const u8 {
+    let binary = unsafe { (*drawing).instruction_set.get_binary() };
+    unsafe { *length = binary.len() };
+    binary.as_ptr()
+}
+
+///
+/// # Parameters:
+/// - `drawing`: The drawing
+/// - `x`: Set to the initial x position of the pen, relative to the top left of the page
+/// - `y`: Set to the initial y position of the pen, relative to the top left of the page
+///
+/// # Safety
+/// Every pointer must be valid.
+///
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_drawing_start(drawing: *const BbDrawing, x: *mut f64, y: *mut f64) {
+    let (start_x, start_y) = unsafe { (*drawing).instruction_set.get_init() };
+    unsafe {
+        *x = start_x;
+        *y = start_y;
+    }
+}
+
+///
+/// Renders the preview of a drawing into an RGBA buffer.
+///
+/// # Parameters:
+/// - `drawing`: The drawing
+/// - `dimensions_toml`: The machine configuration, as TOML
+/// - `scale`: The number of pixels per millimetre of paper
+/// - `image`: Set to the rendered image, which must be freed with `bb_image_free`
+///
+/// # Returns:
+/// - 0 if the preview was rendered, else -1
+///
+/// # Safety
+/// Every pointer must be valid, and the string null-terminated.
+///
+#[cfg(feature = "image")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_render_preview(drawing: *const BbDrawing, dimensions_toml: *const c_char, scale: u32, image: *mut BbImage) -> i32 {
+    use crate::preview::{render_preview, PreviewOptions};
+
+    let result = (|| {
+        let instruction_set = unsafe { &(*drawing).instruction_set };
+        let physical_dimensions = unsafe { read_dimensions(dimensions_toml) }?;
+
+        let options = PreviewOptions { scale, ..Default::default() };
+        render_preview(instruction_set.get_init(), &physical_dimensions, instruction_set, &options).map_err(|err| err.to_string())
+    })();
+
+    match result {
+        Ok(preview_canvas) => {
+            let (width, height) = preview_canvas.buffer.dimensions();
+            let pixels = preview_canvas.buffer.into_raw().into_boxed_slice();
+            let length = pixels.len();
+            unsafe { *image = BbImage { pixels: Box::into_raw(pixels) as *mut u8, length, width, height } };
+            0
+        },
+        Err(err) => {
+            set_last_error(err);
+            -1
+        },
+    }
+}
+
+///
+/// Frees the pixels of an image, and clears it.
+///
+/// # Parameters:
+/// - `image`: The image to free
+///
+/// # Safety
+/// The image must have been set by `bb_render_preview`.
+///
+#[cfg(feature = "image")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_image_free(image: *mut BbImage) {
+    let image = unsafe { &mut *image };
+    if !image.pixels.is_null() {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(image.pixels, image.length)) });
+    }
+    *image = BbImage { pixels: std::ptr::null_mut(), length: 0, width: 0, height: 0 };
+}
+
+///
+/// Draws a drawing on the machine, blocking until it has finished. The pen is moved to the start
+/// of the drawing first.
+///
+/// # Parameters:
+/// - `drawing`: The drawing
+/// - `dimensions_toml`: The machine configuration, as TOML
+/// - `addr`: The IP address of the machine
+/// - `port`: The port address of the machine
+/// - `callback`: An optional callback receiving the progress of the drawing
+/// - `user_data`: Passed to every call of the callback
+///
+/// # Returns:
+/// - 0 if the drawing finished, else -1
+///
+/// # Safety
+/// Every pointer must be valid, and every string null-terminated. The callback is called on
+/// this thread.
+///
+#[cfg(feature = "async")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_draw(drawing: *const BbDrawing, dimensions_toml: *const c_char, addr: *const c_char, port: u16, callback: BbEventCallback, user_data: *mut c_void) -> i32 {
+    unsafe { bb_draw_with_pauses(drawing, dimensions_toml, addr, port, std::ptr::null(), callback, user_data) }
+}
+
+///
+/// Draws a drawing on the machine like `bb_draw`, pausing it for the user. At each pause point,
+/// the callback receives a "waiting_for_user" event, and the drawing carries on once
+/// `bb_pauses_resume` is called from another thread.
+///
+/// # Parameters:
+/// - `drawing`: The drawing
+/// - `dimensions_toml`: The machine configuration, as TOML
+/// - `addr`: The IP address of the machine
+/// - `port`: The port address of the machine
+/// - `pauses`: Where to pause the drawing, or null to draw it without pausing
+/// - `callback`: An optional callback receiving the progress of the drawing
+/// - `user_data`: Passed to every call of the callback
+///
+/// # Returns:
+/// - 0 if the drawing finished, else -1
+///
+/// # Safety
+/// Every pointer must be valid, and every string null-terminated. The pauses must not be freed
+/// until this returns. The callback is called on this thread.
+///
+#[cfg(feature = "async")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_draw_with_pauses(drawing: *const BbDrawing, dimensions_toml: *const c_char, addr: *const c_char, port: u16, pauses: *const BbPauses, callback: BbEventCallback, user_data: *mut c_void) -> i32 {
+    // the callback and its data are only used on this thread, as the job is run with block_on
+    struct CallbackData(*mut c_void);
+    unsafe impl Send for CallbackData {}
+
+    let result = (|| {
+        let instruction_set = unsafe { &(*drawing).instruction_set };
+        let physical_dimensions = unsafe { read_dimensions(dimensions_toml) }?;
+        let addr = unsafe { read_str(addr, "addr") }?;
+
+        let (start_x, start_y) = instruction_set.get_init();
+        let mut job = DrawingJob::new(physical_dimensions).with_instructions(instruction_set.get_binary().clone(), start_x, start_y).with_machine(addr, port);
+        if let Some(pauses) = unsafe { pauses.as_ref() } {
+            job = job.with_pause_schedule(pauses.schedule.clone(), pauses.resume.clone());
+        }
+
+        let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+        let user_data = CallbackData(user_data);
+        runtime.block_on(job.run(move |event| {
+            let user_data = &user_data;
+            if let (Some(callback), Ok(event_json)) = (callback, serde_json::to_string(&event)) {
+                let event_json = CString::new(event_json).unwrap_or_default();
+                unsafe { callback(event_json.as_ptr(), user_data.0) };
+            }
+        })).map(|_| ()).map_err(|err| err.to_string())
+    })();
+
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            set_last_error(err);
+            -1
+        },
+    }
+}
+
+///
+/// # Returns:
+/// - New pauses with no pause points, to pass to `bb_draw_with_pauses`
+///
+#[cfg(feature = "async")]
+#[unsafe(no_mangle)]
+pub extern "C" fn bb_pauses_new() -> *mut BbPauses {
+    Box::into_raw(Box::new(BbPauses { schedule: PauseSchedule::new(), resume: Arc::new(Notify::new()) }))
+}
+
+///
+/// Pauses the drawing before an instruction.
+///
+/// # Parameters:
+/// - `pauses`: The pauses
+/// - `instruction_idx`: The index of the instruction
+///
+/// # Safety
+/// The pauses must have come from `bb_pauses_new`, and not be in use by `bb_draw_with_pauses`.
+///
+#[cfg(feature = "async")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_pauses_add_instruction(pauses: *mut BbPauses, instruction_idx: usize) {
+    let pauses = unsafe { &mut *pauses };
+    pauses.schedule = std::mem::take(&mut pauses.schedule).with_pause(PausePoint::Instruction(instruction_idx));
+}
+
+///
+/// Pauses the drawing once it has been drawing for a time.
+///
+/// # Parameters:
+/// - `pauses`: The pauses
+/// - `minutes`: The time since the drawing started, in minutes
+///
+/// # Safety
+/// The pauses must have come from `bb_pauses_new`, and not be in use by `bb_draw_with_pauses`.
+///
+#[cfg(feature = "async")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_pauses_add_minutes(pauses: *mut BbPauses, minutes: f64) {
+    let pauses = unsafe { &mut *pauses };
+    pauses.schedule = std::mem::take(&mut pauses.schedule).with_pause(PausePoint::AfterMinutes(minutes));
+}
+
+///
+/// Resumes a drawing paused by `bb_draw_with_pauses`. It may be called from any thread.
+///
+/// # Parameters:
+/// - `pauses`: The pauses the drawing was given
+///
+/// # Safety
+/// The pauses must have come from `bb_pauses_new`.
+///
+#[cfg(feature = "async")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_pauses_resume(pauses: *const BbPauses) {
+    unsafe { &*pauses }.resume.notify_one();
+}
+
+///
+/// Frees pauses.
+///
+/// # Parameters:
+/// - `pauses`: The pauses to free, which may be null
+///
+/// # Safety
+/// The pauses must have come from `bb_pauses_new`, and must not be used afterwards.
+///
+#[cfg(feature = "async")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bb_pauses_free(pauses: *mut BbPauses) {
+    if !pauses.is_null() {
+        drop(unsafe { Box::from_raw(pauses) });
+    }
+}
+
+///
+/// Stores the error message of a failed call, for `bb_last_error`.
+///
+/// # Parameters:
+/// - `err`: The error message
+///
+fn set_last_error(err: String) {
+    let err = CString::new(err).unwrap_or_else(|_| CString::from(c"The error message contained a null byte"));
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(err));
+}
+
+///
+/// Borrows a string passed over the FFI.
+///
+/// # Parameters:
+/// - `ptr`: The null-terminated string
+/// - `name`: The name of the parameter, for the error message
+///
+/// # Returns:
+/// - The string
+/// - An error if the pointer was null or the string was not UTF-8
+///
+unsafe fn read_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, String> {
+    if ptr.is_null() {
+        return Err(format!("{} must not be null", name));
+    }
+
+    unsafe { CStr::from_ptr(ptr) }.to_str().map_err(|_| format!("{} must be UTF-8", name))
+}
+
+///
+/// Parses the physical dimensions passed over the FFI.
+///
+/// # Parameters:
+/// - `dimensions_toml`: The null-terminated TOML machine configuration
+///
+/// # Returns:
+/// - The physical dimensions
+/// - An error if the configuration could not be read, parsed or validated
+///
+unsafe fn read_dimensions(dimensions_toml: *const c_char) -> Result<PhysicalDimensions, String> {
+    let contents = unsafe { read_str(dimensions_toml, "dimensions_toml") }?;
+    PhysicalDimensions::from_toml_str(contents).map_err(|err| err.to_string())
+}
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+
+use super::error::{HardwareError, KinematicsError};
+use super::kinematics::Kinematics;
+
+///
+/// A model of where a machine actually places the pen, compared to where it was asked to. It is
+/// fitted from a plotted reference grid, by measuring each point's actual position on the page.
+/// The machine then aims for the corrected point, so belt stretch and mounting errors cancel out.
+/// All positions are in millimetres, relative to the top left of the page.
+///
+/// - `Affine`: A single linear correction for the whole page, fixing scale, skew and offset errors
+///     Parameters:
+///     - `matrix`: The coefficients `[a, b, c, d, e, f]`, mapping (x, y) to (ax + by + c, dx + ey + f)
+/// - `Mesh`: A bilinear interpolated grid of errors, which also fixes lines that bow
+///     Parameters:
+///     - `origin`: The intended position of the first grid point
+///     - `spacing`: The horizontal and vertical distance between intended grid points
+///     - `columns`: The number of grid points in each row
+///     - `rows`: The number of grid points in each column
+///     - `offsets`: The measured minus intended position of each grid point, row by row
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum CalibrationMap {
+    Affine { matrix: [f64; 6] },
+    Mesh { origin: (f64, f64), spacing: (f64, f64), columns: usize, rows: usize, offsets: Vec<(f64, f64)> },
+}
+
+impl CalibrationMap {
+    ///
+    /// Fits an affine map to a set of reference points, by least squares.
+    ///
+    /// # Parameters:
+    /// - `intended`: The positions the machine was asked to draw each point at
+    /// - `measured`: The positions each point was actually drawn at
+    ///
+    /// # Returns:
+    /// - The fitted `CalibrationMap`
+    /// - `HardwareError::CalibrationFailed` if there are too few points, or they are all in a line
+    ///
+    pub fn fit_affine(intended: &[(f64, f64)], measured: &[(f64, f64)]) -> Result<CalibrationMap, HardwareError> {
+        if intended.len() != measured.len() || intended.len() < 3 {
+            return Err(HardwareError::CalibrationFailed { reason: "an affine fit needs at least 3 intended and measured point pairs".to_owned() });
+        }
+
+        // normal equations, (A^T A) p = A^T b, where each row of A is [x, y, 1]
+        let mut ata = [[0.; 3]; 3];
+        let mut atb_x = [0.; 3];
+        let mut atb_y = [0.; 3];
+        for ((x, y), (measured_x, measured_y)) in intended.iter().zip(measured) {
+            let row = [*x, *y, 1.];
+            for i in 0..3 {
+                for j in 0..3 {
+                    ata[i][j] += row[i] * row[j];
+                }
+                atb_x[i] += row[i] * measured_x;
+                atb_y[i] += row[i] * measured_y;
+            }
+        }
+
+        let (Some([a, b, c]), Some([d, e, f])) = (solve_3x3(ata, atb_x), solve_3x3(ata, atb_y)) else {
+            return Err(HardwareError::CalibrationFailed { reason: "the reference points must not all lie in a line".to_owned() });
+        };
+        if (a * e - b * d).abs() < 1e-12 {
+            return Err(HardwareError::CalibrationFailed { reason: "the measured points collapse into a line".to_owned() });
+        }
+
+        Ok(CalibrationMap::Affine { matrix: [a, b, c, d, e, f] })
+    }
+
+    ///
+    /// Fits a mesh to a grid of reference points, such as those from `reference_grid`.
+    ///
+    /// # Parameters:
+    /// - `origin`: The intended position of the first grid point
+    /// - `spacing`: The horizontal and vertical distance between intended grid points
+    /// - `columns`: The number of grid points in each row
+    /// - `rows`: The number of grid points in each column
+    /// - `measured`: The positions each grid point was actually drawn at, row by row
+    ///
+    /// # Returns:
+    /// - The fitted `CalibrationMap`
+    /// - `HardwareError::CalibrationFailed` if the grid is too small, or a measurement is missing
+    ///
+    pub fn fit_mesh(origin: (f64, f64), spacing: (f64, f64), columns: usize, rows: usize, measured: &[(f64, f64)]) -> Result<CalibrationMap, HardwareError> {
+        if columns < 2 || rows < 2 || spacing.0 <= 0. || spacing.1 <= 0. {
+            return Err(HardwareError::CalibrationFailed { reason: "a mesh needs at least 2 rows and columns, with a positive spacing".to_owned() });
+        }
+        if measured.len() != columns * rows {
+            return Err(HardwareError::CalibrationFailed { reason: format!("expected {} measured points, but got {}", columns * rows, measured.len()) });
+        }
+
+        let offsets = reference_grid(origin, spacing, columns, rows).iter().zip(measured)
+            .map(|((x, y), (measured_x, measured_y))| (measured_x - x, measured_y - y))
+            .collect();
+
+        Ok(CalibrationMap::Mesh { origin, spacing, columns, rows, offsets })
+    }
+
+    ///
+    /// Predicts where the machine actually draws a point.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The position the machine is asked to draw at
+    ///
+    /// # Returns:
+    /// - The position the point is actually drawn at
+    ///
+    pub fn distort(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            CalibrationMap::Affine { matrix: [a, b, c, d, e, f] } => (a * x + b * y + c, d * x + e * y + f),
+            CalibrationMap::Mesh { .. } => {
+                let (offset_x, offset_y) = self.mesh_offset(x, y);
+                (x + offset_x, y + offset_y)
+            },
+        }
+    }
+
+    ///
+    /// Calculates where the machine must aim, so a point is actually drawn at the given position.
+    /// This is the inverse of `distort`.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The position the point should be drawn at
+    ///
+    /// # Returns:
+    /// - The position the machine should be asked to draw at
+    ///
+    pub fn correct(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            CalibrationMap::Affine { matrix: [a, b, c, d, e, f] } => {
+                let determinant = a * e - b * d;
+                let (dx, dy) = (x - c, y - f);
+                ((e * dx - b * dy) / determinant, (a * dy - d * dx) / determinant)
+            },
+            CalibrationMap::Mesh { .. } => {
+                // the offsets are small and smooth, so iterating converges quickly
+                let (mut aim_x, mut aim_y) = (x, y);
+                for _ in 0..16 {
+                    let (offset_x, offset_y) = self.mesh_offset(aim_x, aim_y);
+                    (aim_x, aim_y) = (x - offset_x, y - offset_y);
+                }
+                (aim_x, aim_y)
+            },
+        }
+    }
+
+    ///
+    /// Bilinearly interpolates the mesh offsets. Points outside the grid use the nearest edge.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The position to find the offset at
+    ///
+    /// # Returns:
+    /// - The interpolated (x, y) offset, or (0, 0) for an affine map
+    ///
+    fn mesh_offset(&self, x: f64, y: f64) -> (f64, f64) {
+        let CalibrationMap::Mesh { origin, spacing, columns, rows, offsets } = self else {
+            return (0., 0.);
+        };
+
+        let grid_x = ((x - origin.0) / spacing.0).clamp(0., (*columns - 1) as f64);
+        let grid_y = ((y - origin.1) / spacing.1).clamp(0., (*rows - 1) as f64);
+        let column = (grid_x.floor() as usize).min(columns - 2);
+        let row = (grid_y.floor() as usize).min(rows - 2);
+        let (tx, ty) = (grid_x - column as f64, grid_y - row as f64);
+
+        let at = |column: usize, row: usize| offsets[row * columns + column];
+        let lerp = |a: (f64, f64), b: (f64, f64), t: f64| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+        let top = lerp(at(column, row), at(column + 1, row), tx);
+        let bottom = lerp(at(column, row + 1), at(column + 1, row + 1), tx);
+        lerp(top, bottom, ty)
+    }
+}
+
+///
+/// Wraps a machine geometry with a calibration, so every point the machine is asked to draw is
+/// corrected first.
+///
+/// # Fields:
+/// - `inner`: The uncalibrated geometry
+/// - `calibration`: The calibration to apply
+/// - `page_offset`: The position of the top left of the page, relative to the machine origin
+///
+pub struct Calibrated {
+    pub inner: Box<dyn Kinematics + Send + Sync>,
+    pub calibration: CalibrationMap,
+    pub page_offset: (f64, f64),
+}
+
+impl Kinematics for Calibrated {
+    fn cartesian_to_axes(&self, x: f64, y: f64) -> Result<(f64, f64), KinematicsError> {
+        let (aim_x, aim_y) = self.calibration.correct(x - self.page_offset.0, y - self.page_offset.1);
+        self.inner.cartesian_to_axes(aim_x + self.page_offset.0, aim_y + self.page_offset.1)
+    }
+
+    fn axes_to_cartesian(&self, left: f64, right: f64) -> Result<(f64, f64), KinematicsError> {
+        let (aim_x, aim_y) = self.inner.axes_to_cartesian(left, right)?;
+        let (x, y) = self.calibration.distort(aim_x - self.page_offset.0, aim_y - self.page_offset.1);
+        Ok((x + self.page_offset.0, y + self.page_offset.1))
+    }
+}
+
+///
+/// Calculates the intended positions of a reference grid, row by row.
+///
+/// # Parameters:
+/// - `origin`: The position of the first grid point
+/// - `spacing`: The horizontal and vertical distance between grid points
+/// - `columns`: The number of grid points in each row
+/// - `rows`: The number of grid points in each column
+///
+/// # Returns:
+/// - The position of every grid point
+///
+pub fn reference_grid(origin: (f64, f64), spacing: (f64, f64), columns: usize, rows: usize) -> Vec<(f64, f64)> {
+    (0..rows).flat_map(|row| (0..columns).map(move |column| (origin.0 + column as f64 * spacing.0, origin.1 + row as f64 * spacing.1))).collect()
+}
+
+///
+/// Solves a 3x3 system of linear equations, by Cramer's rule.
+///
+/// # Parameters:
+/// - `matrix`: The coefficients of the equations
+/// - `rhs`: The right hand side of the equations
+///
+/// # Returns:
+/// - `None` if the system has no unique solution
+/// - The solution of the system
+///
+fn solve_3x3(matrix: [[f64; 3]; 3], rhs: [f64; 3]) -> Option<[f64; 3]> {
+    let determinant = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let base = determinant(matrix);
+    if base.abs() < 1e-9 {
+        return None;
+    }
+
+    let mut solution = [0.; 3];
+    for (column, value) in solution.iter_mut().enumerate() {
+        let mut replaced = matrix;
+        for row in 0..3 {
+            replaced[row][column] = rhs[row];
+        }
+        *value = determinant(replaced) / base;
+    }
+    Some(solution)
+}
@@ -0,0 +1,94 @@
+use thiserror::Error;
+
+///
+/// All errors emitted from the hardware module.
+/// The error messages can be displayed to users on the frontend.
+///
+/// - `Io`: When a configuration file could not be read or written
+///     Parameters:
+///     - `path`: The path of the configuration file
+///     - `reason`: The underlying error
+/// - `InvalidConfig`: When a configuration file could not be parsed or serialised
+///     Parameters:
+///     - `reason`: The underlying error
+/// - `NonPositiveDimension`: When a measurement which must be positive is zero or negative
+///     Parameters:
+///     - `name`: The name of the measurement
+///     - `value`: The value of the measurement
+/// - `PageOutsideMotors`: When the page does not fit horizontally between the motors
+/// - `InKeepOutZone`: When a point is inside a keep-out zone
+///     Parameters:
+///     - `x`: The x position of the point, relative to the top left of the page
+///     - `y`: The y position of the point, relative to the top left of the page
+/// - `BeltAngleTooShallow`: When a belt is too close to horizontal at a point
+///     Parameters:
+///     - `x`: The x position of the point, relative to the top left of the page
+///     - `y`: The y position of the point, relative to the top left of the page
+///     - `angle`: The angle of the shallowest belt, in degrees
+///     - `min_angle`: The minimum allowed belt angle, in degrees
+/// - `CalibrationFailed`: When a calibration could not be fitted to the measured points
+///     Parameters:
+///     - `reason`: Why the calibration could not be fitted
+/// - `Unreachable`: When the belts can not place the pen at a point
+///     Parameters:
+///     - `x`: The x position of the point, relative to the top left of the page
+///     - `y`: The y position of the point, relative to the top left of the page
+///     - `reason`: Why the belts can not reach the point
+///
+#[derive(Error, Debug)]
+pub enum HardwareError {
+    #[error("Error accessing the machine configuration {}: {}", .path, .reason)]
+    Io { path: String, reason: String },
+
+    #[error("The machine configuration is invalid: {}", .reason)]
+    InvalidConfig { reason: String },
+
+    #[error("The {} must be greater than 0, but was {}", .name, .value)]
+    NonPositiveDimension { name: String, value: f64 },
+
+    #[error("The page must fit between the motors. Check the horizontal offset and page width.")]
+    PageOutsideMotors,
+
+    #[error("The pen can not move to x:{} y:{}, as it is inside a keep-out zone.", .x, .y)]
+    InKeepOutZone { x: f64, y: f64 },
+
+    #[error("The pen can not move to x:{} y:{}, as a belt would be at {:.1} degrees, below the minimum of {} degrees.", .x, .y, .angle, .min_angle)]
+    BeltAngleTooShallow { x: f64, y: f64, angle: f64, min_angle: f64 },
+
+    #[error("The calibration could not be fitted: {}", .reason)]
+    CalibrationFailed { reason: String },
+
+    #[error("The pen can not move to x:{} y:{}. {}", .x, .y, .reason)]
+    Unreachable { x: f64, y: f64, reason: String },
+}
+
+///
+/// All errors emitted converting between cartesian coordinates and belt lengths.
+/// The error messages can be displayed to users on the frontend.
+///
+/// - `NonFiniteCoordinate`: When a coordinate is NaN or infinite
+///     Parameters:
+///     - `x`: The x position of the point, relative to the machine origin
+///     - `y`: The y position of the point, relative to the machine origin
+/// - `NonFiniteLengths`: When a belt length is NaN or infinite
+///     Parameters:
+///     - `left`: The left belt length
+///     - `right`: The right belt length
+/// - `UnreachableLengths`: When the belts can not meet, as one belt is longer than the other belt
+///   and the distance between the motors put together, or they don't reach each other
+///     Parameters:
+///     - `left`: The left belt length
+///     - `right`: The right belt length
+///     - `motor_interspace`: The distance between the motor shafts
+///
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum KinematicsError {
+    #[error("The pen position x:{} y:{} is not a number.", .x, .y)]
+    NonFiniteCoordinate { x: f64, y: f64 },
+
+    #[error("The belt lengths l:{} r:{} are not numbers.", .left, .right)]
+    NonFiniteLengths { left: f64, right: f64 },
+
+    #[error("The belt lengths l:{:.3} r:{:.3} can not meet at a point, with the motors {}mm apart.", .left, .right, .motor_interspace)]
+    UnreachableLengths { left: f64, right: f64, motor_interspace: f64 },
+}
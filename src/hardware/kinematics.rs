@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use super::SagModel;
+use super::error::KinematicsError;
+use super::math::{belt_to_cartesian, belt_to_cartesian_with_sag, cartesian_to_belt, cartesian_to_belt_with_sag};
+
+///
+/// The trait for all machine geometries to implement. A geometry converts between cartesian
+/// coordinates and the lengths travelled by the two motor axes, so the same instruction protocol
+/// can drive different machines.
+/// Cartesian coordinates are relative to the machine origin (the left motor shaft of a hanging
+/// plotter), and grow downwards/rightwards. All values are in millimetres.
+///
+/// # Functions:
+/// - `cartesian_to_axes`: Should return the (left, right) axis lengths of a cartesian coordinate, or an error if it is not a number
+/// - `axes_to_cartesian`: Should return the cartesian coordinate of a pair of axis lengths, or an error if no point has them
+///
+pub trait Kinematics {
+    fn cartesian_to_axes(&self, x: f64, y: f64) -> Result<(f64, f64), KinematicsError>;
+    fn axes_to_cartesian(&self, left: f64, right: f64) -> Result<(f64, f64), KinematicsError>;
+}
+
+///
+/// The supported machine geometries, selected in `PhysicalDimensions`.
+///
+/// - `Polargraph`: A hanging-V plotter, where the pen hangs from two belts driven by motors at the top corners
+/// - `Cartesian`: An XY gantry, where the left motor drives the x axis and the right motor drives the y axis
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum KinematicsKind {
+    #[default]
+    Polargraph,
+    Cartesian,
+}
+
+///
+/// The geometry of a hanging-V plotter. The axes are the belt lengths from each motor shaft.
+///
+/// # Fields:
+/// - `motor_interspace`: The horizontal distance between the motor shafts
+/// - `sag`: An optional model of the belts sagging under their own weight, otherwise belts are straight
+///
+pub struct Polargraph {
+    pub motor_interspace: f64,
+    pub sag: Option<SagModel>,
+}
+
+impl Kinematics for Polargraph {
+    fn cartesian_to_axes(&self, x: f64, y: f64) -> Result<(f64, f64), KinematicsError> {
+        match &self.sag {
+            Some(sag) => cartesian_to_belt_with_sag(x, y, self.motor_interspace, sag.gondola_mass, sag.belt_density),
+            None => cartesian_to_belt(x, y, self.motor_interspace),
+        }
+    }
+
+    fn axes_to_cartesian(&self, left: f64, right: f64) -> Result<(f64, f64), KinematicsError> {
+        match &self.sag {
+            Some(sag) => belt_to_cartesian_with_sag(left, right, self.motor_interspace, sag.gondola_mass, sag.belt_density),
+            None => belt_to_cartesian(left, right, self.motor_interspace),
+        }
+    }
+}
+
+///
+/// The geometry of an XY gantry. The axes are the x and y positions of the pen.
+///
+pub struct Cartesian;
+
+impl Kinematics for Cartesian {
+    fn cartesian_to_axes(&self, x: f64, y: f64) -> Result<(f64, f64), KinematicsError> {
+        match x.is_finite() && y.is_finite() {
+            true => Ok((x, y)),
+            false => Err(KinematicsError::NonFiniteCoordinate { x, y }),
+        }
+    }
+
+    fn axes_to_cartesian(&self, left: f64, right: f64) -> Result<(f64, f64), KinematicsError> {
+        match left.is_finite() && right.is_finite() {
+            true => Ok((left, right)),
+            false => Err(KinematicsError::NonFiniteLengths { left, right }),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::error::KinematicsError;
 
 ///
 /// Converts cartesian into belt lengths. The calculated belt lengths are
@@ -10,12 +11,17 @@
 ///
 /// # Returns:
 /// - A tuple containing the left and right belt lengths, respectively
+/// - `KinematicsError` if the coordinate is not a number
 ///
-pub fn cartesian_to_belt(x: f64, y: f64, motor_interspace: f64) -> (f64, f64) {
+pub fn cartesian_to_belt(x: f64, y: f64, motor_interspace: f64) -> Result<(f64, f64), KinematicsError> {
+    if !(x.is_finite() && y.is_finite()) {
+        return Err(KinematicsError::NonFiniteCoordinate { x, y });
+    }
+
     let left_belt = f64::sqrt(f64::powi(x, 2) + f64::powi(y, 2));
     let right_belt = f64::sqrt(f64::powi(motor_interspace - x, 2) + f64::powi(y, 2));
 
-    (left_belt, right_belt)
+    Ok((left_belt, right_belt))
 }
 
 ///
@@ -30,28 +36,172 @@ pub fn cartesian_to_belt(x: f64, y: f64, motor_interspace: f64) -> (f64, f64) {
 /// 
 /// # Returns:
 /// - A tuple containing the x and y coordinates, respectively
+/// - `KinematicsError` if the lengths are not numbers, or the belts can not meet at a point
 ///
-pub fn belt_to_cartesian(left_length: f64, right_length: f64, motor_interspace: f64) -> (f64, f64) {
+pub fn belt_to_cartesian(left_length: f64, right_length: f64, motor_interspace: f64) -> Result<(f64, f64), KinematicsError> {
+    if !(left_length.is_finite() && right_length.is_finite()) {
+        return Err(KinematicsError::NonFiniteLengths { left: left_length, right: right_length });
+    }
+
     let x = (f64::powi(motor_interspace, 2) + f64::powi(left_length, 2) - f64::powi(right_length, 2)) / (2. * motor_interspace);
-    let y = f64::sqrt(f64::powi(left_length, 2) - f64::powi(x, 2));
+    let y_squared = f64::powi(left_length, 2) - f64::powi(x, 2);
+
+    // belts in a straight line meet at y = 0, but rounding can leave a tiny negative
+    if y_squared < -1e-9 * f64::powi(left_length, 2).max(1.) {
+        return Err(KinematicsError::UnreachableLengths { left: left_length, right: right_length, motor_interspace });
+    }
+
+    Ok((x, f64::sqrt(y_squared.max(0.))))
+}
+
+///
+/// The acceleration due to gravity, in metres per second squared.
+///
+const GRAVITY: f64 = 9.81;
+
+///
+/// Converts cartesian into belt lengths, accounting for the belts sagging under their own weight.
+/// Each belt hangs as a catenary, with the horizontal tension chosen so the belts together
+/// support the gondola. The calculated belt lengths are relative to the motor shaft. All values
+/// are in millimetres.
+///
+/// # Parameters:
+/// - `x`: The x parameter of the cartesian coordinate, horizontally relative to the left motor
+/// - `y`: The y parameter of the cartesian coordinate, vertically relative to the left motor
+/// - `motor_interspace`: The distance between the two motor shafts
+/// - `gondola_mass`: The mass of the gondola and pen, in kilograms
+/// - `belt_density`: The mass of the belt per metre, in kilograms
+///
+/// # Returns:
+/// - A tuple containing the left and right belt lengths, respectively
+/// - `KinematicsError` if the coordinate is not a number
+///
+pub fn cartesian_to_belt_with_sag(x: f64, y: f64, motor_interspace: f64, gondola_mass: f64, belt_density: f64) -> Result<(f64, f64), KinematicsError> {
+    if !(x.is_finite() && y.is_finite()) {
+        return Err(KinematicsError::NonFiniteCoordinate { x, y });
+    }
+
+    // weight of the belt per millimetre, and of the gondola, in newtons
+    let belt_weight = belt_density * GRAVITY / 1000.;
+    let gondola_weight = gondola_mass * GRAVITY;
+    if belt_weight <= 0. || gondola_weight <= 0. || y <= 0. {
+        return cartesian_to_belt(x, y, motor_interspace);
+    }
+
+    let left_span = x.abs().max(1e-9);
+    let right_span = (motor_interspace - x).abs().max(1e-9);
+
+    // the vertical tension at the gondola grows with the horizontal tension, so bisect for it
+    let supported = |horizontal_tension: f64| {
+        let catenary = horizontal_tension / belt_weight;
+        horizontal_tension * (catenary_gondola_slope(left_span, y, catenary) + catenary_gondola_slope(right_span, y, catenary))
+    };
+    let (mut low, mut high) = (1e-9, 1.);
+    while supported(high) < gondola_weight && high < 1e12 {
+        high *= 2.;
+    }
+    for _ in 0..60 {
+        let mid = (low + high) / 2.;
+        if supported(mid) < gondola_weight {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    let catenary = high / belt_weight;
+    Ok((catenary_length(left_span, y, catenary), catenary_length(right_span, y, catenary)))
+}
+
+///
+/// Converts belt lengths into cartesian coordinates, accounting for the belts sagging under their
+/// own weight. This inverts `cartesian_to_belt_with_sag` with Newton's method, starting from the
+/// straight belt solution. All values are in millimetres.
+///
+/// # Parameters:
+/// - `left_length`: The length of the left motor belt, relative to the left motor shaft
+/// - `right_length`: The length of the right motor belt, relative to the right motor shaft
+/// - `motor_interspace`: The distance between the two motor shafts
+/// - `gondola_mass`: The mass of the gondola and pen, in kilograms
+/// - `belt_density`: The mass of the belt per metre, in kilograms
+///
+/// # Returns:
+/// - A tuple containing the x and y coordinates, respectively
+/// - `KinematicsError` if the lengths are not numbers, or the belts can not meet at a point
+///
+pub fn belt_to_cartesian_with_sag(left_length: f64, right_length: f64, motor_interspace: f64, gondola_mass: f64, belt_density: f64) -> Result<(f64, f64), KinematicsError> {
+    let (mut x, mut y) = belt_to_cartesian(left_length, right_length, motor_interspace)?;
+
+    let lengths = |x: f64, y: f64| cartesian_to_belt_with_sag(x, y, motor_interspace, gondola_mass, belt_density);
+    const DELTA: f64 = 1e-4;
+    for _ in 0..20 {
+        let (left, right) = lengths(x, y)?;
+        let (error_left, error_right) = (left - left_length, right - right_length);
+        if error_left.abs() < 1e-9 && error_right.abs() < 1e-9 {
+            break;
+        }
 
-    return (x, y);
+        // numerical jacobian of the belt lengths
+        let (left_dx, right_dx) = lengths(x + DELTA, y)?;
+        let (left_dy, right_dy) = lengths(x, y + DELTA)?;
+        let (j11, j12) = ((left_dx - left) / DELTA, (left_dy - left) / DELTA);
+        let (j21, j22) = ((right_dx - right) / DELTA, (right_dy - right) / DELTA);
+        let determinant = j11 * j22 - j12 * j21;
+        if determinant.abs() < 1e-12 {
+            break;
+        }
+
+        x -= (j22 * error_left - j12 * error_right) / determinant;
+        y -= (j11 * error_right - j21 * error_left) / determinant;
+    }
+
+    Ok((x, y))
 }
 
+///
+/// Calculates the slope of a catenary at its lower end, where it meets the gondola.
+///
+/// # Parameters:
+/// - `span`: The horizontal distance between the motor shaft and gondola
+/// - `drop`: The vertical distance between the motor shaft and gondola
+/// - `catenary`: The catenary parameter, the horizontal tension over the belt weight per millimetre
+///
+/// # Returns:
+/// - The slope of the belt at the gondola, positive when the belt rises towards the motor
+///
+fn catenary_gondola_slope(span: f64, drop: f64, catenary: f64) -> f64 {
+    let half_span = span / (2. * catenary);
+    let middle = (drop / (2. * catenary * half_span.sinh())).asinh();
+    (middle - half_span).sinh()
+}
+
+///
+/// Calculates the length of a catenary between two points.
+///
+/// # Parameters:
+/// - `span`: The horizontal distance between the points
+/// - `drop`: The vertical distance between the points
+/// - `catenary`: The catenary parameter, the horizontal tension over the belt weight per millimetre
+///
+/// # Returns:
+/// - The length of the catenary
+///
+fn catenary_length(span: f64, drop: f64, catenary: f64) -> f64 {
+    (drop * drop + (2. * catenary * (span / (2. * catenary)).sinh()).powi(2)).sqrt()
+}
 
 ///
 /// Calculates the number of steps required to move the belt one millimetre.
 ///
+/// # Parameters:
+/// - `steps_per_rev`: The number of motor steps required for one revolution, including microstepping
+/// - `pulley_diameter`: The diameter of the pulley wheel, in millimetres
+///
 /// # Returns:
 /// - The required number of steps for the belt to move 1 millimetre
 ///
-pub fn steps_per_mm() -> f64 {
-    /// The number of motor steps required for one revolution.
-    const STEPS_PER_REV: f64 = 3200.;
-    /// The diameter of the pulley wheel, in millimetres.
-    const WHEEL_DIAMETER: f64 = 12.63;
-
-    STEPS_PER_REV / (std::f64::consts::PI * WHEEL_DIAMETER)
+pub fn steps_per_mm(steps_per_rev: f64, pulley_diameter: f64) -> f64 {
+    steps_per_rev / (std::f64::consts::PI * pulley_diameter)
 }
 
 ///
@@ -59,10 +209,11 @@ pub fn steps_per_mm() -> f64 {
 ///
 /// # Parameters:
 /// - `steps`: The number of proposed steps
+/// - `steps_per_mm`: The number of steps required for the belt to move 1 millimetre
 ///
 /// # Returns:
 /// - The number of millimetres moved
 ///
-pub fn steps_to_mm(steps: i16) -> f64 {
-    (steps as f64) / steps_per_mm()
+pub fn steps_to_mm(steps: i16, steps_per_mm: f64) -> f64 {
+    (steps as f64) / steps_per_mm
 }
@@ -2,7 +2,18 @@
 //! Physical hardware representations and handling
 //! 
 
+use serde::{Deserialize, Serialize};
+
+use calibration::{Calibrated, CalibrationMap};
+use error::HardwareError;
+use kinematics::{Cartesian, Kinematics, KinematicsKind, Polargraph};
+
+pub mod calibration;
+pub mod error;
+pub mod kinematics;
 pub mod math;
+pub mod motion;
+pub mod pen;
 
 ///
 /// A simple container for the physical dimensions of the machine layout.
@@ -15,15 +26,164 @@ pub mod math;
 /// - `page_vertical_offset`: The vertical distance between the left motor shaft and the top left of the page
 /// - `page_width`: The width of the page
 /// - `page_height`: The height of the page
+/// - `motor_config`: The stepper motors and pulleys driving the belts
+/// - `kinematics`: The geometry of the machine, for an XY gantry `motor_interspace` is the width of the x axis
+/// - `keep_out_zones`: Regions of the page the pen must not enter, such as clips or tape
+/// - `min_belt_angle`: The smallest angle, in degrees, either belt may make with the horizontal, or 0 to allow any
+/// - `calibration`: An optional correction for the measured error of the machine
+/// - `sag`: An optional model of the belts sagging under their own weight, for large machines
+/// - `pen_settle`: How long the pen takes to settle after it is raised or lowered
 /// 
-#[derive(getset::Getters)]
+#[derive(getset::Getters, Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[get = "pub"]
 pub struct PhysicalDimensions {
     motor_interspace: f64,
     page_horizontal_offset: f64,
     page_vertical_offset: f64,
     page_width: f64,
-    page_height: f64
+    page_height: f64,
+
+    #[serde(default)]
+    motor_config: MotorConfig,
+    #[serde(default)]
+    kinematics: KinematicsKind,
+
+    #[serde(default)]
+    keep_out_zones: Vec<KeepOutZone>,
+    #[serde(default)]
+    min_belt_angle: f64,
+    #[serde(default)]
+    calibration: Option<CalibrationMap>,
+    #[serde(default)]
+    sag: Option<SagModel>,
+    #[serde(default)]
+    pen_settle: PenSettle,
+}
+
+///
+/// How long the pen takes to settle after it is raised or lowered, such as a slow servo still
+/// swinging. The machine waits this long after each pen change before moving, so the start of a
+/// stroke isn't skipped while the pen is still coming down.
+///
+/// # Fields:
+/// - `raise_ms`: The wait after the pen is raised, in milliseconds, or 0 to not wait
+/// - `lower_ms`: The wait after the pen is lowered, in milliseconds, or 0 to not wait
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PenSettle {
+    pub raise_ms: u16,
+    pub lower_ms: u16,
+}
+
+impl PenSettle {
+    ///
+    /// # Parameters:
+    /// - `raised`: Whether the pen was raised, else lowered
+    ///
+    /// # Returns:
+    /// - The wait after the pen change, in milliseconds
+    ///
+    pub fn after(&self, raised: bool) -> u16 {
+        match raised {
+            true => self.raise_ms,
+            false => self.lower_ms,
+        }
+    }
+}
+
+///
+/// The physical properties which make a hanging plotter's belts sag. The sag is greatest near
+/// the bottom of large machines, where the belts are long and the gondola pulls them less taut.
+///
+/// # Fields:
+/// - `gondola_mass`: The mass of the gondola and pen, in kilograms
+/// - `belt_density`: The mass of the belt per metre, in kilograms
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SagModel {
+    pub gondola_mass: f64,
+    pub belt_density: f64,
+}
+
+///
+/// A rectangular region of the page the pen must not enter, such as a clip holding the paper
+/// down. All fields are measured in millimetres, relative to the top left of the page.
+///
+/// # Fields:
+/// - `x`: The left edge of the region
+/// - `y`: The top edge of the region
+/// - `width`: The width of the region
+/// - `height`: The height of the region
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeepOutZone {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl KeepOutZone {
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: A point, relative to the top left of the page
+    ///
+    /// # Returns:
+    /// - Whether the point is inside the region
+    ///
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+}
+
+///
+/// The configuration of the stepper motors and the pulleys driving the belts. Both motors are
+/// assumed to be identical.
+/// All features have an associated getter function.
+///
+/// # Fields:
+/// - `steps_per_rev`: The number of motor steps for one revolution, including microstepping
+/// - `pulley_diameter`: The diameter of the pulley wheel, in millimetres
+///
+#[derive(getset::Getters, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[get = "pub"]
+pub struct MotorConfig {
+    steps_per_rev: f64,
+    pulley_diameter: f64,
+}
+
+impl MotorConfig {
+    ///
+    /// Creates a new motor configuration.
+    ///
+    /// # Parameters:
+    /// - `steps_per_rev`: The number of motor steps for one revolution, including microstepping
+    /// - `pulley_diameter`: The diameter of the pulley wheel, in millimetres
+    ///
+    /// # Returns:
+    /// - A new `MotorConfig` instance
+    ///
+    pub fn new(steps_per_rev: f64, pulley_diameter: f64) -> MotorConfig {
+        MotorConfig { steps_per_rev, pulley_diameter }
+    }
+
+    ///
+    /// # Returns:
+    /// - The number of steps required for the belt to move 1 millimetre
+    ///
+    pub fn steps_per_mm(&self) -> f64 {
+        math::steps_per_mm(self.steps_per_rev, self.pulley_diameter)
+    }
+}
+
+impl Default for MotorConfig {
+    ///
+    /// # Returns:
+    /// - The configuration of the stock machine, 200 step motors with 16x microstepping and a 12.63mm pulley
+    ///
+    fn default() -> Self {
+        MotorConfig { steps_per_rev: 3200., pulley_diameter: 12.63 }
+    }
 }
 
 impl PhysicalDimensions {
@@ -32,9 +192,367 @@ impl PhysicalDimensions {
     /// Ideally, this is a singleton which is reconstructed when the user changes the parameters in the frontend.
     ///
     /// # Returns:
-    /// - A new `PhysicalDimension` instance
+    /// - A new `PhysicalDimension` instance, with the default `MotorConfig`
     ///
     pub fn new(motor_interspace: f64, page_horizontal_offset: f64, page_vertical_offset: f64, page_width: f64, page_height: f64) -> PhysicalDimensions {
-        PhysicalDimensions { motor_interspace, page_horizontal_offset, page_vertical_offset, page_width, page_height }
+        PhysicalDimensions { motor_interspace, page_horizontal_offset, page_vertical_offset, page_width, page_height, motor_config: MotorConfig::default(), kinematics: KinematicsKind::default(), keep_out_zones: vec![], min_belt_angle: 0., calibration: None, sag: None, pen_settle: PenSettle::default() }
+    }
+
+    ///
+    /// Replaces the motor configuration, for machines with different steppers, microstepping or pulleys.
+    ///
+    /// # Parameters:
+    /// - `motor_config`: The new motor configuration
+    ///
+    /// # Returns:
+    /// - The `PhysicalDimension` instance, with the new motor configuration
+    ///
+    pub fn with_motor_config(mut self, motor_config: MotorConfig) -> PhysicalDimensions {
+        self.motor_config = motor_config;
+        self
+    }
+
+    ///
+    /// Replaces the machine geometry, to drive machines other than a hanging-V plotter.
+    ///
+    /// # Parameters:
+    /// - `kinematics`: The new machine geometry
+    ///
+    /// # Returns:
+    /// - The `PhysicalDimension` instance, with the new machine geometry
+    ///
+    pub fn with_kinematics(mut self, kinematics: KinematicsKind) -> PhysicalDimensions {
+        self.kinematics = kinematics;
+        self
+    }
+
+    ///
+    /// Replaces the regions of the page the pen must not enter.
+    ///
+    /// # Parameters:
+    /// - `keep_out_zones`: The new keep-out regions
+    ///
+    /// # Returns:
+    /// - The `PhysicalDimension` instance, with the new keep-out regions
+    ///
+    pub fn with_keep_out_zones(mut self, keep_out_zones: Vec<KeepOutZone>) -> PhysicalDimensions {
+        self.keep_out_zones = keep_out_zones;
+        self
+    }
+
+    ///
+    /// Replaces the smallest angle either belt may make with the horizontal. Near the top corners
+    /// the belts are almost horizontal, so they lose tension and line quality suffers.
+    ///
+    /// # Parameters:
+    /// - `min_belt_angle`: The new minimum belt angle, in degrees, or 0 to allow any
+    ///
+    /// # Returns:
+    /// - The `PhysicalDimension` instance, with the new minimum belt angle
+    ///
+    pub fn with_min_belt_angle(mut self, min_belt_angle: f64) -> PhysicalDimensions {
+        self.min_belt_angle = min_belt_angle;
+        self
+    }
+
+    ///
+    /// Replaces the calibration of the machine. The calibration should be removed while plotting
+    /// a new reference grid, so the grid shows the uncorrected error.
+    ///
+    /// # Parameters:
+    /// - `calibration`: The new calibration, or `None` to remove it
+    ///
+    /// # Returns:
+    /// - The `PhysicalDimension` instance, with the new calibration
+    ///
+    pub fn with_calibration(mut self, calibration: Option<CalibrationMap>) -> PhysicalDimensions {
+        self.calibration = calibration;
+        self
+    }
+
+    ///
+    /// Replaces the belt sag model of the machine.
+    ///
+    /// # Parameters:
+    /// - `sag`: The new sag model, or `None` to treat the belts as straight
+    ///
+    /// # Returns:
+    /// - The `PhysicalDimension` instance, with the new sag model
+    ///
+    pub fn with_sag(mut self, sag: Option<SagModel>) -> PhysicalDimensions {
+        self.sag = sag;
+        self
+    }
+
+    ///
+    /// Replaces how long the pen takes to settle after it is raised or lowered.
+    ///
+    /// # Parameters:
+    /// - `pen_settle`: The new settle times
+    ///
+    /// # Returns:
+    /// - The `PhysicalDimension` instance, with the new settle times
+    ///
+    pub fn with_pen_settle(mut self, pen_settle: PenSettle) -> PhysicalDimensions {
+        self.pen_settle = pen_settle;
+        self
+    }
+
+    ///
+    /// Checks whether the pen may be placed at a point: it must be outside every keep-out zone
+    /// and, on a hanging plotter, both belts must be at least `min_belt_angle` from horizontal.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The point, relative to the top left of the page
+    ///
+    /// # Returns:
+    /// - Void if the pen may be placed at the point
+    /// - `HardwareError` explaining why the point can not be reached
+    ///
+    pub fn check_bounds(&self, x: f64, y: f64) -> Result<(), HardwareError> {
+        if self.keep_out_zones.iter().any(|zone| zone.contains(x, y)) {
+            return Err(HardwareError::InKeepOutZone { x, y });
+        }
+
+        if self.kinematics == KinematicsKind::Polargraph && self.min_belt_angle > 0. {
+            let (machine_x, machine_y) = (self.page_horizontal_offset + x, self.page_vertical_offset + y);
+            let left_angle = machine_y.atan2(machine_x).to_degrees();
+            let right_angle = machine_y.atan2(self.motor_interspace - machine_x).to_degrees();
+            let angle = left_angle.min(right_angle);
+
+            if angle < self.min_belt_angle {
+                return Err(HardwareError::BeltAngleTooShallow { x, y, angle, min_angle: self.min_belt_angle });
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// # Returns:
+    /// - The geometry of the machine, to convert between cartesian coordinates and axis lengths
+    ///
+    pub fn kinematics_model(&self) -> Box<dyn Kinematics + Send + Sync> {
+        let inner: Box<dyn Kinematics + Send + Sync> = match self.kinematics {
+            KinematicsKind::Polargraph => Box::new(Polargraph { motor_interspace: self.motor_interspace, sag: self.sag.clone() }),
+            KinematicsKind::Cartesian => Box::new(Cartesian),
+        };
+
+        match &self.calibration {
+            Some(calibration) => Box::new(Calibrated { inner, calibration: calibration.clone(), page_offset: (self.page_horizontal_offset, self.page_vertical_offset) }),
+            None => inner,
+        }
+    }
+
+    ///
+    /// # Returns:
+    /// - The number of steps required for the belt to move 1 millimetre, from the motor configuration
+    ///
+    pub fn steps_per_mm(&self) -> f64 {
+        self.motor_config.steps_per_mm()
+    }
+
+    ///
+    /// Loads the physical dimensions from a TOML configuration file, and validates them.
+    ///
+    /// # Parameters:
+    /// - `path`: The path of the configuration file
+    ///
+    /// # Returns:
+    /// - The loaded `PhysicalDimensions`
+    /// - `HardwareError` if the file could not be read, parsed or validated
+    ///
+    pub fn load_from_toml(path: &str) -> Result<PhysicalDimensions, HardwareError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| HardwareError::Io { path: path.to_owned(), reason: err.to_string() })?;
+        PhysicalDimensions::from_toml_str(&contents)
+    }
+
+    ///
+    /// Parses the physical dimensions from the contents of a TOML configuration, and validates
+    /// them. This is used by frontends which store the configuration themselves.
+    ///
+    /// # Parameters:
+    /// - `contents`: The TOML configuration
+    ///
+    /// # Returns:
+    /// - The parsed `PhysicalDimensions`
+    /// - `HardwareError` if the configuration could not be parsed or validated
+    ///
+    pub fn from_toml_str(contents: &str) -> Result<PhysicalDimensions, HardwareError> {
+        let physical_dimensions: PhysicalDimensions = toml::from_str(contents).map_err(|err| HardwareError::InvalidConfig { reason: err.to_string() })?;
+
+        physical_dimensions.validate()?;
+        Ok(physical_dimensions)
+    }
+
+    ///
+    /// Saves the physical dimensions to a TOML configuration file.
+    ///
+    /// # Parameters:
+    /// - `path`: The path of the configuration file, which is overwritten if it exists
+    ///
+    /// # Returns:
+    /// - Void if the file was saved
+    /// - `HardwareError` if the file could not be serialised or written
+    ///
+    pub fn save_to_toml(&self, path: &str) -> Result<(), HardwareError> {
+        let contents = toml::to_string_pretty(self).map_err(|err| HardwareError::InvalidConfig { reason: err.to_string() })?;
+        std::fs::write(path, contents).map_err(|err| HardwareError::Io { path: path.to_owned(), reason: err.to_string() })
+    }
+
+    ///
+    /// Checks the physical dimensions describe a machine which can draw: every measurement is
+    /// positive, and the page sits horizontally between the motors.
+    ///
+    /// # Returns:
+    /// - Void if the dimensions are valid
+    /// - `HardwareError` explaining the first invalid measurement
+    ///
+    pub fn validate(&self) -> Result<(), HardwareError> {
+        let measurements = [
+            ("motor interspace", self.motor_interspace),
+            ("page vertical offset", self.page_vertical_offset),
+            ("page width", self.page_width),
+            ("page height", self.page_height),
+            ("motor steps per revolution", self.motor_config.steps_per_rev),
+            ("pulley diameter", self.motor_config.pulley_diameter),
+        ];
+        for (name, value) in measurements {
+            if value.is_nan() || value <= 0. {
+                return Err(HardwareError::NonPositiveDimension { name: name.to_owned(), value });
+            }
+        }
+
+        if self.page_horizontal_offset < 0. || self.page_horizontal_offset + self.page_width > self.motor_interspace {
+            return Err(HardwareError::PageOutsideMotors);
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// Tests relating to the physical hardware.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::KinematicsError;
+
+    #[test]
+    fn validate_dimensions() {
+        assert!(PhysicalDimensions::new(500., 150., 150., 200., 250.).validate().is_ok());
+        assert!(matches!(PhysicalDimensions::new(500., 150., 150., 0., 250.).validate(), Err(HardwareError::NonPositiveDimension { .. })));
+        assert!(matches!(PhysicalDimensions::new(500., 350., 150., 200., 250.).validate(), Err(HardwareError::PageOutsideMotors)));
+    }
+
+    #[test]
+    fn configurable_steps_per_mm() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        assert!((physical_dimensions.steps_per_mm() - 80.65).abs() < 0.01);
+
+        let physical_dimensions = physical_dimensions.with_motor_config(MotorConfig::new(1600., 12.63));
+        assert!((physical_dimensions.steps_per_mm() - 40.32).abs() < 0.01);
+    }
+
+    #[test]
+    fn kinematics_round_trip() {
+        for kinematics in [KinematicsKind::Polargraph, KinematicsKind::Cartesian] {
+            let model = PhysicalDimensions::new(500., 150., 150., 200., 250.).with_kinematics(kinematics).kinematics_model();
+            let (left, right) = model.cartesian_to_axes(200., 300.).unwrap();
+            let (x, y) = model.axes_to_cartesian(left, right).unwrap();
+            assert!((x - 200.).abs() < 1e-9 && (y - 300.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn impossible_belt_lengths() {
+        let model = PhysicalDimensions::new(500., 150., 150., 200., 250.).kinematics_model();
+        assert_eq!(model.axes_to_cartesian(100., 700.), Err(KinematicsError::UnreachableLengths { left: 100., right: 700., motor_interspace: 500. }));
+        assert!(matches!(model.axes_to_cartesian(f64::NAN, 300.), Err(KinematicsError::NonFiniteLengths { .. })));
+        assert!(matches!(model.cartesian_to_axes(f64::INFINITY, 10.), Err(KinematicsError::NonFiniteCoordinate { .. })));
+
+        // belts in a straight line meet on the line between the motors
+        let (x, y) = model.axes_to_cartesian(200., 300.).unwrap();
+        assert!((x - 200.).abs() < 1e-9 && y == 0.);
+    }
+
+    #[test]
+    fn acceleration_profile() {
+        let trapezoid = motion::AccelerationProfile::new(40., 400., f64::INFINITY);
+        assert_eq!(trapezoid.peak_speed(100.), 40.);
+        assert!((trapezoid.peak_speed(1.) - 20.).abs() < 1e-6);
+
+        // 0.1s to accelerate over 2mm, 2.4s cruising over 96mm, then 0.1s to decelerate
+        assert!((trapezoid.move_time(100.) - 2.6).abs() < 1e-6);
+
+        // limiting jerk only ever slows movements down
+        let s_curve = motion::AccelerationProfile::new(40., 400., 4000.);
+        assert!(s_curve.move_time(100.) > trapezoid.move_time(100.));
+        assert!(s_curve.move_time(1.) > trapezoid.move_time(1.));
+    }
+
+    #[test]
+    fn keep_out_and_belt_angle() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.)
+            .with_keep_out_zones(vec![KeepOutZone { x: 0., y: 0., width: 20., height: 20. }])
+            .with_min_belt_angle(25.);
+
+        assert!(physical_dimensions.check_bounds(100., 100.).is_ok());
+        assert!(matches!(physical_dimensions.check_bounds(10., 10.), Err(HardwareError::InKeepOutZone { .. })));
+
+        // the left belt is at atan(150 / 350) ~= 23 degrees
+        assert!(matches!(physical_dimensions.check_bounds(200., 0.), Err(HardwareError::BeltAngleTooShallow { .. })));
+    }
+
+    #[test]
+    fn calibration_fits() {
+        let intended = calibration::reference_grid((10., 10.), (50., 50.), 3, 3);
+        let measured: Vec<(f64, f64)> = intended.iter().map(|(x, y)| (x * 1.01 + 0.5, y * 0.99 - 0.2)).collect();
+
+        let affine = CalibrationMap::fit_affine(&intended, &measured).unwrap();
+        let (x, y) = affine.distort(60., 60.);
+        assert!((x - 61.1).abs() < 1e-6 && (y - 59.2).abs() < 1e-6);
+        let (x, y) = affine.distort(affine.correct(30., 40.).0, affine.correct(30., 40.).1);
+        assert!((x - 30.).abs() < 1e-6 && (y - 40.).abs() < 1e-6);
+
+        let mesh = CalibrationMap::fit_mesh((10., 10.), (50., 50.), 3, 3, &measured).unwrap();
+        let (aim_x, aim_y) = mesh.correct(30., 40.);
+        let (x, y) = mesh.distort(aim_x, aim_y);
+        assert!((x - 30.).abs() < 1e-6 && (y - 40.).abs() < 1e-6);
+
+        // a calibrated machine aims off target, so the pen lands on it
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.).with_calibration(Some(mesh));
+        let model = physical_dimensions.kinematics_model();
+        let (left, right) = model.cartesian_to_axes(180., 190.).unwrap();
+        let (x, y) = model.axes_to_cartesian(left, right).unwrap();
+        assert!((x - 180.).abs() < 1e-6 && (y - 190.).abs() < 1e-6);
+        assert_ne!((left, right), Polargraph { motor_interspace: 500., sag: None }.cartesian_to_axes(180., 190.).unwrap());
+    }
+
+    #[test]
+    fn belt_sag() {
+        let sagging = Polargraph { motor_interspace: 1000., sag: Some(SagModel { gondola_mass: 0.1, belt_density: 0.01 }) };
+        let straight = Polargraph { motor_interspace: 1000., sag: None };
+
+        // a sagging belt is longer than the straight line to the gondola
+        let (left, right) = sagging.cartesian_to_axes(300., 900.).unwrap();
+        let (straight_left, straight_right) = straight.cartesian_to_axes(300., 900.).unwrap();
+        assert!(left > straight_left && right > straight_right);
+        assert!(left - straight_left < 5.);
+
+        let (x, y) = sagging.axes_to_cartesian(left, right).unwrap();
+        assert!((x - 300.).abs() < 1e-6 && (y - 900.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let path = std::env::temp_dir().join("bbcore_physical_dimensions.toml");
+        let path = path.to_str().unwrap();
+
+        physical_dimensions.save_to_toml(path).unwrap();
+        assert_eq!(PhysicalDimensions::load_from_toml(path).unwrap(), physical_dimensions);
+        let _ = std::fs::remove_file(path);
     }
 }
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+///
+/// The motion limits of a machine, used to estimate how long movements take. Every movement
+/// starts and ends at rest, accelerating along an S-curve (jerk limited) or trapezoidal (with an
+/// infinite jerk) speed profile. Distances may be in any unit, such as millimetres or motor steps,
+/// as long as every field uses the same one.
+/// All features have an associated getter function.
+///
+/// # Fields:
+/// - `max_speed`: The top speed, in units per second
+/// - `max_accel`: The greatest acceleration, in units per second squared
+/// - `max_jerk`: The greatest rate of change of acceleration, in units per second cubed
+///
+#[derive(getset::Getters, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[get = "pub"]
+pub struct AccelerationProfile {
+    max_speed: f64,
+    max_accel: f64,
+    max_jerk: f64,
+}
+
+impl AccelerationProfile {
+    ///
+    /// Creates a new acceleration profile.
+    ///
+    /// # Parameters:
+    /// - `max_speed`: The top speed, in units per second
+    /// - `max_accel`: The greatest acceleration, in units per second squared
+    /// - `max_jerk`: The greatest rate of change of acceleration, in units per second cubed, or `f64::INFINITY` for a trapezoidal profile
+    ///
+    /// # Returns:
+    /// - A new `AccelerationProfile` instance
+    ///
+    pub fn new(max_speed: f64, max_accel: f64, max_jerk: f64) -> AccelerationProfile {
+        AccelerationProfile { max_speed, max_accel, max_jerk }
+    }
+
+    ///
+    /// Estimates the motion limits of a stepper motor, from only its top speed. The motor is
+    /// assumed to reach full speed in a quarter of a second, with a tenth of that spent ramping
+    /// up its acceleration.
+    ///
+    /// # Parameters:
+    /// - `max_motor_speed`: The motor steps per second
+    ///
+    /// # Returns:
+    /// - A new `AccelerationProfile` instance, measured in motor steps
+    ///
+    pub fn from_max_motor_speed(max_motor_speed: f64) -> AccelerationProfile {
+        AccelerationProfile { max_speed: max_motor_speed, max_accel: max_motor_speed * 4., max_jerk: max_motor_speed * 160. }
+    }
+
+    ///
+    /// Calculates the time taken to accelerate from rest to a given speed, or to decelerate from
+    /// it back to rest. The distance covered meanwhile is always half of `speed * time`.
+    ///
+    /// # Parameters:
+    /// - `speed`: The target speed
+    ///
+    /// # Returns:
+    /// - The time taken, in seconds
+    ///
+    fn ramp_time(&self, speed: f64) -> f64 {
+        // below this speed, the acceleration never reaches its maximum before it must fall again
+        if speed * self.max_jerk <= self.max_accel * self.max_accel {
+            2. * (speed / self.max_jerk).sqrt()
+        } else {
+            speed / self.max_accel + self.max_accel / self.max_jerk
+        }
+    }
+
+    ///
+    /// Calculates the top speed reached along a single movement. Short movements must begin
+    /// decelerating before they reach `max_speed`.
+    ///
+    /// # Parameters:
+    /// - `distance`: The length of the movement
+    ///
+    /// # Returns:
+    /// - The top speed reached, in units per second
+    ///
+    pub fn peak_speed(&self, distance: f64) -> f64 {
+        // accelerating and decelerating covers speed * ramp_time, which grows with speed
+        if distance <= 0. {
+            return 0.;
+        }
+        if self.max_speed * self.ramp_time(self.max_speed) <= distance {
+            return self.max_speed;
+        }
+
+        let (mut low, mut high) = (0., self.max_speed);
+        for _ in 0..64 {
+            let mid = (low + high) / 2.;
+            if mid * self.ramp_time(mid) > distance {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        low
+    }
+
+    ///
+    /// Calculates the time taken for a single movement, starting and ending at rest.
+    ///
+    /// # Parameters:
+    /// - `distance`: The length of the movement
+    ///
+    /// # Returns:
+    /// - The time taken, in seconds
+    ///
+    pub fn move_time(&self, distance: f64) -> f64 {
+        let speed = self.peak_speed(distance);
+        if speed <= 0. {
+            return 0.;
+        }
+
+        // any distance not covered while accelerating is travelled at the top speed
+        let ramp_time = self.ramp_time(speed);
+        2. * ramp_time + (distance - speed * ramp_time).max(0.) / speed
+    }
+}
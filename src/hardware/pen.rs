@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+///
+/// The fraction of a pen's line width to space fill lines by, so neighbouring lines overlap
+/// slightly and merge into a solid fill.
+///
+const FILL_SPACING_RATIO: f64 = 0.8;
+
+///
+/// A pen or other drawing tool, with its physical properties.
+///
+/// # Fields:
+/// - `name`: The display name of the pen
+/// - `line_width_mm`: The width of the line the pen draws, in millimetres
+/// - `lift_height`: The height to raise the pen off the paper, in millimetres
+/// - `down_pressure`: The pressure to press the pen onto the paper with, between 0 and 1
+/// - `colour`: The RGBA colour of the pen's ink
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Pen {
+    pub name: String,
+    pub line_width_mm: f64,
+    pub lift_height: f64,
+    pub down_pressure: f64,
+    pub colour: [u8; 4],
+}
+
+impl Pen {
+    ///
+    /// Creates a new pen.
+    ///
+    /// # Parameters:
+    /// - `name`: The display name of the pen
+    /// - `line_width_mm`: The width of the line the pen draws, in millimetres
+    /// - `lift_height`: The height to raise the pen off the paper, in millimetres
+    /// - `down_pressure`: The pressure to press the pen onto the paper with, between 0 and 1
+    /// - `colour`: The RGBA colour of the pen's ink
+    ///
+    /// # Returns:
+    /// - A new `Pen` instance
+    ///
+    pub fn new(name: &str, line_width_mm: f64, lift_height: f64, down_pressure: f64, colour: [u8; 4]) -> Pen {
+        Pen { name: name.to_owned(), line_width_mm, lift_height, down_pressure, colour }
+    }
+
+    ///
+    /// # Returns:
+    /// - The spacing between hatching or fill lines drawn by this pen, in millimetres
+    ///
+    pub fn fill_spacing(&self) -> f64 {
+        self.line_width_mm * FILL_SPACING_RATIO
+    }
+}
+
+///
+/// A registry of the pens available to a machine, looked up by name.
+///
+/// # Fields:
+/// - `pens`: The registered pens
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PenLibrary {
+    pens: Vec<Pen>,
+}
+
+impl PenLibrary {
+    ///
+    /// Creates a new, empty pen library.
+    ///
+    /// # Returns:
+    /// - A new `PenLibrary` instance
+    ///
+    pub fn new() -> PenLibrary {
+        PenLibrary { pens: vec![] }
+    }
+
+    ///
+    /// Adds a pen to the library, replacing any pen of the same name.
+    ///
+    /// # Parameters:
+    /// - `pen`: The pen to add
+    ///
+    pub fn add(&mut self, pen: Pen) {
+        self.pens.retain(|existing| existing.name != pen.name);
+        self.pens.push(pen);
+    }
+
+    ///
+    /// # Parameters:
+    /// - `name`: The name of the pen
+    ///
+    /// # Returns:
+    /// - The pen with the given name, if it exists
+    ///
+    pub fn get(&self, name: &str) -> Option<&Pen> {
+        self.pens.iter().find(|pen| pen.name == name)
+    }
+
+    ///
+    /// # Returns:
+    /// - Every pen in the library
+    ///
+    pub fn pens(&self) -> &[Pen] {
+        &self.pens
+    }
+}
+
+impl Default for PenLibrary {
+    ///
+    /// # Returns:
+    /// - A library of common black pens: 0.3mm and 0.5mm fineliners, and a 1mm brush pen
+    ///
+    fn default() -> Self {
+        PenLibrary {
+            pens: vec![
+                Pen::new("Fineliner 0.3", 0.3, 2., 0.5, [0, 0, 0, 255]),
+                Pen::new("Fineliner 0.5", 0.5, 2., 0.5, [0, 0, 0, 255]),
+                Pen::new("Brush 1.0", 1., 3., 0.3, [0, 0, 0, 255]),
+            ]
+        }
+    }
+}
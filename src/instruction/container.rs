@@ -0,0 +1,264 @@
+//!
+//! The `.blot` file format, a generated drawing saved to disk so it can be previewed or plotted
+//! later without regenerating it
+//!
+//! Every field is big-endian:
+//! - 4 bytes: the magic bytes `BLOT`
+//! - 1 byte: the format version
+//! - 8 bytes: the initial x position of the pen, as an f64
+//! - 8 bytes: the initial y position of the pen, as an f64
+//! - 4 bytes: the number of instruction bytes, as a u32
+//! - The instruction bytes
+//!
+//! Version 2 files follow the instruction bytes with the metadata of the drawing:
+//! - 4 bytes: the length of the metadata, as a u32
+//! - The metadata, as UTF-8 JSON
+//!
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+use super::InstructionSet;
+use super::error::InstructionError;
+use crate::hardware::PhysicalDimensions;
+
+/// The first bytes of every `.blot` file.
+pub const MAGIC: &[u8; 4] = b"BLOT";
+
+/// The version of the format written by this library for drawings without metadata.
+pub const VERSION: u8 = 1;
+
+/// The version of the format written by this library for drawings with metadata.
+pub const METADATA_VERSION: u8 = 2;
+
+/// The length of the header, before the instruction bytes.
+const HEADER_LENGTH: usize = 4 + 1 + 8 + 8 + 4;
+
+///
+/// Everything needed to generate a drawing again, saved alongside its instructions so the
+/// settings which made a drawing can be shown, and the drawing reproduced.
+///
+/// # Fields:
+/// - `method_id`: The backend ID of the drawing method
+/// - `parameters`: The parameters of the drawing method
+/// - `seed`: The seed of the drawing method's randomness, if it has one
+/// - `crate_version`: The version of this library which generated the drawing
+/// - `generated_at`: When the drawing was generated, in seconds since the Unix epoch
+/// - `physical_dimensions`: The machine the drawing was generated for
+/// - `content_hash`: The `InstructionSet::content_hash` of the drawing, checked when the file
+///   is decoded, so a file whose instructions don't match its settings is rejected
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Metadata {
+    pub method_id: String,
+    pub parameters: serde_json::Value,
+    pub seed: Option<u64>,
+    pub crate_version: String,
+    pub generated_at: u64,
+    pub physical_dimensions: PhysicalDimensions,
+    #[serde(default)]
+    pub content_hash: Option<u64>,
+}
+
+impl Metadata {
+    ///
+    /// Describes a drawing generated now, by this version of the library.
+    ///
+    /// # Parameters:
+    /// - `method_id`: The backend ID of the drawing method
+    /// - `parameters_json`: The parameters of the drawing method, as a JSON object. Its `seed`
+    ///   field is recorded as the seed
+    /// - `physical_dimensions`: The machine the drawing was generated for
+    ///
+    /// # Returns:
+    /// - The metadata
+    /// - `InstructionError::InvalidContainer` if the parameters are not JSON
+    ///
+    pub fn new(method_id: &str, parameters_json: &str, physical_dimensions: &PhysicalDimensions) -> Result<Metadata, InstructionError> {
+        let parameters: serde_json::Value = serde_json::from_str(parameters_json).map_err(|err| InstructionError::InvalidContainer { reason: format!("The parameters are not valid JSON. {}", err) })?;
+
+        Ok(Metadata {
+            method_id: method_id.to_owned(),
+            seed: parameters.get("seed").and_then(|seed| seed.as_u64()),
+            parameters,
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            generated_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+            physical_dimensions: physical_dimensions.clone(),
+            content_hash: None,
+        })
+    }
+}
+
+///
+/// Encodes an instruction set as the contents of a `.blot` file.
+///
+/// # Parameters:
+/// - `instruction_set`: The instruction set to encode
+///
+/// # Returns:
+/// - The contents of the file
+///
+pub fn encode(instruction_set: &InstructionSet) -> Vec<u8> {
+    encode_with_metadata(instruction_set, None)
+}
+
+///
+/// Encodes an instruction set and its metadata as the contents of a `.blot` file. Without
+/// metadata, the file is version 1, so older versions of this library can still read it.
+///
+/// # Parameters:
+/// - `instruction_set`: The instruction set to encode
+/// - `metadata`: The metadata of the drawing, if any
+///
+/// # Returns:
+/// - The contents of the file
+///
+pub fn encode_with_metadata(instruction_set: &InstructionSet, metadata: Option<&Metadata>) -> Vec<u8> {
+    let ins_bytes = instruction_set.get_binary();
+    let (init_x, init_y) = instruction_set.get_init();
+    // serialising plain data to JSON can't fail
+    let metadata_bytes = metadata.map(|metadata| serde_json::to_vec(metadata).unwrap_or_default());
+
+    let mut bytes = Vec::with_capacity(HEADER_LENGTH + ins_bytes.len() + metadata_bytes.as_ref().map_or(0, |metadata_bytes| 4 + metadata_bytes.len()));
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(if metadata_bytes.is_some() { METADATA_VERSION } else { VERSION });
+    bytes.extend_from_slice(&init_x.to_be_bytes());
+    bytes.extend_from_slice(&init_y.to_be_bytes());
+    bytes.extend_from_slice(&(ins_bytes.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(ins_bytes);
+    if let Some(metadata_bytes) = metadata_bytes {
+        bytes.extend_from_slice(&(metadata_bytes.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&metadata_bytes);
+    }
+    bytes
+}
+
+///
+/// Decodes the contents of a `.blot` file, validating the instructions.
+///
+/// # Parameters:
+/// - `bytes`: The contents of the file
+///
+/// # Returns:
+/// - The instruction set
+/// - `InstructionError::InvalidContainer` if the file is not a `.blot` file, or an error
+///   explaining why its instructions are invalid
+///
+pub fn decode(bytes: &[u8]) -> Result<InstructionSet, InstructionError> {
+    decode_with_metadata(bytes).map(|(instruction_set, _)| instruction_set)
+}
+
+///
+/// Decodes the contents of a `.blot` file and its metadata, validating the instructions.
+///
+/// # Parameters:
+/// - `bytes`: The contents of the file
+///
+/// # Returns:
+/// - The instruction set, and its metadata if the file has any
+/// - `InstructionError::InvalidContainer` if the file is not a `.blot` file, or an error
+///   explaining why its instructions are invalid
+///
+pub fn decode_with_metadata(bytes: &[u8]) -> Result<(InstructionSet, Option<Metadata>), InstructionError> {
+    let invalid = |reason: &str| InstructionError::InvalidContainer { reason: reason.to_owned() };
+
+    if bytes.len() < HEADER_LENGTH || &bytes[0..4] != MAGIC {
+        return Err(invalid("The file is not a .blot file"));
+    }
+    if bytes[4] != VERSION && bytes[4] != METADATA_VERSION {
+        return Err(InstructionError::InvalidContainer { reason: format!("Unsupported .blot version {}, expected {} or {}", bytes[4], VERSION, METADATA_VERSION) });
+    }
+
+    let init_x = f64::from_be_bytes(bytes[5..13].try_into().unwrap());
+    let init_y = f64::from_be_bytes(bytes[13..21].try_into().unwrap());
+    let length = u32::from_be_bytes(bytes[21..25].try_into().unwrap()) as usize;
+
+    let rest = &bytes[HEADER_LENGTH..];
+    let (ins_bytes, metadata) = match bytes[4] {
+        VERSION => (rest, None),
+        _ => {
+            if rest.len() < length.saturating_add(4) {
+                return Err(invalid("The file ends before its metadata"));
+            }
+            let (ins_bytes, metadata_bytes) = rest.split_at(length);
+            let metadata_length = u32::from_be_bytes(metadata_bytes[0..4].try_into().unwrap()) as usize;
+            if metadata_bytes.len() - 4 != metadata_length {
+                return Err(InstructionError::InvalidContainer { reason: format!("Expected {} metadata bytes, found {}", metadata_length, metadata_bytes.len() - 4) });
+            }
+            let metadata: Metadata = serde_json::from_slice(&metadata_bytes[4..]).map_err(|err| InstructionError::InvalidContainer { reason: format!("The metadata is invalid. {}", err) })?;
+            (ins_bytes, Some(metadata))
+        },
+    };
+    if ins_bytes.len() != length {
+        return Err(InstructionError::InvalidContainer { reason: format!("Expected {} instruction bytes, found {}", length, ins_bytes.len()) });
+    }
+
+    let instruction_set = InstructionSet::new(ins_bytes.to_vec(), init_x, init_y)?;
+    if let Some(expected) = metadata.as_ref().and_then(|metadata| metadata.content_hash) && instruction_set.content_hash() != expected {
+        return Err(InstructionError::InvalidContainer { reason: format!("The instructions don't match the drawing the file was saved with, expected hash {:016x}, found {:016x}", expected, instruction_set.content_hash()) });
+    }
+
+    Ok((instruction_set, metadata))
+}
+
+///
+/// Saves an instruction set to a `.blot` file.
+///
+/// # Parameters:
+/// - `instruction_set`: The instruction set to save
+/// - `path`: The path to save the file to
+///
+/// # Returns:
+/// - Void if the file was saved
+/// - `InstructionError::Io` if the file could not be written
+///
+pub fn save(instruction_set: &InstructionSet, path: &str) -> Result<(), InstructionError> {
+    save_with_metadata(instruction_set, None, path)
+}
+
+///
+/// Saves an instruction set and its metadata to a `.blot` file.
+///
+/// # Parameters:
+/// - `instruction_set`: The instruction set to save
+/// - `metadata`: The metadata of the drawing, if any
+/// - `path`: The path to save the file to
+///
+/// # Returns:
+/// - Void if the file was saved
+/// - `InstructionError::Io` if the file could not be written
+///
+pub fn save_with_metadata(instruction_set: &InstructionSet, metadata: Option<&Metadata>, path: &str) -> Result<(), InstructionError> {
+    std::fs::write(path, encode_with_metadata(instruction_set, metadata)).map_err(|err| InstructionError::Io { path: path.to_owned(), reason: err.to_string() })
+}
+
+///
+/// Loads an instruction set from a `.blot` file.
+///
+/// # Parameters:
+/// - `path`: The path of the file
+///
+/// # Returns:
+/// - The instruction set
+/// - An error explaining why the file could not be read or decoded
+///
+pub fn load(path: &str) -> Result<InstructionSet, InstructionError> {
+    let bytes = std::fs::read(path).map_err(|err| InstructionError::Io { path: path.to_owned(), reason: err.to_string() })?;
+    decode(&bytes)
+}
+
+///
+/// Reads the metadata of a `.blot` file, such as to show the settings which made it.
+///
+/// # Parameters:
+/// - `path`: The path of the file
+///
+/// # Returns:
+/// - The metadata, or None if the file has none
+/// - An error explaining why the file could not be read or decoded
+///
+pub fn load_metadata(path: &str) -> Result<Option<Metadata>, InstructionError> {
+    let bytes = std::fs::read(path).map_err(|err| InstructionError::Io { path: path.to_owned(), reason: err.to_string() })?;
+    decode_with_metadata(&bytes).map(|(_, metadata)| metadata)
+}
@@ -1,5 +1,9 @@
 use thiserror::Error;
 
+use crate::hardware::error::KinematicsError;
+
+pub use bbcore_codec::error::{CodecError, NextInstructionError};
+
 ///
 /// All errors emitted from the instruction module.
 ///
@@ -14,14 +18,45 @@ use thiserror::Error;
 ///     - `step_y`: The number of y steps
 ///     - `prev_x`: The previous x position of the pen 
 ///     - `prev_y`: The previous y position of the pen 
-///     - `target_x`: The target x position of the pen 
-///     - `target_y`: The target y position of the pen 
+///     - `reason`: Why the belts have no position after the instruction, with their lengths
 /// - `IncompleteInstructions`: When an instruction doesn't end in the 0x0C termination byte
 /// - `EmptyInstructionSet`: When the vector contains no bytes
 /// - `InvalidLength`: When the vectors length modulo 5 is not 0, indicating invalid instructions
 /// - `BufferTooSmall`: When the requested instruction buffer size for the instruction stream is too small
 ///     Parameters:
 ///     - `usize`: The requested buffer size
+/// - `ChunkOutOfBounds`: When a requested instruction buffer (chunk) does not exist
+///     Parameters:
+///     - `chunk_idx`: The requested chunk index
+///     - `num_chunks`: The number of chunks in the instruction set
+/// - `Io`: When a `.blot` file could not be read or written
+///     Parameters:
+///     - `path`: The path of the file
+///     - `reason`: The underlying error
+/// - `InvalidContainer`: When a `.blot` file is malformed, or from an unsupported version
+///     Parameters:
+///     - `reason`: Why the file could not be decoded
+/// - `InvalidTiles`: When a drawing could not be split into tiles
+///     Parameters:
+///     - `reason`: Why the drawing could not be split
+/// - `InvalidResume`: When a drawing could not be resumed from the position of the pen
+///     Parameters:
+///     - `reason`: Why the drawing could not be resumed
+/// - `InvalidPriming`: When pen re-priming could not be inserted into a drawing
+///     Parameters:
+///     - `reason`: Why the re-priming could not be inserted
+/// - `InvalidParking`: When the pen could not be parked after a drawing
+///     Parameters:
+///     - `reason`: Why the pen could not be parked
+/// - `InvalidReplot`: When part of a drawing could not be compiled to plot again
+///     Parameters:
+///     - `reason`: Why the part could not be plotted again
+/// - `InvalidLint`: When a drawing could not be checked for suspicious patterns
+///     Parameters:
+///     - `reason`: Why the drawing could not be checked
+/// - `Uncompilable`: When the strokes of a drawing could not be compiled into instructions
+///     Parameters:
+///     - `reason`: Which point of the drawing can't be reached
 ///
 #[derive(Error, Debug)]
 pub enum InstructionError {
@@ -29,16 +64,15 @@ pub enum InstructionError {
     StartOutOfBounds { start_idx: usize, upper_bound: usize },
 
     #[error(
-        "The pen is out of bounds. Instruction index {} called steps l:{} r:{}. The belt has moved from x:{} y:{} to x:{} y:{}",
+        "The pen is out of bounds. Instruction index {} called steps l:{} r:{}, moving the belts from x:{} y:{}. {}",
         .instruction_idx,
         .step_x,
         .step_y,
         .prev_x,
         .prev_y,
-        .target_x,
-        .target_y
+        .reason
     )]
-    DrawingOutOfBounds { instruction_idx: usize, step_x: i16, step_y: i16, prev_x: f64, prev_y: f64, target_x: f64, target_y: f64 },
+    DrawingOutOfBounds { instruction_idx: usize, step_x: i16, step_y: i16, prev_x: f64, prev_y: f64, reason: KinematicsError },
 
     #[error("An instruction did not end with the instruction termination 0x0C, instead {:#04x}", .0)]
     IncompleteInstructions(u8),
@@ -51,21 +85,45 @@ pub enum InstructionError {
 
     #[error("The configured instruction buffer size is too small {}", .0)]
     BufferTooSmall(usize),
-}
 
+    #[error("Invalid chunk index: {chunk_idx}, the instruction set has {num_chunks} chunks")]
+    ChunkOutOfBounds { chunk_idx: usize, num_chunks: usize },
 
+    #[error("Error accessing the drawing file {}: {}", .path, .reason)]
+    Io { path: String, reason: String },
+
+    #[error("The drawing file is invalid: {}", .reason)]
+    InvalidContainer { reason: String },
+
+    #[error("The drawing could not be split into tiles: {}", .reason)]
+    InvalidTiles { reason: String },
+
+    #[error("The drawing could not be resumed: {}", .reason)]
+    InvalidResume { reason: String },
+
+    #[error("The pen re-priming could not be added to the drawing: {}", .reason)]
+    InvalidPriming { reason: String },
+
+    #[error("The pen could not be parked after the drawing: {}", .reason)]
+    InvalidParking { reason: String },
+
+    #[error("The drawing could not be re-plotted: {}", .reason)]
+    InvalidReplot { reason: String },
+
+    #[error("The drawing could not be checked: {}", .reason)]
+    InvalidLint { reason: String },
+
+    #[error("The drawing could not be compiled: {}", .reason)]
+    Uncompilable { reason: String },
+}
 
-///
-/// All errors emitted from the get_next_instruction function.
-///
-/// - `InstructionStreamEnded`: When the instruction stream has ended, this is called normally once
-/// - `InvalidInstruction`: When an error occured finding the next instruction
-///
-#[derive(Error, Debug)]
-pub enum NextInstructionError {
-    #[error("The instruction stream ended")]
-    EndOfStream,
 
-    #[error("The instruction was invalid at index {}", .0)]
-    InvalidInstruction(usize)
+impl From<CodecError> for InstructionError {
+    fn from(err: CodecError) -> InstructionError {
+        match err {
+            CodecError::IncompleteInstructions(terminator) => InstructionError::IncompleteInstructions(terminator),
+            CodecError::EmptyInstructionSet => InstructionError::EmptyInstructionSet,
+            CodecError::BufferTooSmall(size) => InstructionError::BufferTooSmall(size),
+        }
+    }
 }
@@ -0,0 +1,174 @@
+use std::fmt;
+
+use serde::Serialize;
+
+#[cfg(feature = "async")]
+use crate::client::state::MachineConfiguration;
+use crate::hardware::PhysicalDimensions;
+use crate::preview;
+use crate::preview::error::PreviewError;
+
+use super::InstructionSet;
+use super::codec::Instruction;
+use super::error::InstructionError;
+
+///
+/// How many instructions which neither move nor change the pen a drawing can have before it is
+/// flagged. A few are left by rounding, but thousands usually mean a drawing method sampled the
+/// same point over and over.
+///
+pub const ZERO_STEP_WARNING_COUNT: usize = 1000;
+
+///
+/// How many steps a motor can move in one instruction before it is flagged as close to the
+/// limit of an `i16`, where a slightly longer move would overflow.
+///
+pub const NEAR_STEP_LIMIT: u16 = 30_000;
+
+///
+/// A suspicious pattern in an instruction stream, which the machine would draw but probably
+/// wasn't meant. Positions are relative to the top left of the page, in millimetres.
+///
+/// - `ZeroStepInstructions`: Thousands of instructions neither move nor change the pen
+///     Parameters:
+///     - `count`: The number of such instructions
+///     - `first_instruction`: The index of the first of them
+///     - `position`: Where the pen is at the first of them
+/// - `IdlePenToggle`: The pen is raised and lowered, or lowered and raised, without moving in between
+///     Parameters:
+///     - `first_instruction`: The index of the instruction changing the pen first
+///     - `last_instruction`: The index of the instruction changing it back
+///     - `position`: Where the pen is
+/// - `NearStepLimit`: A single instruction moves a motor close to the most steps an instruction can hold
+///     Parameters:
+///     - `instruction`: The index of the instruction
+///     - `left_steps` and `right_steps`: The steps the motors move
+///     - `position`: Where the pen is before the instruction
+/// - `ExceedsBuffer`: An instruction is longer than the machine's instruction buffer, so it can
+///   never be sent. Only the first is reported
+///     Parameters:
+///     - `instruction`: The index of the instruction
+///     - `length`: The length of the instruction, in bytes
+///     - `buffer_size`: The size of the machine's instruction buffer, in bytes
+///
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LintWarning {
+    ZeroStepInstructions { count: usize, first_instruction: usize, position: (f64, f64) },
+    IdlePenToggle { first_instruction: usize, last_instruction: usize, position: (f64, f64) },
+    NearStepLimit { instruction: usize, left_steps: i16, right_steps: i16, position: (f64, f64) },
+    ExceedsBuffer { instruction: usize, length: usize, buffer_size: usize },
+}
+
+impl LintWarning {
+    ///
+    /// # Returns:
+    /// - The index of the first instruction the warning is about
+    ///
+    pub fn first_instruction(&self) -> usize {
+        match self {
+            LintWarning::ZeroStepInstructions { first_instruction, .. } | LintWarning::IdlePenToggle { first_instruction, .. } => *first_instruction,
+            LintWarning::NearStepLimit { instruction, .. } | LintWarning::ExceedsBuffer { instruction, .. } => *instruction,
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintWarning::ZeroStepInstructions { count, first_instruction, position } => write!(f, "{} instructions don't move, the first at instruction {} ({:.1}, {:.1})", count, first_instruction, position.0, position.1),
+            LintWarning::IdlePenToggle { first_instruction, last_instruction, position } => write!(f, "The pen changes and changes back without moving, at instructions {} to {} ({:.1}, {:.1})", first_instruction, last_instruction, position.0, position.1),
+            LintWarning::NearStepLimit { instruction, left_steps, right_steps, position } => write!(f, "Instruction {} moves l:{} r:{} steps, close to the limit, from ({:.1}, {:.1})", instruction, left_steps, right_steps, position.0, position.1),
+            LintWarning::ExceedsBuffer { instruction, length, buffer_size } => write!(f, "Instruction {} is {} bytes, longer than the machine's {} byte buffer", instruction, length, buffer_size),
+        }
+    }
+}
+
+///
+/// Checks an instruction stream for suspicious patterns before it is plotted, so a frontend can
+/// show them to the user.
+///
+/// # Parameters:
+/// - `instruction_set`: The drawing to check
+/// - `physical_dimensions`: The physical dimensions of the machine, to locate each warning on the page
+/// - `machine_config`: The configuration the machine sent when it connected
+///
+/// # Returns:
+/// - The warnings, in the order of their first instruction
+/// - An error explaining why the drawing could not be checked, such as it leaving the machine's reach
+///
+#[cfg(feature = "async")]
+pub fn lint(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, machine_config: &MachineConfiguration) -> Result<Vec<LintWarning>, InstructionError> {
+    lint_for_buffer(instruction_set, physical_dimensions, machine_config.instruction_buffer_size as usize)
+}
+
+///
+/// Checks an instruction stream like `lint`, for a machine which isn't connected, whose
+/// instruction buffer size is already known.
+///
+/// # Parameters:
+/// - `instruction_set`: The drawing to check
+/// - `physical_dimensions`: The physical dimensions of the machine, to locate each warning on the page
+/// - `buffer_size`: The size of the machine's instruction buffer, in bytes
+///
+/// # Returns:
+/// - The warnings, in the order of their first instruction
+/// - An error explaining why the drawing could not be checked, such as it leaving the machine's reach
+///
+pub fn lint_for_buffer(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, buffer_size: usize) -> Result<Vec<LintWarning>, InstructionError> {
+    let mut positions = vec![];
+    match preview::simulate(instruction_set.get_init(), physical_dimensions, instruction_set, &mut |_, from, _, _| positions.push(from)) {
+        Ok(()) => {},
+        Err(PreviewError::Instruction(err)) => return Err(err),
+        Err(err) => return Err(InstructionError::InvalidLint { reason: err.to_string() }),
+    }
+
+    let bytes = instruction_set.get_binary();
+    let mut warnings = vec![];
+    let mut zero_steps: Option<(usize, usize)> = None;
+    let mut exceeded_buffer = false;
+    // the last pen change, while the pen hasn't moved since
+    let mut pen_change: Option<usize> = None;
+
+    let (mut cidx, mut instruction_idx) = (0, 0);
+    // the instruction set was validated when it was created, so decoding only stops at its end
+    while let Ok((instruction, next_cidx)) = Instruction::decode(bytes, cidx) {
+        let length = next_cidx - cidx;
+        if length > buffer_size && !exceeded_buffer {
+            warnings.push(LintWarning::ExceedsBuffer { instruction: instruction_idx, length, buffer_size });
+            exceeded_buffer = true;
+        }
+
+        // a dwell waits for the pen to settle, so it neither moves nor is a wasted instruction
+        if instruction.dwell_ms == 0 {
+            let position = positions[instruction_idx];
+            if instruction.pen_up.is_some() {
+                if let Some(first_instruction) = pen_change {
+                    warnings.push(LintWarning::IdlePenToggle { first_instruction, last_instruction: instruction_idx, position });
+                }
+                pen_change = Some(instruction_idx);
+            }
+
+            if instruction.left_steps != 0 || instruction.right_steps != 0 {
+                pen_change = None;
+            } else if instruction.pen_up.is_none() {
+                let (count, _) = zero_steps.get_or_insert((0, instruction_idx));
+                *count += 1;
+            }
+
+            if instruction.left_steps.unsigned_abs() >= NEAR_STEP_LIMIT || instruction.right_steps.unsigned_abs() >= NEAR_STEP_LIMIT {
+                warnings.push(LintWarning::NearStepLimit { instruction: instruction_idx, left_steps: instruction.left_steps, right_steps: instruction.right_steps, position });
+            }
+        }
+
+        cidx = next_cidx;
+        instruction_idx += 1;
+    }
+
+    if let Some((count, first_instruction)) = zero_steps.filter(|(count, _)| *count >= ZERO_STEP_WARNING_COUNT) {
+        warnings.push(LintWarning::ZeroStepInstructions { count, first_instruction, position: positions[first_instruction] });
+    }
+    warnings.sort_by_key(LintWarning::first_instruction);
+
+    Ok(warnings)
+}
@@ -3,13 +3,27 @@
 //! 
 
 pub mod error;
+pub mod container;
+mod lint;
+mod parking;
+mod priming;
+mod replot;
+mod resume;
+mod tiles;
 
 use once_cell::sync::OnceCell;
 
-use byteorder::{BigEndian, ByteOrder};
 use error::InstructionError;
+use crate::hardware::PhysicalDimensions;
 
-use crate::instruction::error::NextInstructionError;
+pub use bbcore_codec::{codec, opcode};
+pub use codec::get_next_instruction_bounds;
+#[cfg(feature = "async")]
+pub use lint::lint;
+pub use lint::{lint_for_buffer, LintWarning, NEAR_STEP_LIMIT, ZERO_STEP_WARNING_COUNT};
+pub use parking::ParkingAction;
+pub use priming::PrimingOptions;
+pub use replot::ReplotSelection;
 
 ///
 /// An instruction set, to represent all instructions required to draw an image.
@@ -94,56 +108,45 @@ impl InstructionSet {
     /// - An error explaining why the index boundaries could not be computed
     ///
     pub fn get_buffer_bounds(&self, max_chunk_size: usize) -> Result<&Vec<(usize, usize)>, InstructionError> {
+        self.buffer_bound_cache.get_or_try_init(|| codec::chunk_bounds(&self.binary, max_chunk_size).map_err(InstructionError::from))
+    }
 
-        self.buffer_bound_cache.get_or_try_init(|| {
-            if max_chunk_size < 8 {
-                return Err(InstructionError::BufferTooSmall(max_chunk_size));
-            }
-
-            let mut chunk_bounds: Vec<(usize, usize)> = vec![];
-            let mut c_idx: usize = 0; // current instruction, should always point to the first idx
-            // of an instruction, not an 0x0c or elsewise
-            
-            loop {
-                
-                let start_idx = c_idx; // index of first byte of first ins of instruction buffer
-                let mut last_valid_idx = c_idx; // idx of last valid 0x0c byte
-                loop {
-                    match get_next_instruction_bounds(&self.binary, c_idx) {
-                        Ok((_sb, eb)) => {
-                            if eb >= start_idx + max_chunk_size { // if last byte of this ins is
-                                // out of bounds, push it and move on
-                                chunk_bounds.push((start_idx, last_valid_idx));
-                                break;
-                            }
-
-                            last_valid_idx = eb; // set last valid 0x0c as eb of this ins
-                            c_idx = eb + 1; // point c_idx to first byte of next ins
-                        },
-                        Err(err) => {
-                            // uncomment to print ins bounds
-                            // println!("isb {:?}", chunk_bounds);
-                            
-                            match err {
-                                NextInstructionError::EndOfStream => {
-                                    chunk_bounds.push((start_idx, last_valid_idx));
-                                    return Ok(chunk_bounds);
-                                },
-                                _ => {
-                                    return Err(InstructionError::IncompleteInstructions(self.binary[c_idx]));
-                                }
-                            }
-
-                        }
-                    }
-                }
+    ///
+    /// Finds the range of instruction indices contained within a chunk, where the chunks are
+    /// the buffers sent to the machine. This is used to match the progress of a live drawing to
+    /// the instructions which have been drawn.
+    ///
+    /// # Parameters:
+    /// - `chunk_idx`: The index of the chunk
+    /// - `max_chunk_size`: The maximum preferred chunk size of buffers
+    ///
+    /// # Returns:
+    /// - The range of instruction indices within the chunk
+    /// - An error explaining why the range could not be computed
+    ///
+    pub fn get_chunk_instruction_range(&self, chunk_idx: usize, max_chunk_size: usize) -> Result<std::ops::Range<usize>, InstructionError> {
+        let bounds = self.get_buffer_bounds(max_chunk_size)?;
+        if chunk_idx >= bounds.len() {
+            return Err(InstructionError::ChunkOutOfBounds { chunk_idx, num_chunks: bounds.len() });
+        }
 
-                // we've just pushed an instruction buffer bound, so set the c_idx to the first
-                // byte of the next instruction
-                c_idx = last_valid_idx + 1;
+        // every instruction is at least 5 bytes, so count the instructions before and within
+        let mut start_ins = 0;
+        let mut end_ins = 0;
+        let mut c_idx = 0;
+        while let Ok((_sb, eb)) = get_next_instruction_bounds(&self.binary, c_idx) {
+            if eb < bounds[chunk_idx].0 {
+                start_ins += 1;
+            }
+            if eb <= bounds[chunk_idx].1 {
+                end_ins += 1;
+            } else {
+                break;
             }
+            c_idx = eb + 1;
+        }
 
-        })
+        Ok(start_ins..end_ins)
     }
 
     ///
@@ -156,58 +159,108 @@ impl InstructionSet {
     /// - A vector of tuple (i16, i16, bool) values the belts will move by, and whether the pen is up, as per the provided instruction set.
     ///
     pub fn parse_to_numerical_steps(&self) -> Result<Vec<(i16, i16, bool)>, InstructionError> {
-        // get the instruction bound indices
-        let result_buffer_bounds = match self.get_buffer_bounds(4096) {
-            Ok(value) => value,
-            Err(err) => return Err(err)
-        };
+        Ok(codec::decode_steps(&self.binary)?)
+    }
 
-        // create a list of left motor step, right motor step, pen up/down
-        let mut numerical_instructions: Vec<(i16, i16, bool)> = vec![];
-        let mut pen_up = true;
-
-        for (s_idx, e_idx) in result_buffer_bounds {
-
-            let mut c_idx = *s_idx;
-            loop {
-                
-                match get_next_instruction_bounds(&self.binary, c_idx) {
-                    Ok((sb, eb)) => {
-                        c_idx = eb + 1;
-
-                        let left_steps = BigEndian::read_i16(&[*self.binary.get(sb).unwrap() as u8, *self.binary.get(sb + 1).unwrap() as u8]);
-                        let right_steps = BigEndian::read_i16(&[*self.binary.get(sb + 2).unwrap() as u8, *self.binary.get(sb + 3).unwrap() as u8]);
-
-                        if sb + 4 == eb && self.binary[sb + 4] == 0x0C { // if its only 5 bytes, hence no special instructions
-                        } else {
-
-                            if self.binary[sb + 4] == 0x0A {
-                                pen_up = true;
-                            } else if self.binary[sb + 4] == 0x0B {
-                                pen_up = false;
-                            } else {
-                                return Err(InstructionError::IncompleteInstructions(self.binary[sb + 4]));
-                            }
-                        }
-
-                        // add instruction and pen up/down
-                        numerical_instructions.push((left_steps, right_steps, pen_up));
-
-                        // if this instruction is at the end of the instruction bound, break
-                        if eb == *e_idx {
-                            break;
-                        }
-                    },
-                    Err(_err) => {
-                        // this would error if for some reason, the bounds made were longer than
-                        // the length of the self.binary. this should never happen.
-                        return Err(InstructionError::IncompleteInstructions(0xFF));
-                    }
-                }
-            }
-        }
+    ///
+    /// # Returns:
+    /// - A stable hash of the drawing's movements, the same for any encoding of the same
+    ///   movements, to check a file or queued drawing is the one a machine was drawing
+    ///
+    pub fn content_hash(&self) -> u64 {
+        // the instruction set was validated when it was created, so every instruction decodes
+        codec::content_hash(&self.binary).unwrap_or_default()
+    }
+
+    ///
+    /// Splits a drawing larger than the page into tiles, so it can be drawn across several
+    /// sheets and assembled. Neighbouring tiles overlap, with matching registration marks in the
+    /// overlaps to line the sheets up by.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions of the machine, whose page is the size of one tile.
+    ///   The drawing must have been generated for a larger page at the same position
+    /// - `rows`: The number of rows of tiles
+    /// - `cols`: The number of columns of tiles
+    /// - `overlap_mm`: How far neighbouring tiles overlap, in millimetres
+    ///
+    /// # Returns:
+    /// - An instruction set per tile, row by row from the top left
+    /// - An error explaining why the drawing could not be split
+    ///
+    pub fn split_into_tiles(&self, physical_dimensions: &PhysicalDimensions, rows: usize, cols: usize, overlap_mm: f64) -> Result<Vec<InstructionSet>, InstructionError> {
+        tiles::split(self, physical_dimensions, rows, cols, overlap_mm)
+    }
+
+    ///
+    /// Resumes the drawing after the pen was moved by hand or the gondola was bumped, rather
+    /// than restarting it. The drawing continues from the start of the remaining pen down
+    /// movement nearest to the pen, after a pen up move from where the pen is.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions of the machine
+    /// - `position`: Where the pen is, relative to the top left of the page, as reported by the
+    ///   machine or measured by the user
+    /// - `drawn_instructions`: The number of instructions already drawn, such as the start of the
+    ///   chunk being drawn from `get_chunk_instruction_range`. These are not drawn again
+    ///
+    /// # Returns:
+    /// - The instruction set to draw from `position`, and the index of the instruction it resumes from
+    /// - An error explaining why the drawing could not be resumed
+    ///
+    pub fn resume_from_nearest(&self, physical_dimensions: &PhysicalDimensions, position: (f64, f64), drawn_instructions: usize) -> Result<(InstructionSet, usize), InstructionError> {
+        resume::from_nearest(self, physical_dimensions, position, drawn_instructions)
+    }
 
-        Ok(numerical_instructions)
+    ///
+    /// Inserts pen re-priming into the drawing, for felt and gel pens which dry out during very
+    /// long drawings. After every so many metres of drawing, the pen leaves the drawing to
+    /// scribble on a pad, and returns to exactly where it left off.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions of the machine
+    /// - `options`: Where the scribble pad is, and how often to re-prime
+    ///
+    /// # Returns:
+    /// - The drawing, with re-priming inserted
+    /// - An error explaining why the re-priming could not be inserted, such as the pad being out of reach
+    ///
+    pub fn insert_priming(&self, physical_dimensions: &PhysicalDimensions, options: &PrimingOptions) -> Result<InstructionSet, InstructionError> {
+        priming::insert(self, physical_dimensions, options)
+    }
+
+    ///
+    /// Parks the pen once the drawing finishes, by appending pen up travel to the parking
+    /// position, rather than leaving the pen wherever the drawing ended.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions of the machine
+    /// - `action`: Where to park the pen
+    ///
+    /// # Returns:
+    /// - The drawing, followed by the parking instructions
+    /// - An error explaining why the pen could not be parked, such as the position being out of reach
+    ///
+    pub fn append_parking(&self, physical_dimensions: &PhysicalDimensions, action: ParkingAction) -> Result<InstructionSet, InstructionError> {
+        parking::append(self, physical_dimensions, action)
+    }
+
+    ///
+    /// Compiles part of the drawing on its own, such as an area which smeared, so it can be
+    /// re-inked without plotting the whole drawing again. The pen moves up from `position` to
+    /// the first selected stroke.
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions of the machine
+    /// - `selection`: The labels, region and instructions to plot again
+    /// - `position`: Where the pen is, relative to the top left of the page
+    ///
+    /// # Returns:
+    /// - The instruction set drawing only the selection
+    /// - An error explaining why the selection could not be plotted, such as nothing being selected
+    ///
+    pub fn replot(&self, physical_dimensions: &PhysicalDimensions, selection: &ReplotSelection, position: (f64, f64)) -> Result<InstructionSet, InstructionError> {
+        replot::select(self, physical_dimensions, selection, position)
     }
 
     ///
@@ -239,78 +292,32 @@ impl InstructionSet {
 /// - An error to explain why the bytes were invalid
 ///
 fn is_stream_valid(ins_bytes: &[u8]) -> Option<InstructionError> {
-    if ins_bytes.is_empty() {
-        return Some(InstructionError::EmptyInstructionSet);
-    }
-
-    let mut c_idx = 0;
-    loop {
-        c_idx += 4;
-        if c_idx >= ins_bytes.len() {
-            break;
-        }
-
-        if ins_bytes[c_idx] == 0x0C { // end instruction
-            c_idx += 1; // skip 0x0c, check next ins
-            continue;
-        } else if ins_bytes[c_idx] == 0x0A { // pen up
-            c_idx += 1;
-            if ins_bytes[c_idx] == 0x0C {
-                c_idx += 1;
-                continue
-            }
-        } else if ins_bytes[c_idx] == 0x0B { // pen down
-            c_idx += 1;
-            if ins_bytes[c_idx] == 0x0C {
-                c_idx += 1;
-                continue;
-            }
-        } else {
-            // terminator byte wasnt 0x0c, or technically if extra bytes weren't pen up/down
-            return Some(InstructionError::IncompleteInstructions(ins_bytes[c_idx]));
-        }
-
-        return Some(InstructionError::IncompleteInstructions(ins_bytes[c_idx]));
-    }
-
-    None
+    codec::validate(ins_bytes).err().map(InstructionError::from)
 }
 
-
-/// 
-/// Gets the indices of the next instruction, inclusive. e.g. (13, 17) could be 29-0fa-40-1a-0c
 ///
-/// Parameters:
-/// - `ins_bytes`: Vector of bytes, containing raw binary instructions
-/// - `cidx`: The current starting index, the first byte of the left motor movement (always one
-/// byte after the last 0x0C eoi instruction)
+/// Encodes an instruction, changing the pen before it. If the pen takes time to settle, the pen
+/// is changed on its own and the machine waits before the instruction, as compiled strokes do.
 ///
-/// # Returns:
-/// - a tuple of usizes, the bounds of the next instruction
-/// - An error explaining why the function failed. At the end of every stream, an EndOfStream error occurs
+/// # Parameters:
+/// - `instruction`: The instruction to encode, whose own pen change is replaced
+/// - `raised`: Whether the pen is raised, else lowered
+/// - `physical_dimensions`: The physical dimensions of the machine, with its pen settle times
+/// - `buffer`: The buffer to write the instructions to
 ///
-pub fn get_next_instruction_bounds(ins_bytes: &[u8], cidx: usize) -> Result<(usize, usize), NextInstructionError> {
-    
-    // so we'll skip over the motor movements, relatively 0,1,2,3 bytes
-    let mut potential_eoi_idx = cidx + 4;
-
-    if potential_eoi_idx >= ins_bytes.len() {
-        return Err(NextInstructionError::EndOfStream);
-    }
-    
-    // the next potential eoi is either an 0x0c or another custom byte such as pen up/down
-
-    // if its a pen up or down instruction, we'll assume an 0x0C afterwards so juts increment by 1
-    if ins_bytes[potential_eoi_idx] == 0x0A || ins_bytes[potential_eoi_idx] == 0x0B {
-        potential_eoi_idx += 1;
-    }
-    
-    // check if its eoi
-    if ins_bytes[potential_eoi_idx] == 0x0C {
-        return Ok((cidx, potential_eoi_idx));
+fn encode_with_pen_change(mut instruction: codec::Instruction, raised: bool, physical_dimensions: &PhysicalDimensions, buffer: &mut Vec<u8>) {
+    let settle_ms = physical_dimensions.pen_settle().after(raised);
+    // a dwell can't change the pen, so the pen is changed before it
+    if settle_ms > 0 || instruction.dwell_ms > 0 {
+        codec::Instruction::movement(0, 0, Some(raised)).encode(buffer);
+        if settle_ms > 0 {
+            codec::Instruction::dwell(settle_ms).encode(buffer);
+        }
+        instruction.pen_up = None;
+    } else {
+        instruction.pen_up = Some(raised);
     }
-
-    return Err(NextInstructionError::InvalidInstruction(cidx));
+    instruction.encode(buffer);
 }
 
 
@@ -322,6 +329,7 @@ pub fn get_next_instruction_bounds(ins_bytes: &[u8], cidx: usize) -> Result<(usi
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::instruction::error::NextInstructionError;
 
     #[test]
     #[should_panic]
@@ -373,6 +381,14 @@ mod tests {
         assert_eq!(*bb, [(0, 9)]);
     }
 
+    #[test]
+    fn chunk_instruction_range() {
+        let is = InstructionSet::new("\x0A\x0B\x2A\x3A\x0C\x0A\x0B\x2A\x3A\x0C\x0A\x0B\x2A\x3A\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        assert_eq!(is.get_chunk_instruction_range(0, 11).unwrap(), 0..2);
+        assert_eq!(is.get_chunk_instruction_range(1, 11).unwrap(), 2..3);
+        assert!(is.get_chunk_instruction_range(2, 11).is_err());
+    }
+
     #[test]
     fn validate_valid_stream() {
         assert!(is_stream_valid(&InstructionSet::new("\x0A\x0B\x2A\x3A\x0C\x0A\x0B\x2A\x3A\x0C\x0A\x0B\x2A\x3A\x0C".to_owned().into_bytes(), 0., 0.).unwrap().get_binary()).is_none());
@@ -397,4 +413,338 @@ mod tests {
     fn validate_not_pen_up_down_stream() {
         assert!(InstructionSet::new("\x0A\x0B\x2A\x0C\x0D\x0C\x2A\x3A\x0C\x0A\x0C".to_owned().into_bytes(), 0., 0.).is_err());
     }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let instructions = [
+            codec::Instruction::movement(-300, 12, Some(false)),
+            codec::Instruction::movement(i16::MAX, i16::MIN, None),
+            codec::Instruction::movement(0, 0, Some(true)),
+            codec::Instruction::dwell(u16::MAX),
+        ];
+
+        let mut bytes = vec![];
+        instructions.iter().for_each(|instruction| instruction.encode(&mut bytes));
+        assert!(codec::validate(&bytes).is_ok());
+
+        let mut writer = codec::InstructionWriter::with_estimated_instructions(1);
+        instructions.iter().for_each(|instruction| writer.write(instruction));
+        assert_eq!(writer.into_bytes(), bytes);
+
+        let mut c_idx = 0;
+        for instruction in instructions {
+            let (decoded, next_idx) = codec::Instruction::decode(&bytes, c_idx).unwrap();
+            assert_eq!(decoded, instruction);
+            c_idx = next_idx;
+        }
+        assert!(matches!(codec::Instruction::decode(&bytes, c_idx), Err(NextInstructionError::EndOfStream)));
+    }
+
+    #[test]
+    fn decode_steps_tracks_pen() {
+        let is = InstructionSet::new("\x00\x01\x00\x02\x0B\x0C\x00\x03\x00\x04\x0C\x00\x05\x00\x06\x0A\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        assert_eq!(is.parse_to_numerical_steps().unwrap(), [(1, 2, false), (3, 4, false), (5, 6, true)]);
+    }
+
+    #[test]
+    fn content_hash_ignores_the_encoding() {
+        // the second instruction repeats the pen down, which doesn't change the drawing
+        let is = InstructionSet::new("\x00\x01\x00\x02\x0B\x0C\x00\x03\x00\x04\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let redundant = InstructionSet::new("\x00\x01\x00\x02\x0B\x0C\x00\x03\x00\x04\x0B\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pen_up = InstructionSet::new("\x00\x01\x00\x02\x0B\x0C\x00\x03\x00\x04\x0A\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+
+        assert_eq!(is.content_hash(), redundant.content_hash());
+        assert_ne!(is.content_hash(), pen_up.content_hash());
+        // hashes are stored and compared later, so they must never change
+        assert_eq!(is.content_hash(), 0x2ba6_2772_eb49_fcd3);
+    }
+
+    #[test]
+    fn content_hash_includes_dwell_durations() {
+        let hash = |instructions: &[codec::Instruction]| {
+            let mut bytes = vec![];
+            instructions.iter().for_each(|instruction| instruction.encode(&mut bytes));
+            InstructionSet::new(bytes, 0., 0.).unwrap().content_hash()
+        };
+        let movement = codec::Instruction::movement(1, 2, Some(false));
+
+        assert_ne!(hash(&[movement, codec::Instruction::dwell(100)]), hash(&[movement, codec::Instruction::dwell(200)]));
+        assert_ne!(hash(&[movement, codec::Instruction::dwell(100)]), hash(&[movement, codec::Instruction::movement(0, 0, None)]));
+    }
+
+    #[test]
+    fn container_round_trip() {
+        let is = InstructionSet::new("\x00\x01\x00\x02\x0B\x0C\x00\x03\x00\x04\x0C".to_owned().into_bytes(), 12.5, -3.).unwrap();
+        let bytes = container::encode(&is);
+
+        let decoded = container::decode(&bytes).unwrap();
+        assert_eq!(decoded.get_binary(), is.get_binary());
+        assert_eq!(decoded.get_init(), (12.5, -3.));
+
+        assert!(matches!(container::decode(&bytes[..bytes.len() - 1]), Err(InstructionError::InvalidContainer { .. })));
+        assert!(matches!(container::decode(b"PNG"), Err(InstructionError::InvalidContainer { .. })));
+    }
+
+    #[test]
+    fn container_metadata_round_trip() {
+        let is = InstructionSet::new("\x00\x01\x00\x02\x0B\x0C\x00\x03\x00\x04\x0C".to_owned().into_bytes(), 12.5, -3.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let metadata = container::Metadata::new("atom", r#"{"seed": 42, "num_rings": 3}"#, &pd).unwrap();
+        assert_eq!(metadata.seed, Some(42));
+        assert_eq!(metadata.crate_version, env!("CARGO_PKG_VERSION"));
+
+        let bytes = container::encode_with_metadata(&is, Some(&metadata));
+        assert_eq!(bytes[4], container::METADATA_VERSION);
+        let (decoded, decoded_metadata) = container::decode_with_metadata(&bytes).unwrap();
+        assert_eq!(decoded.get_binary(), is.get_binary());
+        assert_eq!(decoded_metadata, Some(metadata));
+
+        // files without metadata are still version 1
+        assert_eq!(container::decode_with_metadata(&container::encode(&is)).unwrap().1, None);
+        assert!(matches!(container::decode(&bytes[..bytes.len() - 1]), Err(InstructionError::InvalidContainer { .. })));
+        assert!(container::Metadata::new("atom", "not json", &pd).is_err());
+
+        // a file whose instructions changed since it was saved is rejected
+        let hashed = container::Metadata { content_hash: Some(is.content_hash()), ..container::Metadata::new("atom", "{}", &pd).unwrap() };
+        let mut bytes = container::encode_with_metadata(&is, Some(&hashed));
+        assert!(container::decode(&bytes).is_ok());
+        bytes[4 + 1 + 8 + 8 + 4 + 1] ^= 1; // the first step of the left motor
+        assert!(matches!(container::decode(&bytes), Err(InstructionError::InvalidContainer { .. })));
+    }
+
+    #[test]
+    fn drawings_split_into_overlapping_tiles() {
+        use crate::drawing::stroke::{self, Stroke};
+
+        // a line across a page twice the width of the machine's
+        let artwork = stroke::compile(&[Stroke { points: vec![(10., 50.), (390., 50.)], pen_up: false, layer: 0, label: None }], &PhysicalDimensions::new(600., 100., 100., 400., 100.)).unwrap();
+        let is = InstructionSet::new(artwork.0, artwork.1, artwork.2).unwrap();
+
+        let pd = PhysicalDimensions::new(600., 100., 100., 210., 100.);
+        let tiles = is.split_into_tiles(&pd, 1, 2, 20.).unwrap();
+        assert_eq!(tiles.len(), 2);
+
+        // each tile has half the line, and two registration marks in the overlap of 2 lines of 10mm
+        for tile in &tiles {
+            let mut pen_down_mm = 0.;
+            crate::preview::simulate(tile.get_init(), &pd, tile, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+                if !is_pen_up {
+                    pen_down_mm += (to.0 - from.0).hypot(to.1 - from.1);
+                }
+            }).unwrap();
+            assert!((pen_down_mm - 240.).abs() < 0.5, "{}", pen_down_mm);
+        }
+
+        assert!(is.split_into_tiles(&pd, 1, 2, 0.).is_err());
+        assert!(is.split_into_tiles(&pd, 0, 2, 20.).is_err());
+    }
+
+    #[test]
+    fn drawings_resume_from_the_nearest_point() {
+        use crate::drawing::stroke::{self, Stroke};
+
+        let pd = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let strokes = [
+            Stroke { points: (0..=10).map(|i| (20. + i as f64 * 10., 50.)).collect(), pen_up: false, layer: 0, label: None },
+            Stroke { points: (0..=10).map(|i| (20. + i as f64 * 10., 150.)).collect(), pen_up: false, layer: 0, label: None },
+        ];
+        let (ins_bytes, init_x, init_y) = stroke::compile(&strokes, &pd).unwrap();
+        let is = InstructionSet::new(ins_bytes, init_x, init_y).unwrap();
+
+        let pen_down = |instruction_set: &InstructionSet| {
+            let mut segments = vec![];
+            crate::preview::simulate(instruction_set.get_init(), &pd, instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+                if !is_pen_up {
+                    segments.push((from, to));
+                }
+            }).unwrap();
+            segments
+        };
+
+        // the pen was knocked just below the middle of the second line, so only its second half is left
+        let (resumed, resume_idx) = is.resume_from_nearest(&pd, (73., 158.), 0).unwrap();
+        assert_eq!(resumed.get_init(), (73., 158.));
+        assert!(resume_idx > 10);
+        let segments = pen_down(&resumed);
+        let length: f64 = segments.iter().map(|(from, to)| (to.0 - from.0).hypot(to.1 - from.1)).sum();
+        assert!((length - 50.).abs() < 0.5, "{}", length);
+        assert!((segments[0].0.0 - 70.).abs() < 0.1 && (segments[0].0.1 - 150.).abs() < 0.1, "{:?}", segments[0]);
+
+        // lines already drawn are never resumed from
+        let (_, resume_idx) = is.resume_from_nearest(&pd, (73., 58.), 10).unwrap();
+        assert!(resume_idx > 10);
+        assert!(is.resume_from_nearest(&pd, (73., 58.), 1000).is_err());
+    }
+
+    #[test]
+    fn priming_returns_to_where_the_drawing_left_off() {
+        use crate::drawing::stroke::{self, Stroke};
+
+        // 20 lines of 100mm, each drawn separately
+        let pd = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let strokes: Vec<Stroke> = (0..20).map(|i| Stroke { points: vec![(20., 20. + i as f64 * 10.), (120., 20. + i as f64 * 10.)], pen_up: false, layer: 0, label: None }).collect();
+        let (ins_bytes, init_x, init_y) = stroke::compile(&strokes, &pd).unwrap();
+        let is = InstructionSet::new(ins_bytes, init_x, init_y).unwrap();
+
+        let options = PrimingOptions { interval_m: 0.5, pad: (180., 20.), scribble_size: 10. };
+        let primed = is.insert_priming(&pd, &options).unwrap();
+
+        // after every 5 lines but the last, so 3 scribbles
+        let pen_down_mm = |instruction_set: &InstructionSet| {
+            let mut pen_down_mm = 0.;
+            crate::preview::simulate(instruction_set.get_init(), &pd, instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+                if !is_pen_up {
+                    pen_down_mm += (to.0 - from.0).hypot(to.1 - from.1);
+                }
+            }).unwrap();
+            pen_down_mm
+        };
+        let scribble_mm = 4. * 10_f64.hypot(2.5);
+        assert!((pen_down_mm(&primed) - pen_down_mm(&is) - 3. * scribble_mm).abs() < 1., "{}", pen_down_mm(&primed));
+
+        let total_steps = |instruction_set: &InstructionSet| instruction_set.parse_to_numerical_steps().unwrap().iter().fold((0, 0), |(l, r), &(ls, rs, _)| (l + ls as i32, r + rs as i32));
+        assert_eq!(total_steps(&primed), total_steps(&is));
+
+        assert!(is.insert_priming(&pd, &PrimingOptions { interval_m: 0., ..options.clone() }).is_err());
+        // a pad just below the motors, where the belts are too shallow
+        assert!(is.insert_priming(&pd.clone().with_min_belt_angle(10.), &PrimingOptions { pad: (180., -140.), ..options }).is_err());
+    }
+
+    #[test]
+    fn parking_raises_the_pen_and_moves_it_away() {
+        use crate::drawing::stroke::{self, Stroke};
+
+        let pd = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let (ins_bytes, init_x, init_y) = stroke::compile(&[Stroke { points: vec![(20., 20.), (120., 120.)], pen_up: false, layer: 0, label: None }], &pd).unwrap();
+        let is = InstructionSet::new(ins_bytes, init_x, init_y).unwrap();
+
+        let end_of = |instruction_set: &InstructionSet| {
+            let mut end = ((0., 0.), true);
+            crate::preview::simulate(instruction_set.get_init(), &pd, instruction_set, &mut |_, _, to: (f64, f64), is_pen_up: bool| end = (to, is_pen_up)).unwrap();
+            end
+        };
+        let near = |(x, y): (f64, f64), (tx, ty): (f64, f64)| (x - tx).hypot(y - ty) < 0.5;
+        assert!(!end_of(&is).1);
+
+        for (action, target) in [(ParkingAction::Home, (0., 0.)), (ParkingAction::BottomCorner, (0., 250.)), (ParkingAction::RaisePen, (120., 120.))] {
+            let parked = is.append_parking(&pd, action).unwrap();
+            let (end, pen_up) = end_of(&parked);
+
+            assert!(parked.get_binary().starts_with(is.get_binary()));
+            assert!(pen_up && near(end, target), "{:?} ended at {:?}", action, end);
+        }
+
+        // raising a pen which is already raised changes nothing
+        let raised = is.append_parking(&pd, ParkingAction::RaisePen).unwrap();
+        assert_eq!(raised.append_parking(&pd, ParkingAction::RaisePen).unwrap().get_binary(), raised.get_binary());
+    }
+
+    #[test]
+    fn replots_draw_only_the_selection() {
+        use crate::drawing::stroke::{self, Stroke};
+        use crate::drawing::util::placement::Rect;
+
+        let pd = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let strokes = [
+            Stroke { points: vec![(20., 50.), (120., 50.)], pen_up: false, layer: 0, label: Some("top".to_owned()) },
+            Stroke { points: vec![(20., 150.), (120., 150.)], pen_up: false, layer: 0, label: Some("bottom".to_owned()) },
+        ];
+        let ((ins_bytes, init_x, init_y), spans) = stroke::compile_with_labels(&strokes, &pd).unwrap();
+        let is = InstructionSet::new(ins_bytes, init_x, init_y).unwrap();
+
+        let pen_down = |instruction_set: &InstructionSet| {
+            let mut segments = vec![];
+            crate::preview::simulate(instruction_set.get_init(), &pd, instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+                if !is_pen_up {
+                    segments.push((from, to));
+                }
+            }).unwrap();
+            segments
+        };
+        let length = |segments: &[((f64, f64), (f64, f64))]| segments.iter().map(|(from, to)| (to.0 - from.0).hypot(to.1 - from.1)).sum::<f64>();
+
+        // only the bottom line, approached from where the pen is
+        let bottom = is.replot(&pd, &ReplotSelection::new().with_labels(vec!["bottom".to_owned()], spans.clone()), (180., 20.)).unwrap();
+        assert_eq!(bottom.get_init(), (180., 20.));
+        let segments = pen_down(&bottom);
+        assert!((length(&segments) - 100.).abs() < 0.5, "{}", length(&segments));
+        assert!(segments.iter().all(|(from, to)| (from.1 - 150.).abs() < 0.5 && (to.1 - 150.).abs() < 0.5));
+
+        // the region cuts the lines at its edge
+        let region = Rect { x: 50., y: 0., width: 30., height: 250. };
+        let segments = pen_down(&is.replot(&pd, &ReplotSelection::new().with_region(region), (0., 0.)).unwrap());
+        assert!((length(&segments) - 60.).abs() < 0.5, "{}", length(&segments));
+        assert!(segments.iter().all(|(from, to)| from.0 > 49.5 && to.0 < 80.5));
+
+        // an empty selection can't be plotted
+        let selection = ReplotSelection::new().with_labels(vec!["bottom".to_owned()], spans).with_instructions(0..1);
+        assert!(matches!(is.replot(&pd, &selection, (0., 0.)), Err(InstructionError::InvalidReplot { .. })));
+    }
+
+    #[test]
+    fn lint_flags_suspicious_instructions() {
+        use codec::Instruction;
+
+        let pd = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let mut instructions = vec![Instruction::movement(80, -80, Some(false))];
+        instructions.extend((0..ZERO_STEP_WARNING_COUNT).map(|_| Instruction::movement(0, 0, None)));
+        instructions.extend([
+            Instruction::movement(0, 0, Some(true)),
+            Instruction::dwell(100),
+            Instruction::movement(0, 0, Some(false)),
+            Instruction::movement(30_000, -30_000, None),
+            Instruction::movement(-30_000, 30_000, Some(true)),
+        ]);
+        let mut bytes = vec![];
+        instructions.iter().for_each(|instruction| instruction.encode(&mut bytes));
+        let is = InstructionSet::new(bytes, 100., 100.).unwrap();
+
+        let warnings = lint_for_buffer(&is, &pd, 5).unwrap();
+        let toggle = ZERO_STEP_WARNING_COUNT + 1;
+        assert!(matches!(warnings[0], LintWarning::ExceedsBuffer { instruction: 0, length: 6, buffer_size: 5 }), "{:?}", warnings);
+        assert!(matches!(warnings[1], LintWarning::ZeroStepInstructions { count: ZERO_STEP_WARNING_COUNT, first_instruction: 1, .. }), "{:?}", warnings);
+        assert!(matches!(warnings[2], LintWarning::IdlePenToggle { first_instruction, last_instruction, .. } if first_instruction == toggle && last_instruction == toggle + 2), "{:?}", warnings);
+        assert!(matches!(warnings[3], LintWarning::NearStepLimit { left_steps: 30_000, .. }), "{:?}", warnings);
+        assert!(matches!(warnings[4], LintWarning::NearStepLimit { left_steps: -30_000, .. }), "{:?}", warnings);
+        assert_eq!(warnings.len(), 5);
+
+        // the pen is where the first instruction left it, a millimetre below the start
+        if let LintWarning::ZeroStepInstructions { position, .. } = warnings[1] {
+            assert!((position.0 - 100.).abs() < 0.1 && (position.1 - 101.4).abs() < 0.1, "{:?}", position);
+        }
+
+        // a drawing which only moves has nothing to flag
+        let (ins_bytes, init_x, init_y) = crate::drawing::stroke::compile(&[crate::drawing::stroke::Stroke { points: vec![(20., 20.), (120., 120.)], pen_up: false, layer: 0, label: None }], &pd).unwrap();
+        assert!(lint_for_buffer(&InstructionSet::new(ins_bytes, init_x, init_y).unwrap(), &pd, 1024).unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_streams_are_rejected_without_panicking() {
+        let valid = b"\x00\x01\x00\x02\x0B\x0C\xFF\xFE\x00\x03\x0C\x00\x00\x00\x00\x0A\x0C".to_vec();
+
+        // every truncation and every single byte corruption of a valid stream, as the fuzz
+        // corpus starts from
+        let mut streams: Vec<Vec<u8>> = (0..valid.len()).map(|length| valid[..length].to_vec()).collect();
+        for idx in 0..valid.len() {
+            for byte in [0x00, opcode::END_OF_INSTRUCTION, 0xFF].into_iter().chain(opcode::OPCODES.map(|opcode| opcode.byte)) {
+                let mut corrupted = valid.clone();
+                corrupted[idx] = byte;
+                streams.push(corrupted);
+            }
+        }
+
+        for stream in streams {
+            let is_valid = codec::validate(&stream).is_ok();
+            assert_eq!(codec::decode_steps(&stream).is_ok() && !stream.is_empty(), is_valid, "{:02x?}", stream);
+            assert_eq!(codec::chunk_bounds(&stream, 8).is_ok(), is_valid, "{:02x?}", stream);
+
+            if let Ok(is) = InstructionSet::new(stream.clone(), 0., 0.) {
+                // a valid stream must decode completely, without a partial instruction left over
+                let decoded_length: usize = is.parse_to_numerical_steps().unwrap().len();
+                let (_, last_eb) = codec::chunk_bounds(&stream, 8).unwrap().last().copied().unwrap();
+                assert_eq!(last_eb + 1, stream.len(), "{:02x?}", stream);
+                assert!(decoded_length > 0);
+            }
+        }
+    }
 }
@@ -0,0 +1,104 @@
+use serde::{Serialize, Deserialize};
+
+use crate::drawing::stroke::{self, Stroke};
+use crate::hardware::PhysicalDimensions;
+use crate::preview;
+use crate::preview::error::PreviewError;
+
+use super::InstructionSet;
+use super::codec::Instruction;
+use super::error::InstructionError;
+
+///
+/// The longest pen up move to the parking position in one instruction, in millimetres, so a
+/// long move can't overflow the motor steps of an instruction.
+///
+const PARKING_SEGMENT_MM: f64 = 10.;
+
+///
+/// What the machine does once a drawing finishes, rather than leaving the pen wherever the
+/// drawing ended.
+///
+/// - `Home`: Raises the pen and returns to the top left of the page, where `move_to_start`
+///   expects the pen to be before the next drawing
+/// - `BottomCorner`: Raises the pen and moves to the bottom left of the page, out of the way of
+///   the paper being taken down
+/// - `RaisePen`: Raises the pen off the paper where the drawing ended, so it doesn't bleed
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ParkingAction {
+    Home,
+    BottomCorner,
+    RaisePen,
+}
+
+///
+/// Appends the instructions parking the pen to the end of a drawing.
+///
+/// # Parameters:
+/// - `instruction_set`: The drawing
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `action`: Where to park the pen
+///
+/// # Returns:
+/// - The drawing, followed by the parking instructions
+/// - An error if the parking position can't be reached
+///
+pub fn append(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, action: ParkingAction) -> Result<InstructionSet, InstructionError> {
+    let (end, pen_up) = end_of(instruction_set, physical_dimensions)?;
+    let target = match action {
+        ParkingAction::Home => (0., 0.),
+        ParkingAction::BottomCorner => (0., *physical_dimensions.page_height()),
+        ParkingAction::RaisePen => end,
+    };
+
+    let mut ins_bytes = instruction_set.get_binary().clone();
+    if target == end {
+        if !pen_up {
+            Instruction::movement(0, 0, Some(true)).encode(&mut ins_bytes);
+        }
+    } else {
+        let distance = (target.0 - end.0).hypot(target.1 - end.1);
+        let segments = (distance / PARKING_SEGMENT_MM).ceil().max(1.) as usize;
+        let travel = Stroke {
+            points: (0..=segments).map(|i| {
+                let t = i as f64 / segments as f64;
+                (end.0 + (target.0 - end.0) * t, end.1 + (target.1 - end.1) * t)
+            }).collect(),
+            pen_up: true,
+            layer: 0,
+            label: None,
+        };
+        let (compiled, _, _) = stroke::compile(&[travel], physical_dimensions)
+            .map_err(|reason| InstructionError::InvalidParking { reason: format!("the parking position can't be reached, {}", reason) })?;
+
+        // the surface compiling the travel assumes the pen starts raised, but it may be drawing.
+        // the travel has at least one segment, so there is a first instruction
+        let (first, rest_idx) = Instruction::decode(&compiled, 0).unwrap();
+        match pen_up {
+            true => first.encode(&mut ins_bytes),
+            false => super::encode_with_pen_change(first, true, physical_dimensions, &mut ins_bytes),
+        }
+        ins_bytes.extend_from_slice(&compiled[rest_idx..]);
+    }
+
+    let (init_x, init_y) = instruction_set.get_init();
+    InstructionSet::new(ins_bytes, init_x, init_y)
+}
+
+///
+/// # Returns:
+/// - Where the drawing ends relative to the top left of the page, and whether the pen is raised
+/// - An error explaining why the instructions could not be simulated
+///
+fn end_of(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions) -> Result<((f64, f64), bool), InstructionError> {
+    let mut end = (instruction_set.get_init(), true);
+    let result = preview::simulate(instruction_set.get_init(), physical_dimensions, instruction_set, &mut |_, _, to: (f64, f64), is_pen_up: bool| end = (to, is_pen_up));
+
+    match result {
+        Ok(()) => Ok(end),
+        Err(PreviewError::Instruction(err)) => Err(err),
+        Err(err) => Err(InstructionError::InvalidParking { reason: err.to_string() }),
+    }
+}
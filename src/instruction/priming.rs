@@ -0,0 +1,165 @@
+use serde::{Serialize, Deserialize};
+
+use crate::drawing::stroke::{self, Stroke};
+use crate::hardware::PhysicalDimensions;
+use crate::preview;
+use crate::preview::error::PreviewError;
+
+use super::InstructionSet;
+use super::codec::Instruction;
+use super::error::InstructionError;
+
+///
+/// The number of times the pen crosses the scribble pad on each re-priming.
+///
+const SCRIBBLE_PASSES: usize = 4;
+
+///
+/// The (from, to) positions of the pen over one instruction.
+///
+type Segment = ((f64, f64), (f64, f64));
+
+///
+/// How a pen is re-primed during a long drawing, for felt and gel pens which dry out. After
+/// every `interval_m` of drawing, the pen is lifted, scribbles on a pad off the artwork, and
+/// returns to where it left off.
+///
+/// # Fields:
+/// - `interval_m`: How far the pen draws between re-primings, in metres
+/// - `pad`: The centre of the scribble pad, in millimetres relative to the top left of the page.
+///   It may be off the page, as long as the machine can reach it
+/// - `scribble_size`: The width and height of the scribble, in millimetres
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PrimingOptions {
+    pub interval_m: f64,
+    pub pad: (f64, f64),
+    pub scribble_size: f64,
+}
+
+///
+/// Inserts re-priming excursions into a drawing. Each excursion waits for the pen to be lifted
+/// after `interval_m` of drawing, so lines aren't interrupted, unless a single line runs on for
+/// twice the interval.
+///
+/// # Parameters:
+/// - `instruction_set`: The drawing
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `options`: Where and how often to re-prime
+///
+/// # Returns:
+/// - The drawing, with the excursions inserted
+/// - An error explaining why the excursions could not be inserted
+///
+pub fn insert(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, options: &PrimingOptions) -> Result<InstructionSet, InstructionError> {
+    if !(options.interval_m.is_finite() && options.interval_m > 0.) {
+        return Err(InstructionError::InvalidPriming { reason: "the interval must be above 0m".to_owned() });
+    }
+    if !(options.scribble_size.is_finite() && options.scribble_size > 0.) {
+        return Err(InstructionError::InvalidPriming { reason: "the scribble size must be above 0mm".to_owned() });
+    }
+
+    let interval_mm = options.interval_m * 1000.;
+    let steps = instruction_set.parse_to_numerical_steps()?;
+    let segments = segments(instruction_set, physical_dimensions)?;
+
+    // the instruction set was validated when it was created, so every instruction decodes
+    let binary = instruction_set.get_binary();
+    let mut primed = Vec::with_capacity(binary.len());
+    let mut c_idx = 0;
+    let mut pen_up = true;
+    let mut drawn_mm = 0.;
+
+    for ((_, _, next_pen_up), (from, to)) in steps.into_iter().zip(segments) {
+        let (instruction, next_idx) = Instruction::decode(binary, c_idx).unwrap();
+
+        let lifting = !pen_up && next_pen_up;
+        let mut excursed = false;
+        if drawn_mm >= interval_mm && (lifting || drawn_mm >= 2. * interval_mm) {
+            primed.extend(excursion(from, physical_dimensions, options)?);
+            drawn_mm = 0.;
+            excursed = true;
+        }
+        // the excursion leaves the pen raised, so the drawing lowers it again
+        match excursed && !next_pen_up {
+            true => super::encode_with_pen_change(instruction, false, physical_dimensions, &mut primed),
+            false => instruction.encode(&mut primed),
+        }
+
+        if !next_pen_up {
+            drawn_mm += (to.0 - from.0).hypot(to.1 - from.1);
+        }
+        pen_up = next_pen_up;
+        c_idx = next_idx;
+    }
+
+    let (init_x, init_y) = instruction_set.get_init();
+    InstructionSet::new(primed, init_x, init_y)
+}
+
+///
+/// # Returns:
+/// - The (from, to) positions of every instruction, relative to the top left of the page
+/// - An error explaining why the instructions could not be simulated
+///
+fn segments(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions) -> Result<Vec<Segment>, InstructionError> {
+    let mut segments = vec![];
+    let result = preview::simulate(instruction_set.get_init(), physical_dimensions, instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), _| segments.push((from, to)));
+
+    match result {
+        Ok(()) => Ok(segments),
+        Err(PreviewError::Instruction(err)) => Err(err),
+        Err(err) => Err(InstructionError::InvalidPriming { reason: err.to_string() }),
+    }
+}
+
+///
+/// # Parameters:
+/// - `position`: Where the pen leaves the drawing, relative to the top left of the page
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `options`: Where and how large the scribble is
+///
+/// # Returns:
+/// - The instructions lifting the pen, scribbling on the pad and returning to exactly the same
+///   motor position, with the pen raised
+/// - An error if the pad can't be reached
+///
+fn excursion(position: (f64, f64), physical_dimensions: &PhysicalDimensions, options: &PrimingOptions) -> Result<Vec<u8>, InstructionError> {
+    let (pad_x, pad_y, half) = (options.pad.0, options.pad.1, options.scribble_size / 2.);
+    let scribble = (0..=SCRIBBLE_PASSES).map(|pass| {
+        let x = if pass % 2 == 0 { pad_x - half } else { pad_x + half };
+        (x, pad_y - half + options.scribble_size * pass as f64 / SCRIBBLE_PASSES as f64)
+    }).collect();
+
+    // compile joins the strokes with pen up travel, and the strokes at `position` lift the pen
+    // and bring it back
+    let strokes = [
+        Stroke { points: vec![position, position], pen_up: true, layer: 0, label: None },
+        Stroke { points: scribble, pen_up: false, layer: 0, label: None },
+        Stroke { points: vec![position, position], pen_up: true, layer: 0, label: None },
+    ];
+    let (compiled, _, _) = stroke::compile(&strokes, physical_dimensions)
+        .map_err(|reason| InstructionError::InvalidPriming { reason: format!("the scribble pad can't be reached, {}", reason) })?;
+
+    let mut bytes = Vec::with_capacity(compiled.len() + 12);
+    let (mut left, mut right) = (0i32, 0i32);
+    let mut c_idx = 0;
+    while let Ok((instruction, next_idx)) = Instruction::decode(&compiled, c_idx) {
+        // the surface compiling the excursion assumes the pen starts raised, but it is drawing
+        match c_idx == 0 {
+            true => super::encode_with_pen_change(instruction, true, physical_dimensions, &mut bytes),
+            false => instruction.encode(&mut bytes),
+        }
+        left += instruction.left_steps as i32;
+        right += instruction.right_steps as i32;
+        c_idx = next_idx;
+    }
+
+    // each move is rounded to whole steps, so the rounding is undone for the drawing to carry on
+    // from exactly where it left off
+    if left != 0 || right != 0 {
+        Instruction::movement(-left as i16, -right as i16, None).encode(&mut bytes);
+    }
+
+    Ok(bytes)
+}
@@ -0,0 +1,157 @@
+use std::ops::Range;
+
+use crate::drawing::stroke::{self, LabelSpan, Stroke};
+use crate::drawing::util::placement::Rect;
+use crate::hardware::PhysicalDimensions;
+use crate::preview;
+use crate::preview::error::PreviewError;
+
+use super::InstructionSet;
+use super::error::InstructionError;
+
+///
+/// The part of a drawing to plot again, such as an area which smeared. Each criterion narrows
+/// the selection further, and a selection without any criteria is the whole drawing.
+///
+/// # Fields:
+/// - `labels`: The labels of the strokes to plot again, if selecting by label
+/// - `label_spans`: The instructions of each run of labelled strokes, as returned by
+///   `stroke::compile_with_labels`
+/// - `region`: The area of the page to plot again, relative to the top left of the page
+/// - `instructions`: The range of instruction indices to plot again
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplotSelection {
+    labels: Option<Vec<String>>,
+    label_spans: Vec<LabelSpan>,
+    region: Option<Rect>,
+    instructions: Option<Range<usize>>,
+}
+
+impl ReplotSelection {
+    ///
+    /// # Returns:
+    /// - A selection of the whole drawing
+    ///
+    pub fn new() -> ReplotSelection {
+        ReplotSelection::default()
+    }
+
+    ///
+    /// Only selects the strokes with one of the labels.
+    ///
+    /// # Parameters:
+    /// - `labels`: The labels of the strokes to plot again
+    /// - `label_spans`: The instructions of each run of labelled strokes, as returned by
+    ///   `stroke::compile_with_labels` when the drawing was compiled
+    ///
+    /// # Returns:
+    /// - The modified selection
+    ///
+    pub fn with_labels(mut self, labels: Vec<String>, label_spans: Vec<LabelSpan>) -> ReplotSelection {
+        self.labels = Some(labels);
+        self.label_spans = label_spans;
+        self
+    }
+
+    ///
+    /// Only selects the parts of strokes inside an area of the page. Strokes crossing its edge
+    /// are cut at the edge.
+    ///
+    /// # Parameters:
+    /// - `region`: The area to plot again, relative to the top left of the page
+    ///
+    /// # Returns:
+    /// - The modified selection
+    ///
+    pub fn with_region(mut self, region: Rect) -> ReplotSelection {
+        self.region = Some(region);
+        self
+    }
+
+    ///
+    /// Only selects the instructions in a range, such as the chunks drawn while the pen was
+    /// running dry.
+    ///
+    /// # Parameters:
+    /// - `instructions`: The range of instruction indices to plot again
+    ///
+    /// # Returns:
+    /// - The modified selection
+    ///
+    pub fn with_instructions(mut self, instructions: Range<usize>) -> ReplotSelection {
+        self.instructions = Some(instructions);
+        self
+    }
+
+    ///
+    /// # Parameters:
+    /// - `instruction_idx`: The index of a pen down instruction
+    ///
+    /// # Returns:
+    /// - Whether the instruction is selected by its index and label. The region is checked
+    ///   once the selected instructions are joined into polylines
+    ///
+    fn selects(&self, instruction_idx: usize) -> bool {
+        if self.instructions.as_ref().is_some_and(|instructions| !instructions.contains(&instruction_idx)) {
+            return false;
+        }
+
+        match &self.labels {
+            Some(labels) => self.label_spans.iter().any(|span| span.contains(instruction_idx) && labels.contains(&span.label)),
+            None => true,
+        }
+    }
+}
+
+///
+/// Compiles part of a drawing on its own, so it can be plotted again without plotting the whole
+/// drawing, such as to re-ink an area which smeared. The selected pen down strokes are drawn in
+/// their original order and direction, after a pen up move from where the pen is.
+///
+/// # Parameters:
+/// - `instruction_set`: The drawing to plot part of again
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `selection`: The part of the drawing to plot again
+/// - `position`: Where the pen is, relative to the top left of the page
+///
+/// # Returns:
+/// - The instruction set drawing the selection, starting at `position`
+/// - An error explaining why the selection could not be plotted, such as it being empty
+///
+pub fn select(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, selection: &ReplotSelection, position: (f64, f64)) -> Result<InstructionSet, InstructionError> {
+    let mut polylines: Vec<Vec<(f64, f64)>> = vec![];
+    let mut joined = false;
+
+    let result = preview::simulate(instruction_set.get_init(), physical_dimensions, instruction_set, &mut |instruction_idx: usize, from, to, is_pen_up: bool| {
+        if is_pen_up || !selection.selects(instruction_idx) {
+            joined = false;
+            return;
+        }
+        match polylines.last_mut() {
+            Some(polyline) if joined => polyline.push(to),
+            _ => polylines.push(vec![from, to]),
+        }
+        joined = true;
+    });
+    match result {
+        Ok(()) => {},
+        Err(PreviewError::Instruction(err)) => return Err(err),
+        Err(err) => return Err(InstructionError::InvalidReplot { reason: err.to_string() }),
+    }
+
+    if let Some(region) = &selection.region {
+        polylines = polylines.iter().flat_map(|polyline| region.clip_polyline(polyline)).filter(|polyline| polyline.len() > 1).collect();
+    }
+    if polylines.is_empty() {
+        return Err(InstructionError::InvalidReplot { reason: "nothing is drawn in the selection".to_owned() });
+    }
+
+    // the pen up stroke at the pen's position makes the compiler move from it to the first stroke
+    let mut strokes = vec![Stroke { points: vec![position, position], pen_up: true, layer: 0, label: None }];
+    strokes.extend(polylines.into_iter().map(|points| Stroke { points, pen_up: false, layer: 0, label: None }));
+    let (ins_bytes, init_x, init_y) = stroke::compile(&strokes, physical_dimensions)
+        .map_err(|reason| InstructionError::InvalidReplot { reason: format!("the selection can't be reached, {}", reason) })?;
+
+    InstructionSet::new(ins_bytes, init_x, init_y)
+}
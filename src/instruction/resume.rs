@@ -0,0 +1,107 @@
+use crate::drawing::stroke::{self, Stroke};
+use crate::hardware::PhysicalDimensions;
+use crate::preview;
+use crate::preview::error::PreviewError;
+
+use super::InstructionSet;
+use super::codec::Instruction;
+use super::error::InstructionError;
+
+///
+/// The longest pen up move of the approach in one instruction, in millimetres, so a long
+/// approach can't overflow the motor steps of an instruction.
+///
+const APPROACH_SEGMENT_MM: f64 = 10.;
+
+///
+/// Resumes a drawing from wherever the pen is, such as after the gondola was bumped. The
+/// remaining path is searched for the pen down movement nearest to the pen, and the drawing
+/// resumes from the start of that movement, after a pen up move to it.
+///
+/// # Parameters:
+/// - `instruction_set`: The drawing being resumed
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `position`: Where the pen is, relative to the top left of the page, as reported by the
+///   machine or measured by the user
+/// - `drawn_instructions`: The number of instructions already drawn, which are not searched
+///
+/// # Returns:
+/// - The instruction set starting at `position`, and the index of the instruction in the
+///   original drawing it resumes from
+/// - An error explaining why the drawing could not be resumed
+///
+pub fn from_nearest(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, position: (f64, f64), drawn_instructions: usize) -> Result<(InstructionSet, usize), InstructionError> {
+    let (resume_idx, target) = nearest_movement(instruction_set, physical_dimensions, position, drawn_instructions)?;
+
+    // the approach leaves the pen raised, so the first instruction must lower it if it was down
+    let pen_up = instruction_set.parse_to_numerical_steps()?[resume_idx].2;
+    let distance = (target.0 - position.0).hypot(target.1 - position.1);
+    let segments = (distance / APPROACH_SEGMENT_MM).ceil().max(1.) as usize;
+    let approach = Stroke {
+        points: (0..=segments).map(|i| {
+            let t = i as f64 / segments as f64;
+            (position.0 + (target.0 - position.0) * t, position.1 + (target.1 - position.1) * t)
+        }).collect(),
+        pen_up: true,
+        layer: 0,
+        label: None,
+    };
+    let (mut ins_bytes, _, _) = stroke::compile(&[approach], physical_dimensions)
+        .map_err(|reason| InstructionError::InvalidResume { reason: format!("the pen can't move back to the drawing, {}", reason) })?;
+
+    // the instruction set was validated when it was created, so every instruction decodes
+    let binary = instruction_set.get_binary();
+    let mut c_idx = 0;
+    for _ in 0..resume_idx {
+        c_idx = Instruction::decode(binary, c_idx).unwrap().1;
+    }
+    let (first, rest_idx) = Instruction::decode(binary, c_idx).unwrap();
+    match pen_up {
+        true => first.encode(&mut ins_bytes),
+        false => super::encode_with_pen_change(first, false, physical_dimensions, &mut ins_bytes),
+    }
+    ins_bytes.extend_from_slice(&binary[rest_idx..]);
+
+    Ok((InstructionSet::new(ins_bytes, position.0, position.1)?, resume_idx))
+}
+
+///
+/// # Returns:
+/// - The index of the remaining pen down instruction passing nearest to `position`, and where
+///   the pen is before it, relative to the top left of the page
+/// - An error if nothing is left to draw, or the instructions could not be simulated
+///
+fn nearest_movement(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, position: (f64, f64), drawn_instructions: usize) -> Result<(usize, (f64, f64)), InstructionError> {
+    let mut nearest: Option<(f64, usize, (f64, f64))> = None;
+
+    let result = preview::simulate(instruction_set.get_init(), physical_dimensions, instruction_set, &mut |instruction_idx: usize, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+        if is_pen_up || instruction_idx < drawn_instructions {
+            return;
+        }
+
+        let distance = distance_to_segment(position, from, to);
+        if nearest.is_none_or(|(nearest_distance, _, _)| distance < nearest_distance) {
+            nearest = Some((distance, instruction_idx, from));
+        }
+    });
+
+    match result {
+        Ok(()) => nearest.map(|(_, instruction_idx, from)| (instruction_idx, from)).ok_or(InstructionError::InvalidResume { reason: "nothing is left to draw".to_owned() }),
+        Err(PreviewError::Instruction(err)) => Err(err),
+        Err(err) => Err(InstructionError::InvalidResume { reason: err.to_string() }),
+    }
+}
+
+///
+/// # Returns:
+/// - The distance from a point to the nearest point on the segment from `a` to `b`
+///
+fn distance_to_segment(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = match length_squared > 0. {
+        true => (((point.0 - a.0) * dx + (point.1 - a.1) * dy) / length_squared).clamp(0., 1.),
+        false => 0.,
+    };
+    (point.0 - (a.0 + dx * t)).hypot(point.1 - (a.1 + dy * t))
+}
@@ -0,0 +1,106 @@
+use crate::drawing::stroke::{self, Stroke};
+use crate::drawing::util::placement::Rect;
+use crate::hardware::PhysicalDimensions;
+use crate::preview;
+use crate::preview::error::PreviewError;
+
+use super::InstructionSet;
+use super::error::InstructionError;
+
+///
+/// The longest arm of a registration mark, in millimetres. Marks in a narrow overlap are
+/// shortened to fit inside it.
+///
+const REGISTRATION_MARK_ARM_MM: f64 = 5.;
+
+///
+/// Splits a drawing larger than the page into tiles, each drawn on its own sheet. Neighbouring
+/// tiles overlap, and registration marks are drawn in the middle of each overlap, so both
+/// sheets have the same mark at the same place in the drawing to line them up by.
+///
+/// # Parameters:
+/// - `instruction_set`: The drawing, generated for a page as large as all the tiles together,
+///   at the same position relative to the motors as the machine's page
+/// - `physical_dimensions`: The physical dimensions of the machine, whose page is the size of one tile
+/// - `rows`: The number of rows of tiles
+/// - `cols`: The number of columns of tiles
+/// - `overlap_mm`: How far neighbouring tiles overlap, in millimetres
+///
+/// # Returns:
+/// - An instruction set per tile, row by row, each starting at its top left
+/// - An error explaining why the drawing could not be split
+///
+pub fn split(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions, rows: usize, cols: usize, overlap_mm: f64) -> Result<Vec<InstructionSet>, InstructionError> {
+    let (page_width, page_height) = (*physical_dimensions.page_width(), *physical_dimensions.page_height());
+    if rows == 0 || cols == 0 {
+        return Err(InstructionError::InvalidTiles { reason: "there must be at least one row and column of tiles".to_owned() });
+    }
+    if !(overlap_mm.is_finite() && overlap_mm > 0. && overlap_mm < page_width.min(page_height)) {
+        return Err(InstructionError::InvalidTiles { reason: format!("the overlap must be above 0mm and less than the page, which is {:.1}mm by {:.1}mm", page_width, page_height) });
+    }
+
+    let polylines = pen_down_polylines(instruction_set, physical_dimensions)?;
+
+    // the marks sit in the middle of the overlaps, so both tiles draw them at the same place
+    let arm = REGISTRATION_MARK_ARM_MM.min(overlap_mm / 2.);
+    let (step_x, step_y) = (page_width - overlap_mm, page_height - overlap_mm);
+
+    let mut tiles = Vec::with_capacity(rows * cols);
+    for row in 0..rows {
+        for col in 0..cols {
+            let tile = Rect { x: col as f64 * step_x, y: row as f64 * step_y, width: page_width, height: page_height };
+            let mut strokes = vec![];
+
+            let (left, right) = (overlap_mm / 2., page_width - overlap_mm / 2.);
+            let (top, bottom) = (overlap_mm / 2., page_height - overlap_mm / 2.);
+            let corners = [(left, top, col > 0, row > 0), (right, top, col + 1 < cols, row > 0), (left, bottom, col > 0, row + 1 < rows), (right, bottom, col + 1 < cols, row + 1 < rows)];
+            for (x, y, beside, above_or_below) in corners {
+                if !(beside || above_or_below) {
+                    continue;
+                }
+                strokes.push(Stroke { points: vec![(x - arm, y), (x + arm, y)], pen_up: false, layer: 0, label: None });
+                strokes.push(Stroke { points: vec![(x, y - arm), (x, y + arm)], pen_up: false, layer: 0, label: None });
+            }
+
+            for polyline in &polylines {
+                for clipped in tile.clip_polyline(polyline) {
+                    strokes.push(Stroke { points: clipped.iter().map(|&(x, y)| (x - tile.x, y - tile.y)).collect(), pen_up: false, layer: 0, label: None });
+                }
+            }
+
+            let (ins_bytes, init_x, init_y) = stroke::compile(&strokes, physical_dimensions)
+                .map_err(|reason| InstructionError::InvalidTiles { reason: format!("tile {} of row {} could not be compiled, {}", col + 1, row + 1, reason) })?;
+            tiles.push(InstructionSet::new(ins_bytes, init_x, init_y)?);
+        }
+    }
+
+    Ok(tiles)
+}
+
+///
+/// # Returns:
+/// - The polylines drawn with the pen down, relative to the top left of the page
+/// - An error explaining why the instructions could not be simulated
+///
+fn pen_down_polylines(instruction_set: &InstructionSet, physical_dimensions: &PhysicalDimensions) -> Result<Vec<Vec<(f64, f64)>>, InstructionError> {
+    let mut polylines: Vec<Vec<(f64, f64)>> = vec![];
+    let mut joined = false;
+
+    let result = preview::simulate(instruction_set.get_init(), physical_dimensions, instruction_set, &mut |_, from, to, is_pen_up: bool| {
+        if is_pen_up {
+            joined = false;
+            return;
+        }
+        match polylines.last_mut() {
+            Some(polyline) if joined => polyline.push(to),
+            _ => polylines.push(vec![from, to]),
+        }
+        joined = true;
+    });
+
+    match result {
+        Ok(()) => Ok(polylines),
+        Err(PreviewError::Instruction(err)) => Err(err),
+        Err(err) => Err(InstructionError::InvalidTiles { reason: err.to_string() }),
+    }
+}
@@ -1,6 +1,16 @@
+extern crate alloc;
+
 pub mod drawing;
 pub mod instruction;
 pub mod preview;
 pub mod hardware;
 pub mod client;
+#[cfg(feature = "plugin")]
 pub mod plugin;
+pub mod pipeline;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "python")]
+pub mod python;
@@ -0,0 +1,63 @@
+use thiserror::Error;
+
+use crate::client::error::ClientError;
+use crate::hardware::error::HardwareError;
+use crate::instruction::error::InstructionError;
+use crate::preview::error::PreviewError;
+
+///
+/// All errors emitted from the pipeline module.
+/// The error messages can be displayed to users on the frontend.
+///
+/// - `NoMethod`: When a job is run before a drawing method was selected
+/// - `Generation`: When the drawing method could not generate its instructions
+///     Parameters:
+///     - `reason`: The error returned by the drawing method
+/// - `OutOfPage`: When the drawing puts the pen down outside the page
+///     Parameters:
+///     - `instruction_idx`: The index of the offending instruction
+///     - `x`: The x position of the pen, relative to the top left of the page
+///     - `y`: The y position of the pen, relative to the top left of the page
+/// - `Export`: When the strokes of the drawing could not be exported
+///     Parameters:
+///     - `path`: The path the strokes were being exported to
+///     - `reason`: Why the strokes could not be exported
+/// - `Hardware`: When the drawing reaches a point the machine can not
+///     Parameters:
+///     - `HardwareError`: The reason the point can not be reached
+/// - `Instruction`: When the generated instructions were invalid
+///     Parameters:
+///     - `InstructionError`: The reason the instructions were invalid
+/// - `Preview`: When the preview could not be rendered or saved
+///     Parameters:
+///     - `PreviewError`: The reason the preview failed
+/// - `Client`: When the drawing could not be sent to the machine
+///     Parameters:
+///     - `ClientError`: The reason the transmission failed
+///
+#[derive(Error, Debug)]
+pub enum PipelineError {
+    #[error("Select a drawing method before starting the drawing.")]
+    NoMethod,
+
+    #[error("{}", .reason)]
+    Generation { reason: String },
+
+    #[error("The drawing leaves the page at x:{:.1} y:{:.1} (instruction {}). Check the drawing size and offsets.", .x, .y, .instruction_idx)]
+    OutOfPage { instruction_idx: usize, x: f64, y: f64 },
+
+    #[error("The drawing could not be exported to {}. {}", .path, .reason)]
+    Export { path: String, reason: String },
+
+    #[error(transparent)]
+    Hardware(#[from] HardwareError),
+
+    #[error(transparent)]
+    Instruction(#[from] InstructionError),
+
+    #[error(transparent)]
+    Preview(#[from] PreviewError),
+
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
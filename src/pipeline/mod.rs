@@ -0,0 +1,1168 @@
+//!
+//! A high-level pipeline to generate, check, preview and draw a drawing in the correct order
+//!
+//! Generating and checking drawings is always available. Previews need the `image` feature, and
+//! drawing on the machine needs the `async` feature.
+//!
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(feature = "async")]
+use tokio::sync::{Mutex, Notify};
+#[cfg(feature = "async")]
+use std::sync::Arc;
+use serde::Serialize;
+use error::PipelineError;
+
+#[cfg(feature = "async")]
+use crate::client;
+#[cfg(feature = "async")]
+use crate::client::error::ClientError;
+use crate::client::metrics::TransmissionReport;
+#[cfg(feature = "async")]
+use crate::client::schedule::PauseSchedule;
+#[cfg(feature = "async")]
+use crate::client::state::{ClientState, ListenEvent, MachineConfiguration};
+#[cfg(feature = "async")]
+use crate::drawing::background;
+use crate::drawing::{registry, DrawMethod};
+use crate::drawing::stroke::{self, DegenerateReport, Stroke};
+use crate::drawing::registration::RegistrationParameters;
+use crate::hardware::PhysicalDimensions;
+#[cfg(feature = "async")]
+use crate::instruction;
+use crate::instruction::{InstructionSet, LintWarning, ParkingAction, PrimingOptions};
+use crate::drawing::util::placement::Margins;
+use crate::preview;
+use crate::preview::PreviewSink;
+use crate::preview::margins::{MarginCheck, MarginReport};
+
+pub mod error;
+
+///
+/// How far, in millimetres, the pen may stray outside the page while drawing before the drawing
+/// is rejected. This absorbs the rounding of positions to whole motor steps.
+///
+const PAGE_TOLERANCE_MM: f64 = 0.5;
+
+///
+/// How many times a job connects to the machine again when a chunk couldn't be written, before
+/// the job fails.
+///
+#[cfg(feature = "async")]
+const RECONNECT_ATTEMPTS: usize = 3;
+
+///
+/// The progress of a `DrawingJob`, emitted as each stage completes.
+///
+/// - `Generating`: The drawing method is still generating, only emitted by `DrawingJob::run`
+///     Parameters:
+///     - `samples`: The number of positions sampled so far
+/// - `Generated`: The drawing method generated its instructions
+///     Parameters:
+///     - `bytes`: The length of the instructions, in bytes
+///     - `start`: The start position of the pen, relative to the top left of the page
+///     - `content_hash`: The `InstructionSet::content_hash` of the drawing
+/// - `Degenerate`: The drawing method drew strokes or segments which leave a blob rather than a
+///   line, emitted before `Generated`
+///     Parameters:
+///     - `report`: How many of each kind of degenerate stroke or segment were drawn
+///     - `removed`: Whether they were removed before compiling, see `DrawingJob::with_degenerate_removal`
+/// - `SvgSaved`: The strokes of the drawing method were exported as an SVG, emitted before `Generated`
+///     Parameters:
+///     - `path`: The path of the SVG
+/// - `Duplicate`: The drawing is the same as an earlier job's, only emitted by `run_queue`
+///     Parameters:
+///     - `job`: The index of the earlier job
+/// - `MarginViolations`: Pen down strokes cross the margins or the edge of the page, emitted before
+///   `Validated`, or before the job fails if a stroke leaves the page
+///     Parameters:
+///     - `report`: Each offending stroke, with its instructions and how much of it is outside
+/// - `Validated`: Every position of the drawing was checked against the page and machine
+/// - `PreviewSaved`: The preview image was saved
+///     Parameters:
+///     - `path`: The path of the preview image
+/// - `MovedToStart`: The pen was moved to the start position of the drawing
+/// - `Connected`: The machine accepted the drawing
+///     Parameters:
+///     - `protocol_version`: The protocol version of the drawing machine
+///     - `instruction_buffer_size`: The size of the machine's instruction buffer
+/// - `LintWarnings`: The drawing has suspicious patterns for the connected machine, emitted
+///   after `Connected` and before streaming, see `instruction::lint`
+///     Parameters:
+///     - `warnings`: Each suspicious pattern, in drawing order
+/// - `WaitingForUser`: The machine was paused at a pause point of the job's schedule, and waits
+///   for the user to resume it
+///     Parameters:
+///     - `reason`: The pause points reached
+/// - `Streaming`: A chunk of instructions was sent to the machine
+///     Parameters:
+///     - `chunk`: The number of chunks sent so far
+///     - `num_chunks`: The total number of chunks
+///     - `secs_remaining`: The estimated number of seconds left in the drawing
+///     - `ack_latency_ms`: The time between the previous chunk being sent and the machine asking
+///       for this one, in milliseconds
+/// - `Transmitted`: The drawing finished or was stopped, emitted before `Finished` or `Stopped`
+///     Parameters:
+///     - `report`: A summary of the transmission to the machine
+/// - `Finished`: Every instruction was drawn
+/// - `Stopped`: The machine stopped the drawing before it finished
+/// - `ConnectionLost`: The connection to the machine failed during the drawing
+///     Parameters:
+///     - `chunk`: The number of chunks sent before the connection failed, where a resumed
+///       drawing would start
+///     - `reason`: Why the connection failed
+/// - `Reconnecting`: A chunk couldn't be written, so the job is connecting to the machine again
+///   to carry on from it, emitted after `ConnectionLost`
+///     Parameters:
+///     - `chunk`: The number of chunks sent, where the drawing carries on
+///     - `attempt`: The number of times the job has reconnected, counting this one
+/// - `PaperAdvanced`: The paper roll was advanced after the drawing finished
+///     Parameters:
+///     - `mm`: How far the paper was advanced, in millimetres
+///
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    Generating { samples: usize },
+    Generated { bytes: usize, start: (f64, f64), content_hash: u64 },
+    Degenerate { report: DegenerateReport, removed: bool },
+    SvgSaved { path: String },
+    Duplicate { job: usize },
+    MarginViolations { report: MarginReport },
+    Validated,
+    PreviewSaved { path: String },
+    MovedToStart,
+    Connected { protocol_version: u16, instruction_buffer_size: u32 },
+    LintWarnings { warnings: Vec<LintWarning> },
+    WaitingForUser { reason: String },
+    Streaming { chunk: usize, num_chunks: usize, secs_remaining: u64, ack_latency_ms: f64 },
+    Transmitted { report: TransmissionReport },
+    Finished,
+    Stopped,
+    ConnectionLost { chunk: usize, reason: String },
+    Reconnecting { chunk: usize, attempt: usize },
+    PaperAdvanced { mm: f64 },
+}
+
+///
+/// A drawing, as its generator produces it.
+///
+/// - `Strokes`: The strokes of a drawing method, which are checked and compiled by the job
+/// - `Instructions`: Instructions which were already generated, as an (instruction set,
+///   start_x, start_y)
+///
+enum Generated {
+    Strokes(Vec<Stroke>),
+    Instructions((Vec<u8>, f64, f64)),
+}
+
+///
+/// A drawing generator, taking the physical dimensions and returning the drawing.
+///
+type Generator<'j> = Box<dyn FnOnce(&PhysicalDimensions) -> Result<Generated, String> + Send + 'j>;
+
+///
+/// A drawing, from choosing the drawing method through to streaming it to the machine. The job
+/// runs each stage in order: generate → validate bounds → preview → move to start → connect →
+/// stream, so frontends don't need to orchestrate the stages themselves.
+///
+/// # Fields:
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `generator`: The selected drawing method and its parameters
+/// - `registration_marks`: The alignment marks to draw before the drawing, if any
+/// - `priming`: How to re-prime the pen during the drawing, if it needs it
+/// - `parking`: Where to park the pen once the drawing finishes, if anywhere
+/// - `line_width_mm`: The width of the pen's line, below which a stroke is degenerate
+/// - `remove_degenerate`: Whether to remove degenerate strokes and segments before compiling
+/// - `reproducible`: Whether to snap the strokes to `stroke::REPRODUCIBLE_GRID_MM` before compiling
+/// - `simplify_tolerance_mm`: How far strokes may stray when simplified before compiling, if they are
+/// - `margins`: The space to keep clear around each edge of the page, checked when validating
+/// - `svg_path`: Where to export the strokes of the drawing method as an SVG, if anywhere
+/// - `preview_path`: Where to save the preview image, if a preview is wanted
+/// - `preview_metadata`: The settings of the drawing, embedded in the preview image
+/// - `machine`: The (address, port) of the machine, if the drawing should be drawn
+/// - `paper_advance`: How far to advance the paper roll once the drawing finishes, in millimetres
+/// - `pause_schedule`: Where to pause the drawing for the user, such as to swap pens
+/// - `resume`: Notified by the user to resume the drawing after a pause
+///
+pub struct DrawingJob<'j> {
+    physical_dimensions: PhysicalDimensions,
+    generator: Option<Generator<'j>>,
+    registration_marks: Option<RegistrationParameters>,
+    priming: Option<PrimingOptions>,
+    parking: Option<ParkingAction>,
+    line_width_mm: f64,
+    remove_degenerate: bool,
+    reproducible: bool,
+    simplify_tolerance_mm: Option<f64>,
+    margins: Margins,
+    svg_path: Option<String>,
+    #[cfg(feature = "image")]
+    preview_path: Option<String>,
+    #[cfg(feature = "image")]
+    preview_metadata: preview::metadata::PreviewMetadata,
+    #[cfg(feature = "async")]
+    machine: Option<(String, u16)>,
+    #[cfg(feature = "async")]
+    paper_advance: Option<f64>,
+    #[cfg(feature = "async")]
+    pause_schedule: PauseSchedule,
+    #[cfg(feature = "async")]
+    resume: Arc<Notify>,
+}
+
+impl<'j> DrawingJob<'j> {
+    ///
+    /// # Parameters:
+    /// - `physical_dimensions`: The physical dimensions of the machine
+    ///
+    /// # Returns:
+    /// - A new job, with no drawing method selected
+    ///
+    pub fn new(physical_dimensions: PhysicalDimensions) -> DrawingJob<'j> {
+        DrawingJob {
+            physical_dimensions,
+            generator: None,
+            registration_marks: None,
+            priming: None,
+            parking: None,
+            line_width_mm: 0.,
+            remove_degenerate: false,
+            reproducible: false,
+            simplify_tolerance_mm: None,
+            margins: Margins::default(),
+            svg_path: None,
+            #[cfg(feature = "image")]
+            preview_path: None,
+            #[cfg(feature = "image")]
+            preview_metadata: preview::metadata::PreviewMetadata::default(),
+            #[cfg(feature = "async")]
+            machine: None,
+            #[cfg(feature = "async")]
+            paper_advance: None,
+            #[cfg(feature = "async")]
+            pause_schedule: PauseSchedule::new(),
+            #[cfg(feature = "async")]
+            resume: Arc::new(Notify::new()),
+        }
+    }
+
+    ///
+    /// Selects the drawing method to generate the drawing with.
+    ///
+    /// # Parameters:
+    /// - `method`: The drawing method
+    /// - `parameters`: The parameters of the drawing method
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_method<M>(mut self, method: M, parameters: M::DrawParameters) -> DrawingJob<'j>
+    where
+        M: DrawMethod + Send + 'j,
+        M::DrawParameters: Send + 'j {
+        #[cfg(feature = "image")]
+        {
+            self.preview_metadata = preview::metadata::PreviewMetadata { method_id: Some(method.get_id().to_owned()), ..Default::default() };
+        }
+        self.generator = Some(Box::new(move |physical_dimensions| method.gen_strokes(physical_dimensions, &parameters).map(Generated::Strokes)));
+        self
+    }
+
+    ///
+    /// Selects the drawing method to generate the drawing with by its backend ID, for frontends
+    /// which receive the method and parameters at runtime.
+    ///
+    /// # Parameters:
+    /// - `method_id`: The backend ID of the drawing method
+    /// - `parameters_json`: The parameters of the drawing method, as a JSON object
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_method_id(mut self, method_id: &str, parameters_json: &str) -> DrawingJob<'j> {
+        #[cfg(feature = "image")]
+        {
+            self.preview_metadata = preview::metadata::PreviewMetadata::for_method(method_id, parameters_json);
+        }
+        let (method_id, parameters_json) = (method_id.to_owned(), parameters_json.to_owned());
+        self.generator = Some(Box::new(move |physical_dimensions| registry::gen_strokes(&method_id, physical_dimensions, &parameters_json).map(Generated::Strokes)));
+        self
+    }
+
+    ///
+    /// Draws instructions which were already generated, such as a drawing loaded from a file,
+    /// rather than generating them with a drawing method.
+    ///
+    /// # Parameters:
+    /// - `ins_bytes`: The drawing instruction bytes
+    /// - `start_x`: The initial x position of the pen, relative to the top left of the page
+    /// - `start_y`: The initial y position of the pen, relative to the top left of the page
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_instructions(mut self, ins_bytes: Vec<u8>, start_x: f64, start_y: f64) -> DrawingJob<'j> {
+        #[cfg(feature = "image")]
+        {
+            self.preview_metadata = preview::metadata::PreviewMetadata::default();
+        }
+        self.generator = Some(Box::new(move |_| Ok(Generated::Instructions((ins_bytes, start_x, start_y)))));
+        self
+    }
+
+    ///
+    /// Draws alignment marks before the drawing, such as to line the paper up again for another
+    /// pen. The marks are part of the generated instructions, so they are previewed and checked too.
+    ///
+    /// # Parameters:
+    /// - `registration_marks`: The marks to draw
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_registration_marks(mut self, registration_marks: RegistrationParameters) -> DrawingJob<'j> {
+        self.registration_marks = Some(registration_marks);
+        self
+    }
+
+    ///
+    /// Re-primes the pen on a scribble pad during the drawing, for felt and gel pens which dry
+    /// out during long drawings. The excursions to the pad are previewed and checked too.
+    ///
+    /// # Parameters:
+    /// - `priming`: Where the scribble pad is, and how often to re-prime
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_priming(mut self, priming: PrimingOptions) -> DrawingJob<'j> {
+        self.priming = Some(priming);
+        self
+    }
+
+    ///
+    /// Parks the pen once the drawing finishes, rather than leaving it wherever the drawing
+    /// ended. The travel to the parking position is previewed and checked too.
+    ///
+    /// # Parameters:
+    /// - `parking`: Where to park the pen
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_parking(mut self, parking: ParkingAction) -> DrawingJob<'j> {
+        self.parking = Some(parking);
+        self
+    }
+
+    ///
+    /// Sets the width of the pen's line, so strokes of a drawing method shorter than it are
+    /// reported by `JobEvent::Degenerate`. Strokes and moves which don't move the pen at all are
+    /// always reported.
+    ///
+    /// # Parameters:
+    /// - `line_width_mm`: The width of the pen's line, in millimetres
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_line_width(mut self, line_width_mm: f64) -> DrawingJob<'j> {
+        self.line_width_mm = line_width_mm;
+        self
+    }
+
+    ///
+    /// Sets the margins to keep clear around each edge of the page. Strokes crossing them are
+    /// reported by `JobEvent::MarginViolations`, but only strokes leaving the page fail the job.
+    ///
+    /// # Parameters:
+    /// - `margins`: The space to keep clear around each edge of the page
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_margins(mut self, margins: Margins) -> DrawingJob<'j> {
+        self.margins = margins;
+        self
+    }
+
+    ///
+    /// Removes the strokes and segments reported by `JobEvent::Degenerate` before compiling, so
+    /// stray dots from degenerate samples aren't drawn. Methods which draw dots on purpose, such
+    /// as stippling, lose them too.
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_degenerate_removal(mut self) -> DrawingJob<'j> {
+        self.remove_degenerate = true;
+        self
+    }
+
+    ///
+    /// Snaps the strokes of the drawing method to a grid far finer than a motor step before
+    /// compiling, so a seeded drawing compiles to byte-identical instructions on every platform,
+    /// see `stroke::quantise`. Belt sag is modelled with hyperbolic functions while compiling, so
+    /// machines configured with a sag model may still differ.
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_reproducibility(mut self) -> DrawingJob<'j> {
+        self.reproducible = true;
+        self
+    }
+
+    ///
+    /// Simplifies the strokes of the drawing method before compiling, see `stroke::simplify`.
+    /// Methods sampling smooth curves finely draw the same lines in far fewer instructions.
+    ///
+    /// # Parameters:
+    /// - `tolerance_mm`: The furthest a simplified stroke may stray from the original, in millimetres
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_simplification(mut self, tolerance_mm: f64) -> DrawingJob<'j> {
+        self.simplify_tolerance_mm = Some(tolerance_mm);
+        self
+    }
+
+    ///
+    /// Exports the strokes of the drawing method as an SVG once they are generated, with strokes
+    /// grouped by their labels. Registration marks, priming and parking aren't part of the
+    /// drawing method's strokes, so they aren't exported.
+    ///
+    /// # Parameters:
+    /// - `path`: The path to save the SVG to
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    pub fn with_svg(mut self, path: &str) -> DrawingJob<'j> {
+        self.svg_path = Some(path.to_owned());
+        self
+    }
+
+    ///
+    /// Saves a preview once the drawing is generated. The drawing method and its parameters are
+    /// embedded in the image, see `preview::metadata::read_metadata`.
+    ///
+    /// # Parameters:
+    /// - `path`: The path to save the preview image to, once the drawing is generated
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    #[cfg(feature = "image")]
+    pub fn with_preview(mut self, path: &str) -> DrawingJob<'j> {
+        self.preview_path = Some(path.to_owned());
+        self
+    }
+
+    ///
+    /// # Parameters:
+    /// - `addr`: The IP address of the machine to draw on
+    /// - `port`: The port address of the machine
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    #[cfg(feature = "async")]
+    pub fn with_machine(mut self, addr: &str, port: u16) -> DrawingJob<'j> {
+        self.machine = Some((addr.to_owned(), port));
+        self
+    }
+
+    ///
+    /// Advances the paper once the drawing finishes, for machines with a paper roll. The advance
+    /// should be at least the height of the drawing, so the next drawing starts on clean paper.
+    ///
+    /// # Parameters:
+    /// - `mm`: How far to advance the paper, in millimetres
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    #[cfg(feature = "async")]
+    pub fn with_paper_advance(mut self, mm: f64) -> DrawingJob<'j> {
+        self.paper_advance = Some(mm);
+        self
+    }
+
+    ///
+    /// Pauses the drawing at the schedule's pause points, emitting `JobEvent::WaitingForUser`.
+    /// The machine is resumed each time `resume` is notified. Pausing at a layer needs the
+    /// schedule's layer starts.
+    ///
+    /// # Parameters:
+    /// - `schedule`: Where to pause the drawing
+    /// - `resume`: Notified by the user to resume the drawing after a pause
+    ///
+    /// # Returns:
+    /// - The modified job
+    ///
+    #[cfg(feature = "async")]
+    pub fn with_pause_schedule(mut self, schedule: PauseSchedule, resume: Arc<Notify>) -> DrawingJob<'j> {
+        self.pause_schedule = schedule;
+        self.resume = resume;
+        self
+    }
+
+    ///
+    /// Generates the drawing, checks it stays on the page and within the machine's reach, and
+    /// saves the preview if one was requested. No connection is made to the machine.
+    ///
+    /// # Parameters:
+    /// - `emit`: A callback receiving the progress of each stage
+    ///
+    /// # Returns:
+    /// - The validated instruction set
+    /// - A `PipelineError` explaining which stage failed, and why
+    ///
+    pub fn generate<F: FnMut(JobEvent)>(&mut self, mut emit: F) -> Result<InstructionSet, PipelineError> {
+        let generator = self.generator.take().ok_or(PipelineError::NoMethod)?;
+        let mut generated = match generator(&self.physical_dimensions).map_err(|reason| PipelineError::Generation { reason })? {
+            Generated::Strokes(mut strokes) => {
+                if self.reproducible {
+                    strokes = stroke::quantise(strokes, stroke::REPRODUCIBLE_GRID_MM);
+                }
+                // after snapping, so which points are kept doesn't depend on the platform either
+                if let Some(tolerance_mm) = self.simplify_tolerance_mm {
+                    strokes = stroke::simplify(strokes, tolerance_mm);
+                }
+                let report = stroke::find_degenerate(&strokes, self.line_width_mm);
+                if !report.is_empty() {
+                    if self.remove_degenerate {
+                        strokes = stroke::remove_degenerate(strokes, self.line_width_mm);
+                    }
+                    emit(JobEvent::Degenerate { report, removed: self.remove_degenerate });
+                }
+                if let Some(path) = &self.svg_path {
+                    std::fs::write(path, stroke::to_svg(&strokes, &self.physical_dimensions)).map_err(|err| PipelineError::Export { path: path.clone(), reason: err.to_string() })?;
+                    emit(JobEvent::SvgSaved { path: path.clone() });
+                }
+                stroke::compile(&strokes, &self.physical_dimensions).map_err(|reason| PipelineError::Generation { reason })?
+            },
+            Generated::Instructions(generated) => match &self.svg_path {
+                Some(path) => return Err(PipelineError::Export { path: path.clone(), reason: "Only drawings generated by a drawing method have strokes to export.".to_owned() }),
+                None => generated,
+            },
+        };
+        if let Some(registration_marks) = &self.registration_marks {
+            generated = registration_marks.prepend_to(&self.physical_dimensions, generated).map_err(|reason| PipelineError::Generation { reason })?;
+        }
+        let (bytes, start_x, start_y) = generated;
+
+        let mut instruction_set = InstructionSet::new(bytes, start_x, start_y)?;
+        if let Some(priming) = &self.priming {
+            instruction_set = instruction_set.insert_priming(&self.physical_dimensions, priming)?;
+        }
+        if let Some(parking) = self.parking {
+            instruction_set = instruction_set.append_parking(&self.physical_dimensions, parking)?;
+        }
+        emit(JobEvent::Generated { bytes: instruction_set.get_binary().len(), start: instruction_set.get_init(), content_hash: instruction_set.content_hash() });
+
+        let (report, bounds) = validate_bounds(&self.physical_dimensions, &self.margins, &instruction_set)?;
+        if !report.is_empty() {
+            emit(JobEvent::MarginViolations { report });
+        }
+        bounds?;
+        emit(JobEvent::Validated);
+
+        #[cfg(feature = "image")]
+        if let Some(path) = &self.preview_path {
+            preview::render_preview(instruction_set.get_init(), &self.physical_dimensions, &instruction_set, &preview::PreviewOptions::default())?.save_with_metadata(path, &self.preview_metadata)?;
+            emit(JobEvent::PreviewSaved { path: path.clone() });
+        }
+
+        Ok(instruction_set)
+    }
+}
+
+#[cfg(feature = "async")]
+impl DrawingJob<'static> {
+    ///
+    /// Runs every stage of the job. If no machine was given, the job finishes after the preview.
+    /// If a paper advance was given, the paper is advanced once the drawing finishes, but not if
+    /// the machine stopped it. Generation runs on tokio's blocking thread pool, so the runtime isn't blocked while the
+    /// drawing method works.
+    ///
+    /// # Parameters:
+    /// - `emit`: A callback receiving the progress of each stage
+    ///
+    /// # Returns:
+    /// - The instruction set which was drawn
+    /// - A `PipelineError` explaining which stage failed, and why
+    ///
+    pub async fn run<F: FnMut(JobEvent) + Send>(mut self, mut emit: F) -> Result<InstructionSet, PipelineError> {
+        let machine = self.machine.take();
+        let paper_advance = self.paper_advance.take();
+        let pause_schedule = std::mem::take(&mut self.pause_schedule);
+        let resume = self.resume.clone();
+        let physical_dimensions = self.physical_dimensions.clone();
+
+        // the stages are collected on the blocking thread and emitted here, as `emit` is not 'static
+        let (events, generated) = background::spawn_generation(move || {
+            let mut events = vec![];
+            let generated = self.generate(|event| events.push(event));
+            (events, generated)
+        }, |samples| emit(JobEvent::Generating { samples })).await.map_err(|reason| PipelineError::Generation { reason })?;
+        events.into_iter().for_each(&mut emit);
+        let instruction_set = generated?;
+
+        let Some((addr, port)) = machine else {
+            return Ok(instruction_set);
+        };
+
+        // the pen must be at the start of the drawing before it is streamed, and moving it uses
+        // its own blocking connection to the machine
+        let (start_x, start_y) = instruction_set.get_init();
+        let (move_addr, move_dimensions) = (addr.clone(), physical_dimensions.clone());
+        match tokio::task::spawn_blocking(move || client::move_to_start(&move_addr, port, &move_dimensions, start_x, start_y)).await {
+            Ok(result) => result?,
+            Err(err) => return Err(ClientError::InvalidBytes { reason: format!("Moving the pen to the start position was interrupted. {}", err) }.into()),
+        };
+        emit(JobEvent::MovedToStart);
+
+        let (mut socket, machine_config) = ClientState::new(&addr, port).await?;
+        emit(JobEvent::Connected { protocol_version: machine_config.protocol_version, instruction_buffer_size: machine_config.instruction_buffer_size });
+        let warnings = instruction::lint(&instruction_set, &physical_dimensions, &machine_config)?;
+        if !warnings.is_empty() {
+            emit(JobEvent::LintWarnings { warnings });
+        }
+
+        // a chunk which couldn't be written is sent again on a new connection, as the machine
+        // asked for it with an empty buffer
+        let buf_idx = Arc::new(Mutex::new(0));
+        let mut reconnects = 0;
+        let finished = loop {
+            match stream(&mut socket, &buf_idx, &instruction_set, &machine_config, &pause_schedule, &resume, &mut emit).await? {
+                Streamed::Finished => break true,
+                Streamed::Stopped => break false,
+                Streamed::Interrupted { err } => {
+                    // a failed reconnect uses an attempt too, and once they run out the latest
+                    // failure is reported, as it's why the drawing couldn't carry on
+                    let mut latest_err = err;
+                    socket = loop {
+                        if reconnects == RECONNECT_ATTEMPTS {
+                            return Err(latest_err.into());
+                        }
+                        reconnects += 1;
+                        emit(JobEvent::Reconnecting { chunk: *buf_idx.lock().await, attempt: reconnects });
+                        match ClientState::reconnect(&addr, port).await {
+                            Ok((socket, _)) => break socket,
+                            Err(err) => latest_err = err,
+                        }
+                    };
+                },
+            }
+        };
+
+        if let (true, Some(mm)) = (finished, paper_advance) {
+            match tokio::task::spawn_blocking(move || client::advance_paper(&addr, port, mm)).await {
+                Ok(result) => result?,
+                Err(err) => return Err(ClientError::InvalidBytes { reason: format!("Advancing the paper was interrupted. {}", err) }.into()),
+            };
+            emit(JobEvent::PaperAdvanced { mm });
+        }
+
+        Ok(instruction_set)
+    }
+}
+
+///
+/// Runs jobs one after another, such as many small drawings on a paper roll, each advancing the
+/// paper for the next with `DrawingJob::with_paper_advance`. If the machine stops a drawing,
+/// the rest of the queue is not drawn, so nothing is drawn over paper which wasn't advanced.
+/// A job drawing the same as an earlier job is still drawn, but emits `JobEvent::Duplicate`, as
+/// it may have been queued twice by mistake.
+///
+/// # Parameters:
+/// - `jobs`: The jobs, in the order they are drawn
+/// - `emit`: A callback receiving the index of the job and the progress of its stages
+///
+/// # Returns:
+/// - The instruction sets of the jobs which were run, in order
+/// - A `PipelineError` explaining which stage of the failing job failed, and why. The jobs
+///   after it are not run
+///
+#[cfg(feature = "async")]
+pub async fn run_queue<F: FnMut(usize, JobEvent) + Send>(jobs: Vec<DrawingJob<'static>>, mut emit: F) -> Result<Vec<InstructionSet>, PipelineError> {
+    let mut drawn = Vec::with_capacity(jobs.len());
+    let mut content_hashes: Vec<u64> = Vec::with_capacity(jobs.len());
+
+    for (index, job) in jobs.into_iter().enumerate() {
+        let mut stopped = false;
+        let instruction_set = job.run(|event| {
+            stopped |= event == JobEvent::Stopped;
+            let duplicate = match event {
+                JobEvent::Generated { content_hash, .. } => content_hashes.iter().position(|&earlier| earlier == content_hash),
+                _ => None,
+            };
+            emit(index, event);
+            if let Some(job) = duplicate {
+                emit(index, JobEvent::Duplicate { job });
+            }
+        }).await?;
+        content_hashes.push(instruction_set.content_hash());
+        drawn.push(instruction_set);
+
+        if stopped {
+            break;
+        }
+    }
+
+    Ok(drawn)
+}
+
+///
+/// Simulates an instruction set, checking the pen never draws outside the page and never
+/// reaches a point the machine can not, and finding every stroke crossing the margins.
+///
+/// # Parameters:
+/// - `physical_dimensions`: The physical dimensions of the machine
+/// - `margins`: The space to keep clear around each edge of the page
+/// - `instruction_set`: The instruction set to check
+///
+/// # Returns:
+/// - The strokes crossing the margins or the edge of the page, and either void if the whole
+///   drawing can be drawn or a `PipelineError` describing the first position which can not be drawn
+/// - A `PipelineError` if the drawing could not be simulated
+///
+fn validate_bounds(physical_dimensions: &PhysicalDimensions, margins: &Margins, instruction_set: &InstructionSet) -> Result<(MarginReport, Result<(), PipelineError>), PipelineError> {
+    let (page_width, page_height) = (*physical_dimensions.page_width(), *physical_dimensions.page_height());
+    let mut first_error: Option<PipelineError> = None;
+    let mut check = MarginCheck::new(page_width, page_height, margins, PAGE_TOLERANCE_MM);
+
+    preview::simulate(instruction_set.get_init(), physical_dimensions, instruction_set, &mut |instruction_idx: usize, from: (f64, f64), (x, y): (f64, f64), is_pen_up: bool| {
+        check.segment(instruction_idx, from, (x, y), is_pen_up);
+        if first_error.is_some() {
+            return;
+        }
+
+        let off_page = x < -PAGE_TOLERANCE_MM || y < -PAGE_TOLERANCE_MM || x > page_width + PAGE_TOLERANCE_MM || y > page_height + PAGE_TOLERANCE_MM;
+        if !is_pen_up && off_page {
+            first_error = Some(PipelineError::OutOfPage { instruction_idx, x, y });
+        } else if let Err(err) = physical_dimensions.check_bounds(x, y) {
+            first_error = Some(err.into());
+        }
+    })?;
+
+    let bounds = match first_error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    };
+    Ok((check.finish(), bounds))
+}
+
+///
+/// Streams an instruction set to a connected machine with `ClientState::listen`'s loop, sending
+/// a chunk of instructions each time the machine asks for more, until the drawing is finished.
+/// The machine is resumed each time `resume` is notified.
+///
+/// # Parameters:
+/// - `socket`: The connection to the machine, after the greeting
+/// - `buf_idx`: The number of chunks sent, where the drawing carries on from
+/// - `instruction_set`: The instruction set to draw
+/// - `machine_config`: The configuration reported by the machine
+/// - `schedule`: Where to pause the drawing for the user
+/// - `resume`: Notified by the user to resume the drawing after a pause
+/// - `emit`: A callback receiving the progress of the drawing
+///
+/// # Returns:
+/// - How the drawing ended, where an interrupted drawing can carry on from `buf_idx` on a new
+///   connection, after emitting `JobEvent::ConnectionLost`
+/// - A `PipelineError` if the connection failed during the drawing, after emitting
+///   `JobEvent::ConnectionLost`
+///
+#[cfg(feature = "async")]
+async fn stream<S, F>(socket: &mut S, buf_idx: &Arc<Mutex<usize>>, instruction_set: &InstructionSet, machine_config: &MachineConfiguration, schedule: &PauseSchedule, resume: &Notify, emit: &mut F) -> Result<Streamed, PipelineError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnMut(JobEvent) {
+    let (mut reader, writer) = tokio::io::split(socket);
+    let writer = Arc::new(Mutex::new(Some(writer)));
+    let mut streamed = Streamed::Stopped;
+    let mut resumable = false;
+
+    let mut on_event = |event| match event {
+        ListenEvent::Drawing { chunk, num_chunks, secs_remaining, ack_latency, .. } => emit(JobEvent::Streaming { chunk, num_chunks, secs_remaining, ack_latency_ms: ack_latency.as_secs_f64() * 1000. }),
+        ListenEvent::WaitingForUser { reason } => emit(JobEvent::WaitingForUser { reason }),
+        ListenEvent::ConnectionLost { chunk, reason, resumable: write_failed } => {
+            resumable = write_failed;
+            emit(JobEvent::ConnectionLost { chunk, reason });
+        },
+        ListenEvent::Finished { report } => {
+            streamed = Streamed::Finished;
+            emit(JobEvent::Transmitted { report });
+            emit(JobEvent::Finished);
+        },
+        ListenEvent::Stopped { report } => {
+            emit(JobEvent::Transmitted { report });
+            emit(JobEvent::Stopped);
+        },
+    };
+    let listening = ClientState::stream(&mut reader, &writer, buf_idx, instruction_set, machine_config, schedule, &mut on_event);
+    let resuming = async {
+        loop {
+            resume.notified().await;
+            if let Some(writer) = writer.lock().await.as_mut() && let Err(err) = ClientState::pause(writer, false, |_| {}).await {
+                return err;
+            }
+        }
+    };
+
+    // resuming only finishes if the resume couldn't be sent, so the drawing ends with listening
+    let failed_resume = match tokio::select! {
+        result = listening => result.map(|_| None),
+        err = resuming => Ok(Some(err)),
+    } {
+        Err(err) if resumable => return Ok(Streamed::Interrupted { err }),
+        result => result?,
+    };
+    if let Some(err) = failed_resume {
+        if let ClientError::ConnectionLost { reason } = &err {
+            let chunk = *buf_idx.lock().await;
+            emit(JobEvent::ConnectionLost { chunk, reason: reason.clone() });
+        }
+        return Err(err.into());
+    }
+
+    Ok(streamed)
+}
+
+///
+/// How streaming a drawing to the machine ended.
+///
+/// - `Finished`: Every instruction was drawn
+/// - `Stopped`: The machine stopped the drawing before it finished
+/// - `Interrupted`: A chunk couldn't be written while the machine waited for it, so the drawing
+///   can carry on from it on a new connection
+///     Parameters:
+///     - `err`: Why the chunk couldn't be written
+///
+#[cfg(feature = "async")]
+enum Streamed {
+    Finished,
+    Stopped,
+    Interrupted { err: ClientError },
+}
+
+///
+/// Tests relating to the drawing pipeline.
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drawing::lines::{LinesMethod, LinesParameters};
+
+    #[test]
+    fn generates_and_validates_in_order() {
+        let mut events = vec![];
+        let mut job = DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 });
+
+        let instruction_set = job.generate(|event| events.push(event)).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], JobEvent::Generated { bytes: instruction_set.get_binary().len(), start: instruction_set.get_init(), content_hash: instruction_set.content_hash() });
+        assert_eq!(events[1], JobEvent::Validated);
+    }
+
+    #[test]
+    fn draws_registration_marks_before_the_drawing() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let marks: RegistrationParameters = serde_json::from_str(r#"{"placement": {"margins": {"top": 10, "right": 10, "bottom": 10, "left": 10}}, "mark_size": 5, "centre_cross": true}"#).unwrap();
+        let drawing = DrawingJob::new(physical_dimensions.clone())
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+            .generate(|_| {})
+            .unwrap();
+
+        let instruction_set = DrawingJob::new(physical_dimensions)
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+            .with_registration_marks(marks)
+            .generate(|_| {})
+            .unwrap();
+
+        // the marks start at the top left crop mark, and the drawing follows them unchanged
+        assert_eq!(instruction_set.get_init(), (10., 15.));
+        assert!(instruction_set.get_binary().ends_with(drawing.get_binary()));
+    }
+
+    #[test]
+    fn simplifies_strokes_before_compiling() {
+        let parameters = crate::drawing::registry::default_parameters("superformula").unwrap();
+        let generate = |tolerance_mm: Option<f64>| {
+            let mut job = DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.)).with_method_id("superformula", &parameters);
+            if let Some(tolerance_mm) = tolerance_mm {
+                job = job.with_simplification(tolerance_mm);
+            }
+            job.generate(|_| {}).unwrap()
+        };
+
+        // a finely sampled curve draws the same shape in far fewer instructions
+        let (plain, simplified) = (generate(None), generate(Some(0.1)));
+        assert!(simplified.get_binary().len() * 2 < plain.get_binary().len(), "{} {}", simplified.get_binary().len(), plain.get_binary().len());
+        assert_eq!(simplified.get_init(), plain.get_init());
+    }
+
+    #[test]
+    fn rejects_drawings_leaving_the_page() {
+        // 40 lines, 10mm apart, run off the bottom of a 250mm page
+        let mut job = DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method_id("lines", r#"{"num_lines": 40, "horizontal_margin": 10}"#);
+
+        assert!(matches!(job.generate(|_| {}), Err(PipelineError::OutOfPage { .. })));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn embeds_the_method_in_the_preview() {
+        let path = std::env::temp_dir().join("bbcore_pipeline_preview.png");
+        let path = path.to_str().unwrap();
+        let mut events = vec![];
+        DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method_id("lines", r#"{"num_lines": 3, "horizontal_margin": 10}"#)
+            .with_preview(path)
+            .generate(|event| events.push(event))
+            .unwrap();
+
+        let metadata = preview::metadata::read_metadata(path).unwrap();
+        assert_eq!(events.last(), Some(&JobEvent::PreviewSaved { path: path.to_owned() }));
+        assert_eq!(metadata.method_id.as_deref(), Some("lines"));
+        assert_eq!(metadata.parameters.as_deref(), Some(r#"{"num_lines": 3, "horizontal_margin": 10}"#));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn reports_strokes_in_the_margins() {
+        let mut events = vec![];
+        DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+            .with_margins(Margins::symmetric(20., 0.))
+            .generate(|event| events.push(event))
+            .unwrap();
+
+        // the zigzag is one stroke, each line running 10mm into the left and right margins less
+        // the tolerance, and each 9mm step down lying in the margin
+        let JobEvent::MarginViolations { report } = &events[1] else { panic!("expected a margin report, got {:?}", events[1]) };
+        assert_eq!(report.violations.len(), 1);
+        assert_eq!(report.off_page().count(), 0);
+        assert!((report.violations[0].length_outside_margins - 3. * (19. + 9.)).abs() < 3.);
+        assert_eq!(events[2], JobEvent::Validated);
+
+        // strokes leaving the page are reported before the job fails
+        let mut events = vec![];
+        let result = DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method_id("lines", r#"{"num_lines": 40, "horizontal_margin": 10}"#)
+            .generate(|event| events.push(event));
+        assert!(matches!(result, Err(PipelineError::OutOfPage { .. })));
+        assert!(matches!(events.last(), Some(JobEvent::MarginViolations { report }) if report.off_page().count() > 0));
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_methods() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+
+        assert!(matches!(DrawingJob::new(physical_dimensions.clone()).generate(|_| {}), Err(PipelineError::NoMethod)));
+        assert!(matches!(DrawingJob::new(physical_dimensions).with_method_id("nonexistent", "{}").generate(|_| {}), Err(PipelineError::Generation { .. })));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn runs_generation_off_the_runtime() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let expected = DrawingJob::new(physical_dimensions.clone())
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+            .generate(|_| {})
+            .unwrap();
+
+        let mut events = vec![];
+        let instruction_set = DrawingJob::new(physical_dimensions)
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+            .run(|event| events.push(event))
+            .await
+            .unwrap();
+
+        assert_eq!(instruction_set.get_binary(), expected.get_binary());
+        let stages: Vec<&JobEvent> = events.iter().filter(|event| !matches!(event, JobEvent::Generating { .. })).collect();
+        assert_eq!(stages, [&JobEvent::Generated { bytes: expected.get_binary().len(), start: expected.get_init(), content_hash: expected.content_hash() }, &JobEvent::Validated]);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn runs_queued_jobs_in_order() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let jobs = vec![
+            DrawingJob::new(physical_dimensions.clone())
+                .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+                .with_paper_advance(120.),
+            DrawingJob::new(physical_dimensions)
+                .with_method(LinesMethod, LinesParameters { num_lines: 5, horizontal_margin: 20 })
+                .with_paper_advance(120.),
+        ];
+
+        let mut validated = vec![];
+        let mut duplicates = vec![];
+        let drawn = run_queue(jobs, |index, event| match event {
+            JobEvent::Validated => validated.push(index),
+            JobEvent::Duplicate { job } => duplicates.push((index, job)),
+            _ => {},
+        }).await.unwrap();
+
+        // without a machine, neither job is drawn, so the paper is never advanced
+        assert_eq!(drawn.len(), 2);
+        assert_eq!(validated, [0, 1]);
+        assert_ne!(drawn[0].get_binary(), drawn[1].get_binary());
+        assert!(duplicates.is_empty());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn reports_a_connection_lost_while_streaming() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let instruction_set = DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+            .generate(|_| {})
+            .unwrap();
+        let machine_config = MachineConfiguration { protocol_version: 2, instruction_buffer_size: 4096, max_motor_speed: 4000, min_pulse_width: 100, unknown_fields: vec![] };
+
+        // the machine asks for a chunk, then goes away partway through receiving it
+        let (mut socket, mut machine) = tokio::io::duplex(16);
+        let machine = tokio::spawn(async move {
+            machine.write_all(&[0x03]).await.unwrap();
+            let mut received = [0u8; 8];
+            machine.read_exact(&mut received).await.unwrap();
+        });
+
+        let mut events = vec![];
+        let buf_idx = Arc::new(Mutex::new(0));
+        let result = stream(&mut socket, &buf_idx, &instruction_set, &machine_config, &PauseSchedule::new(), &Notify::new(), &mut |event| events.push(event)).await;
+        machine.await.unwrap();
+
+        // the chunk wasn't written, so the drawing can carry on from it
+        assert!(matches!(result, Ok(Streamed::Interrupted { err: ClientError::ConnectionLost { .. } })));
+        assert!(matches!(events.as_slice(), [JobEvent::ConnectionLost { chunk: 0, .. }]));
+        assert_eq!(*buf_idx.lock().await, 0);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn carries_on_from_the_failed_chunk_on_a_new_connection() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let instruction_set = DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method(LinesMethod, LinesParameters { num_lines: 20, horizontal_margin: 10 })
+            .generate(|_| {})
+            .unwrap();
+        let machine_config = MachineConfiguration { protocol_version: 2, instruction_buffer_size: 1024, max_motor_speed: 4000, min_pulse_width: 100, unknown_fields: vec![] };
+        let bounds = instruction_set.get_buffer_bounds(1024).unwrap().clone();
+        assert!(bounds.len() > 1);
+
+        // the machine draws the first chunk, asks for the second, then goes away
+        let (mut socket, mut machine) = tokio::io::duplex(16);
+        let first_chunk = bounds[0].1 - bounds[0].0 + 1;
+        tokio::spawn(async move {
+            machine.write_all(&[0x03]).await.unwrap();
+            let mut received = vec![0u8; 1 + first_chunk];
+            machine.read_exact(&mut received).await.unwrap();
+            machine.write_all(&[0x03]).await.unwrap();
+        });
+
+        let mut events = vec![];
+        let buf_idx = Arc::new(Mutex::new(0));
+        let result = stream(&mut socket, &buf_idx, &instruction_set, &machine_config, &PauseSchedule::new(), &Notify::new(), &mut |event| events.push(event)).await;
+        assert!(matches!(result, Ok(Streamed::Interrupted { .. })));
+        assert_eq!(*buf_idx.lock().await, 1);
+
+        // a new connection is sent the rest of the drawing, from the second chunk
+        let (mut socket, mut machine) = tokio::io::duplex(16);
+        let rest = bounds[1..].to_vec();
+        let machine = tokio::spawn(async move {
+            let mut received = vec![];
+            for (lb, ub) in rest {
+                machine.write_all(&[0x03]).await.unwrap();
+                let mut chunk = vec![0u8; 1 + ub - lb + 1];
+                machine.read_exact(&mut chunk).await.unwrap();
+                received.extend_from_slice(&chunk[1..]);
+            }
+            machine.write_all(&[0x03]).await.unwrap();
+            let mut finished = [0u8; 1];
+            machine.read_exact(&mut finished).await.unwrap();
+            (received, finished)
+        });
+
+        let result = stream(&mut socket, &buf_idx, &instruction_set, &machine_config, &PauseSchedule::new(), &Notify::new(), &mut |event| events.push(event)).await;
+        let (received, finished) = machine.await.unwrap();
+
+        assert!(matches!(result, Ok(Streamed::Finished)));
+        assert_eq!(received[..], instruction_set.get_binary()[bounds[1].0..]);
+        assert_eq!(finished, [0x02]);
+        assert!(matches!(events.first(), Some(JobEvent::Streaming { chunk: 1, .. })));
+        assert!(events.iter().any(|event| matches!(event, JobEvent::ConnectionLost { chunk: 1, .. })));
+        assert_eq!(events.last(), Some(&JobEvent::Finished));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn pauses_at_the_schedule_until_resumed() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use crate::client::schedule::PausePoint;
+
+        let instruction_set = DrawingJob::new(PhysicalDimensions::new(500., 150., 150., 200., 250.))
+            .with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 })
+            .generate(|_| {})
+            .unwrap();
+        let machine_config = MachineConfiguration { protocol_version: 2, instruction_buffer_size: 1 << 20, max_motor_speed: 4000, min_pulse_width: 100, unknown_fields: vec![] };
+        let chunk_length = instruction_set.get_binary().len();
+
+        // the machine is paused, then sent the whole drawing in one chunk, then resumed
+        let (mut socket, mut machine) = tokio::io::duplex(1 << 20);
+        let machine = tokio::spawn(async move {
+            machine.write_all(&[0x03]).await.unwrap();
+            let mut received = vec![0u8; 2 + 1 + chunk_length + 2];
+            machine.read_exact(&mut received).await.unwrap();
+            machine.write_all(&[0x03]).await.unwrap();
+            let mut finished = [0u8; 1];
+            machine.read_exact(&mut finished).await.unwrap();
+            (received, finished)
+        });
+
+        let resume = Notify::new();
+        let schedule = PauseSchedule::new().with_pause(PausePoint::Instruction(0));
+        let mut events = vec![];
+        let streamed = stream(&mut socket, &Arc::new(Mutex::new(0)), &instruction_set, &machine_config, &schedule, &resume, &mut |event| {
+            if matches!(event, JobEvent::WaitingForUser { .. }) {
+                resume.notify_one();
+            }
+            events.push(event);
+        }).await.unwrap();
+        let (received, end) = machine.await.unwrap();
+
+        assert!(matches!(streamed, Streamed::Finished));
+        assert_eq!(received[..3], [0x04, 0x01, 0x01]);
+        assert_eq!(received[3..3 + chunk_length], instruction_set.get_binary()[..]);
+        assert_eq!(received[3 + chunk_length..], [0x04, 0x00]);
+        assert_eq!(end, [0x02]);
+        assert_eq!(events.first(), Some(&JobEvent::WaitingForUser { reason: "instruction 0".to_owned() }));
+        assert_eq!(events.last(), Some(&JobEvent::Finished));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn warns_of_duplicate_jobs_in_the_queue() {
+        let physical_dimensions = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+        let job = || DrawingJob::new(physical_dimensions.clone()).with_method(LinesMethod, LinesParameters { num_lines: 3, horizontal_margin: 10 });
+
+        let mut duplicates = vec![];
+        let drawn = run_queue(vec![job(), job(), job()], |index, event| if let JobEvent::Duplicate { job } = event { duplicates.push((index, job)) }).await.unwrap();
+
+        // duplicates are still drawn
+        assert_eq!(drawn.len(), 3);
+        assert_eq!(duplicates, [(1, 0), (2, 0)]);
+    }
+}
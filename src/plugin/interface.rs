@@ -1,5 +1,10 @@
+#[cfg(feature = "image")]
+use pyo3::exceptions::PyIOError;
 use pyo3::prelude::*;
 
+#[cfg(feature = "image")]
+use crate::drawing::util::imageproc::edges;
+
 /// 
 /// An interfacing object, used in the Python code, to store drawing instructions
 /// so they can be later iterated and performed internally on a drawing surface.
@@ -45,6 +50,32 @@ impl SurfaceInterface {
         self.instructions.push(GenericInstruction::sample_xy(x, y));
     }
 
+    ///
+    /// Traces the edges of an image with the Canny edge detector, so plugins can draw outlines
+    /// without their own image processing.
+    ///
+    /// # Parameters:
+    /// - `image_path`: The path of the input image
+    /// - `sigma`: The blur applied before detection, in pixels
+    /// - `low_threshold`: The gradient magnitude needed to continue an edge
+    /// - `high_threshold`: The gradient magnitude needed to start an edge
+    /// - `min_length`: The fewest pixels an edge can have
+    ///
+    /// # Returns:
+    /// - A list of polylines, each a list of (x, y) pixel positions
+    /// - A PyIOError if the image could not be opened
+    ///
+    #[cfg(feature = "image")]
+    pub fn trace_edges(&self, image_path: &str, sigma: f32, low_threshold: f32, high_threshold: f32, min_length: usize) -> PyResult<Vec<Vec<(f64, f64)>>> {
+        let image = match image::open(image_path) {
+            Ok(img) => img.into_luma8(),
+            Err(err) => return Err(PyIOError::new_err(format!("Error loading image {}: {}", image_path, err))),
+        };
+
+        let edges = edges::canny(&image, sigma, low_threshold, high_threshold);
+        Ok(edges::link_edges(&edges, min_length))
+    }
+
     ///
     /// # Returns:
     /// - The list of instructions on the object
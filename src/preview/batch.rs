@@ -0,0 +1,204 @@
+use image::{Rgba, RgbaImage};
+use imageproc::pixelops::interpolate;
+use rayon::prelude::*;
+
+///
+/// The number of pixel rows rasterised together. Each band is drawn on its own thread, and a
+/// segment is only visited by the bands it crosses.
+///
+const BAND_ROWS: u32 = 32;
+
+///
+/// A hairline segment waiting to be rasterised, in pixels. The endpoints are ordered along the
+/// major axis, as Wu's algorithm steps along it.
+///
+/// # Fields:
+/// - `start` and `end`: The endpoints, along the major axis then the minor axis
+/// - `is_steep`: Whether the major axis is y, rather than x
+/// - `colour`: The colour of the segment
+///
+#[derive(Clone, Copy, Debug)]
+struct QueuedSegment {
+    start: (i32, i32),
+    end: (i32, i32),
+    is_steep: bool,
+    colour: Rgba<u8>,
+}
+
+impl QueuedSegment {
+    ///
+    /// # Returns:
+    /// - The first and last pixel rows the segment can touch, inclusive
+    ///
+    fn rows(&self) -> (i32, i32) {
+        match self.is_steep {
+            true => (self.start.0, self.end.0),
+            false => {
+                // the minor axis is accumulated in f32, so it can drift past the endpoints, and is
+                // truncated towards zero, then each column also shades the pixel below. the drift
+                // is at most an f32 rounding error per column
+                let columns = (self.end.0 - self.start.0) as f32;
+                let magnitude = self.start.1.abs().max(self.end.1.abs()) as f32 + columns;
+                let margin = 2 + (columns * magnitude * 2. * f32::EPSILON).ceil() as i32;
+                (self.start.1.min(self.end.1) - margin, self.start.1.max(self.end.1) + margin)
+            },
+        }
+    }
+}
+
+///
+/// Antialiased hairline segments, queued to be rasterised together rather than one at a time.
+/// The pixels are shaded exactly as `imageproc`'s `draw_antialiased_line_segment_mut` shades
+/// them, in the order the segments were queued, but the image is split into bands of rows drawn
+/// in parallel, and pixels are written straight to the buffer.
+///
+/// # Fields:
+/// - `segments`: The segments queued since the batch was last rasterised, in order
+///
+#[derive(Clone, Debug, Default)]
+pub struct SegmentBatch {
+    segments: Vec<QueuedSegment>,
+}
+
+impl SegmentBatch {
+    ///
+    /// Queues a segment to be rasterised.
+    ///
+    /// # Parameters:
+    /// - `start` and `end`: The endpoints of the segment, in pixels
+    /// - `colour`: The colour of the segment
+    ///
+    pub fn push(&mut self, start: (i32, i32), end: (i32, i32), colour: Rgba<u8>) {
+        let is_steep = (end.1 - start.1).abs() > (end.0 - start.0).abs();
+        let (start, end) = match is_steep {
+            true => ((start.1, start.0), (end.1, end.0)),
+            false => (start, end),
+        };
+        let (start, end) = if start.0 > end.0 { (end, start) } else { (start, end) };
+        self.segments.push(QueuedSegment { start, end, is_steep, colour });
+    }
+
+    ///
+    /// # Returns:
+    /// - Whether no segments are queued
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    ///
+    /// Rasterises every queued segment onto an image, emptying the batch.
+    ///
+    /// # Parameters:
+    /// - `image`: The image to draw on
+    ///
+    pub fn rasterise(&mut self, image: &mut RgbaImage) {
+        let (width, height) = image.dimensions();
+        if self.segments.is_empty() || width == 0 || height == 0 {
+            self.segments.clear();
+            return;
+        }
+
+        // find the segments crossing each band up front, so a band never scans the whole batch
+        let num_bands = height.div_ceil(BAND_ROWS) as usize;
+        let mut bands: Vec<Vec<u32>> = vec![vec![]; num_bands];
+        for (segment_idx, segment) in self.segments.iter().enumerate() {
+            let (first_row, last_row) = segment.rows();
+            if last_row < 0 || first_row >= height as i32 {
+                continue;
+            }
+            let first_band = first_row.max(0) as usize / BAND_ROWS as usize;
+            let last_band = (last_row as usize / BAND_ROWS as usize).min(num_bands - 1);
+            for band in &mut bands[first_band..=last_band] {
+                band.push(segment_idx as u32);
+            }
+        }
+
+        let segments = &self.segments;
+        image.par_chunks_mut((BAND_ROWS * width * 4) as usize).zip(bands.par_iter()).enumerate().for_each(|(band_idx, (pixels, segment_indices))| {
+            let first_row = band_idx as i32 * BAND_ROWS as i32;
+            let num_rows = (pixels.len() / (width as usize * 4)) as i32;
+            let mut band = Band { pixels, width: width as i32, rows: first_row..first_row + num_rows };
+            for &segment_idx in segment_indices {
+                band.draw(&segments[segment_idx as usize]);
+            }
+        });
+
+        self.segments.clear();
+    }
+}
+
+///
+/// The rows of the image being drawn by one thread.
+///
+/// # Fields:
+/// - `pixels`: The RGBA bytes of the rows
+/// - `width`: The width of the image, in pixels
+/// - `rows`: The rows of the image in the band
+///
+struct Band<'p> {
+    pixels: &'p mut [u8],
+    width: i32,
+    rows: std::ops::Range<i32>,
+}
+
+impl Band<'_> {
+    ///
+    /// Draws the part of a segment inside the band, with Wu's algorithm. The minor axis is
+    /// accumulated from the start of the segment, rather than computed for each pixel, so every
+    /// pixel is shaded as if the whole segment were drawn at once.
+    ///
+    /// # Parameters:
+    /// - `segment`: The segment to draw
+    ///
+    fn draw(&mut self, segment: &QueuedSegment) {
+        let QueuedSegment { start, end, is_steep, colour } = *segment;
+        let gradient = (end.1 - start.1) as f32 / (end.0 - start.0) as f32;
+        let mut minor = start.1 as f32;
+
+        // the two directions are separate loops, so neither branches on the direction per pixel
+        if is_steep {
+            for y in start.0..(end.0 + 1) {
+                // a steep segment steps down the rows, so the rest of it is below the band
+                if y >= self.rows.end {
+                    return;
+                }
+                if y >= self.rows.start {
+                    self.plot(minor as i32, y, colour, 1.0 - minor.fract());
+                    self.plot(minor as i32 + 1, y, colour, minor.fract());
+                }
+                minor += gradient;
+            }
+        } else {
+            for x in start.0..(end.0 + 1) {
+                self.plot(x, minor as i32, colour, 1.0 - minor.fract());
+                self.plot(x, minor as i32 + 1, colour, minor.fract());
+                minor += gradient;
+            }
+        }
+    }
+
+    ///
+    /// Blends a colour into a pixel, if the pixel is in the band.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The pixel, in the coordinates of the whole image
+    /// - `colour`: The colour of the segment
+    /// - `weight`: How much of the pixel the segment covers, from 0 to 1
+    ///
+    #[inline]
+    fn plot(&mut self, x: i32, y: i32, colour: Rgba<u8>, weight: f32) {
+        // a pixel the segment doesn't cover is left as it is, as blending it would
+        if weight == 0. || x < 0 || x >= self.width || !self.rows.contains(&y) {
+            return;
+        }
+        let idx = (((y - self.rows.start) * self.width + x) * 4) as usize;
+        let pixel = &mut self.pixels[idx..idx + 4];
+        // and a pixel the segment covers entirely is the colour of the segment
+        let blended = match weight == 1. {
+            true => colour,
+            false => interpolate(colour, Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]), weight),
+        };
+        pixel.copy_from_slice(&blended.0);
+    }
+}
@@ -1,17 +1,26 @@
+use crate::hardware::PhysicalDimensions;
+use crate::hardware::error::KinematicsError;
+use crate::hardware::kinematics::Kinematics;
 use crate::hardware::math::*;
 
 ///
-/// A structure representing the real world belts. 
+/// A structure representing the real world belts. On machines other than a hanging-V plotter,
+/// the belt lengths are the distances travelled by each motor axis.
 ///
 /// # Fields:
 /// - `left_belt_length`: The distance between the left motor shaft and the pen, in millimetres.
 /// - `right_belt_length`: The distance between the right motor shaft and the pen, in millimetres.
-/// - `motor_interspace`: The distance (horizontal) between the two motor shafts, in millimetres.
+/// - `kinematics`: The geometry of the machine, converting belt lengths to cartesian coordinates.
+/// - `steps_per_mm`: The number of motor steps to move a belt one millimetre.
+/// - `target_lengths`: The left and right belt lengths last moved towards, which whole steps can
+///   only approximate.
 ///
 pub struct Belts {
     left_belt_length: f64,
     right_belt_length: f64,
-    motor_interspace: f64
+    kinematics: Box<dyn Kinematics + Send + Sync>,
+    steps_per_mm: f64,
+    target_lengths: (f64, f64),
 }
 
 impl Belts {
@@ -21,13 +30,13 @@ impl Belts {
     /// # Parameters:
     /// - `left_belt_length`: The initial left belt length, between the left motor shaft and pen
     /// - `right_belt_length`: The initial right belt length, between the left motor shaft and pen
-    /// - `motor_interspace`: The distance (horizontal) between the two motor shafts
+    /// - `physical_dimensions`: The physical dimensions of the machine, for its geometry and motors
     ///
     /// # Returns:
     /// - A new `Belts` instance
     ///
-    pub fn new_by_length(left_belt_length: f64, right_belt_length: f64, motor_interspace: f64) -> Belts {
-        Belts { left_belt_length, right_belt_length, motor_interspace }
+    pub fn new_by_length(left_belt_length: f64, right_belt_length: f64, physical_dimensions: &PhysicalDimensions) -> Belts {
+        Belts { left_belt_length, right_belt_length, kinematics: physical_dimensions.kinematics_model(), steps_per_mm: physical_dimensions.steps_per_mm(), target_lengths: (left_belt_length, right_belt_length) }
     }
 
     /// 
@@ -36,14 +45,16 @@ impl Belts {
     /// # Parameters:
     /// - `canvas_x`: The initial x coordinate of the pen, relative to the left motor shaft 
     /// - `canvas_y`: The initial y coordinate of the pen, relative to the left motor shaft 
-    /// - `motor_interspace`: The distance (horizontal) between the two motor shafts
+    /// - `physical_dimensions`: The physical dimensions of the machine, for its geometry and motors
     ///
     /// # Returns:
     /// - A new `Belts` instance
+    /// - `KinematicsError` if the coordinates are not numbers
     ///
-    pub fn new_by_cartesian(canvas_x: f64, canvas_y: f64, motor_interspace: f64) -> Belts {
-        let (left_belt_length, right_belt_length) = cartesian_to_belt(canvas_x, canvas_y, motor_interspace);
-        Self {left_belt_length, right_belt_length, motor_interspace }
+    pub fn new_by_cartesian(canvas_x: f64, canvas_y: f64, physical_dimensions: &PhysicalDimensions) -> Result<Belts, KinematicsError> {
+        let kinematics = physical_dimensions.kinematics_model();
+        let (left_belt_length, right_belt_length) = kinematics.cartesian_to_axes(canvas_x, canvas_y)?;
+        Ok(Self {left_belt_length, right_belt_length, kinematics, steps_per_mm: physical_dimensions.steps_per_mm(), target_lengths: (left_belt_length, right_belt_length) })
     }
 
     ///
@@ -53,7 +64,7 @@ impl Belts {
     /// - `steps`: The number of steps to move, can be negative
     ///
     fn move_left(&mut self, steps: i16) {
-        self.left_belt_length += steps_to_mm(steps);
+        self.left_belt_length += steps_to_mm(steps, self.steps_per_mm);
     }
 
     ///
@@ -63,7 +74,7 @@ impl Belts {
     /// - `steps`: The number of steps to move, can be negative
     ///
     fn move_right(&mut self, steps: i16) {
-        self.right_belt_length += steps_to_mm(steps);
+        self.right_belt_length += steps_to_mm(steps, self.steps_per_mm);
     }
 
     ///
@@ -76,6 +87,31 @@ impl Belts {
     pub fn move_by_steps(&mut self, left_steps: i16, right_steps: i16) {
         self.move_left(left_steps);
         self.move_right(right_steps);
+        self.target_lengths = (self.left_belt_length, self.right_belt_length);
+    }
+
+    ///
+    /// Performs a movement of both belts towards target lengths, which the steps approximate. The
+    /// difference left over is the quantisation error of the movement.
+    ///
+    /// # Parameters:
+    /// - `left_steps`: The number of steps to move the left belt, can be negative
+    /// - `right_steps`: The number of steps to move the right belt, can be negative
+    /// - `target_lengths`: The left and right belt lengths the steps were rounded from
+    ///
+    pub fn move_by_steps_towards(&mut self, left_steps: i16, right_steps: i16, target_lengths: (f64, f64)) {
+        self.move_by_steps(left_steps, right_steps);
+        self.target_lengths = target_lengths;
+    }
+
+    ///
+    /// # Returns:
+    /// - The left and right target belt lengths minus the realised lengths, in millimetres. The
+    ///   next movement is measured from the realised lengths, so this error is carried into it
+    ///   rather than accumulating, and never grows past half a step
+    ///
+    pub fn quantisation_error(&self) -> (f64, f64) {
+        (self.target_lengths.0 - self.left_belt_length, self.target_lengths.1 - self.right_belt_length)
     }
 
     ///
@@ -84,9 +120,25 @@ impl Belts {
     ///
     /// # Returns:
     /// - The (x, y) coordinates of the current pen position
+    /// - `KinematicsError` with the belt lengths, if the belts can not meet at a point
+    ///
+    pub fn get_as_cartesian(&self) -> Result<(f64, f64), KinematicsError> {
+        self.kinematics.axes_to_cartesian(self.left_belt_length, self.right_belt_length)
+    }
+
+    ///
+    /// Calculates the belt lengths for a cartesian coordinate, without moving the belts.
+    ///
+    /// # Parameters:
+    /// - `canvas_x`: The x coordinate of the pen, relative to the top left motor shaft
+    /// - `canvas_y`: The y coordinate of the pen, relative to the top left motor shaft
+    ///
+    /// # Returns:
+    /// - The left and right belt lengths, respectively
+    /// - `KinematicsError` if the coordinates are not numbers
     ///
-    pub fn get_as_cartesian(&self) -> (f64, f64) {
-        belt_to_cartesian(self.left_belt_length, self.right_belt_length, self.motor_interspace)
+    pub fn lengths_at(&self, canvas_x: f64, canvas_y: f64) -> Result<(f64, f64), KinematicsError> {
+        self.kinematics.cartesian_to_axes(canvas_x, canvas_y)
     }
 
     ///
@@ -96,4 +148,18 @@ impl Belts {
     pub fn get_lengths(&self) -> (f64, f64) {
         (self.left_belt_length, self.right_belt_length)
     }
+
+    ///
+    /// Sets the belt lengths directly, such as to return to an earlier position. The lengths are
+    /// exact, so there is no quantisation error.
+    ///
+    /// # Parameters:
+    /// - `left_belt_length`: The left belt length, between the left motor shaft and pen
+    /// - `right_belt_length`: The right belt length, between the right motor shaft and pen
+    ///
+    pub fn set_lengths(&mut self, left_belt_length: f64, right_belt_length: f64) {
+        self.left_belt_length = left_belt_length;
+        self.right_belt_length = right_belt_length;
+        self.target_lengths = (left_belt_length, right_belt_length);
+    }
 }
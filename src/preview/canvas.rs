@@ -1,8 +1,38 @@
-use image::GrayImage;
-use image::Luma;
+use std::io::Cursor;
+use std::path::Path;
+
+use image::Rgba;
+use image::RgbaImage;
 use imageproc::drawing::draw_antialiased_line_segment_mut;
+use imageproc::drawing::draw_filled_circle_mut;
 use imageproc::pixelops::interpolate;
 
+use super::batch::SegmentBatch;
+use super::error::PreviewError;
+use super::metadata::{self, PreviewMetadata};
+
+///
+/// The largest canvas which can be created, in pixels. Each pixel is 4 bytes, so this is 1GiB.
+///
+const MAX_CANVAS_PIXELS: u64 = 1 << 28;
+
+///
+/// The 3x5 block glyph of each digit, used by `PreviewCanvas::number`. Each row is three bits,
+/// the most significant bit being the leftmost block.
+///
+const DIGIT_GLYPHS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
 ///
 /// A canvas image with appropriate handling methods, to generate previews of drawings.
 ///
@@ -10,39 +40,66 @@ use imageproc::pixelops::interpolate;
 /// - `width`: The width of the paper, in millimetres
 /// - `height`: The height of the paper, in millimetres
 /// - `scale`: The scale to adjust resolution of the preview
+/// - `padding`: The blank space surrounding the paper on every side, in millimetres
+/// - `pen_width`: The width of drawn lines in millimetres, or 0 for hairlines one pixel wide
+/// - `buffer`: The image. Lines queued with `queue_line` are only in it after `flush_lines`
+/// - `batch`: The hairlines queued to be drawn together
 ///
 pub struct PreviewCanvas {
     pub width: u32,
     pub height: u32,
     pub scale: u32,
+    pub padding: u32,
+    pub pen_width: f64,
 
-    pub buffer: GrayImage,
+    pub buffer: RgbaImage,
+    batch: SegmentBatch,
 }
 
 impl PreviewCanvas {
     ///
-    /// Creates a new instance of the image canvas, with a black/white image buffer.
+    /// Creates a new instance of the image canvas, with a white colour image buffer.
+    ///
+    /// # Parameters:
+    /// - `paper_width`: The width of the paper in millimetres
+    /// - `paper_height`: The height of the paper in millimetres
+    /// - `scale`: An optional scale to adjust the preview by, defaults to 1 
+    /// 
+    /// # Returns:
+    /// - A new `PreviewCanvas` instance
+    /// - `PreviewError::CanvasTooLarge` if the canvas is too large to allocate
+    ///
+    pub fn new(paper_width: u32, paper_height: u32, scale: Option<u32>) -> Result<PreviewCanvas, PreviewError> {
+        PreviewCanvas::new_with_padding(paper_width, paper_height, scale, 0)
+    }
+
+    ///
+    /// Creates a new instance of the image canvas, with a white colour image buffer and a blank
+    /// border around the paper. Lines which stray slightly off the paper are then still visible.
     ///
     /// # Parameters:
     /// - `paper_width`: The width of the paper in millimetres
     /// - `paper_height`: The height of the paper in millimetres
     /// - `scale`: An optional scale to adjust the preview by, defaults to 1 
+    /// - `padding`: The space to leave around each side of the paper, in millimetres
     /// 
     /// # Returns:
     /// - A new `PreviewCanvas` instance
+    /// - `PreviewError::CanvasTooLarge` if the canvas is too large to allocate
     ///
-    pub fn new(paper_width: u32, paper_height: u32, scale: Option<u32>) -> PreviewCanvas {
+    pub fn new_with_padding(paper_width: u32, paper_height: u32, scale: Option<u32>, padding: u32) -> Result<PreviewCanvas, PreviewError> {
         let scale = scale.unwrap_or(1);
 
-        let width = paper_width * scale;
-        let height = paper_height * scale;
-        
-        let mut img_buffer = GrayImage::new(width, height);
-        for (_, _, pixel) in img_buffer.enumerate_pixels_mut() {
-            *pixel = Luma([255]);
+        let width = (paper_width as u64 + 2 * padding as u64) * scale as u64;
+        let height = (paper_height as u64 + 2 * padding as u64) * scale as u64;
+
+        if width > u32::MAX as u64 || height > u32::MAX as u64 || width * height > MAX_CANVAS_PIXELS {
+            return Err(PreviewError::CanvasTooLarge { width, height });
         }
         
-        PreviewCanvas { width, height, scale, buffer: img_buffer }
+        let img_buffer = RgbaImage::from_pixel(width as u32, height as u32, Rgba([255, 255, 255, 255]));
+        
+        Ok(PreviewCanvas { width: width as u32, height: height as u32, scale, padding, pen_width: 0., buffer: img_buffer, batch: SegmentBatch::default() })
     }
 
     /// 
@@ -51,26 +108,304 @@ impl PreviewCanvas {
     /// # Parameters:
     /// - `path`: The path to save the image file to
     ///
-    pub fn save(&self, path: &str) {
-        let _ = self.buffer.save_with_format(path, image::ImageFormat::Png);
+    /// # Returns:
+    /// - Void if the image was saved
+    /// - `PreviewError` explaining why the image could not be saved
+    ///
+    pub fn save(&self, path: &str) -> Result<(), PreviewError> {
+        save_image(&self.buffer, path)
+    }
+
+    ///
+    /// Downscales the preview to fit a width, keeping its aspect ratio. Previews narrower than
+    /// the width are copied at their full size rather than enlarged.
+    ///
+    /// # Parameters:
+    /// - `max_width`: The widest the thumbnail can be, in pixels
+    ///
+    /// # Returns:
+    /// - The downscaled image, at least one pixel in each direction
+    ///
+    pub fn thumbnail(&self, max_width: u32) -> RgbaImage {
+        if max_width >= self.buffer.width() {
+            return self.buffer.clone();
+        }
+
+        let width = max_width.max(1);
+        let height = ((self.buffer.height() as u64 * width as u64) / self.buffer.width() as u64).max(1) as u32;
+        image::imageops::thumbnail(&self.buffer, width, height)
+    }
+
+    ///
+    /// Saves the preview to a PNG file on the disk, with the settings it was generated with.
+    ///
+    /// # Parameters:
+    /// - `path`: The path to save the image file to
+    /// - `metadata`: The settings to embed in the image
+    ///
+    /// # Returns:
+    /// - Void if the image was saved
+    /// - `PreviewError` explaining why the image could not be saved
+    ///
+    pub fn save_with_metadata(&self, path: &str, metadata: &PreviewMetadata) -> Result<(), PreviewError> {
+        metadata::save_image_with_metadata(&self.buffer, metadata, path)
+    }
+
+    ///
+    /// Encodes the preview as a PNG, in memory.
+    ///
+    /// # Returns:
+    /// - The bytes of the PNG encoded preview
+    /// - `PreviewError::Encoding` explaining why the image could not be encoded
+    ///
+    pub fn to_png_bytes(&self) -> Result<Vec<u8>, PreviewError> {
+        let mut png_bytes: Vec<u8> = Vec::new();
+        match self.buffer.write_to(&mut Cursor::new(&mut png_bytes), image::ImageFormat::Png) {
+            Ok(()) => Ok(png_bytes),
+            Err(err) => Err(PreviewError::Encoding { reason: err.to_string() })
+        }
     }
 
     /// 
-    /// Draws an antialiased line between two points on the canvas. This function respects `scale`.
+    /// Draws an antialiased line between two points on the canvas. This function respects `scale`
+    /// and `pen_width`.
     ///
     /// # Parameters:
     /// - `x1` and `y1`: The x/y of the first point on the line
     /// - `x2` and `y2`: The x/y of the second point on the line
     ///
     pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64) {
+        self.coloured_line(x1, y1, x2, y2, Rgba([0, 0, 0, 255]));
+    }
+
+    /// 
+    /// Draws an antialiased line of a given colour between two points on the canvas. This function
+    /// respects `scale`, `padding` and `pen_width`.
+    ///
+    /// # Parameters:
+    /// - `x1` and `y1`: The x/y of the first point on the line, relative to the top left of the paper
+    /// - `x2` and `y2`: The x/y of the second point on the line, relative to the top left of the paper
+    /// - `colour`: The colour of the line
+    ///
+    pub fn coloured_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, colour: Rgba<u8>) {
+        // anything queued was drawn first, so it goes underneath
+        self.flush_lines();
+        let padding = self.padding as f64;
+
+        // pens thinner than a pixel are drawn as hairlines
+        if self.pen_width * self.scale as f64 > 1. {
+            let scale = self.scale as f64;
+            self.thick_line(
+                ((x1 + padding) * scale, (y1 + padding) * scale),
+                ((x2 + padding) * scale, (y2 + padding) * scale),
+                self.pen_width * scale / 2.,
+                colour
+            );
+            return;
+        }
+
         draw_antialiased_line_segment_mut(
             &mut self.buffer,
-            scale_floor_coordinates(x1, y1, self.scale),
-            scale_floor_coordinates(x2, y2, self.scale),
-            image::Luma([0]), interpolate
+            scale_floor_coordinates(x1 + padding, y1 + padding, self.scale),
+            scale_floor_coordinates(x2 + padding, y2 + padding, self.scale),
+            colour, interpolate
         );
     }
 
+    ///
+    /// Queues an antialiased line of a given colour, to be drawn with the other queued lines by
+    /// `flush_lines`. Drawing millions of short lines this way is much faster than with
+    /// `coloured_line`, and the result is the same. Lines thicker than a pixel aren't batched, so
+    /// they are drawn straight away, after the lines queued before them.
+    ///
+    /// # Parameters:
+    /// - `x1` and `y1`: The x/y of the first point on the line, relative to the top left of the paper
+    /// - `x2` and `y2`: The x/y of the second point on the line, relative to the top left of the paper
+    /// - `colour`: The colour of the line
+    ///
+    pub fn queue_line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, colour: Rgba<u8>) {
+        if self.pen_width * self.scale as f64 > 1. {
+            self.coloured_line(x1, y1, x2, y2, colour);
+            return;
+        }
+
+        let padding = self.padding as f64;
+        self.batch.push(
+            scale_floor_coordinates(x1 + padding, y1 + padding, self.scale),
+            scale_floor_coordinates(x2 + padding, y2 + padding, self.scale),
+            colour
+        );
+    }
+
+    ///
+    /// Draws every line queued by `queue_line` onto the image, in the order they were queued.
+    ///
+    pub fn flush_lines(&mut self) {
+        if !self.batch.is_empty() {
+            self.batch.rasterise(&mut self.buffer);
+        }
+    }
+
+    /// 
+    /// Draws the outline of a rectangle on the canvas, used for page borders and margin guides.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The top left corner of the rectangle, relative to the top left of the paper
+    /// - `width` and `height`: The size of the rectangle
+    /// - `colour`: The colour of the outline
+    ///
+    pub fn rectangle(&mut self, x: f64, y: f64, width: f64, height: f64, colour: Rgba<u8>) {
+        self.coloured_line(x, y, x + width, y, colour);
+        self.coloured_line(x + width, y, x + width, y + height, colour);
+        self.coloured_line(x + width, y + height, x, y + height, colour);
+        self.coloured_line(x, y + height, x, y, colour);
+    }
+
+    /// 
+    /// Draws a filled circle on the canvas, used to mark points such as motor shafts.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The centre of the circle, relative to the top left of the paper
+    /// - `radius`: The radius of the circle
+    /// - `colour`: The colour of the circle
+    ///
+    pub fn circle(&mut self, x: f64, y: f64, radius: f64, colour: Rgba<u8>) {
+        self.flush_lines();
+        let padding = self.padding as f64;
+        draw_filled_circle_mut(
+            &mut self.buffer,
+            scale_floor_coordinates(x + padding, y + padding, self.scale),
+            (radius * self.scale as f64).round().max(1.) as i32,
+            colour
+        );
+    }
+
+    ///
+    /// Draws an open arrowhead on the canvas, pointing along a direction, used to show which way
+    /// strokes are drawn.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The tip of the arrowhead, relative to the top left of the paper
+    /// - `direction`: The (x, y) direction the arrow points in, of any length
+    /// - `size`: The length of each side of the arrowhead
+    /// - `colour`: The colour of the arrowhead
+    ///
+    pub fn arrow(&mut self, x: f64, y: f64, direction: (f64, f64), size: f64, colour: Rgba<u8>) {
+        let angle = direction.1.atan2(direction.0);
+        for side in [-1., 1.] {
+            let barb = angle + std::f64::consts::PI + side * std::f64::consts::FRAC_PI_6;
+            self.coloured_line(x, y, x + size * barb.cos(), y + size * barb.sin(), colour);
+        }
+    }
+
+    ///
+    /// Writes a number on the canvas in a small blocky font, which needs no font files. Each
+    /// digit is 3x5 blocks, and each block is half a millimetre, but at least a pixel.
+    ///
+    /// # Parameters:
+    /// - `x` and `y`: The top left corner of the number, relative to the top left of the paper
+    /// - `number`: The number to write
+    /// - `colour`: The colour of the number
+    ///
+    pub fn number(&mut self, x: f64, y: f64, number: usize, colour: Rgba<u8>) {
+        self.flush_lines();
+        let block = (self.scale / 2).max(1);
+        let (left, top) = scale_floor_coordinates(x + self.padding as f64, y + self.padding as f64, self.scale);
+
+        for (digit_idx, digit) in number.to_string().bytes().enumerate() {
+            let glyph = DIGIT_GLYPHS[(digit - b'0') as usize];
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) == 0 {
+                        continue;
+                    }
+
+                    let block_x = left as i64 + ((digit_idx * 4 + column) as i64) * block as i64;
+                    let block_y = top as i64 + (row as i64) * block as i64;
+                    for py in block_y..block_y + block as i64 {
+                        for px in block_x..block_x + block as i64 {
+                            if px >= 0 && py >= 0 && px < self.width as i64 && py < self.height as i64 {
+                                self.buffer.put_pixel(px as u32, py as u32, colour);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Draws a thick antialiased line with round caps. Each pixel near the line is shaded by its
+    /// distance to the line, so the stroke has a smooth edge of roughly one pixel.
+    ///
+    /// # Parameters:
+    /// - `start` and `end`: The endpoints of the line, in pixels
+    /// - `radius`: Half the width of the line, in pixels
+    /// - `colour`: The colour of the line
+    ///
+    fn thick_line(&mut self, start: (f64, f64), end: (f64, f64), radius: f64, colour: Rgba<u8>) {
+        let min_x = (start.0.min(end.0) - radius - 1.).floor().max(0.) as u32;
+        let min_y = (start.1.min(end.1) - radius - 1.).floor().max(0.) as u32;
+        let max_x = ((start.0.max(end.0) + radius + 1.).ceil().max(0.) as u32).min(self.width);
+        let max_y = ((start.1.max(end.1) + radius + 1.).ceil().max(0.) as u32).min(self.height);
+
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length_sq = dx * dx + dy * dy;
+
+        for py in min_y..max_y {
+            for px in min_x..max_x {
+                // distance from the pixel centre to the closest point on the line
+                let (cx, cy) = (px as f64 + 0.5, py as f64 + 0.5);
+                let t = if length_sq == 0. { 0. } else { (((cx - start.0) * dx + (cy - start.1) * dy) / length_sq).clamp(0., 1.) };
+                let distance = ((cx - start.0 - t * dx).powi(2) + (cy - start.1 - t * dy).powi(2)).sqrt();
+
+                let coverage = (radius + 0.5 - distance).clamp(0., 1.) as f32;
+                if coverage > 0. {
+                    let existing = *self.buffer.get_pixel(px, py);
+                    self.buffer.put_pixel(px, py, interpolate(colour, existing, coverage));
+                }
+            }
+        }
+    }
+}
+
+///
+/// Checks an image can be saved to a path, as its directory exists and it isn't a directory itself.
+///
+/// # Parameters:
+/// - `path`: The path to save the image file to
+///
+/// # Returns:
+/// - The path, if the image can be saved to it
+/// - `PreviewError::InvalidPath` if the image can't be saved to it
+///
+pub(crate) fn check_path(path: &str) -> Result<&Path, PreviewError> {
+    let file_path = Path::new(path);
+    let directory_exists = match file_path.parent() {
+        Some(parent) => parent.as_os_str().is_empty() || parent.is_dir(),
+        None => false,
+    };
+    if !directory_exists || file_path.is_dir() {
+        return Err(PreviewError::InvalidPath { path: path.to_owned() });
+    }
+
+    Ok(file_path)
+}
+
+///
+/// Saves an image to the disk as a PNG, checking the destination directory exists first.
+///
+/// # Parameters:
+/// - `image`: The image to save
+/// - `path`: The path to save the image file to
+///
+/// # Returns:
+/// - Void if the image was saved
+/// - `PreviewError` explaining why the image could not be saved
+///
+pub(crate) fn save_image(image: &RgbaImage, path: &str) -> Result<(), PreviewError> {
+    let file_path = check_path(path)?;
+    image.save_with_format(file_path, image::ImageFormat::Png).map_err(|err| PreviewError::Io { path: path.to_owned(), reason: err.to_string() })
 }
 
 ///
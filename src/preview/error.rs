@@ -0,0 +1,49 @@
+use thiserror::Error;
+
+use crate::instruction::error::InstructionError;
+
+///
+/// All errors emitted from the preview module.
+/// The error messages can be displayed to users on the frontend.
+///
+/// - `Instruction`: When the instruction set could not be simulated
+///     Parameters:
+///     - `InstructionError`: The reason the simulation failed
+/// - `InvalidPath`: When the preview can not be saved to the path, e.g. its directory does not exist
+///     Parameters:
+///     - `path`: The requested path
+/// - `Io`: When the preview image could not be written to the disk
+///     Parameters:
+///     - `path`: The requested path
+///     - `reason`: The underlying error
+/// - `Encoding`: When the preview image could not be encoded
+///     Parameters:
+///     - `reason`: The underlying error
+/// - `Metadata`: When the generation settings could not be read from a preview image
+///     Parameters:
+///     - `reason`: Why the settings could not be read
+/// - `CanvasTooLarge`: When the requested canvas is too large to allocate, usually due to a huge scale
+///     Parameters:
+///     - `width`: The requested width, in pixels
+///     - `height`: The requested height, in pixels
+///
+#[derive(Error, Debug)]
+pub enum PreviewError {
+    #[error(transparent)]
+    Instruction(#[from] InstructionError),
+
+    #[error("The preview can not be saved to {}. Check the directory exists.", .path)]
+    InvalidPath { path: String },
+
+    #[error("Error saving the preview to {}: {}", .path, .reason)]
+    Io { path: String, reason: String },
+
+    #[error("Error encoding the preview image: {}", .reason)]
+    Encoding { reason: String },
+
+    #[error("Error reading the preview image's settings: {}", .reason)]
+    Metadata { reason: String },
+
+    #[error("The preview canvas is too large to create: {}x{} pixels. Try a smaller scale.", .width, .height)]
+    CanvasTooLarge { width: u64, height: u64 },
+}
@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::drawing::registry;
+use crate::hardware::PhysicalDimensions;
+use crate::instruction::InstructionSet;
+use super::error::PreviewError;
+use super::metadata::PreviewMetadata;
+use super::{canvas, render_preview, PreviewOptions};
+
+///
+/// The widest a gallery thumbnail can be, in pixels.
+///
+pub const GALLERY_THUMBNAIL_WIDTH: u32 = 256;
+
+///
+/// A drawing method in the gallery.
+///
+/// # Fields:
+/// - `method_id`: The backend ID of the drawing method
+/// - `name`: The frontend display name of the drawing method
+/// - `parameters`: The parameters the preview was generated with, if the method had any
+/// - `preview`: The file name of the full size preview, if it was generated
+/// - `thumbnail`: The file name of the thumbnail, if it was generated
+/// - `error`: Why the preview could not be generated, if it failed
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct GalleryEntry {
+    pub method_id: String,
+    pub name: String,
+    pub parameters: Option<serde_json::Value>,
+    pub preview: Option<String>,
+    pub thumbnail: Option<String>,
+    pub error: Option<String>,
+}
+
+///
+/// The index of a gallery, saved as `index.json` beside the previews.
+///
+/// # Fields:
+/// - `crate_version`: The version of this library which generated the gallery
+/// - `methods`: An entry for every drawing method enabled in this build, in registry order
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct GalleryIndex {
+    pub crate_version: String,
+    pub methods: Vec<GalleryEntry>,
+}
+
+///
+/// Renders a preview and thumbnail of every drawing method in the registry into a directory,
+/// and saves an `index.json` describing them, for a method picker or for reviewing changes to
+/// the methods side by side. A method which fails to generate is recorded in the index with
+/// its error, rather than stopping the gallery.
+///
+/// # Parameters:
+/// - `physical_dimensions`: The physical dimensions of the machine to generate for
+/// - `directory`: The existing directory to save the previews and index to
+/// - `parameters`: The parameters of a method as JSON, by its backend ID. Methods without
+///   parameters are recorded in the index, but not previewed
+///
+/// # Returns:
+/// - The index of the gallery
+/// - `PreviewError` if the previews or index could not be saved
+///
+pub fn generate_gallery<F: Fn(&str) -> Option<String>>(physical_dimensions: &PhysicalDimensions, directory: &str, parameters: F) -> Result<GalleryIndex, PreviewError> {
+    let mut methods = vec![];
+    for (method_id, name) in registry::methods() {
+        let mut entry = GalleryEntry { method_id: method_id.to_owned(), name: name.to_owned(), parameters: None, preview: None, thumbnail: None, error: None };
+
+        match parameters(method_id) {
+            Some(parameters_json) => {
+                entry.parameters = serde_json::from_str(&parameters_json).ok();
+                match render_entry(physical_dimensions, directory, method_id, &parameters_json) {
+                    Ok((preview, thumbnail)) => (entry.preview, entry.thumbnail) = (Some(preview), Some(thumbnail)),
+                    Err(GalleryError::Generation(reason)) => entry.error = Some(reason),
+                    Err(GalleryError::Preview(err)) => return Err(err),
+                }
+            },
+            None => entry.error = Some("No parameters were given for this method.".to_owned()),
+        }
+
+        methods.push(entry);
+    }
+
+    let index = GalleryIndex { crate_version: env!("CARGO_PKG_VERSION").to_owned(), methods };
+    let index_path = Path::new(directory).join("index.json");
+    let index_path = index_path.to_string_lossy();
+    let index_json = serde_json::to_string_pretty(&index).map_err(|err| PreviewError::Encoding { reason: err.to_string() })?;
+    std::fs::write(canvas::check_path(&index_path)?, index_json).map_err(|err| PreviewError::Io { path: index_path.to_string(), reason: err.to_string() })?;
+
+    Ok(index)
+}
+
+///
+/// Why one method of a gallery could not be previewed.
+///
+/// - `Generation`: The method could not generate its drawing, which is recorded in the index
+/// - `Preview`: The preview could not be saved, which stops the gallery
+///
+enum GalleryError {
+    Generation(String),
+    Preview(PreviewError),
+}
+
+///
+/// Generates one method's drawing and saves its preview and thumbnail, named by its backend ID.
+///
+/// # Parameters:
+/// - `physical_dimensions`: The physical dimensions of the machine to generate for
+/// - `directory`: The directory to save the preview and thumbnail to
+/// - `method_id`: The backend ID of the drawing method
+/// - `parameters_json`: The parameters of the method, as JSON
+///
+/// # Returns:
+/// - The file names of the (preview, thumbnail)
+/// - `GalleryError` explaining why the method could not be previewed
+///
+fn render_entry(physical_dimensions: &PhysicalDimensions, directory: &str, method_id: &str, parameters_json: &str) -> Result<(String, String), GalleryError> {
+    let (bytes, start_x, start_y) = registry::gen_instructions(method_id, physical_dimensions, parameters_json).map_err(GalleryError::Generation)?;
+    let instruction_set = InstructionSet::new(bytes, start_x, start_y).map_err(|err| GalleryError::Generation(err.to_string()))?;
+
+    // drawings which can't be simulated, such as ones leaving the machine's reach, are the method's fault
+    let preview_canvas = match render_preview(instruction_set.get_init(), physical_dimensions, &instruction_set, &PreviewOptions { draw_border: true, ..Default::default() }) {
+        Ok(preview_canvas) => preview_canvas,
+        Err(PreviewError::Instruction(err)) => return Err(GalleryError::Generation(err.to_string())),
+        Err(err) => return Err(GalleryError::Preview(err)),
+    };
+
+    let (preview, thumbnail) = (format!("{}.png", method_id), format!("{}-thumbnail.png", method_id));
+    let metadata = PreviewMetadata::for_method(method_id, parameters_json);
+    let path = |file_name: &str| Path::new(directory).join(file_name).to_string_lossy().into_owned();
+    preview_canvas.save_with_metadata(&path(&preview), &metadata).map_err(GalleryError::Preview)?;
+    super::metadata::save_image_with_metadata(&preview_canvas.thumbnail(GALLERY_THUMBNAIL_WIDTH), &metadata, &path(&thumbnail)).map_err(GalleryError::Preview)?;
+
+    Ok((preview, thumbnail))
+}
@@ -0,0 +1,119 @@
+use image::Rgba;
+use image::RgbaImage;
+use imageproc::drawing::BresenhamLineIter;
+
+///
+/// A grid of per-pixel stroke counts, used to find areas of a drawing where the pen passes many
+/// times over the same spot.
+///
+/// # Fields:
+/// - `width`: The width of the grid, in pixels
+/// - `height`: The height of the grid, in pixels
+/// - `scale`: The number of pixels per millimetre
+/// - `padding`: The blank space surrounding the paper on every side, in millimetres
+/// - `counts`: The number of strokes which have passed over each pixel, row by row
+///
+pub struct Heatmap {
+    pub width: u32,
+    pub height: u32,
+    pub scale: u32,
+    pub padding: u32,
+
+    counts: Vec<u32>,
+}
+
+impl Heatmap {
+    ///
+    /// Creates a new, empty heatmap.
+    ///
+    /// # Parameters:
+    /// - `paper_width`: The width of the paper in millimetres
+    /// - `paper_height`: The height of the paper in millimetres
+    /// - `scale`: The number of pixels per millimetre
+    /// - `padding`: The space to leave around each side of the paper, in millimetres
+    ///
+    /// # Returns:
+    /// - A new `Heatmap` instance
+    ///
+    pub fn new(paper_width: u32, paper_height: u32, scale: u32, padding: u32) -> Heatmap {
+        let width = (paper_width + 2 * padding) * scale;
+        let height = (paper_height + 2 * padding) * scale;
+
+        Heatmap { width, height, scale, padding, counts: vec![0; (width * height) as usize] }
+    }
+
+    ///
+    /// Adds a stroke between two points to the heatmap. The first pixel of the line is skipped,
+    /// so a continuous path does not count its joins twice.
+    ///
+    /// # Parameters:
+    /// - `x1` and `y1`: The x/y of the first point on the line, relative to the top left of the paper
+    /// - `x2` and `y2`: The x/y of the second point on the line, relative to the top left of the paper
+    ///
+    pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64) {
+        let padding = self.padding as f64;
+        let scale = self.scale as f64;
+        let start = (((x1 + padding) * scale) as f32, ((y1 + padding) * scale) as f32);
+        let end = (((x2 + padding) * scale) as f32, ((y2 + padding) * scale) as f32);
+
+        for (px, py) in BresenhamLineIter::new(start, end).skip(1) {
+            if px < 0 || py < 0 || px as u32 >= self.width || py as u32 >= self.height {
+                continue;
+            }
+            self.counts[(py as u32 * self.width + px as u32) as usize] += 1;
+        }
+    }
+
+    ///
+    /// # Returns:
+    /// - The greatest number of strokes over any one pixel
+    ///
+    pub fn max_count(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    ///
+    /// Renders the heatmap to an image. Untouched pixels are white, and pixels are coloured from
+    /// blue (one pass) through yellow to red (the most passes) on a logarithmic scale.
+    ///
+    /// # Returns:
+    /// - The rendered heatmap image
+    ///
+    pub fn render(&self) -> RgbaImage {
+        let max_count = self.max_count();
+        let mut image = RgbaImage::from_pixel(self.width, self.height, Rgba([255, 255, 255, 255]));
+
+        for (index, count) in self.counts.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+
+            let t = if max_count <= 1 { 0. } else { (*count as f64).ln() / (max_count as f64).ln() };
+            let x = index as u32 % self.width;
+            let y = index as u32 / self.width;
+            image.put_pixel(x, y, heat_colour(t));
+        }
+
+        image
+    }
+}
+
+///
+/// Maps a value along a blue -> yellow -> red colour ramp. This is shared with the speed preview.
+///
+/// # Parameters:
+/// - `t`: The heat of a pixel, between 0 and 1
+///
+/// # Returns:
+/// - The colour of the pixel
+///
+pub(crate) fn heat_colour(t: f64) -> Rgba<u8> {
+    const STOPS: [(f64, [f64; 3]); 3] = [(0., [40., 90., 220.]), (0.5, [250., 220., 40.]), (1., [200., 20., 20.])];
+
+    let t = t.clamp(0., 1.);
+    let (lower, upper) = if t <= STOPS[1].0 { (STOPS[0], STOPS[1]) } else { (STOPS[1], STOPS[2]) };
+    let local_t = (t - lower.0) / (upper.0 - lower.0);
+
+    let channel = |i: usize| (lower.1[i] + (upper.1[i] - lower.1[i]) * local_t).round() as u8;
+    Rgba([channel(0), channel(1), channel(2), 255])
+}
@@ -0,0 +1,115 @@
+use image::Rgba;
+use image::RgbaImage;
+
+///
+/// A grid of how much light the ink on each pixel lets through, used to preview how ink builds up
+/// where the pen passes over the same spot many times. Each pass multiplies a pixel's
+/// transmittance, so overlapping strokes darken gradually rather than all being solid black.
+///
+/// # Fields:
+/// - `width`: The width of the grid, in pixels
+/// - `height`: The height of the grid, in pixels
+/// - `scale`: The number of pixels per millimetre
+/// - `padding`: The blank space surrounding the paper on every side, in millimetres
+/// - `opacity`: How much of the light one pass of ink blocks, between 0 and 1
+/// - `transmittance`: The fraction of red, green and blue light let through each pixel, row by row
+///
+pub struct InkLayer {
+    pub width: u32,
+    pub height: u32,
+    pub scale: u32,
+    pub padding: u32,
+    pub opacity: f64,
+
+    transmittance: Vec<[f32; 3]>,
+}
+
+impl InkLayer {
+    ///
+    /// Creates a new, blank ink layer.
+    ///
+    /// # Parameters:
+    /// - `paper_width`: The width of the paper in millimetres
+    /// - `paper_height`: The height of the paper in millimetres
+    /// - `scale`: The number of pixels per millimetre
+    /// - `padding`: The space to leave around each side of the paper, in millimetres
+    /// - `opacity`: How much of the light one pass of ink blocks, clamped between 0 and 1
+    ///
+    /// # Returns:
+    /// - A new `InkLayer` instance
+    ///
+    pub fn new(paper_width: u32, paper_height: u32, scale: u32, padding: u32, opacity: f64) -> InkLayer {
+        let width = (paper_width + 2 * padding) * scale;
+        let height = (paper_height + 2 * padding) * scale;
+        let opacity = if opacity.is_nan() { 1. } else { opacity.clamp(0., 1.) };
+
+        InkLayer { width, height, scale, padding, opacity, transmittance: vec![[1.; 3]; (width * height) as usize] }
+    }
+
+    ///
+    /// Adds one pass of ink between two points. The edge of the stroke is antialiased, and pens
+    /// thinner than a pixel are drawn a pixel wide. The start cap is skipped unless asked for, so
+    /// a continuous path does not ink its joins twice.
+    ///
+    /// # Parameters:
+    /// - `from`: The x/y of the first point on the line, relative to the top left of the paper
+    /// - `to`: The x/y of the second point on the line, relative to the top left of the paper
+    /// - `pen_width`: The width of the pen tip, in millimetres
+    /// - `colour`: The colour of the ink
+    /// - `include_start`: Whether to ink the start cap, for the first segment of a stroke
+    ///
+    pub fn line(&mut self, (x1, y1): (f64, f64), (x2, y2): (f64, f64), pen_width: f64, colour: Rgba<u8>, include_start: bool) {
+        let padding = self.padding as f64;
+        let scale = self.scale as f64;
+        let start = ((x1 + padding) * scale, (y1 + padding) * scale);
+        let end = ((x2 + padding) * scale, (y2 + padding) * scale);
+        let radius = (pen_width * scale / 2.).max(0.5);
+
+        let min_x = (start.0.min(end.0) - radius - 1.).floor().max(0.) as u32;
+        let min_y = (start.1.min(end.1) - radius - 1.).floor().max(0.) as u32;
+        let max_x = ((start.0.max(end.0) + radius + 1.).ceil().max(0.) as u32).min(self.width);
+        let max_y = ((start.1.max(end.1) + radius + 1.).ceil().max(0.) as u32).min(self.height);
+
+        // the light each channel of the ink absorbs
+        let absorbance = [0, 1, 2].map(|i| (1. - colour.0[i] as f64 / 255.) * self.opacity);
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length_sq = dx * dx + dy * dy;
+
+        for py in min_y..max_y {
+            for px in min_x..max_x {
+                // the pixel belongs to the previous segment if it is behind the start of this one
+                let (cx, cy) = (px as f64 + 0.5, py as f64 + 0.5);
+                let t = if length_sq == 0. { 0. } else { ((cx - start.0) * dx + (cy - start.1) * dy) / length_sq };
+                if t <= 0. && !include_start {
+                    continue;
+                }
+
+                let t = t.clamp(0., 1.);
+                let distance = ((cx - start.0 - t * dx).powi(2) + (cy - start.1 - t * dy).powi(2)).sqrt();
+                let coverage = (radius + 0.5 - distance).clamp(0., 1.);
+                if coverage > 0. {
+                    let pixel = &mut self.transmittance[(py * self.width + px) as usize];
+                    for (channel, absorbed) in pixel.iter_mut().zip(absorbance) {
+                        *channel *= (1. - absorbed * coverage) as f32;
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Renders the ink layer to an image, on white paper.
+    ///
+    /// # Returns:
+    /// - The rendered image
+    ///
+    pub fn render(&self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        for (pixel, transmittance) in image.pixels_mut().zip(&self.transmittance) {
+            let [r, g, b] = transmittance.map(|channel| (channel * 255.).round() as u8);
+            *pixel = Rgba([r, g, b, 255]);
+        }
+
+        image
+    }
+}
@@ -0,0 +1,187 @@
+use serde::Serialize;
+
+use crate::drawing::util::placement::{Margins, Rect};
+use crate::hardware::PhysicalDimensions;
+use crate::instruction::InstructionSet;
+use super::error::PreviewError;
+use super::PreviewSink;
+
+///
+/// How far, in millimetres, a stroke may stray outside the margins or page before it is
+/// reported by a preview. This absorbs the rounding of positions to whole motor steps.
+///
+pub const DEFAULT_TOLERANCE_MM: f64 = 0.5;
+
+///
+/// A pen down stroke which crosses the margins or the edge of the page.
+/// All lengths are measured in millimetres.
+///
+/// # Fields:
+/// - `first_instruction`: The index of the stroke's first instruction
+/// - `last_instruction`: The index of the stroke's last instruction
+/// - `length`: The length of the whole stroke
+/// - `length_outside_margins`: How much of the stroke is drawn in the margins or off the page
+/// - `length_outside_page`: How much of the stroke is drawn off the page
+///
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MarginViolation {
+    pub first_instruction: usize,
+    pub last_instruction: usize,
+    pub length: f64,
+    pub length_outside_margins: f64,
+    pub length_outside_page: f64,
+}
+
+impl MarginViolation {
+    ///
+    /// # Returns:
+    /// - Whether part of the stroke is drawn off the page, rather than only in the margins
+    ///
+    pub fn is_off_page(&self) -> bool {
+        self.length_outside_page > 0.
+    }
+}
+
+///
+/// Every pen down stroke of a drawing which crosses the margins or the edge of the page, so a
+/// frontend can point out each one rather than the drawing being silently cropped.
+///
+/// # Fields:
+/// - `violations`: The offending strokes, in the order they are drawn
+///
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct MarginReport {
+    pub violations: Vec<MarginViolation>,
+}
+
+impl MarginReport {
+    ///
+    /// # Returns:
+    /// - Whether every stroke stays inside the margins
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    ///
+    /// # Returns:
+    /// - The strokes which are partly drawn off the page
+    ///
+    pub fn off_page(&self) -> impl Iterator<Item = &MarginViolation> {
+        self.violations.iter().filter(|violation| violation.is_off_page())
+    }
+}
+
+///
+/// A `PreviewSink` which measures how much of each pen down stroke falls outside the margins and
+/// the page, so the check can share a simulation with a preview.
+///
+/// # Fields:
+/// - `inside_margins`: The area inside the margins, grown by the tolerance
+/// - `page`: The page, grown by the tolerance
+/// - `stroke`: The stroke being drawn, if the pen is down
+/// - `report`: The offending strokes found so far
+///
+pub struct MarginCheck {
+    inside_margins: Rect,
+    page: Rect,
+    stroke: Option<MarginViolation>,
+    report: MarginReport,
+}
+
+impl MarginCheck {
+    ///
+    /// # Parameters:
+    /// - `page_width` and `page_height`: The size of the page
+    /// - `margins`: The space to keep clear around each edge of the page
+    /// - `tolerance`: How far a stroke may stray outside the margins or page without being
+    ///   reported, to absorb the rounding of positions to whole motor steps
+    ///
+    /// # Returns:
+    /// - A check which has seen no strokes
+    ///
+    pub fn new(page_width: f64, page_height: f64, margins: &Margins, tolerance: f64) -> MarginCheck {
+        let grow = |rect: Rect| Rect { x: rect.x - tolerance, y: rect.y - tolerance, width: (rect.width + 2. * tolerance).max(0.), height: (rect.height + 2. * tolerance).max(0.) };
+        let inside_margins = Rect { x: margins.left, y: margins.top, width: page_width - margins.left - margins.right, height: page_height - margins.top - margins.bottom };
+
+        MarginCheck { inside_margins: grow(inside_margins), page: grow(Rect { x: 0., y: 0., width: page_width, height: page_height }), stroke: None, report: MarginReport::default() }
+    }
+
+    ///
+    /// Records the stroke being drawn, if it crossed the margins.
+    ///
+    fn end_stroke(&mut self) {
+        if let Some(stroke) = self.stroke.take().filter(|stroke| stroke.length_outside_margins > 0.) {
+            self.report.violations.push(stroke);
+        }
+    }
+
+    ///
+    /// # Returns:
+    /// - The offending strokes
+    ///
+    pub fn finish(mut self) -> MarginReport {
+        self.end_stroke();
+        self.report
+    }
+}
+
+///
+/// # Parameters:
+/// - `rect`: The area the segment should stay inside
+/// - `from` and `to`: The segment
+/// - `length`: The length of the segment
+///
+/// # Returns:
+/// - The length of the segment outside the area
+///
+fn length_outside(rect: &Rect, from: (f64, f64), to: (f64, f64), length: f64) -> f64 {
+    let inside = rect.clip_segment(from, to).map_or(0., |(start, end)| (end.0 - start.0).hypot(end.1 - start.1));
+
+    // clipping a segment which is all inside can still lose a rounding error of its length
+    match length - inside {
+        outside if outside > 1e-9 => outside,
+        _ => 0.,
+    }
+}
+
+impl PreviewSink for MarginCheck {
+    fn segment(&mut self, instruction_idx: usize, from: (f64, f64), to: (f64, f64), is_pen_up: bool) {
+        if is_pen_up {
+            self.end_stroke();
+            return;
+        }
+
+        let length = (to.0 - from.0).hypot(to.1 - from.1);
+        let length_outside_margins = length_outside(&self.inside_margins, from, to, length);
+        let length_outside_page = length_outside(&self.page, from, to, length);
+
+        let stroke = self.stroke.get_or_insert(MarginViolation { first_instruction: instruction_idx, last_instruction: instruction_idx, length: 0., length_outside_margins: 0., length_outside_page: 0. });
+        stroke.last_instruction = instruction_idx;
+        stroke.length += length;
+        stroke.length_outside_margins += length_outside_margins;
+        stroke.length_outside_page += length_outside_page;
+    }
+}
+
+///
+/// Simulates an instruction set to find every pen down stroke crossing the margins or the edge
+/// of the page.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `instruction_set`: The instruction set to check
+/// - `margins`: The space to keep clear around each edge of the page
+/// - `tolerance`: How far a stroke may stray outside the margins or page without being reported
+///
+/// # Returns:
+/// - The offending strokes
+/// - `PreviewError` to explain why the drawing could not be simulated
+///
+pub fn find_margin_violations(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, margins: &Margins, tolerance: f64) -> Result<MarginReport, PreviewError> {
+    let mut check = MarginCheck::new(*physical_dim.page_width(), *physical_dim.page_height(), margins, tolerance);
+    super::simulate(init_xy, physical_dim, instruction_set, &mut check)?;
+
+    Ok(check.finish())
+}
@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Seek};
+use std::time::Duration;
+
+use image::RgbaImage;
+
+use super::canvas;
+use super::error::PreviewError;
+
+const METHOD_KEYWORD: &str = "bbcore:method";
+const PARAMETERS_KEYWORD: &str = "bbcore:parameters";
+const SEED_KEYWORD: &str = "bbcore:seed";
+const DURATION_KEYWORD: &str = "bbcore:duration";
+
+///
+/// The settings a drawing was generated with, embedded in its preview image as text chunks so a
+/// preview found later can be traced back to the drawing it shows. Any field may be missing.
+///
+/// # Fields:
+/// - `method_id`: The backend ID of the drawing method
+/// - `parameters`: The parameters of the drawing method, as a JSON object
+/// - `seed`: The seed of the drawing, if it was randomised
+/// - `duration`: The estimated time taken to draw the drawing
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PreviewMetadata {
+    pub method_id: Option<String>,
+    pub parameters: Option<String>,
+    pub seed: Option<u64>,
+    pub duration: Option<Duration>,
+}
+
+impl PreviewMetadata {
+    ///
+    /// Describes a drawing generated by a drawing method, taking the seed from the `seed`
+    /// parameter if the method has one.
+    ///
+    /// # Parameters:
+    /// - `method_id`: The backend ID of the drawing method
+    /// - `parameters_json`: The parameters of the drawing method, as a JSON object
+    ///
+    /// # Returns:
+    /// - The metadata of the drawing, without a duration
+    ///
+    pub fn for_method(method_id: &str, parameters_json: &str) -> PreviewMetadata {
+        let seed = serde_json::from_str::<serde_json::Value>(parameters_json).ok()
+            .and_then(|parameters| parameters.get("seed").and_then(serde_json::Value::as_u64));
+
+        PreviewMetadata { method_id: Some(method_id.to_owned()), parameters: Some(parameters_json.to_owned()), seed, duration: None }
+    }
+
+    ///
+    /// # Returns:
+    /// - The (keyword, text) of each text chunk to write, for the fields which are set
+    ///
+    fn text_chunks(&self) -> Vec<(&'static str, String)> {
+        [
+            (METHOD_KEYWORD, self.method_id.clone()),
+            (PARAMETERS_KEYWORD, self.parameters.clone()),
+            (SEED_KEYWORD, self.seed.map(|seed| seed.to_string())),
+            (DURATION_KEYWORD, self.duration.map(|duration| duration.as_secs_f64().to_string())),
+        ].into_iter().filter_map(|(keyword, text)| text.map(|text| (keyword, text))).collect()
+    }
+}
+
+///
+/// Encodes an image as a PNG, in memory, with the metadata embedded as text chunks. Text which
+/// can't be written as Latin-1, such as parameters holding a unicode file path, is written as a
+/// UTF-8 iTXt chunk instead of tEXt.
+///
+/// # Parameters:
+/// - `image`: The image to encode
+/// - `metadata`: The settings to embed
+///
+/// # Returns:
+/// - The bytes of the PNG encoded image
+/// - `PreviewError::Encoding` explaining why the image could not be encoded
+///
+pub fn encode_png(image: &RgbaImage, metadata: &PreviewMetadata) -> Result<Vec<u8>, PreviewError> {
+    let encoding_error = |err: png::EncodingError| PreviewError::Encoding { reason: err.to_string() };
+
+    let mut png_bytes: Vec<u8> = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png_bytes, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in metadata.text_chunks() {
+        if text.chars().all(|c| (c as u32) < 256) {
+            encoder.add_text_chunk(keyword.to_owned(), text).map_err(encoding_error)?;
+        } else {
+            encoder.add_itxt_chunk(keyword.to_owned(), text).map_err(encoding_error)?;
+        }
+    }
+
+    let mut writer = encoder.write_header().map_err(encoding_error)?;
+    writer.write_image_data(image.as_raw()).map_err(encoding_error)?;
+    writer.finish().map_err(encoding_error)?;
+
+    Ok(png_bytes)
+}
+
+///
+/// Saves an image to the disk as a PNG, with the metadata embedded as text chunks.
+///
+/// # Parameters:
+/// - `image`: The image to save
+/// - `metadata`: The settings to embed
+/// - `path`: The path to save the image file to
+///
+/// # Returns:
+/// - Void if the image was saved
+/// - `PreviewError` explaining why the image could not be encoded or saved
+///
+pub fn save_image_with_metadata(image: &RgbaImage, metadata: &PreviewMetadata, path: &str) -> Result<(), PreviewError> {
+    let file_path = canvas::check_path(path)?;
+    std::fs::write(file_path, encode_png(image, metadata)?).map_err(|err| PreviewError::Io { path: path.to_owned(), reason: err.to_string() })
+}
+
+///
+/// Reads the generation settings back from a saved preview image.
+///
+/// # Parameters:
+/// - `path`: The path of the preview image
+///
+/// # Returns:
+/// - The settings embedded in the image, with a field for each chunk found
+/// - `PreviewError` explaining why the image could not be read
+///
+pub fn read_metadata(path: &str) -> Result<PreviewMetadata, PreviewError> {
+    let file = File::open(path).map_err(|err| PreviewError::Io { path: path.to_owned(), reason: err.to_string() })?;
+    read_metadata_from(BufReader::new(file))
+}
+
+///
+/// Reads the generation settings back from a PNG encoded preview, in memory.
+///
+/// # Parameters:
+/// - `png_bytes`: The bytes of the PNG encoded preview
+///
+/// # Returns:
+/// - The settings embedded in the image, with a field for each chunk found
+/// - `PreviewError::Metadata` explaining why the image could not be read
+///
+pub fn read_metadata_from_png(png_bytes: &[u8]) -> Result<PreviewMetadata, PreviewError> {
+    read_metadata_from(Cursor::new(png_bytes))
+}
+
+///
+/// Reads the text chunks of a PNG into its metadata, ignoring chunks written by other programs.
+/// Only the chunks before the image data are read, which is where `encode_png` writes them.
+///
+/// # Parameters:
+/// - `reader`: The PNG encoded preview
+///
+/// # Returns:
+/// - The settings embedded in the image
+/// - `PreviewError::Metadata` explaining why the image could not be read
+///
+fn read_metadata_from<R: BufRead + Seek>(reader: R) -> Result<PreviewMetadata, PreviewError> {
+    let metadata_error = |reason: String| PreviewError::Metadata { reason };
+    let png_reader = png::Decoder::new(reader).read_info().map_err(|err| metadata_error(err.to_string()))?;
+    let info = png_reader.info();
+
+    let mut chunks: Vec<(String, String)> = info.uncompressed_latin1_text.iter().map(|chunk| (chunk.keyword.clone(), chunk.text.clone())).collect();
+    for chunk in &info.utf8_text {
+        chunks.push((chunk.keyword.clone(), chunk.get_text().map_err(|err| metadata_error(err.to_string()))?));
+    }
+
+    let mut metadata = PreviewMetadata::default();
+    for (keyword, text) in chunks {
+        match keyword.as_str() {
+            METHOD_KEYWORD => metadata.method_id = Some(text),
+            PARAMETERS_KEYWORD => metadata.parameters = Some(text),
+            SEED_KEYWORD => metadata.seed = Some(text.parse().map_err(|_| metadata_error(format!("The seed {} is not a whole number.", text)))?),
+            DURATION_KEYWORD => {
+                let secs = text.parse::<f64>().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+                metadata.duration = Some(secs.ok_or_else(|| metadata_error(format!("The duration {} is not a number of seconds.", text)))?);
+            },
+            _ => {},
+        }
+    }
+
+    Ok(metadata)
+}
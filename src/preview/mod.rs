@@ -1,71 +1,126 @@
 //!
 //! Image-based preview generation and related components
 //! 
+//! Simulating drawings is always available. Rendering them to images needs the `image` feature.
+//!
 
 use crate::hardware::PhysicalDimensions;
 use crate::instruction::InstructionSet;
 use crate::instruction::error::InstructionError;
+use error::PreviewError;
 
 pub mod belts;
+pub mod error;
+pub mod margins;
+pub mod stats;
+
+#[cfg(feature = "image")]
+mod batch;
+#[cfg(feature = "image")]
 pub mod canvas;
+#[cfg(feature = "image")]
+pub mod gallery;
+#[cfg(feature = "image")]
+pub mod heatmap;
+#[cfg(feature = "image")]
+pub mod ink;
+#[cfg(feature = "image")]
+pub mod metadata;
+#[cfg(feature = "image")]
+mod render;
+
+#[cfg(feature = "image")]
+pub use render::*;
+
+///
+/// The style a preview is rendered in.
+///
+/// - `Ink`: Strokes are drawn as lines, in the colour of their pen
+/// - `Heatmap`: Pixels are coloured by how many times the pen passes over them, to find areas
+///   which risk ink bleed or paper damage
+/// - `Speed`: Strokes are coloured by the top speed the machine reaches along them, from red
+///   (slow) to blue (full speed), to show which regions dominate the drawing time
+/// - `InkBuildUp`: Each pass of the pen lays down ink of `PreviewOptions::ink_opacity`, so
+///   overlapping strokes darken as real ink builds up, to tune methods relying on overdraw density
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PreviewMode {
+    Ink,
+    Heatmap,
+    Speed,
+    InkBuildUp,
+}
 
+
+///
+/// A receiver of the segments produced while simulating an instruction set. Frontends can
+/// implement this to incrementally render very large drawings, rather than waiting for a full
+/// image. It is implemented for any `FnMut(usize, (f64, f64), (f64, f64), bool)` closure.
 ///
-/// Performs the provided motor instructions on a canvas, and saves the file.
+/// # Functions:
+/// - `segment`: Called for every instruction, with the instruction index, the start and end
+///   positions of the pen relative to the top left of the page in millimetres, and whether the
+///   pen was up during the movement
+///
+pub trait PreviewSink {
+    fn segment(&mut self, instruction_idx: usize, from: (f64, f64), to: (f64, f64), is_pen_up: bool);
+}
+
+impl<F: FnMut(usize, (f64, f64), (f64, f64), bool)> PreviewSink for F {
+    fn segment(&mut self, instruction_idx: usize, from: (f64, f64), to: (f64, f64), is_pen_up: bool) {
+        self(instruction_idx, from, to, is_pen_up)
+    }
+}
+
+///
+/// Simulates the provided motor instructions on the belts, passing every movement to a sink.
 ///
 /// # Parameters:
 /// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
-/// - `instruction_set`: The instruction set to preview
-/// - `path`: The path to save the preview image to - *no checks are done to confirm the directory exists*
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `instruction_set`: The instruction set to simulate
+/// - `sink`: The receiver of each simulated segment
 ///
 /// # Returns:
-/// - `None` if the preview generated successfully, and the image was saved
-/// - `InstructionError` to explain why the preview was unable to be generated
+/// - Void if the simulation completed
+/// - `PreviewError` to explain why the simulation failed
 ///
-pub fn generate_preview(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, path: &str) -> Option<InstructionError> {
-    let mut preview_canvas = canvas::PreviewCanvas::new(physical_dim.page_width().ceil() as u32, physical_dim.page_height().ceil() as u32, Some(4));
-    let step_instructions: Vec<(i16, i16, bool)> = match instruction_set.parse_to_numerical_steps() {
-        Ok(value) => value,
-        Err(err) => return Some(err)
-    };
+pub fn simulate<S: PreviewSink + ?Sized>(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, sink: &mut S) -> Result<(), PreviewError> {
+    let step_instructions: Vec<(i16, i16, bool)> = instruction_set.parse_to_numerical_steps()?;
     
-    let mut belts = belts::Belts::new_by_cartesian(physical_dim.page_horizontal_offset() + init_xy.0, physical_dim.page_vertical_offset() + init_xy.1, *physical_dim.motor_interspace());
-    let mut last_xy = belts.get_as_cartesian();
+    let out_of_bounds = |instruction_idx: usize, (step_x, step_y): (i16, i16), (prev_x, prev_y): (f64, f64), reason| {
+        PreviewError::Instruction(InstructionError::DrawingOutOfBounds { instruction_idx, step_x, step_y, prev_x, prev_y, reason })
+    };
+
+    let belts = belts::Belts::new_by_cartesian(physical_dim.page_horizontal_offset() + init_xy.0, physical_dim.page_vertical_offset() + init_xy.1, physical_dim);
+    let mut belts = belts.map_err(|reason| out_of_bounds(0, (0, 0), init_xy, reason))?;
+    let mut last_xy = belts.get_as_cartesian().map_err(|reason| out_of_bounds(0, (0, 0), init_xy, reason))?;
 
     for (index, (ld, rd, is_pen_up)) in step_instructions.iter().enumerate() {
         belts.move_by_steps(*ld, -rd);
-        let (x, y) = belts.get_as_cartesian();
-
-        // an error has occured, so we will say that
-        if x.is_nan() || y.is_nan() && !last_xy.0.is_nan() && !last_xy.1.is_nan() {
-            println!("Error generating instructions - sample point #{} (zero-indexed) steps {} {} to point from x:{} y:{} to x:{} y:{}", index, *ld, -rd, last_xy.0, last_xy.1, x, y);
-            return Some(
-                InstructionError::DrawingOutOfBounds {
-                    instruction_idx: index,
-                    step_x: *ld,
-                    step_y: *rd,
-                    prev_x: last_xy.0,
-                    prev_y: last_xy.1,
-                    target_x: x,
-                    target_y: y
-                }
-            );
-        }
-        if !is_pen_up {
-            preview_canvas.line(last_xy.0 - *physical_dim.page_horizontal_offset(), last_xy.1 - *physical_dim.page_vertical_offset(), x - *physical_dim.page_horizontal_offset(), y - *physical_dim.page_vertical_offset());
-        }
+        let (x, y) = belts.get_as_cartesian().map_err(|reason| out_of_bounds(index, (*ld, *rd), last_xy, reason))?;
+
+        sink.segment(
+            index,
+            (last_xy.0 - *physical_dim.page_horizontal_offset(), last_xy.1 - *physical_dim.page_vertical_offset()),
+            (x - *physical_dim.page_horizontal_offset(), y - *physical_dim.page_vertical_offset()),
+            *is_pen_up
+        );
         last_xy = (x, y);
     }
 
-    preview_canvas.save(path);
-    None
+    Ok(())
 }
 
 ///
 /// Tests relating to preview generation.
 ///
-#[cfg(test)]
+#[cfg(all(test, feature = "image"))]
 mod tests {
     use super::*;
+    use image::Rgba;
+    use crate::hardware::motion::AccelerationProfile;
+    use crate::hardware::pen::Pen;
 
     #[test]
     #[should_panic]
@@ -74,11 +129,324 @@ mod tests {
         let pd = PhysicalDimensions::new(100., 100., 100., 100., 100.);
 
         match generate_preview((0., 0.), &pd, &is, "./") {
-            Some(_) => { 
+            Err(PreviewError::Instruction(_)) => { 
                 panic!();
             },
             _ => { }
         }
 
     }
+
+    #[test]
+    fn preview_png_bytes() {
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0B\x0C\x00\x10\x00\x10\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+
+        let png = generate_preview_png((10., 10.), &pd, &is).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+
+    #[test]
+    fn preview_metadata_round_trip() {
+        let image = image::RgbaImage::from_pixel(4, 2, Rgba([255, 255, 255, 255]));
+        let mut settings = metadata::PreviewMetadata::for_method("spiral", r#"{"seed": 42, "path": "é™.png"}"#);
+        settings.duration = Some(std::time::Duration::from_millis(1500));
+        assert_eq!(settings.seed, Some(42));
+
+        // the unicode parameters are written as UTF-8, and the image is still a plain PNG
+        let png = metadata::encode_png(&image, &settings).unwrap();
+        assert_eq!(metadata::read_metadata_from_png(&png).unwrap(), settings);
+        assert_eq!(image::load_from_memory(&png).unwrap().to_rgba8(), image);
+
+        let plain = canvas::PreviewCanvas::new(4, 2, Some(1)).unwrap().to_png_bytes().unwrap();
+        assert_eq!(metadata::read_metadata_from_png(&plain).unwrap(), metadata::PreviewMetadata::default());
+        assert!(matches!(metadata::read_metadata_from_png(b"not a png"), Err(PreviewError::Metadata { .. })));
+    }
+
+    #[test]
+    fn preview_thumbnails() {
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0B\x0C\x00\x10\x00\x10\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 50.);
+
+        // the 100x50mm page is 400x200 pixels, thumbnails keep its aspect and never grow
+        let (full, thumbnails) = generate_preview_images((10., 10.), &pd, &is, &PreviewOptions::default(), &[100, 1000, 0]).unwrap();
+        assert_eq!(full.dimensions(), (400, 200));
+        assert_eq!(thumbnails.iter().map(|thumbnail| thumbnail.dimensions()).collect::<Vec<_>>(), [(100, 50), (400, 200), (1, 1)]);
+        assert!(thumbnails[0].pixels().any(|pixel| pixel.0[0] < 255));
+    }
+
+    #[test]
+    fn debug_overlay() {
+        // the top left block of a 2 is filled, the block diagonally inside it isn't
+        let mut canvas = canvas::PreviewCanvas::new(40, 20, Some(4)).unwrap();
+        canvas.number(1., 1., 208, Rgba([255, 0, 0, 255]));
+        assert_eq!(*canvas.buffer.get_pixel(4, 4), Rgba([255, 0, 0, 255]));
+        assert_eq!(*canvas.buffer.get_pixel(6, 6), Rgba([255, 255, 255, 255]));
+
+        // two strokes, either side of a pen-up move
+        let is = InstructionSet::new("\x00\x40\x00\x40\x0B\x0C\x00\x40\x00\x40\x0A\x0C\x00\x40\x00\x40\x0B\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+        let plain = render_preview((10., 10.), &pd, &is, &PreviewOptions::default()).unwrap();
+        let debug = render_preview((10., 10.), &pd, &is, &PreviewOptions { debug_arrow_spacing: Some(2.), ..Default::default() }).unwrap();
+
+        let debug_pixels = |image: &image::RgbaImage| image.pixels().filter(|pixel| pixel.0[0] > 200 && pixel.0[1] < 50).count();
+        assert_eq!(debug_pixels(&plain.buffer), 0);
+        assert!(debug_pixels(&debug.buffer) > 0);
+    }
+
+    #[test]
+    fn ink_builds_up() {
+        let mut layer = ink::InkLayer::new(20, 20, 4, 0, 0.5);
+        layer.line((2., 10.125), (18., 10.125), 0., Rgba([0, 0, 0, 255]), true);
+        let once = layer.render().get_pixel(40, 40).0[0];
+        layer.line((18., 10.125), (2., 10.125), 0., Rgba([0, 0, 0, 255]), true);
+        let twice = layer.render().get_pixel(40, 40).0[0];
+
+        // each pass lets through half the light, and a path's joins are inked once
+        assert_eq!((once, twice), (128, 64));
+        layer.line((10.125, 2.), (10.125, 10.125), 0., Rgba([255, 0, 0, 255]), true);
+        layer.line((10.125, 10.125), (10.125, 18.), 0., Rgba([255, 0, 0, 255]), false);
+        assert_eq!(*layer.render().get_pixel(40, 40), Rgba([64, 32, 32, 255]));
+        assert_eq!(*layer.render().get_pixel(40, 60), Rgba([255, 128, 128, 255]));
+
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0B\x0C\x00\x10\x00\x10\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+        let preview_canvas = render_preview((10., 10.), &pd, &is, &PreviewOptions { mode: PreviewMode::InkBuildUp, ..Default::default() }).unwrap();
+        assert!(preview_canvas.buffer.pixels().any(|pixel| pixel.0[0] < 255));
+        assert!(preview_canvas.buffer.pixels().all(|pixel| pixel.0[0] > 100));
+    }
+
+    #[test]
+    fn hidden_labels_are_not_drawn() {
+        use crate::drawing::stroke::{self, Stroke};
+
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+        let line = |y: f64, label: &str| Stroke { points: vec![(20., y), (80., y)], pen_up: false, layer: 0, label: Some(label.to_owned()) };
+        let ((bytes, start_x, start_y), labels) = stroke::compile_with_labels(&[line(20., "top"), line(80., "bottom")], &pd).unwrap();
+        let is = InstructionSet::new(bytes, start_x, start_y).unwrap();
+
+        let options = PreviewOptions { scale: 1, labels, hidden_labels: vec!["top".to_owned()], ..Default::default() };
+        let preview_canvas = render_preview(is.get_init(), &pd, &is, &options).unwrap();
+        let is_inked = |y: u32| (30..70).any(|x| (y - 1..=y + 1).any(|y| preview_canvas.buffer.get_pixel(x, y).0[0] < 255));
+        assert!(!is_inked(20));
+        assert!(is_inked(80));
+    }
+
+    #[test]
+    fn preview_margin_report() {
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0B\x0C\x00\x10\x00\x10\x0A\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+
+        // the pen starts 10mm into the 20mm margins, and only its pen down move is reported
+        let (_, report) = render_preview_with_report((10., 10.), &pd, &is, &PreviewOptions { margins: Some((20., 20.)), ..Default::default() }).unwrap();
+        assert_eq!(report.violations.len(), 1);
+        assert_eq!((report.violations[0].first_instruction, report.violations[0].last_instruction), (0, 0));
+        assert!(!report.violations[0].is_off_page());
+
+        let (_, report) = render_preview_with_report((10., 10.), &pd, &is, &PreviewOptions::default()).unwrap();
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    fn drawing_stays_inside_margin_guides() {
+        use crate::drawing::stroke::{self, Stroke};
+
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+        let square = Stroke { points: vec![(20., 20.), (80., 20.), (80., 80.), (20., 80.), (20., 20.)], pen_up: false, layer: 0, label: None };
+        let (ins_bytes, init_x, init_y) = stroke::compile(&[square], &pd).unwrap();
+        let is = InstructionSet::new(ins_bytes, init_x, init_y).unwrap();
+
+        let options = PreviewOptions { scale: 2, padding: 5, draw_border: true, margins: Some((15., 15.)), ..Default::default() };
+        let preview_canvas = render_preview((init_x, init_y), &pd, &is, &options).unwrap();
+        assert_eq!((preview_canvas.width, preview_canvas.height), (220, 220));
+
+        // the page starts 10px in and the margins 30px further, so ink only falls within them
+        let (margin_start, margin_end) = ((5 + 15) * 2, (5 + 85) * 2);
+        for (x, y, pixel) in preview_canvas.buffer.enumerate_pixels() {
+            if pixel.0[0] < 100 {
+                assert!((margin_start..=margin_end).contains(&x) && (margin_start..=margin_end).contains(&y), "ink at ({}, {})", x, y);
+            }
+        }
+        assert!(preview_canvas.buffer.pixels().any(|pixel| pixel.0[0] < 100));
+
+        // the border and margin guides are drawn, and the padding outside the page is blank
+        assert_eq!(preview_canvas.buffer.get_pixel(110, 10).0[0], 128);
+        assert_eq!(preview_canvas.buffer.get_pixel(110, margin_start).0[0], 200);
+        assert_eq!(*preview_canvas.buffer.get_pixel(110, 5), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn method_gallery() {
+        let directory = std::env::temp_dir().join("bbcore_gallery");
+        let _ = std::fs::create_dir_all(&directory);
+        let pd = PhysicalDimensions::new(500., 150., 150., 200., 250.);
+
+        let index = gallery::generate_gallery(&pd, directory.to_str().unwrap(), |method_id| match method_id {
+            "lines" => Some(r#"{"num_lines": 3, "horizontal_margin": 10}"#.to_owned()),
+            "calibration" => Some(r#"{"columns": 0}"#.to_owned()),
+            _ => None,
+        }).unwrap();
+
+        // every method is listed, and only the one with valid parameters is previewed
+        let lines = index.methods.iter().find(|entry| entry.method_id == "lines").unwrap();
+        let calibration = index.methods.iter().find(|entry| entry.method_id == "calibration").unwrap();
+        assert_eq!(index.methods.len(), crate::drawing::registry::methods().len());
+        assert_eq!(lines.preview.as_deref(), Some("lines.png"));
+        assert!(calibration.preview.is_none() && calibration.error.is_some());
+        assert_eq!(metadata::read_metadata(directory.join("lines-thumbnail.png").to_str().unwrap()).unwrap().method_id.as_deref(), Some("lines"));
+
+        let index_json: serde_json::Value = serde_json::from_slice(&std::fs::read(directory.join("index.json")).unwrap()).unwrap();
+        assert_eq!(index_json["methods"][0]["parameters"]["num_lines"], 3);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn thick_pen_width() {
+        let mut canvas = canvas::PreviewCanvas::new(20, 20, Some(4)).unwrap();
+        canvas.pen_width = 1.;
+        canvas.line(2., 10., 18., 10.);
+
+        // a 1mm pen at 4px/mm covers 2 pixels either side of the line's centre
+        assert_eq!(*canvas.buffer.get_pixel(40, 38), Rgba([0, 0, 0, 255]));
+        assert_eq!(*canvas.buffer.get_pixel(40, 41), Rgba([0, 0, 0, 255]));
+        assert_eq!(*canvas.buffer.get_pixel(40, 44), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn diff_identical_sets() {
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0B\x0C\x00\x10\x00\x10\x0C".to_owned().into_bytes(), 10., 10.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+
+        // identical drawings have no red or blue strokes, only black
+        let image = diff_preview_image(&is, &is, &pd).unwrap();
+        assert!(image.pixels().all(|pixel| pixel.0[0] == pixel.0[1] && pixel.0[1] == pixel.0[2]));
+        assert!(image.pixels().any(|pixel| pixel.0[0] < 255));
+    }
+
+    #[test]
+    fn machine_preview_size() {
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+        let preview_canvas = render_machine_preview((50., 50.), &pd, &PreviewOptions { scale: 1, ..Default::default() }).unwrap();
+
+        // the motors are 500mm apart and the page ends 250mm below them, with 10mm of padding
+        assert_eq!((preview_canvas.width, preview_canvas.height), (520, 270));
+        assert_eq!(*preview_canvas.buffer.get_pixel(10, 10), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn machine_envelope_follows_the_physical_dimensions() {
+        use crate::hardware::KeepOutZone;
+        use crate::hardware::kinematics::KinematicsKind;
+
+        // the cell whose centre is nearest a point, relative to the left motor shaft
+        let at = |(cell, reachable): &(f64, Vec<Vec<bool>>), x: f64, y: f64| reachable[(y / cell) as usize][(x / cell) as usize];
+
+        // between the motors, 30 degree belts meet about 144mm below them
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.).with_min_belt_angle(30.);
+        let cells = reachable_cells(&pd, 0., 500., 250.);
+        assert!(!at(&cells, 250., 135.) && at(&cells, 250., 155.));
+        assert!(!at(&cells, 20., 200.) && at(&cells, 250., 200.));
+
+        let pd = pd.with_keep_out_zones(vec![KeepOutZone { x: 20., y: 20., width: 30., height: 30. }]);
+        let cells = reachable_cells(&pd, 0., 500., 250.);
+        assert!(!at(&cells, 185., 185.) && at(&cells, 215., 215.));
+
+        // a gantry has no belt angle, so everything outside the keep-out zone is reachable
+        let pd = pd.with_kinematics(KinematicsKind::Cartesian);
+        let cells = reachable_cells(&pd, 0., 500., 250.);
+        assert!(at(&cells, 20., 10.) && at(&cells, 250., 10.));
+        assert!(!at(&cells, 185., 185.));
+    }
+
+    #[test]
+    fn preview_stats() {
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0A\x0C\x00\x10\x00\x10\x0B\x0C\x00\x10\x00\x10\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+
+        let (_, preview_stats) = render_preview_with_stats((10., 10.), &pd, &is, &PreviewOptions::default(), &AccelerationProfile::new(16., f64::INFINITY, f64::INFINITY)).unwrap();
+        assert_eq!(preview_stats.instruction_count, 3);
+        assert!(preview_stats.travel_distance > 0.);
+        assert!((preview_stats.ink_distance - 2. * preview_stats.travel_distance).abs() < 0.01);
+        assert_eq!(preview_stats.duration, std::time::Duration::from_secs(3));
+    }
+
+    #[test]
+    fn preview_errors() {
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0B\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+
+        assert!(matches!(generate_preview((10., 10.), &pd, &is, "./missing_directory/preview.png"), Err(PreviewError::InvalidPath { .. })));
+        assert!(matches!(canvas::PreviewCanvas::new(100_000, 100_000, Some(100)), Err(PreviewError::CanvasTooLarge { .. })));
+    }
+
+    #[test]
+    fn pen_colour_by_layer() {
+        let options = PreviewOptions { layer_starts: vec![10, 20], pen_colours: vec![Rgba([0, 0, 0, 255]), Rgba([255, 0, 0, 255])], ..Default::default() };
+
+        assert_eq!(options.pen_colour_at(0), Rgba([0, 0, 0, 255]));
+        assert_eq!(options.pen_colour_at(10), Rgba([255, 0, 0, 255]));
+        assert_eq!(options.pen_colour_at(25), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn pens_by_layer() {
+        let pens = [Pen::new("Fineliner", 0.3, 2., 0.5, [0, 0, 0, 255]), Pen::new("Brush", 1., 3., 0.3, [255, 0, 0, 255])];
+        let options = PreviewOptions { layer_starts: vec![10], ..Default::default() }.with_pens(&pens);
+
+        assert_eq!(options.pen_width_at(0), 0.3);
+        assert_eq!(options.pen_width_at(10), 1.);
+        assert_eq!(options.pen_colour_at(10), Rgba([255, 0, 0, 255]));
+        assert!((pens[1].fill_spacing() - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn partial_range_colours() {
+        let options = PreviewOptions { range: Some(5..10), remaining_colour: Some(Rgba([200, 200, 200, 255])), ..Default::default() };
+
+        assert_eq!(options.colour_at(4), Some(Rgba([200, 200, 200, 255])));
+        assert_eq!(options.colour_at(5), Some(Rgba([0, 0, 0, 255])));
+        assert_eq!(PreviewOptions { range: Some(5..10), ..Default::default() }.colour_at(10), None);
+    }
+
+    #[test]
+    fn simulate_to_sink() {
+        let is = InstructionSet::new("\x00\x10\x00\x10\x0B\x0C\x00\x10\x00\x10\x0C".to_owned().into_bytes(), 0., 0.).unwrap();
+        let pd = PhysicalDimensions::new(500., 150., 150., 100., 100.);
+
+        let mut segments: Vec<bool> = vec![];
+        simulate((10., 10.), &pd, &is, &mut |_, _, _, is_pen_up| segments.push(is_pen_up)).unwrap();
+        assert_eq!(segments, [false, false]);
+    }
+
+    #[test]
+    fn batched_lines_match_single_lines() {
+        use rand::Rng;
+
+        let mut rng = rand::rng();
+        let mut batched = canvas::PreviewCanvas::new_with_padding(100, 150, Some(3), 5).unwrap();
+        let mut single = canvas::PreviewCanvas::new_with_padding(100, 150, Some(3), 5).unwrap();
+
+        // short and long lines in every direction, overlapping, some leaving the canvas
+        for _ in 0..5000 {
+            let (x1, y1) = (rng.random_range(-10. ..110.), rng.random_range(-10. ..160.));
+            let length = if rng.random_bool(0.9) { 2. } else { 80. };
+            let (x2, y2) = (x1 + rng.random_range(-length..length), y1 + rng.random_range(-length..length));
+            let colour = Rgba([rng.random(), rng.random(), rng.random(), 255]);
+            batched.queue_line(x1, y1, x2, y2, colour);
+            single.coloured_line(x1, y1, x2, y2, colour);
+        }
+        batched.flush_lines();
+
+        assert!(batched.buffer == single.buffer);
+    }
+
+    #[test]
+    fn heatmap_counts_overdraw() {
+        let mut heat = heatmap::Heatmap::new(10, 10, 1, 0);
+        heat.line(0., 5., 9., 5.);
+        heat.line(9., 5., 0., 5.);
+        heat.line(0., 5., 9., 5.);
+
+        assert_eq!(heat.max_count(), 3);
+    }
 }
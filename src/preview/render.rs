@@ -0,0 +1,710 @@
+use std::ops::Range;
+
+use image::{Rgba, RgbaImage};
+
+use crate::hardware::PhysicalDimensions;
+use crate::hardware::motion::AccelerationProfile;
+use crate::hardware::pen::Pen;
+use crate::instruction::InstructionSet;
+use super::error::PreviewError;
+use crate::drawing::stroke::LabelSpan;
+use crate::drawing::util::placement::Margins;
+use super::margins::{self, MarginCheck, MarginReport};
+use super::{canvas, heatmap, ink, stats, simulate, PreviewMode, PreviewSink};
+
+///
+/// Options to adjust how a preview is rendered.
+/// All lengths are measured in millimetres.
+///
+/// # Fields:
+/// - `mode`: The style to render the preview in
+/// - `scale`: The number of pixels per millimetre of paper
+/// - `padding`: The blank space drawn around the paper, so strokes leaving the page are visible
+/// - `pen_width`: The width of the pen tip, so strokes are drawn as thick as they will be on paper
+/// - `draw_border`: Whether to outline the edge of the paper
+/// - `margins`: An optional (horizontal, vertical) margin to outline as a guide
+/// - `layer_starts`: The instruction indices at which each new layer (pen change) begins, ascending
+/// - `pen_colours`: The pen colour of each layer, the first colour is used before any layer starts
+/// - `pen_widths`: The pen width of each layer, cycled like `pen_colours`, if empty `pen_width` is used
+/// - `labels`: The instructions of each run of labelled strokes, ascending, as returned by
+///   `stroke::compile_with_labels`
+/// - `hidden_labels`: The labels whose strokes are not drawn, to inspect the rest of the drawing
+/// - `range`: An optional range of instruction indices to draw, to show the progress of a live drawing
+/// - `remaining_colour`: If set, instructions outside of `range` are drawn in this colour rather than skipped
+/// - `acceleration`: The motion limits of the pen in millimetres, used by `PreviewMode::Speed`
+/// - `ink_opacity`: How much of the light one pass of ink blocks, between 0 and 1, used by `PreviewMode::InkBuildUp`
+/// - `debug_arrow_spacing`: If set, draws a debug overlay over the preview. Each stroke is numbered
+///   at its start in the order it is drawn, a direction arrow is drawn every this many millimetres
+///   along it, and the pen-up travel between strokes is drawn faintly
+///
+pub struct PreviewOptions {
+    pub mode: PreviewMode,
+    pub scale: u32,
+    pub padding: u32,
+    pub pen_width: f64,
+    pub draw_border: bool,
+    pub margins: Option<(f64, f64)>,
+
+    pub layer_starts: Vec<usize>,
+    pub pen_colours: Vec<Rgba<u8>>,
+    pub pen_widths: Vec<f64>,
+
+    pub labels: Vec<LabelSpan>,
+    pub hidden_labels: Vec<String>,
+
+    pub range: Option<Range<usize>>,
+    pub remaining_colour: Option<Rgba<u8>>,
+
+    pub acceleration: AccelerationProfile,
+    pub ink_opacity: f64,
+
+    pub debug_arrow_spacing: Option<f64>,
+}
+
+impl PreviewOptions {
+    ///
+    /// Sets the colour and width of each layer from the pens drawing them.
+    ///
+    /// # Parameters:
+    /// - `pens`: The pen drawing each layer, in order
+    ///
+    /// # Returns:
+    /// - The `PreviewOptions`, drawing each layer with its pen
+    ///
+    pub fn with_pens(mut self, pens: &[Pen]) -> PreviewOptions {
+        self.pen_colours = pens.iter().map(|pen| Rgba(pen.colour)).collect();
+        self.pen_widths = pens.iter().map(|pen| pen.line_width_mm).collect();
+        self
+    }
+
+    ///
+    /// # Parameters:
+    /// - `instruction_idx`: The index of an instruction
+    ///
+    /// # Returns:
+    /// - The index of the layer the instruction belongs to
+    ///
+    fn layer_at(&self, instruction_idx: usize) -> usize {
+        self.layer_starts.iter().take_while(|start| **start <= instruction_idx).count()
+    }
+
+    ///
+    /// Finds the pen colour to draw an instruction with. If there are more layers than pen
+    /// colours, the colours are cycled.
+    ///
+    /// # Parameters:
+    /// - `instruction_idx`: The index of the instruction being drawn
+    ///
+    /// # Returns:
+    /// - The colour of the pen for the given instruction
+    ///
+    pub fn pen_colour_at(&self, instruction_idx: usize) -> Rgba<u8> {
+        if self.pen_colours.is_empty() {
+            return Rgba([0, 0, 0, 255]);
+        }
+
+        self.pen_colours[self.layer_at(instruction_idx) % self.pen_colours.len()]
+    }
+
+    ///
+    /// Finds the pen width to draw an instruction with. If there are more layers than pen
+    /// widths, the widths are cycled.
+    ///
+    /// # Parameters:
+    /// - `instruction_idx`: The index of the instruction being drawn
+    ///
+    /// # Returns:
+    /// - The width of the pen for the given instruction, in millimetres
+    ///
+    pub fn pen_width_at(&self, instruction_idx: usize) -> f64 {
+        if self.pen_widths.is_empty() {
+            return self.pen_width;
+        }
+
+        self.pen_widths[self.layer_at(instruction_idx) % self.pen_widths.len()]
+    }
+
+    ///
+    /// # Parameters:
+    /// - `instruction_idx`: The index of an instruction
+    ///
+    /// # Returns:
+    /// - Whether the instruction belongs to strokes with a hidden label
+    ///
+    pub fn is_hidden(&self, instruction_idx: usize) -> bool {
+        if self.hidden_labels.is_empty() {
+            return false;
+        }
+
+        let idx = self.labels.partition_point(|span| span.last_instruction < instruction_idx);
+        self.labels.get(idx).is_some_and(|span| span.contains(instruction_idx) && self.hidden_labels.contains(&span.label))
+    }
+
+    ///
+    /// Finds the colour to draw an instruction with, respecting the configured `range` and
+    /// `hidden_labels`.
+    ///
+    /// # Parameters:
+    /// - `instruction_idx`: The index of the instruction being drawn
+    ///
+    /// # Returns:
+    /// - `None` if the instruction should not be drawn
+    /// - The colour to draw the instruction with
+    ///
+    pub fn colour_at(&self, instruction_idx: usize) -> Option<Rgba<u8>> {
+        if self.is_hidden(instruction_idx) {
+            return None;
+        }
+
+        match &self.range {
+            Some(range) if !range.contains(&instruction_idx) => self.remaining_colour,
+            _ => Some(self.pen_colour_at(instruction_idx)),
+        }
+    }
+}
+
+impl Default for PreviewOptions {
+    ///
+    /// # Returns:
+    /// - The options used by `generate_preview`, a plain page at 4 pixels per millimetre with hairline strokes
+    ///
+    fn default() -> Self {
+        PreviewOptions { mode: PreviewMode::Ink, scale: 4, padding: 0, pen_width: 0., draw_border: false, margins: None, layer_starts: vec![], pen_colours: vec![Rgba([0, 0, 0, 255])], pen_widths: vec![], labels: vec![], hidden_labels: vec![], range: None, remaining_colour: None, acceleration: AccelerationProfile::new(40., 400., 10000.), ink_opacity: 0.4, debug_arrow_spacing: None }
+    }
+}
+
+///
+/// Performs the provided motor instructions on a canvas, and saves the file.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `instruction_set`: The instruction set to preview
+/// - `path`: The path to save the preview image to
+///
+/// # Returns:
+/// - Void if the preview generated successfully, and the image was saved
+/// - `PreviewError` to explain why the preview was unable to be generated or saved
+///
+pub fn generate_preview(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, path: &str) -> Result<(), PreviewError> {
+    render_preview(init_xy, physical_dim, instruction_set, &PreviewOptions::default())?.save(path)
+}
+
+///
+/// Performs the provided motor instructions on a canvas once, and saves the full resolution
+/// preview along with downscaled thumbnails of it, so a gallery does not simulate the drawing again.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `instruction_set`: The instruction set to preview
+/// - `path`: The path to save the full resolution preview image to
+/// - `thumbnails`: The (maximum width in pixels, path) of each thumbnail to save
+///
+/// # Returns:
+/// - Void if the preview and every thumbnail generated successfully, and were saved
+/// - `PreviewError` to explain why the preview was unable to be generated or saved
+///
+pub fn generate_preview_with_thumbnails(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, path: &str, thumbnails: &[(u32, &str)]) -> Result<(), PreviewError> {
+    let preview_canvas = render_preview(init_xy, physical_dim, instruction_set, &PreviewOptions::default())?;
+    preview_canvas.save(path)?;
+    for (max_width, thumbnail_path) in thumbnails {
+        canvas::save_image(&preview_canvas.thumbnail(*max_width), thumbnail_path)?;
+    }
+
+    Ok(())
+}
+
+///
+/// Performs the provided motor instructions on a canvas once, and returns the full resolution
+/// image buffer along with downscaled thumbnails of it.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `instruction_set`: The instruction set to preview
+/// - `options`: The options to render the full resolution preview with
+/// - `thumbnail_widths`: The maximum width in pixels of each thumbnail
+///
+/// # Returns:
+/// - The full resolution image buffer, and a thumbnail for each width in the same order
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+pub fn generate_preview_images(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, options: &PreviewOptions, thumbnail_widths: &[u32]) -> Result<(RgbaImage, Vec<RgbaImage>), PreviewError> {
+    let preview_canvas = render_preview(init_xy, physical_dim, instruction_set, options)?;
+    let thumbnails = thumbnail_widths.iter().map(|max_width| preview_canvas.thumbnail(*max_width)).collect();
+
+    Ok((preview_canvas.buffer, thumbnails))
+}
+
+///
+/// Performs the provided motor instructions on a canvas, and returns the image buffer rather
+/// than writing it to the disk. This is useful for frontends which display the preview directly.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `instruction_set`: The instruction set to preview
+///
+/// # Returns:
+/// - The preview image buffer
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+pub fn generate_preview_image(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet) -> Result<RgbaImage, PreviewError> {
+    render_preview(init_xy, physical_dim, instruction_set, &PreviewOptions::default()).map(|preview_canvas| preview_canvas.buffer)
+}
+
+///
+/// Performs the provided motor instructions on a canvas, and returns the image encoded as PNG
+/// bytes, ready to be sent over a socket or displayed by a web frontend.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `instruction_set`: The instruction set to preview
+///
+/// # Returns:
+/// - The PNG encoded bytes of the preview
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+pub fn generate_preview_png(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet) -> Result<Vec<u8>, PreviewError> {
+    render_preview(init_xy, physical_dim, instruction_set, &PreviewOptions::default())?.to_png_bytes()
+}
+
+///
+/// Simulates the provided motor instructions on a canvas, and summarises the drawing alongside
+/// it, so a shared preview can also answer how long the drawing took.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `instruction_set`: The instruction set to preview
+/// - `options`: The options to render the preview with
+/// - `acceleration_profile`: The motion limits of the motors in motor steps, used to estimate the duration
+///
+/// # Returns:
+/// - The canvas, with the drawing performed on it, and the summary of the drawing
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+pub fn render_preview_with_stats(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, options: &PreviewOptions, acceleration_profile: &AccelerationProfile) -> Result<(canvas::PreviewCanvas, stats::PreviewStats), PreviewError> {
+    let preview_canvas = render_preview(init_xy, physical_dim, instruction_set, options)?;
+    let preview_stats = stats::calculate_stats(init_xy, physical_dim, instruction_set, acceleration_profile)?;
+
+    Ok((preview_canvas, preview_stats))
+}
+
+///
+/// Renders two instruction sets on one canvas and saves the file, to check that an optimisation
+/// or refactor has not changed a drawing. Strokes only in `a` are red, strokes only in `b` are
+/// blue, and strokes in both are black. Each set starts from its own initial position.
+///
+/// # Parameters:
+/// - `a`: The first instruction set to compare
+/// - `b`: The second instruction set to compare
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `path`: The path to save the preview image to
+///
+/// # Returns:
+/// - Void if the preview generated successfully, and the image was saved
+/// - `PreviewError` to explain why the preview was unable to be generated or saved
+///
+pub fn diff_preview(a: &InstructionSet, b: &InstructionSet, physical_dim: &PhysicalDimensions, path: &str) -> Result<(), PreviewError> {
+    canvas::save_image(&diff_preview_image(a, b, physical_dim)?, path)
+}
+
+///
+/// Renders two instruction sets on one canvas, and returns the image buffer. See `diff_preview`
+/// for the colours used.
+///
+/// # Parameters:
+/// - `a`: The first instruction set to compare
+/// - `b`: The second instruction set to compare
+/// - `physical_dim`: The physical dimensions of the machine
+///
+/// # Returns:
+/// - The comparison image buffer
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+pub fn diff_preview_image(a: &InstructionSet, b: &InstructionSet, physical_dim: &PhysicalDimensions) -> Result<RgbaImage, PreviewError> {
+    let options = PreviewOptions::default();
+    let canvas_a = render_preview(a.get_init(), physical_dim, a, &options)?;
+    let canvas_b = render_preview(b.get_init(), physical_dim, b, &options)?;
+
+    // each channel is darkened by the ink of the other set, so lone strokes keep one colour
+    let mut image = canvas_a.buffer;
+    for (pixel_a, pixel_b) in image.pixels_mut().zip(canvas_b.buffer.pixels()) {
+        let ink_a = 255 - pixel_a.0[0];
+        let ink_b = 255 - pixel_b.0[0];
+        *pixel_a = Rgba([255 - ink_b, 255 - ink_a.max(ink_b), 255 - ink_a, 255]);
+    }
+
+    Ok(image)
+}
+
+///
+/// Simulates the provided motor instructions, drawing every pen-down movement on a new canvas.
+/// The canvas is sized from the page dimensions of `physical_dim`.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `instruction_set`: The instruction set to preview
+/// - `options`: The options to render the preview with
+///
+/// # Returns:
+/// - The canvas, with the drawing performed on it
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+pub fn render_preview(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, options: &PreviewOptions) -> Result<canvas::PreviewCanvas, PreviewError> {
+    render_preview_to_sink(init_xy, physical_dim, instruction_set, options, &mut |_, _, _, _| {})
+}
+
+///
+/// Simulates the provided motor instructions on a new canvas, and reports every pen down stroke
+/// crossing the margins of `options` or the edge of the page, from the same simulation. Without
+/// margins, only strokes leaving the page are reported.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `instruction_set`: The instruction set to preview
+/// - `options`: The options to render the preview with
+///
+/// # Returns:
+/// - The canvas, with the drawing performed on it, and the strokes crossing the margins
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+pub fn render_preview_with_report(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, options: &PreviewOptions) -> Result<(canvas::PreviewCanvas, MarginReport), PreviewError> {
+    let margins = options.margins.map_or(Margins::default(), |(horizontal, vertical)| Margins::symmetric(horizontal, vertical));
+    let mut check = MarginCheck::new(*physical_dim.page_width(), *physical_dim.page_height(), &margins, margins::DEFAULT_TOLERANCE_MM);
+    let preview_canvas = render_preview_to_sink(init_xy, physical_dim, instruction_set, options, &mut check)?;
+
+    Ok((preview_canvas, check.finish()))
+}
+
+///
+/// Simulates the provided motor instructions on a new canvas, passing every movement on to
+/// another sink too, so it shares the simulation.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `instruction_set`: The instruction set to preview
+/// - `options`: The options to render the preview with
+/// - `sink`: The receiver of each simulated segment, alongside the canvas
+///
+/// # Returns:
+/// - The canvas, with the drawing performed on it
+/// - `PreviewError` to explain why the preview was unable to be generated
+///
+fn render_preview_to_sink<S: PreviewSink + ?Sized>(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, options: &PreviewOptions, sink: &mut S) -> Result<canvas::PreviewCanvas, PreviewError> {
+    let page_width = *physical_dim.page_width();
+    let page_height = *physical_dim.page_height();
+    let mut preview_canvas = canvas::PreviewCanvas::new_with_padding(page_width.ceil() as u32, page_height.ceil() as u32, Some(options.scale.max(1)), options.padding)?;
+    let (paper_width, paper_height) = (preview_canvas.width / preview_canvas.scale - 2 * options.padding, preview_canvas.height / preview_canvas.scale - 2 * options.padding);
+    let mut heat = match options.mode {
+        PreviewMode::Heatmap => Some(heatmap::Heatmap::new(paper_width, paper_height, preview_canvas.scale, options.padding)),
+        _ => None,
+    };
+    let mut ink = match options.mode {
+        PreviewMode::InkBuildUp => Some(ink::InkLayer::new(paper_width, paper_height, preview_canvas.scale, options.padding, options.ink_opacity)),
+        _ => None,
+    };
+
+    // guides are drawn first, so the drawing sits on top of them
+    if heat.is_none() && ink.is_none() {
+        draw_guides(&mut preview_canvas, physical_dim, options);
+    }
+
+    let mut overlay = options.debug_arrow_spacing.map(DebugOverlay::new);
+    let mut was_pen_up = true;
+    simulate(init_xy, physical_dim, instruction_set, &mut |index: usize, (x1, y1): (f64, f64), (x2, y2): (f64, f64), is_pen_up: bool| {
+        sink.segment(index, (x1, y1), (x2, y2), is_pen_up);
+        let colour = options.colour_at(index);
+        if let Some(overlay) = overlay.as_mut() {
+            overlay.segment((x1, y1), (x2, y2), is_pen_up, colour.is_some());
+        }
+        let is_stroke_start = std::mem::replace(&mut was_pen_up, is_pen_up);
+        preview_canvas.pen_width = options.pen_width_at(index);
+        if let (false, Some(colour)) = (is_pen_up, colour) {
+            match (heat.as_mut(), ink.as_mut(), options.mode) {
+                (Some(heat), _, _) => heat.line(x1, y1, x2, y2),
+                (_, Some(ink), _) => ink.line((x1, y1), (x2, y2), preview_canvas.pen_width, colour, is_stroke_start),
+                (None, None, PreviewMode::Speed) => {
+                    let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+                    let speed = options.acceleration.peak_speed(length);
+                    preview_canvas.queue_line(x1, y1, x2, y2, heatmap::heat_colour(1. - speed / options.acceleration.max_speed()));
+                },
+                (None, None, _) => preview_canvas.queue_line(x1, y1, x2, y2, colour),
+            }
+        }
+    })?;
+    preview_canvas.flush_lines();
+
+    // the heatmap and ink layer replace the whole buffer, so their guides are drawn over the top
+    if let Some(heat) = heat {
+        preview_canvas.buffer = heat.render();
+        draw_guides(&mut preview_canvas, physical_dim, options);
+    }
+    if let Some(ink) = ink {
+        preview_canvas.buffer = ink.render();
+        draw_guides(&mut preview_canvas, physical_dim, options);
+    }
+    if let Some(overlay) = overlay {
+        overlay.draw(&mut preview_canvas);
+    }
+
+    Ok(preview_canvas)
+}
+
+///
+/// The colour of the stroke numbers and direction arrows of the debug overlay.
+///
+const DEBUG_COLOUR: Rgba<u8> = Rgba([230, 0, 120, 255]);
+
+///
+/// The colour of the pen-up travel drawn by the debug overlay.
+///
+const DEBUG_TRAVEL_COLOUR: Rgba<u8> = Rgba([150, 190, 255, 255]);
+
+///
+/// The annotations of the debug overlay, collected while simulating so they can be drawn on top
+/// of the finished preview.
+///
+/// # Fields:
+/// - `arrow_spacing`: The distance between direction arrows along each stroke
+/// - `to_next_arrow`: The distance left along the current stroke until the next arrow
+/// - `num_strokes`: The number of strokes started so far
+/// - `is_pen_up`: Whether the pen was up during the last segment
+/// - `labels`: The (x, y, stroke number) of each stroke start to number
+/// - `arrows`: The (x, y, direction) of each arrow tip
+/// - `travel`: The start and end of each pen-up movement
+///
+struct DebugOverlay {
+    arrow_spacing: f64,
+    to_next_arrow: f64,
+    num_strokes: usize,
+    is_pen_up: bool,
+
+    labels: Vec<(f64, f64, usize)>,
+    arrows: Vec<(f64, f64, (f64, f64))>,
+    travel: Vec<((f64, f64), (f64, f64))>,
+}
+
+impl DebugOverlay {
+    ///
+    /// # Parameters:
+    /// - `arrow_spacing`: The distance between direction arrows along each stroke, in millimetres.
+    ///   Arrows are not drawn if it isn't a positive number
+    ///
+    /// # Returns:
+    /// - An empty overlay
+    ///
+    fn new(arrow_spacing: f64) -> DebugOverlay {
+        DebugOverlay { arrow_spacing, to_next_arrow: arrow_spacing, num_strokes: 0, is_pen_up: true, labels: vec![], arrows: vec![], travel: vec![] }
+    }
+
+    ///
+    /// Annotates a simulated segment. Strokes are counted even when they aren't drawn, so the
+    /// numbers stay the same when only part of the drawing is previewed.
+    ///
+    /// # Parameters:
+    /// - `from` and `to`: The start and end of the segment, relative to the top left of the page
+    /// - `is_pen_up`: Whether the pen was up during the segment
+    /// - `is_drawn`: Whether the segment is drawn in the preview
+    ///
+    fn segment(&mut self, from: (f64, f64), to: (f64, f64), is_pen_up: bool, is_drawn: bool) {
+        let was_pen_up = std::mem::replace(&mut self.is_pen_up, is_pen_up);
+        if is_pen_up {
+            if is_drawn {
+                self.travel.push((from, to));
+            }
+            return;
+        }
+
+        if was_pen_up {
+            if is_drawn {
+                self.labels.push((from.0, from.1, self.num_strokes));
+            }
+            self.num_strokes += 1;
+            self.to_next_arrow = self.arrow_spacing;
+        }
+
+        let direction = (to.0 - from.0, to.1 - from.1);
+        let length = (direction.0.powi(2) + direction.1.powi(2)).sqrt();
+        if !(self.arrow_spacing.is_finite() && self.arrow_spacing > 0.) || length == 0. {
+            return;
+        }
+
+        let mut along = 0.;
+        while self.to_next_arrow <= length - along {
+            along += self.to_next_arrow;
+            self.to_next_arrow = self.arrow_spacing;
+            if is_drawn {
+                let t = along / length;
+                self.arrows.push((from.0 + direction.0 * t, from.1 + direction.1 * t, direction));
+            }
+        }
+        self.to_next_arrow -= length - along;
+    }
+
+    ///
+    /// Draws the overlay over a finished preview, travel first so the numbers and arrows sit on top.
+    ///
+    /// # Parameters:
+    /// - `preview_canvas`: The canvas to draw on
+    ///
+    fn draw(self, preview_canvas: &mut canvas::PreviewCanvas) {
+        preview_canvas.pen_width = 0.;
+        for ((x1, y1), (x2, y2)) in self.travel {
+            preview_canvas.queue_line(x1, y1, x2, y2, DEBUG_TRAVEL_COLOUR);
+        }
+        preview_canvas.flush_lines();
+
+        let arrow_size = (self.arrow_spacing / 4.).clamp(0.5, 2.);
+        for (x, y, direction) in self.arrows {
+            preview_canvas.arrow(x, y, direction, arrow_size, DEBUG_COLOUR);
+        }
+        for (x, y, stroke_num) in self.labels {
+            preview_canvas.number(x + 0.5, y + 0.5, stroke_num, DEBUG_COLOUR);
+        }
+    }
+}
+
+///
+/// The number of cells along the longest side of the machine, when finding the envelope the pen
+/// can reach for `render_machine_preview`.
+///
+const ENVELOPE_CELLS: f64 = 400.;
+
+///
+/// Renders the layout of the whole machine and saves the file, so users can check their measured
+/// offsets before drawing. See `render_machine_preview` for what is drawn.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left of the page
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `path`: The path to save the preview image to
+///
+/// # Returns:
+/// - Void if the preview generated successfully, and the image was saved
+/// - `PreviewError` to explain why the preview was unable to be generated or saved
+///
+pub fn generate_machine_preview(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, path: &str) -> Result<(), PreviewError> {
+    render_machine_preview(init_xy, physical_dim, &PreviewOptions::default())?.save(path)
+}
+
+///
+/// Renders the layout of the whole machine: the motors, the belts to the pen's start position,
+/// the placement of the page and its keep-out zones, and the envelope the pen can reach, as
+/// `reachable_cells` finds it. Coordinates on the returned canvas are relative to the left motor
+/// shaft, rather than the page.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left of the page
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `options`: The options to render the preview with, only `scale`, `padding` (at least 10mm, so the motors are visible) and `margins` are used
+///
+/// # Returns:
+/// - The canvas, with the machine drawn on it
+/// - `PreviewError::CanvasTooLarge` if the machine is too large to render at the requested scale
+///
+pub fn render_machine_preview(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, options: &PreviewOptions) -> Result<canvas::PreviewCanvas, PreviewError> {
+    let motor_interspace = *physical_dim.motor_interspace();
+    let (page_x, page_y) = (*physical_dim.page_horizontal_offset(), *physical_dim.page_vertical_offset());
+    let (page_width, page_height) = (*physical_dim.page_width(), *physical_dim.page_height());
+
+    // the frame must fit the motors and the page, even when the page overhangs the motors
+    let frame_width = motor_interspace.max(page_x + page_width).ceil();
+    let frame_height = (page_y + page_height).ceil();
+    let left = page_x.min(0.).floor();
+    let mut preview_canvas = canvas::PreviewCanvas::new_with_padding((frame_width - left) as u32, frame_height as u32, Some(options.scale.max(1)), options.padding.max(10))?;
+    let origin_x = -left;
+
+    // reachable envelope: the edges between reachable cells and unreachable ones, or the frame
+    let (cell, reachable) = reachable_cells(physical_dim, left, frame_width - left, frame_height);
+    let envelope_colour = Rgba([60, 170, 90, 255]);
+    let is_reachable = |row: isize, col: isize| row >= 0 && col >= 0 && reachable.get(row as usize).and_then(|cells| cells.get(col as usize)).copied().unwrap_or(false);
+    for (row, cells) in reachable.iter().enumerate() {
+        for (col, _) in cells.iter().enumerate().filter(|(_, reachable)| **reachable) {
+            let (r, c) = (row as isize, col as isize);
+            let (x, y) = (origin_x + left + col as f64 * cell, row as f64 * cell);
+            if !is_reachable(r - 1, c) {
+                preview_canvas.queue_line(x, y, x + cell, y, envelope_colour);
+            }
+            if !is_reachable(r + 1, c) {
+                preview_canvas.queue_line(x, y + cell, x + cell, y + cell, envelope_colour);
+            }
+            if !is_reachable(r, c - 1) {
+                preview_canvas.queue_line(x, y, x, y + cell, envelope_colour);
+            }
+            if !is_reachable(r, c + 1) {
+                preview_canvas.queue_line(x + cell, y, x + cell, y + cell, envelope_colour);
+            }
+        }
+    }
+    preview_canvas.flush_lines();
+
+    // page placement, with its margins
+    preview_canvas.rectangle(origin_x + page_x, page_y, page_width, page_height, Rgba([128, 128, 128, 255]));
+    if let Some((horizontal_margin, vertical_margin)) = options.margins {
+        preview_canvas.rectangle(origin_x + page_x + horizontal_margin, page_y + vertical_margin, page_width - 2. * horizontal_margin, page_height - 2. * vertical_margin, Rgba([200, 200, 200, 255]));
+    }
+
+    for zone in physical_dim.keep_out_zones() {
+        preview_canvas.rectangle(origin_x + page_x + zone.x, page_y + zone.y, zone.width, zone.height, Rgba([220, 40, 40, 255]));
+    }
+
+    // belts from each motor to the start position, then the motors and pen on top
+    let (pen_x, pen_y) = (origin_x + page_x + init_xy.0, page_y + init_xy.1);
+    let belt_colour = Rgba([40, 90, 220, 255]);
+    preview_canvas.coloured_line(origin_x, 0., pen_x, pen_y, belt_colour);
+    preview_canvas.coloured_line(origin_x + motor_interspace, 0., pen_x, pen_y, belt_colour);
+    preview_canvas.circle(origin_x, 0., 5., Rgba([0, 0, 0, 255]));
+    preview_canvas.circle(origin_x + motor_interspace, 0., 5., Rgba([0, 0, 0, 255]));
+    preview_canvas.circle(pen_x, pen_y, 2., Rgba([220, 40, 40, 255]));
+
+    Ok(preview_canvas)
+}
+
+///
+/// Finds where the pen can reach, on a grid of square cells covering the machine. A cell is
+/// reachable if its centre passes `PhysicalDimensions::check_bounds`, so it is outside every
+/// keep-out zone and within the belt angle limit, and the machine's kinematics can place the pen
+/// there. This works for every kinematics, rather than assuming the geometry of one.
+///
+/// # Parameters:
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `left`: The left edge of the grid, relative to the left motor shaft
+/// - `width` and `height`: The size of the grid, from `left` and the motor shafts
+///
+/// # Returns:
+/// - The size of a cell, in millimetres
+/// - Whether each cell is reachable, by row from the top, then by column from the left
+///
+pub(super) fn reachable_cells(physical_dim: &PhysicalDimensions, left: f64, width: f64, height: f64) -> (f64, Vec<Vec<bool>>) {
+    let (page_x, page_y) = (*physical_dim.page_horizontal_offset(), *physical_dim.page_vertical_offset());
+    let kinematics = physical_dim.kinematics_model();
+    let cell = width.max(height) / ENVELOPE_CELLS;
+    let (rows, cols) = ((height / cell).ceil() as usize, (width / cell).ceil() as usize);
+
+    let reachable = (0..rows).map(|row| (0..cols).map(|col| {
+        let (x, y) = (left + (col as f64 + 0.5) * cell, (row as f64 + 0.5) * cell);
+        physical_dim.check_bounds(x - page_x, y - page_y).is_ok() && kinematics.cartesian_to_axes(x, y).is_ok()
+    }).collect()).collect();
+    (cell, reachable)
+}
+
+///
+/// Draws the page border and margin guides on a canvas, if they are enabled.
+///
+/// # Parameters:
+/// - `preview_canvas`: The canvas to draw the guides on
+/// - `physical_dim`: The physical dimensions, containing the page size
+/// - `options`: The preview options, stating which guides to draw
+///
+fn draw_guides(preview_canvas: &mut canvas::PreviewCanvas, physical_dim: &PhysicalDimensions, options: &PreviewOptions) {
+    let page_width = *physical_dim.page_width();
+    let page_height = *physical_dim.page_height();
+
+    if options.draw_border {
+        preview_canvas.rectangle(0., 0., page_width, page_height, Rgba([128, 128, 128, 255]));
+    }
+    if let Some((horizontal_margin, vertical_margin)) = options.margins {
+        preview_canvas.rectangle(horizontal_margin, vertical_margin, page_width - 2. * horizontal_margin, page_height - 2. * vertical_margin, Rgba([200, 200, 200, 255]));
+    }
+}
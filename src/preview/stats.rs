@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use crate::client::calculate_draw_time;
+use crate::hardware::PhysicalDimensions;
+use crate::hardware::motion::AccelerationProfile;
+use crate::instruction::InstructionSet;
+use super::error::PreviewError;
+
+///
+/// A summary of a drawing, to be shown alongside its preview.
+/// All lengths are measured in millimetres.
+///
+/// # Fields:
+/// - `instruction_count`: The number of instructions in the drawing
+/// - `ink_distance`: The distance the pen travels while down
+/// - `travel_distance`: The distance the pen travels while up
+/// - `duration`: The estimated time taken to draw the drawing
+/// - `page_width`: The width of the page
+/// - `page_height`: The height of the page
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewStats {
+    pub instruction_count: usize,
+    pub ink_distance: f64,
+    pub travel_distance: f64,
+    pub duration: Duration,
+    pub page_width: f64,
+    pub page_height: f64,
+}
+
+///
+/// Simulates an instruction set to summarise the drawing.
+///
+/// # Parameters:
+/// - `init_xy`: The initial x and y value of the pen, relative to the top left motor shaft
+/// - `physical_dim`: The physical dimensions of the machine
+/// - `instruction_set`: The instruction set to summarise
+/// - `acceleration_profile`: The motion limits of the motors in motor steps, used to estimate the duration
+///
+/// # Returns:
+/// - The summary of the drawing
+/// - `PreviewError` to explain why the drawing could not be simulated
+///
+pub fn calculate_stats(init_xy: (f64, f64), physical_dim: &PhysicalDimensions, instruction_set: &InstructionSet, acceleration_profile: &AccelerationProfile) -> Result<PreviewStats, PreviewError> {
+    let mut instruction_count = 0;
+    let mut ink_distance = 0.;
+    let mut travel_distance = 0.;
+
+    super::simulate(init_xy, physical_dim, instruction_set, &mut |_, (x1, y1): (f64, f64), (x2, y2): (f64, f64), is_pen_up: bool| {
+        let distance = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+        if is_pen_up {
+            travel_distance += distance;
+        } else {
+            ink_distance += distance;
+        }
+        instruction_count += 1;
+    })?;
+
+    Ok(PreviewStats {
+        instruction_count,
+        ink_distance,
+        travel_distance,
+        duration: calculate_draw_time(instruction_set.get_binary(), acceleration_profile),
+        page_width: *physical_dim.page_width(),
+        page_height: *physical_dim.page_height(),
+    })
+}
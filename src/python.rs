@@ -0,0 +1,236 @@
+//!
+//! The `blotbot_core` Python module, so notebooks and scripts can generate, preview and plot
+//! drawings without the desktop app
+//!
+//! Build and install into the active environment with `maturin develop --release`. Physical
+//! dimensions are read from a TOML machine configuration file, and drawing method parameters
+//! are passed as a dict (or a JSON string) matching the method's parameters.
+//!
+
+#[cfg(feature = "async")]
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+use crate::drawing::registry;
+use crate::hardware::PhysicalDimensions;
+use crate::instruction::InstructionSet;
+use crate::preview;
+#[cfg(feature = "async")]
+use crate::pipeline::DrawingJob;
+
+/// A line between two points on the page, in millimetres.
+type Segment = ((f64, f64), (f64, f64));
+
+///
+/// A generated drawing.
+///
+/// # Fields:
+/// - `instruction_set`: The validated drawing instructions
+///
+#[pyclass(name = "Drawing", module = "blotbot_core", frozen)]
+pub struct PyDrawing {
+    instruction_set: InstructionSet,
+}
+
+#[pymethods]
+impl PyDrawing {
+    ///
+    /// # Returns:
+    /// - The instruction bytes of the drawing
+    ///
+    pub fn bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.instruction_set.get_binary())
+    }
+
+    ///
+    /// # Returns:
+    /// - The initial x and y position of the pen, relative to the top left of the page
+    ///
+    pub fn start(&self) -> (f64, f64) {
+        self.instruction_set.get_init()
+    }
+
+    ///
+    /// Simulates the drawing on the machine, for plotting with matplotlib or similar.
+    ///
+    /// # Parameters:
+    /// - `dimensions_path`: The path of the TOML machine configuration
+    ///
+    /// # Returns:
+    /// - Every line drawn with the pen down, as ((x1, y1), (x2, y2)) in millimetres relative to
+    ///   the top left of the page
+    /// - A ValueError explaining why the drawing could not be simulated
+    ///
+    pub fn segments(&self, dimensions_path: &str) -> PyResult<Vec<Segment>> {
+        let physical_dimensions = read_dimensions(dimensions_path)?;
+        let mut segments: Vec<Segment> = vec![];
+
+        preview::simulate(self.instruction_set.get_init(), &physical_dimensions, &self.instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+            if !is_pen_up {
+                segments.push((from, to));
+            }
+        }).map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+        Ok(segments)
+    }
+
+    ///
+    /// Renders the preview of the drawing.
+    ///
+    /// # Parameters:
+    /// - `dimensions_path`: The path of the TOML machine configuration
+    /// - `scale`: The number of pixels per millimetre of paper
+    ///
+    /// # Returns:
+    /// - The preview, encoded as a PNG, which notebooks can display with `IPython.display.Image`
+    /// - A ValueError explaining why the preview could not be rendered
+    ///
+    #[cfg(feature = "image")]
+    #[pyo3(signature = (dimensions_path, scale = 1))]
+    pub fn preview_png<'py>(&self, py: Python<'py>, dimensions_path: &str, scale: u32) -> PyResult<Bound<'py, PyBytes>> {
+        let physical_dimensions = read_dimensions(dimensions_path)?;
+        let options = preview::PreviewOptions { scale, ..Default::default() };
+
+        let png_bytes = preview::render_preview(self.instruction_set.get_init(), &physical_dimensions, &self.instruction_set, &options)
+            .and_then(|preview_canvas| preview_canvas.to_png_bytes())
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(PyBytes::new(py, &png_bytes))
+    }
+
+    ///
+    /// Renders the preview of the drawing to an image file.
+    ///
+    /// # Parameters:
+    /// - `dimensions_path`: The path of the TOML machine configuration
+    /// - `path`: The path to save the image file to
+    /// - `scale`: The number of pixels per millimetre of paper
+    ///
+    /// # Returns:
+    /// - None if the preview was saved
+    /// - A ValueError explaining why the preview could not be rendered or saved
+    ///
+    #[cfg(feature = "image")]
+    #[pyo3(signature = (dimensions_path, path, scale = 1))]
+    pub fn save_preview(&self, dimensions_path: &str, path: &str, scale: u32) -> PyResult<()> {
+        let physical_dimensions = read_dimensions(dimensions_path)?;
+        let options = preview::PreviewOptions { scale, ..Default::default() };
+
+        preview::render_preview(self.instruction_set.get_init(), &physical_dimensions, &self.instruction_set, &options)
+            .and_then(|preview_canvas| preview_canvas.save(path))
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    ///
+    /// Draws the drawing on the machine, blocking until it has finished. The pen is moved to the
+    /// start of the drawing first, and the GIL is released while drawing.
+    ///
+    /// # Parameters:
+    /// - `dimensions_path`: The path of the TOML machine configuration
+    /// - `addr`: The IP address of the machine
+    /// - `port`: The port address of the machine
+    /// - `on_event`: An optional callable receiving the progress of the drawing, as a dict with
+    ///   an "event" key
+    ///
+    /// # Returns:
+    /// - None if the drawing finished
+    /// - A RuntimeError explaining why the drawing failed
+    ///
+    #[cfg(feature = "async")]
+    #[pyo3(signature = (dimensions_path, addr, port, on_event = None))]
+    pub fn draw(&self, py: Python<'_>, dimensions_path: &str, addr: &str, port: u16, on_event: Option<PyObject>) -> PyResult<()> {
+        let physical_dimensions = read_dimensions(dimensions_path)?;
+        let (start_x, start_y) = self.instruction_set.get_init();
+        let job = DrawingJob::new(physical_dimensions).with_instructions(self.instruction_set.get_binary().clone(), start_x, start_y).with_machine(addr, port);
+
+        let runtime = tokio::runtime::Runtime::new().map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        py.allow_threads(|| {
+            runtime.block_on(job.run(|event| {
+                let (Some(on_event), Ok(event_json)) = (&on_event, serde_json::to_string(&event)) else { return };
+                Python::with_gil(|py| {
+                    // an exception in the callback shouldn't abandon the machine mid-drawing
+                    let result = py.import("json").and_then(|json| json.call_method1("loads", (event_json,))).and_then(|event| on_event.call1(py, (event,)));
+                    if let Err(err) = result {
+                        err.print(py);
+                    }
+                });
+            }))
+        }).map(|_| ()).map_err(|err| PyRuntimeError::new_err(err.to_string()))
+    }
+}
+
+///
+/// # Returns:
+/// - The drawing methods in this build, as (backend ID, display name) pairs
+///
+#[pyfunction]
+pub fn methods() -> Vec<(&'static str, &'static str)> {
+    registry::methods()
+}
+
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+///
+/// # Returns:
+/// - The default parameters of the drawing method, as a dict
+/// - A ValueError if there is no such drawing method
+///
+#[pyfunction]
+pub fn default_parameters(py: Python<'_>, method_id: &str) -> PyResult<PyObject> {
+    let parameters_json = registry::default_parameters(method_id).map_err(PyValueError::new_err)?;
+    Ok(py.import("json")?.call_method1("loads", (parameters_json,))?.unbind())
+}
+
+///
+/// Generates a drawing with a built-in drawing method.
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+/// - `parameters`: The parameters of the drawing method, as a dict or a JSON string
+/// - `dimensions_path`: The path of the TOML machine configuration
+///
+/// # Returns:
+/// - The drawing
+/// - A ValueError explaining why the drawing could not be generated
+///
+#[pyfunction]
+pub fn generate(py: Python<'_>, method_id: &str, parameters: &Bound<'_, PyAny>, dimensions_path: &str) -> PyResult<PyDrawing> {
+    let parameters_json: String = match parameters.downcast::<PyString>() {
+        Ok(json) => json.to_str()?.to_owned(),
+        Err(_) => py.import("json")?.call_method1("dumps", (parameters,))?.extract()?,
+    };
+    let physical_dimensions = read_dimensions(dimensions_path)?;
+
+    let (ins_bytes, start_x, start_y) = py.allow_threads(|| registry::gen_instructions(method_id, &physical_dimensions, &parameters_json)).map_err(PyValueError::new_err)?;
+    let instruction_set = InstructionSet::new(ins_bytes, start_x, start_y).map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+    Ok(PyDrawing { instruction_set })
+}
+
+///
+/// Registers the functions and classes of the `blotbot_core` module.
+///
+#[pymodule]
+pub fn blotbot_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyDrawing>()?;
+    m.add_function(wrap_pyfunction!(methods, m)?)?;
+    m.add_function(wrap_pyfunction!(default_parameters, m)?)?;
+    m.add_function(wrap_pyfunction!(generate, m)?)?;
+    Ok(())
+}
+
+///
+/// Loads the physical dimensions passed from Python.
+///
+/// # Parameters:
+/// - `dimensions_path`: The path of the TOML machine configuration
+///
+/// # Returns:
+/// - The physical dimensions
+/// - A ValueError if the configuration could not be read, parsed or validated
+///
+fn read_dimensions(dimensions_path: &str) -> PyResult<PhysicalDimensions> {
+    PhysicalDimensions::load_from_toml(dimensions_path).map_err(|err| PyValueError::new_err(err.to_string()))
+}
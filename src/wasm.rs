@@ -0,0 +1,185 @@
+//!
+//! Browser bindings, so drawing methods can be generated and previewed entirely client-side
+//!
+//! Build with `wasm-pack build --target web -- --no-default-features --features wasm,image,audio`.
+//! There is no filesystem in the browser, so images and audio are passed in with `add_file`,
+//! and drawing method parameters refer to them by the name they were added with. Physical
+//! dimensions are passed as the contents of a TOML machine configuration.
+//!
+
+use wasm_bindgen::prelude::*;
+
+use crate::drawing::registry;
+use crate::drawing::util::files;
+use crate::hardware::PhysicalDimensions;
+use crate::instruction::InstructionSet;
+use crate::preview;
+
+///
+/// Adds a file for drawing methods to read, replacing any file with the same name.
+///
+/// # Parameters:
+/// - `name`: The name drawing method parameters refer to the file by, in place of a path
+/// - `bytes`: The contents of the file
+///
+#[wasm_bindgen(js_name = addFile)]
+pub fn add_file(name: &str, bytes: Vec<u8>) {
+    files::insert(name, bytes);
+}
+
+///
+/// Removes a file added with `add_file`.
+///
+/// # Parameters:
+/// - `name`: The name of the file
+///
+/// # Returns:
+/// - Whether there was a file with the name
+///
+#[wasm_bindgen(js_name = removeFile)]
+pub fn remove_file(name: &str) -> bool {
+    files::remove(name)
+}
+
+///
+/// # Returns:
+/// - The drawing methods in this build, as a JSON array of [backend ID, display name] pairs
+///
+#[wasm_bindgen(js_name = methodsJson)]
+pub fn methods_json() -> String {
+    serde_json::to_string(&registry::methods()).unwrap_or_default()
+}
+
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+///
+/// # Returns:
+/// - The default parameters of the drawing method, as a JSON object
+/// - An error if there is no such drawing method
+///
+#[wasm_bindgen(js_name = defaultParametersJson)]
+pub fn default_parameters_json(method_id: &str) -> Result<String, JsError> {
+    registry::default_parameters(method_id).map_err(|err| JsError::new(&err))
+}
+
+///
+/// A generated drawing.
+///
+/// # Fields:
+/// - `instruction_set`: The validated drawing instructions
+///
+#[wasm_bindgen]
+pub struct Drawing {
+    instruction_set: InstructionSet,
+}
+
+///
+/// Generates a drawing with a built-in drawing method.
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+/// - `parameters_json`: The parameters of the drawing method, as a JSON object
+/// - `dimensions_toml`: The machine configuration, as TOML
+///
+/// # Returns:
+/// - The drawing
+/// - An error explaining why the drawing could not be generated
+///
+#[wasm_bindgen]
+pub fn generate(method_id: &str, parameters_json: &str, dimensions_toml: &str) -> Result<Drawing, JsError> {
+    let physical_dimensions = read_dimensions(dimensions_toml)?;
+    let (ins_bytes, start_x, start_y) = registry::gen_instructions(method_id, &physical_dimensions, parameters_json).map_err(|err| JsError::new(&err))?;
+    let instruction_set = InstructionSet::new(ins_bytes, start_x, start_y).map_err(|err| JsError::new(&err.to_string()))?;
+
+    Ok(Drawing { instruction_set })
+}
+
+#[wasm_bindgen]
+impl Drawing {
+    ///
+    /// # Returns:
+    /// - The instruction bytes of the drawing
+    ///
+    pub fn bytes(&self) -> Vec<u8> {
+        self.instruction_set.get_binary().clone()
+    }
+
+    ///
+    /// # Returns:
+    /// - The initial x position of the pen, relative to the top left of the page
+    ///
+    #[wasm_bindgen(js_name = startX)]
+    pub fn start_x(&self) -> f64 {
+        self.instruction_set.get_init().0
+    }
+
+    ///
+    /// # Returns:
+    /// - The initial y position of the pen, relative to the top left of the page
+    ///
+    #[wasm_bindgen(js_name = startY)]
+    pub fn start_y(&self) -> f64 {
+        self.instruction_set.get_init().1
+    }
+
+    ///
+    /// Simulates the drawing on the machine, for previews drawn by the page itself.
+    ///
+    /// # Parameters:
+    /// - `dimensions_toml`: The machine configuration, as TOML
+    ///
+    /// # Returns:
+    /// - Every line drawn with the pen down, flattened to x1, y1, x2, y2 in millimetres relative
+    ///   to the top left of the page
+    /// - An error explaining why the drawing could not be simulated
+    ///
+    pub fn segments(&self, dimensions_toml: &str) -> Result<Vec<f64>, JsError> {
+        let physical_dimensions = read_dimensions(dimensions_toml)?;
+        let mut segments: Vec<f64> = vec![];
+
+        preview::simulate(self.instruction_set.get_init(), &physical_dimensions, &self.instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+            if !is_pen_up {
+                segments.extend_from_slice(&[from.0, from.1, to.0, to.1]);
+            }
+        }).map_err(|err| JsError::new(&err.to_string()))?;
+
+        Ok(segments)
+    }
+
+    ///
+    /// Renders the preview of the drawing.
+    ///
+    /// # Parameters:
+    /// - `dimensions_toml`: The machine configuration, as TOML
+    /// - `scale`: The number of pixels per millimetre of paper
+    ///
+    /// # Returns:
+    /// - The preview, encoded as a PNG
+    /// - An error explaining why the preview could not be rendered
+    ///
+    #[cfg(feature = "image")]
+    #[wasm_bindgen(js_name = previewPng)]
+    pub fn preview_png(&self, dimensions_toml: &str, scale: u32) -> Result<Vec<u8>, JsError> {
+        let physical_dimensions = read_dimensions(dimensions_toml)?;
+        let options = preview::PreviewOptions { scale, ..Default::default() };
+
+        preview::render_preview(self.instruction_set.get_init(), &physical_dimensions, &self.instruction_set, &options)
+            .and_then(|preview_canvas| preview_canvas.to_png_bytes())
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+}
+
+///
+/// Parses the physical dimensions passed from the page.
+///
+/// # Parameters:
+/// - `dimensions_toml`: The TOML machine configuration
+///
+/// # Returns:
+/// - The physical dimensions
+/// - An error if the configuration could not be parsed or validated
+///
+fn read_dimensions(dimensions_toml: &str) -> Result<PhysicalDimensions, JsError> {
+    PhysicalDimensions::from_toml_str(dimensions_toml).map_err(|err| JsError::new(&err.to_string()))
+}
@@ -0,0 +1,343 @@
+//!
+//! Golden regression tests for the drawing methods
+//!
+//! Every registered drawing method is run with fixed parameters and seeds, the drawing is
+//! simulated on the belts, and the result is compared with the golden data checked in to
+//! `tests/golden`. Drawings are snapped to `stroke::REPRODUCIBLE_GRID_MM` before compiling, as
+//! `DrawingJob::with_reproducibility` does, so the hashes match on every platform. A refactor to `DrawSurface` or a utility which changes any artwork fails here.
+//!
+//! When a change to the artwork is intended, regenerate the golden data and review the diff:
+//! ```
+//! UPDATE_GOLDEN=1 cargo test --test golden
+//! ```
+//!
+
+use std::path::PathBuf;
+
+use bbcore::drawing::{registry, stroke};
+use bbcore::hardware::PhysicalDimensions;
+use bbcore::instruction::InstructionSet;
+use bbcore::preview;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+///
+/// The artwork a drawing method produced, reduced to what a regression would change.
+///
+/// # Fields:
+/// - `hash`: The FNV-1a hash of the instruction bytes, as hex
+/// - `bytes`: The number of instruction bytes
+/// - `start`: The initial pen position
+/// - `strokes`: The number of separate pen down strokes
+/// - `pen_down_mm`: The distance drawn with the pen down, in millimetres
+/// - `bounds`: The (min_x, min_y, max_x, max_y) of every pen down line, in millimetres
+///
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Golden {
+    hash: String,
+    bytes: usize,
+    start: (f64, f64),
+    strokes: usize,
+    pen_down_mm: f64,
+    bounds: (f64, f64, f64, f64),
+}
+
+fn physical_dimensions() -> PhysicalDimensions {
+    PhysicalDimensions::new(600., 195., 150., 210., 297.)
+}
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+///
+/// # Returns:
+/// - The fixed parameters every drawing method is tested with, by backend ID
+///
+fn cases() -> Vec<(&'static str, Value)> {
+    bbcore::drawing::util::files::insert("golden.geojson", fixture_map());
+    bbcore::drawing::util::files::insert("golden-strokes.json", fixture_recording());
+    bbcore::drawing::util::files::insert("golden-font.svg", fixture_font());
+
+    #[allow(unused_mut)]
+    let mut cases = vec![
+        ("lines", json!({ "num_lines": 12, "horizontal_margin": 20 })),
+        ("cascade", json!({ "horizontal_margin": 20., "vertical_margin": 20., "boxes_vertical": 30, "boxes_horizontal": 10, "seed": 7 })),
+        ("islands", json!({
+            "seed": 7, "layers": 40, "sample_per_mm": 1, "width": 150, "height": 150, "vertical_offset": 0, "ocean_height": 60,
+            "base_size": 120., "base_amplitude": 1., "mid_size": 40., "mid_amplitude": 0.4, "high_size": 10., "high_amplitude": 0.1,
+        })),
+        ("dunes", json!({
+            "seed": 7, "layers": 40, "sample_per_mm": 1, "width": 150, "height": 150, "vertical_offset": 0,
+            "base_size": 120., "base_amplitude": 1., "mid_size": 40., "mid_amplitude": 0.4, "high_size": 10., "high_amplitude": 0.1,
+        })),
+        ("entropy", json!({
+            "start_radius": 10., "cycle_distance": 3., "cycle_density": 60, "total_steps": 1500, "swirl_factor": 1., "swirl_decay": 0.5,
+            "horizontal_offset": 0., "vertical_offset": 0., "seed": 7,
+            "base_size": 80., "base_strength": 4., "mid_size": 20., "mid_strength": 1., "high_size": 5., "high_strength": 0.2,
+        })),
+        ("shades", json!({ "placement": { "margins": { "top": 73.5, "right": 30., "bottom": 73.5, "left": 30. } }, "num_lines": 30, "power": 2 })),
+        ("atom", json!({ "seed": 7, "num_shells": 4, "min_shell_width": 10., "max_shell_width": 20., "nucleus_size": 15., "nucleus_scramble": 2., "nucleus_circles": 6 })),
+        ("calibration", json!({ "columns": 3, "rows": 4, "margin": 15., "cross_size": 6. })),
+        ("superformula", json!({ "shape": { "Superformula": { "m": 5., "n1": 0.3, "n2": 0.3, "n3": 0.3 } }, "scale": 80., "layers": 6, "rotation_per_layer": 10., "samples_per_layer": 180 })),
+        ("fractal", json!({ "curve": "Dragon", "depth": 10, "placement": { "margins": { "top": 20., "right": 20., "bottom": 20., "left": 20. } } })),
+        ("pattern", json!({
+            "pattern": "IsometricCubes", "placement": { "margins": { "top": 30., "right": 30., "bottom": 30., "left": 30. } },
+            "cell_size": 15., "line_spacing": 2., "jitter": 0.5, "rotation": 15., "seed": 7,
+        })),
+        ("stars", json!({
+            "seed": 7, "num_stars": 300, "placement": { "margins": { "top": 20., "right": 20., "bottom": 20., "left": 20. } },
+            "star_size": 6., "bright_fraction": 0.1, "constellation_stars": 12,
+        })),
+        ("map", json!({
+            "geojson_path": "golden.geojson", "bounding_box": [-0.01, 51.495, 0.01, 51.505],
+            "placement": { "margins": { "top": 20., "right": 20., "bottom": 20., "left": 20. } },
+            "road_passes": 3, "pen_width": 0.5, "water_spacing": 2.,
+        })),
+        ("masked", json!({
+            "method_id": "shades", "method_parameters_json": json!({ "num_lines": 40, "power": 10 }).to_string(),
+            "mask": { "Polygon": { "rings": [[[0., 0.], [100., 20.], [80., 100.], [10., 70.]], [[40., 40.], [60., 45.], [45., 60.]]] } },
+            "placement": { "margins": { "top": 30., "right": 30., "bottom": 30., "left": 30. } },
+        })),
+        ("registration", json!({
+            "placement": { "margins": { "top": 15., "right": 15., "bottom": 15., "left": 15. } },
+            "mark_size": 10., "centre_cross": true, "tick_spacing": 20., "crosses": [[50., 50.], [160., 247.]],
+        })),
+        ("handwriting", json!({ "recording_path": "golden-strokes.json", "placement": { "margins": { "top": 20., "right": 20., "bottom": 20., "left": 20. } }, "sample_spacing": 1. })),
+        ("text", json!({ "font_path": "golden-font.svg", "text": "LTV\nVT", "line_spacing": 1.2, "placement": { "h_align": "End", "margins": { "top": 20., "right": 20., "bottom": 20., "left": 20. } } })),
+    ];
+
+    #[cfg(feature = "image")]
+    {
+        bbcore::drawing::util::files::insert("golden.png", fixture_image());
+        let stipple = json!({
+            "image_path": "golden.png", "width": 150., "height": 150., "horizontal_offset": 30., "vertical_offset": 30.,
+            "placement": { "h_align": "Start", "v_align": "Start", "margins": { "top": 30., "right": 30., "bottom": 117., "left": 30. } },
+            "brightness_threshold": 200, "num_stipples": 150, "num_iterations": 5, "relaxation_tendency": 50, "seed": 7,
+        });
+        let mut scribble = stipple.clone();
+        scribble["scribble_size"] = json!(50);
+
+        cases.extend([
+            ("scribble", scribble),
+            ("bubbles", stipple),
+            ("waves", json!({ "image_path": "golden.png", "num_waves": 20, "horizontal_samples": 80, "placement": { "margins": { "top": 20., "right": 20., "bottom": 20., "left": 20. } }, "wave_amplifier": 10. })),
+        ]);
+    }
+
+    #[cfg(feature = "audio")]
+    {
+        bbcore::drawing::util::files::insert("golden.wav", fixture_audio());
+        cases.extend([
+            ("vinyl", json!({ "audio_path": "golden.wav", "placement": { "margins": { "top": 118.5, "right": 30., "bottom": 118.5, "left": 30. } }, "num_samples": 100 })),
+            ("record", json!({ "audio_path": "golden.wav", "inner_radius": 20., "outer_radius": 70., "rotations": 8., "amplitude_scale": 2., "samples_per_rotation": 60 })),
+        ]);
+    }
+
+    #[cfg(feature = "plugin")]
+    cases.push(("custom", json!({ "plugin_path": golden_dir().join("square.py"), "plugin_parameters_json": r#"{"turns": 6}"# })));
+
+    cases
+}
+
+///
+/// # Returns:
+/// - A GeoJSON street map around (0, 51.5), with a crossroads, a footpath, a lake with an
+///   island, a building, and a road running out of the bounding box
+///
+fn fixture_map() -> Vec<u8> {
+    let feature = |properties: Value, geometry_type: &str, coordinates: Value| json!({
+        "type": "Feature", "properties": properties, "geometry": { "type": geometry_type, "coordinates": coordinates },
+    });
+    let map = json!({
+        "type": "FeatureCollection",
+        "features": [
+            feature(json!({ "highway": "primary" }), "LineString", json!([[-0.012, 51.5], [0., 51.5], [0.012, 51.5]])),
+            feature(json!({ "highway": "residential" }), "LineString", json!([[0., 51.494], [0., 51.5], [0.002, 51.503], [0.002, 51.506]])),
+            feature(json!({ "highway": "footway" }), "MultiLineString", json!([[[-0.008, 51.496], [-0.004, 51.498], [-0.006, 51.499]]])),
+            feature(json!({ "natural": "water" }), "Polygon", json!([
+                [[0.003, 51.496], [0.008, 51.496], [0.008, 51.499], [0.003, 51.499], [0.003, 51.496]],
+                [[0.005, 51.497], [0.006, 51.497], [0.006, 51.498], [0.005, 51.498], [0.005, 51.497]],
+            ])),
+            feature(json!({ "building": "yes" }), "Polygon", json!([[[-0.006, 51.502], [-0.003, 51.502], [-0.003, 51.504], [-0.006, 51.504], [-0.006, 51.502]]])),
+            feature(json!({ "amenity": "bench" }), "Point", json!([-0.001, 51.501])),
+        ],
+    });
+    map.to_string().into_bytes()
+}
+
+///
+/// # Returns:
+/// - A recording of a signature, as exported from a tablet app: a looping stroke sampled at a
+///   fixed rate, an underline, and a dot recorded before the underline but listed after it
+///
+fn fixture_recording() -> Vec<u8> {
+    let signature: Vec<Value> = (0..120).map(|i| {
+        let t = i as f64 / 10.;
+        json!({ "x": 40. * t + 30. * t.cos(), "y": 200. + 60. * t.sin(), "t": i * 16 })
+    }).collect();
+    let recording = json!({
+        "strokes": [
+            signature,
+            [{ "x": 0., "y": 300., "t": 2000 }, { "x": 250., "y": 310., "t": 2100 }, { "x": 500., "y": 300., "t": 2200 }],
+            [{ "x": 520., "y": 150., "t": 1950 }],
+        ],
+    });
+    recording.to_string().into_bytes()
+}
+
+///
+/// # Returns:
+/// - An SVG font with the letters L, T and V, a curved V, and kerning between L and T
+///
+fn fixture_font() -> Vec<u8> {
+    r#"<svg><font horiz-adv-x="600"><font-face units-per-em="1000" cap-height="700"/>
+        <glyph unicode="L" d="M 100 700 L 100 0 L 500 0"/>
+        <glyph unicode="T" d="M 0 700 H 600 M 300 700 V 0"/>
+        <glyph unicode="V" horiz-adv-x="700" d="M 0 700 Q 350 -200 700 700"/>
+        <hkern u1="L" u2="T" k="150"/>
+    </font></svg>"#.as_bytes().to_vec()
+}
+
+///
+/// # Returns:
+/// - A 64x64 PNG, a vertical gradient with a dark disc, so stippling has dense and sparse areas
+///
+#[cfg(feature = "image")]
+fn fixture_image() -> Vec<u8> {
+    let image = image::RgbImage::from_fn(64, 64, |x, y| {
+        let in_disc = (x as f64 - 40.).hypot(y as f64 - 24.) < 14.;
+        let value = if in_disc { 20 } else { 255 - (y * 3) as u8 };
+        image::Rgb([value, value, value])
+    });
+
+    let mut bytes = vec![];
+    image.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png).unwrap();
+    bytes
+}
+
+///
+/// # Returns:
+/// - Half a second of 8kHz mono 16-bit WAV, a tone which swells and fades
+///
+#[cfg(feature = "audio")]
+fn fixture_audio() -> Vec<u8> {
+    let samples: Vec<i16> = (0..4000)
+        .map(|i| {
+            let t = i as f64 / 8000.;
+            let envelope = (std::f64::consts::PI * t / 0.5).sin();
+            (envelope * (2. * std::f64::consts::PI * 440. * t).sin() * 20000.) as i16
+        })
+        .collect();
+
+    let data_length = samples.len() as u32 * 2;
+    let mut bytes = vec![];
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_length).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // pcm
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&8000u32.to_le_bytes());
+    bytes.extend_from_slice(&16000u32.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_length.to_le_bytes());
+    samples.iter().for_each(|sample| bytes.extend_from_slice(&sample.to_le_bytes()));
+    bytes
+}
+
+///
+/// Generates and simulates a drawing, reducing it to its golden data.
+///
+/// # Parameters:
+/// - `method_id`: The backend ID of the drawing method
+/// - `parameters`: The parameters of the drawing method
+///
+/// # Returns:
+/// - The golden data of the drawing
+/// - An error explaining why the drawing could not be generated or simulated
+///
+fn run_method(method_id: &str, parameters: &Value) -> Result<Golden, String> {
+    let physical_dimensions = physical_dimensions();
+    let strokes = stroke::quantise(registry::gen_strokes(method_id, &physical_dimensions, &parameters.to_string())?, stroke::REPRODUCIBLE_GRID_MM);
+    let (ins_bytes, start_x, start_y) = stroke::compile(&strokes, &physical_dimensions)?;
+    let instruction_set = InstructionSet::new(ins_bytes, start_x, start_y).map_err(|err| err.to_string())?;
+
+    let mut strokes = 0;
+    let mut pen_down_mm = 0.;
+    let mut bounds = (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+    let mut was_pen_up = true;
+    preview::simulate(instruction_set.get_init(), &physical_dimensions, &instruction_set, &mut |_, from: (f64, f64), to: (f64, f64), is_pen_up: bool| {
+        if !is_pen_up {
+            strokes += was_pen_up as usize;
+            pen_down_mm += (to.0 - from.0).hypot(to.1 - from.1);
+            bounds = (bounds.0.min(from.0).min(to.0), bounds.1.min(from.1).min(to.1), bounds.2.max(from.0).max(to.0), bounds.3.max(from.1).max(to.1));
+        }
+        was_pen_up = is_pen_up;
+    }).map_err(|err| err.to_string())?;
+
+    // rounded, so the golden data doesn't churn on the last bits of a float
+    let round = |value: f64| (value * 100.).round() / 100.;
+    Ok(Golden {
+        hash: format!("{:016x}", fnv1a(instruction_set.get_binary())),
+        bytes: instruction_set.get_binary().len(),
+        start: (round(start_x), round(start_y)),
+        strokes,
+        pen_down_mm: round(pen_down_mm),
+        bounds: (round(bounds.0), round(bounds.1), round(bounds.2), round(bounds.3)),
+    })
+}
+
+///
+/// # Returns:
+/// - The 64-bit FNV-1a hash of the bytes
+///
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[test]
+fn every_method_has_a_case() {
+    let cases = cases();
+    for (method_id, _) in registry::methods() {
+        assert!(cases.iter().any(|(id, _)| *id == method_id), "The drawing method \"{}\" has no golden test case", method_id);
+    }
+}
+
+#[test]
+fn methods_match_golden_data() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = vec![];
+
+    for (method_id, parameters) in cases() {
+        let golden = match run_method(method_id, &parameters) {
+            Ok(val) => val,
+            Err(err) => {
+                failures.push(format!("{}: failed to generate. {}", method_id, err));
+                continue;
+            },
+        };
+
+        let path = golden_dir().join(format!("{}.json", method_id));
+        if update {
+            std::fs::write(&path, serde_json::to_string_pretty(&golden).unwrap() + "\n").unwrap();
+            continue;
+        }
+
+        let expected: Golden = match std::fs::read_to_string(&path).map(|contents| serde_json::from_str(&contents)) {
+            Ok(Ok(val)) => val,
+            _ => {
+                failures.push(format!("{}: no golden data at {}, run with UPDATE_GOLDEN=1", method_id, path.display()));
+                continue;
+            },
+        };
+
+        if golden != expected {
+            failures.push(format!("{}: the drawing changed\n  expected {:?}\n  found    {:?}", method_id, expected, golden));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,18 @@
+# A fixed plugin for the golden drawing tests: a square spiral inset from the page edges.
+
+def params():
+    return '{"turns": {"type": "int", "default": 6}}'
+
+def run(surface, params, width, height):
+    turns = params["turns"]
+    step = min(width, height) / (4 * turns)
+
+    surface.goto(step, step)
+    surface.raise_pen(False)
+    for turn in range(turns):
+        inset = step * (turn + 1)
+        surface.goto(width - inset, inset)
+        surface.goto(width - inset, height - inset)
+        surface.goto(inset, height - inset)
+        surface.goto(inset, inset + step)
+    surface.raise_pen(True)